@@ -49,7 +49,10 @@ else()
 	set(Service_Library_Name sgx_tservice)
 endif()
 
-set(SGX_ENCLAVE_FEATURES -Z package-features --features mesalock_sgx)
+set(SGX_ENCLAVE_FEATURE_LIST mesalock_sgx)
+if (METRICS)
+    list(APPEND SGX_ENCLAVE_FEATURE_LIST metrics)
+endif()
 string(TOLOWER "${CMAKE_BUILD_TYPE}" CMAKE_BUILD_TYPE_LOWER)
 if (CMAKE_BUILD_TYPE_LOWER STREQUAL "debug")
     set(TARGET debug)
@@ -57,7 +60,7 @@ if (CMAKE_BUILD_TYPE_LOWER STREQUAL "debug")
 
     if (COV)
         check_exe_dependencies(lcov llvm-cov)
-        set(SGX_ENCLAVE_FEATURES -Z package-features --features "mesalock_sgx cov")
+        list(APPEND SGX_ENCLAVE_FEATURE_LIST cov)
         set(CARGO_INCREMENTAL 0)
         set(RUSTFLAGS "${RUSTFLAGS} -D warnings -Zprofile -Ccodegen-units=1 \
 -Cllvm_args=-inline-threshold=0 -Coverflow-checks=off -Zno-landing-pads")
@@ -66,6 +69,8 @@ else()
     set(TARGET release)
     set(CARGO_BUILD_FLAGS --release)
 endif()
+string(REPLACE ";" " " SGX_ENCLAVE_FEATURE_LIST "${SGX_ENCLAVE_FEATURE_LIST}")
+set(SGX_ENCLAVE_FEATURES -Z package-features --features "${SGX_ENCLAVE_FEATURE_LIST}")
 
 if (OFFLINE)
   set(EXTRA_CARGO_FLAGS "--offline")
@@ -78,6 +83,32 @@ execute_process (
 string(STRIP ${RUSTUP_TOOLCHAIN} RUSTUP_TOOLCHAIN)
 set(RUSTUP_TOOLCHAIN ${RUSTUP_TOOLCHAIN})
 
+# source revision and SDK version embedded in BUILD_CONFIG and enclave info
+execute_process (
+    COMMAND git describe --always --dirty --abbrev=12
+    WORKING_DIRECTORY ${PROJECT_SOURCE_DIR}
+    OUTPUT_VARIABLE MESATEE_BUILD_ID
+    OUTPUT_STRIP_TRAILING_WHITESPACE
+    ERROR_QUIET
+    )
+execute_process (
+    COMMAND git describe --tags --always
+    WORKING_DIRECTORY ${PROJECT_SOURCE_DIR}/third_party/rust-sgx-sdk
+    OUTPUT_VARIABLE SGX_SDK_VERSION
+    OUTPUT_STRIP_TRAILING_WHITESPACE
+    ERROR_QUIET
+    )
+# the version of teaclave_config, which carries it in BUILD_CONFIG
+file(STRINGS ${PROJECT_SOURCE_DIR}/teaclave_config/Cargo.toml MESATEE_CRATE_VERSION
+    REGEX "^version *= *\"[^\"]*\"" LIMIT_COUNT 1)
+string(REGEX REPLACE "^version *= *\"([^\"]*)\"" "\\1" MESATEE_CRATE_VERSION "${MESATEE_CRATE_VERSION}")
+if(NOT MESATEE_BUILD_ID)
+    set(MESATEE_BUILD_ID "unknown")
+endif()
+if(NOT SGX_SDK_VERSION)
+    set(SGX_SDK_VERSION "unknown")
+endif()
+
 set(UNIXAPP_PREFIX "unixapp")
 set(UNIXLIB_PREFIX "unixlib")
 set(SGXAPP_PREFIX "sgxapp")
@@ -107,6 +138,8 @@ set(MESATEE_COMMON_ENVS
     ENCLAVE_OUT_DIR=${ENCLAVE_OUT_DIR}
     RUSTUP_TOOLCHAIN=${RUSTUP_TOOLCHAIN}
     RUST_SGX_SDK=${RUST_SGX_SDK}
+    MESATEE_BUILD_ID=${MESATEE_BUILD_ID}
+    SGX_SDK_VERSION=${SGX_SDK_VERSION}
     MT_SCRIPT_DIR=${MT_SCRIPT_DIR}
     CARGO_INCREMENTAL=${CARGO_INCREMENTAL}
     CMAKE_C_COMPILER=${CMAKE_C_COMPILER}
@@ -163,7 +163,8 @@ function(add_sgx_build_target sgx_lib_path pkg_name)
         COMMAND ${CMAKE_COMMAND} -E env ${TARGET_SGXLIB_ENVS} SGX_COMMON_CFLAGS=${STR_SGX_COMMON_CFLAGS}
             CUR_PKG_NAME=${pkg_name} CUR_PKG_PATH=${sgx_lib_path} CUR_INSTALL_DIR=${_copy_dir} ${MT_SCRIPT_DIR}/sgx_link_sign.sh
         ${_depends}
-        COMMAND cat ${MESATEE_OUT_DIR}/${pkg_name}.meta.txt | python ${MT_SCRIPT_DIR}/gen_enclave_info_toml.py ${pkg_name_no_enclave} > ${_enclave_info}
+        COMMAND cat ${MESATEE_OUT_DIR}/${pkg_name}.meta.txt | python ${MT_SCRIPT_DIR}/gen_enclave_info_toml.py ${pkg_name_no_enclave}
+            ${MESATEE_CRATE_VERSION} ${MESATEE_BUILD_ID} ${SGX_SDK_VERSION} > ${_enclave_info}
         COMMENT "Building ${_target_name}, enclave info to ${_enclave_info}"
         WORKING_DIRECTORY ${MT_SGXLIB_TOML_DIR}
     )
@@ -18,3 +18,10 @@ sys.stdout.write("""[{}]
 mr_enclave = "{}"
 mr_signer  = "{}"
 """.format(sys.argv[1], mr_enclave_hex, mr_signer_hex))
+
+# optional: crate version, build id and SGX SDK version, as in BUILD_CONFIG
+if len(sys.argv) > 4:
+    sys.stdout.write("""crate_version   = "{}"
+build_id        = "{}"
+sgx_sdk_version = "{}"
+""".format(sys.argv[2], sys.argv[3], sys.argv[4]))
@@ -20,3 +20,18 @@ cp -RT ${MESATEE_AUDITORS_DIR}/ ${MESATEE_EXAMPLE_AUDITORS_DIR}/
 cp -r ${MESATEE_AUDITORS_DIR} ${MESATEE_TEST_INSTALL_DIR}/
 cp ${MESATEE_SERVICE_INSTALL_DIR}/enclave_info.toml ${MESATEE_EXAMPLE_INSTALL_DIR}/
 cp ${MESATEE_SERVICE_INSTALL_DIR}/enclave_info.toml ${MESATEE_TEST_INSTALL_DIR}/
+
+# Enclave info of a service the cluster does not run, which the functional
+# tests reload to check that services added to the cluster are accepted.
+cat > ${MESATEE_TEST_INSTALL_DIR}/test_service_enclave_info.toml <<EOF
+[test_service]
+mr_signer = "83d719e77deaca1470f6baf62a4d774303c899db69020f9c70ee1dfc08c7ce9e"
+mr_enclave = "0000000000000000000000000000000000000000000000000000000000000002"
+EOF
+for auditor_path in ${AUDITOR_PATHS}; do
+auditor=$(basename ${auditor_path})
+openssl dgst -sha256 \
+        -sign ${MESATEE_AUDITORS_DIR}/${auditor}/${auditor}.private.pem \
+        -out ${MESATEE_TEST_INSTALL_DIR}/auditors/${auditor}/test_service.sign.sha256 \
+        ${MESATEE_TEST_INSTALL_DIR}/test_service_enclave_info.toml;
+done
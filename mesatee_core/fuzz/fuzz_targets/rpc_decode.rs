@@ -0,0 +1,74 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// Decodes arbitrary bytes as a frame carrying a request, as an RPC server
+// does. Decoding may fail, but must neither panic nor allocate beyond the
+// frame limit. Run with `cargo fuzz run rpc_decode` in this directory.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mesatee_core::rpc::codec;
+use serde_derive::Deserialize;
+use std::collections::HashMap;
+use std::io::Cursor;
+
+// Largest frame read, small enough to keep iterations fast.
+const MAX_FRAME_LEN: u64 = 1 << 20;
+
+// Shaped like the requests of the services: tagged variants with strings,
+// byte buffers, optional fields, maps and nested lists.
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+enum Request {
+    Create(CreateRequest),
+    Get(GetRequest),
+    Batch(BatchRequest),
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+struct CreateRequest {
+    name: String,
+    data: Vec<u8>,
+    size: u64,
+    #[serde(default)]
+    labels: HashMap<String, String>,
+    #[serde(default)]
+    parent: Option<String>,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+struct GetRequest {
+    ids: Vec<String>,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+struct BatchRequest {
+    requests: Vec<Request>,
+}
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(frame) = codec::read_frame(&mut Cursor::new(data), MAX_FRAME_LEN) {
+        let _ = codec::decode::<Request>(&frame);
+    }
+    // The bytes may also be a payload on their own
+    let _ = codec::decode::<Request>(data);
+});
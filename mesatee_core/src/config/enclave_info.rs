@@ -0,0 +1,210 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// The measurements of the enclaves of the cluster, by service name, with the
+// signed fragments they were verified from. The enclave attributes verifiers
+// accept are resolved from them for every connection, so replacing them, e.g.
+// to accept a service added to the cluster, takes effect without a restart.
+// Measurements failing to load, or lacking a service still connected to,
+// leave the current ones in place.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+#[cfg(not(feature = "mesalock_sgx"))]
+use std::sync::RwLock;
+#[cfg(feature = "mesalock_sgx")]
+use std::sync::SgxRwLock as RwLock;
+use teaclave_utils::EnclaveMeasurement;
+
+use super::SignedEnclaveInfo;
+use crate::content_id::ContentHasher;
+use crate::{Error, ErrorKind, Result};
+
+pub(crate) type Measurements = HashMap<String, EnclaveMeasurement>;
+
+pub(crate) struct EnclaveInfo {
+    pub(crate) measurements: Measurements,
+    pub(crate) fragments: Vec<SignedEnclaveInfo>,
+}
+
+impl EnclaveInfo {
+    // Digest of the measurements, equal for enclave info listing the same
+    // enclaves however it is split into fragments.
+    pub(crate) fn digest(&self) -> String {
+        let mut names: Vec<&String> = self.measurements.keys().collect();
+        names.sort();
+        let mut hasher = ContentHasher::new("enclave-info-v1");
+        hasher.count(names.len());
+        for name in names {
+            let measurement = &self.measurements[name];
+            hasher
+                .field(name.as_bytes())
+                .field(&measurement.mr_signer)
+                .field(&measurement.mr_enclave);
+        }
+        hasher.finish()
+    }
+}
+
+pub(crate) struct EnclaveIdentities {
+    // Services this build connects to, which replacements must keep.
+    required: &'static [&'static str],
+    current: RwLock<Option<Arc<EnclaveInfo>>>,
+}
+
+impl EnclaveIdentities {
+    pub(crate) fn new(required: &'static [&'static str], initial: Option<EnclaveInfo>) -> Self {
+        EnclaveIdentities {
+            required,
+            current: RwLock::new(initial.map(Arc::new)),
+        }
+    }
+
+    // Unwrapping failing means the RwLock is poisoned.
+    pub(crate) fn current(&self) -> Option<Arc<EnclaveInfo>> {
+        self.current.read().unwrap().clone()
+    }
+
+    // Replaces the enclave info with the one `load` returns, all at once, and
+    // returns it. Enclave info lacking a required service the current one
+    // has is rejected, as connecting to the service would fail. Connections
+    // already verified are not affected.
+    pub(crate) fn replace_with<F>(&self, load: F) -> Result<Arc<EnclaveInfo>>
+    where
+        F: FnOnce() -> Result<EnclaveInfo>,
+    {
+        let enclave_info = Arc::new(load()?);
+        let mut current = self.current.write().unwrap();
+        if let Some(ref current) = *current {
+            let missing: Vec<&str> = self
+                .required
+                .iter()
+                .filter(|name| {
+                    current.measurements.contains_key(**name)
+                        && !enclave_info.measurements.contains_key(**name)
+                })
+                .cloned()
+                .collect();
+            if !missing.is_empty() {
+                return Err(Error::new(
+                    ErrorKind::InvalidInputError,
+                    format!("enclave info lacks services {}", missing.join(", ")),
+                ));
+            }
+        }
+        *current = Some(enclave_info.clone());
+        Ok(enclave_info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REQUIRED: &[&str] = &["tms", "tdfs"];
+
+    fn enclave_info(names: &[&str]) -> EnclaveInfo {
+        let measurements = names
+            .iter()
+            .enumerate()
+            .map(|(index, name)| {
+                let mr_enclave = [index as u8; 32];
+                (
+                    name.to_string(),
+                    EnclaveMeasurement::new(mr_enclave, [0; 32]),
+                )
+            })
+            .collect();
+        EnclaveInfo {
+            measurements,
+            fragments: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_replace_expands_the_accepted_set() {
+        let identities = EnclaveIdentities::new(REQUIRED, Some(enclave_info(&["tms", "tdfs"])));
+        let replaced = identities
+            .replace_with(|| Ok(enclave_info(&["tms", "tdfs", "acs"])))
+            .unwrap();
+        assert_eq!(replaced.measurements.len(), 3);
+        assert!(identities
+            .current()
+            .unwrap()
+            .measurements
+            .contains_key("acs"));
+    }
+
+    #[test]
+    fn test_failed_replace_keeps_the_current_set() {
+        let identities = EnclaveIdentities::new(REQUIRED, Some(enclave_info(&["tms", "tdfs"])));
+        let before = identities.current().unwrap();
+        let err = identities
+            .replace_with(|| Err(Error::from(ErrorKind::CryptoError)))
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::CryptoError);
+        let after = identities.current().unwrap();
+        assert!(Arc::ptr_eq(&before, &after));
+        assert!(!after.measurements.contains_key("acs"));
+    }
+
+    #[test]
+    fn test_replace_must_keep_required_services() {
+        let identities =
+            EnclaveIdentities::new(REQUIRED, Some(enclave_info(&["tms", "tdfs", "acs"])));
+        let before = identities.current().unwrap();
+        let err = identities
+            .replace_with(|| Ok(enclave_info(&["tms", "acs"])))
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInputError);
+        assert!(err.to_string().contains("tdfs"));
+        assert!(Arc::ptr_eq(&before, &identities.current().unwrap()));
+
+        // Services which are not required may be dropped
+        identities
+            .replace_with(|| Ok(enclave_info(&["tms", "tdfs"])))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_replace_loads_missing_set() {
+        let identities = EnclaveIdentities::new(REQUIRED, None);
+        assert!(identities.current().is_none());
+        identities
+            .replace_with(|| Ok(enclave_info(&["fns"])))
+            .unwrap();
+        assert!(identities
+            .current()
+            .unwrap()
+            .measurements
+            .contains_key("fns"));
+    }
+
+    #[test]
+    fn test_digest_changes_with_the_services() {
+        let first = enclave_info(&["tms", "tdfs"]);
+        let mut second = enclave_info(&["tms", "tdfs"]);
+        assert_eq!(first.digest(), second.digest());
+        second
+            .measurements
+            .insert("acs".to_string(), EnclaveMeasurement::new([9; 32], [0; 32]));
+        assert_ne!(first.digest(), second.digest());
+    }
+}
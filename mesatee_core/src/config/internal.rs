@@ -34,7 +34,7 @@ impl Internal {
     pub fn kms() -> ServiceConfig {
         ServiceConfig::new(
             runtime_config().internal_endpoints.kms.listen_address,
-            InboundDesc::Sgx(get_trusted_enclave_attr(vec!["fns", "tdfs"])),
+            InboundDesc::Sgx(get_trusted_enclave_attr(vec!["fns", "tdfs", "tms"])),
         )
     }
 
@@ -18,18 +18,23 @@
 // ip/port is dynamically dispatched for fns client.
 // we cannot use the &'static str in this struct.
 
-use std::collections::HashMap;
+use serde_derive::{Deserialize, Serialize};
 use std::net::SocketAddr;
 use std::prelude::v1::*;
+use std::sync::Arc;
 use teaclave_attestation;
-use teaclave_attestation::verifier::EnclaveAttr;
+pub use teaclave_attestation::verifier::EnclaveAttr;
 use teaclave_config::build_config::BUILD_CONFIG;
 use teaclave_config::runtime_config;
 use teaclave_config::runtime_config::RuntimeConfig;
 use teaclave_utils::EnclaveMeasurement;
 
+use crate::{Error, ErrorKind, Result};
+
+mod enclave_info;
 mod external;
 mod internal;
+use enclave_info::{EnclaveIdentities, EnclaveInfo, Measurements};
 pub use external::External;
 pub use internal::Internal;
 
@@ -79,35 +84,133 @@ impl ServiceConfig {
     }
 }
 
+/// Part of the enclave info with the auditor signatures over it, like the
+/// fragments of the `[audit]` section of the runtime config.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SignedEnclaveInfo {
+    pub enclave_info: String,
+    pub auditor_signatures: Vec<Vec<u8>>,
+}
+
 use lazy_static::lazy_static;
 
-fn load_presigned_enclave_info() -> HashMap<String, EnclaveMeasurement> {
-    if runtime_config().audit.auditor_signatures.len() < BUILD_CONFIG.auditor_public_keys.len() {
-        panic!("Number of auditor signatures is not enough for verification.")
-    }
+// Services whose measurements `Internal` and `External` resolve. Reloaded
+// enclave info may not drop one of them.
+const CONNECTED_SERVICES: &[&str] = &["acs", "fns", "kms", "tdfs", "tms"];
 
-    if !teaclave_utils::verify_enclave_info(
-        &runtime_config().audit.enclave_info.as_bytes(),
-        BUILD_CONFIG.auditor_public_keys,
-        &runtime_config().audit.auditor_signatures,
-    ) {
-        panic!("Failed to verify the signatures of enclave info.");
+fn load_presigned_enclave_info() -> Result<EnclaveInfo> {
+    if !is_runtime_config_initialized() {
+        return Err(Error::new(
+            ErrorKind::MissingValue,
+            "runtime config is not initialized",
+        ));
     }
 
-    teaclave_utils::load_enclave_info(&runtime_config().audit.enclave_info)
+    // Each fragment is signed on its own, e.g. by the build of each service
+    let fragments: Vec<SignedEnclaveInfo> = runtime_config()
+        .audit
+        .enclave_info_fragments
+        .iter()
+        .map(|fragment| SignedEnclaveInfo {
+            enclave_info: fragment.enclave_info.to_owned(),
+            auditor_signatures: fragment.auditor_signatures.clone(),
+        })
+        .collect();
+    load_signed_enclave_info(fragments)
+}
+
+fn load_signed_enclave_info(fragments: Vec<SignedEnclaveInfo>) -> Result<EnclaveInfo> {
+    let threshold = BUILD_CONFIG.auditor_signature_threshold;
+    for (index, fragment) in fragments.iter().enumerate() {
+        let auditor_signatures = &fragment.auditor_signatures;
+        if auditor_signatures.len() < threshold {
+            return Err(Error::new(
+                ErrorKind::InvalidInputError,
+                format!(
+                    "{} auditor signatures found for fragment #{} of enclave info, {} required \
+                     for verification",
+                    auditor_signatures.len(),
+                    index,
+                    threshold
+                ),
+            ));
+        }
+    }
+    let signed: Vec<(&str, &[Vec<u8>])> = fragments
+        .iter()
+        .map(|fragment| {
+            (
+                fragment.enclave_info.as_str(),
+                fragment.auditor_signatures.as_slice(),
+            )
+        })
+        .collect();
+    let measurements: Measurements = teaclave_utils::load_enclave_info_fragments(
+        &signed,
+        BUILD_CONFIG.auditor_public_keys,
+        threshold,
+    )?;
+    Ok(EnclaveInfo {
+        measurements,
+        fragments,
+    })
 }
 
 lazy_static! {
     static ref RUNTIME_CONFIG: Option<RuntimeConfig> =
         RuntimeConfig::from_toml("runtime.config.toml");
-    static ref ENCLAVE_IDENTITIES: HashMap<String, EnclaveMeasurement> =
-        load_presigned_enclave_info();
+    static ref ENCLAVE_IDENTITIES: EnclaveIdentities = EnclaveIdentities::new(
+        CONNECTED_SERVICES,
+        match load_presigned_enclave_info() {
+            Ok(enclave_info) => Some(enclave_info),
+            Err(e) => {
+                error!("Cannot load enclave info: {}", e);
+                None
+            }
+        }
+    );
 }
 
 pub fn is_runtime_config_initialized() -> bool {
     RUNTIME_CONFIG.is_some()
 }
 
+pub fn is_enclave_info_loaded() -> bool {
+    ENCLAVE_IDENTITIES.current().is_some()
+}
+
+fn current_enclave_info() -> Arc<EnclaveInfo> {
+    ENCLAVE_IDENTITIES
+        .current()
+        .expect("Invalid enclave info, should gracefully exit during enclave_init!")
+}
+
+/// Replaces the enclave info of the runtime config with `fragments`, each
+/// given with the auditor signatures over it and verified like the runtime
+/// config. New connections are verified against the new measurements, whose
+/// services are returned sorted by name. The enclave info is left unchanged
+/// if a fragment fails verification, or if it lacks a service this enclave
+/// connects to.
+pub fn reload_enclave_info(fragments: &[SignedEnclaveInfo]) -> Result<Vec<String>> {
+    let enclave_info =
+        ENCLAVE_IDENTITIES.replace_with(|| load_signed_enclave_info(fragments.to_vec()))?;
+    let mut names: Vec<String> = enclave_info.measurements.keys().cloned().collect();
+    names.sort();
+    Ok(names)
+}
+
+/// The fragments the current enclave info was verified from, so that it can
+/// be handed to other services.
+pub fn signed_enclave_info() -> Vec<SignedEnclaveInfo> {
+    current_enclave_info().fragments.clone()
+}
+
+/// Digest of the measurements of the current enclave info, for services to
+/// tell whether they verify peers against the same enclaves.
+pub fn enclave_info_digest() -> String {
+    current_enclave_info().digest()
+}
+
 pub fn runtime_config() -> &'static RuntimeConfig {
     RUNTIME_CONFIG
         .as_ref()
@@ -115,9 +218,15 @@ pub fn runtime_config() -> &'static RuntimeConfig {
 }
 
 pub fn get_trusted_enclave_attr(service_names: Vec<&str>) -> EnclaveAttr {
+    let enclave_info = current_enclave_info();
     let measures = service_names
         .iter()
-        .map(|name| *ENCLAVE_IDENTITIES.get(&(*name).to_string()).unwrap())
+        .map(|name| {
+            *enclave_info
+                .measurements
+                .get(*name)
+                .unwrap_or_else(|| panic!("Enclave info lacks the {} service", name))
+        })
         .collect();
     EnclaveAttr { measures }
 }
@@ -0,0 +1,124 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! IDs derived from the content of an object, so that an identical object
+//! gets the same ID wherever and whenever it is described.
+//!
+//! The content is a sequence of fields, hashed in this canonical form:
+//!
+//! * the domain, e.g. `task-v1`, encoded as a field;
+//! * each field as its length in bytes, a big-endian `u64`, followed by its
+//!   bytes;
+//! * a count, which precedes a list of fields, as a big-endian `u64`.
+//!
+//! Every field being length-prefixed, distinct sequences never share an
+//! encoding. The ID is the lowercase hex of the SHA-256 digest of the
+//! encoding, 64 characters long.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use ring::digest::{Context, SHA256};
+
+pub struct ContentHasher {
+    context: Context,
+}
+
+impl ContentHasher {
+    /// Starts the content of an object of `domain`. Objects of distinct
+    /// domains never share an ID.
+    pub fn new(domain: &str) -> Self {
+        let mut hasher = ContentHasher {
+            context: Context::new(&SHA256),
+        };
+        hasher.field(domain.as_bytes());
+        hasher
+    }
+
+    /// Appends a field.
+    pub fn field(&mut self, bytes: &[u8]) -> &mut Self {
+        self.count(bytes.len());
+        self.context.update(bytes);
+        self
+    }
+
+    /// Appends the number of fields of the list which follows.
+    pub fn count(&mut self, count: usize) -> &mut Self {
+        self.context.update(&(count as u64).to_be_bytes());
+        self
+    }
+
+    /// Appends a list of fields, preceded by their count.
+    pub fn fields<S: AsRef<[u8]>>(&mut self, fields: &[S]) -> &mut Self {
+        self.count(fields.len());
+        for field in fields {
+            self.field(field.as_ref());
+        }
+        self
+    }
+
+    /// ID of the content appended so far.
+    pub fn finish(self) -> String {
+        self.context
+            .finish()
+            .as_ref()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task_id(function: &str, inputs: &[&str], arguments: &str) -> String {
+        let mut hasher = ContentHasher::new("task-v1");
+        hasher
+            .field(function.as_bytes())
+            .fields(inputs)
+            .field(arguments.as_bytes());
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_identical_contents_get_the_same_id() {
+        let first = task_id("wordcount@v1", &["a1b2", "c3d4"], r#"{"n":1}"#);
+        let second = task_id("wordcount@v1", &["a1b2", "c3d4"], r#"{"n":1}"#);
+        assert_eq!(first, second);
+        assert_eq!(first.len(), 64);
+        assert!(first.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+
+    #[test]
+    fn test_distinct_contents_get_distinct_ids() {
+        let id = task_id("wordcount@v1", &["a1b2", "c3d4"], r#"{"n":1}"#);
+        assert_ne!(id, task_id("wordcount@v2", &["a1b2", "c3d4"], r#"{"n":1}"#));
+        assert_ne!(id, task_id("wordcount@v1", &["c3d4", "a1b2"], r#"{"n":1}"#));
+        assert_ne!(id, task_id("wordcount@v1", &["a1b2", "c3d4"], r#"{"n":2}"#));
+        // Field boundaries are part of the content
+        assert_ne!(id, task_id("wordcount@v1", &["a1b2c3d4"], r#"{"n":1}"#));
+        assert_ne!(id, task_id("wordcount@v1", &["a1", "b2c3d4"], r#"{"n":1}"#));
+
+        let mut other_domain = ContentHasher::new("file-v1");
+        other_domain
+            .field(b"wordcount@v1")
+            .fields(&["a1b2", "c3d4"])
+            .field(br#"{"n":1}"#);
+        assert_ne!(id, other_domain.finish());
+    }
+}
@@ -0,0 +1,123 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+//! Content types declared for files, e.g. `application/parquet`, with an
+//! optional reference to the schema of the content.
+//!
+//! A content type is checked against the restricted names of RFC 6838: a
+//! type and a subtype, separated by `/`, each starting with a letter or
+//! digit and made of at most 127 letters, digits and `!#$&-^_.+`. Parameters
+//! such as `; charset=utf-8` are not accepted. A schema reference is any
+//! non-empty text of at most `MAX_SCHEMA_LEN` bytes without control
+//! characters, e.g. a URL or a file ID, which is not resolved.
+//!
+//! Both are metadata of a file only: they are not covered by its digest.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use crate::{Error, ErrorKind, Result};
+
+const MAX_NAME_LEN: usize = 127;
+pub const MAX_SCHEMA_LEN: usize = 1024;
+
+fn is_restricted_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) if first.is_ascii_alphanumeric() => {}
+        _ => return false,
+    }
+    name.len() <= MAX_NAME_LEN
+        && chars.all(|c| c.is_ascii_alphanumeric() || "!#$&-^_.+".contains(c))
+}
+
+pub fn check_content_type(content_type: &str) -> Result<()> {
+    let mut names = content_type.splitn(2, '/');
+    let valid = match (names.next(), names.next()) {
+        (Some(type_name), Some(subtype)) => {
+            is_restricted_name(type_name) && is_restricted_name(subtype)
+        }
+        _ => false,
+    };
+    if !valid {
+        return Err(Error::new(
+            ErrorKind::InvalidInputError,
+            format!(
+                "content type '{}' is not of the form type/subtype",
+                content_type
+            ),
+        ));
+    }
+    Ok(())
+}
+
+pub fn check_schema(schema: &str) -> Result<()> {
+    if schema.is_empty() || schema.len() > MAX_SCHEMA_LEN {
+        return Err(Error::new(
+            ErrorKind::InvalidInputError,
+            format!("schema must have 1 to {} bytes", MAX_SCHEMA_LEN),
+        ));
+    }
+    if schema.chars().any(char::is_control) {
+        return Err(Error::new(
+            ErrorKind::InvalidInputError,
+            "schema must not have control characters",
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_types() {
+        for content_type in &[
+            "application/parquet",
+            "text/csv",
+            "application/vnd.apache.arrow.file",
+            "application/ld+json",
+        ] {
+            assert!(check_content_type(content_type).is_ok(), "{}", content_type);
+        }
+        for content_type in &[
+            "",
+            "parquet",
+            "application/",
+            "/parquet",
+            "application/parquet/v2",
+            "text/csv; charset=utf-8",
+            "application/.parquet",
+        ] {
+            assert!(
+                check_content_type(content_type).is_err(),
+                "{}",
+                content_type
+            );
+        }
+        let long_subtype = "x".repeat(MAX_NAME_LEN + 1);
+        assert!(check_content_type(&format!("application/{}", long_subtype)).is_err());
+    }
+
+    #[test]
+    fn test_schemas() {
+        assert!(check_schema("https://schemas.example.com/sales-v2.json").is_ok());
+        assert!(check_schema("").is_err());
+        assert!(check_schema("line\nbreak").is_err());
+        assert!(check_schema(&"s".repeat(MAX_SCHEMA_LEN + 1)).is_err());
+    }
+}
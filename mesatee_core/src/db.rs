@@ -30,9 +30,13 @@ cfg_if! {
     }
 }
 
-use crate::Result;
-use std::collections::HashMap;
+use crate::{Error, ErrorKind, Result};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::hash;
+use std::time::{Duration, SystemTime};
+#[cfg(feature = "mesalock_sgx")]
+use std::untrusted::time::SystemTimeEx;
 
 pub struct Memdb<K: Clone + Eq + hash::Hash, V: Clone> {
     hashmap: RwLock<HashMap<K, V>>,
@@ -55,8 +59,514 @@ impl<K: Clone + Eq + hash::Hash, V: Clone> Memdb<K, V> {
         Ok(hashmap.get(key).cloned())
     }
 
+    /// Values of `keys` in the same order, read at once.
+    pub fn get_many(&self, keys: &[K]) -> Result<Vec<Option<V>>> {
+        let hashmap = self.hashmap.read()?;
+        Ok(keys.iter().map(|key| hashmap.get(key).cloned()).collect())
+    }
+
     pub fn del(&self, key: &K) -> Result<Option<V>> {
         let mut hashmap = self.hashmap.write()?;
         Ok(hashmap.remove(key))
     }
+
+    // Snapshot of all entries, in no particular order.
+    pub fn entries(&self) -> Result<Vec<(K, V)>> {
+        let hashmap = self.hashmap.read()?;
+        Ok(hashmap
+            .iter()
+            .map(|(key, value)| (key.to_owned(), value.to_owned()))
+            .collect())
+    }
+}
+
+/// Write of a batch applied by `Kvdb::write`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum WriteOp {
+    /// Puts `value`, which expires `ttl` after the write if set.
+    Put {
+        key: String,
+        value: String,
+        #[serde(default)]
+        ttl: Option<Duration>,
+    },
+    /// Puts `value` if the entry still has `version`, or is absent if
+    /// `version` is 0, and fails the batch otherwise.
+    PutIfVersion {
+        key: String,
+        value: String,
+        version: u64,
+    },
+    Delete {
+        key: String,
+    },
+    /// Fails the batch unless `key` is present, or absent if `present` is
+    /// false, once the previous writes of the batch are applied.
+    Check {
+        key: String,
+        present: bool,
+    },
+}
+
+/// Value of an entry with the version of its latest write.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Versioned {
+    pub value: String,
+    pub version: u64,
+}
+
+#[derive(Clone)]
+struct Stored {
+    entry: Versioned,
+    expires_at: Option<SystemTime>,
+}
+
+impl Stored {
+    fn is_live(&self, now: SystemTime) -> bool {
+        self.expires_at.map_or(true, |expires_at| now < expires_at)
+    }
+}
+
+struct Entries {
+    map: BTreeMap<String, Stored>,
+    last_version: u64,
+}
+
+impl Entries {
+    fn live(&self, key: &str, now: SystemTime) -> Option<&Versioned> {
+        self.map
+            .get(key)
+            .filter(|stored| stored.is_live(now))
+            .map(|stored| &stored.entry)
+    }
+}
+
+/// String entries sorted by key. A batch of writes is applied atomically:
+/// readers see none or all of it, and none of it is kept if a write fails.
+/// Every batch gives the entries it puts a new version, greater than all the
+/// versions given before, so that a version is never reused, even by an
+/// entry deleted and put again.
+///
+/// An entry put with a TTL is absent for all reads and writes once it
+/// expires, and is removed by the next `sweep`.
+pub struct Kvdb {
+    entries: RwLock<Entries>,
+}
+
+impl Kvdb {
+    pub fn open() -> Result<Self> {
+        Ok(Self {
+            entries: RwLock::new(Entries {
+                map: BTreeMap::new(),
+                last_version: 0,
+            }),
+        })
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<Versioned>> {
+        let entries = self.entries.read()?;
+        Ok(entries.live(key, SystemTime::now()).cloned())
+    }
+
+    /// Returns the version of the entry.
+    pub fn put(&self, key: &str, value: &str) -> Result<u64> {
+        self.write(&[WriteOp::Put {
+            key: key.to_owned(),
+            value: value.to_owned(),
+            ttl: None,
+        }])
+    }
+
+    /// Puts `value`, which expires `ttl` from now. Returns the version of the
+    /// entry.
+    pub fn put_with_ttl(&self, key: &str, value: &str, ttl: Duration) -> Result<u64> {
+        self.write(&[WriteOp::Put {
+            key: key.to_owned(),
+            value: value.to_owned(),
+            ttl: Some(ttl),
+        }])
+    }
+
+    /// Puts `value` if the entry still has `version`, or is absent if
+    /// `version` is 0. Fails with `StorageConflict` otherwise. Returns the new
+    /// version of the entry.
+    pub fn compare_and_put(&self, key: &str, value: &str, version: u64) -> Result<u64> {
+        self.write(&[WriteOp::PutIfVersion {
+            key: key.to_owned(),
+            value: value.to_owned(),
+            version,
+        }])
+    }
+
+    pub fn delete(&self, key: &str) -> Result<()> {
+        self.write(&[WriteOp::Delete {
+            key: key.to_owned(),
+        }])?;
+        Ok(())
+    }
+
+    /// Entries whose key starts with `prefix`, sorted by key.
+    pub fn scan(&self, prefix: &str) -> Result<Vec<(String, Versioned)>> {
+        let entries = self.entries.read()?;
+        let now = SystemTime::now();
+        Ok(entries
+            .map
+            .range(prefix.to_owned()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .filter(|(_, stored)| stored.is_live(now))
+            .map(|(key, stored)| (key.to_owned(), stored.entry.to_owned()))
+            .collect())
+    }
+
+    /// At most `limit` entries whose key starts with `prefix`, from
+    /// `start_key` on if set, sorted by key. Returns the key to start the
+    /// next page at, if there are more entries. `start_key` must start with
+    /// `prefix`, so that a scan never leaves its prefix.
+    pub fn scan_page(
+        &self,
+        prefix: &str,
+        start_key: Option<&str>,
+        limit: usize,
+    ) -> Result<(Vec<(String, Versioned)>, Option<String>)> {
+        let start = match start_key {
+            Some(start_key) if !start_key.starts_with(prefix) => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInputError,
+                    "start key must start with the prefix",
+                ));
+            }
+            Some(start_key) => start_key,
+            None => prefix,
+        };
+        if limit == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInputError,
+                "limit must be positive",
+            ));
+        }
+        let entries = self.entries.read()?;
+        let now = SystemTime::now();
+        let mut page: Vec<(String, Versioned)> = entries
+            .map
+            .range(start.to_owned()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .filter(|(_, stored)| stored.is_live(now))
+            .take(limit + 1)
+            .map(|(key, stored)| (key.to_owned(), stored.entry.to_owned()))
+            .collect();
+        let next_key = if page.len() > limit {
+            page.pop().map(|(key, _)| key)
+        } else {
+            None
+        };
+        Ok((page, next_key))
+    }
+
+    /// Removes the expired entries, and returns how many there were.
+    pub fn sweep(&self) -> Result<usize> {
+        let mut entries = self.entries.write()?;
+        let now = SystemTime::now();
+        let before = entries.map.len();
+        entries.map.retain(|_, stored| stored.is_live(now));
+        Ok(before - entries.map.len())
+    }
+
+    /// Applies `ops` in order and returns the version of the entries put. If
+    /// one fails, the previous ones are rolled back and its error is returned:
+    /// `StorageConflict` for a failed check or version, `InvalidInputError`
+    /// for an empty key.
+    pub fn write(&self, ops: &[WriteOp]) -> Result<u64> {
+        let mut entries = self.entries.write()?;
+        let version = entries.last_version + 1;
+        let now = SystemTime::now();
+        // Previous values of the keys written so far, to roll them back.
+        let mut undo: Vec<(String, Option<Stored>)> = Vec::with_capacity(ops.len());
+        for op in ops.iter() {
+            if let Err(e) = apply(&mut entries, op, version, now, &mut undo) {
+                for (key, previous) in undo.into_iter().rev() {
+                    match previous {
+                        Some(stored) => entries.map.insert(key, stored),
+                        None => entries.map.remove(&key),
+                    };
+                }
+                return Err(e);
+            }
+        }
+        entries.last_version = version;
+        Ok(version)
+    }
+}
+
+fn apply(
+    entries: &mut Entries,
+    op: &WriteOp,
+    version: u64,
+    now: SystemTime,
+    undo: &mut Vec<(String, Option<Stored>)>,
+) -> Result<()> {
+    let (key, value, ttl) = match op {
+        WriteOp::Put { key, value, ttl } => (key, value, *ttl),
+        WriteOp::PutIfVersion {
+            key,
+            value,
+            version: expected,
+        } => {
+            let current = entries.live(key, now).map_or(0, |entry| entry.version);
+            if current != *expected {
+                return Err(Error::new(
+                    ErrorKind::StorageConflict,
+                    format!("entry {} has version {}, not {}", key, current, expected),
+                ));
+            }
+            (key, value, None)
+        }
+        WriteOp::Delete { key } => {
+            let previous = entries.map.remove(key);
+            undo.push((key.to_owned(), previous));
+            return Ok(());
+        }
+        WriteOp::Check { key, present } => {
+            if entries.live(key, now).is_some() != *present {
+                let state = if *present { "absent" } else { "present" };
+                return Err(Error::new(
+                    ErrorKind::StorageConflict,
+                    format!("entry {} is {}", key, state),
+                ));
+            }
+            return Ok(());
+        }
+    };
+    if key.is_empty() {
+        return Err(Error::new(ErrorKind::InvalidInputError, "empty key"));
+    }
+    let stored = Stored {
+        entry: Versioned {
+            value: value.to_owned(),
+            version,
+        },
+        expires_at: ttl.map(|ttl| now + ttl),
+    };
+    let previous = entries.map.insert(key.to_owned(), stored);
+    undo.push((key.to_owned(), previous));
+    Ok(())
+}
+
+/// Runs `update` until it does not fail with `StorageConflict`, at most
+/// `max_attempts` times. `update` is expected to read the entries it changes
+/// again, and to write them with the versions it read.
+pub fn retry_on_conflict<T, F>(max_attempts: usize, mut update: F) -> Result<T>
+where
+    F: FnMut() -> Result<T>,
+{
+    let mut attempt = 1;
+    loop {
+        match update() {
+            Err(ref e) if e.kind() == ErrorKind::StorageConflict && attempt < max_attempts => {
+                attempt += 1
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put(key: &str, value: &str) -> WriteOp {
+        WriteOp::Put {
+            key: key.to_owned(),
+            value: value.to_owned(),
+            ttl: None,
+        }
+    }
+
+    fn value(db: &Kvdb, key: &str) -> Option<String> {
+        db.get(key).unwrap().map(|entry| entry.value)
+    }
+
+    #[test]
+    fn test_get_many_keeps_the_order_of_keys() {
+        let db = Memdb::<String, u32>::open().unwrap();
+        db.set(&"a".to_owned(), &1).unwrap();
+        db.set(&"c".to_owned(), &3).unwrap();
+        let keys: Vec<String> = ["c", "b", "a", "c"]
+            .iter()
+            .map(|key| key.to_string())
+            .collect();
+        assert_eq!(
+            db.get_many(&keys).unwrap(),
+            vec![Some(3), None, Some(1), Some(3)]
+        );
+        assert!(db.get_many(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_failed_write_rolls_back_the_batch() {
+        let db = Kvdb::open().unwrap();
+        db.put("a", "1").unwrap();
+
+        let ops = vec![
+            put("a", "2"),
+            put("b", "2"),
+            WriteOp::Delete {
+                key: "a".to_owned(),
+            },
+            WriteOp::Check {
+                key: "c".to_owned(),
+                present: true,
+            },
+        ];
+        let err = db.write(&ops).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::StorageConflict);
+        assert_eq!(value(&db, "a"), Some("1".to_owned()));
+        assert_eq!(value(&db, "b"), None);
+
+        let err = db.write(&[put("b", "2"), put("", "3")]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInputError);
+        assert_eq!(value(&db, "b"), None);
+    }
+
+    #[test]
+    fn test_checks_see_previous_writes() {
+        let db = Kvdb::open().unwrap();
+        let ops = vec![
+            put("a", "1"),
+            WriteOp::Check {
+                key: "a".to_owned(),
+                present: true,
+            },
+        ];
+        db.write(&ops).unwrap();
+        assert_eq!(value(&db, "a"), Some("1".to_owned()));
+    }
+
+    #[test]
+    fn test_versions_are_never_reused() {
+        let db = Kvdb::open().unwrap();
+        let first = db.put("a", "1").unwrap();
+        db.delete("a").unwrap();
+        let second = db.compare_and_put("a", "2", 0).unwrap();
+        assert!(second > first);
+        assert_eq!(db.get("a").unwrap().unwrap().version, second);
+
+        let err = db.compare_and_put("a", "3", first).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::StorageConflict);
+        assert_eq!(value(&db, "a"), Some("2".to_owned()));
+    }
+
+    // Two updates of a counter read the same version. The second one to
+    // write gets a conflict, and adds to the value of the first one when
+    // retried.
+    #[test]
+    fn test_concurrent_update_retries_on_conflict() {
+        let db = Kvdb::open().unwrap();
+        db.put("counter", "0").unwrap();
+        let increment = |read: &Versioned| {
+            let count: u32 = read.value.parse().unwrap();
+            db.compare_and_put("counter", &(count + 1).to_string(), read.version)
+        };
+
+        let stale = db.get("counter").unwrap().unwrap();
+        let mut attempts = 0;
+        retry_on_conflict(3, || {
+            attempts += 1;
+            let read = if attempts == 1 {
+                // The other update writes after this one has read.
+                let read = stale.clone();
+                increment(&db.get("counter").unwrap().unwrap()).unwrap();
+                read
+            } else {
+                db.get("counter").unwrap().unwrap()
+            };
+            increment(&read)
+        })
+        .unwrap();
+
+        assert_eq!(attempts, 2);
+        assert_eq!(value(&db, "counter"), Some("2".to_owned()));
+    }
+
+    #[test]
+    fn test_retry_gives_up() {
+        let mut attempts = 0;
+        let result: Result<()> = retry_on_conflict(3, || {
+            attempts += 1;
+            Err(Error::from(ErrorKind::StorageConflict))
+        });
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::StorageConflict);
+        assert_eq!(attempts, 3);
+    }
+
+    #[test]
+    fn test_expired_entry_is_gone() {
+        let db = Kvdb::open().unwrap();
+        db.put_with_ttl("session", "s", Duration::from_millis(50))
+            .unwrap();
+        db.put("kept", "k").unwrap();
+        assert_eq!(value(&db, "session"), Some("s".to_owned()));
+
+        std::thread::sleep(Duration::from_millis(100));
+        // Expired but not yet swept
+        assert_eq!(value(&db, "session"), None);
+        assert_eq!(db.scan("").unwrap().len(), 1);
+        db.compare_and_put("session", "t", 0).unwrap();
+        db.delete("session").unwrap();
+
+        db.put_with_ttl("session", "s", Duration::from_millis(0))
+            .unwrap();
+        assert_eq!(db.sweep().unwrap(), 1);
+        assert_eq!(value(&db, "kept"), Some("k".to_owned()));
+    }
+
+    #[test]
+    fn test_scan_pages() {
+        let db = Kvdb::open().unwrap();
+        for key in ["task/3", "task/1", "task/2", "tasks", "t"].iter() {
+            db.put(key, key).unwrap();
+        }
+        let keys = |page: &[(String, Versioned)]| -> Vec<String> {
+            page.iter().map(|(key, _)| key.to_owned()).collect()
+        };
+
+        let (page, next_key) = db.scan_page("task/", None, 2).unwrap();
+        assert_eq!(keys(&page), vec!["task/1", "task/2"]);
+        assert_eq!(page[0].1.value, "task/1");
+        assert_eq!(next_key, Some("task/3".to_owned()));
+
+        let (page, next_key) = db
+            .scan_page("task/", next_key.as_ref().map(|key| key.as_str()), 2)
+            .unwrap();
+        assert_eq!(keys(&page), vec!["task/3"]);
+        assert_eq!(next_key, None);
+
+        let (page, next_key) = db.scan_page("task/", None, 3).unwrap();
+        assert_eq!(page.len(), 3);
+        assert_eq!(next_key, None);
+    }
+
+    #[test]
+    fn test_scan_stays_in_prefix() {
+        let db = Kvdb::open().unwrap();
+        db.put("other/1", "v").unwrap();
+        let err = db.scan_page("task/", Some("other/"), 10).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInputError);
+        let err = db.scan_page("task/", None, 0).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInputError);
+    }
+
+    #[test]
+    fn test_scan_prefix() {
+        let db = Kvdb::open().unwrap();
+        for key in ["task/2", "task/1", "tasks", "user/1"].iter() {
+            db.put(key, "v").unwrap();
+        }
+        let keys: Vec<String> = db
+            .scan("task/")
+            .unwrap()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(keys, vec!["task/1", "task/2"]);
+    }
 }
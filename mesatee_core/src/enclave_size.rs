@@ -0,0 +1,110 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+//! Heap and stack sizes of a service enclave. The sizes are fixed by the
+//! `HeapMaxSize` and `StackMaxSize` of its `Enclave.config.xml` when it is
+//! signed. A service declares the sizes it expects to need under load, and
+//! is warned at init when the enclave is configured with less, instead of
+//! failing allocations mid-request.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EnclaveSize {
+    // Bytes of the enclave heap
+    pub heap_size: usize,
+    // Bytes of the stack of each enclave thread
+    pub stack_size: usize,
+}
+
+impl EnclaveSize {
+    pub const fn new(heap_size: usize, stack_size: usize) -> EnclaveSize {
+        EnclaveSize {
+            heap_size,
+            stack_size,
+        }
+    }
+
+    /// Sizes the running enclave is configured with.
+    #[cfg(feature = "mesalock_sgx")]
+    pub fn configured() -> EnclaveSize {
+        use sgx_trts::enclave::{SgxGlobalData, SgxThreadData};
+        let thread_data = SgxThreadData::current();
+        EnclaveSize {
+            heap_size: SgxGlobalData::new().heap_size(),
+            stack_size: thread_data.stack_base() - thread_data.stack_limit(),
+        }
+    }
+
+    /// Describes each size below the one of `hints`.
+    pub fn shortfalls(&self, hints: &EnclaveSize) -> Vec<String> {
+        let mut shortfalls = Vec::new();
+        if self.heap_size < hints.heap_size {
+            shortfalls.push(format!(
+                "HeapMaxSize {:#x} is below the expected {:#x}",
+                self.heap_size, hints.heap_size
+            ));
+        }
+        if self.stack_size < hints.stack_size {
+            shortfalls.push(format!(
+                "StackMaxSize {:#x} is below the expected {:#x}",
+                self.stack_size, hints.stack_size
+            ));
+        }
+        shortfalls
+    }
+}
+
+/// Logs the configured sizes of the enclave, and warns about those below
+/// `hints`.
+#[cfg(feature = "mesalock_sgx")]
+pub(crate) fn check(name: &str, hints: &EnclaveSize) {
+    let configured = EnclaveSize::configured();
+    info!(
+        "Enclave [{}]: Heap size {:#x}, stack size {:#x}",
+        name, configured.heap_size, configured.stack_size
+    );
+    for shortfall in configured.shortfalls(hints) {
+        warn!(
+            "Enclave [{}]: {}, allocations may fail under load",
+            name, shortfall
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shortfalls() {
+        let configured = EnclaveSize::new(0x1000000, 0x200000);
+        assert!(configured.shortfalls(&EnclaveSize::default()).is_empty());
+        assert!(configured.shortfalls(&configured).is_empty());
+
+        let shortfalls = configured.shortfalls(&EnclaveSize::new(0x3800000, 0x100000));
+        assert_eq!(
+            shortfalls,
+            vec!["HeapMaxSize 0x1000000 is below the expected 0x3800000"]
+        );
+        let shortfalls = configured.shortfalls(&EnclaveSize::new(0, 0x400000));
+        assert_eq!(
+            shortfalls,
+            vec!["StackMaxSize 0x200000 is below the expected 0x400000"]
+        );
+    }
+}
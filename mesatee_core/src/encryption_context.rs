@@ -0,0 +1,167 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Task a file is encrypted for, bound to its ciphertext as additional
+//! authenticated data (AAD), so that the ciphertext of one task cannot be
+//! passed off as a file of another.
+//!
+//! The AAD of a file encrypted in a context is, in order:
+//!
+//! * the additional data of the file key, 5 bytes;
+//! * the domain `teaclave-file-context-v1`;
+//! * the task ID;
+//! * the function ID, i.e. the versioned ID of the registered function of the
+//!   task, or the name of its built-in function.
+//!
+//! Each of the last three is its length in bytes, as a big-endian `u64`,
+//! followed by its bytes. The AAD of a file without a context is the
+//! additional data of its key alone.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use crate::{Error, ErrorKind};
+use serde_derive::{Deserialize, Serialize};
+
+const CONTEXT_DOMAIN: &str = "teaclave-file-context-v1";
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct EncryptionContext {
+    pub task_id: String,
+    pub function_id: String,
+}
+
+impl EncryptionContext {
+    pub fn new(task_id: &str, function_id: &str) -> Self {
+        EncryptionContext {
+            task_id: task_id.to_owned(),
+            function_id: function_id.to_owned(),
+        }
+    }
+
+    /// AAD of a file whose key has the additional data `ad`.
+    pub fn aad(&self, ad: &[u8]) -> Vec<u8> {
+        let mut aad = ad.to_vec();
+        let fields = [
+            CONTEXT_DOMAIN,
+            self.task_id.as_str(),
+            self.function_id.as_str(),
+        ];
+        for field in fields.iter() {
+            aad.extend_from_slice(&(field.len() as u64).to_be_bytes());
+            aad.extend_from_slice(field.as_bytes());
+        }
+        aad
+    }
+}
+
+/// AAD of a file whose key has the additional data `ad`, encrypted in
+/// `context` if any.
+pub fn file_aad(ad: &[u8], context: Option<&EncryptionContext>) -> Vec<u8> {
+    match context {
+        Some(context) => context.aad(ad),
+        None => ad.to_vec(),
+    }
+}
+
+/// Error of content failing to decrypt with the AAD of `context`. Content
+/// encrypted in a context fails with `DataIntegrityError`, which is what
+/// decrypting it in another context yields.
+pub fn decryption_error(err: Error, context: Option<&EncryptionContext>) -> Error {
+    match context {
+        Some(context) if err.kind() == ErrorKind::CryptoError => Error::new(
+            ErrorKind::DataIntegrityError,
+            format!(
+                "content does not match the encryption context of task {}",
+                context.task_id
+            ),
+        ),
+        _ => err,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM};
+
+    const KEY: [u8; 32] = [7; 32];
+    const NONCE: [u8; 12] = [3; 12];
+    const AD: &[u8] = b"abcde";
+
+    fn key() -> LessSafeKey {
+        LessSafeKey::new(UnboundKey::new(&AES_256_GCM, &KEY).unwrap())
+    }
+
+    fn seal(plaintext: &[u8], context: Option<&EncryptionContext>) -> Vec<u8> {
+        let mut data = plaintext.to_vec();
+        let nonce = Nonce::assume_unique_for_key(NONCE);
+        let aad = Aad::from(file_aad(AD, context));
+        key()
+            .seal_in_place_append_tag(nonce, aad, &mut data)
+            .unwrap();
+        data
+    }
+
+    fn open(mut sealed: Vec<u8>, context: Option<&EncryptionContext>) -> Option<Vec<u8>> {
+        let nonce = Nonce::assume_unique_for_key(NONCE);
+        let aad = Aad::from(file_aad(AD, context));
+        let plaintext = key().open_in_place(nonce, aad, &mut sealed).ok()?;
+        Some(plaintext.to_vec())
+    }
+
+    #[test]
+    fn test_aad_without_context_is_the_key_ad() {
+        assert_eq!(file_aad(AD, None), AD.to_vec());
+        let context = EncryptionContext::new("t", "f@v1");
+        let aad = context.aad(AD);
+        assert!(aad.starts_with(AD));
+        assert_eq!(aad.len(), AD.len() + 3 * 8 + CONTEXT_DOMAIN.len() + 1 + 4);
+    }
+
+    #[test]
+    fn test_opens_in_the_context_it_was_sealed_in() {
+        let context = EncryptionContext::new("task-1", "wordcount@v1");
+        let sealed = seal(b"output", Some(&context));
+        assert_eq!(open(sealed, Some(&context)), Some(b"output".to_vec()));
+    }
+
+    #[test]
+    fn test_mismatched_context_fails_to_open() {
+        let context = EncryptionContext::new("task-1", "wordcount@v1");
+        let sealed = seal(b"output", Some(&context));
+        let other_task = EncryptionContext::new("task-2", "wordcount@v1");
+        let other_function = EncryptionContext::new("task-1", "wordcount@v2");
+        assert_eq!(open(sealed.clone(), Some(&other_task)), None);
+        assert_eq!(open(sealed.clone(), Some(&other_function)), None);
+        assert_eq!(open(sealed, None), None);
+        // Field boundaries are part of the context
+        let sealed = seal(b"output", Some(&EncryptionContext::new("ab", "c")));
+        assert_eq!(open(sealed, Some(&EncryptionContext::new("a", "bc"))), None);
+    }
+
+    #[test]
+    fn test_decryption_error_in_a_context_is_an_integrity_error() {
+        let context = EncryptionContext::new("task-1", "wordcount@v1");
+        let err = decryption_error(Error::from(ErrorKind::CryptoError), Some(&context));
+        assert_eq!(err.kind(), ErrorKind::DataIntegrityError);
+        let err = decryption_error(Error::from(ErrorKind::CryptoError), None);
+        assert_eq!(err.kind(), ErrorKind::CryptoError);
+        let err = decryption_error(Error::from(ErrorKind::IoError), Some(&context));
+        assert_eq!(err.kind(), ErrorKind::IoError);
+    }
+}
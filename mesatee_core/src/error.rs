@@ -20,6 +20,7 @@
 use std::prelude::v1::*;
 
 use std::fmt;
+use std::time::Duration;
 use std::{io, net};
 use teaclave_utils;
 
@@ -48,10 +49,105 @@ impl fmt::Debug for Error {
 #[derive(serde_derive::Serialize, serde_derive::Deserialize)]
 enum Repr {
     Simple(ErrorKind),
+    // Milliseconds after which the request may be retried. Unlike `Custom`,
+    // sent to RPC clients.
+    RetryAfter(ErrorKind, u64),
+    // Human-readable detail of a `Custom` error, kept when it is sent to RPC
+    // clients.
+    Detailed(ErrorKind, String),
+    // Problems with the fields of a request, all found at once.
+    Invalid(ErrorKind, Vec<FieldError>),
     #[serde(skip)]
     Custom(Box<Custom>),
 }
 
+/// A problem with one field of a request, e.g. `files[2]: NotFound`.
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Clone, Debug, PartialEq)]
+pub struct FieldError {
+    /// Path of the field in the request, e.g. `files[2]` or `labels.team`.
+    pub field_path: String,
+    pub code: ErrorKind,
+    pub message: String,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "{}: {:?}: {}",
+            self.field_path, self.code, self.message
+        )
+    }
+}
+
+/// Collects the problems found validating the fields of a request, so that
+/// the client sees all of them rather than the first one.
+#[derive(Default, Debug)]
+pub struct FieldErrors {
+    errors: Vec<FieldError>,
+}
+
+impl FieldErrors {
+    pub fn new() -> FieldErrors {
+        FieldErrors::default()
+    }
+
+    /// Records the error of `result` for the field `field_path`, returning
+    /// the value of `result` if there is no error. The field errors of an
+    /// error are recorded under `field_path`.
+    pub fn check<T>(&mut self, field_path: &str, result: Result<T>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(err) => {
+                self.push(field_path, err);
+                None
+            }
+        }
+    }
+
+    pub fn push(&mut self, field_path: &str, err: Error) {
+        if let Repr::Invalid(_, ref errors) = err.repr {
+            for error in errors.iter() {
+                self.errors.push(FieldError {
+                    field_path: join_field_path(field_path, &error.field_path),
+                    ..error.clone()
+                });
+            }
+            return;
+        }
+        self.errors.push(FieldError {
+            field_path: field_path.to_owned(),
+            code: err.kind(),
+            message: err.to_string(),
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Fails with all the recorded errors, if any.
+    pub fn into_result(self) -> Result<()> {
+        if self.errors.is_empty() {
+            return Ok(());
+        }
+        Err(Error::from_field_errors(self.errors))
+    }
+}
+
+// `inner` is relative to `outer`, e.g. `[2]` or `file_id`.
+fn join_field_path(outer: &str, inner: &str) -> String {
+    if outer.is_empty() {
+        inner.to_owned()
+    } else if inner.is_empty() {
+        outer.to_owned()
+    } else if inner.starts_with('[') {
+        format!("{}{}", outer, inner)
+    } else {
+        format!("{}.{}", outer, inner)
+    }
+}
+
 #[derive(Debug)]
 struct Custom {
     kind: ErrorKind,
@@ -141,6 +237,26 @@ pub enum ErrorKind {
     MesaPyError,
     /// RPC input/output is invalid.
     RpcProtocolError,
+    /// RPC request did not complete within its timeout.
+    TimedOut,
+    /// Data does not match its recorded digest.
+    DataIntegrityError,
+    /// The server is serving its maximum number of connections.
+    TooManyConnections,
+    /// The client sent more requests than it is allowed to.
+    RateLimited,
+    /// The deadline of the request passed before it was completed.
+    DeadlineExceeded,
+    /// The requested entity, e.g. a task or a file, does not exist.
+    NotFound,
+    /// The credentials of the request are invalid.
+    Unauthenticated,
+    /// The handler of the request panicked.
+    HandlerPanicked,
+    /// Stored entries do not match the expectations of a write.
+    StorageConflict,
+    /// A dependency of the service is failing, and is not called for now.
+    Unavailable,
     /// Others.
     Unknown,
 }
@@ -215,9 +331,95 @@ impl ErrorKind {
             ErrorKind::MsgSizeLimitExceedError => "message size exceeds limit",
             ErrorKind::MesaPyError => "unhandled mesapy exception",
             ErrorKind::RpcProtocolError => "invalid rpc protocol",
+            ErrorKind::TimedOut => "rpc request timed out",
+            ErrorKind::DataIntegrityError => "data integrity error",
+            ErrorKind::TooManyConnections => "too many connections",
+            ErrorKind::RateLimited => "rate limited",
+            ErrorKind::DeadlineExceeded => "deadline exceeded",
+            ErrorKind::NotFound => "not found",
+            ErrorKind::Unauthenticated => "unauthenticated",
+            ErrorKind::HandlerPanicked => "request handler panicked",
+            ErrorKind::StorageConflict => "storage conflict",
+            ErrorKind::Unavailable => "service unavailable",
             ErrorKind::Unknown => "unknown error",
         }
     }
+
+    pub fn status(self) -> Status {
+        match self {
+            ErrorKind::MissingValue
+            | ErrorKind::InvalidHTTPRequest
+            | ErrorKind::ParseError
+            | ErrorKind::FunctionNotSupportedError
+            | ErrorKind::InvalidInputError
+            | ErrorKind::MsgSizeLimitExceedError
+            | ErrorKind::RpcProtocolError => Status::InvalidArgument,
+            ErrorKind::TimedOut | ErrorKind::DeadlineExceeded => Status::DeadlineExceeded,
+            ErrorKind::NotFound | ErrorKind::KeyNotFoundError => Status::NotFound,
+            ErrorKind::PermissionDenied => Status::PermissionDenied,
+            ErrorKind::TooManyConnections | ErrorKind::RateLimited => Status::ResourceExhausted,
+            ErrorKind::ConnectionRefused
+            | ErrorKind::IoError
+            | ErrorKind::TCPError
+            | ErrorKind::TLSError
+            | ErrorKind::NoValidWorkerError
+            | ErrorKind::Unavailable => Status::Unavailable,
+            ErrorKind::Unauthenticated => Status::Unauthenticated,
+            ErrorKind::StorageConflict => Status::Aborted,
+            _ => Status::Internal,
+        }
+    }
+}
+
+/// Coarse classification of error kinds after the gRPC status codes, for
+/// clients to handle failed requests without knowing every `ErrorKind`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Status {
+    /// The request is malformed, whatever the state of the system.
+    InvalidArgument,
+    /// The request did not complete before its deadline.
+    DeadlineExceeded,
+    /// The requested entity does not exist.
+    NotFound,
+    /// The user is not allowed to perform the request.
+    PermissionDenied,
+    /// The client sent too many requests or connections.
+    ResourceExhausted,
+    /// The request conflicted with a concurrent change. It may succeed if
+    /// made again from the current state.
+    Aborted,
+    /// A bug or an unexpected failure of the service.
+    Internal,
+    /// The service cannot be reached for now.
+    Unavailable,
+    /// The credentials of the request are invalid.
+    Unauthenticated,
+}
+
+impl Status {
+    /// Stable numeric code of the status, the same as the gRPC one.
+    pub fn code(self) -> u32 {
+        match self {
+            Status::InvalidArgument => 3,
+            Status::DeadlineExceeded => 4,
+            Status::NotFound => 5,
+            Status::PermissionDenied => 7,
+            Status::ResourceExhausted => 8,
+            Status::Aborted => 10,
+            Status::Internal => 13,
+            Status::Unavailable => 14,
+            Status::Unauthenticated => 16,
+        }
+    }
+
+    /// Whether a request failing with this status may succeed if sent again
+    /// unchanged.
+    pub fn is_retryable(self) -> bool {
+        match self {
+            Status::Unavailable | Status::ResourceExhausted => true,
+            _ => false,
+        }
+    }
 }
 
 impl From<ErrorKind> for Error {
@@ -268,6 +470,16 @@ impl From<u32> for Error {
             0x0000_1013 => ErrorKind::MsgSizeLimitExceedError,
             0x0000_1014 => ErrorKind::MesaPyError,
             0x0000_1015 => ErrorKind::RpcProtocolError,
+            0x0000_1016 => ErrorKind::TimedOut,
+            0x0000_1017 => ErrorKind::DataIntegrityError,
+            0x0000_1018 => ErrorKind::TooManyConnections,
+            0x0000_1019 => ErrorKind::RateLimited,
+            0x0000_101a => ErrorKind::DeadlineExceeded,
+            0x0000_101b => ErrorKind::NotFound,
+            0x0000_101c => ErrorKind::Unauthenticated,
+            0x0000_101d => ErrorKind::HandlerPanicked,
+            0x0000_101e => ErrorKind::StorageConflict,
+            0x0000_101f => ErrorKind::Unavailable,
             _ => ErrorKind::Unknown,
         };
 
@@ -317,6 +529,16 @@ impl Into<u32> for Error {
             ErrorKind::MsgSizeLimitExceedError => 0x0000_1013,
             ErrorKind::MesaPyError => 0x0000_1014,
             ErrorKind::RpcProtocolError => 0x0000_1015,
+            ErrorKind::TimedOut => 0x0000_1016,
+            ErrorKind::DataIntegrityError => 0x0000_1017,
+            ErrorKind::TooManyConnections => 0x0000_1018,
+            ErrorKind::RateLimited => 0x0000_1019,
+            ErrorKind::DeadlineExceeded => 0x0000_101a,
+            ErrorKind::NotFound => 0x0000_101b,
+            ErrorKind::Unauthenticated => 0x0000_101c,
+            ErrorKind::HandlerPanicked => 0x0000_101d,
+            ErrorKind::StorageConflict => 0x0000_101e,
+            ErrorKind::Unavailable => 0x0000_101f,
             ErrorKind::Unknown => 0xffff_ffff,
         }
     }
@@ -336,41 +558,112 @@ impl Error {
         }
     }
 
+    /// An error for the problems `errors` with the fields of a request. Its
+    /// kind is the code of the problems if they all have the same one, and
+    /// `InvalidInputError` otherwise.
+    pub fn from_field_errors(errors: Vec<FieldError>) -> Error {
+        let kind = match errors.first() {
+            Some(first) if errors.iter().all(|error| error.code == first.code) => first.code,
+            _ => ErrorKind::InvalidInputError,
+        };
+        Error {
+            repr: Repr::Invalid(kind, errors),
+        }
+    }
+
+    /// The problems with the fields of the request, empty unless the error
+    /// was made from them.
+    pub fn field_errors(&self) -> &[FieldError] {
+        match self.repr {
+            Repr::Invalid(_, ref errors) => errors,
+            _ => &[],
+        }
+    }
+
+    /// An error of a request which may be retried after `retry_after`.
+    pub fn with_retry_after(kind: ErrorKind, retry_after: Duration) -> Error {
+        let millis = retry_after.as_millis() as u64;
+        Error {
+            repr: Repr::RetryAfter(kind, millis),
+        }
+    }
+
+    /// When the failed request may be retried, if the server told.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self.repr {
+            Repr::RetryAfter(_, millis) => Some(Duration::from_millis(millis)),
+            _ => None,
+        }
+    }
+
     pub fn get_ref(&self) -> Option<&(dyn std::error::Error + Send + Sync + 'static)> {
         match self.repr {
-            Repr::Simple(..) => None,
             Repr::Custom(ref c) => Some(&*c.error),
+            _ => None,
         }
     }
 
     pub fn get_mut(&mut self) -> Option<&mut (dyn std::error::Error + Send + Sync + 'static)> {
         match self.repr {
-            Repr::Simple(..) => None,
             Repr::Custom(ref mut c) => Some(&mut *c.error),
+            _ => None,
         }
     }
 
     pub fn into_inner(self) -> Option<Box<dyn std::error::Error + Send + Sync>> {
         match self.repr {
-            Repr::Simple(..) => None,
             Repr::Custom(c) => Some(c.error),
+            _ => None,
         }
     }
 
+    // The detail of internal errors may reveal the state of the enclave and
+    // is not kept.
     pub fn into_simple_error(self) -> Error {
         match self.repr {
-            Repr::Simple(_) => self,
-            Repr::Custom(c) => Error::from(c.kind),
+            Repr::Simple(_) | Repr::RetryAfter(..) | Repr::Detailed(..) => self,
+            Repr::Invalid(kind, errors) => {
+                let errors = errors
+                    .into_iter()
+                    .map(|error| match error.code.status() {
+                        Status::Internal => FieldError {
+                            message: error.code.as_str().to_owned(),
+                            ..error
+                        },
+                        _ => error,
+                    })
+                    .collect();
+                Error {
+                    repr: Repr::Invalid(kind, errors),
+                }
+            }
+            Repr::Custom(c) => match c.kind.status() {
+                Status::Internal => Error::from(c.kind),
+                _ => Error {
+                    repr: Repr::Detailed(c.kind, c.error.to_string()),
+                },
+            },
         }
     }
 
     pub fn kind(&self) -> ErrorKind {
         match self.repr {
             Repr::Custom(ref c) => c.kind,
-            Repr::Simple(kind) => kind,
+            Repr::Simple(kind)
+            | Repr::RetryAfter(kind, _)
+            | Repr::Detailed(kind, _)
+            | Repr::Invalid(kind, _) => kind,
         }
     }
 
+    pub fn status(&self) -> Status {
+        self.kind().status()
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        self.status().is_retryable()
+    }
+
     pub fn unknown() -> Error {
         Error::from(ErrorKind::Unknown)
     }
@@ -381,6 +674,21 @@ impl fmt::Debug for Repr {
         match *self {
             Repr::Custom(ref c) => fmt::Debug::fmt(&c, fmt),
             Repr::Simple(kind) => fmt.debug_tuple("Kind").field(&kind).finish(),
+            Repr::RetryAfter(kind, millis) => fmt
+                .debug_struct("RetryAfter")
+                .field("kind", &kind)
+                .field("millis", &millis)
+                .finish(),
+            Repr::Detailed(kind, ref detail) => fmt
+                .debug_struct("Detailed")
+                .field("kind", &kind)
+                .field("detail", detail)
+                .finish(),
+            Repr::Invalid(kind, ref errors) => fmt
+                .debug_struct("Invalid")
+                .field("kind", &kind)
+                .field("errors", errors)
+                .finish(),
         }
     }
 }
@@ -390,6 +698,20 @@ impl fmt::Display for Error {
         match self.repr {
             Repr::Custom(ref c) => c.error.fmt(fmt),
             Repr::Simple(kind) => write!(fmt, "{}", kind.as_str()),
+            Repr::RetryAfter(kind, millis) => {
+                write!(fmt, "{}, retry after {} ms", kind.as_str(), millis)
+            }
+            Repr::Detailed(_, ref detail) => write!(fmt, "{}", detail),
+            // Flattened for logs
+            Repr::Invalid(_, ref errors) => {
+                for (index, error) in errors.iter().enumerate() {
+                    if index > 0 {
+                        write!(fmt, "; ")?;
+                    }
+                    write!(fmt, "{}", error)?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -397,8 +719,8 @@ impl fmt::Display for Error {
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self.repr {
-            Repr::Simple(..) => None,
             Repr::Custom(ref c) => c.error.source(),
+            _ => None,
         }
     }
 }
@@ -406,7 +728,14 @@ impl std::error::Error for Error {
 impl From<io::Error> for Error {
     #[inline]
     fn from(err: io::Error) -> Error {
-        Error::new(ErrorKind::IoError, err)
+        match err.kind() {
+            // A read or write timeout on a socket is reported as `WouldBlock`
+            // on Unix and `TimedOut` on Windows.
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => {
+                Error::new(ErrorKind::TimedOut, err)
+            }
+            _ => Error::new(ErrorKind::IoError, err),
+        }
     }
 }
 
@@ -469,6 +798,21 @@ impl From<teaclave_utils::UtilsError> for Error {
     }
 }
 
+impl From<teaclave_utils::EnclaveInfoError> for Error {
+    #[inline]
+    fn from(err: teaclave_utils::EnclaveInfoError) -> Error {
+        fn kind(err: &teaclave_utils::EnclaveInfoError) -> ErrorKind {
+            match err {
+                teaclave_utils::EnclaveInfoError::Signature { .. } => ErrorKind::CryptoError,
+                teaclave_utils::EnclaveInfoError::Fragment { error, .. } => kind(error),
+                teaclave_utils::EnclaveInfoError::Duplicate(_) => ErrorKind::InvalidInputError,
+                _ => ErrorKind::ParseError,
+            }
+        }
+        Error::new(kind(&err), err)
+    }
+}
+
 #[repr(u32)]
 #[derive(Copy, Clone, PartialEq, Eq, Ord, PartialOrd, Debug)]
 pub enum SgxStatus {
@@ -500,9 +844,10 @@ impl std::error::Error for SgxStatus {
 
 #[cfg(test)]
 mod test {
-    use super::{Custom, Error, ErrorKind, Repr};
+    use super::{Custom, Error, ErrorKind, FieldErrors, Repr, Status};
     use std::boxed::Box;
     use std::fmt;
+    use std::time::Duration;
 
     #[test]
     fn test_debug_error() {
@@ -548,4 +893,84 @@ mod test {
         let extracted = err.into_inner().unwrap();
         extracted.downcast::<TestError>().unwrap();
     }
+
+    #[test]
+    fn test_retry_after_survives_serialization() {
+        let err = Error::with_retry_after(ErrorKind::RateLimited, Duration::from_millis(1500));
+        let err = err.into_simple_error();
+        let serialized = serde_json::to_string(&err).unwrap();
+        let deserialized: Error = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized.kind(), ErrorKind::RateLimited);
+        assert_eq!(
+            deserialized.retry_after(),
+            Some(Duration::from_millis(1500))
+        );
+        assert!(Error::from(ErrorKind::RateLimited).retry_after().is_none());
+    }
+
+    #[test]
+    fn test_detail_survives_serialization() {
+        let err = Error::new(ErrorKind::InvalidInputError, "missing input port `model`");
+        let err = err.into_simple_error();
+        let serialized = serde_json::to_string(&err).unwrap();
+        let deserialized: Error = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized.status(), Status::InvalidArgument);
+        assert_eq!(deserialized.to_string(), "missing input port `model`");
+
+        let err = Error::new(ErrorKind::CryptoError, "bad key 0123");
+        let err = err.into_simple_error();
+        assert_eq!(err.status(), Status::Internal);
+        assert_eq!(err.to_string(), "crypto error");
+    }
+
+    #[test]
+    fn test_field_errors() {
+        let mut nested = FieldErrors::new();
+        nested.push("file_id", Error::from(ErrorKind::NotFound));
+        let mut errors = FieldErrors::new();
+        errors.check::<()>("inputs[2]", nested.into_result());
+        assert_eq!(errors.check("priority", Ok(3)), Some(3));
+        errors.push(
+            "labels",
+            Error::new(ErrorKind::InvalidInputError, "too many labels"),
+        );
+        errors.push(
+            "output_key",
+            Error::new(ErrorKind::CryptoError, "bad key 0123"),
+        );
+        let err = errors.into_result().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInputError);
+        assert_eq!(err.field_errors().len(), 3);
+        assert_eq!(err.field_errors()[0].field_path, "inputs[2].file_id");
+        assert_eq!(err.field_errors()[0].code, ErrorKind::NotFound);
+        assert_eq!(
+            err.to_string(),
+            "inputs[2].file_id: NotFound: not found; \
+             labels: InvalidInputError: too many labels; \
+             output_key: CryptoError: bad key 0123"
+        );
+
+        // The detail of internal errors is not sent
+        let serialized = serde_json::to_string(&err.into_simple_error()).unwrap();
+        let deserialized: Error = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized.field_errors().len(), 3);
+        assert_eq!(deserialized.field_errors()[2].message, "crypto error");
+
+        let mut errors = FieldErrors::new();
+        errors.push("files[0]", Error::from(ErrorKind::PermissionDenied));
+        errors.push("files[1]", Error::from(ErrorKind::PermissionDenied));
+        let err = errors.into_result().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        assert!(FieldErrors::new().into_result().is_ok());
+    }
+
+    #[test]
+    fn test_status_codes() {
+        assert_eq!(ErrorKind::NotFound.status().code(), 5);
+        assert_eq!(ErrorKind::Unauthenticated.status().code(), 16);
+        assert!(Error::from(ErrorKind::TCPError).is_retryable());
+        assert!(Error::from(ErrorKind::RateLimited).is_retryable());
+        assert!(!Error::from(ErrorKind::PermissionDenied).is_retryable());
+        assert!(!Error::from(ErrorKind::DeadlineExceeded).is_retryable());
+    }
 }
@@ -0,0 +1,149 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Health and identity of a service enclave, as reported by its `HealthCheck`,
+//! `GetEnclaveMeasurement` and `GetVersionInfo` requests.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use lazy_static::lazy_static;
+use serde_derive::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::SystemTime;
+#[cfg(feature = "mesalock_sgx")]
+use std::untrusted::time::SystemTimeEx;
+use teaclave_config::build_config::BUILD_CONFIG;
+
+lazy_static! {
+    static ref STARTED_AT: SystemTime = SystemTime::now();
+}
+
+// Set once all dependencies have been up
+static WARMED_UP: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceStatus {
+    /// Some dependencies have not been up since the enclave started.
+    Starting,
+    Ready,
+    /// Some dependencies went down after the service was ready.
+    Degraded,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct DependencyHealth {
+    pub name: String,
+    pub up: bool,
+}
+
+impl DependencyHealth {
+    pub fn new(name: &str, up: bool) -> Self {
+        DependencyHealth {
+            name: name.to_owned(),
+            up,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ServiceHealth {
+    pub status: ServiceStatus,
+    pub uptime_secs: u64,
+    pub dependencies: Vec<DependencyHealth>,
+}
+
+impl ServiceHealth {
+    /// Health of this enclave given the current state of its dependencies.
+    pub fn current(dependencies: Vec<DependencyHealth>) -> Self {
+        let status = if dependencies.iter().all(|dependency| dependency.up) {
+            WARMED_UP.store(true, Ordering::SeqCst);
+            ServiceStatus::Ready
+        } else if WARMED_UP.load(Ordering::SeqCst) {
+            ServiceStatus::Degraded
+        } else {
+            ServiceStatus::Starting
+        };
+        let uptime_secs = SystemTime::now()
+            .duration_since(*STARTED_AT)
+            .map(|uptime| uptime.as_secs())
+            .unwrap_or(0);
+
+        ServiceHealth {
+            status,
+            uptime_secs,
+            dependencies,
+        }
+    }
+}
+
+/// Identity of a running enclave, with the measurements hex-encoded as in the
+/// enclave info.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct EnclaveIdentity {
+    pub mr_enclave: String,
+    pub mr_signer: String,
+    pub isv_svn: u16,
+    pub isv_prod_id: u16,
+}
+
+impl EnclaveIdentity {
+    pub fn new(mr_enclave: &[u8], mr_signer: &[u8], isv_svn: u16, isv_prod_id: u16) -> Self {
+        EnclaveIdentity {
+            mr_enclave: to_hex(mr_enclave),
+            mr_signer: to_hex(mr_signer),
+            isv_svn,
+            isv_prod_id,
+        }
+    }
+
+    /// Identity of this enclave, read from the report endorsed in the RA
+    /// certificate presented to its peers.
+    #[cfg(feature = "mesalock_sgx")]
+    pub fn current() -> crate::Result<Self> {
+        crate::rpc::sgx::enclave_identity()
+    }
+}
+
+/// Version of the code running in an enclave, embedded at build time. The
+/// same version is recorded in the enclave info next to the measurements.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct VersionInfo {
+    pub crate_version: String,
+    pub build_id: String,
+    pub sgx_sdk_version: String,
+}
+
+impl VersionInfo {
+    pub fn current() -> Self {
+        VersionInfo {
+            crate_version: BUILD_CONFIG.crate_version.to_owned(),
+            build_id: BUILD_CONFIG.build_id.to_owned(),
+            sgx_sdk_version: BUILD_CONFIG.sgx_sdk_version.to_owned(),
+        }
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Records the start of the enclave, from which the uptime is counted.
+pub fn mark_started() {
+    lazy_static::initialize(&STARTED_AT);
+}
@@ -21,6 +21,8 @@ pub enum ECallCommand {
     FinalizeEnclave,
     RunFunctionalTest,
     ServeConnection,
+    GetMetrics,
+    RunMaintenance,
     Unimplemented,
 }
 
@@ -32,6 +34,8 @@ impl From<u32> for ECallCommand {
             0x0000_1002 => ECallCommand::FinalizeEnclave,
             0x0000_1003 => ECallCommand::RunFunctionalTest,
             0x0000_1004 => ECallCommand::ServeConnection,
+            0x0000_1005 => ECallCommand::GetMetrics,
+            0x0000_1006 => ECallCommand::RunMaintenance,
             _ => ECallCommand::Unimplemented,
         }
     }
@@ -45,6 +49,8 @@ impl Into<u32> for ECallCommand {
             ECallCommand::FinalizeEnclave => 0x0000_1002,
             ECallCommand::RunFunctionalTest => 0x0000_1003,
             ECallCommand::ServeConnection => 0x0000_1004,
+            ECallCommand::GetMetrics => 0x0000_1005,
+            ECallCommand::RunMaintenance => 0x0000_1006,
             ECallCommand::Unimplemented => 0xffff_ffff,
         }
     }
@@ -16,6 +16,8 @@
 // under the License.
 
 // Insert std prelude in the top for the sgx feature
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
 
 use cfg_if::cfg_if;
 use serde_derive::{Deserialize, Serialize};
@@ -53,11 +55,35 @@ pub struct RunFunctionalTestOutput {
 pub struct ServeConnectionInput {
     pub socket_fd: c_int,
     pub port: u16,
+    // Set when the listener has no room for the connection, which is then
+    // answered with `TooManyConnections` and closed.
+    #[serde(default)]
+    pub reject: bool,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug, Default)]
 pub struct ServeConnectionOutput;
 
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct GetMetricsInput;
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct GetMetricsOutput {
+    /// Metrics in the Prometheus text exposition format
+    pub metrics: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct RunMaintenanceInput {
+    /// Whether the housekeeping which runs less often is due as well, e.g.
+    /// deleting expired tasks
+    #[serde(default)]
+    pub sweep: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct RunMaintenanceOutput;
+
 impl RunFunctionalTestOutput {
     pub fn new(failed_count: usize) -> RunFunctionalTestOutput {
         RunFunctionalTestOutput { failed_count }
@@ -66,6 +92,24 @@ impl RunFunctionalTestOutput {
 
 impl ServeConnectionInput {
     pub fn new(socket_fd: c_int, port: u16) -> ServeConnectionInput {
-        ServeConnectionInput { socket_fd, port }
+        ServeConnectionInput {
+            socket_fd,
+            port,
+            reject: false,
+        }
+    }
+
+    pub fn new_rejected(socket_fd: c_int, port: u16) -> ServeConnectionInput {
+        ServeConnectionInput {
+            socket_fd,
+            port,
+            reject: true,
+        }
+    }
+}
+
+impl GetMetricsOutput {
+    pub fn new(metrics: String) -> GetMetricsOutput {
+        GetMetricsOutput { metrics }
     }
 }
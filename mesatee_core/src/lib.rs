@@ -29,15 +29,26 @@ extern crate log;
 #[cfg(feature = "mesalock_sgx")]
 extern crate ring;
 
+pub mod content_id;
+pub mod content_type;
 pub mod db;
+pub mod enclave_size;
+pub mod encryption_context;
+pub mod health;
+pub mod logging;
+pub mod metrics;
 pub mod rpc; // Syntax sugar for monadic error handling, defined in mayfail.rs
+pub mod worker_pool;
 
 // MesaTEE Error is defined in error.rs
 mod error;
 pub use error::EnclaveStatus;
 pub use error::Error;
 pub use error::ErrorKind;
+pub use error::FieldError;
+pub use error::FieldErrors;
 pub use error::Result;
+pub use error::Status;
 pub use error::UntrustedStatus;
 
 #[cfg(feature = "ipc")]
@@ -56,10 +67,19 @@ pub mod config;
 
 #[cfg(feature = "mesalock_sgx")]
 pub fn init_service(name: &str) -> Result<()> {
+    init_service_with_hints(name, &enclave_size::EnclaveSize::default())
+}
+
+// Initializes the service like `init_service`, and warns about the enclave
+// sizes below `size_hints`.
+#[cfg(feature = "mesalock_sgx")]
+pub fn init_service_with_hints(name: &str, size_hints: &enclave_size::EnclaveSize) -> Result<()> {
     use std::backtrace;
-    env_logger::init();
+    logging::init();
+    health::mark_started();
 
     debug!("Enclave [{}]: Initializing...", name);
+    enclave_size::check(name, size_hints);
 
     if backtrace::enable_backtrace(format!("{}.signed.so", name), backtrace::PrintFormat::Full)
         .is_err()
@@ -71,7 +91,21 @@ pub fn init_service(name: &str) -> Result<()> {
         error!("Runtime config is not initialized");
         return Err(Error::from(ErrorKind::ECallError));
     }
-    crate::rpc::sgx::prelude()?;
+    if !config::is_enclave_info_loaded() {
+        error!("Enclave info is not loaded");
+        return Err(Error::from(ErrorKind::ECallError));
+    }
+    if config::runtime_config().server.catch_panics {
+        rpc::panic::install_hook();
+    }
+    if let Err(e) = crate::rpc::sgx::TlsPolicy::from_runtime_config() {
+        error!("Enclave [{}]: Invalid TLS config: {}", name, e);
+        return Err(e);
+    }
+    if let Err(e) = crate::rpc::sgx::prelude(name) {
+        error!("Enclave [{}]: Attestation generation failed: {}", name, e);
+        return Err(e);
+    }
 
     Ok(())
 }
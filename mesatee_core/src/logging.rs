@@ -0,0 +1,86 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Request correlation for log lines.
+//!
+//! The trusted RPC server assigns every incoming request an ID and records it
+//! for the thread handling the request. Log lines emitted on that thread
+//! while the request is handled are prefixed with `[req=<id>]`.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use std::cell::RefCell;
+use std::io::Write;
+use uuid::Uuid;
+
+thread_local! {
+    static REQUEST_ID: RefCell<Option<String>> = RefCell::new(None);
+}
+
+/// Returns the ID of the request handled by the current thread.
+pub fn request_id() -> Option<String> {
+    REQUEST_ID.with(|id| id.borrow().clone())
+}
+
+/// Marks the current thread as handling a request until dropped.
+pub struct RequestScope {
+    previous: Option<String>,
+}
+
+impl RequestScope {
+    /// Enters a request with a newly generated ID.
+    pub fn enter() -> Self {
+        Self::enter_with_id(Uuid::new_v4().to_simple().to_string())
+    }
+
+    pub fn enter_with_id(id: String) -> Self {
+        let previous = REQUEST_ID.with(|current| current.replace(Some(id)));
+        RequestScope { previous }
+    }
+}
+
+impl Drop for RequestScope {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        REQUEST_ID.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+/// Initializes the logger from `RUST_LOG`, adding the request ID to log lines
+/// emitted while a request is handled.
+pub fn init() {
+    env_logger::Builder::from_default_env()
+        .format(|buf, record| match request_id() {
+            Some(id) => writeln!(
+                buf,
+                "[{} {}] [req={}] {}",
+                record.level(),
+                record.target(),
+                id,
+                record.args()
+            ),
+            None => writeln!(
+                buf,
+                "[{} {}] {}",
+                record.level(),
+                record.target(),
+                record.args()
+            ),
+        })
+        .init();
+}
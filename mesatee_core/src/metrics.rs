@@ -0,0 +1,376 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Prometheus-style metrics of a service enclave.
+//!
+//! RPC calls and remote attestations are counted inside the enclave, as are
+//! the sizes of requests as decoded and of responses before they are encoded,
+//! into buckets in bytes of `metrics.size_buckets`. The host reads the
+//! counters in the Prometheus text format through the `GetMetrics` ecall.
+//! Without the `metrics` feature nothing is recorded and the rendered text is
+//! empty.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+/// Measures the latency and message sizes of one RPC call.
+pub struct RpcTimer {
+    #[cfg(feature = "metrics")]
+    inner: registry::Timer,
+}
+
+impl RpcTimer {
+    /// Starts timing a call whose request is `request_bytes` bytes long.
+    #[allow(unused_variables)]
+    pub fn start(service: &'static str, method: String, request_bytes: usize) -> Self {
+        RpcTimer {
+            #[cfg(feature = "metrics")]
+            inner: registry::Timer::start(service, method, request_bytes),
+        }
+    }
+
+    /// Records the call with its latency, whether it succeeded and the size
+    /// of its response.
+    #[allow(unused_variables)]
+    pub fn finish(self, ok: bool, response_bytes: usize) {
+        #[cfg(feature = "metrics")]
+        self.inner.finish(ok, response_bytes);
+    }
+}
+
+/// Records a panic of the handler of a `method` request of `service`.
+#[allow(unused_variables)]
+pub fn observe_panic(service: &'static str, method: &str) {
+    #[cfg(feature = "metrics")]
+    registry::observe_panic(service, method);
+}
+
+/// Records the result of generating and endorsing an attestation report.
+#[allow(unused_variables)]
+pub fn observe_attestation(ok: bool) {
+    #[cfg(feature = "metrics")]
+    registry::observe_attestation(ok);
+}
+
+/// Renders all metrics in the Prometheus text exposition format.
+#[cfg(feature = "metrics")]
+pub fn render() -> String {
+    registry::render()
+}
+
+#[cfg(not(feature = "metrics"))]
+pub fn render() -> String {
+    String::new()
+}
+
+#[cfg(feature = "metrics")]
+mod registry {
+    #[cfg(feature = "mesalock_sgx")]
+    use std::prelude::v1::*;
+
+    use crate::config;
+    use lazy_static::lazy_static;
+    use std::collections::BTreeMap;
+    use std::fmt::Write;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::time::{Duration, SystemTime};
+
+    #[cfg(not(feature = "mesalock_sgx"))]
+    use std::sync::Mutex;
+    #[cfg(feature = "mesalock_sgx")]
+    use std::sync::SgxMutex as Mutex;
+    #[cfg(feature = "mesalock_sgx")]
+    use std::untrusted::time::SystemTimeEx;
+    use teaclave_config::runtime_config::MetricsConfig;
+
+    const BUCKET_COUNT: usize = 10;
+    // Upper bounds in seconds of the latency histogram buckets
+    const LATENCY_BUCKETS: [f64; BUCKET_COUNT] =
+        [0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 60.0];
+
+    #[derive(Default)]
+    struct RpcStats {
+        calls: u64,
+        errors: u64,
+        panics: u64,
+        // Cumulative counts of calls within each bucket of LATENCY_BUCKETS
+        buckets: [u64; BUCKET_COUNT],
+        latency_sum: f64,
+        request_sizes: SizeHistogram,
+        response_sizes: SizeHistogram,
+    }
+
+    #[derive(Default)]
+    struct SizeHistogram {
+        // Cumulative counts of messages within each bucket of SIZE_BUCKETS
+        buckets: Vec<u64>,
+        sum: u64,
+        count: u64,
+    }
+
+    impl SizeHistogram {
+        fn observe(&mut self, bytes: usize) {
+            let bytes = bytes as u64;
+            self.buckets.resize(SIZE_BUCKETS.len(), 0);
+            for (count, bound) in self.buckets.iter_mut().zip(SIZE_BUCKETS.iter()) {
+                if bytes <= *bound {
+                    *count += 1;
+                }
+            }
+            self.sum += bytes;
+            self.count += 1;
+        }
+    }
+
+    lazy_static! {
+        // Keyed by service, then method
+        static ref RPC_STATS: Mutex<BTreeMap<&'static str, BTreeMap<String, RpcStats>>> =
+            Mutex::new(BTreeMap::new());
+        // Upper bounds in bytes of the size histogram buckets
+        static ref SIZE_BUCKETS: Vec<u64> = if config::is_runtime_config_initialized() {
+            config::runtime_config().metrics.size_buckets.clone()
+        } else {
+            MetricsConfig::default().size_buckets
+        };
+    }
+
+    static ATTESTATION_SUCCESSES: AtomicU64 = AtomicU64::new(0);
+    static ATTESTATION_FAILURES: AtomicU64 = AtomicU64::new(0);
+
+    pub(super) struct Timer {
+        service: &'static str,
+        method: String,
+        start: SystemTime,
+        request_bytes: usize,
+    }
+
+    impl Timer {
+        pub(super) fn start(service: &'static str, method: String, request_bytes: usize) -> Self {
+            Timer {
+                service,
+                method,
+                start: SystemTime::now(),
+                request_bytes,
+            }
+        }
+
+        pub(super) fn finish(self, ok: bool, response_bytes: usize) {
+            let latency = self.start.elapsed().unwrap_or_default();
+            let sizes = (self.request_bytes, response_bytes);
+            observe_rpc(self.service, self.method, latency, ok, sizes);
+        }
+    }
+
+    // `sizes` are the sizes in bytes of the request and of the response.
+    fn observe_rpc(
+        service: &'static str,
+        method: String,
+        latency: Duration,
+        ok: bool,
+        sizes: (usize, usize),
+    ) {
+        let mut services = match RPC_STATS.lock() {
+            Ok(services) => services,
+            Err(_) => return,
+        };
+        let stats = services
+            .entry(service)
+            .or_insert_with(BTreeMap::new)
+            .entry(method)
+            .or_insert_with(RpcStats::default);
+
+        let seconds = latency.as_secs() as f64 + f64::from(latency.subsec_nanos()) / 1e9;
+        stats.calls += 1;
+        if !ok {
+            stats.errors += 1;
+        }
+        for (count, bound) in stats.buckets.iter_mut().zip(LATENCY_BUCKETS.iter()) {
+            if seconds <= *bound {
+                *count += 1;
+            }
+        }
+        stats.latency_sum += seconds;
+        if !SIZE_BUCKETS.is_empty() {
+            stats.request_sizes.observe(sizes.0);
+            stats.response_sizes.observe(sizes.1);
+        }
+    }
+
+    pub(super) fn observe_panic(service: &'static str, method: &str) {
+        if let Ok(mut services) = RPC_STATS.lock() {
+            services
+                .entry(service)
+                .or_insert_with(BTreeMap::new)
+                .entry(method.to_owned())
+                .or_insert_with(RpcStats::default)
+                .panics += 1;
+        }
+    }
+
+    pub(super) fn observe_attestation(ok: bool) {
+        let counter = if ok {
+            &ATTESTATION_SUCCESSES
+        } else {
+            &ATTESTATION_FAILURES
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn render() -> String {
+        let mut out = String::new();
+        if let Ok(services) = RPC_STATS.lock() {
+            let calls = services.iter().flat_map(|(service, methods)| {
+                methods
+                    .iter()
+                    .map(move |(method, stats)| (*service, method, stats))
+            });
+
+            out.push_str("# HELP rpc_requests_total RPC requests handled.\n");
+            out.push_str("# TYPE rpc_requests_total counter\n");
+            for (service, method, stats) in calls.clone() {
+                let _ = writeln!(
+                    out,
+                    "rpc_requests_total{{service=\"{}\",method=\"{}\"}} {}",
+                    service, method, stats.calls
+                );
+            }
+
+            out.push_str("# HELP rpc_errors_total RPC requests answered with an error.\n");
+            out.push_str("# TYPE rpc_errors_total counter\n");
+            for (service, method, stats) in calls.clone() {
+                let _ = writeln!(
+                    out,
+                    "rpc_errors_total{{service=\"{}\",method=\"{}\"}} {}",
+                    service, method, stats.errors
+                );
+            }
+
+            out.push_str("# HELP rpc_panics_total RPC handlers which panicked.\n");
+            out.push_str("# TYPE rpc_panics_total counter\n");
+            for (service, method, stats) in calls.clone() {
+                let _ = writeln!(
+                    out,
+                    "rpc_panics_total{{service=\"{}\",method=\"{}\"}} {}",
+                    service, method, stats.panics
+                );
+            }
+
+            out.push_str("# HELP rpc_handler_duration_seconds Latency of RPC handlers.\n");
+            out.push_str("# TYPE rpc_handler_duration_seconds histogram\n");
+            for (service, method, stats) in calls {
+                let labels = format!("service=\"{}\",method=\"{}\"", service, method);
+                for (count, bound) in stats.buckets.iter().zip(LATENCY_BUCKETS.iter()) {
+                    let _ = writeln!(
+                        out,
+                        "rpc_handler_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                        labels, bound, count
+                    );
+                }
+                let _ = writeln!(
+                    out,
+                    "rpc_handler_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+                    labels, stats.calls
+                );
+                let _ = writeln!(
+                    out,
+                    "rpc_handler_duration_seconds_sum{{{}}} {}",
+                    labels, stats.latency_sum
+                );
+                let _ = writeln!(
+                    out,
+                    "rpc_handler_duration_seconds_count{{{}}} {}",
+                    labels, stats.calls
+                );
+            }
+
+            if !SIZE_BUCKETS.is_empty() {
+                render_sizes(
+                    &mut out,
+                    "rpc_request_size_bytes",
+                    "Sizes of RPC requests as decoded.",
+                    calls_with(&services, |stats| &stats.request_sizes),
+                );
+                render_sizes(
+                    &mut out,
+                    "rpc_response_size_bytes",
+                    "Sizes of RPC responses before they are encoded.",
+                    calls_with(&services, |stats| &stats.response_sizes),
+                );
+            }
+        }
+
+        out.push_str("# HELP attestation_total Attestation reports generated for the enclave.\n");
+        out.push_str("# TYPE attestation_total counter\n");
+        let _ = writeln!(
+            out,
+            "attestation_total{{result=\"success\"}} {}",
+            ATTESTATION_SUCCESSES.load(Ordering::Relaxed)
+        );
+        let _ = writeln!(
+            out,
+            "attestation_total{{result=\"failure\"}} {}",
+            ATTESTATION_FAILURES.load(Ordering::Relaxed)
+        );
+        out
+    }
+
+    // Size histograms `histogram` of the methods of all services, labelled.
+    fn calls_with<'a, F>(
+        services: &'a BTreeMap<&'static str, BTreeMap<String, RpcStats>>,
+        histogram: F,
+    ) -> Vec<(String, &'a SizeHistogram)>
+    where
+        F: Fn(&RpcStats) -> &SizeHistogram,
+    {
+        let mut calls = Vec::new();
+        for (service, methods) in services.iter() {
+            for (method, stats) in methods.iter() {
+                let labels = format!("service=\"{}\",method=\"{}\"", service, method);
+                calls.push((labels, histogram(stats)));
+            }
+        }
+        calls
+    }
+
+    fn render_sizes(
+        out: &mut String,
+        name: &str,
+        help: &str,
+        calls: Vec<(String, &SizeHistogram)>,
+    ) {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        for (labels, histogram) in calls {
+            // Histograms of calls not yet finished have no buckets
+            for (i, bound) in SIZE_BUCKETS.iter().enumerate() {
+                let count = histogram.buckets.get(i).cloned().unwrap_or(0);
+                let _ = writeln!(
+                    out,
+                    "{}_bucket{{{},le=\"{}\"}} {}",
+                    name, labels, bound, count
+                );
+            }
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{},le=\"+Inf\"}} {}",
+                name, labels, histogram.count
+            );
+            let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, histogram.sum);
+            let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, histogram.count);
+        }
+    }
+}
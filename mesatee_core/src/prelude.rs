@@ -32,6 +32,10 @@ cfg_if! {
             FinalizeEnclaveOutput,
             ServeConnectionInput,
             ServeConnectionOutput,
+            GetMetricsInput,
+            GetMetricsOutput,
+            RunMaintenanceInput,
+            RunMaintenanceOutput,
         };
         pub use crate::ipc_attribute::handle_ecall;
     } else if #[cfg(all(not(feature = "mesalock_sgx"), feature = "ipc"))] {
@@ -40,6 +44,10 @@ cfg_if! {
         pub use crate::ipc::protos::ecall::{
             ServeConnectionInput,
             ServeConnectionOutput,
+            GetMetricsInput,
+            GetMetricsOutput,
+            RunMaintenanceInput,
+            RunMaintenanceOutput,
         };
 
     } else if #[cfg(all(not(feature = "mesalock_sgx"), not(feature = "ipc")))] {
@@ -0,0 +1,299 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Roles required to call the methods of a service, and access to objects
+//! granted by relationships.
+//!
+//! Users are granted roles, and a method may require one. A method requiring
+//! no role may be called by any user, the owner checks of its handler
+//! aside. The user is the one whose identity the caller already verified,
+//! e.g. by an interceptor checking the user token.
+//!
+//! Access to an object beyond what its owner shared may be granted by the
+//! relationships of the user and the object, e.g. to a file owned by a group
+//! the user belongs to. An `AccessEvaluator` decides from the relationships,
+//! typically a remote access control service, and positive decisions are
+//! cached for a short while. Access is never granted when the evaluator
+//! fails.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use crate::{Error, ErrorKind, Result};
+use std::collections::{HashMap, HashSet};
+#[cfg(not(feature = "mesalock_sgx"))]
+use std::sync::Mutex;
+#[cfg(feature = "mesalock_sgx")]
+use std::sync::SgxMutex as Mutex;
+use std::time::{Duration, SystemTime};
+#[cfg(feature = "mesalock_sgx")]
+use std::untrusted::time::SystemTimeEx;
+
+#[derive(Clone, Debug, Default)]
+pub struct RolePolicy {
+    // Roles of each user.
+    user_roles: HashMap<String, HashSet<String>>,
+    // Role required by each method, keyed by method name.
+    method_roles: HashMap<String, String>,
+}
+
+impl RolePolicy {
+    pub fn new() -> Self {
+        RolePolicy::default()
+    }
+
+    /// Grants `role` to `users`.
+    pub fn grant<S: AsRef<str>>(mut self, role: &str, users: &[S]) -> Self {
+        for user_id in users {
+            self.user_roles
+                .entry(user_id.as_ref().to_owned())
+                .or_insert_with(HashSet::new)
+                .insert(role.to_owned());
+        }
+        self
+    }
+
+    /// Requires `role` to call `method`, replacing the role it required.
+    pub fn require(mut self, method: &str, role: &str) -> Self {
+        self.method_roles.insert(method.to_owned(), role.to_owned());
+        self
+    }
+
+    pub fn has_role(&self, user_id: &str, role: &str) -> bool {
+        self.user_roles
+            .get(user_id)
+            .map_or(false, |roles| roles.contains(role))
+    }
+
+    /// Role required to call `method`, if any.
+    pub fn required_role(&self, method: &str) -> Option<&str> {
+        self.method_roles.get(method).map(|role| role.as_str())
+    }
+
+    /// Fails with `PermissionDenied`, naming the missing role, unless
+    /// `user_id` has the role `method` requires.
+    pub fn check(&self, method: &str, user_id: &str) -> Result<()> {
+        match self.required_role(method) {
+            Some(role) if !self.has_role(user_id, role) => Err(Error::new(
+                ErrorKind::PermissionDenied,
+                format!("{} requires the {} role", method, role),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Access a user asks for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Access {
+    ReadData,
+    UseFunction,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AccessQuery {
+    pub user_id: String,
+    pub access: Access,
+    // ID of the file or function.
+    pub object_id: String,
+}
+
+impl AccessQuery {
+    pub fn new(user_id: &str, access: Access, object_id: &str) -> Self {
+        AccessQuery {
+            user_id: user_id.to_owned(),
+            access,
+            object_id: object_id.to_owned(),
+        }
+    }
+}
+
+pub trait AccessEvaluator: Send + Sync {
+    /// Whether the relationships of the user and the object grant the
+    /// access.
+    fn evaluate(&self, query: &AccessQuery) -> Result<bool>;
+}
+
+/// Evaluates queries with `E`, remembering the granted ones for `ttl`.
+pub struct CachedAccessEvaluator<E: AccessEvaluator> {
+    evaluator: E,
+    ttl: Duration,
+    capacity: usize,
+    // Expiry of the cached grants.
+    granted: Mutex<HashMap<AccessQuery, SystemTime>>,
+}
+
+impl<E: AccessEvaluator> CachedAccessEvaluator<E> {
+    /// Caches up to `capacity` grants for `ttl` each. A zero `ttl` or
+    /// `capacity` disables the cache.
+    pub fn new(evaluator: E, ttl: Duration, capacity: usize) -> Self {
+        CachedAccessEvaluator {
+            evaluator,
+            ttl,
+            capacity,
+            granted: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Fails with `PermissionDenied` unless the access is granted. A failed
+    /// evaluation denies the access, and its error is returned so that the
+    /// caller may tell an unavailable evaluator from a denial.
+    pub fn check(&self, query: &AccessQuery) -> Result<()> {
+        let now = SystemTime::now();
+        if let Some(expires_at) = self.granted.lock()?.get(query) {
+            if *expires_at > now {
+                return Ok(());
+            }
+        }
+        match self.evaluator.evaluate(query) {
+            Ok(true) => {
+                self.remember(query, now)?;
+                Ok(())
+            }
+            Ok(false) => Err(Error::new(
+                ErrorKind::PermissionDenied,
+                format!("{:?} of {} is denied", query.access, query.object_id),
+            )),
+            Err(e) => {
+                warn!("Cannot evaluate the access of {}: {}", query.user_id, e);
+                Err(e)
+            }
+        }
+    }
+
+    fn remember(&self, query: &AccessQuery, now: SystemTime) -> Result<()> {
+        if self.capacity == 0 || self.ttl == Duration::from_secs(0) {
+            return Ok(());
+        }
+        let mut granted = self.granted.lock()?;
+        if granted.len() >= self.capacity {
+            granted.retain(|_, expires_at| *expires_at > now);
+        }
+        if granted.len() < self.capacity {
+            granted.insert(query.clone(), now + self.ttl);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    // Grants the users of group `staff` the files owned by the group, and
+    // fails once `available` is cleared.
+    struct StubEvaluator {
+        evaluations: AtomicUsize,
+        available: AtomicBool,
+    }
+
+    impl StubEvaluator {
+        fn new() -> Self {
+            StubEvaluator {
+                evaluations: AtomicUsize::new(0),
+                available: AtomicBool::new(true),
+            }
+        }
+    }
+
+    impl AccessEvaluator for StubEvaluator {
+        fn evaluate(&self, query: &AccessQuery) -> Result<bool> {
+            self.evaluations.fetch_add(1, Ordering::SeqCst);
+            if !self.available.load(Ordering::SeqCst) {
+                return Err(Error::from(ErrorKind::ConnectionRefused));
+            }
+            let staff = ["alice", "bob"];
+            let staff_files = ["report"];
+            Ok(query.access == Access::ReadData
+                && staff.contains(&query.user_id.as_str())
+                && staff_files.contains(&query.object_id.as_str()))
+        }
+    }
+
+    fn policy() -> RolePolicy {
+        RolePolicy::new()
+            .grant("admin", &["alice"])
+            .grant("auditor", &["alice", "bob"])
+            .require("RevokeUser", "admin")
+            .require("QueryAuditLog", "auditor")
+    }
+
+    #[test]
+    fn test_admin_method_is_denied_to_non_admins() {
+        let policy = policy();
+        assert!(policy.check("RevokeUser", "alice").is_ok());
+        let err = policy.check("RevokeUser", "bob").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        assert!(err.to_string().contains("admin"));
+        assert!(policy.check("RevokeUser", "mallory").is_err());
+    }
+
+    #[test]
+    fn test_methods_without_role_are_open() {
+        let policy = policy();
+        assert!(policy.check("QueryAuditLog", "bob").is_ok());
+        assert!(policy.check("QueryAuditLog", "mallory").is_err());
+        assert!(policy.check("Create", "mallory").is_ok());
+        assert_eq!(policy.required_role("Create"), None);
+    }
+
+    #[test]
+    fn test_require_replaces_the_role() {
+        let policy = policy().require("RevokeUser", "auditor");
+        assert!(policy.check("RevokeUser", "bob").is_ok());
+        assert!(policy.has_role("alice", "admin"));
+        assert!(!policy.has_role("bob", "admin"));
+    }
+
+    #[test]
+    fn test_relationships_grant_and_deny_access() {
+        let evaluator =
+            CachedAccessEvaluator::new(StubEvaluator::new(), Duration::from_secs(60), 8);
+        let granted = AccessQuery::new("alice", Access::ReadData, "report");
+        assert!(evaluator.check(&granted).is_ok());
+
+        let outsider = AccessQuery::new("mallory", Access::ReadData, "report");
+        let err = evaluator.check(&outsider).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        let function = AccessQuery::new("alice", Access::UseFunction, "report");
+        assert!(evaluator.check(&function).is_err());
+    }
+
+    #[test]
+    fn test_only_grants_are_cached() {
+        let evaluator =
+            CachedAccessEvaluator::new(StubEvaluator::new(), Duration::from_secs(60), 8);
+        let granted = AccessQuery::new("bob", Access::ReadData, "report");
+        let denied = AccessQuery::new("mallory", Access::ReadData, "report");
+        for _ in 0..3 {
+            assert!(evaluator.check(&granted).is_ok());
+            assert!(evaluator.check(&denied).is_err());
+        }
+        assert_eq!(evaluator.evaluator.evaluations.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn test_unavailable_evaluator_denies_access() {
+        let evaluator = CachedAccessEvaluator::new(StubEvaluator::new(), Duration::from_secs(0), 8);
+        let query = AccessQuery::new("alice", Access::ReadData, "report");
+        assert!(evaluator.check(&query).is_ok());
+        evaluator.evaluator.available.store(false, Ordering::SeqCst);
+        let err = evaluator.check(&query).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ConnectionRefused);
+    }
+}
@@ -19,14 +19,38 @@
 #[cfg(feature = "mesalock_sgx")]
 use std::prelude::v1::*;
 
-use crate::rpc::sgx;
+use crate::config::{OutboundDesc, TargetDesc};
+use crate::rpc::circuit_breaker::CircuitBreaker;
+use crate::rpc::compression::Algorithm;
+use crate::rpc::deadline;
+use crate::rpc::sgx::{self, TlsPolicy};
 use crate::rpc::RpcClient;
-use crate::Result;
+use crate::{Error, ErrorKind, Result};
 use net2::TcpBuilder;
 use serde::{de::DeserializeOwned, Serialize};
+#[cfg(not(feature = "mesalock_sgx"))]
+use std::sync::Mutex;
+#[cfg(feature = "mesalock_sgx")]
+use std::sync::SgxMutex as Mutex;
+use std::time::{Duration, SystemTime};
+#[cfg(feature = "mesalock_sgx")]
+use std::untrusted::time::SystemTimeEx;
 
 use teaclave_attestation::verifier::EnclaveAttr;
-use teaclave_attestation::verifier::SgxQuoteVerifier;
+use teaclave_attestation::verifier::{default_quote_verifier, QuoteVerifier, SgxQuoteVerifier};
+
+/// Timeouts of a trusted channel. `None` means blocking indefinitely.
+///
+/// `handshake` bounds each socket read/write of the attested TLS handshake,
+/// which includes transferring and verifying the peer's attestation report.
+/// `request` bounds each socket read/write of a request made afterwards, so a
+/// slow handshake never consumes the time budget of a request and vice versa.
+/// A request exceeding its timeout fails with `ErrorKind::TimedOut`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ChannelTimeouts {
+    pub handshake: Option<Duration>,
+    pub request: Option<Duration>,
+}
 
 pub struct SgxTrustedChannel<U: Serialize, V: DeserializeOwned> {
     client: sgx::PipeClient<U, V>,
@@ -40,6 +64,50 @@ where
     pub fn new(
         addr: std::net::SocketAddr,
         enclave_attr: EnclaveAttr,
+    ) -> Result<SgxTrustedChannel<U, V>> {
+        Self::with_timeouts(addr, enclave_attr, ChannelTimeouts::default())
+    }
+
+    pub fn with_timeouts(
+        addr: std::net::SocketAddr,
+        enclave_attr: EnclaveAttr,
+        timeouts: ChannelTimeouts,
+    ) -> Result<SgxTrustedChannel<U, V>> {
+        Self::with_tls_policy(addr, enclave_attr, timeouts, TlsPolicy::runtime())
+    }
+
+    /// Same as `with_timeouts`, overriding the TLS policy of the runtime
+    /// config.
+    pub fn with_tls_policy(
+        addr: std::net::SocketAddr,
+        enclave_attr: EnclaveAttr,
+        timeouts: ChannelTimeouts,
+        tls_policy: TlsPolicy,
+    ) -> Result<SgxTrustedChannel<U, V>> {
+        let server_verifier = SgxQuoteVerifier::new(enclave_attr);
+        Self::with_verifier(addr, server_verifier, timeouts, tls_policy)
+    }
+
+    /// Same as `with_tls_policy`, verifying the server with a custom quote
+    /// verifier.
+    pub fn with_verifier(
+        addr: std::net::SocketAddr,
+        server_verifier: SgxQuoteVerifier,
+        timeouts: ChannelTimeouts,
+        tls_policy: TlsPolicy,
+    ) -> Result<SgxTrustedChannel<U, V>> {
+        Self::with_compression(addr, server_verifier, timeouts, tls_policy, Algorithm::None)
+    }
+
+    /// Same as `with_verifier`, offering the server to compress payloads
+    /// with `compression`. Payloads are sent uncompressed if the server does
+    /// not accept it.
+    pub fn with_compression(
+        addr: std::net::SocketAddr,
+        server_verifier: SgxQuoteVerifier,
+        timeouts: ChannelTimeouts,
+        tls_policy: TlsPolicy,
+        compression: Algorithm,
     ) -> Result<SgxTrustedChannel<U, V>> {
         let tcp_builder = TcpBuilder::new_v4()?;
         tcp_builder.reuse_address(true)?;
@@ -53,7 +121,11 @@ where
             )
             .unwrap()
             .to_owned(),
-            server_verifier: SgxQuoteVerifier::new(enclave_attr),
+            server_verifier,
+            tls_policy,
+            handshake_timeout: timeouts.handshake,
+            timeout: timeouts.request,
+            compression,
         };
         let client = sgx::PipeClient::<U, V>::open(config)?;
 
@@ -63,4 +135,397 @@ where
     pub fn invoke(&mut self, input: U) -> Result<V> {
         self.client.invoke(input)
     }
+
+    /// Checks that the server still answers on the connection, within the
+    /// request timeout.
+    pub fn heartbeat(&mut self) -> Result<()> {
+        self.client.heartbeat()
+    }
+}
+
+/// Controls how a pool retries a request failing with a retryable status,
+/// i.e. `Unavailable` when the connection to the target is broken, e.g. after
+/// the target restarts, or `ResourceExhausted` when the target is busy.
+///
+/// A request is attempted at most `max_attempts` times. Before the n-th
+/// retry the pool waits `base_delay * 2^(n-1)`, or longer if the target told
+/// when to retry. Retries after a broken connection run a fresh attested TLS
+/// handshake.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32, base_delay: Duration) -> Self {
+        RetryPolicy {
+            max_attempts: std::cmp::max(max_attempts, 1),
+            base_delay,
+        }
+    }
+
+    /// Surface errors immediately.
+    pub fn never() -> Self {
+        RetryPolicy::new(1, Duration::from_secs(0))
+    }
+
+    pub(crate) fn delay(&self, retry: u32) -> Duration {
+        // Cap the exponent so that the delay cannot overflow.
+        self.base_delay * 2u32.pow(std::cmp::min(retry.saturating_sub(1), 16))
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy::new(3, Duration::from_millis(100))
+    }
+}
+
+fn is_connection_error(err: &Error) -> bool {
+    match err.kind() {
+        ErrorKind::IoError | ErrorKind::TCPError | ErrorKind::TLSError => true,
+        _ => false,
+    }
+}
+
+// Same as the default lifetime of endorsed attestation reports, after which
+// the peer enclave presents a new report.
+const DEFAULT_CHANNEL_MAX_AGE: Duration = Duration::from_secs(86400);
+
+struct PooledChannel<U: Serialize, V: DeserializeOwned> {
+    channel: SgxTrustedChannel<U, V>,
+    created_at: SystemTime,
+    last_used: SystemTime,
+}
+
+/// A pool of trusted channels to the same target enclave.
+///
+/// Channels are established lazily and returned to the pool after a
+/// request. A channel whose connection breaks is dropped, and channels
+/// older than `max_age` are re-established so that the peer is attested again
+/// before its report expires. At most `size` idle channels are kept; requests
+/// beyond that use temporary channels. Failed requests are retried according
+/// to the pool's `RetryPolicy`.
+///
+/// With a `keepalive` interval, a channel idle for longer is probed with a
+/// heartbeat before it is used, and `keep_alive` probes all such channels,
+/// replacing the dead ones, so that a connection dropped while idle fails no
+/// request.
+///
+/// With a circuit breaker, requests fail at once with `Unavailable` while the
+/// target is failing, see `rpc::circuit_breaker`. A request counts as failed
+/// once its retries are exhausted.
+///
+/// With `resolve_target`, the target is resolved again for every channel, so
+/// that new channels verify the target against the enclave info as reloaded
+/// since the pool was created.
+pub struct SgxTrustedChannelPool<U: Serialize, V: DeserializeOwned> {
+    target: TargetDesc,
+    resolve_target: Option<fn() -> TargetDesc>,
+    size: usize,
+    max_age: Duration,
+    retry_policy: RetryPolicy,
+    timeouts: ChannelTimeouts,
+    tls_policy: TlsPolicy,
+    quote_verifier: QuoteVerifier,
+    compression: Algorithm,
+    keepalive: Option<Duration>,
+    circuit_breaker: Option<CircuitBreaker>,
+    idle: Mutex<Vec<PooledChannel<U, V>>>,
+}
+
+impl<U, V> SgxTrustedChannelPool<U, V>
+where
+    U: Serialize + Clone,
+    V: DeserializeOwned,
+{
+    pub fn new(target: TargetDesc, size: usize) -> Self {
+        SgxTrustedChannelPool {
+            target,
+            resolve_target: None,
+            size: std::cmp::max(size, 1),
+            max_age: DEFAULT_CHANNEL_MAX_AGE,
+            retry_policy: RetryPolicy::default(),
+            timeouts: ChannelTimeouts::default(),
+            tls_policy: TlsPolicy::runtime(),
+            quote_verifier: default_quote_verifier(),
+            compression: Algorithm::None,
+            keepalive: None,
+            circuit_breaker: None,
+            idle: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Resolves the target with `resolve`, e.g. `Internal::target_tdfs`, for
+    /// every new channel instead of once.
+    pub fn resolve_target(mut self, resolve: fn() -> TargetDesc) -> Self {
+        self.resolve_target = Some(resolve);
+        self
+    }
+
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeouts.request = Some(timeout);
+        self
+    }
+
+    pub fn handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.timeouts.handshake = Some(timeout);
+        self
+    }
+
+    pub fn tls_policy(mut self, tls_policy: TlsPolicy) -> Self {
+        self.tls_policy = tls_policy;
+        self
+    }
+
+    /// Overrides the quote verifier of the build config for the target.
+    pub fn quote_verifier(mut self, verifier: QuoteVerifier) -> Self {
+        self.quote_verifier = verifier;
+        self
+    }
+
+    /// Offers the target to compress payloads with `algorithm`. Compression
+    /// is off by default, see `rpc::compression` for the tradeoffs.
+    pub fn compression(mut self, algorithm: Algorithm) -> Self {
+        self.compression = algorithm;
+        self
+    }
+
+    /// Probes channels idle for `interval` with a heartbeat. Off by default.
+    pub fn keepalive(mut self, interval: Duration) -> Self {
+        self.keepalive = Some(interval);
+        self
+    }
+
+    /// Opens the circuit breaker of the pool after `failure_threshold` failed
+    /// requests in a row, for `cool_down`. Off by default.
+    pub fn circuit_breaker(mut self, failure_threshold: u32, cool_down: Duration) -> Self {
+        self.circuit_breaker = Some(CircuitBreaker::new(failure_threshold, cool_down));
+        self
+    }
+
+    /// Probes the channels idle for the keepalive interval, and replaces the
+    /// ones whose heartbeat is not answered, reconnecting with the backoff of
+    /// the retry policy. Meant to be called periodically, does nothing
+    /// without a keepalive interval.
+    pub fn keep_alive(&self) -> Result<()> {
+        if self.keepalive.is_none() {
+            return Ok(());
+        }
+        let channels: Vec<PooledChannel<U, V>> = match self.idle.lock() {
+            Ok(mut idle) => idle.drain(..).collect(),
+            Err(_) => return Ok(()),
+        };
+        let mut dead = 0;
+        for mut pooled in channels.into_iter() {
+            if self.is_fresh(&pooled) && self.probe_if_idle(&mut pooled) {
+                self.release(pooled);
+            } else {
+                dead += 1;
+            }
+        }
+        if dead > 0 {
+            warn!(
+                "Reconnecting {} idle channels to {}",
+                dead, self.target.addr
+            );
+        }
+        for _ in 0..dead {
+            let pooled = self.reconnect()?;
+            self.release(pooled);
+        }
+        Ok(())
+    }
+
+    /// Checks that a channel to the target is up, establishing one if the
+    /// pool has no idle channels.
+    pub fn check_connection(&self) -> Result<()> {
+        let pooled = self.acquire()?;
+        self.release(pooled);
+        Ok(())
+    }
+
+    pub fn invoke(&self, input: U) -> Result<V> {
+        match self.circuit_breaker {
+            Some(ref breaker) => breaker.call(|| self.invoke_with_retries(input)),
+            None => self.invoke_with_retries(input),
+        }
+    }
+
+    fn invoke_with_retries(&self, input: U) -> Result<V> {
+        let mut attempt = 1;
+        loop {
+            let err = match self.try_invoke(input.clone()) {
+                Ok(output) => return Ok(output),
+                Err(err) => err,
+            };
+            if !err.is_retryable() || attempt >= self.retry_policy.max_attempts {
+                return Err(err);
+            }
+
+            // Servers which are busy may tell when to retry
+            let delay = match err.retry_after() {
+                Some(retry_after) => std::cmp::max(retry_after, self.retry_policy.delay(attempt)),
+                None => self.retry_policy.delay(attempt),
+            };
+            // No retry can complete before the deadline of the request being
+            // handled
+            if let Some(deadline) = deadline::current() {
+                if deadline.remaining() <= delay {
+                    return Err(Error::from(ErrorKind::DeadlineExceeded));
+                }
+            }
+            warn!(
+                "Request to {} failed: {}, retrying in {:?} (attempt {}/{})",
+                self.target.addr,
+                err,
+                delay,
+                attempt + 1,
+                self.retry_policy.max_attempts
+            );
+            std::thread::sleep(delay);
+            attempt += 1;
+        }
+    }
+
+    fn try_invoke(&self, input: U) -> Result<V> {
+        let mut pooled = self.acquire()?;
+        let result = pooled.channel.invoke(input);
+        pooled.last_used = SystemTime::now();
+        match result {
+            Ok(output) => {
+                self.release(pooled);
+                Ok(output)
+            }
+            // The connection is dropped so that the next attempt reconnects,
+            // servers close the connections they have no room for.
+            // After a timeout the stream may still carry a late response, so
+            // it cannot be reused either.
+            Err(err)
+                if is_connection_error(&err)
+                    || err.kind() == ErrorKind::TooManyConnections
+                    || err.kind() == ErrorKind::TimedOut
+                    || err.kind() == ErrorKind::DeadlineExceeded =>
+            {
+                Err(err)
+            }
+            Err(err) => {
+                self.release(pooled);
+                Err(err)
+            }
+        }
+    }
+
+    fn acquire(&self) -> Result<PooledChannel<U, V>> {
+        // Take the lock only to pop a channel, connecting happens outside it.
+        loop {
+            let pooled = match self.idle.lock() {
+                Ok(mut idle) => idle.pop(),
+                Err(_) => None,
+            };
+            match pooled {
+                Some(mut pooled) if self.is_fresh(&pooled) => {
+                    if self.probe_if_idle(&mut pooled) {
+                        return Ok(pooled);
+                    }
+                }
+                Some(_) => continue,
+                None => break,
+            }
+        }
+        self.connect()
+    }
+
+    fn connect(&self) -> Result<PooledChannel<U, V>> {
+        let target = match self.resolve_target {
+            Some(resolve) => resolve(),
+            None => self.target.clone(),
+        };
+        let channel = match target.desc {
+            OutboundDesc::Sgx(ref enclave_attr) => SgxTrustedChannel::with_compression(
+                target.addr,
+                SgxQuoteVerifier::with_verifier(enclave_attr.clone(), self.quote_verifier),
+                self.timeouts,
+                self.tls_policy.clone(),
+                self.compression,
+            )?,
+        };
+        let now = SystemTime::now();
+        Ok(PooledChannel {
+            channel,
+            created_at: now,
+            last_used: now,
+        })
+    }
+
+    // Connects like a request is retried after a broken connection.
+    fn reconnect(&self) -> Result<PooledChannel<U, V>> {
+        let mut attempt = 1;
+        loop {
+            match self.connect() {
+                Ok(pooled) => return Ok(pooled),
+                Err(err) if attempt < self.retry_policy.max_attempts => {
+                    let delay = self.retry_policy.delay(attempt);
+                    warn!(
+                        "Connecting to {} failed: {}, retrying in {:?}",
+                        self.target.addr, err, delay
+                    );
+                    std::thread::sleep(delay);
+                    attempt += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    // Whether the channel is alive, probing it with a heartbeat if it has
+    // been idle for the keepalive interval.
+    fn probe_if_idle(&self, pooled: &mut PooledChannel<U, V>) -> bool {
+        let interval = match self.keepalive {
+            Some(interval) => interval,
+            None => return true,
+        };
+        let idle = SystemTime::now()
+            .duration_since(pooled.last_used)
+            .unwrap_or(interval);
+        if idle < interval {
+            return true;
+        }
+        match pooled.channel.heartbeat() {
+            Ok(()) => {
+                pooled.last_used = SystemTime::now();
+                true
+            }
+            Err(err) => {
+                debug!("Idle channel to {} is dead: {}", self.target.addr, err);
+                false
+            }
+        }
+    }
+
+    fn release(&self, pooled: PooledChannel<U, V>) {
+        if let Ok(mut idle) = self.idle.lock() {
+            if idle.len() < self.size {
+                idle.push(pooled);
+            }
+        }
+    }
+
+    fn is_fresh(&self, pooled: &PooledChannel<U, V>) -> bool {
+        match SystemTime::now().duration_since(pooled.created_at) {
+            Ok(age) => age < self.max_age,
+            Err(_) => false,
+        }
+    }
 }
@@ -0,0 +1,230 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Circuit breaker of the requests to a failing target.
+//!
+//! The breaker is closed while the target answers. After
+//! `failure_threshold` requests in a row fail because the target is
+//! unreachable or too slow, i.e. with the `Unavailable` or
+//! `DeadlineExceeded` status, it opens: requests fail at once with
+//! `Unavailable` for `cool_down`, instead of occupying the threads making
+//! them until they time out. It then half-opens and lets one request through
+//! to probe the target, which closes the breaker if it is answered and opens
+//! it again otherwise. Requests made while the probe is in flight fail at
+//! once too.
+//!
+//! Any answer of the target, including an error such as `NotFound`, counts
+//! as a success.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use crate::{Error, ErrorKind, Result, Status};
+#[cfg(not(feature = "mesalock_sgx"))]
+use std::sync::{Mutex, MutexGuard};
+#[cfg(feature = "mesalock_sgx")]
+use std::sync::{SgxMutex as Mutex, SgxMutexGuard as MutexGuard};
+use std::time::{Duration, SystemTime};
+#[cfg(feature = "mesalock_sgx")]
+use std::untrusted::time::SystemTimeEx;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum State {
+    Closed { failures: u32 },
+    Open { until: SystemTime },
+    // A probe made at `since` is in flight. A probe which does not report
+    // within the cool-down, e.g. because its thread panicked, is replaced.
+    HalfOpen { since: SystemTime },
+}
+
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cool_down: Duration,
+    state: Mutex<State>,
+}
+
+fn is_failure(err: &Error) -> bool {
+    match err.status() {
+        Status::Unavailable | Status::DeadlineExceeded => true,
+        _ => false,
+    }
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, cool_down: Duration) -> Self {
+        CircuitBreaker {
+            failure_threshold: std::cmp::max(failure_threshold, 1),
+            cool_down,
+            state: Mutex::new(State::Closed { failures: 0 }),
+        }
+    }
+
+    /// Makes the request `request` unless the breaker is open.
+    pub fn call<T, F>(&self, request: F) -> Result<T>
+    where
+        F: FnOnce() -> Result<T>,
+    {
+        self.admit(SystemTime::now())?;
+        let result = request();
+        let failed = match result {
+            Ok(_) => false,
+            Err(ref err) => is_failure(err),
+        };
+        self.record(failed, SystemTime::now());
+        result
+    }
+
+    /// Whether requests fail at once, without probing the target.
+    pub fn is_open(&self) -> bool {
+        match *self.lock() {
+            State::Closed { .. } => false,
+            State::Open { until } => SystemTime::now() < until,
+            State::HalfOpen { .. } => true,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    fn admit(&self, now: SystemTime) -> Result<()> {
+        let mut state = self.lock();
+        let probe_due = match *state {
+            State::Closed { .. } => return Ok(()),
+            State::Open { until } => until,
+            State::HalfOpen { since } => since + self.cool_down,
+        };
+        match probe_due.duration_since(now) {
+            Ok(remaining) if remaining > Duration::from_secs(0) => {
+                Err(Error::with_retry_after(ErrorKind::Unavailable, remaining))
+            }
+            _ => {
+                *state = State::HalfOpen { since: now };
+                Ok(())
+            }
+        }
+    }
+
+    fn record(&self, failed: bool, now: SystemTime) {
+        let mut state = self.lock();
+        *state = match (*state, failed) {
+            (_, false) => State::Closed { failures: 0 },
+            (State::Closed { failures }, true) if failures + 1 < self.failure_threshold => {
+                State::Closed {
+                    failures: failures + 1,
+                }
+            }
+            // Requests admitted before the breaker opened do not extend the
+            // cool-down.
+            (State::Open { until }, true) => State::Open { until },
+            (_, true) => State::Open {
+                until: now + self.cool_down,
+            },
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COOL_DOWN: Duration = Duration::from_secs(30);
+
+    #[test]
+    fn test_consecutive_failures_open_the_breaker() {
+        let breaker = CircuitBreaker::new(3, COOL_DOWN);
+        let now = SystemTime::now();
+        for _ in 0..2 {
+            breaker.admit(now).unwrap();
+            breaker.record(true, now);
+        }
+        // A success resets the count
+        breaker.record(false, now);
+        for _ in 0..2 {
+            breaker.admit(now).unwrap();
+            breaker.record(true, now);
+        }
+        assert!(!breaker.is_open());
+        breaker.admit(now).unwrap();
+        breaker.record(true, now);
+        assert!(breaker.is_open());
+
+        let err = breaker.admit(now).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Unavailable);
+        assert!(err.is_retryable());
+        assert_eq!(err.retry_after(), Some(COOL_DOWN));
+        let later = now + COOL_DOWN / 2;
+        let err = breaker.admit(later).unwrap_err();
+        assert_eq!(err.retry_after(), Some(COOL_DOWN / 2));
+    }
+
+    #[test]
+    fn test_breaker_recovers_after_the_cool_down() {
+        let breaker = CircuitBreaker::new(1, COOL_DOWN);
+        let now = SystemTime::now();
+        breaker.admit(now).unwrap();
+        breaker.record(true, now);
+        assert!(breaker.admit(now).is_err());
+
+        // One probe is let through after the cool-down
+        let probe_at = now + COOL_DOWN;
+        breaker.admit(probe_at).unwrap();
+        assert!(breaker.admit(probe_at).is_err());
+        // A failed probe opens the breaker again
+        breaker.record(true, probe_at);
+        assert!(breaker.admit(probe_at + COOL_DOWN / 2).is_err());
+
+        let probe_at = probe_at + COOL_DOWN;
+        breaker.admit(probe_at).unwrap();
+        breaker.record(false, probe_at);
+        assert!(!breaker.is_open());
+        breaker.admit(probe_at).unwrap();
+    }
+
+    #[test]
+    fn test_stuck_probe_is_replaced() {
+        let breaker = CircuitBreaker::new(1, COOL_DOWN);
+        let now = SystemTime::now();
+        breaker.record(true, now);
+        breaker.admit(now + COOL_DOWN).unwrap();
+        assert!(breaker.admit(now + COOL_DOWN).is_err());
+        breaker.admit(now + 2 * COOL_DOWN).unwrap();
+    }
+
+    #[test]
+    fn test_only_unreachable_targets_are_failures() {
+        let breaker = CircuitBreaker::new(2, COOL_DOWN);
+        for _ in 0..3 {
+            let result: Result<()> = breaker.call(|| Err(Error::from(ErrorKind::NotFound)));
+            assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+        }
+        assert!(!breaker.is_open());
+
+        let result: Result<()> = breaker.call(|| Err(Error::from(ErrorKind::ConnectionRefused)));
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::ConnectionRefused);
+        let result: Result<()> = breaker.call(|| Err(Error::from(ErrorKind::TimedOut)));
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::TimedOut);
+        assert!(breaker.is_open());
+
+        // Requests are not made while the breaker is open
+        let result: Result<()> = breaker.call(|| panic!("request made"));
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::Unavailable);
+    }
+}
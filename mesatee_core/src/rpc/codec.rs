@@ -0,0 +1,205 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Decoding of the frames a server receives.
+//!
+//! Peers are attested, but a buggy peer may still send malformed frames, so
+//! decoding must neither panic nor allocate far beyond the size of a frame.
+//! The length prefix of a frame is checked against the message size limit
+//! before the frame is read, and its buffer grows with the bytes actually
+//! received. The JSON of a request is then scanned for its nesting depth,
+//! the fields of each object and the number of arrays and objects, which
+//! bound the memory of the deserialized request, before it is deserialized.
+//! Malformed frames fail with `RpcProtocolError`, reported to clients as
+//! `InvalidArgument`.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use crate::rpc::sendrecv::receive_vec_within;
+use crate::{Error, ErrorKind, Result};
+use serde::de::DeserializeOwned;
+use std::io::Read;
+
+/// Deepest nesting of arrays and objects in a request.
+pub const MAX_DEPTH: usize = 64;
+/// Most fields of one object in a request.
+pub const MAX_OBJECT_FIELDS: usize = 4096;
+/// Most arrays and objects in a request. Arrays of numbers, such as byte
+/// buffers, are only bounded by the message size.
+pub const MAX_CONTAINERS: usize = 65536;
+
+/// Reads one frame of at most `max_len` bytes.
+pub fn read_frame<R: Read>(reader: &mut R, max_len: u64) -> Result<Vec<u8>> {
+    receive_vec_within(reader, max_len)
+}
+
+/// Checks that `payload` is within the bounds of a request. Syntax errors
+/// are left to the deserializer.
+pub fn validate(payload: &[u8]) -> Result<()> {
+    // Fields of each open object, or `None` for an array.
+    let mut open: Vec<Option<usize>> = Vec::new();
+    let mut containers = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for byte in payload.iter() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if *byte == b'\\' {
+                escaped = true;
+            } else if *byte == b'"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match *byte {
+            b'"' => in_string = true,
+            b'{' | b'[' => {
+                if open.len() == MAX_DEPTH {
+                    return Err(malformed(format!(
+                        "request is nested deeper than {} levels",
+                        MAX_DEPTH
+                    )));
+                }
+                containers += 1;
+                if containers > MAX_CONTAINERS {
+                    return Err(malformed(format!(
+                        "request has more than {} arrays and objects",
+                        MAX_CONTAINERS
+                    )));
+                }
+                open.push(if *byte == b'{' { Some(1) } else { None });
+            }
+            b'}' | b']' => {
+                if open.pop().is_none() {
+                    return Err(malformed("request closes an unopened container"));
+                }
+            }
+            b',' => {
+                if let Some(Some(fields)) = open.last_mut() {
+                    *fields += 1;
+                    if *fields > MAX_OBJECT_FIELDS {
+                        return Err(malformed(format!(
+                            "request has an object of more than {} fields",
+                            MAX_OBJECT_FIELDS
+                        )));
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// Validates and deserializes a request.
+pub fn decode<T: DeserializeOwned>(payload: &[u8]) -> Result<T> {
+    validate(payload)?;
+    serde_json::from_slice(payload).map_err(|e| malformed(e.to_string()))
+}
+
+fn malformed<M: Into<String>>(msg: M) -> Error {
+    Error::new(ErrorKind::RpcProtocolError, msg.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_derive::Deserialize;
+    use std::collections::HashMap;
+    use std::io::Cursor;
+
+    #[derive(Debug, Deserialize)]
+    #[serde(tag = "type")]
+    enum Request {
+        Put(PutRequest),
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct PutRequest {
+        key: String,
+        data: Vec<u8>,
+        labels: HashMap<String, String>,
+    }
+
+    fn frame(payload: &[u8]) -> Vec<u8> {
+        let mut frame = (payload.len() as u64).to_be_bytes().to_vec();
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    fn assert_malformed<T: std::fmt::Debug>(result: Result<T>) {
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::RpcProtocolError);
+    }
+
+    #[test]
+    fn test_well_formed_request_is_decoded() {
+        let payload = br#"{"type":"Put","key":"a\"]}","data":[1,2,3],"labels":{"x":"y"}}"#;
+        let frame = read_frame(&mut Cursor::new(frame(payload)), 1024).unwrap();
+        let request: Request = decode(&frame).unwrap();
+        let Request::Put(request) = request;
+        assert_eq!(request.key, "a\"]}");
+        assert_eq!(request.data, vec![1, 2, 3]);
+        assert_eq!(request.labels["x"], "y");
+    }
+
+    #[test]
+    fn test_malformed_requests_are_rejected() {
+        assert_malformed(decode::<Request>(b""));
+        assert_malformed(decode::<Request>(b"\xff\xfe"));
+        assert_malformed(decode::<Request>(br#"{"type":"Put","key":"a""#));
+        assert_malformed(decode::<Request>(br#"{"type":"Unknown"}"#));
+        assert_malformed(decode::<Request>(br#"{"type":"Put","data":[256]}"#));
+        assert_malformed(decode::<Request>(b"]]]]"));
+    }
+
+    #[test]
+    fn test_bounds_are_enforced() {
+        let deep = "[".repeat(MAX_DEPTH + 1) + &"]".repeat(MAX_DEPTH + 1);
+        assert_malformed(validate(deep.as_bytes()));
+        let shallow = "[".repeat(MAX_DEPTH) + &"]".repeat(MAX_DEPTH);
+        assert!(validate(shallow.as_bytes()).is_ok());
+
+        let fields: Vec<String> = (0..=MAX_OBJECT_FIELDS)
+            .map(|i| format!("\"{}\":0", i))
+            .collect();
+        let wide = format!("{{{}}}", fields.join(","));
+        assert_malformed(validate(wide.as_bytes()));
+
+        let many = format!("[{}]", vec!["[]"; MAX_CONTAINERS].join(","));
+        assert_malformed(validate(many.as_bytes()));
+        // Numbers are not containers
+        let bytes = format!("[{}]", vec!["0"; MAX_CONTAINERS].join(","));
+        assert!(validate(bytes.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn test_length_prefix_is_checked_before_allocation() {
+        // A huge announced length fails without a huge allocation
+        let mut huge = u64::max_value().to_be_bytes().to_vec();
+        huge.extend_from_slice(b"{}");
+        let err = read_frame(&mut Cursor::new(huge), 1024).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::MsgSizeLimitExceedError);
+
+        // A frame shorter than its length prefix is truncated
+        let mut truncated = 1000u64.to_be_bytes().to_vec();
+        truncated.extend_from_slice(b"{}");
+        assert!(read_frame(&mut Cursor::new(truncated), 1024).is_err());
+        assert!(read_frame(&mut Cursor::new(vec![0u8; 3]), 1024).is_err());
+    }
+}
@@ -0,0 +1,187 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+//! Optional compression of RPC payloads.
+//!
+//! Compression is negotiated once per connection. A client configured with
+//! an algorithm sends a hello frame before its first request, listing the
+//! algorithm it offers. The server replies with the algorithm it selected,
+//! which is `Algorithm::None` unless it is configured with the same one, and
+//! from then on every request and response payload is compressed with it.
+//! Clients without compression send no hello, so the framing of existing
+//! peers is unchanged.
+//!
+//! Payloads are compressed before they are written to the TLS session, so
+//! only compressed plaintext is encrypted. The length of each TLS record
+//! still reveals the compressed length of the payload, which depends on its
+//! content. A peer that can inject chosen data into a payload carrying a
+//! secret may thereby learn about the secret (as in the CRIME attack).
+//! Compression should therefore only be enabled for channels whose payloads
+//! do not mix attacker-controlled data with secrets, and is off by default.
+
+// Insert std prelude in the top for the sgx feature
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use std::io::{Read, Write};
+
+use crate::rpc::sendrecv::{receive_vec, send_vec};
+use crate::{Error, ErrorKind, Result};
+
+use teaclave_config::build_config::BUILD_CONFIG;
+
+// JSON payloads never start with a NUL byte, so the hello frame cannot be
+// mistaken for a request.
+const HELLO_MAGIC: &[u8] = b"\0teaclave-rpc-compression\0";
+
+/// Compression algorithm of RPC payloads.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Algorithm {
+    None,
+    Snappy,
+}
+
+impl Default for Algorithm {
+    fn default() -> Self {
+        Algorithm::None
+    }
+}
+
+impl Algorithm {
+    fn id(self) -> u8 {
+        match self {
+            Algorithm::None => 0,
+            Algorithm::Snappy => 1,
+        }
+    }
+
+    fn from_id(id: u8) -> Option<Algorithm> {
+        match id {
+            0 => Some(Algorithm::None),
+            1 => Some(Algorithm::Snappy),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn compress(self, payload: Vec<u8>) -> Result<Vec<u8>> {
+        match self {
+            Algorithm::None => Ok(payload),
+            Algorithm::Snappy => {
+                if payload.len() as u64 > BUILD_CONFIG.rpc_max_message_size {
+                    return Err(Error::from(ErrorKind::MsgSizeLimitExceedError));
+                }
+                snap::Encoder::new()
+                    .compress_vec(&payload)
+                    .map_err(|e| Error::new(ErrorKind::RpcProtocolError, e))
+            }
+        }
+    }
+
+    pub(crate) fn decompress(self, payload: Vec<u8>) -> Result<Vec<u8>> {
+        match self {
+            Algorithm::None => Ok(payload),
+            Algorithm::Snappy => {
+                // Check the announced length first, so that a small frame
+                // cannot expand beyond the message size limit.
+                let len = snap::decompress_len(&payload)
+                    .map_err(|e| Error::new(ErrorKind::RpcProtocolError, e))?;
+                if len as u64 > BUILD_CONFIG.rpc_max_message_size {
+                    return Err(Error::from(ErrorKind::MsgSizeLimitExceedError));
+                }
+                snap::Decoder::new()
+                    .decompress_vec(&payload)
+                    .map_err(|e| Error::new(ErrorKind::RpcProtocolError, e))
+            }
+        }
+    }
+}
+
+/// Returns the algorithms offered by a client if `frame` is a hello frame.
+pub(crate) fn parse_hello(frame: &[u8]) -> Option<Vec<Algorithm>> {
+    if !frame.starts_with(HELLO_MAGIC) {
+        return None;
+    }
+    // Unknown algorithms of a newer client are skipped.
+    let offered = frame[HELLO_MAGIC.len()..]
+        .iter()
+        .filter_map(|id| Algorithm::from_id(*id))
+        .collect();
+    Some(offered)
+}
+
+/// Selects the algorithm of the server if the client offered it.
+pub(crate) fn select(local: Algorithm, offered: &[Algorithm]) -> Algorithm {
+    if offered.contains(&local) {
+        local
+    } else {
+        Algorithm::None
+    }
+}
+
+/// Answers the hello frame of a client with the selected algorithm.
+pub(crate) fn accept<T>(sock: &mut T, selected: Algorithm) -> Result<()>
+where
+    T: Write,
+{
+    send_vec(sock, vec![selected.id()])
+}
+
+/// Negotiates the algorithm of a connection on the client side. Returns
+/// `Algorithm::None` without sending anything if `preferred` is `None`.
+pub(crate) fn negotiate<T>(sock: &mut T, preferred: Algorithm) -> Result<Algorithm>
+where
+    T: Read + Write,
+{
+    if preferred == Algorithm::None {
+        return Ok(Algorithm::None);
+    }
+
+    let mut hello = HELLO_MAGIC.to_vec();
+    hello.push(preferred.id());
+    send_vec(sock, hello)?;
+
+    let reply = receive_vec(sock)?;
+    match reply.as_slice() {
+        [id] => match Algorithm::from_id(*id) {
+            Some(selected) if selected == preferred || selected == Algorithm::None => Ok(selected),
+            _ => Err(Error::from(ErrorKind::RpcProtocolError)),
+        },
+        _ => Err(Error::from(ErrorKind::RpcProtocolError)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snappy_roundtrip() {
+        let payload = br#"{"type":"Get","key":"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"}"#.to_vec();
+        let compressed = Algorithm::Snappy.compress(payload.clone()).unwrap();
+        assert!(compressed.len() < payload.len());
+        let decompressed = Algorithm::Snappy.decompress(compressed).unwrap();
+        assert_eq!(decompressed, payload);
+    }
+
+    #[test]
+    fn test_select_falls_back_to_none() {
+        let offered = parse_hello(b"\0teaclave-rpc-compression\0\x01\x7f").unwrap();
+        assert_eq!(offered, vec![Algorithm::Snappy]);
+        assert_eq!(select(Algorithm::Snappy, &offered), Algorithm::Snappy);
+        assert_eq!(select(Algorithm::None, &offered), Algorithm::None);
+        assert!(parse_hello(br#"{"type":"Get"}"#).is_none());
+    }
+}
@@ -0,0 +1,112 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Deadlines of RPC requests.
+//!
+//! A client sends the time left until the deadline of a request in the
+//! request metadata, see `rpc::metadata`. The trusted server makes it the
+//! deadline of the thread handling the request, and requests sent by trusted
+//! channels on that thread carry what is left of it. Downstream calls thereby
+//! give up together with the client. Only the time left is sent, so the
+//! clocks of client and server need not agree.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use crate::{Error, ErrorKind, Result};
+use std::cell::Cell;
+use std::time::{Duration, SystemTime};
+#[cfg(feature = "mesalock_sgx")]
+use std::untrusted::time::SystemTimeEx;
+
+thread_local! {
+    static DEADLINE: Cell<Option<Deadline>> = Cell::new(None);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Deadline {
+    at: SystemTime,
+}
+
+impl Deadline {
+    pub fn after(timeout: Duration) -> Self {
+        Deadline {
+            at: SystemTime::now() + timeout,
+        }
+    }
+
+    /// Time left until the deadline, zero once it passed.
+    pub fn remaining(&self) -> Duration {
+        self.at
+            .duration_since(SystemTime::now())
+            .unwrap_or_else(|_| Duration::from_secs(0))
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.remaining() == Duration::from_secs(0)
+    }
+
+    /// Fails with `DeadlineExceeded` once the deadline passed.
+    pub fn check(&self) -> Result<()> {
+        if self.is_expired() {
+            return Err(Error::from(ErrorKind::DeadlineExceeded));
+        }
+        Ok(())
+    }
+}
+
+/// The earlier of two optional deadlines.
+pub fn earliest(a: Option<Deadline>, b: Option<Deadline>) -> Option<Deadline> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(std::cmp::min(a, b)),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+/// Returns the deadline of the request handled by the current thread.
+pub fn current() -> Option<Deadline> {
+    DEADLINE.with(|deadline| deadline.get())
+}
+
+/// Fails with `DeadlineExceeded` once the deadline of the request handled by
+/// the current thread passed. Long-running handlers call it between steps.
+pub fn check_current() -> Result<()> {
+    match current() {
+        Some(deadline) => deadline.check(),
+        None => Ok(()),
+    }
+}
+
+/// Sets the deadline of the current thread until dropped.
+pub struct DeadlineScope {
+    previous: Option<Deadline>,
+}
+
+impl DeadlineScope {
+    pub fn enter(deadline: Option<Deadline>) -> Self {
+        let previous = DEADLINE.with(|current| current.replace(deadline));
+        DeadlineScope { previous }
+    }
+}
+
+impl Drop for DeadlineScope {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        DEADLINE.with(|current| current.set(previous));
+    }
+}
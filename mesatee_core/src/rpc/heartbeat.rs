@@ -0,0 +1,59 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Heartbeats of idle connections.
+//!
+//! Intermediaries may silently drop connections which carry no traffic for a
+//! while, which a client would otherwise only discover on its next request.
+//! A client probes an idle connection by sending a heartbeat frame, which the
+//! server answers with the same frame without dispatching it to the service.
+//! A connection whose heartbeat is not answered is dead and is reconnected.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use crate::rpc::compression::Algorithm;
+use crate::rpc::sendrecv::{receive_vec, send_vec};
+use crate::{Error, ErrorKind, Result};
+use std::io::{Read, Write};
+
+// Like the metadata frame, it cannot be mistaken for a JSON request.
+const HEARTBEAT_MAGIC: &[u8] = b"\0teaclave-rpc-heartbeat\0";
+
+/// Whether `frame` is a heartbeat.
+pub(crate) fn is_heartbeat(frame: &[u8]) -> bool {
+    frame == HEARTBEAT_MAGIC
+}
+
+/// Answers a heartbeat received by a server.
+pub(crate) fn answer<S: Write>(sock: &mut S, compression: Algorithm) -> Result<()> {
+    send_vec(sock, compression.compress(HEARTBEAT_MAGIC.to_vec())?)
+}
+
+/// Sends a heartbeat and waits for the server to answer it, within the read
+/// timeout of the connection.
+pub(crate) fn probe<C: Read + Write>(client: &mut C, compression: Algorithm) -> Result<()> {
+    send_vec(client, compression.compress(HEARTBEAT_MAGIC.to_vec())?)?;
+    let answer = compression.decompress(receive_vec(client)?)?;
+    if !is_heartbeat(&answer) {
+        return Err(Error::new(
+            ErrorKind::RpcProtocolError,
+            "heartbeat is not answered",
+        ));
+    }
+    Ok(())
+}
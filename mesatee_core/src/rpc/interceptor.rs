@@ -0,0 +1,246 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Interceptors wrap the dispatch of every request of a server, for checks
+//! and bookkeeping shared by all the handlers of a service.
+//!
+//! Interceptors run in the order they are registered. Each one may reject a
+//! request in `before`, in which case the remaining interceptors and the
+//! handler are skipped and the error is sent as the response. The `after`
+//! hooks of the interceptors that let the request through then run in
+//! reverse order, and may rewrite the response.
+//!
+//! `InboundServices` is the allowlist of the enclaves an internal endpoint
+//! serves, checked for every request of the connection.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use crate::config::EnclaveAttr;
+use crate::rpc::deadline::Deadline;
+use crate::{Error, ErrorKind, Result};
+use std::sync::Arc;
+use teaclave_utils::decode_hex;
+
+/// Identity of the peer of a connection. The measurements are only known for
+/// attested peers, whose quotes are verified during the TLS handshake.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PeerIdentity {
+    pub addr: Option<String>,
+    // Hex encoded MRENCLAVE and MRSIGNER of the peer.
+    pub mr_enclave: Option<String>,
+    pub mr_signer: Option<String>,
+}
+
+/// What an interceptor knows about a request besides its content.
+pub struct RequestContext<'a> {
+    // Short type name of the service, e.g. `TMSExternalEnclave`
+    pub service: &'static str,
+    // Variant name of the request, e.g. `Create`, or `unknown`
+    pub method: &'a str,
+    pub peer: &'a PeerIdentity,
+    // Deadline sent by the client, if any
+    pub deadline: Option<Deadline>,
+}
+
+pub trait Interceptor<U, V> {
+    /// Called before the request is handled. An error rejects the request.
+    fn before(&self, _context: &RequestContext, _request: &U) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called with the response of every request let through by `before`.
+    fn after(&self, _context: &RequestContext, _result: &mut Result<V>) {}
+}
+
+/// Rejects requests with `PermissionDenied` unless the peer was attested as
+/// one of the enclaves of `enclave_attr`, e.g. the inbound services of an
+/// internal endpoint. Peers are matched as in the handshake, by MRSIGNER only
+/// in test mode, and are not attested in simulation mode.
+pub struct InboundServices {
+    enclave_attr: EnclaveAttr,
+}
+
+impl InboundServices {
+    pub fn new(enclave_attr: EnclaveAttr) -> Self {
+        InboundServices { enclave_attr }
+    }
+
+    fn allows(&self, peer: &PeerIdentity) -> bool {
+        let measure = |hex: &Option<String>| hex.as_ref().and_then(|hex| decode_hex(hex).ok());
+        match (measure(&peer.mr_enclave), measure(&peer.mr_signer)) {
+            (Some(mr_enclave), Some(mr_signer)) => {
+                self.enclave_attr.accepts(&mr_enclave, &mr_signer)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<U, V> Interceptor<U, V> for InboundServices {
+    fn before(&self, context: &RequestContext, _request: &U) -> Result<()> {
+        if cfg!(sgx_sim) || self.allows(context.peer) {
+            return Ok(());
+        }
+        debug!(
+            "Rejecting {} request of peer {:?}, not an inbound service of {}",
+            context.method, context.peer.mr_enclave, context.service
+        );
+        Err(Error::new(
+            ErrorKind::PermissionDenied,
+            "peer is not an inbound service",
+        ))
+    }
+}
+
+/// Runs `handle` on `request` wrapped by `interceptors`.
+pub(crate) fn intercept<U, V, F>(
+    interceptors: &[Arc<dyn Interceptor<U, V>>],
+    context: &RequestContext,
+    request: U,
+    handle: F,
+) -> Result<V>
+where
+    F: FnOnce(U) -> Result<V>,
+{
+    let mut passed = 0;
+    let mut rejection = None;
+    for interceptor in interceptors.iter() {
+        match interceptor.before(context, &request) {
+            Ok(()) => passed += 1,
+            Err(e) => {
+                rejection = Some(e);
+                break;
+            }
+        }
+    }
+    let mut result = match rejection {
+        Some(e) => Err(e),
+        None => handle(request),
+    };
+    for interceptor in interceptors[..passed].iter().rev() {
+        interceptor.after(context, &mut result);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Error, ErrorKind};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // Records its hooks in `log`, and rejects requests equal to `reject`.
+    struct Recorder {
+        name: &'static str,
+        reject: &'static str,
+        log: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl Interceptor<String, String> for Recorder {
+        fn before(&self, context: &RequestContext, request: &String) -> Result<()> {
+            self.log
+                .borrow_mut()
+                .push(format!("{} before {}", self.name, context.method));
+            if request == self.reject {
+                return Err(Error::from(ErrorKind::PermissionDenied));
+            }
+            Ok(())
+        }
+
+        fn after(&self, _context: &RequestContext, result: &mut Result<String>) {
+            self.log.borrow_mut().push(format!("{} after", self.name));
+            if let Ok(ref mut response) = result {
+                response.push_str(self.name);
+            }
+        }
+    }
+
+    fn run(request: &str) -> (Result<String>, Vec<String>) {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let interceptors: Vec<Arc<dyn Interceptor<String, String>>> = vec![
+            Arc::new(Recorder {
+                name: "a",
+                reject: "",
+                log: log.clone(),
+            }),
+            Arc::new(Recorder {
+                name: "b",
+                reject: "denied",
+                log: log.clone(),
+            }),
+        ];
+        let peer = PeerIdentity::default();
+        let context = RequestContext {
+            service: "EchoService",
+            method: "Echo",
+            peer: &peer,
+            deadline: None,
+        };
+        let handler_log = log.clone();
+        let result = intercept(&interceptors, &context, request.to_string(), |request| {
+            handler_log.borrow_mut().push("handle".to_string());
+            Ok(request)
+        });
+        let log = log.borrow().clone();
+        (result, log)
+    }
+
+    #[test]
+    fn test_interceptors_wrap_the_handler() {
+        let (result, log) = run("ok ");
+        assert_eq!(result.unwrap(), "ok ba");
+        assert_eq!(
+            log,
+            vec![
+                "a before Echo",
+                "b before Echo",
+                "handle",
+                "b after",
+                "a after"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_inbound_services_allow_listed_enclaves() {
+        use teaclave_utils::EnclaveMeasurement;
+
+        let listed = EnclaveMeasurement::new([2; 32], [1; 32]);
+        let inbound = InboundServices::new(EnclaveAttr {
+            measures: vec![listed],
+        });
+        let hex = |byte: u8| Some(format!("{:02x}", byte).repeat(32));
+        let peer = |mr_enclave: u8, mr_signer: u8| PeerIdentity {
+            addr: None,
+            mr_enclave: hex(mr_enclave),
+            mr_signer: hex(mr_signer),
+        };
+        assert!(inbound.allows(&peer(2, 1)));
+        assert_eq!(inbound.allows(&peer(3, 1)), cfg!(test_mode));
+        assert!(!inbound.allows(&peer(2, 3)));
+        assert!(!inbound.allows(&PeerIdentity::default()));
+    }
+
+    #[test]
+    fn test_rejected_request_is_not_handled() {
+        let (result, log) = run("denied");
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::PermissionDenied);
+        assert_eq!(log, vec!["a before Echo", "b before Echo", "a after"]);
+    }
+}
@@ -0,0 +1,275 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Listeners of the untrusted service hosts, which accept connections and
+//! hand them to the trusted servers of an enclave.
+
+use crate::config;
+use crate::{Error, ErrorKind, Result};
+use net2::TcpBuilder;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{sync_channel, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+pub struct ListenerBuilder {
+    addr: SocketAddr,
+    backlog: i32,
+    name: String,
+}
+
+impl ListenerBuilder {
+    /// Listens on `addr` with the backlog of the runtime config.
+    pub fn new(addr: SocketAddr) -> Self {
+        ListenerBuilder {
+            addr,
+            backlog: config::runtime_config().server.backlog,
+            name: "listen_address".to_string(),
+        }
+    }
+
+    /// Overrides the number of pending connections the OS queues before
+    /// refusing new ones.
+    pub fn backlog(mut self, n: i32) -> Self {
+        self.backlog = n;
+        self
+    }
+
+    /// Names the address in diagnostics, e.g. `TMS internal listen_address`.
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn bind(self) -> Result<TcpListener> {
+        let listener = self.listen().map_err(|e| {
+            Error::new(
+                ErrorKind::IoError,
+                format!("{} '{}' failed to bind: {}", self.name, self.addr, e),
+            )
+        })?;
+        info!("{} listening on {}", self.name, listener.local_addr()?);
+        Ok(listener)
+    }
+
+    fn listen(&self) -> std::io::Result<TcpListener> {
+        let builder = match self.addr {
+            SocketAddr::V4(_) => TcpBuilder::new_v4()?,
+            SocketAddr::V6(_) => TcpBuilder::new_v6()?,
+        };
+        builder.reuse_address(true)?;
+        builder.bind(self.addr)?;
+        builder.listen(self.backlog)
+    }
+}
+
+// Interval between two attempts to connect to a service which is not up yet.
+const REACHABLE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Waits up to the `dependency_wait` of the runtime config for the service
+/// advertised at `addr` to accept connections, so that a wrong address fails
+/// the startup of a service naming the address, `name`, rather than its
+/// first request.
+pub fn wait_reachable(name: &str, addr: SocketAddr) -> Result<()> {
+    let wait = Duration::from_secs(config::runtime_config().server.dependency_wait);
+    let start = Instant::now();
+    loop {
+        match TcpStream::connect_timeout(&addr, REACHABLE_POLL_INTERVAL) {
+            Ok(_) => {
+                info!("{} {} is reachable", name, addr);
+                return Ok(());
+            }
+            Err(e) if start.elapsed() >= wait => {
+                return Err(Error::new(
+                    ErrorKind::ConnectionRefused,
+                    format!("{} '{}' is unreachable: {}", name, addr, e),
+                ));
+            }
+            Err(_) => thread::sleep(REACHABLE_POLL_INTERVAL),
+        }
+    }
+}
+
+// Connections rejected by the rejecting thread of a pool wait in a queue of
+// this depth, further connections are closed.
+const REJECT_QUEUE_DEPTH: usize = 16;
+
+/// A fixed set of worker threads handing the accepted connections of a
+/// listener to an enclave.
+///
+/// Connections wait for a free worker in a queue of bounded depth. Connections
+/// beyond it are handed to a single rejecting thread, which tells the peer
+/// that the service is busy, so that the number of enclave threads and the
+/// memory used by a listener stay bounded under load.
+pub struct ConnectionPool<T: Send + 'static> {
+    queue: SyncSender<T>,
+    overflow: SyncSender<T>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl<T: Send + 'static> ConnectionPool<T> {
+    /// Runs `serve` on `workers` threads, with at most `queue_depth`
+    /// connections waiting for them. `reject` runs on the connections the
+    /// queue has no room for.
+    pub fn new<S, R>(workers: usize, queue_depth: usize, serve: S, reject: R) -> Self
+    where
+        S: Fn(T) + Send + Sync + 'static,
+        R: Fn(T) + Send + 'static,
+    {
+        let (queue, receiver) = sync_channel(queue_depth);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let serve = Arc::new(serve);
+        let mut threads = Vec::new();
+        for _ in 0..std::cmp::max(workers, 1) {
+            let receiver = receiver.clone();
+            let serve = serve.clone();
+            threads.push(thread::spawn(move || loop {
+                // Idle workers take turns waiting on the queue
+                let connection = match receiver.lock() {
+                    Ok(receiver) => receiver.recv(),
+                    Err(_) => return,
+                };
+                match connection {
+                    Ok(connection) => serve(connection),
+                    Err(_) => return,
+                }
+            }));
+        }
+
+        let (overflow, rejected) = sync_channel(REJECT_QUEUE_DEPTH);
+        threads.push(thread::spawn(move || {
+            for connection in rejected.iter() {
+                reject(connection);
+            }
+        }));
+
+        ConnectionPool {
+            queue,
+            overflow,
+            threads,
+        }
+    }
+
+    /// A pool of the `worker_threads` and `max_queued_connections` of the
+    /// runtime config.
+    pub fn from_runtime_config<S, R>(serve: S, reject: R) -> Self
+    where
+        S: Fn(T) + Send + Sync + 'static,
+        R: Fn(T) + Send + 'static,
+    {
+        let server_config = &config::runtime_config().server;
+        Self::new(
+            server_config.worker_threads,
+            server_config.max_queued_connections,
+            serve,
+            reject,
+        )
+    }
+
+    /// Queues `connection` for a worker. Returns false if the queue is full,
+    /// in which case the connection is rejected.
+    pub fn dispatch(&self, connection: T) -> bool {
+        match self.queue.try_send(connection) {
+            Ok(()) => true,
+            Err(TrySendError::Full(connection)) | Err(TrySendError::Disconnected(connection)) => {
+                // Dropped, and closed, if the rejecting thread is behind
+                let _ = self.overflow.try_send(connection);
+                false
+            }
+        }
+    }
+
+    /// Waits for the queued connections to be served.
+    pub fn join(self) {
+        let ConnectionPool {
+            queue,
+            overflow,
+            threads,
+        } = self;
+        drop(queue);
+        drop(overflow);
+        for thread in threads {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    fn wait_until<F: Fn() -> bool>(condition: F) {
+        for _ in 0..500 {
+            if condition() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("timed out");
+    }
+
+    #[test]
+    fn test_concurrency_is_capped_at_worker_count() {
+        let active = Arc::new(AtomicUsize::new(0));
+        let max_active = Arc::new(Mutex::new(0));
+        let served = Arc::new(AtomicUsize::new(0));
+        let rejected = Arc::new(AtomicUsize::new(0));
+        let release = Arc::new(AtomicBool::new(false));
+
+        let pool = {
+            let (active, max_active) = (active.clone(), max_active.clone());
+            let (served, rejected) = (served.clone(), rejected.clone());
+            let release = release.clone();
+            ConnectionPool::new(
+                2,
+                2,
+                move |_connection: u32| {
+                    let now_active = active.fetch_add(1, Ordering::SeqCst) + 1;
+                    {
+                        let mut max_active = max_active.lock().unwrap();
+                        *max_active = std::cmp::max(*max_active, now_active);
+                    }
+                    while !release.load(Ordering::SeqCst) {
+                        thread::sleep(Duration::from_millis(10));
+                    }
+                    active.fetch_sub(1, Ordering::SeqCst);
+                    served.fetch_add(1, Ordering::SeqCst);
+                },
+                move |_connection| {
+                    rejected.fetch_add(1, Ordering::SeqCst);
+                },
+            )
+        };
+
+        // Two connections are served, two wait and the last one is rejected
+        assert!(pool.dispatch(1));
+        assert!(pool.dispatch(2));
+        wait_until(|| active.load(Ordering::SeqCst) == 2);
+        assert!(pool.dispatch(3));
+        assert!(pool.dispatch(4));
+        assert!(!pool.dispatch(5));
+        wait_until(|| rejected.load(Ordering::SeqCst) == 1);
+        assert_eq!(active.load(Ordering::SeqCst), 2);
+
+        release.store(true, Ordering::SeqCst);
+        pool.join();
+        assert_eq!(served.load(Ordering::SeqCst), 4);
+        assert_eq!(*max_active.lock().unwrap(), 2);
+    }
+}
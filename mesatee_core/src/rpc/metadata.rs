@@ -0,0 +1,75 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Metadata of RPC requests.
+//!
+//! A client may send a metadata frame right before a request. The server
+//! applies it to the request that follows and does not reply to the frame
+//! itself. Clients only send metadata if there is any, e.g. a deadline, so
+//! the framing of requests without metadata is unchanged.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use crate::rpc::codec;
+use crate::rpc::deadline::Deadline;
+use crate::Result;
+use serde_derive::{Deserialize, Serialize};
+use std::time::Duration;
+
+// JSON payloads never start with a NUL byte, so the metadata frame cannot be
+// mistaken for a request.
+const METADATA_MAGIC: &[u8] = b"\0teaclave-rpc-metadata\0";
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    // Milliseconds left until the deadline of the request.
+    #[serde(default)]
+    pub deadline_ms: Option<u64>,
+}
+
+impl Metadata {
+    pub fn with_deadline(deadline: Option<Deadline>) -> Self {
+        Metadata {
+            deadline_ms: deadline.map(|deadline| deadline.remaining().as_millis() as u64),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deadline_ms.is_none()
+    }
+
+    /// Deadline of the request, counted from when the metadata is received.
+    pub fn deadline(&self) -> Option<Deadline> {
+        self.deadline_ms
+            .map(|deadline_ms| Deadline::after(Duration::from_millis(deadline_ms)))
+    }
+
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut frame = METADATA_MAGIC.to_vec();
+        frame.extend(serde_json::to_vec(self).expect("infallable"));
+        frame
+    }
+
+    /// Returns the metadata if `frame` is a metadata frame.
+    pub(crate) fn parse(frame: &[u8]) -> Option<Result<Metadata>> {
+        if !frame.starts_with(METADATA_MAGIC) {
+            return None;
+        }
+        Some(codec::decode(&frame[METADATA_MAGIC.len()..]))
+    }
+}
@@ -21,9 +21,36 @@
 #[cfg(feature = "mesalock_sgx")]
 use std::prelude::v1::*;
 
+use crate::logging::RequestScope;
+use crate::metrics::RpcTimer;
 use crate::{Error, ErrorKind, Result};
 use serde::{de::DeserializeOwned, Serialize};
 use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use teaclave_config::build_config::BUILD_CONFIG;
+
+pub mod authorization;
+
+pub mod circuit_breaker;
+
+pub mod codec;
+
+pub mod compression;
+use crate::rpc::compression::Algorithm;
+
+pub mod deadline;
+use crate::rpc::deadline::{Deadline, DeadlineScope};
+
+mod heartbeat;
+
+pub mod interceptor;
+use crate::rpc::interceptor::{Interceptor, PeerIdentity, RequestContext};
+
+pub mod metadata;
+use crate::rpc::metadata::Metadata;
+
+pub mod panic;
 
 mod sendrecv;
 use crate::rpc::sendrecv::*;
@@ -46,6 +73,28 @@ where
 
     fn start(config: &Self::Config) -> Result<Self>;
 
+    // The compression algorithm the server accepts if a client offers it.
+    fn compression(&self) -> Algorithm {
+        Algorithm::None
+    }
+
+    // Size in bytes of the largest request the server reads, after
+    // decompression. Capped by the message size limit of the build config.
+    fn max_request_size(&self) -> u64 {
+        BUILD_CONFIG.rpc_max_message_size
+    }
+
+    // Whether a panic of the handler is answered with `HandlerPanicked`
+    // instead of unwinding through the server. See `rpc::panic`.
+    fn catch_panics(&self) -> bool {
+        false
+    }
+
+    // Identity of the peer, known once the first request is received.
+    fn peer_identity(&self) -> PeerIdentity {
+        PeerIdentity::default()
+    }
+
     // This call would block -- contains main loop
     // Returns error on socket close or any exceptions.
     // The `loop` here is used for multi-round communications:
@@ -56,17 +105,133 @@ where
     // let _ = conn.invoke(third_req).unwrap();
     // ```
     // The `serve` function would loop its body for 3 times.
-    fn serve(&mut self, mut x: X) -> Result<()> {
+    fn serve(&mut self, x: X) -> Result<()> {
+        self.serve_with_shutdown(x, &AtomicBool::new(false))
+    }
+
+    // Same as `serve`, but `shutdown` is checked before each round. Once it
+    // is set, the in-flight request is completed and `Ok(())` is returned.
+    fn serve_with_shutdown(&mut self, x: X, shutdown: &AtomicBool) -> Result<()> {
+        self.serve_with_interceptors(x, shutdown, &[])
+    }
+
+    // Same as `serve_with_shutdown`, but each request is dispatched through
+    // `interceptors`, see `rpc::interceptor`.
+    fn serve_with_interceptors(
+        &mut self,
+        mut x: X,
+        shutdown: &AtomicBool,
+        interceptors: &[Arc<dyn Interceptor<U, V>>],
+    ) -> Result<()> {
+        let mut negotiated = Algorithm::None;
+        let mut first = true;
+        let mut peer: Option<PeerIdentity> = None;
+        let mut metadata: Result<Metadata> = Ok(Metadata::default());
         loop {
+            if shutdown.load(Ordering::SeqCst) {
+                return Ok(());
+            }
+
             // First receive a payload from client
-            let recv_buf: Vec<u8> = receive_vec(self)?;
+            let recv_buf: Vec<u8> = match receive_vec_within(self, self.max_request_size()) {
+                Ok(recv_buf) => recv_buf,
+                Err(e) => return reject_oversize::<Self, V>(self, negotiated, e),
+            };
+
+            // Only the first frame of a connection may negotiate compression
+            if first {
+                first = false;
+                if let Some(offered) = compression::parse_hello(&recv_buf) {
+                    negotiated = compression::select(self.compression(), &offered);
+                    debug!("SERVER negotiated compression: {:?}", negotiated);
+                    compression::accept(self, negotiated)?;
+                    continue;
+                }
+            }
+            let recv_buf: Vec<u8> = match negotiated.decompress(recv_buf) {
+                Ok(ref recv_buf) if recv_buf.len() as u64 > self.max_request_size() => {
+                    let e = Error::from(ErrorKind::MsgSizeLimitExceedError);
+                    return reject_oversize::<Self, V>(self, negotiated, e);
+                }
+                Ok(recv_buf) => recv_buf,
+                Err(e) => return reject_oversize::<Self, V>(self, negotiated, e),
+            };
+            // Metadata applies to the request that follows it
+            if let Some(parsed) = Metadata::parse(&recv_buf) {
+                metadata = parsed;
+                continue;
+            }
+            // Heartbeats of idle clients are answered without dispatching
+            if heartbeat::is_heartbeat(&recv_buf) {
+                heartbeat::answer(self, negotiated)?;
+                continue;
+            }
+            let deadline = match std::mem::replace(&mut metadata, Ok(Metadata::default())) {
+                Ok(metadata) => metadata.deadline(),
+                Err(e) => {
+                    // Clients expect no answer to the metadata frame itself,
+                    // so the request it came with is answered with the error.
+                    warn!("SERVER reject malformed metadata: {}", e);
+                    let result: Result<V> = Err(e.into_simple_error());
+                    let response = serde_json::to_vec(&result).expect("infallable");
+                    send_vec(self, negotiated.compress(response)?)?;
+                    continue;
+                }
+            };
+            let _deadline_scope = DeadlineScope::enter(deadline);
+            // Log lines until the response is sent carry the request ID
+            let _scope = RequestScope::enter();
+            // The payload is only parsed for the method if it is used
+            let method =
+                if cfg!(feature = "metrics") || !interceptors.is_empty() || self.catch_panics() {
+                    request_method(&recv_buf)
+                } else {
+                    String::new()
+                };
+            if peer.is_none() && !interceptors.is_empty() {
+                peer = Some(self.peer_identity());
+            }
+            let timer = RpcTimer::start(service_name::<X>(), method.clone(), recv_buf.len());
 
             // Now we received a payload in recv_buf
             // recv_buf should be a serialized incoming request U
             // The server needs deser it into U first
-            let request: U = serde_json::from_slice(&recv_buf)?;
+            let request: U = match codec::decode(&recv_buf) {
+                Ok(request) => request,
+                Err(e) => {
+                    // The frame was read whole, so the connection is still
+                    // in sync and may be served further.
+                    warn!("SERVER reject malformed request: {}", e);
+                    let result: Result<V> = Err(e.into_simple_error());
+                    let response = serde_json::to_vec(&result).expect("infallable");
+                    timer.finish(false, response.len());
+                    send_vec(self, negotiated.compress(response)?)?;
+                    continue;
+                }
+            };
             debug!("SERVER get request: {:?}", request);
-            let result: Result<V> = x.handle_invoke(request).map_err(|e| e.into_simple_error());
+            // The client has given up on a request past its deadline
+            let dispatch = || match (deadline::check_current(), &peer) {
+                (Err(e), _) => Err(e),
+                (Ok(()), Some(peer)) => {
+                    let context = RequestContext {
+                        service: service_name::<X>(),
+                        method: &method,
+                        peer,
+                        deadline,
+                    };
+                    interceptor::intercept(interceptors, &context, request, |request| {
+                        x.handle_invoke(request)
+                    })
+                }
+                (Ok(()), None) => x.handle_invoke(request),
+            };
+            let (result, panicked): (Result<V>, bool) = if self.catch_panics() {
+                panic::catch(service_name::<X>(), &method, dispatch)
+            } else {
+                (dispatch(), false)
+            };
+            let result = result.map_err(|e| e.into_simple_error());
             debug!("SERVER handle_invoke result: {:?}", result);
 
             let response = match serde_json::to_vec(&result) {
@@ -76,13 +241,72 @@ where
                     serde_json::to_vec(&r).expect("infallable")
                 }
             };
+            timer.finish(result.is_ok(), response.len());
             debug!("SERVER send response {:?}", response);
 
             // Now the result is stored in ret and we need to sent it back.
             // `ret` is cleared here. Performance is not very good.
-            send_vec(self, response)?;
+            send_vec(self, negotiated.compress(response)?)?;
+
+            // The service may be left inconsistent by the panic, so the
+            // connection is not served further.
+            if panicked {
+                return Err(Error::from(ErrorKind::HandlerPanicked));
+            }
+        }
+    }
+
+    // Answers the first request of the connection with `error` instead of
+    // serving it. Compression offered by the client is declined.
+    fn reject(&mut self, error: Error) -> Result<()> {
+        let recv_buf: Vec<u8> = receive_vec(self)?;
+        if compression::parse_hello(&recv_buf).is_some() {
+            compression::accept(self, Algorithm::None)?;
+            let _ = receive_vec(self)?;
         }
+        debug!("SERVER reject request: {:?}", error);
+
+        let result: Result<V> = Err(error);
+        let response = serde_json::to_vec(&result).expect("infallable");
+        send_vec(self, response)
+    }
+}
+
+// Answers an oversize request with `error` and closes the connection, since
+// the rest of the request is left unread. Other errors are returned as is.
+fn reject_oversize<S, V>(sock: &mut S, negotiated: Algorithm, error: Error) -> Result<()>
+where
+    S: Write,
+    V: Serialize,
+{
+    if error.kind() != ErrorKind::MsgSizeLimitExceedError {
+        return Err(error);
     }
+    warn!("SERVER reject request: {:?}", error);
+    let result: Result<V> = Err(Error::from(ErrorKind::MsgSizeLimitExceedError));
+    let response = serde_json::to_vec(&result).expect("infallable");
+    send_vec(sock, negotiated.compress(response)?)?;
+    Err(error)
+}
+
+// Short type name of the service, e.g. `TMSExternalEnclave`
+fn service_name<X>() -> &'static str {
+    let name = std::any::type_name::<X>();
+    let name = name.split('<').next().unwrap_or(name);
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+// Every RPC request enum is tagged with its variant name in `type`.
+fn request_method(payload: &[u8]) -> String {
+    #[derive(serde_derive::Deserialize)]
+    struct Tagged {
+        #[serde(rename = "type")]
+        method: String,
+    }
+
+    serde_json::from_slice::<Tagged>(payload)
+        .map(|tagged| tagged.method)
+        .unwrap_or_else(|_| "unknown".to_string())
 }
 
 pub trait RpcClient<U, V>: Read + Write
@@ -95,19 +319,53 @@ where
 
     fn open(config: Self::Config) -> Result<Self>;
 
-    fn invoke(&mut self, input: U) -> Result<V> {
-        let request_payload: Vec<u8> = serde_json::to_vec(&input)?;
+    // The compression algorithm negotiated with the server.
+    fn compression(&self) -> Algorithm {
+        Algorithm::None
+    }
 
-        debug!("CLIENT: sending req: {:?}", request_payload);
-        send_vec(self, request_payload)?;
+    // Deadline of the next request, sent to the server in the request
+    // metadata. Requests sent while handling a request share its deadline.
+    fn deadline(&self) -> Option<Deadline> {
+        deadline::current()
+    }
 
-        let result_buf: Vec<u8> = receive_vec(self)?;
-        debug!("CLIENT: receiving resp: {:?}", result_buf);
+    fn invoke(&mut self, input: U) -> Result<V> {
+        exchange(self, input)
+    }
+
+    // Checks that the server still answers on this connection, see
+    // `rpc::heartbeat`.
+    fn heartbeat(&mut self) -> Result<()> {
+        let compression = self.compression();
+        heartbeat::probe(self, compression)
+    }
+}
 
-        let resp: Result<V> = serde_json::from_slice(&result_buf)?;
+// Sends a request with its metadata and receives the response.
+pub(crate) fn exchange<C, U, V>(client: &mut C, input: U) -> Result<V>
+where
+    C: RpcClient<U, V>,
+    U: Serialize,
+    V: DeserializeOwned,
+{
+    let request_payload: Vec<u8> = serde_json::to_vec(&input)?;
 
-        resp
+    let compression = client.compression();
+    if let Some(deadline) = client.deadline() {
+        deadline.check()?;
+        let metadata = Metadata::with_deadline(Some(deadline));
+        send_vec(client, compression.compress(metadata.encode())?)?;
     }
+    debug!("CLIENT: sending req: {:?}", request_payload);
+    send_vec(client, compression.compress(request_payload)?)?;
+
+    let result_buf: Vec<u8> = compression.decompress(receive_vec(client)?)?;
+    debug!("CLIENT: receiving resp: {:?}", result_buf);
+
+    let resp: Result<V> = serde_json::from_slice(&result_buf)?;
+
+    resp
 }
 
 // EnclaveService takes two generic type and provides
@@ -128,8 +386,384 @@ where
 // Please reivse this later if we have a dedicated rpc for mesalock_sgx.
 
 pub mod channel;
+#[cfg(not(feature = "mesalock_sgx"))]
+pub mod listener;
 #[cfg(feature = "mesalock_sgx")]
 pub mod server;
 
 pub mod sgx;
 pub mod unix;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Status;
+    use std::io::{self, Cursor};
+    use std::time::Duration;
+
+    // An in-memory connection accepting requests of up to 16 bytes.
+    #[derive(Default)]
+    struct MockPipe {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for MockPipe {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for MockPipe {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    struct EchoService;
+
+    impl EnclaveService<String, String> for EchoService {
+        fn handle_invoke(&mut self, input: String) -> Result<String> {
+            Ok(input)
+        }
+    }
+
+    impl RpcServer<String, String, EchoService> for MockPipe {
+        type Config = ();
+
+        fn start(_config: &Self::Config) -> Result<Self> {
+            Ok(MockPipe::default())
+        }
+
+        fn max_request_size(&self) -> u64 {
+            16
+        }
+    }
+
+    // Echoes requests up to the default size limit.
+    struct UnlimitedEchoService;
+
+    impl EnclaveService<String, String> for UnlimitedEchoService {
+        fn handle_invoke(&mut self, input: String) -> Result<String> {
+            Ok(input)
+        }
+    }
+
+    impl RpcServer<String, String, UnlimitedEchoService> for MockPipe {
+        type Config = ();
+
+        fn start(_config: &Self::Config) -> Result<Self> {
+            Ok(MockPipe::default())
+        }
+    }
+
+    // Handles requests past their deadline by forwarding them downstream.
+    struct SlowService;
+
+    impl EnclaveService<String, String> for SlowService {
+        fn handle_invoke(&mut self, input: String) -> Result<String> {
+            std::thread::sleep(Duration::from_millis(20));
+            let mut downstream = MockPipe {
+                input: Cursor::new(Vec::new()),
+                output: Vec::new(),
+            };
+            let result = downstream.invoke(input);
+            // Nothing is sent downstream once the deadline passed
+            assert!(downstream.output.is_empty());
+            result
+        }
+    }
+
+    impl RpcServer<String, String, SlowService> for MockPipe {
+        type Config = ();
+
+        fn start(_config: &Self::Config) -> Result<Self> {
+            Ok(MockPipe::default())
+        }
+    }
+
+    // Panics on every request.
+    struct PanicService;
+
+    impl EnclaveService<String, String> for PanicService {
+        fn handle_invoke(&mut self, _input: String) -> Result<String> {
+            panic!("secret in a panic message");
+        }
+    }
+
+    impl RpcServer<String, String, PanicService> for MockPipe {
+        type Config = ();
+
+        fn start(_config: &Self::Config) -> Result<Self> {
+            Ok(MockPipe::default())
+        }
+
+        fn catch_panics(&self) -> bool {
+            true
+        }
+    }
+
+    // Echoes requests, recorded in metrics under its own name.
+    #[cfg(feature = "metrics")]
+    struct SizedEchoService;
+
+    #[cfg(feature = "metrics")]
+    impl EnclaveService<String, String> for SizedEchoService {
+        fn handle_invoke(&mut self, input: String) -> Result<String> {
+            Ok(input)
+        }
+    }
+
+    #[cfg(feature = "metrics")]
+    impl RpcServer<String, String, SizedEchoService> for MockPipe {
+        type Config = ();
+
+        fn start(_config: &Self::Config) -> Result<Self> {
+            Ok(MockPipe::default())
+        }
+    }
+
+    impl RpcClient<String, String> for MockPipe {
+        type Config = ();
+
+        fn open(_config: Self::Config) -> Result<Self> {
+            Ok(MockPipe::default())
+        }
+    }
+
+    fn frame(payload: &[u8]) -> Vec<u8> {
+        let mut frame = (payload.len() as u64).to_be_bytes().to_vec();
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    struct DenyAll;
+
+    impl Interceptor<String, String> for DenyAll {
+        fn before(&self, _context: &RequestContext, _request: &String) -> Result<()> {
+            Err(Error::from(ErrorKind::PermissionDenied))
+        }
+    }
+
+    fn serve_frame(len: u64, payload: &[u8]) -> (Result<()>, Result<String>) {
+        serve_frame_with_interceptors(len, payload, &[])
+    }
+
+    fn serve_frame_with_interceptors(
+        len: u64,
+        payload: &[u8],
+        interceptors: &[Arc<dyn Interceptor<String, String>>],
+    ) -> (Result<()>, Result<String>) {
+        let mut input = len.to_be_bytes().to_vec();
+        input.extend_from_slice(payload);
+        let mut pipe = MockPipe {
+            input: Cursor::new(input),
+            output: Vec::new(),
+        };
+        let served =
+            pipe.serve_with_interceptors(EchoService, &AtomicBool::new(false), interceptors);
+        let response = receive_vec(&mut Cursor::new(pipe.output)).unwrap();
+        (served, serde_json::from_slice(&response).unwrap())
+    }
+
+    #[test]
+    fn test_oversize_request_is_rejected() {
+        // Announced far beyond the heap of an enclave, but never allocated
+        let (served, response) = serve_frame(1 << 40, b"");
+        assert_eq!(
+            served.unwrap_err().kind(),
+            ErrorKind::MsgSizeLimitExceedError
+        );
+        assert_eq!(
+            response.unwrap_err().kind(),
+            ErrorKind::MsgSizeLimitExceedError
+        );
+
+        let payload = br#""an oversize request""#;
+        let (served, response) = serve_frame(payload.len() as u64, payload);
+        assert_eq!(
+            served.unwrap_err().kind(),
+            ErrorKind::MsgSizeLimitExceedError
+        );
+        assert_eq!(
+            response.unwrap_err().kind(),
+            ErrorKind::MsgSizeLimitExceedError
+        );
+    }
+
+    #[test]
+    fn test_request_within_limit_is_served() {
+        let payload = br#""a request""#;
+        let (_, response) = serve_frame(payload.len() as u64, payload);
+        assert_eq!(response.unwrap(), "a request");
+    }
+
+    // A malformed request is answered, and the next one is still served.
+    #[test]
+    fn test_malformed_request_is_answered() {
+        let deep = "[".repeat(codec::MAX_DEPTH + 1);
+        let mut input = frame(br#""unterminated"#);
+        input.extend(frame(deep.as_bytes()));
+        input.extend(frame(br#""a request""#));
+        let mut pipe = MockPipe {
+            input: Cursor::new(input),
+            output: Vec::new(),
+        };
+        let _ = pipe.serve(UnlimitedEchoService);
+
+        let mut output = Cursor::new(pipe.output);
+        for _ in 0..2 {
+            let response: Result<String> =
+                serde_json::from_slice(&receive_vec(&mut output).unwrap()).unwrap();
+            let err = response.unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::RpcProtocolError);
+            assert_eq!(err.status(), Status::InvalidArgument);
+        }
+        let response: Result<String> =
+            serde_json::from_slice(&receive_vec(&mut output).unwrap()).unwrap();
+        assert_eq!(response.unwrap(), "a request");
+    }
+
+    // A request after malformed metadata is answered with the error, and the
+    // next one is still served.
+    #[test]
+    fn test_malformed_metadata_is_answered() {
+        let mut metadata = Metadata::default().encode();
+        metadata.pop();
+        let mut input = frame(&metadata);
+        input.extend(frame(br#""a request""#));
+        input.extend(frame(br#""another request""#));
+        let mut pipe = MockPipe {
+            input: Cursor::new(input),
+            output: Vec::new(),
+        };
+        let _ = pipe.serve(UnlimitedEchoService);
+
+        let mut output = Cursor::new(pipe.output);
+        let response: Result<String> =
+            serde_json::from_slice(&receive_vec(&mut output).unwrap()).unwrap();
+        assert_eq!(response.unwrap_err().kind(), ErrorKind::RpcProtocolError);
+        let response: Result<String> =
+            serde_json::from_slice(&receive_vec(&mut output).unwrap()).unwrap();
+        assert_eq!(response.unwrap(), "another request");
+    }
+
+    #[test]
+    fn test_interceptor_rejects_request() {
+        let payload = br#""a request""#;
+        let interceptors: Vec<Arc<dyn Interceptor<String, String>>> = vec![Arc::new(DenyAll)];
+        let (_, response) =
+            serve_frame_with_interceptors(payload.len() as u64, payload, &interceptors);
+        assert_eq!(response.unwrap_err().kind(), ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn test_deadline_cancels_downstream_call() {
+        let metadata = Metadata {
+            deadline_ms: Some(10),
+        };
+        let mut input = frame(&metadata.encode());
+        input.extend(frame(br#""a request""#));
+        let mut pipe = MockPipe {
+            input: Cursor::new(input),
+            output: Vec::new(),
+        };
+        let _ = pipe.serve(SlowService);
+        let response = receive_vec(&mut Cursor::new(pipe.output)).unwrap();
+        let response: Result<String> = serde_json::from_slice(&response).unwrap();
+        assert_eq!(response.unwrap_err().kind(), ErrorKind::DeadlineExceeded);
+        // The deadline ends with the request
+        assert!(deadline::current().is_none());
+    }
+
+    // Heartbeats keep an idle connection served until the next request.
+    #[test]
+    fn test_heartbeats_are_answered_between_requests() {
+        let heartbeat = b"\0teaclave-rpc-heartbeat\0";
+        let mut input = frame(br#""a request""#);
+        input.extend(frame(heartbeat));
+        input.extend(frame(heartbeat));
+        input.extend(frame(br#""another request""#));
+        let mut pipe = MockPipe {
+            input: Cursor::new(input),
+            output: Vec::new(),
+        };
+        let _ = pipe.serve(UnlimitedEchoService);
+
+        let mut output = Cursor::new(pipe.output);
+        let response: Result<String> =
+            serde_json::from_slice(&receive_vec(&mut output).unwrap()).unwrap();
+        assert_eq!(response.unwrap(), "a request");
+        for _ in 0..2 {
+            assert_eq!(receive_vec(&mut output).unwrap(), heartbeat.to_vec());
+        }
+        let response: Result<String> =
+            serde_json::from_slice(&receive_vec(&mut output).unwrap()).unwrap();
+        assert_eq!(response.unwrap(), "another request");
+
+        // The client sees the answers as a live connection
+        let mut client = MockPipe {
+            input: Cursor::new(frame(heartbeat)),
+            output: Vec::new(),
+        };
+        RpcClient::<String, String>::heartbeat(&mut client).unwrap();
+        let mut dead = MockPipe {
+            input: Cursor::new(Vec::new()),
+            output: Vec::new(),
+        };
+        assert!(RpcClient::<String, String>::heartbeat(&mut dead).is_err());
+    }
+
+    #[test]
+    fn test_handler_panic_is_answered() {
+        let mut input = frame(br#""a request""#);
+        input.extend(frame(br#""another request""#));
+        let mut pipe = MockPipe {
+            input: Cursor::new(input),
+            output: Vec::new(),
+        };
+        let served = pipe.serve(PanicService);
+        assert_eq!(served.unwrap_err().kind(), ErrorKind::HandlerPanicked);
+
+        // Only the first request is answered, without the panic message
+        let mut output = Cursor::new(pipe.output);
+        let response = receive_vec(&mut output).unwrap();
+        let response: Result<String> = serde_json::from_slice(&response).unwrap();
+        let err = response.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::HandlerPanicked);
+        assert_eq!(err.status(), Status::Internal);
+        assert!(!err.to_string().contains("secret"));
+        assert!(receive_vec(&mut output).is_err());
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_message_sizes_are_recorded() {
+        let request = br#""a sized request""#;
+        let mut pipe = MockPipe {
+            input: Cursor::new(frame(request)),
+            output: Vec::new(),
+        };
+        let _ = pipe.serve(SizedEchoService);
+        let response = receive_vec(&mut Cursor::new(pipe.output)).unwrap();
+        assert_eq!(response, br#"{"Ok":"a sized request"}"#.to_vec());
+
+        let metrics = crate::metrics::render();
+        let labels = r#"{service="SizedEchoService",method="unknown"}"#;
+        for (name, size) in [
+            ("rpc_request_size_bytes", request.len()),
+            ("rpc_response_size_bytes", response.len()),
+        ]
+        .iter()
+        {
+            assert!(metrics.contains(&format!("{}_sum{} {}\n", name, labels, size)));
+            assert!(metrics.contains(&format!("{}_count{} 1\n", name, labels)));
+            let bucket = r#"{service="SizedEchoService",method="unknown",le="256"}"#;
+            assert!(metrics.contains(&format!("{}_bucket{} 1\n", name, bucket)));
+        }
+    }
+}
@@ -0,0 +1,64 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+//! Panics of request handlers.
+//!
+//! By default a panic of a handler unwinds through the server and drops the
+//! connection without a response, or aborts the enclave if it is built with
+//! `panic = "abort"`. With the `catch_panics` of the server config, the panic
+//! is caught instead: the request is answered with `HandlerPanicked`, the
+//! connection is closed, and other connections are served as usual.
+//!
+//! The panic message may contain secrets, so it is neither sent to the
+//! client nor logged. Only its location is logged, by the hook installed by
+//! `install_hook`.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use crate::metrics;
+use crate::{Error, ErrorKind, Result};
+use std::panic::{self, AssertUnwindSafe};
+
+/// Replaces the panic hook of the enclave by one logging the location of
+/// panics, without their message.
+pub fn install_hook() {
+    panic::set_hook(Box::new(|info| match info.location() {
+        Some(location) => error!(
+            "Panicked at {}:{}:{}",
+            location.file(),
+            location.line(),
+            location.column()
+        ),
+        None => error!("Panicked at an unknown location"),
+    }));
+}
+
+/// Runs `dispatch` for a `method` request of `service`. Returns its result,
+/// or `HandlerPanicked` if it panicked, and whether it panicked.
+pub(crate) fn catch<V, F>(service: &'static str, method: &str, dispatch: F) -> (Result<V>, bool)
+where
+    F: FnOnce() -> Result<V>,
+{
+    match panic::catch_unwind(AssertUnwindSafe(dispatch)) {
+        Ok(result) => (result, false),
+        Err(_) => {
+            error!("{} handler of {} request panicked", service, method);
+            metrics::observe_panic(service, method);
+            (Err(Error::from(ErrorKind::HandlerPanicked)), true)
+        }
+    }
+}
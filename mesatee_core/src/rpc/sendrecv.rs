@@ -19,7 +19,6 @@
 #[cfg(feature = "mesalock_sgx")]
 use std::prelude::v1::*;
 
-use std::io::BufReader;
 use std::io::{Read, Write};
 use std::mem::transmute;
 
@@ -27,6 +26,9 @@ use crate::{Error, ErrorKind, Result};
 
 use teaclave_config::build_config::BUILD_CONFIG;
 
+// Bytes allocated for a message before any of it is received.
+const INITIAL_RECV_CAPACITY: u64 = 64 * 1024;
+
 fn get_send_vec(mut to_send: &mut Vec<u8>) -> Vec<u8> {
     let buf_len: u64 = to_send.len() as u64;
     let lbuf: [u8; 8] = unsafe { transmute(buf_len.to_be()) };
@@ -55,19 +57,37 @@ pub fn receive_vec<T>(sock: &mut T) -> Result<Vec<u8>>
 where
     T: Read,
 {
-    let mut br = BufReader::new(sock);
+    receive_vec_within(sock, BUILD_CONFIG.rpc_max_message_size)
+}
+
+// Receives a message of at most `max_len` bytes. The length prefix of a larger
+// message is checked before its buffer is allocated, and the message itself is
+// left unread. The buffer grows as the message is received, so that a peer
+// announcing a large message without sending it cannot make the buffer
+// allocated up front.
+pub fn receive_vec_within<T>(sock: &mut T, max_len: u64) -> Result<Vec<u8>>
+where
+    T: Read,
+{
+    // Read unbuffered, since bytes buffered past this message would be lost
+    // to the next one.
     let mut lbuf: [u8; 8] = [0; 8];
 
-    br.read_exact(&mut lbuf)?;
+    sock.read_exact(&mut lbuf)?;
 
     let buf_len: u64 = u64::from_be(unsafe { transmute::<[u8; 8], u64>(lbuf) });
-    if buf_len > BUILD_CONFIG.rpc_max_message_size {
+    if buf_len > max_len || buf_len > BUILD_CONFIG.rpc_max_message_size {
         return Err(Error::from(ErrorKind::MsgSizeLimitExceedError));
     }
 
-    let mut recv_buf: Vec<u8> = vec![0u8; buf_len as usize];
-
-    br.read_exact(&mut recv_buf)?;
+    let mut recv_buf: Vec<u8> = Vec::with_capacity(buf_len.min(INITIAL_RECV_CAPACITY) as usize);
+    sock.take(buf_len).read_to_end(&mut recv_buf)?;
+    if recv_buf.len() as u64 != buf_len {
+        return Err(Error::new(
+            ErrorKind::RpcProtocolError,
+            "message is shorter than its length prefix",
+        ));
+    }
 
     Ok(recv_buf)
 }
@@ -15,14 +15,81 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use crate::rpc::sgx;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::sync::SgxMutex as Mutex;
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+
+use crate::config;
+use crate::rpc::compression::Algorithm;
+use crate::rpc::interceptor::Interceptor;
+use crate::rpc::sgx::{self, TlsPolicy};
 use crate::rpc::EnclaveService;
 use crate::rpc::RpcServer;
-use crate::Result;
+use crate::{Error, ErrorKind, Result};
 use serde::{de::DeserializeOwned, Serialize};
 use sgx_types::c_int;
 use teaclave_attestation::verifier::EnclaveAttr;
-use teaclave_attestation::verifier::SgxQuoteVerifier;
+use teaclave_attestation::verifier::{QuoteVerifier, SgxQuoteVerifier};
+use teaclave_config::build_config::BUILD_CONFIG;
+
+lazy_static! {
+    static ref SHUTDOWN_SIGNAL: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+    // Connections being served, by service type.
+    static ref CONNECTIONS: Mutex<HashMap<&'static str, usize>> = Mutex::new(HashMap::new());
+}
+
+// Deadline for each socket read of a connection being rejected.
+const REJECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// A connection slot of the service `name`, freed on drop.
+struct ConnectionSlot {
+    name: &'static str,
+}
+
+impl ConnectionSlot {
+    fn acquire(name: &'static str, max_connections: usize) -> Option<Self> {
+        let mut connections = CONNECTIONS.lock().unwrap();
+        let count = connections.entry(name).or_insert(0);
+        if *count >= max_connections {
+            return None;
+        }
+        *count += 1;
+        Some(ConnectionSlot { name })
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        let mut connections = CONNECTIONS.lock().unwrap();
+        if let Some(count) = connections.get_mut(self.name) {
+            *count = count.saturating_sub(1);
+        }
+    }
+}
+
+// The limit of the runtime config, which cannot exceed the one of the build
+// config.
+fn max_request_size() -> u64 {
+    config::runtime_config()
+        .server
+        .max_request_size
+        .min(BUILD_CONFIG.rpc_max_message_size)
+}
+
+/// Shutdown signal shared by the trusted servers of this enclave.
+pub fn shutdown_signal() -> Arc<AtomicBool> {
+    SHUTDOWN_SIGNAL.clone()
+}
+
+/// Stops the trusted servers of this enclave from accepting new connections
+/// and requests. In-flight requests are completed.
+pub fn shutdown() {
+    SHUTDOWN_SIGNAL.store(true, Ordering::SeqCst);
+}
 
 pub struct SgxTrustedServer<U, V, X>
 where
@@ -32,6 +99,9 @@ where
 {
     config: sgx::PipeConfig,
     service: X,
+    max_connections: usize,
+    reject: bool,
+    interceptors: Vec<Arc<dyn Interceptor<U, V>>>,
     marker: std::marker::PhantomData<(U, V)>,
 }
 
@@ -48,22 +118,118 @@ where
                 sgx::PipeConfig {
                     fd,
                     client_verifier: Some(client_verifier),
+                    tls_policy: TlsPolicy::runtime(),
+                    compression: Algorithm::None,
+                    max_request_size: max_request_size(),
+                    catch_panics: config::runtime_config().server.catch_panics,
                 }
             }
             _ => sgx::PipeConfig {
                 fd,
                 client_verifier: None,
+                tls_policy: TlsPolicy::runtime(),
+                compression: Algorithm::None,
+                max_request_size: max_request_size(),
+                catch_panics: config::runtime_config().server.catch_panics,
             },
         };
         Ok(Self {
             config,
             service,
+            max_connections: config::runtime_config().server.max_connections,
+            reject: false,
+            interceptors: Vec::new(),
             marker: std::marker::PhantomData,
         })
     }
 
+    /// Overrides the TLS policy of the runtime config.
+    pub fn tls_policy(mut self, tls_policy: TlsPolicy) -> Self {
+        self.config.tls_policy = tls_policy;
+        self
+    }
+
+    /// Overrides the quote verifier of the build config for client reports.
+    /// Has no effect if clients are not attested.
+    pub fn quote_verifier(mut self, verifier: QuoteVerifier) -> Self {
+        if let Some(ref mut client_verifier) = self.config.client_verifier {
+            client_verifier.verifier = verifier;
+        }
+        self
+    }
+
+    /// Accepts compressed payloads from clients offering `algorithm`.
+    /// Compression is off by default, see `rpc::compression` for the
+    /// tradeoffs.
+    pub fn compression(mut self, algorithm: Algorithm) -> Self {
+        self.config.compression = algorithm;
+        self
+    }
+
+    /// Overrides the `max_connections` of the runtime config. Connections
+    /// of this service beyond `n` are answered with `TooManyConnections`.
+    pub fn max_connections(mut self, n: usize) -> Self {
+        self.max_connections = n;
+        self
+    }
+
+    /// Answers the connection with `TooManyConnections` instead of serving
+    /// it, for connections the untrusted listener has no room for.
+    pub fn reject(mut self, reject: bool) -> Self {
+        self.reject = reject;
+        self
+    }
+
+    /// Overrides the `max_request_size` of the runtime config. Requests of
+    /// this service larger than `n` bytes are answered with
+    /// `MsgSizeLimitExceedError`, and the connection is closed.
+    pub fn max_request_size(mut self, n: u64) -> Self {
+        self.config.max_request_size = n.min(BUILD_CONFIG.rpc_max_message_size);
+        self
+    }
+
+    /// Overrides the `catch_panics` of the runtime config. See `rpc::panic`.
+    pub fn catch_panics(mut self, catch_panics: bool) -> Self {
+        self.config.catch_panics = catch_panics;
+        self
+    }
+
+    /// Dispatches each request through `interceptor`, after the interceptors
+    /// registered before it. See `rpc::interceptor`.
+    pub fn interceptor<I>(mut self, interceptor: I) -> Self
+    where
+        I: Interceptor<U, V> + 'static,
+    {
+        self.interceptors.push(Arc::new(interceptor));
+        self
+    }
+
     pub fn start(self) -> Result<()> {
-        let mut server = sgx::Pipe::start(&self.config)?;
-        server.serve(self.service)
+        self.start_with_shutdown(Arc::new(AtomicBool::new(false)))
+    }
+
+    pub fn start_with_shutdown(self, shutdown: Arc<AtomicBool>) -> Result<()> {
+        if shutdown.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        let mut server = sgx::Pipe::<U, V, X>::start(&self.config)?;
+        if self.reject {
+            warn!("Rejecting connection: the listener has no room for it");
+            let error = Error::from(ErrorKind::TooManyConnections);
+            return server.reject_within(error, REJECT_TIMEOUT);
+        }
+        let _slot = match ConnectionSlot::acquire(std::any::type_name::<X>(), self.max_connections)
+        {
+            Some(slot) => slot,
+            None => {
+                warn!(
+                    "Rejecting connection: {} connections are being served",
+                    self.max_connections
+                );
+                let error = Error::from(ErrorKind::TooManyConnections);
+                return server.reject_within(error, REJECT_TIMEOUT);
+            }
+        };
+        server.serve_with_interceptors(self.service, &shutdown, &self.interceptors)
     }
 }
@@ -17,6 +17,7 @@
 
 use std::sync::Arc;
 
+use crate::rpc::sgx::TlsPolicy;
 use teaclave_attestation::verifier::SgxQuoteVerifier;
 
 #[cfg(feature = "mesalock_sgx")]
@@ -42,24 +43,32 @@ lazy_static! {
 #[derive(Default)]
 struct ClientConfigCache {
     private_key_sha256: sgx_sha256_hash_t,
-    target_configs: HashMap<Arc<SgxQuoteVerifier>, Arc<rustls::ClientConfig>>,
+    target_configs: HashMap<(Arc<SgxQuoteVerifier>, TlsPolicy), Arc<rustls::ClientConfig>>,
 }
 
 #[cfg(not(feature = "mesalock_sgx"))]
 #[derive(Default)]
 struct ClientConfigCache {
-    target_configs: HashMap<Arc<SgxQuoteVerifier>, Arc<rustls::ClientConfig>>,
+    target_configs: HashMap<(Arc<SgxQuoteVerifier>, TlsPolicy), Arc<rustls::ClientConfig>>,
 }
 
 #[cfg(feature = "mesalock_sgx")]
-pub(crate) fn get_tls_config(server_verifier: Arc<SgxQuoteVerifier>) -> Arc<rustls::ClientConfig> {
+pub(crate) fn get_tls_config(
+    server_verifier: Arc<SgxQuoteVerifier>,
+    tls_policy: TlsPolicy,
+) -> Arc<rustls::ClientConfig> {
     use crate::rpc::sgx::ra::get_current_ra_credential;
 
     let ra_credential = get_current_ra_credential();
 
+    let cache_key = (server_verifier, tls_policy);
+    // Configs, and thus their TLS sessions, are dropped with the RA
+    // credential they present.
     if let Ok(cfg_cache) = CLIENT_CONFIG_CACHE.try_read() {
-        if let Some(cfg) = cfg_cache.target_configs.get(&server_verifier) {
-            return cfg.clone();
+        if cfg_cache.private_key_sha256 == ra_credential.private_key_sha256 {
+            if let Some(cfg) = cfg_cache.target_configs.get(&cache_key) {
+                return cfg.clone();
+            }
         }
     }
 
@@ -70,9 +79,10 @@ pub(crate) fn get_tls_config(server_verifier: Arc<SgxQuoteVerifier>) -> Arc<rust
     client_cfg.set_single_client_cert(certs, privkey);
     client_cfg
         .dangerous()
-        .set_certificate_verifier(server_verifier.clone());
+        .set_certificate_verifier(cache_key.0.clone());
     client_cfg.versions.clear();
     client_cfg.versions.push(rustls::ProtocolVersion::TLSv1_2);
+    cache_key.1.apply_to_client(&mut client_cfg);
 
     let final_arc = Arc::new(client_cfg);
 
@@ -86,16 +96,20 @@ pub(crate) fn get_tls_config(server_verifier: Arc<SgxQuoteVerifier>) -> Arc<rust
 
         let _ = cfg_cache
             .target_configs
-            .insert(server_verifier, final_arc.clone());
+            .insert(cache_key, final_arc.clone());
     }
 
     final_arc
 }
 
 #[cfg(not(feature = "mesalock_sgx"))]
-pub(crate) fn get_tls_config(server_verifier: Arc<SgxQuoteVerifier>) -> Arc<rustls::ClientConfig> {
+pub(crate) fn get_tls_config(
+    server_verifier: Arc<SgxQuoteVerifier>,
+    tls_policy: TlsPolicy,
+) -> Arc<rustls::ClientConfig> {
+    let cache_key = (server_verifier, tls_policy);
     if let Ok(cfg_cache) = CLIENT_CONFIG_CACHE.try_read() {
-        if let Some(cfg) = cfg_cache.target_configs.get(&server_verifier) {
+        if let Some(cfg) = cfg_cache.target_configs.get(&cache_key) {
             return cfg.clone();
         }
     }
@@ -104,16 +118,17 @@ pub(crate) fn get_tls_config(server_verifier: Arc<SgxQuoteVerifier>) -> Arc<rust
 
     client_cfg
         .dangerous()
-        .set_certificate_verifier(server_verifier.clone());
+        .set_certificate_verifier(cache_key.0.clone());
     client_cfg.versions.clear();
     client_cfg.versions.push(rustls::ProtocolVersion::TLSv1_2);
+    cache_key.1.apply_to_client(&mut client_cfg);
 
     let final_arc = Arc::new(client_cfg);
 
     if let Ok(mut cfg_cache) = CLIENT_CONFIG_CACHE.try_write() {
         let _ = cfg_cache
             .target_configs
-            .insert(server_verifier, final_arc.clone());
+            .insert(cache_key, final_arc.clone());
     }
 
     final_arc
@@ -18,6 +18,7 @@
 //! rpc support for MesaTEE-SGX
 
 // Insert std prelude in the top for the sgx feature
+use rustls::Session;
 use serde::{de::DeserializeOwned, Serialize};
 #[cfg(feature = "mesalock_sgx")]
 use sgx_types::c_int;
@@ -25,15 +26,20 @@ use std::io::{self, Read, Write};
 use std::marker::PhantomData;
 use std::net::TcpStream;
 use std::sync::Arc;
+use std::time::Duration;
 
 #[cfg(feature = "mesalock_sgx")]
 use std::prelude::v1::*;
 
+#[cfg(feature = "mesalock_sgx")]
+use crate::rpc::interceptor::PeerIdentity;
 #[cfg(feature = "mesalock_sgx")]
 use crate::rpc::{EnclaveService, RpcServer};
 
-use crate::rpc::RpcClient;
-use crate::Result;
+use crate::rpc::compression::{self, Algorithm};
+use crate::rpc::deadline::{self, Deadline};
+use crate::rpc::{self, RpcClient};
+use crate::{Error, ErrorKind, Result};
 
 use teaclave_attestation;
 use teaclave_attestation::verifier::SgxQuoteVerifier;
@@ -42,15 +48,27 @@ pub mod client;
 #[cfg(feature = "mesalock_sgx")]
 pub mod server;
 
+mod session_cache;
+mod signing;
+#[cfg(any(feature = "mesalock_sgx", test))]
+mod startup;
+mod tls;
+#[cfg(feature = "mesalock_sgx")]
+pub use signing::sign_with_ra_key;
+pub use signing::verify_ra_signature;
+pub use tls::TlsPolicy;
+
 #[cfg(feature = "mesalock_sgx")]
 mod ra;
+#[cfg(feature = "mesalock_sgx")]
+pub use ra::{enclave_identity, ra_certificate, RemoteAttestation};
 
 // Export this function for sgx enclave initialization
 #[cfg(feature = "mesalock_sgx")]
-pub fn prelude() -> Result<()> {
-    // Hard coded RACredential validity in seconds for all enclave.
-    // We may allow each enclave to setup its own validity in the future.
-    ra::init_ra_credential(86400u64)
+pub fn prelude(name: &str) -> Result<()> {
+    // RACredential validity is configured by the report_cache_ttl of the
+    // runtime config for all enclave.
+    RemoteAttestation::new().init_at_startup(name)
 }
 
 #[cfg(feature = "mesalock_sgx")]
@@ -58,11 +76,21 @@ pub struct PipeConfig {
     pub fd: c_int,
     // the SGX server can optionally verify the identity of the client
     pub client_verifier: Option<SgxQuoteVerifier>,
+    pub tls_policy: TlsPolicy,
+    // Compression accepted if the client offers it, `None` to disable.
+    pub compression: Algorithm,
+    // Size in bytes of the largest request read from the client.
+    pub max_request_size: u64,
+    // Whether panics of the handler are caught, see `rpc::panic`.
+    pub catch_panics: bool,
 }
 
 #[cfg(feature = "mesalock_sgx")]
 pub struct Pipe<U, V, X> {
     inner: rustls::StreamOwned<rustls::ServerSession, TcpStream>,
+    compression: Algorithm,
+    max_request_size: u64,
+    catch_panics: bool,
     u: PhantomData<U>,
     v: PhantomData<V>,
     x: PhantomData<X>,
@@ -105,23 +133,68 @@ where
         // TODO: Due to switching to the SDK-style design, performing an
         // initial RA at enclave start is not longer a viable design. Need
         // to refactor the related API.
-        let rustls_server_cfg = server::get_tls_config(&config.client_verifier)?;
-        let sess = rustls::ServerSession::new(&rustls_server_cfg);
+        let rustls_server_cfg =
+            server::get_tls_config(&config.client_verifier, &config.tls_policy)?;
+        let mut sess = rustls::ServerSession::new(&rustls_server_cfg);
+        let mut tcp = tcp;
+        if log_enabled!(log::Level::Debug) {
+            // Complete the handshake to log the verified identity of the peer
+            while sess.is_handshaking() {
+                sess.complete_io(&mut tcp)?;
+            }
+            server::log_peer(&tcp, &sess);
+        }
 
         Ok(Pipe {
             inner: rustls::StreamOwned::new(sess, tcp),
+            compression: config.compression,
+            max_request_size: config.max_request_size,
+            catch_panics: config.catch_panics,
             u: PhantomData::<U>,
             v: PhantomData::<V>,
             x: PhantomData::<X>,
         })
     }
 
+    fn compression(&self) -> Algorithm {
+        self.compression
+    }
+
+    fn max_request_size(&self) -> u64 {
+        self.max_request_size
+    }
+
+    fn catch_panics(&self) -> bool {
+        self.catch_panics
+    }
+
+    fn peer_identity(&self) -> PeerIdentity {
+        server::peer_identity(&self.inner.sock, &self.inner.sess)
+    }
+
     // Use default implementation
     // fn serve(&mut self, mut s: X) -> Result<()>;
 }
 
+#[cfg(feature = "mesalock_sgx")]
+impl<U, V, X> Pipe<U, V, X>
+where
+    U: DeserializeOwned + std::fmt::Debug,
+    V: Serialize + std::fmt::Debug,
+    X: EnclaveService<U, V>,
+{
+    /// Rejects the connection with `error`, giving the client `timeout` for
+    /// each socket read so a silent client cannot hold the connection.
+    pub fn reject_within(&mut self, error: crate::Error, timeout: Duration) -> Result<()> {
+        self.inner.sock.set_read_timeout(Some(timeout))?;
+        RpcServer::<U, V, X>::reject(self, error)
+    }
+}
+
 pub struct PipeClient<U, V> {
     inner: rustls::StreamOwned<rustls::ClientSession, TcpStream>,
+    compression: Algorithm,
+    timeout: Option<Duration>,
     u: PhantomData<U>,
     v: PhantomData<V>,
 }
@@ -130,6 +203,13 @@ pub struct PipeClientConfig {
     pub tcp: TcpStream,
     pub hostname: webpki::DNSName,
     pub server_verifier: SgxQuoteVerifier,
+    pub tls_policy: TlsPolicy,
+    // Deadline for each socket read/write during the attested TLS handshake.
+    pub handshake_timeout: Option<Duration>,
+    // Deadline for each socket read/write of a request after the handshake.
+    pub timeout: Option<Duration>,
+    // Compression offered to the server, `None` to disable.
+    pub compression: Algorithm,
 }
 
 impl<U, V> Read for PipeClient<U, V> {
@@ -155,16 +235,74 @@ where
 {
     type Config = PipeClientConfig;
     fn open(config: Self::Config) -> Result<Self> {
-        let rustls_client_cfg = client::get_tls_config(Arc::new(config.server_verifier));
-        let sess = rustls::ClientSession::new(&rustls_client_cfg, config.hostname.as_ref());
+        let rustls_client_cfg = client::get_tls_config(
+            Arc::new(config.tls_policy.check_report_age(config.server_verifier)),
+            config.tls_policy,
+        );
+        let mut sess = rustls::ClientSession::new(&rustls_client_cfg, config.hostname.as_ref());
+        let mut tcp = config.tcp;
 
-        Ok(PipeClient {
-            inner: rustls::StreamOwned::new(sess, config.tcp),
+        // Complete the handshake (and thus the verification of the server's
+        // attestation report) eagerly, so that it runs under its own deadline
+        // rather than the one of the first request.
+        tcp.set_read_timeout(config.handshake_timeout)?;
+        tcp.set_write_timeout(config.handshake_timeout)?;
+        while sess.is_handshaking() {
+            sess.complete_io(&mut tcp)?;
+        }
+        tcp.set_read_timeout(config.timeout)?;
+        tcp.set_write_timeout(config.timeout)?;
+
+        let mut client = PipeClient {
+            inner: rustls::StreamOwned::new(sess, tcp),
+            compression: Algorithm::None,
+            timeout: config.timeout,
             u: PhantomData::<U>,
             v: PhantomData::<V>,
-        })
+        };
+        // Falls back to no compression if the server does not accept it.
+        client.compression = compression::negotiate(&mut client, config.compression)?;
+
+        Ok(client)
     }
 
-    // use default implementation
-    // fn invoke(&mut self, input: U) -> Result<V>;
+    fn compression(&self) -> Algorithm {
+        self.compression
+    }
+
+    // The server is given the request timeout as its deadline.
+    fn deadline(&self) -> Option<Deadline> {
+        deadline::earliest(deadline::current(), self.timeout.map(Deadline::after))
+    }
+
+    fn invoke(&mut self, input: U) -> Result<V> {
+        let shared = match deadline::current() {
+            Some(shared) => shared,
+            None => return rpc::exchange(self, input),
+        };
+        // Stop waiting for the server at the deadline of the request being
+        // handled, if it comes before the timeout. A zero timeout would
+        // block indefinitely.
+        let timeout = match self.timeout {
+            Some(timeout) => std::cmp::min(timeout, shared.remaining()),
+            None => shared.remaining(),
+        };
+        self.set_timeout(Some(std::cmp::max(timeout, Duration::from_millis(1))))?;
+        let result = rpc::exchange(self, input);
+        self.set_timeout(self.timeout)?;
+        match result {
+            Err(ref e) if e.kind() == ErrorKind::TimedOut && shared.is_expired() => {
+                Err(Error::from(ErrorKind::DeadlineExceeded))
+            }
+            result => result,
+        }
+    }
+}
+
+impl<U, V> PipeClient<U, V> {
+    fn set_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        self.inner.sock.set_read_timeout(timeout)?;
+        self.inner.sock.set_write_timeout(timeout)?;
+        Ok(())
+    }
 }
@@ -20,28 +20,45 @@
 // This entire file is solely used for the sgx environment
 use std::prelude::v1::*;
 
-use sgx_tcrypto::rsgx_sha256_slice;
+use sgx_rand::os::SgxRng;
+use sgx_rand::Rng;
+use sgx_tcrypto::{rsgx_rijndael128GCM_decrypt, rsgx_rijndael128GCM_encrypt, rsgx_sha256_slice};
+use sgx_tse::{rsgx_get_key, rsgx_self_report};
 use sgx_types::*;
 
+use std::path::PathBuf;
 use std::sync::SgxRwLock;
 use std::time::{self, SystemTime};
+use std::untrusted::fs;
 use std::untrusted::time::SystemTimeEx;
 
 use lazy_static::lazy_static;
+use serde_derive::{Deserialize, Serialize};
 
 use crate::{Error, ErrorKind, Result};
 
 use crate::config::runtime_config;
+use crate::health::EnclaveIdentity;
+use crate::metrics;
+use crate::rpc::channel::RetryPolicy;
+use crate::rpc::sgx::startup::{self, Attested};
 use teaclave_attestation;
+use teaclave_attestation::quote::SgxQuote;
+use teaclave_attestation::AttestationConfig;
+use teaclave_config::build_config::BUILD_CONFIG;
+use teaclave_config::runtime_config::AttestationScheme;
 
 lazy_static! {
     static ref RACACHE: SgxRwLock<RACache> = {
         SgxRwLock::new(RACache {
             ra_credential: RACredential::default(),
+            report_data: Vec::new(),
             gen_time: SystemTime::UNIX_EPOCH,
             validity: time::Duration::from_secs(0),
         })
     };
+    // Where the endorsed credential of this enclave is kept across runs.
+    static ref CREDENTIAL_STORE: SgxRwLock<Option<PathBuf>> = SgxRwLock::new(None);
 }
 
 /// Certificate and public key in DER format
@@ -52,26 +69,161 @@ pub(crate) struct RACredential {
     pub private_key_sha256: sgx_sha256_hash_t,
 }
 
+// The report data endorsed for the credential is its public key. A kept
+// credential is only loaded by the enclave whose MRENCLAVE and report data
+// its report endorses.
 #[derive(Clone)]
 struct RACache {
     ra_credential: RACredential,
+    report_data: Vec<u8>,
     gen_time: SystemTime,
     validity: time::Duration,
 }
 
-pub(crate) fn init_ra_credential(valid_secs: u64) -> Result<()> {
-    match RACache::new(valid_secs) {
-        Ok(new_entry) => {
-            *RACACHE.write().unwrap() = new_entry;
-            Ok(())
+/// Remote attestation of the running enclave. The endorsed RA credential is
+/// cached and reused until the TTL expires, including by a restarted enclave
+/// if it is kept in the credential store.
+pub struct RemoteAttestation {
+    cache_ttl: time::Duration,
+}
+
+impl Default for RemoteAttestation {
+    fn default() -> Self {
+        RemoteAttestation {
+            cache_ttl: time::Duration::from_secs(runtime_config().attestation.report_cache_ttl),
         }
-        Err(e) => {
-            error!("Cannot initialize RACredential: {:?}", e);
-            Err(Error::from(ErrorKind::RAInternalError))
+    }
+}
+
+impl RemoteAttestation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_cache_ttl(mut self, ttl: time::Duration) -> Self {
+        self.cache_ttl = ttl;
+        self
+    }
+
+    /// Generates and endorses the RA credential, unless a cached or kept one
+    /// of this enclave is still valid.
+    pub fn init(self) -> Result<()> {
+        if self.reuse() {
+            return Ok(());
+        }
+        self.refresh()
+    }
+
+    // Whether the cached credential, or else the kept one, is younger than
+    // the TTL and may be used without attesting again.
+    fn reuse(&self) -> bool {
+        let mut g_cache = RACACHE.write().unwrap();
+        g_cache.validity = self.cache_ttl;
+        if g_cache.is_valid() {
+            return true;
+        }
+        match RACache::load(self.cache_ttl.as_secs(), self.cache_ttl) {
+            Some(kept) => {
+                *g_cache = kept;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Generates and endorses the RA credential when the enclave `name`
+    /// starts, retrying as configured in the runtime config. The credential
+    /// kept from a previous run is used instead while it is younger than the
+    /// TTL. If the attestation service cannot be reached, the enclave starts
+    /// with the kept credential, provided its report is still younger than
+    /// `max_report_age`.
+    pub fn init_at_startup(self, name: &str) -> Result<()> {
+        let config = &runtime_config().attestation;
+        let path = config
+            .credential_cache_dir
+            .join(format!("{}.ra_credential", name));
+        *CREDENTIAL_STORE.write().unwrap() = Some(path);
+        if self.reuse() {
+            return Ok(());
+        }
+
+        let retry_policy = RetryPolicy::new(
+            config.startup_attempts,
+            time::Duration::from_millis(config.startup_backoff_ms),
+        );
+        let valid_secs = self.cache_ttl.as_secs();
+        let max_age = time::Duration::from_secs(config.max_report_age);
+        let attested = startup::attest_at_startup(
+            &retry_policy,
+            config.allow_cached_on_outage,
+            || RACache::new(valid_secs),
+            || RACache::load(valid_secs, max_age),
+            std::thread::sleep,
+        );
+        match attested {
+            Ok(Attested::Endorsed(new_entry)) | Ok(Attested::Cached(new_entry)) => {
+                *RACACHE.write().unwrap() = new_entry;
+                Ok(())
+            }
+            Err(e) => {
+                error!("Cannot initialize RACredential: {:?}", e);
+                Err(e)
+            }
+        }
+    }
+
+    /// Discards the cached RA credential and requests a new one from the
+    /// attestation service.
+    pub fn refresh(self) -> Result<()> {
+        match RACache::new(self.cache_ttl.as_secs()) {
+            Ok(new_entry) => {
+                *RACACHE.write().unwrap() = new_entry;
+                Ok(())
+            }
+            Err(e) => {
+                error!("Cannot initialize RACredential: {:?}", e);
+                Err(e)
+            }
         }
     }
 }
 
+/// RA certificate (DER) presented by this enclave to its peers.
+pub fn ra_certificate() -> Vec<u8> {
+    get_current_ra_credential().cert
+}
+
+/// Identity of this enclave, read from the report endorsed in its RA
+/// certificate, so that it is the identity peers attest. Reports are not
+/// endorsed in simulation mode, where the enclave's own report is read.
+pub fn enclave_identity() -> Result<EnclaveIdentity> {
+    if cfg!(sgx_sim) {
+        let body = rsgx_self_report().body;
+        return Ok(EnclaveIdentity::new(
+            &body.mr_enclave.m,
+            &body.mr_signer.m,
+            body.isv_svn,
+            body.isv_prod_id,
+        ));
+    }
+
+    let cert = ra_certificate();
+    let quote = SgxQuote::extract_verified_quote_with_roots(&cert, BUILD_CONFIG.ias_root_ca_certs)
+        .map_err(|e| {
+            Error::new(
+                ErrorKind::RAInternalError,
+                format!("cannot read the endorsed report: {}", e),
+            )
+        })?;
+    let report = &quote.body.report_body;
+    Ok(EnclaveIdentity::new(
+        &report.mr_enclave,
+        &report.mr_signer,
+        report.isv_svn,
+        report.isv_prod_id,
+    ))
+}
+
 pub(crate) fn get_current_ra_credential() -> RACredential {
     // Check if the global cert valid
     // If valid, use it directly
@@ -126,23 +278,137 @@ pub(crate) fn get_current_ra_credential() -> RACredential {
     g_cache.ra_credential.clone()
 }
 
+fn attestation_config() -> AttestationConfig {
+    match &runtime_config().attestation.scheme {
+        AttestationScheme::Epid => AttestationConfig::new(
+            &runtime_config().env.ias_key,
+            &runtime_config().env.ias_spid,
+            false,
+        ),
+        AttestationScheme::Dcap { pccs_url } => AttestationConfig::dcap(pccs_url),
+    }
+}
+
+fn current_mr_enclave() -> sgx_measurement_t {
+    rsgx_self_report().body.mr_enclave
+}
+
+// Credential as kept in the credential store.
+#[derive(Serialize, Deserialize)]
+struct StoredCredential {
+    cert: Vec<u8>,
+    private_key: Vec<u8>,
+    // Seconds since the UNIX epoch.
+    generated_at: u64,
+    mr_enclave: Vec<u8>,
+    report_data: Vec<u8>,
+}
+
+// Report data endorsing the public key `pub_k`, as created for the report.
+fn report_data(pub_k: &sgx_ec256_public_t) -> Vec<u8> {
+    let mut gx = pub_k.gx;
+    gx.reverse();
+    let mut gy = pub_k.gy;
+    gy.reverse();
+    [&gx[..], &gy[..]].concat()
+}
+
+// Whether the report in `cert` endorses `report_data` for the enclave
+// `mr_enclave`. Reports are not endorsed in simulation mode.
+fn is_endorsed_for(cert: &[u8], mr_enclave: &sgx_measurement_t, report_data: &[u8]) -> bool {
+    if cfg!(sgx_sim) {
+        return true;
+    }
+    match SgxQuote::extract_verified_quote_with_roots(cert, BUILD_CONFIG.ias_root_ca_certs) {
+        Ok(quote) => {
+            let report = &quote.body.report_body;
+            report.mr_enclave == mr_enclave.m && report.report_data[..] == report_data[..]
+        }
+        Err(_) => false,
+    }
+}
+
+const KEY_ID_SIZE: usize = 32;
+const NONCE_SIZE: usize = 12;
+const TAG_SIZE: usize = 16;
+
+// Seal key of this enclave, bound to its MRENCLAVE so that other enclaves
+// and other builds cannot read the kept credential.
+fn seal_key(key_id: &[u8; KEY_ID_SIZE]) -> Result<sgx_key_128bit_t> {
+    let body = rsgx_self_report().body;
+    let key_request = sgx_key_request_t {
+        key_name: SGX_KEYSELECT_SEAL,
+        key_policy: SGX_KEYPOLICY_MRENCLAVE,
+        isv_svn: body.isv_svn,
+        cpu_svn: body.cpu_svn,
+        attribute_mask: sgx_attributes_t {
+            flags: TSEAL_DEFAULT_FLAGSMASK,
+            xfrm: 0,
+        },
+        key_id: sgx_key_id_t { id: *key_id },
+        misc_mask: TSEAL_DEFAULT_MISCMASK,
+        config_svn: body.config_svn,
+        ..Default::default()
+    };
+    Ok(rsgx_get_key(&key_request)?)
+}
+
+// Sealed layout: key ID | nonce | tag | ciphertext.
+fn seal(plaintext: &[u8]) -> Result<Vec<u8>> {
+    let mut key_id = [0u8; KEY_ID_SIZE];
+    let mut nonce = [0u8; NONCE_SIZE];
+    let mut rng = SgxRng::new()?;
+    rng.fill_bytes(&mut key_id);
+    rng.fill_bytes(&mut nonce);
+    let key = seal_key(&key_id)?;
+    let mut tag = [0u8; TAG_SIZE];
+    let mut ciphertext = vec![0u8; plaintext.len()];
+    rsgx_rijndael128GCM_encrypt(&key, plaintext, &nonce, &[], &mut ciphertext, &mut tag)?;
+    Ok([&key_id[..], &nonce[..], &tag[..], &ciphertext[..]].concat())
+}
+
+fn unseal(sealed: &[u8]) -> Result<Vec<u8>> {
+    if sealed.len() < KEY_ID_SIZE + NONCE_SIZE + TAG_SIZE {
+        return Err(Error::new(
+            ErrorKind::InvalidInputError,
+            "sealed credential is truncated",
+        ));
+    }
+    let (key_id, rest) = sealed.split_at(KEY_ID_SIZE);
+    let (nonce, rest) = rest.split_at(NONCE_SIZE);
+    let (tag, ciphertext) = rest.split_at(TAG_SIZE);
+    let mut key_id_bytes = [0u8; KEY_ID_SIZE];
+    key_id_bytes.copy_from_slice(key_id);
+    let mut tag_bytes = [0u8; TAG_SIZE];
+    tag_bytes.copy_from_slice(tag);
+    let key = seal_key(&key_id_bytes)?;
+    let mut plaintext = vec![0u8; ciphertext.len()];
+    rsgx_rijndael128GCM_decrypt(&key, ciphertext, nonce, &[], &tag_bytes, &mut plaintext)?;
+    Ok(plaintext)
+}
+
 impl RACredential {
-    fn generate_and_endorse() -> Result<RACredential> {
-        let key_pair = teaclave_attestation::key::Secp256k1KeyPair::new()
-            .map_err(|_| Error::from(ErrorKind::RAInternalError))?;
+    // Also returns the report data endorsed for the credential.
+    fn generate_and_endorse() -> Result<(RACredential, Vec<u8>)> {
+        let key_pair = teaclave_attestation::key::Secp256k1KeyPair::new().map_err(|e| {
+            Error::new(
+                ErrorKind::RAInternalError,
+                format!("key pair generation failed: {:?}", e),
+            )
+        })?;
         let report = if cfg!(sgx_sim) {
             teaclave_attestation::IasReport::default()
         } else {
-            match teaclave_attestation::IasReport::new(
+            match teaclave_attestation::IasReport::from_config(
                 key_pair.pub_k,
-                &runtime_config().env.ias_key,
-                &runtime_config().env.ias_spid,
-                false,
+                &attestation_config(),
             ) {
                 Ok(r) => r,
                 Err(e) => {
-                    error!("{:?}", e);
-                    return Err(Error::from(ErrorKind::RAInternalError));
+                    return Err(Error::new(
+                        ErrorKind::RAInternalError,
+                        format!("attestation report endorsement failed: {:?}", e),
+                    ));
                 }
             }
         };
@@ -152,23 +418,102 @@ impl RACredential {
         let prv_key_der = key_pair.private_key_into_der();
         let sha256 = rsgx_sha256_slice(&prv_key_der)?;
 
-        Ok(RACredential {
+        let credential = RACredential {
             cert: cert_der,
             private_key: prv_key_der,
             private_key_sha256: sha256,
-        })
+        };
+        Ok((credential, report_data(&key_pair.pub_k)))
     }
 }
 
 impl RACache {
     fn new(valid_secs: u64) -> Result<RACache> {
-        let ra_credential = RACredential::generate_and_endorse()?;
+        let ra_credential = RACredential::generate_and_endorse();
+        metrics::observe_attestation(ra_credential.is_ok());
+        let (ra_credential, report_data) = ra_credential?;
         let gen_time = SystemTime::now();
         let validity = time::Duration::from_secs(valid_secs);
-        Ok(RACache {
+        let cache = RACache {
             ra_credential,
+            report_data,
             gen_time,
             validity,
+        };
+        // The enclave is served by the new credential even if it cannot be
+        // kept.
+        if let Err(e) = cache.store() {
+            warn!("Cannot keep the endorsed RACredential: {}", e);
+        }
+        Ok(cache)
+    }
+
+    // Keeps the credential in the credential store, if configured.
+    fn store(&self) -> Result<()> {
+        let path = match *CREDENTIAL_STORE.read().unwrap() {
+            Some(ref path) => path.clone(),
+            None => return Ok(()),
+        };
+        let generated_at = self
+            .gen_time
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_err(|_| Error::from(ErrorKind::RAInternalError))?
+            .as_secs();
+        let stored = StoredCredential {
+            cert: self.ra_credential.cert.clone(),
+            private_key: self.ra_credential.private_key.clone(),
+            generated_at,
+            mr_enclave: current_mr_enclave().m.to_vec(),
+            report_data: self.report_data.clone(),
+        };
+        let plaintext = serde_json::to_vec(&stored)?;
+        fs::write(path, seal(&plaintext)?)?;
+        Ok(())
+    }
+
+    // Credential kept in the credential store by a previous run of this
+    // enclave, if its report is younger than `max_age` and endorses the kept
+    // report data for this enclave.
+    fn load(valid_secs: u64, max_age: time::Duration) -> Option<RACache> {
+        let path = CREDENTIAL_STORE.read().unwrap().clone()?;
+        let sealed = fs::read(&path).ok()?;
+        let stored: StoredCredential = match unseal(&sealed)
+            .and_then(|plaintext| serde_json::from_slice(&plaintext).map_err(Error::from))
+        {
+            Ok(stored) => stored,
+            Err(e) => {
+                warn!("Cannot read the kept RACredential {:?}: {}", path, e);
+                return None;
+            }
+        };
+        let gen_time = SystemTime::UNIX_EPOCH + time::Duration::from_secs(stored.generated_at);
+        match SystemTime::now().duration_since(gen_time) {
+            Ok(age) if age < max_age => {}
+            _ => {
+                warn!("Kept RACredential {:?} is too old to be accepted", path);
+                return None;
+            }
+        }
+        let mr_enclave = current_mr_enclave();
+        if stored.mr_enclave[..] != mr_enclave.m[..]
+            || !is_endorsed_for(&stored.cert, &mr_enclave, &stored.report_data)
+        {
+            warn!(
+                "Kept RACredential {:?} is not endorsed for this enclave",
+                path
+            );
+            return None;
+        }
+        let private_key_sha256 = rsgx_sha256_slice(&stored.private_key).ok()?;
+        Some(RACache {
+            ra_credential: RACredential {
+                cert: stored.cert,
+                private_key: stored.private_key,
+                private_key_sha256,
+            },
+            report_data: stored.report_data,
+            gen_time,
+            validity: time::Duration::from_secs(valid_secs),
         })
     }
 
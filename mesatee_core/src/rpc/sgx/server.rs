@@ -15,13 +15,22 @@
 // specific language governing permissions and limitations
 // under the License.
 
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use rustls::Session;
 use std::collections::HashMap;
+use std::net::TcpStream;
 use std::sync::Arc;
 
+use crate::rpc::interceptor::PeerIdentity;
+use crate::rpc::sgx::TlsPolicy;
 use crate::Error;
 use crate::ErrorKind;
 use crate::Result;
+use teaclave_attestation::quote::SgxQuote;
 use teaclave_attestation::verifier::SgxQuoteVerifier;
+use teaclave_config::build_config::BUILD_CONFIG;
 
 use sgx_types::sgx_sha256_hash_t;
 use std::sync::SgxRwLock as RwLock;
@@ -33,47 +42,55 @@ lazy_static! {
         { RwLock::new(ServerConfigCache::default()) };
 }
 
+// Configs of servers not verifying clients have no verifier.
+type ConfigKey = (Option<Arc<SgxQuoteVerifier>>, TlsPolicy);
+
 #[derive(Default)]
 struct ServerConfigCache {
     private_key_sha256: sgx_sha256_hash_t,
-    target_configs: HashMap<Arc<SgxQuoteVerifier>, Arc<rustls::ServerConfig>>,
+    target_configs: HashMap<ConfigKey, Arc<rustls::ServerConfig>>,
 }
 
 pub(crate) fn get_tls_config(
     client_verifier: &Option<SgxQuoteVerifier>,
+    tls_policy: &TlsPolicy,
 ) -> Result<Arc<rustls::ServerConfig>> {
     use crate::rpc::sgx::ra::get_current_ra_credential;
 
     let ra_credential = get_current_ra_credential();
 
-    let client_verifier = match client_verifier {
-        Some(attr) => Arc::new(attr.clone()),
-        None => {
-            let certs = vec![rustls::Certificate(ra_credential.cert)];
-            let privkey = rustls::PrivateKey(ra_credential.private_key);
-            // Build a default authenticator which allow every authenticated client
-            let authenticator = rustls::NoClientAuth::new();
-            let mut cfg = rustls::ServerConfig::new(authenticator);
-            cfg.set_single_cert(certs, privkey)
-                .map_err(|_| Error::from(ErrorKind::TLSError))?;
-            return Ok(Arc::new(cfg));
-        }
-    };
-
+    let cache_key = (
+        client_verifier
+            .as_ref()
+            .map(|c| Arc::new(tls_policy.check_report_age(c.clone()))),
+        tls_policy.clone(),
+    );
+    // Configs, and thus their TLS sessions, are dropped with the RA
+    // credential they present.
     if let Ok(cfg_cache) = SERVER_CONFIG_CACHE.try_read() {
-        if let Some(cfg) = cfg_cache.target_configs.get(&client_verifier) {
-            // Hit Cache. Be quick!
-            return Ok(cfg.clone());
+        if cfg_cache.private_key_sha256 == ra_credential.private_key_sha256 {
+            if let Some(cfg) = cfg_cache.target_configs.get(&cache_key) {
+                // Hit Cache. Be quick!
+                return Ok(cfg.clone());
+            }
         }
     }
 
     let certs = vec![rustls::Certificate(ra_credential.cert)];
     let privkey = rustls::PrivateKey(ra_credential.private_key);
 
-    let mut server_cfg = rustls::ServerConfig::new(client_verifier.clone());
-    server_cfg
-        .set_single_cert(certs, privkey)
-        .map_err(|_| Error::from(ErrorKind::TLSError))?;
+    let mut server_cfg = match cache_key.0 {
+        Some(ref client_verifier) => rustls::ServerConfig::new(client_verifier.clone()),
+        // Build a default authenticator which allow every authenticated client
+        None => rustls::ServerConfig::new(rustls::NoClientAuth::new()),
+    };
+    tls_policy.apply_to_server(&mut server_cfg);
+    server_cfg.set_single_cert(certs, privkey).map_err(|e| {
+        Error::new(
+            ErrorKind::TLSError,
+            format!("TLS config construction failed: {}", e),
+        )
+    })?;
 
     let final_arc = Arc::new(server_cfg);
 
@@ -86,8 +103,55 @@ pub(crate) fn get_tls_config(
         }
         let _ = cfg_cache
             .target_configs
-            .insert(client_verifier, final_arc.clone()); // Overwrite
+            .insert(cache_key, final_arc.clone()); // Overwrite
     }
 
     Ok(final_arc)
 }
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// The quote of the peer certificate, `None` if the peer is not attested.
+fn peer_quote(sess: &rustls::ServerSession) -> Option<std::result::Result<SgxQuote, ()>> {
+    let cert = match sess.get_peer_certificates() {
+        Some(ref certs) if !certs.is_empty() => certs[0].0.clone(),
+        _ => return None,
+    };
+    Some(
+        SgxQuote::extract_verified_quote_with_roots(&cert, BUILD_CONFIG.ias_root_ca_certs)
+            .map_err(|_| ()),
+    )
+}
+
+// Identity of the peer of a connection whose handshake is complete.
+pub(crate) fn peer_identity(tcp: &TcpStream, sess: &rustls::ServerSession) -> PeerIdentity {
+    let mut identity = PeerIdentity {
+        addr: tcp.peer_addr().map(|addr| addr.to_string()).ok(),
+        ..Default::default()
+    };
+    if let Some(Ok(quote)) = peer_quote(sess) {
+        identity.mr_enclave = Some(hex(&quote.body.report_body.mr_enclave));
+        identity.mr_signer = Some(hex(&quote.body.report_body.mr_signer));
+    }
+    identity
+}
+
+// Logs the address of the peer and, for attested peers, its MRENCLAVE.
+pub(crate) fn log_peer(tcp: &TcpStream, sess: &rustls::ServerSession) {
+    let peer = tcp
+        .peer_addr()
+        .map(|addr| addr.to_string())
+        .unwrap_or_else(|_| "unknown peer".to_string());
+    match peer_quote(sess) {
+        Some(Ok(quote)) => debug!(
+            "Inbound connection from {}, MRENCLAVE {}, endorsed by root CA #{}",
+            peer,
+            hex(&quote.body.report_body.mr_enclave),
+            quote.root_ca_index
+        ),
+        Some(Err(_)) => debug!("Inbound connection from {}, quote not verified", peer),
+        None => debug!("Inbound connection from {}, not attested", peer),
+    }
+}
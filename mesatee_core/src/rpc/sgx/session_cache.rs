@@ -0,0 +1,176 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Sessions of trusted channels kept for TLS session resumption.
+//!
+//! A resumed handshake skips the key exchange and the verification of the
+//! peer's RA certificate, which is where most of the cost of a full attested
+//! handshake goes. The attestation of the peer is thus the one verified by
+//! the full handshake that created the session, and sessions expire
+//! `window` after it so that a peer is attested again regularly.
+//!
+//! Each server and client TLS config has its own cache, and configs are
+//! replaced once the RA credential of the enclave is renewed. A session
+//! therefore never outlives the attestation report of either side: a peer
+//! with a renewed report has an empty cache and runs a full handshake.
+//!
+//! Servers only issue session IDs and stateful TLS 1.3 tickets, which are
+//! looked up in the cache. Stateless tickets are not issued, since their
+//! lifetime could not be enforced here.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use std::collections::HashMap;
+#[cfg(not(feature = "mesalock_sgx"))]
+use std::sync::Mutex;
+#[cfg(feature = "mesalock_sgx")]
+use std::sync::SgxMutex as Mutex;
+use std::time::{Duration, SystemTime};
+#[cfg(feature = "mesalock_sgx")]
+use std::untrusted::time::SystemTimeEx;
+
+struct Session {
+    value: Vec<u8>,
+    expires_at: SystemTime,
+}
+
+pub(crate) struct SessionCache {
+    sessions: Mutex<HashMap<Vec<u8>, Session>>,
+    capacity: usize,
+    window: Duration,
+}
+
+impl SessionCache {
+    /// Keeps up to `capacity` sessions, each resumable for `window`. A zero
+    /// `window` or `capacity` disables resumption.
+    pub(crate) fn new(capacity: usize, window: Duration) -> Self {
+        SessionCache {
+            sessions: Mutex::new(HashMap::new()),
+            capacity,
+            window,
+        }
+    }
+
+    fn is_disabled(&self) -> bool {
+        self.capacity == 0 || self.window == Duration::from_secs(0)
+    }
+
+    fn insert(&self, key: Vec<u8>, value: Vec<u8>) -> bool {
+        if self.is_disabled() {
+            return false;
+        }
+        let now = SystemTime::now();
+        let mut sessions = match self.sessions.lock() {
+            Ok(sessions) => sessions,
+            Err(_) => return false,
+        };
+        if !sessions.contains_key(&key) && sessions.len() >= self.capacity {
+            sessions.retain(|_, session| session.expires_at > now);
+        }
+        if !sessions.contains_key(&key) && sessions.len() >= self.capacity {
+            // Drop the session expiring first
+            let oldest = sessions
+                .iter()
+                .min_by_key(|(_, session)| session.expires_at)
+                .map(|(key, _)| key.to_owned());
+            if let Some(oldest) = oldest {
+                sessions.remove(&oldest);
+            }
+        }
+        let session = Session {
+            value,
+            expires_at: now + self.window,
+        };
+        sessions.insert(key, session);
+        true
+    }
+
+    fn lookup(&self, key: &[u8], remove: bool) -> Option<Vec<u8>> {
+        let mut sessions = self.sessions.lock().ok()?;
+        let expired = match sessions.get(key) {
+            Some(session) => session.expires_at <= SystemTime::now(),
+            None => return None,
+        };
+        if expired {
+            debug!("TLS session expired, the peer is attested again");
+            sessions.remove(key);
+            return None;
+        }
+        if remove {
+            return sessions.remove(key).map(|session| session.value);
+        }
+        sessions.get(key).map(|session| session.value.clone())
+    }
+}
+
+impl rustls::StoresServerSessions for SessionCache {
+    fn put(&self, key: Vec<u8>, value: Vec<u8>) -> bool {
+        self.insert(key, value)
+    }
+
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.lookup(key, false)
+    }
+
+    fn take(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.lookup(key, true)
+    }
+}
+
+impl rustls::StoresClientSessions for SessionCache {
+    fn put(&self, key: Vec<u8>, value: Vec<u8>) -> bool {
+        self.insert(key, value)
+    }
+
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.lookup(key, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_expires_after_window() {
+        let cache = SessionCache::new(2, Duration::from_millis(20));
+        assert!(cache.insert(b"id".to_vec(), b"session".to_vec()));
+        assert_eq!(cache.lookup(b"id", false), Some(b"session".to_vec()));
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(cache.lookup(b"id", false), None);
+    }
+
+    #[test]
+    fn test_full_cache_drops_oldest_session() {
+        let cache = SessionCache::new(2, Duration::from_secs(60));
+        assert!(cache.insert(b"a".to_vec(), b"1".to_vec()));
+        std::thread::sleep(Duration::from_millis(2));
+        assert!(cache.insert(b"b".to_vec(), b"2".to_vec()));
+        assert!(cache.insert(b"c".to_vec(), b"3".to_vec()));
+        assert_eq!(cache.lookup(b"a", false), None);
+        assert_eq!(cache.lookup(b"c", true), Some(b"3".to_vec()));
+        assert_eq!(cache.lookup(b"c", false), None);
+    }
+
+    #[test]
+    fn test_zero_window_disables_resumption() {
+        let cache = SessionCache::new(2, Duration::from_secs(0));
+        assert!(!cache.insert(b"id".to_vec(), b"session".to_vec()));
+        assert_eq!(cache.lookup(b"id", false), None);
+    }
+}
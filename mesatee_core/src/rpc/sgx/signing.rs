@@ -0,0 +1,80 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Signatures made with the key of the RA certificate of an enclave.
+//!
+//! The public key of the certificate is bound to the attestation report it
+//! embeds, so a signature that verifies against the certificate was made by
+//! the attested enclave. Signatures are ASN.1 encoded ECDSA P-256 signatures
+//! of the SHA-256 digest of the message, and can be checked offline by anyone
+//! holding the certificate.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use crate::{Error, ErrorKind, Result};
+use teaclave_attestation::verifier::{EnclaveAttr, SgxQuoteVerifier};
+
+/// Signs `message` with the private key of the current RA certificate.
+/// Returns the signature and the DER certificate to verify it with.
+#[cfg(feature = "mesalock_sgx")]
+pub fn sign_with_ra_key(message: &[u8]) -> Result<(Vec<u8>, Vec<u8>)> {
+    use ring::signature::{EcdsaKeyPair, ECDSA_P256_SHA256_ASN1_SIGNING};
+
+    let credential = super::ra::get_current_ra_credential();
+    let key_pair =
+        EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &credential.private_key)
+            .map_err(|_| Error::new(ErrorKind::CryptoError, "cannot load the RA key"))?;
+    let rng = ring::rand::SystemRandom::new();
+    let signature = key_pair
+        .sign(&rng, message)
+        .map_err(|_| Error::new(ErrorKind::CryptoError, "cannot sign with the RA key"))?;
+    Ok((signature.as_ref().to_vec(), credential.cert))
+}
+
+/// Verifies that `signature` of `message` was made with the key of the RA
+/// certificate `cert_der`, and that the certificate was issued by one of the
+/// enclaves of `enclave_attr`, as the peer of a channel is verified.
+pub fn verify_ra_signature(
+    enclave_attr: EnclaveAttr,
+    cert_der: &[u8],
+    message: &[u8],
+    signature: &[u8],
+) -> Result<()> {
+    if !SgxQuoteVerifier::new(enclave_attr).verify_cert(cert_der) {
+        return Err(Error::new(
+            ErrorKind::CryptoError,
+            "the certificate is not issued by an accepted enclave",
+        ));
+    }
+    let cert = webpki::EndEntityCert::from(cert_der)
+        .map_err(|_| Error::new(ErrorKind::CryptoError, "malformed certificate"))?;
+    cert.verify_signature(&webpki::ECDSA_P256_SHA256, message, signature)
+        .map_err(|_| Error::new(ErrorKind::CryptoError, "bad signature"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_malformed_certificate_is_rejected() {
+        let enclave_attr = EnclaveAttr { measures: vec![] };
+        let result = verify_ra_signature(enclave_attr, b"not a certificate", b"message", b"sig");
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,150 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Attestation of an enclave when it starts.
+//!
+//! An enclave retries endorsing its report with exponential backoff, since
+//! the attestation service may be briefly unreachable while a cluster starts.
+//! Once all attempts fail, the enclave may start with the credential it kept
+//! from a previous run, as long as its report is still fresh enough for peers
+//! to accept it, instead of failing to start during an outage.
+
+use std::time::Duration;
+
+use crate::rpc::channel::RetryPolicy;
+use crate::Result;
+
+/// Credential an enclave starts with.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Attested<T> {
+    /// Endorsed by the attestation service at startup.
+    Endorsed(T),
+    /// Kept from a previous run, because the attestation service could not
+    /// be reached.
+    Cached(T),
+}
+
+pub(crate) fn attest_at_startup<T, A, C, S>(
+    retry_policy: &RetryPolicy,
+    allow_cached: bool,
+    mut attest: A,
+    cached: C,
+    mut sleep: S,
+) -> Result<Attested<T>>
+where
+    A: FnMut() -> Result<T>,
+    C: FnOnce() -> Option<T>,
+    S: FnMut(Duration),
+{
+    let mut attempt = 1;
+    let err = loop {
+        match attest() {
+            Ok(credential) => return Ok(Attested::Endorsed(credential)),
+            Err(e) if attempt >= retry_policy.max_attempts => break e,
+            Err(e) => {
+                warn!(
+                    "attestation attempt {} of {} failed: {}",
+                    attempt, retry_policy.max_attempts, e
+                );
+                sleep(retry_policy.delay(attempt));
+                attempt += 1;
+            }
+        }
+    };
+    if !allow_cached {
+        return Err(err);
+    }
+    match cached() {
+        Some(credential) => {
+            warn!(
+                "attestation service unreachable ({}), starting with cached RA credential",
+                err
+            );
+            Ok(Attested::Cached(credential))
+        }
+        None => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Error, ErrorKind};
+
+    fn outage() -> Result<&'static str> {
+        Err(Error::from(ErrorKind::RAInternalError))
+    }
+
+    #[test]
+    fn test_outage_starts_with_valid_cache() {
+        let policy = RetryPolicy::new(3, Duration::from_millis(10));
+        let mut attempts = 0;
+        let mut sleeps = Vec::new();
+        let attested = attest_at_startup(
+            &policy,
+            true,
+            || {
+                attempts += 1;
+                outage()
+            },
+            || Some("cached"),
+            |delay| sleeps.push(delay),
+        );
+        assert_eq!(attested.unwrap(), Attested::Cached("cached"));
+        assert_eq!(attempts, 3);
+        assert_eq!(
+            sleeps,
+            vec![Duration::from_millis(10), Duration::from_millis(20)]
+        );
+    }
+
+    #[test]
+    fn test_outage_without_cache_fails() {
+        let policy = RetryPolicy::new(2, Duration::from_millis(10));
+        let attested = attest_at_startup(&policy, true, outage, || None, |_| ());
+        assert_eq!(attested.unwrap_err().kind(), ErrorKind::RAInternalError);
+    }
+
+    #[test]
+    fn test_outage_fails_if_cache_not_allowed() {
+        let policy = RetryPolicy::new(2, Duration::from_millis(10));
+        let attested = attest_at_startup(&policy, false, outage, || Some("cached"), |_| ());
+        assert!(attested.is_err());
+    }
+
+    #[test]
+    fn test_recovered_service_endorses() {
+        let policy = RetryPolicy::new(3, Duration::from_millis(10));
+        let mut attempts = 0;
+        let attested = attest_at_startup(
+            &policy,
+            true,
+            || {
+                attempts += 1;
+                if attempts < 2 {
+                    outage()
+                } else {
+                    Ok("endorsed")
+                }
+            },
+            || Some("cached"),
+            |_| (),
+        );
+        assert_eq!(attested.unwrap(), Attested::Endorsed("endorsed"));
+        assert_eq!(attempts, 2);
+    }
+}
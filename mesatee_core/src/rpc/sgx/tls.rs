@@ -0,0 +1,347 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! TLS protocol versions and cipher suites of trusted channels.
+//!
+//! The RA certificates of enclaves carry a P-256 ECDSA key, so the suites of
+//! the vendored rustls that can be negotiated are:
+//!
+//! - TLS 1.3: `TLS13_CHACHA20_POLY1305_SHA256`, `TLS13_AES_256_GCM_SHA384`
+//!   and `TLS13_AES_128_GCM_SHA256`. The RA certificate is signed with
+//!   `ecdsa_secp256r1_sha256`, which TLS 1.3 accepts, so attestation works
+//!   the same under a TLS 1.3-only policy.
+//! - TLS 1.2: `TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256`,
+//!   `TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384` and
+//!   `TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256`. The `ECDHE_RSA` suites are
+//!   rejected since no enclave holds an RSA key.
+//!
+//! The policy also sets how long sessions may be resumed, see
+//! `rpc::sgx::session_cache`, and how old the attestation report of a peer
+//! may be. A report is only accepted by a full handshake if it stays young
+//! enough for the whole resumption window, so that no session is resumed past
+//! the maximum age of the report that attested it.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+use rustls::internal::msgs::enums::SignatureAlgorithm;
+use rustls::{CipherSuite, ProtocolVersion, SupportedCipherSuite};
+use teaclave_attestation::verifier::SgxQuoteVerifier;
+
+use crate::config::{is_runtime_config_initialized, runtime_config};
+use crate::rpc::sgx::session_cache::SessionCache;
+use crate::{Error, ErrorKind, Result};
+
+lazy_static! {
+    // Clients such as the SDK may run without a runtime config
+    static ref RUNTIME_POLICY: TlsPolicy = if is_runtime_config_initialized() {
+        TlsPolicy::from_runtime_config().unwrap_or_default()
+    } else {
+        TlsPolicy::default()
+    };
+}
+
+// Same as the defaults of the runtime config.
+const DEFAULT_SESSION_RESUMPTION_WINDOW: Duration = Duration::from_secs(60);
+const DEFAULT_SESSION_CACHE_SIZE: usize = 256;
+const DEFAULT_MAX_REPORT_AGE: Duration = Duration::from_secs(2 * 86400);
+const DEFAULT_CLOCK_SKEW: Duration = Duration::from_secs(60);
+
+/// Protocol versions and cipher suites of a TLS client or server. Without
+/// restrictions each side keeps its default: servers accept TLS 1.2 and 1.3,
+/// clients offer TLS 1.2, and all suites listed above are enabled.
+#[derive(Clone, Debug)]
+pub struct TlsPolicy {
+    versions: Option<Vec<ProtocolVersion>>,
+    cipher_suites: Option<Vec<&'static SupportedCipherSuite>>,
+    session_resumption_window: Duration,
+    session_cache_size: usize,
+    max_report_age: Duration,
+    clock_skew: Duration,
+}
+
+impl Default for TlsPolicy {
+    fn default() -> Self {
+        TlsPolicy {
+            versions: None,
+            cipher_suites: None,
+            session_resumption_window: DEFAULT_SESSION_RESUMPTION_WINDOW,
+            session_cache_size: DEFAULT_SESSION_CACHE_SIZE,
+            max_report_age: DEFAULT_MAX_REPORT_AGE,
+            clock_skew: DEFAULT_CLOCK_SKEW,
+        }
+    }
+}
+
+impl TlsPolicy {
+    /// Policy of the `[tls]` section of the runtime config, or the default
+    /// without a runtime config.
+    pub fn runtime() -> TlsPolicy {
+        RUNTIME_POLICY.clone()
+    }
+
+    /// Builds the policy of the runtime config, failing on invalid settings.
+    pub fn from_runtime_config() -> Result<TlsPolicy> {
+        let tls_config = &runtime_config().tls;
+        let mut policy = TlsPolicy::default();
+        if let Some(ref version) = tls_config.min_protocol_version {
+            let version = match version.as_str() {
+                "1.2" => ProtocolVersion::TLSv1_2,
+                "1.3" => ProtocolVersion::TLSv1_3,
+                _ => {
+                    return Err(Error::new(
+                        ErrorKind::TLSError,
+                        format!("unsupported TLS version {}", version),
+                    ))
+                }
+            };
+            policy = policy.min_protocol_version(version)?;
+        }
+        if !tls_config.cipher_suites.is_empty() {
+            let mut suites = Vec::new();
+            for name in tls_config.cipher_suites.iter() {
+                let suite = rustls::ALL_CIPHERSUITES
+                    .iter()
+                    .find(|suite| format!("{:?}", suite.suite) == *name)
+                    .ok_or_else(|| {
+                        Error::new(
+                            ErrorKind::TLSError,
+                            format!("unknown cipher suite {}", name),
+                        )
+                    })?;
+                suites.push(suite.suite);
+            }
+            policy = policy.cipher_suites(&suites)?;
+        }
+        policy = policy.session_resumption(
+            Duration::from_secs(tls_config.session_resumption_window),
+            tls_config.session_cache_size,
+        );
+
+        // A resumed session trusts the attestation of its full handshake, so
+        // sessions must expire long before the report gets too old.
+        let attestation_config = &runtime_config().attestation;
+        if tls_config.session_resumption_window > attestation_config.max_report_age / 10 {
+            return Err(Error::new(
+                ErrorKind::TLSError,
+                "session_resumption_window must not exceed a tenth of max_report_age",
+            ));
+        }
+
+        // Peers would reject the reports this enclave presents from its cache
+        let youngest_rejected = attestation_config
+            .report_cache_ttl
+            .saturating_add(tls_config.session_resumption_window);
+        if attestation_config.max_report_age <= youngest_rejected {
+            return Err(Error::new(
+                ErrorKind::TLSError,
+                "max_report_age must exceed report_cache_ttl plus session_resumption_window",
+            ));
+        }
+        policy = policy
+            .max_report_age(Duration::from_secs(attestation_config.max_report_age))
+            .clock_skew(Duration::from_secs(runtime_config().clock.skew_tolerance));
+        Ok(policy)
+    }
+
+    /// Rejects peers presenting an attestation report issued more than `age`
+    /// ago, when they connect or resume a session.
+    pub fn max_report_age(mut self, age: Duration) -> Self {
+        self.max_report_age = age;
+        self
+    }
+
+    /// Tolerates a disagreement of up to `skew` between the local clock and
+    /// the clock of the attestation service when checking the age of reports.
+    pub fn clock_skew(mut self, skew: Duration) -> Self {
+        self.clock_skew = skew;
+        self
+    }
+
+    /// `verifier` checking the age of the reports of the peers by this
+    /// policy. The age is checked at the full handshake, for the report to be
+    /// young enough until the session may no longer be resumed.
+    pub(crate) fn check_report_age(&self, verifier: SgxQuoteVerifier) -> SgxQuoteVerifier {
+        let max_age = self
+            .max_report_age
+            .checked_sub(self.session_resumption_window)
+            .unwrap_or_default();
+        verifier.max_report_age(max_age).clock_skew(self.clock_skew)
+    }
+
+    /// Lets peers resume sessions for `window` after the full handshake
+    /// attesting them, keeping up to `cache_size` sessions per TLS config.
+    /// A zero `window` disables resumption.
+    pub fn session_resumption(mut self, window: Duration, cache_size: usize) -> Self {
+        self.session_resumption_window = window;
+        self.session_cache_size = cache_size;
+        self
+    }
+
+    fn session_cache(&self) -> Arc<SessionCache> {
+        Arc::new(SessionCache::new(
+            self.session_cache_size,
+            self.session_resumption_window,
+        ))
+    }
+
+    /// Disables protocol versions older than `version`, which must be TLS 1.2
+    /// or TLS 1.3.
+    pub fn min_protocol_version(mut self, version: ProtocolVersion) -> Result<Self> {
+        let versions = match version {
+            ProtocolVersion::TLSv1_2 => vec![ProtocolVersion::TLSv1_3, ProtocolVersion::TLSv1_2],
+            ProtocolVersion::TLSv1_3 => vec![ProtocolVersion::TLSv1_3],
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::TLSError,
+                    format!("unsupported TLS version {:?}", version),
+                ))
+            }
+        };
+        self.versions = Some(versions);
+        self.check()?;
+        Ok(self)
+    }
+
+    /// Restricts the cipher suites to `suites`, in order of preference.
+    pub fn cipher_suites(mut self, suites: &[CipherSuite]) -> Result<Self> {
+        if suites.is_empty() {
+            return Err(Error::new(
+                ErrorKind::TLSError,
+                "no cipher suites are enabled",
+            ));
+        }
+        let mut supported = Vec::new();
+        for suite in suites {
+            let found = rustls::ALL_CIPHERSUITES
+                .iter()
+                .find(|supported| supported.suite == *suite)
+                .filter(|supported| supported.usable_for_sigalg(SignatureAlgorithm::ECDSA))
+                .ok_or_else(|| {
+                    Error::new(
+                        ErrorKind::TLSError,
+                        format!("cipher suite {:?} is not supported", suite),
+                    )
+                })?;
+            supported.push(*found);
+        }
+        self.cipher_suites = Some(supported);
+        self.check()?;
+        Ok(self)
+    }
+
+    // Every enabled version needs a suite, and every suite an enabled version.
+    fn check(&self) -> Result<()> {
+        let (versions, suites) = match (&self.versions, &self.cipher_suites) {
+            (Some(versions), Some(suites)) => (versions, suites),
+            _ => return Ok(()),
+        };
+        for version in versions {
+            if !suites
+                .iter()
+                .any(|suite| suite.usable_for_version(*version))
+            {
+                return Err(Error::new(
+                    ErrorKind::TLSError,
+                    format!("no enabled cipher suite supports {:?}", version),
+                ));
+            }
+        }
+        for suite in suites {
+            if !versions
+                .iter()
+                .any(|version| suite.usable_for_version(*version))
+            {
+                return Err(Error::new(
+                    ErrorKind::TLSError,
+                    format!(
+                        "cipher suite {:?} needs a disabled TLS version",
+                        suite.suite
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn apply_to_server(&self, config: &mut rustls::ServerConfig) {
+        if let Some(ref versions) = self.versions {
+            config.versions = versions.clone();
+        }
+        if let Some(ref suites) = self.cipher_suites {
+            config.ciphersuites = suites.clone();
+        }
+        config.session_storage = self.session_cache();
+    }
+
+    pub(crate) fn apply_to_client(&self, config: &mut rustls::ClientConfig) {
+        if let Some(ref versions) = self.versions {
+            config.versions = versions.clone();
+        }
+        if let Some(ref suites) = self.cipher_suites {
+            config.ciphersuites = suites.clone();
+        }
+        config.set_persistence(self.session_cache());
+    }
+
+    fn key(
+        &self,
+    ) -> (
+        Option<Vec<u16>>,
+        Option<Vec<u16>>,
+        Duration,
+        usize,
+        Duration,
+        Duration,
+    ) {
+        let versions = self
+            .versions
+            .as_ref()
+            .map(|versions| versions.iter().map(|version| version.get_u16()).collect());
+        let suites = self
+            .cipher_suites
+            .as_ref()
+            .map(|suites| suites.iter().map(|suite| suite.suite.get_u16()).collect());
+        (
+            versions,
+            suites,
+            self.session_resumption_window,
+            self.session_cache_size,
+            self.max_report_age,
+            self.clock_skew,
+        )
+    }
+}
+
+impl PartialEq for TlsPolicy {
+    fn eq(&self, other: &TlsPolicy) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for TlsPolicy {}
+
+impl Hash for TlsPolicy {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
@@ -0,0 +1,241 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Worker enclaves pulling tasks from a scheduler.
+//!
+//! A worker registers with the number of tasks it runs at once, and is then
+//! handed tasks while it has room for them, so that the load is spread by
+//! capacity across the workers. A worker which sends no heartbeat for the
+//! heartbeat timeout is considered gone: it is removed with the tasks it was
+//! handed, which the scheduler queues again. Times are seconds since the
+//! epoch, passed in by the caller.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use crate::{Error, ErrorKind, Result};
+use std::collections::{BTreeMap, BTreeSet};
+#[cfg(not(feature = "mesalock_sgx"))]
+use std::sync::Mutex;
+#[cfg(feature = "mesalock_sgx")]
+use std::sync::SgxMutex as Mutex;
+
+struct Worker {
+    capacity: usize,
+    last_seen: u64,
+    in_flight: BTreeSet<String>,
+}
+
+struct Workers {
+    next_id: u64,
+    workers: BTreeMap<String, Worker>,
+}
+
+pub struct WorkerPool {
+    workers: Mutex<Workers>,
+    heartbeat_timeout: u64,
+}
+
+impl WorkerPool {
+    pub fn new(heartbeat_timeout: u64) -> Self {
+        WorkerPool {
+            workers: Mutex::new(Workers {
+                next_id: 0,
+                workers: BTreeMap::new(),
+            }),
+            heartbeat_timeout,
+        }
+    }
+
+    /// Seconds a worker may go without a heartbeat before it is removed.
+    pub fn heartbeat_timeout(&self) -> u64 {
+        self.heartbeat_timeout
+    }
+
+    /// Registers a worker running up to `capacity` tasks at once, and
+    /// returns its ID.
+    pub fn register(&self, capacity: usize, now: u64) -> Result<String> {
+        if capacity == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInputError,
+                "worker capacity must be positive",
+            ));
+        }
+        let mut workers = self.workers.lock()?;
+        workers.next_id += 1;
+        let worker_id = format!("worker-{}", workers.next_id);
+        workers.workers.insert(
+            worker_id.clone(),
+            Worker {
+                capacity,
+                last_seen: now,
+                in_flight: BTreeSet::new(),
+            },
+        );
+        Ok(worker_id)
+    }
+
+    /// Records a heartbeat of `worker_id`. Returns false if the worker is not
+    /// registered, e.g. because it was removed after missing its heartbeats,
+    /// in which case it has to register again.
+    pub fn heartbeat(&self, worker_id: &str, now: u64) -> Result<bool> {
+        let mut workers = self.workers.lock()?;
+        match workers.workers.get_mut(worker_id) {
+            Some(worker) => {
+                worker.last_seen = now;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Whether `worker_id` may be handed another task. Pulling counts as a
+    /// heartbeat. Fails with `NotFound` if the worker is not registered.
+    pub fn has_room(&self, worker_id: &str, now: u64) -> Result<bool> {
+        let mut workers = self.workers.lock()?;
+        let worker = workers
+            .workers
+            .get_mut(worker_id)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "worker is not registered"))?;
+        worker.last_seen = now;
+        Ok(worker.in_flight.len() < worker.capacity)
+    }
+
+    /// Records that `task_id` was handed to `worker_id`.
+    pub fn assign(&self, worker_id: &str, task_id: &str) -> Result<()> {
+        let mut workers = self.workers.lock()?;
+        let worker = workers
+            .workers
+            .get_mut(worker_id)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "worker is not registered"))?;
+        worker.in_flight.insert(task_id.to_owned());
+        Ok(())
+    }
+
+    /// Called when `task_id` no longer runs on its worker, e.g. it ended or
+    /// was cancelled.
+    pub fn complete(&self, task_id: &str) -> Result<()> {
+        let mut workers = self.workers.lock()?;
+        for worker in workers.workers.values_mut() {
+            if worker.in_flight.remove(task_id) {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Worker `task_id` was handed to, if any.
+    pub fn worker_of(&self, task_id: &str) -> Result<Option<String>> {
+        let workers = self.workers.lock()?;
+        Ok(workers
+            .workers
+            .iter()
+            .find(|(_, worker)| worker.in_flight.contains(task_id))
+            .map(|(worker_id, _)| worker_id.clone()))
+    }
+
+    /// Removes the workers whose last heartbeat is older than the heartbeat
+    /// timeout, and returns the tasks they were handed.
+    pub fn expire(&self, now: u64) -> Result<Vec<String>> {
+        let mut workers = self.workers.lock()?;
+        let timeout = self.heartbeat_timeout;
+        let gone: Vec<String> = workers
+            .workers
+            .iter()
+            .filter(|(_, worker)| now.saturating_sub(worker.last_seen) > timeout)
+            .map(|(worker_id, _)| worker_id.clone())
+            .collect();
+        let mut tasks = Vec::new();
+        for worker_id in gone.iter() {
+            if let Some(worker) = workers.workers.remove(worker_id) {
+                tasks.extend(worker.in_flight.into_iter());
+            }
+        }
+        Ok(tasks)
+    }
+
+    /// Number of registered workers.
+    pub fn len(&self) -> Result<usize> {
+        Ok(self.workers.lock()?.workers.len())
+    }
+
+    pub fn is_empty(&self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workers_register_with_distinct_ids() {
+        let pool = WorkerPool::new(30);
+        let first = pool.register(1, 100).unwrap();
+        let second = pool.register(1, 100).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(pool.len().unwrap(), 2);
+        assert!(pool.heartbeat(&first, 110).unwrap());
+        assert!(!pool.heartbeat("worker-unknown", 110).unwrap());
+        assert_eq!(
+            pool.has_room("worker-unknown", 110).unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+        assert_eq!(
+            pool.register(0, 100).unwrap_err().kind(),
+            ErrorKind::InvalidInputError
+        );
+    }
+
+    #[test]
+    fn test_tasks_are_spread_by_capacity() {
+        let pool = WorkerPool::new(30);
+        let first = pool.register(1, 100).unwrap();
+        let second = pool.register(1, 100).unwrap();
+
+        assert!(pool.has_room(&first, 100).unwrap());
+        pool.assign(&first, "task-1").unwrap();
+        assert!(!pool.has_room(&first, 100).unwrap());
+        assert!(pool.has_room(&second, 100).unwrap());
+        pool.assign(&second, "task-2").unwrap();
+        assert_eq!(pool.worker_of("task-1").unwrap(), Some(first.clone()));
+        assert_eq!(pool.worker_of("task-2").unwrap(), Some(second));
+
+        pool.complete("task-1").unwrap();
+        assert!(pool.has_room(&first, 100).unwrap());
+        assert_eq!(pool.worker_of("task-1").unwrap(), None);
+    }
+
+    #[test]
+    fn test_tasks_of_a_gone_worker_are_handed_to_another() {
+        let pool = WorkerPool::new(30);
+        let first = pool.register(1, 100).unwrap();
+        let second = pool.register(1, 100).unwrap();
+        pool.assign(&first, "task-1").unwrap();
+
+        // Only the second worker keeps sending heartbeats
+        assert!(pool.heartbeat(&second, 120).unwrap());
+        assert!(pool.expire(130).unwrap().is_empty());
+        assert_eq!(pool.expire(140).unwrap(), vec!["task-1".to_string()]);
+        assert!(!pool.heartbeat(&first, 140).unwrap());
+        assert_eq!(pool.len().unwrap(), 1);
+
+        assert!(pool.has_room(&second, 140).unwrap());
+        pool.assign(&second, "task-1").unwrap();
+        assert_eq!(pool.worker_of("task-1").unwrap(), Some(second));
+    }
+}
@@ -207,7 +207,10 @@ use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use tdfs_external_client::TDFSClient;
 use teaclave_utils;
-use tms_external_client::TMSClient;
+/// Typed client of Task Management Service with one method per request,
+/// for applications which need more than `Mesatee`, built with
+/// `TMSClient::builder()`.
+pub use tms_external_client::{TMSClient, TMSClientBuilder};
 pub use tms_external_proto::TaskStatus;
 
 /// `Mesatee` stands for a connection to MesaTEE Service
@@ -382,7 +385,7 @@ impl Mesatee {
                     enclave_info.enclave_info_file_path
                 )
             });
-        let enclave_identities = teaclave_utils::load_enclave_info(&enclave_info_content);
+        let enclave_identities = teaclave_utils::load_enclave_info(&enclave_info_content)?;
 
         let tms_outbound_desc = OutboundDesc::new(
             *enclave_identities
@@ -21,13 +21,30 @@ use std::prelude::v1::*;
 
 use acs_proto::*;
 use mesatee_core::config::{OutboundDesc, TargetDesc};
-use mesatee_core::rpc::channel::SgxTrustedChannel;
+use mesatee_core::rpc::channel::{SgxTrustedChannel, SgxTrustedChannelPool};
 use mesatee_core::{Error, ErrorKind, Result};
 
 use std::collections::HashSet;
+use std::sync::Arc;
+
+pub type ACSChannelPool = SgxTrustedChannelPool<ACSRequest, ACSResponse>;
+
+enum ACSChannel {
+    Dedicated(SgxTrustedChannel<ACSRequest, ACSResponse>),
+    Pooled(Arc<ACSChannelPool>),
+}
+
+impl ACSChannel {
+    fn invoke(&mut self, req: ACSRequest) -> Result<ACSResponse> {
+        match self {
+            ACSChannel::Dedicated(channel) => channel.invoke(req),
+            ACSChannel::Pooled(pool) => pool.invoke(req),
+        }
+    }
+}
 
 pub struct ACSClient {
-    channel: SgxTrustedChannel<ACSRequest, ACSResponse>,
+    channel: ACSChannel,
 }
 
 impl ACSClient {
@@ -38,7 +55,17 @@ impl ACSClient {
                 SgxTrustedChannel::<ACSRequest, ACSResponse>::new(addr, enclave_addr)?
             }
         };
-        Ok(ACSClient { channel })
+        Ok(ACSClient {
+            channel: ACSChannel::Dedicated(channel),
+        })
+    }
+
+    /// Sends requests over connections borrowed from a shared pool instead
+    /// of a connection owned by this client.
+    pub fn with_pool(pool: Arc<ACSChannelPool>) -> Self {
+        ACSClient {
+            channel: ACSChannel::Pooled(pool),
+        }
     }
 
     pub fn enforce_task_launch(
@@ -90,6 +117,27 @@ impl ACSClient {
         }
     }
 
+    /// Whether `usr` may read `data` as its owner or as a member of a group
+    /// owning it.
+    pub fn enforce_data_read(&mut self, usr: String, data: String) -> Result<bool> {
+        let req = ACSRequest::Enforce(EnforceRequest::ReadData(usr, data));
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            ACSResponse::Enforce(allow) => Ok(allow),
+            _ => Err(Error::from(ErrorKind::RPCResponseError)),
+        }
+    }
+
+    /// Whether `usr` may run `script` in their tasks.
+    pub fn enforce_script_use(&mut self, usr: String, script: String) -> Result<bool> {
+        let req = ACSRequest::Enforce(EnforceRequest::UseScript(usr, script));
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            ACSResponse::Enforce(allow) => Ok(allow),
+            _ => Err(Error::from(ErrorKind::RPCResponseError)),
+        }
+    }
+
     fn _announce_terms(&mut self, facts: Vec<AccessControlTerms>) -> Result<()> {
         let req = ACSRequest::Announce(AnnounceRequest { facts });
         let resp = self.channel.invoke(req)?;
@@ -133,4 +181,20 @@ impl ACSClient {
         terms.push(AccessControlTerms::ScriptOwner(script, creator));
         self._announce_terms(terms)
     }
+
+    pub fn announce_group_members(&mut self, group: String, members: &[String]) -> Result<()> {
+        let facts = members
+            .iter()
+            .map(|usr| AccessControlTerms::GroupMember(group.clone(), usr.clone()))
+            .collect();
+        self._announce_terms(facts)
+    }
+
+    pub fn announce_data_group(&mut self, data: String, group: String) -> Result<()> {
+        self._announce_terms(std::vec!(AccessControlTerms::DataGroup(data, group)))
+    }
+
+    pub fn announce_script_group(&mut self, script: String, group: String) -> Result<()> {
+        self._announce_terms(std::vec!(AccessControlTerms::ScriptGroup(script, group)))
+    }
 }
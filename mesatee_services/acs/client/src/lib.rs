@@ -21,4 +21,4 @@
 extern crate sgx_tstd as std;
 
 mod acs_client;
-pub use acs_client::ACSClient;
+pub use acs_client::{ACSChannelPool, ACSClient};
@@ -52,6 +52,12 @@ pub enum EnforceRequest {
 
     // delete_script = usr, script
     DeleteScript(String, String),
+
+    // read_data = usr, data
+    ReadData(String, String),
+
+    // use_script = usr, script
+    UseScript(String, String),
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -75,4 +81,13 @@ pub enum AccessControlTerms {
 
     // is_public_script = script
     IsPublicScript(String),
+
+    // group_member = group, usr
+    GroupMember(String, String),
+
+    // data_group = data, group
+    DataGroup(String, String),
+
+    // script_group = script, group
+    ScriptGroup(String, String),
 }
@@ -80,3 +80,26 @@ if __name__ == '__main__':
     assert not acs_enforce_request('delete_data', repr([FUSION_TASK_SCRIPT_WRITER, IRRELEVANT_DATA]))
     assert acs_enforce_request('delete_script', repr([FUSION_TASK_SCRIPT_WRITER, FUSION_TASK_SCRIPT]))
     assert not acs_enforce_request('delete_script', repr([FUSION_TASK_SCRIPT_WRITER, PUBLIC_SCRIPT]))
+
+    GROUP                     = "group_analysts"
+    GROUP_DATA                = "data_group"
+    GROUP_SCRIPT              = "script_group"
+
+    acs_announce_fact('group_member', repr([GROUP, FUSION_TASK_PARTY_1]))
+    acs_announce_fact('group_member', repr([GROUP, FUSION_TASK_PARTY_2]))
+    acs_announce_fact('data_owner', repr([GROUP_DATA, FUSION_TASK_PARTY_1]))
+    acs_announce_fact('data_group', repr([GROUP_DATA, GROUP]))
+    acs_announce_fact('script_owner', repr([GROUP_SCRIPT, FUSION_TASK_SCRIPT_WRITER]))
+    acs_announce_fact('script_group', repr([GROUP_SCRIPT, GROUP]))
+
+    assert acs_enforce_request('read_data', repr([FUSION_TASK_PARTY_1, FUSION_TASK_DATA_1]))
+    assert not acs_enforce_request('read_data', repr([FUSION_TASK_PARTY_1, FUSION_TASK_DATA_2]))
+    assert acs_enforce_request('read_data', repr([FUSION_TASK_PARTY_1, GROUP_DATA]))
+    assert acs_enforce_request('read_data', repr([FUSION_TASK_PARTY_2, GROUP_DATA]))
+    assert not acs_enforce_request('read_data', repr([IRRELEVANT_PARTY, GROUP_DATA]))
+
+    assert acs_enforce_request('use_script', repr([IRRELEVANT_PARTY, PUBLIC_SCRIPT]))
+    assert acs_enforce_request('use_script', repr([FUSION_TASK_SCRIPT_WRITER, GROUP_SCRIPT]))
+    assert acs_enforce_request('use_script', repr([FUSION_TASK_PARTY_2, GROUP_SCRIPT]))
+    assert not acs_enforce_request('use_script', repr([IRRELEVANT_PARTY, GROUP_SCRIPT]))
+    assert not acs_enforce_request('use_script', repr([FUSION_TASK_PARTY_2, FUSION_TASK_SCRIPT]))
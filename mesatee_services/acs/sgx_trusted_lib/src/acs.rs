@@ -151,6 +151,16 @@ impl HandleRequest for EnforceRequest {
                 (usr, script).marshal(&mut buffer);
                 ("delete_script", buffer)
             }
+            EnforceRequest::ReadData(usr, data) => {
+                let mut buffer = String::new();
+                (usr, data).marshal(&mut buffer);
+                ("read_data", buffer)
+            }
+            EnforceRequest::UseScript(usr, script) => {
+                let mut buffer = String::new();
+                (usr, script).marshal(&mut buffer);
+                ("use_script", buffer)
+            }
         };
 
         let c_request_type = CString::new(request_type.to_string()).unwrap();
@@ -196,6 +206,21 @@ impl HandleRequest for AnnounceRequest {
                     (script,).marshal(&mut buffer);
                     ("is_public_script", buffer)
                 }
+                GroupMember(group, usr) => {
+                    let mut buffer = String::new();
+                    (group, usr).marshal(&mut buffer);
+                    ("group_member", buffer)
+                }
+                DataGroup(data, group) => {
+                    let mut buffer = String::new();
+                    (data, group).marshal(&mut buffer);
+                    ("data_group", buffer)
+                }
+                ScriptGroup(script, group) => {
+                    let mut buffer = String::new();
+                    (script, group).marshal(&mut buffer);
+                    ("script_group", buffer)
+                }
             };
 
             let c_term_type = CString::new(term_type.to_string()).unwrap();
@@ -23,8 +23,9 @@ use std::ffi::CString;
 use std::os::raw::c_char;
 
 use mesatee_core::config;
+use mesatee_core::enclave_size::EnclaveSize;
 use mesatee_core::prelude::*;
-use mesatee_core::rpc::server::SgxTrustedServer;
+use mesatee_core::rpc::server::{self, SgxTrustedServer};
 use mesatee_core::{Error, ErrorKind, Result};
 
 use crate::acs::ACSEnclave;
@@ -59,7 +60,9 @@ fn handle_serve_connection(args: &ServeConnectionInput) -> Result<ServeConnectio
             return Ok(ServeConnectionOutput::default());
         }
     };
-    let _ = server.start();
+    let _ = server
+        .reject(args.reject)
+        .start_with_shutdown(server::shutdown_signal());
 
     // We discard all enclave internal errors here.
     Ok(ServeConnectionOutput::default())
@@ -67,9 +70,12 @@ fn handle_serve_connection(args: &ServeConnectionInput) -> Result<ServeConnectio
 
 const MODEL_TEXT: &str = include_str!("../../model.conf");
 
+// Sizes ACS is expected to need under load.
+const SIZE_HINTS: EnclaveSize = EnclaveSize::new(0x100_0000, 0x20_0000);
+
 #[handle_ecall]
 fn handle_init_enclave(_args: &InitEnclaveInput) -> Result<InitEnclaveOutput> {
-    mesatee_core::init_service(env!("CARGO_PKG_NAME"))?;
+    mesatee_core::init_service_with_hints(env!("CARGO_PKG_NAME"), &SIZE_HINTS)?;
 
     eprintln!("setting up acs model");
 
@@ -84,6 +90,10 @@ fn handle_init_enclave(_args: &InitEnclaveInput) -> Result<InitEnclaveOutput> {
 
 #[handle_ecall]
 fn handle_finalize_enclave(_args: &FinalizeEnclaveInput) -> Result<FinalizeEnclaveOutput> {
+    // Stop serving new requests. In-flight requests are completed by their
+    // own ServeConnection ecalls.
+    server::shutdown();
+
     #[cfg(feature = "cov")]
     sgx_cov::cov_writeout();
 
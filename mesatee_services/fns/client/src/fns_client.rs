@@ -21,7 +21,7 @@ use std::prelude::v1::*;
 
 use fns_proto::{InvokeTaskRequest, InvokeTaskResponse};
 use mesatee_core::config::{OutboundDesc, TargetDesc};
-use mesatee_core::rpc::channel::SgxTrustedChannel;
+use mesatee_core::rpc::channel::{ChannelTimeouts, SgxTrustedChannel};
 use mesatee_core::Result;
 
 pub struct FNSClient {
@@ -30,13 +30,17 @@ pub struct FNSClient {
 
 impl FNSClient {
     pub fn new(target: &TargetDesc) -> Result<Self> {
+        Self::with_timeouts(target, ChannelTimeouts::default())
+    }
+
+    // Invocations waiting longer than `timeouts` for FNS fail.
+    pub fn with_timeouts(target: &TargetDesc, timeouts: ChannelTimeouts) -> Result<Self> {
         let addr = target.addr;
 
         let channel = match &target.desc {
-            OutboundDesc::Sgx(enclave_attr) => SgxTrustedChannel::<
-                InvokeTaskRequest,
-                InvokeTaskResponse,
-            >::new(addr, enclave_attr.clone())?,
+            OutboundDesc::Sgx(enclave_attr) => {
+                SgxTrustedChannel::with_timeouts(addr, enclave_attr.clone(), timeouts)?
+            }
         };
 
         Ok(FNSClient { channel })
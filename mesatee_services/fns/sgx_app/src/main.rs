@@ -19,15 +19,23 @@
 extern crate log;
 
 use mesatee_core::prelude::*;
+use mesatee_core::rpc::listener::{wait_reachable, ConnectionPool, ListenerBuilder};
 use mesatee_core::{config, Result};
 
-use std::net::TcpListener;
+use std::io;
+use std::net::TcpStream;
 use std::os::unix::io::IntoRawFd;
-use threadpool::ThreadPool;
 
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use teaclave_binder::TeeBinder;
 
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Interval to check the shutdown signal when there is no incoming connection
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 fn main() -> Result<()> {
     env_logger::init();
 
@@ -44,45 +52,112 @@ fn main() -> Result<()> {
 
     let tee = Arc::new(tee);
 
+    let shutdown = Arc::new(AtomicBool::new(false));
     {
-        let ref_tee = tee.clone();
+        let shutdown = shutdown.clone();
         ctrlc::set_handler(move || {
-            info!("\nCTRL+C pressed. Destroying server enclave");
-            ref_tee.finalize();
-            std::process::exit(0);
+            info!("\nCTRL+C pressed. Shutting down server enclave");
+            shutdown.store(true, Ordering::SeqCst);
         })
         .expect("Error setting Ctrl-C handler");
     }
 
-    run_function_node_service(tee)?;
+    run_function_node_service(tee, shutdown)?;
 
     Ok(())
 }
 
-fn run_function_node_service(tee: Arc<TeeBinder>) -> Result<()> {
+// Registers the executors of FNS with TMS, and again periodically so that TMS
+// knows them soon after it restarts.
+fn launch_executor_registration(
+    tee: Arc<TeeBinder>,
+    shutdown: Arc<AtomicBool>,
+) -> thread::JoinHandle<()> {
+    let interval = Duration::from_secs(
+        config::runtime_config()
+            .task_management
+            .executor_registration_interval,
+    );
+    let register = |tee: &TeeBinder| {
+        if let Err(e) = tee.run_maintenance(false) {
+            warn!("couldn't register the executors with TMS: {:?}", e);
+        }
+    };
+    // Before FNS serves, so that functions can be registered once it does
+    register(&tee);
+    thread::spawn(move || {
+        let mut last_run = Instant::now();
+        while !shutdown.load(Ordering::SeqCst) {
+            if last_run.elapsed() >= interval {
+                last_run = Instant::now();
+                register(&tee);
+            }
+            thread::sleep(ACCEPT_POLL_INTERVAL);
+        }
+    })
+}
+
+fn run_function_node_service(tee: Arc<TeeBinder>, shutdown: Arc<AtomicBool>) -> Result<()> {
     info!("Running as FNS Server ...");
 
+    let internal_endpoints = &config::runtime_config().internal_endpoints;
+    wait_reachable(
+        "TDFS internal advertised_address",
+        internal_endpoints.tdfs.advertised_address,
+    )?;
+    wait_reachable(
+        "TMS internal advertised_address",
+        internal_endpoints.tms.advertised_address,
+    )?;
+    let registration = launch_executor_registration(tee.clone(), shutdown.clone());
+
     let config = config::External::fns();
-    let listener = TcpListener::bind(config.addr)?;
+    let listener = ListenerBuilder::new(config.addr)
+        .name("FNS API listen_address")
+        .bind()?;
+    listener.set_nonblocking(true)?;
     let port = config.addr.port();
-    let n_workers = 10;
-    let pool = ThreadPool::new(n_workers);
-    for stream in listener.incoming() {
-        match stream {
-            Ok(stream) => {
-                let tee = tee.clone();
-                pool.execute(move || {
-                    debug!("new worker from {:?}", stream.peer_addr());
-                    let fd = stream.into_raw_fd();
-                    let input = ServeConnectionInput::new(fd, port);
-                    let cmd = ECallCommand::ServeConnection;
-                    let _ = tee
-                        .invoke::<ServeConnectionInput, ServeConnectionOutput>(cmd.into(), input);
-                });
+    let pool = connection_pool(tee.clone(), port);
+    while !shutdown.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                if !pool.dispatch(stream) {
+                    warn!("Rejecting connection: no worker is free");
+                }
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(ACCEPT_POLL_INTERVAL)
             }
             Err(e) => warn!("couldn't get client: {:?}", e),
         }
     }
 
+    // Stop the trusted server and wait for in-flight connections
+    let _ = registration.join();
+    tee.finalize();
+    pool.join();
+
     Ok(())
 }
+
+// Hands the connections accepted by the listener on `port` to the enclave.
+fn connection_pool(tee: Arc<TeeBinder>, port: u16) -> ConnectionPool<TcpStream> {
+    let rejecting_tee = tee.clone();
+    ConnectionPool::from_runtime_config(
+        move |stream| serve_connection(&tee, stream, port, false),
+        move |stream| serve_connection(&rejecting_tee, stream, port, true),
+    )
+}
+
+fn serve_connection(tee: &TeeBinder, stream: TcpStream, port: u16, reject: bool) {
+    debug!("new client from {:?}", stream.peer_addr());
+    let _ = stream.set_nonblocking(false);
+    let fd = stream.into_raw_fd();
+    let input = if reject {
+        ServeConnectionInput::new_rejected(fd, port)
+    } else {
+        ServeConnectionInput::new(fd, port)
+    };
+    let cmd = ECallCommand::ServeConnection;
+    let _ = tee.invoke::<ServeConnectionInput, ServeConnectionOutput>(cmd.into(), input);
+}
@@ -0,0 +1,83 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// Insert std prelude in the top for the sgx feature
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use mesatee_core::config;
+use mesatee_core::Result;
+use tms_internal_client::TMSClient;
+use tms_internal_proto::{Executor, ExecutorInfo};
+
+fn executor_info(
+    executor: Executor,
+    input_modes: &[&str],
+    output_modes: &[&str],
+    resource_limits: &[&str],
+) -> ExecutorInfo {
+    let to_strings = |values: &[&str]| values.iter().map(|value| value.to_string()).collect();
+    ExecutorInfo {
+        executor,
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        input_modes: to_strings(input_modes),
+        output_modes: to_strings(output_modes),
+        resource_limits: to_strings(resource_limits),
+    }
+}
+
+// Executors of this execution service. Builtin workers get the payload of the
+// task and its files, ports and collections, and may save files for any
+// output port. WASM modules and Python scripts read the input files of the
+// task and save files for its creator. The wall-clock limit of a task is
+// checked once its worker returns, the interpreters enforce the other limits.
+pub fn executors() -> Vec<ExecutorInfo> {
+    vec![
+        executor_info(
+            Executor::Builtin,
+            &["payload", "files", "ports", "collections"],
+            &["result", "files", "ports", "dynamic_ports"],
+            &["max_wall_seconds"],
+        ),
+        executor_info(
+            Executor::Wasm,
+            &["files"],
+            &["result", "files"],
+            &["max_memory_mb", "max_wall_seconds"],
+        ),
+        executor_info(
+            Executor::Python,
+            &["files"],
+            &["result", "files"],
+            &["max_memory_mb", "max_cpu_seconds", "max_wall_seconds"],
+        ),
+    ]
+}
+
+// Registers the executors with TMS, which only accepts functions for the
+// executors of the execution services. TMS returns its enclave info when it
+// differs from ours, which we reload so that an admin only has to reload TMS.
+pub fn register_executors() -> Result<()> {
+    let target = config::Internal::target_tms();
+    let mut client = TMSClient::new(target)?;
+    let resp = client.request_register_executors(&executors(), &config::enclave_info_digest())?;
+    if !resp.enclave_info.is_empty() {
+        let services = config::reload_enclave_info(&resp.enclave_info)?;
+        info!("Enclave info of {} services reloaded", services.len());
+    }
+    Ok(())
+}
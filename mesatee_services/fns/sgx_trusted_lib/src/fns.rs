@@ -23,8 +23,12 @@ use crate::running_task::RunningTask;
 use crate::worker::{Worker, WorkerInfoQueue};
 use fns_proto::{InvokeTaskRequest, InvokeTaskResponse};
 use mesatee_core::rpc::EnclaveService;
-use mesatee_core::Result;
+use mesatee_core::{config, Error, ErrorKind, Result};
 use std::marker::PhantomData;
+use std::time::{Duration, SystemTime};
+#[cfg(feature = "mesalock_sgx")]
+use std::untrusted::time::SystemTimeEx;
+use tms_internal_proto::Executor;
 
 pub trait HandleRequest {
     fn handle_request(&self) -> Result<InvokeTaskResponse>;
@@ -35,25 +39,69 @@ fn invoke_worker(
     request: &InvokeTaskRequest,
 ) -> Result<InvokeTaskResponse> {
     // Generate RunningTask
-    let running_task = RunningTask::init(&request)?;
+    let mut running_task = RunningTask::init(&request)?;
     let file_list = running_task.get_file_list();
     // New worker context
-    let worker_context = running_task.get_worker_context();
-    let payload = request.payload.clone();
+    let mut worker_context = running_task.get_worker_context();
+    // The module of a WASM function and the script of a Python function are
+    // kept by TMS rather than sent along with the invocation.
+    let payload = match running_task.function_info() {
+        Ok(Some(ref function_info)) if function_info.executor != Executor::Builtin => {
+            // Capabilities withdrawn from the cluster since the function was
+            // registered are not granted.
+            let allowed = &config::runtime_config().executor_capabilities.allowed;
+            worker_context.capabilities = function_info
+                .capabilities
+                .iter()
+                .filter(|capability| allowed.iter().any(|name| name == capability.name()))
+                .copied()
+                .collect();
+            function_info.payload.clone()
+        }
+        Ok(_) => request.payload.clone(),
+        Err(err) => {
+            let _ = running_task.fail(&err);
+            return Err(err);
+        }
+    };
     if let Err(err) = worker.prepare_input(payload, file_list) {
-        let _ = running_task.finish();
+        let _ = running_task.fail(&err);
         return Err(err);
     }
-    let result = worker.execute(worker_context);
+    // Workers run to completion inside the enclave and cannot be preempted,
+    // so the wall-clock limit is checked once the worker returns and the
+    // output of an overrunning task is discarded.
+    let max_wall_time = Duration::from_secs(running_task.resource_limits().max_wall_seconds);
+    let start_time = SystemTime::now();
+    let result = worker.execute(worker_context).and_then(|output| {
+        let elapsed = SystemTime::now()
+            .duration_since(start_time)
+            .unwrap_or_default();
+        if elapsed > max_wall_time {
+            Err(Error::new(
+                ErrorKind::OutputGenerationError,
+                format!(
+                    "task exceeded its wall-clock limit of {} seconds",
+                    max_wall_time.as_secs()
+                ),
+            ))
+        } else {
+            Ok(output)
+        }
+    });
     match result {
         Ok(output) => {
             let _ = running_task.save_dynamic_output(&output);
+            if let Err(err) = running_task.save_result(&output) {
+                let _ = running_task.fail(&err);
+                return Err(err);
+            }
             running_task.finish()?;
             let response = InvokeTaskResponse::new(&output);
             Ok(response)
         }
         Err(err) => {
-            let _ = running_task.finish();
+            let _ = running_task.fail(&err);
             Err(err)
         }
     }
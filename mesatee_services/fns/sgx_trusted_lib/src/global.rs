@@ -15,12 +15,14 @@
 // specific language governing permissions and limitations
 // under the License.
 use crate::running_task::RunningTask;
+use crate::task_log;
 use crate::trusted_worker::{
-    BytesPlusOneWorker, ConcatWorker, DBSCANWorker, EchoFileWorker, EchoWorker,
-    FileBytesPlusOneWorker, GBDTPredictWorker, GBDTTrainWorker, GPWorker, GenLinearModelWorker,
-    GmmWorker, ImageResizeWorker, KmeansWorker, LinRegWorker, LogisticRegPredictWorker,
-    LogisticRegTrainWorker, MesaPyWorker, NaiveBayesWorker, NeuralNetWorker, OnlineDecryptWorker,
-    PSIWorker, PrivateJoinAndComputeWorker, RSASignWorker, SvmWorker, SwapFileWorker, WASMWorker,
+    BytesPlusOneWorker, ConcatWorker, DBSCANWorker, EchoArgWorker, EchoEnvWorker, EchoFileWorker,
+    EchoLogWorker, EchoWorker, FileBytesPlusOneWorker, GBDTPredictWorker, GBDTTrainWorker,
+    GPWorker, GenLinearModelWorker, GmmWorker, ImageResizeWorker, KmeansWorker, LinRegWorker,
+    LogisticRegPredictWorker, LogisticRegTrainWorker, MesaPyWorker, NaiveBayesWorker,
+    NeuralNetWorker, OnlineDecryptWorker, PSIWorker, PartitionWorker, PrivateJoinAndComputeWorker,
+    PythonFunctionWorker, RSASignWorker, SvmWorker, SwapFileWorker, WASMWorker, WasmFunctionWorker,
 };
 use crate::worker::WorkerInfoQueue;
 use mesatee_core::Result;
@@ -30,8 +32,10 @@ use std::ffi::CStr;
 use std::prelude::v1::*;
 
 use std::slice;
+use tms_internal_proto::{FileFormat, LogStream};
 
 const FFI_FILE_ERROR: c_int = -1;
+const FFI_LOG_ERROR: c_int = -1;
 const FFI_BUFFER_NOT_ENOUGH_ERROR: c_int = -2;
 const UUID_SIZE: size_t = 36;
 
@@ -198,6 +202,36 @@ extern "C" fn c_save_file_for_file_owner(
     }
 }
 
+// C API of write_log for workers, `fd` being 1 for stdout and 2 for stderr
+//
+// int c_write_task_log(char* context_id,
+//                      char* context_token,
+//                      int fd,
+//                      char* in_buf,
+//                      size_t in_buf_size);
+#[allow(unused)]
+#[no_mangle]
+extern "C" fn c_write_task_log(
+    context_id: *const c_char,
+    context_token: *const c_char,
+    fd: c_int,
+    in_buf: *const u8,
+    in_buf_size: size_t,
+) -> c_int {
+    let stream = match task_log::stream_of_fd(fd) {
+        Some(stream) => stream,
+        None => return FFI_LOG_ERROR,
+    };
+    let context_id = unsafe { CStr::from_ptr(context_id).to_string_lossy().into_owned() };
+    let context_token = unsafe { CStr::from_ptr(context_token).to_string_lossy().into_owned() };
+    let in_buf: &[u8] = unsafe { slice::from_raw_parts(in_buf, in_buf_size) };
+
+    match write_log(&context_id, &context_token, stream, in_buf) {
+        Ok(()) => 0,
+        Err(_) => FFI_LOG_ERROR,
+    }
+}
+
 pub fn read_file(context_id: &str, context_token: &str, file_id: &str) -> Result<Vec<u8>> {
     let mut running_task = RunningTask::retrieve_running_task(context_id, context_token)?;
     running_task.read_file(file_id)
@@ -212,6 +246,26 @@ pub fn save_file_for_task_creator(
     running_task.save_file_for_task_creator(data)
 }
 
+pub fn save_file_for_task_creator_with_format(
+    context_id: &str,
+    context_token: &str,
+    data: &[u8],
+    format: &FileFormat,
+) -> Result<String> {
+    let mut running_task = RunningTask::retrieve_running_task(context_id, context_token)?;
+    running_task.save_file_for_task_creator_with_format(data, format)
+}
+
+pub fn save_file_for_output_port(
+    context_id: &str,
+    context_token: &str,
+    port: &str,
+    data: &[u8],
+) -> Result<String> {
+    let mut running_task = RunningTask::retrieve_running_task(context_id, context_token)?;
+    running_task.save_file_for_output_port(port, data)
+}
+
 pub fn save_file_for_all_participants(
     context_id: &str,
     context_token: &str,
@@ -230,6 +284,27 @@ pub fn save_file_for_file_owner(
     running_task.save_file_for_file_owner(data, file_id)
 }
 
+pub fn report_progress(
+    context_id: &str,
+    context_token: &str,
+    percent: u8,
+    message: &str,
+    partial_output: Option<&[u8]>,
+) -> Result<()> {
+    let running_task = RunningTask::retrieve_running_task(context_id, context_token)?;
+    running_task.report_progress(percent, message, partial_output)
+}
+
+pub fn write_log(
+    context_id: &str,
+    context_token: &str,
+    stream: LogStream,
+    data: &[u8],
+) -> Result<()> {
+    let running_task = RunningTask::retrieve_running_task(context_id, context_token)?;
+    running_task.write_log(stream, data)
+}
+
 pub fn register_trusted_worker_statically() {
     for _i in 0..10 {
         let worker = Box::new(EchoWorker::new());
@@ -241,6 +316,15 @@ pub fn register_trusted_worker_statically() {
         let worker = Box::new(EchoFileWorker::new());
         let _ = WorkerInfoQueue::register(worker);
 
+        let worker = Box::new(EchoEnvWorker::new());
+        let _ = WorkerInfoQueue::register(worker);
+
+        let worker = Box::new(EchoArgWorker::new());
+        let _ = WorkerInfoQueue::register(worker);
+
+        let worker = Box::new(EchoLogWorker::new());
+        let _ = WorkerInfoQueue::register(worker);
+
         let worker = Box::new(BytesPlusOneWorker::new());
         let _ = WorkerInfoQueue::register(worker);
 
@@ -253,12 +337,21 @@ pub fn register_trusted_worker_statically() {
         let worker = Box::new(SwapFileWorker::new());
         let _ = WorkerInfoQueue::register(worker);
 
+        let worker = Box::new(PartitionWorker::new());
+        let _ = WorkerInfoQueue::register(worker);
+
         let worker = Box::new(WASMWorker::new());
         let _ = WorkerInfoQueue::register(worker);
 
+        let worker = Box::new(WasmFunctionWorker::new());
+        let _ = WorkerInfoQueue::register(worker);
+
         let worker = Box::new(MesaPyWorker::new());
         let _ = WorkerInfoQueue::register(worker);
 
+        let worker = Box::new(PythonFunctionWorker::new());
+        let _ = WorkerInfoQueue::register(worker);
+
         let worker = Box::new(GBDTPredictWorker::new());
         let _ = WorkerInfoQueue::register(worker);
 
@@ -22,9 +22,11 @@ extern crate sgx_tstd as std;
 #[macro_use]
 extern crate log;
 
+mod executors;
 mod fns;
 mod global;
 mod running_task;
+mod task_log;
 mod trusted_worker;
 mod worker;
 
@@ -18,24 +18,34 @@
 // Insert std prelude in the top for the sgx feature
 use fns_proto::InvokeTaskRequest;
 use lazy_static::lazy_static;
-use mesatee_core::{config, Error, ErrorKind, Result};
-use std::collections::HashMap;
+use mesatee_core::{config, content_type, Error, ErrorKind, Result, Status};
+use std::collections::{BTreeMap, HashMap};
 #[cfg(feature = "mesalock_sgx")]
 use std::prelude::v1::*;
 use std::sync::Arc;
 #[cfg(feature = "mesalock_sgx")]
 use std::sync::SgxRwLock as RwLock;
-use tdfs_internal_client::TDFSClient;
+use tdfs_internal_client::{DigestAlgorithm, EncryptionContext, TDFSClient};
+use teaclave_config::runtime_config::IntegrityCheck;
 use tms_internal_client::TMSClient;
-use tms_internal_proto::{TaskFile, TaskInfo, TaskStatus};
+use tms_internal_proto::{
+    ArgumentsFile, DynamicOutputBindings, FileFormat, FunctionInfo, LogStream, PartialOutput,
+    ResourceLimits, ResultFormat, TaskArguments, TaskError, TaskFile, TaskInfo, TaskResultRecord,
+    TaskStatus,
+};
 use uuid::Uuid;
 
+use crate::task_log::TaskLogWriter;
 use crate::worker::WorkerContext;
 
 struct TaskResult {
     output: Option<String>,
     output_files: Vec<TaskFile>,
     task_result_file_id: Option<String>,
+    // Output files saved on dynamic output ports, also in `output_files`.
+    dynamic_outputs: DynamicOutputBindings,
+    // Output file of the serialized result, also in `output_files`.
+    result_file_id: Option<String>,
 }
 
 lazy_static! {
@@ -50,6 +60,12 @@ pub struct RunningTask {
     context_token: String,
     task_result: Arc<RwLock<TaskResult>>,
     file_owner_map: HashMap<String, String>,
+    // Values of the task secrets, by environment variable.
+    env: BTreeMap<String, String>,
+    // Arguments of the task, with those read from its arguments file.
+    arguments: TaskArguments,
+    // What the function writes to stdout and stderr.
+    log: Arc<RwLock<TaskLogWriter>>,
 }
 
 impl RunningTask {
@@ -76,6 +92,73 @@ impl RunningTask {
             _ => return Err(Error::from(ErrorKind::PermissionDenied)),
         }
 
+        // Outputs of the task creator cannot be saved without the key
+        if let Some(ref output_key) = task_info.output_key {
+            if !output_key.is_complete() {
+                let status = Some(&TaskStatus::Failed);
+                let _ = client.request_update_task(&task_id, None, &[], status);
+                return Err(Error::new(
+                    ErrorKind::InvalidInputError,
+                    format!("output key of task {} is missing its key material", task_id),
+                ));
+            }
+        }
+
+        // Secrets which are deleted or no longer shared fail the task
+        let env = if task_info.secrets.is_empty() {
+            BTreeMap::new()
+        } else {
+            match client.request_get_task_secrets(&task_id, &task_info.task_token) {
+                Ok(resp) => resp.env,
+                Err(err) => {
+                    if err.kind() == ErrorKind::NotFound
+                        || err.kind() == ErrorKind::PermissionDenied
+                    {
+                        let status = Some(&TaskStatus::Failed);
+                        let _ = client.request_update_task(&task_id, None, &[], status);
+                    }
+                    return Err(err);
+                }
+            }
+        };
+
+        // Verify input files against the digests recorded at upload
+        let integrity_check = config::runtime_config().task_inputs.integrity_check;
+        match Self::verify_input_files(&task_info) {
+            Ok(()) => {}
+            Err(ref err)
+                if err.kind() == ErrorKind::DataIntegrityError
+                    && integrity_check == IntegrityCheck::BestEffort =>
+            {
+                warn!("task {}: {:?}", task_id, err);
+            }
+            Err(err) => {
+                if err.kind() == ErrorKind::DataIntegrityError {
+                    let status = Some(&TaskStatus::Failed);
+                    let _ = client.request_update_task(&task_id, None, &[], status);
+                }
+                return Err(err);
+            }
+        }
+
+        // An arguments file which cannot be read as pinned fails the task
+        let arguments = match Self::read_arguments(&task_info) {
+            Ok(arguments) => arguments,
+            Err(err) => {
+                match err.kind() {
+                    ErrorKind::NotFound
+                    | ErrorKind::PermissionDenied
+                    | ErrorKind::DataIntegrityError
+                    | ErrorKind::InvalidInputError => {
+                        let status = Some(&TaskStatus::Failed);
+                        let _ = client.request_update_task(&task_id, None, &[], status);
+                    }
+                    _ => {}
+                }
+                return Err(err);
+            }
+        };
+
         // Prepare task input
         let mut file_owner_map: HashMap<String, String> = HashMap::new();
         for task_file in task_info.input_files.iter() {
@@ -90,14 +173,20 @@ impl RunningTask {
             output: None,
             output_files: Vec::new(),
             task_result_file_id: None,
+            dynamic_outputs: DynamicOutputBindings::new(),
+            result_file_id: None,
         };
 
+        let log = TaskLogWriter::new(&task_id, env.values());
         let running_task = RunningTask {
             task_info,
             task_id: task_id.to_owned(),
             context_token,
             task_result: Arc::new(RwLock::new(task_result)),
             file_owner_map,
+            env,
+            arguments,
+            log: Arc::new(RwLock::new(log)),
         };
 
         // Todo: verify this is the expected worker
@@ -114,7 +203,13 @@ impl RunningTask {
         let status = Some(&TaskStatus::Running);
         let result = client.request_update_task(&task_id, None, &[], status);
         match result {
-            Ok(_) => Ok(running_task),
+            Ok(ref resp) if resp.success => Ok(running_task),
+            Ok(_) => {
+                // Cancelled after the status check above
+                let mut queue = RUNNING_TASK_QUEUE.write()?;
+                queue.remove(&task_id);
+                Err(Error::from(ErrorKind::PermissionDenied))
+            }
             Err(err) => {
                 let mut queue = RUNNING_TASK_QUEUE.write()?;
                 queue.remove(&task_id);
@@ -123,6 +218,66 @@ impl RunningTask {
         }
     }
 
+    fn verify_input_files(task_info: &TaskInfo) -> Result<()> {
+        let target = config::Internal::target_tdfs();
+        let mut client = TDFSClient::new(target)?;
+        for task_file in task_info.input_files.iter() {
+            client.verify_file(&task_file.file_id)?;
+        }
+        Ok(())
+    }
+
+    // Arguments of the task. Those of its arguments file, which must match
+    // the digest it was pinned to, are replaced by the inline arguments with
+    // the same name.
+    fn read_arguments(task_info: &TaskInfo) -> Result<TaskArguments> {
+        let pinned = match task_info.arguments_file {
+            Some(ref pinned) => pinned,
+            None => return Ok(task_info.arguments.clone()),
+        };
+        let target = config::Internal::target_tdfs();
+        let mut client = TDFSClient::new(target)?;
+        let data = Self::read_pinned_file(&mut client, pinned)?;
+        let mut arguments: TaskArguments = serde_json::from_slice(&data).map_err(|err| {
+            Error::new(
+                ErrorKind::InvalidInputError,
+                format!(
+                    "arguments file {} is not a JSON object: {}",
+                    pinned.file_id, err
+                ),
+            )
+        })?;
+        arguments.extend(task_info.arguments.clone());
+        Ok(arguments)
+    }
+
+    fn read_pinned_file(client: &mut TDFSClient, pinned: &ArgumentsFile) -> Result<Vec<u8>> {
+        let integrity_error = || {
+            Error::new(
+                ErrorKind::DataIntegrityError,
+                format!(
+                    "arguments file {} does not match the digest it was pinned to",
+                    pinned.file_id
+                ),
+            )
+        };
+        let file_info = client
+            .get_files(&[pinned.file_id.to_owned()])?
+            .pop()
+            .and_then(|file_info| file_info)
+            .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+        if file_info.digest_algorithm != pinned.digest_algorithm {
+            return Err(integrity_error());
+        }
+        // TMS checked that the task creator may read the file
+        let data = client.read_file(&pinned.file_id, None)?;
+        let algorithm = DigestAlgorithm::from_tag(&pinned.digest_algorithm)?;
+        if algorithm.cal_digest(&data, &file_info.mac_key)? != pinned.digest {
+            return Err(integrity_error());
+        }
+        Ok(data)
+    }
+
     pub fn get_file_list(&self) -> Vec<String> {
         self.task_info
             .input_files
@@ -143,8 +298,55 @@ impl RunningTask {
         Ok(())
     }
 
+    // Saves the result of a task in its result format, if it has a file, as
+    // an output of the task creator.
+    pub fn save_result(&mut self, output: &str) -> Result<()> {
+        let result_format = self.task_info.result_format;
+        let content_type = match result_format.content_type() {
+            Some(content_type) => content_type,
+            None => return Ok(()),
+        };
+        let record = TaskResultRecord {
+            task_id: self.task_id.to_owned(),
+            output: output.to_owned(),
+        };
+        let data = match result_format {
+            ResultFormat::Cbor => serde_cbor::to_vec(&record).map_err(|err| {
+                Error::new(
+                    ErrorKind::OutputGenerationError,
+                    format!("cannot serialize the result: {}", err),
+                )
+            })?,
+            _ => serde_json::to_vec(&record)?,
+        };
+        let format = FileFormat::new(content_type, None);
+        let user_id = self.task_info.user_id.to_owned();
+        let file_id = self.save_file(&data, &user_id, false, Some(&format))?;
+        let mut task_result = self.task_result.write()?;
+        task_result.result_file_id = Some(file_id);
+        Ok(())
+    }
+
+    // Appends what the function writes to `stream` to the task log.
+    pub fn write_log(&self, stream: LogStream, data: &[u8]) -> Result<()> {
+        let mut log = self.log.write()?;
+        log.write(stream, data)
+    }
+
+    // The log is complete before the task ends, for clients tailing it.
+    fn flush_log(&self) {
+        let result = match self.log.write() {
+            Ok(mut log) => log.flush(),
+            Err(err) => Err(err.into()),
+        };
+        if let Err(err) = result {
+            warn!("task {}: cannot append to its log: {:?}", self.task_id, err);
+        }
+    }
+
     pub fn finish(self) -> Result<()> {
         let _ = Self::remove_from_queue(&self.task_id);
+        self.flush_log();
 
         let task_result = self.task_result.read()?;
         let target = config::Internal::target_tms();
@@ -155,11 +357,14 @@ impl RunningTask {
                 let status = Some(&TaskStatus::Finished);
                 let task_result_file_id = task_result.task_result_file_id.as_ref();
                 let output_files: Vec<&TaskFile> = task_result.output_files.iter().collect();
+                let result_file_id = task_result.result_file_id.as_ref();
 
-                let _ = client.request_update_task(
+                let _ = client.request_update_task_with_dynamic_outputs(
                     &self.task_id,
                     task_result_file_id.map(|s| s.as_str()),
                     &output_files,
+                    &task_result.dynamic_outputs,
+                    result_file_id.map(|s| s.as_str()),
                     status,
                 )?;
                 Ok(())
@@ -172,12 +377,51 @@ impl RunningTask {
         }
     }
 
+    // Ends the task as failed with `err`. Failures of unavailable services
+    // and timeouts are transient, and TMS may run the task again.
+    pub fn fail(self, err: &Error) -> Result<()> {
+        let _ = Self::remove_from_queue(&self.task_id);
+        self.flush_log();
+
+        let retryable = match err.status() {
+            Status::Unavailable | Status::DeadlineExceeded => true,
+            _ => false,
+        };
+        let error = TaskError {
+            message: err.to_string(),
+            retryable,
+        };
+        let target = config::Internal::target_tms();
+        let mut client = TMSClient::new(target)?;
+        let _ = client.request_fail_task(&self.task_id, &error)?;
+        Ok(())
+    }
+
     pub fn get_worker_context(&self) -> WorkerContext {
         WorkerContext {
             context_id: self.task_id.to_owned(),
             context_token: self.context_token.to_owned(),
+            resource_limits: self.resource_limits(),
+            env: self.env.clone(),
+            arguments: self.arguments.clone(),
+            capabilities: Vec::new(),
         }
     }
+    pub fn resource_limits(&self) -> ResourceLimits {
+        self.task_info.resource_limits
+    }
+
+    // The registered function the task was created from, if any.
+    pub fn function_info(&self) -> Result<Option<FunctionInfo>> {
+        let function_id = match self.task_info.function_id {
+            Some(ref function_id) => function_id,
+            None => return Ok(None),
+        };
+        let target = config::Internal::target_tms();
+        let mut client = TMSClient::new(target)?;
+        let resp = client.request_get_function(function_id)?;
+        Ok(Some(resp.function_info))
+    }
     pub fn retrieve_running_task(context_id: &str, context_token: &str) -> Result<RunningTask> {
         let queue = RUNNING_TASK_QUEUE.read()?;
         let task = queue
@@ -190,11 +434,71 @@ impl RunningTask {
         }
     }
 
+    // Tasks are cancelled in TMS. A running task stops at the next file it
+    // reads or saves after being cancelled.
+    fn check_cancelled(&self) -> Result<()> {
+        let target = config::Internal::target_tms();
+        let mut client = TMSClient::new(target)?;
+        let resp = client.request_get_task(&self.task_id)?;
+        match resp.task_info.status {
+            TaskStatus::Cancelled => Err(Error::new(
+                ErrorKind::PermissionDenied,
+                format!("task {} was cancelled", self.task_id),
+            )),
+            _ => Ok(()),
+        }
+    }
+
     fn save_file(
         &mut self,
         data: &[u8],
         user_id: &str,
         is_for_all_participants: bool,
+        format: Option<&FileFormat>,
+    ) -> Result<String> {
+        self.check_cancelled()?;
+        if !is_for_all_participants {
+            let max_outputs = config::runtime_config().task_management.max_output_files;
+            let task_result = self.task_result.read()?;
+            if task_result.output_files.len() >= max_outputs {
+                return Err(Error::new(
+                    ErrorKind::InvalidInputError,
+                    format!("task may produce at most {} output files", max_outputs),
+                ));
+            }
+        }
+        let file_id = self.store_file(data, user_id, is_for_all_participants, format)?;
+
+        let mut task_result = self.task_result.write()?;
+        if is_for_all_participants {
+            task_result.task_result_file_id = Some(file_id.clone());
+        } else {
+            task_result.output_files.push(TaskFile {
+                user_id: user_id.to_string(),
+                file_id: file_id.to_string(),
+                format: format.cloned(),
+            });
+        }
+        Ok(file_id)
+    }
+
+    // Files of the task are encrypted for the task and the function it runs.
+    fn encryption_context(&self) -> EncryptionContext {
+        let function_id = match self.task_info.function_id {
+            Some(ref function_id) => function_id,
+            None => &self.task_info.function_name,
+        };
+        EncryptionContext::new(&self.task_id, function_id)
+    }
+
+    // Saves `data` in TDFS without making it an output of the task. TDFS
+    // records the `format` of the file, which TMS then takes for the output.
+    fn store_file(
+        &self,
+        data: &[u8],
+        user_id: &str,
+        is_for_all_participants: bool,
+        format: Option<&FileFormat>,
     ) -> Result<String> {
         let allow_policy: u32;
         let collaborator_list: Vec<&str>;
@@ -211,30 +515,75 @@ impl RunningTask {
             collaborator_list = Vec::new();
         };
 
+        let format = format.map(|format| tdfs_internal_client::FileFormat {
+            content_type: format.content_type.to_string(),
+            schema: format.schema.clone(),
+        });
+        let context = self.encryption_context();
         let target = config::Internal::target_tdfs();
         let mut client = TDFSClient::new(target)?;
 
-        let file_id = client.save_file(
-            data,
-            user_id,
-            &self.task_id,
-            &collaborator_list,
-            allow_policy,
-        )?;
-
-        let mut task_result = self.task_result.write()?;
-        if is_for_all_participants {
-            task_result.task_result_file_id = Some(file_id.clone());
-        } else {
-            task_result.output_files.push(TaskFile {
-                user_id: user_id.to_string(),
-                file_id: file_id.to_string(),
-            });
-        }
+        // Outputs for the task creator alone are encrypted under the output
+        // key of the task, so that they are never stored under a platform key.
+        let output_key = match self.task_info.output_key {
+            Some(ref output_key)
+                if !is_for_all_participants && user_id == self.task_info.user_id =>
+            {
+                Some(output_key)
+            }
+            _ => None,
+        };
+        let file_id = match output_key {
+            Some(output_key) => client.save_file_with_output_key(
+                data,
+                user_id,
+                &self.task_id,
+                &output_key.key_id,
+                &output_key.kek,
+                format.as_ref(),
+                Some(&context),
+            )?,
+            None => client.save_file_with_format(
+                data,
+                user_id,
+                &self.task_id,
+                &collaborator_list,
+                allow_policy,
+                format.as_ref(),
+                Some(&context),
+            )?,
+        };
         Ok(file_id)
     }
 
+    // Records a progress checkpoint of the task in TMS. The partial output,
+    // if any, is saved for the task creator in a new file, which stays out
+    // of the results of the task.
+    pub fn report_progress(
+        &self,
+        percent: u8,
+        message: &str,
+        partial_output: Option<&[u8]>,
+    ) -> Result<()> {
+        self.check_cancelled()?;
+        let partial_output = match partial_output {
+            Some(data) => {
+                let file_id = self.store_file(data, &self.task_info.user_id, false, None)?;
+                let sha256 = DigestAlgorithm::Sha256.cal_digest(data, &[])?;
+                Some(PartialOutput { file_id, sha256 })
+            }
+            None => None,
+        };
+
+        let target = config::Internal::target_tms();
+        let mut client = TMSClient::new(target)?;
+        client.request_report_progress(&self.task_id, percent, message, partial_output.as_ref())?;
+        Ok(())
+    }
+
     pub fn read_file(&mut self, file_id: &str) -> Result<Vec<u8>> {
+        self.check_cancelled()?;
+
         // TMS already checked
         let check_user_id = if self.file_owner_map.contains_key(file_id) {
             None
@@ -251,10 +600,61 @@ impl RunningTask {
     }
 
     pub fn save_file_for_task_creator(&mut self, data: &[u8]) -> Result<String> {
-        self.save_file(data, &self.task_info.user_id.to_string(), false)
+        self.save_file(data, &self.task_info.user_id.to_string(), false, None)
+    }
+    // Saves `data` for the task creator, tagged with the content type and
+    // schema of `format`.
+    pub fn save_file_for_task_creator_with_format(
+        &mut self,
+        data: &[u8],
+        format: &FileFormat,
+    ) -> Result<String> {
+        content_type::check_content_type(&format.content_type)?;
+        if let Some(schema) = &format.schema {
+            content_type::check_schema(schema)?;
+        }
+        self.save_file(
+            data,
+            &self.task_info.user_id.to_string(),
+            false,
+            Some(format),
+        )
+    }
+    // Saves `data` for the task creator as one of the files the function
+    // produces on its dynamic output port `port`, tagged with the format the
+    // port declares. TMS checks the port when the task finishes.
+    pub fn save_file_for_output_port(&mut self, port: &str, data: &[u8]) -> Result<String> {
+        let max_outputs = config::runtime_config().task_management.max_dynamic_outputs;
+        {
+            let task_result = self.task_result.read()?;
+            let saved: usize = task_result.dynamic_outputs.values().map(Vec::len).sum();
+            if saved >= max_outputs {
+                return Err(Error::new(
+                    ErrorKind::InvalidInputError,
+                    format!("task may produce at most {} dynamic outputs", max_outputs),
+                ));
+            }
+        }
+        let format = self
+            .function_info()?
+            .and_then(|info| info.output_ports.into_iter().find(|spec| spec.name == port))
+            .and_then(|spec| spec.format);
+        let file_id = self.save_file(
+            data,
+            &self.task_info.user_id.to_string(),
+            false,
+            format.as_ref(),
+        )?;
+        let mut task_result = self.task_result.write()?;
+        task_result
+            .dynamic_outputs
+            .entry(port.to_string())
+            .or_insert_with(Vec::new)
+            .push(file_id.clone());
+        Ok(file_id)
     }
     pub fn save_file_for_all_participants(&mut self, data: &[u8]) -> Result<String> {
-        self.save_file(data, &self.task_info.user_id.to_string(), true)
+        self.save_file(data, &self.task_info.user_id.to_string(), true, None)
     }
     pub fn save_file_for_file_owner(&mut self, data: &[u8], file_id: &str) -> Result<String> {
         let file_owner: String = match self.file_owner_map.get(file_id) {
@@ -262,6 +662,6 @@ impl RunningTask {
             None => return Err(Error::from(ErrorKind::BadImplementation)),
         };
 
-        self.save_file(data, &file_owner, false)
+        self.save_file(data, &file_owner, false, None)
     }
 }
@@ -20,10 +20,12 @@
 use std::prelude::v1::*;
 
 use mesatee_core::config;
+use mesatee_core::enclave_size::EnclaveSize;
 use mesatee_core::prelude::*;
-use mesatee_core::rpc::server::SgxTrustedServer;
+use mesatee_core::rpc::server::{self, SgxTrustedServer};
 use mesatee_core::Result;
 
+use crate::executors;
 use crate::fns::FNSEnclave;
 use crate::global::register_trusted_worker_statically;
 
@@ -32,11 +34,16 @@ register_ecall_handler!(
     (ECallCommand::ServeConnection, ServeConnectionInput, ServeConnectionOutput),
     (ECallCommand::InitEnclave, InitEnclaveInput, InitEnclaveOutput),
     (ECallCommand::FinalizeEnclave, FinalizeEnclaveInput, FinalizeEnclaveOutput),
+    (ECallCommand::RunMaintenance, RunMaintenanceInput, RunMaintenanceOutput),
 );
 
+// Functions run in the enclave, the interpreters and the task inputs and
+// outputs need the larger heap.
+const SIZE_HINTS: EnclaveSize = EnclaveSize::new(0x380_0000, 0x20_0000);
+
 #[handle_ecall]
 fn handle_init_enclave(_args: &InitEnclaveInput) -> Result<InitEnclaveOutput> {
-    mesatee_core::init_service(env!("CARGO_PKG_NAME"))?;
+    mesatee_core::init_service_with_hints(env!("CARGO_PKG_NAME"), &SIZE_HINTS)?;
 
     register_trusted_worker_statically();
     Ok(InitEnclaveOutput::default())
@@ -44,6 +51,10 @@ fn handle_init_enclave(_args: &InitEnclaveInput) -> Result<InitEnclaveOutput> {
 
 #[handle_ecall]
 fn handle_finalize_enclave(_args: &FinalizeEnclaveInput) -> Result<FinalizeEnclaveOutput> {
+    // Stop serving new requests. In-flight requests are completed by their
+    // own ServeConnection ecalls.
+    server::shutdown();
+
     #[cfg(feature = "cov")]
     sgx_cov::cov_writeout();
 
@@ -51,6 +62,12 @@ fn handle_finalize_enclave(_args: &FinalizeEnclaveInput) -> Result<FinalizeEncla
     Ok(FinalizeEnclaveOutput::default())
 }
 
+#[handle_ecall]
+fn handle_run_maintenance(_args: &RunMaintenanceInput) -> Result<RunMaintenanceOutput> {
+    executors::register_executors()?;
+    Ok(RunMaintenanceOutput::default())
+}
+
 #[handle_ecall]
 fn handle_serve_connection(args: &ServeConnectionInput) -> Result<ServeConnectionOutput> {
     debug!("Enclave [FNS]: Serve Connection.");
@@ -69,7 +86,9 @@ fn handle_serve_connection(args: &ServeConnectionInput) -> Result<ServeConnectio
             return Ok(ServeConnectionOutput::default());
         }
     };
-    let _ = server.start();
+    let _ = server
+        .reject(args.reject)
+        .start_with_shutdown(server::shutdown_signal());
 
     // We discard all enclave internal errors here.
     Ok(ServeConnectionOutput::default())
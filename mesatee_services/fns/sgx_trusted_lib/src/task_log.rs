@@ -0,0 +1,153 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// Capture of what the function of a running task writes to stdout and stderr.
+// Each stream is split into lines, and a line is appended to the task log in
+// TMS once its line break is written, or when the task ends. The values of the
+// task secrets are redacted from a line before it leaves the enclave, and a
+// line is cut to `task_logs.max_line_bytes` after it is redacted. Only the
+// line being written is buffered, so that capture is bounded however much
+// the function writes.
+
+// Insert std prelude in the top for the sgx feature
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use mesatee_core::{config, Result};
+use tms_internal_client::TMSClient;
+use tms_internal_proto::{LogStream, TaskLogLine};
+
+const REDACTED: &str = "[REDACTED]";
+const LINE_TRUNCATED: &str = " [line truncated]";
+
+// Streams by their file descriptor, as used by the C API and by WASM modules.
+pub fn stream_of_fd(fd: i32) -> Option<LogStream> {
+    match fd {
+        1 => Some(LogStream::Stdout),
+        2 => Some(LogStream::Stderr),
+        _ => None,
+    }
+}
+
+#[derive(Default)]
+struct PendingLine {
+    data: Vec<u8>,
+    // Bytes beyond the buffer were dropped.
+    overflowed: bool,
+}
+
+pub struct TaskLogWriter {
+    task_id: String,
+    // Longest first, so that a secret containing another is redacted whole.
+    secrets: Vec<String>,
+    max_line_bytes: usize,
+    stdout: PendingLine,
+    stderr: PendingLine,
+}
+
+impl TaskLogWriter {
+    pub fn new<'a, I>(task_id: &str, secrets: I) -> Self
+    where
+        I: Iterator<Item = &'a String>,
+    {
+        let mut secrets: Vec<String> = secrets.filter(|s| !s.is_empty()).cloned().collect();
+        secrets.sort_by(|a, b| b.len().cmp(&a.len()));
+        TaskLogWriter {
+            task_id: task_id.to_owned(),
+            secrets,
+            max_line_bytes: config::runtime_config().task_logs.max_line_bytes,
+            stdout: PendingLine::default(),
+            stderr: PendingLine::default(),
+        }
+    }
+
+    // A secret starting within the kept part of a line is buffered whole, so
+    // that no part of it survives the cut.
+    fn buffer_limit(&self) -> usize {
+        let longest_secret = self.secrets.first().map_or(0, |secret| secret.len());
+        self.max_line_bytes + longest_secret
+    }
+
+    fn pending(&mut self, stream: LogStream) -> &mut PendingLine {
+        match stream {
+            LogStream::Stdout => &mut self.stdout,
+            LogStream::Stderr => &mut self.stderr,
+        }
+    }
+
+    pub fn write(&mut self, stream: LogStream, data: &[u8]) -> Result<()> {
+        let buffer_limit = self.buffer_limit();
+        let mut lines = Vec::new();
+        for &byte in data.iter() {
+            if byte == b'\n' {
+                let pending = std::mem::replace(self.pending(stream), PendingLine::default());
+                lines.push(self.line(stream, pending));
+                continue;
+            }
+            let pending = self.pending(stream);
+            if pending.data.len() < buffer_limit {
+                pending.data.push(byte);
+            } else {
+                pending.overflowed = true;
+            }
+        }
+        self.append(&lines)
+    }
+
+    // Appends the lines still without a line break, when the task ends.
+    pub fn flush(&mut self) -> Result<()> {
+        let mut lines = Vec::new();
+        for &stream in [LogStream::Stdout, LogStream::Stderr].iter() {
+            let pending = std::mem::replace(self.pending(stream), PendingLine::default());
+            if !pending.data.is_empty() || pending.overflowed {
+                lines.push(self.line(stream, pending));
+            }
+        }
+        self.append(&lines)
+    }
+
+    fn line(&self, stream: LogStream, pending: PendingLine) -> TaskLogLine {
+        let mut line = String::from_utf8_lossy(&pending.data).into_owned();
+        for secret in self.secrets.iter() {
+            line = line.replace(secret.as_str(), REDACTED);
+        }
+        let mut truncated = pending.overflowed;
+        if line.len() > self.max_line_bytes {
+            let mut end = self.max_line_bytes;
+            while !line.is_char_boundary(end) {
+                end -= 1;
+            }
+            line.truncate(end);
+            truncated = true;
+        }
+        if truncated {
+            line.push_str(LINE_TRUNCATED);
+        }
+        TaskLogLine { stream, line }
+    }
+
+    // Lines of a task which has ended, e.g. cancelled, are dropped by TMS.
+    fn append(&self, lines: &[TaskLogLine]) -> Result<()> {
+        if lines.is_empty() {
+            return Ok(());
+        }
+        let target = config::Internal::target_tms();
+        let mut client = TMSClient::new(target)?;
+        client.request_append_task_log(&self.task_id, lines)?;
+        Ok(())
+    }
+}
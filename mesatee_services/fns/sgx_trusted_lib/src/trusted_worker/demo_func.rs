@@ -19,7 +19,7 @@
 #[cfg(feature = "mesalock_sgx")]
 use std::prelude::v1::*;
 
-use crate::worker::{FunctionType, Worker, WorkerContext};
+use crate::worker::{FunctionType, LogStream, Worker, WorkerContext};
 use mesatee_core::{Error, ErrorKind, Result};
 
 pub struct EchoWorker {
@@ -72,6 +72,162 @@ impl Worker for EchoWorker {
     }
 }
 
+pub struct EchoEnvWorker {
+    worker_id: u32,
+    func_name: String,
+    func_type: FunctionType,
+    input: Option<EchoEnvWorkerInput>,
+}
+impl EchoEnvWorker {
+    pub fn new() -> Self {
+        EchoEnvWorker {
+            worker_id: 0,
+            func_name: "echo_env".to_string(),
+            func_type: FunctionType::Single,
+            input: None,
+        }
+    }
+}
+struct EchoEnvWorkerInput {
+    name: String,
+}
+impl Worker for EchoEnvWorker {
+    fn function_name(&self) -> &str {
+        self.func_name.as_str()
+    }
+    fn function_type(&self) -> FunctionType {
+        self.func_type
+    }
+    fn set_id(&mut self, worker_id: u32) {
+        self.worker_id = worker_id;
+    }
+    fn id(&self) -> u32 {
+        self.worker_id
+    }
+    fn prepare_input(
+        &mut self,
+        dynamic_input: Option<String>,
+        _file_ids: Vec<String>,
+    ) -> Result<()> {
+        let name = dynamic_input.ok_or_else(|| Error::from(ErrorKind::InvalidInputError))?;
+        self.input = Some(EchoEnvWorkerInput { name });
+        Ok(())
+    }
+    fn execute(&mut self, context: WorkerContext) -> Result<String> {
+        let input = self
+            .input
+            .take()
+            .ok_or_else(|| Error::from(ErrorKind::InvalidInputError))?;
+        context
+            .env(&input.name)
+            .map(|value| value.to_string())
+            .ok_or_else(|| Error::from(ErrorKind::InvalidInputError))
+    }
+}
+
+// Returns the task argument its payload names, as JSON.
+pub struct EchoArgWorker {
+    worker_id: u32,
+    func_name: String,
+    func_type: FunctionType,
+    input: Option<EchoEnvWorkerInput>,
+}
+impl EchoArgWorker {
+    pub fn new() -> Self {
+        EchoArgWorker {
+            worker_id: 0,
+            func_name: "echo_arg".to_string(),
+            func_type: FunctionType::Single,
+            input: None,
+        }
+    }
+}
+impl Worker for EchoArgWorker {
+    fn function_name(&self) -> &str {
+        self.func_name.as_str()
+    }
+    fn function_type(&self) -> FunctionType {
+        self.func_type
+    }
+    fn set_id(&mut self, worker_id: u32) {
+        self.worker_id = worker_id;
+    }
+    fn id(&self) -> u32 {
+        self.worker_id
+    }
+    fn prepare_input(
+        &mut self,
+        dynamic_input: Option<String>,
+        _file_ids: Vec<String>,
+    ) -> Result<()> {
+        let name = dynamic_input.ok_or_else(|| Error::from(ErrorKind::InvalidInputError))?;
+        self.input = Some(EchoEnvWorkerInput { name });
+        Ok(())
+    }
+    fn execute(&mut self, context: WorkerContext) -> Result<String> {
+        let input = self
+            .input
+            .take()
+            .ok_or_else(|| Error::from(ErrorKind::InvalidInputError))?;
+        context
+            .arguments
+            .get(&input.name)
+            .map(|value| value.to_string())
+            .ok_or_else(|| Error::from(ErrorKind::InvalidInputError))
+    }
+}
+
+// Writes its payload to stdout, and then to stderr how many bytes it wrote.
+pub struct EchoLogWorker {
+    worker_id: u32,
+    func_name: String,
+    func_type: FunctionType,
+    input: Option<EchoWorkerInput>,
+}
+impl EchoLogWorker {
+    pub fn new() -> Self {
+        EchoLogWorker {
+            worker_id: 0,
+            func_name: "echo_log".to_string(),
+            func_type: FunctionType::Single,
+            input: None,
+        }
+    }
+}
+impl Worker for EchoLogWorker {
+    fn function_name(&self) -> &str {
+        self.func_name.as_str()
+    }
+    fn function_type(&self) -> FunctionType {
+        self.func_type
+    }
+    fn set_id(&mut self, worker_id: u32) {
+        self.worker_id = worker_id;
+    }
+    fn id(&self) -> u32 {
+        self.worker_id
+    }
+    fn prepare_input(
+        &mut self,
+        dynamic_input: Option<String>,
+        _file_ids: Vec<String>,
+    ) -> Result<()> {
+        let msg = dynamic_input.ok_or_else(|| Error::from(ErrorKind::InvalidInputError))?;
+        self.input = Some(EchoWorkerInput { msg });
+        Ok(())
+    }
+    fn execute(&mut self, context: WorkerContext) -> Result<String> {
+        let input = self
+            .input
+            .take()
+            .ok_or_else(|| Error::from(ErrorKind::InvalidInputError))?;
+        context.write_log(LogStream::Stdout, input.msg.as_bytes())?;
+        let summary = format!("wrote {} bytes\n", input.msg.len());
+        context.write_log(LogStream::Stderr, summary.as_bytes())?;
+        Ok(input.msg)
+    }
+}
+
 pub struct EchoFileWorker {
     worker_id: u32,
     func_name: String,
@@ -355,3 +511,78 @@ impl Worker for SwapFileWorker {
         Ok(result_file_id)
     }
 }
+
+// Dynamic output port the partitions are saved on.
+pub const PARTITION_PORT: &str = "parts";
+
+// Splits the lines of a file into the number of partitions given as the
+// payload, each saved as a file on the dynamic output port `parts`.
+pub struct PartitionWorker {
+    worker_id: u32,
+    func_name: String,
+    func_type: FunctionType,
+    input: Option<PartitionWorkerInput>,
+}
+impl PartitionWorker {
+    pub fn new() -> Self {
+        PartitionWorker {
+            worker_id: 0,
+            func_name: "partition".to_string(),
+            func_type: FunctionType::Single,
+            input: None,
+        }
+    }
+}
+struct PartitionWorkerInput {
+    file_id: String,
+    partitions: usize,
+}
+impl Worker for PartitionWorker {
+    fn function_name(&self) -> &str {
+        self.func_name.as_str()
+    }
+    fn function_type(&self) -> FunctionType {
+        self.func_type
+    }
+    fn set_id(&mut self, worker_id: u32) {
+        self.worker_id = worker_id;
+    }
+    fn id(&self) -> u32 {
+        self.worker_id
+    }
+    fn prepare_input(
+        &mut self,
+        dynamic_input: Option<String>,
+        file_ids: Vec<String>,
+    ) -> Result<()> {
+        if file_ids.len() != 1 {
+            return Err(Error::from(ErrorKind::InvalidInputError));
+        }
+        let partitions = dynamic_input
+            .and_then(|payload| payload.trim().parse::<usize>().ok())
+            .filter(|partitions| *partitions > 0)
+            .ok_or_else(|| Error::from(ErrorKind::InvalidInputError))?;
+        self.input = Some(PartitionWorkerInput {
+            file_id: file_ids[0].to_string(),
+            partitions,
+        });
+        Ok(())
+    }
+    fn execute(&mut self, context: WorkerContext) -> Result<String> {
+        let input = self
+            .input
+            .take()
+            .ok_or_else(|| Error::from(ErrorKind::InvalidInputError))?;
+        let plaintext = context.read_file(&input.file_id)?;
+        let content = String::from_utf8_lossy(&plaintext);
+        let lines: Vec<&str> = content.lines().collect();
+        let mut file_ids = Vec::with_capacity(input.partitions);
+        for index in 0..input.partitions {
+            let start = index * lines.len() / input.partitions;
+            let end = (index + 1) * lines.len() / input.partitions;
+            let partition = lines[start..end].join("\n");
+            file_ids.push(context.save_file_for_output_port(PARTITION_PORT, partition.as_bytes())?);
+        }
+        Ok(file_ids.join(","))
+    }
+}
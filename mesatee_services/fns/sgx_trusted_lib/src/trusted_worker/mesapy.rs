@@ -21,10 +21,11 @@ use std::prelude::v1::*;
 
 use crate::worker::{FunctionType, Worker, WorkerContext};
 use itertools::Itertools;
-use mesatee_core::{Error, ErrorKind, Result};
+use mesatee_core::{config, Error, ErrorKind, Result};
 use sgx_types;
 use std::ffi::CString;
 use std::{format, vec};
+use tms_internal_proto::{python_modules, ExecutorCapability};
 
 const MAXPYBUFLEN: usize = 20480;
 const MESAPY_ERROR_BUFFER_TOO_SHORT: i64 = -1i64;
@@ -99,41 +100,324 @@ impl Worker for MesaPyWorker {
             .input
             .take()
             .ok_or_else(|| Error::from(ErrorKind::InvalidInputError))?;
-        let mut py_result = [0u8; MAXPYBUFLEN];
 
         let mut context_vec = vec![context.context_id, context.context_token];
         context_vec.extend_from_slice(&input.file_id_vec);
-        let cstr_argv: Vec<_> = context_vec
-            .iter()
-            .map(|arg| CString::new(arg.as_str()).unwrap())
-            .collect();
-
-        let mut p_argv: Vec<_> = cstr_argv
-            .iter() // do NOT into_iter()
-            .map(|arg| arg.as_ptr())
-            .collect();
-
-        p_argv.push(std::ptr::null());
-        let mesapy_exec_argc = context_vec.len();
-
-        let result = unsafe {
-            mesapy_exec(
-                input.py_script_vec.as_ptr(),
-                mesapy_exec_argc,
-                p_argv.as_ptr(),
-                &mut py_result as *mut _ as *mut u8,
-                MAXPYBUFLEN as u64,
-            )
+        match exec_script(&input.py_script_vec, &context_vec) {
+            Ok(result) => Ok(marshaled_result(&result)),
+            Err(MESAPY_ERROR_BUFFER_TOO_SHORT) => Ok("MESAPY_ERROR_BUFFER_TOO_SHORT".to_string()),
+            Err(_) => Ok("MESAPY_EXEC_ERROR".to_string()),
+        }
+    }
+}
+
+// Runs a NUL terminated script defining `entrypoint(argv)`, and returns the
+// marshaled result or the MesaPy error code.
+fn exec_script(py_script_vec: &[u8], argv: &[String]) -> std::result::Result<Vec<u8>, i64> {
+    let mut py_result = [0u8; MAXPYBUFLEN];
+
+    let cstr_argv: Vec<_> = argv
+        .iter()
+        .map(|arg| CString::new(arg.as_str()).unwrap())
+        .collect();
+
+    let mut p_argv: Vec<_> = cstr_argv
+        .iter() // do NOT into_iter()
+        .map(|arg| arg.as_ptr())
+        .collect();
+
+    p_argv.push(std::ptr::null());
+    let mesapy_exec_argc = argv.len();
+
+    let result = unsafe {
+        mesapy_exec(
+            py_script_vec.as_ptr(),
+            mesapy_exec_argc,
+            p_argv.as_ptr(),
+            &mut py_result as *mut _ as *mut u8,
+            MAXPYBUFLEN as u64,
+        )
+    };
+
+    if result == MESAPY_ERROR_BUFFER_TOO_SHORT || result == MESAPY_EXEC_ERROR {
+        return Err(result);
+    }
+    Ok(py_result.iter().take(result as usize).copied().collect())
+}
+
+fn marshaled_result(result: &[u8]) -> String {
+    format!("marshal.loads(b\"\\x{:02X}\")", result.iter().format("\\x"))
+}
+
+// Runs the script of a Python function. The script is compiled and run in
+// its own namespace, whose builtins lack those evaluating code or reaching
+// other namespaces, so that it can neither replace the entrypoint nor the
+// guards. Every import, made by the script or by the modules it uses, is
+// checked against the allowed modules while the script runs; the allowed
+// modules are loaded beforehand, so that their own imports at load time
+// succeed. TMS rejects scripts naming these builtins or the interpreter
+// internals when they are registered. Without the filesystem capability the
+// script may only use its task files.
+//
+// Keep DENIED_BUILTINS in sync with the static check of TMS.
+const PYTHON_FUNCTION_PRELUDE: &str = r#"
+def _task_entrypoint(source, allowed_modules, filesystem, line_budget, memory_limit):
+    import sys
+    import base64
+    import __builtin__
+    import mesatee
+
+    DENIED_BUILTINS = ('compile', 'delattr', 'eval', 'execfile', 'getattr', 'globals',
+                       'input', 'locals', 'reload', 'setattr', 'vars')
+
+    original_import = __builtin__.__import__
+
+    def denied_open(*args, **kwargs):
+        raise IOError('filesystem access is not allowed')
+
+    # A bound method rather than a closure, so the original import is out of
+    # reach of func_closure and only found through the denied im_self
+    class ImportGuard(object):
+        __slots__ = ('_allowed', '_load')
+
+        def __init__(self, allowed, load):
+            self._allowed = allowed
+            self._load = load
+
+        def load(self, name, globals=None, locals=None, fromlist=(), level=-1):
+            module = name.split('.')[0]
+            if module != '__future__' and module not in self._allowed:
+                raise ImportError('import of module %s is not allowed' % name)
+            return self._load(name, globals, locals, fromlist, level)
+
+    guarded_import = ImportGuard(allowed_modules, original_import).load
+
+    def guarded(function, *args):
+        __builtin__.__import__ = guarded_import
+        try:
+            return function(*args)
+        finally:
+            __builtin__.__import__ = original_import
+
+    usage = {'lines': 0, 'bytes': 0}
+
+    def trace(frame, event, arg):
+        if event == 'line':
+            usage['lines'] += 1
+            if usage['lines'] > line_budget:
+                raise RuntimeError('CPU limit exceeded')
+        return trace
+
+    def use_memory(size):
+        usage['bytes'] += size
+        if usage['bytes'] > memory_limit:
+            raise MemoryError('memory limit exceeded')
+
+    class TaskInput(object):
+        def __init__(self, context_id, context_token, file_id):
+            self.file_id = file_id
+            self._data = mesatee.mesatee_read_file(context_id, context_token, file_id)
+            self._pos = 0
+            use_memory(len(self._data))
+
+        def read(self, size=-1):
+            end = len(self._data) if size < 0 else min(self._pos + size, len(self._data))
+            data = self._data[self._pos:end]
+            self._pos = end
+            return data
+
+        def readline(self):
+            end = self._data.find('\n', self._pos)
+            return self.read(-1 if end < 0 else end + 1 - self._pos)
+
+        def __iter__(self):
+            line = self.readline()
+            while line:
+                yield line
+                line = self.readline()
+
+        def close(self):
+            pass
+
+        def __enter__(self):
+            return self
+
+        def __exit__(self, *args):
+            self.close()
+
+    class TaskOutput(object):
+        def __init__(self, context_id, context_token):
+            self.file_id = None
+            self._context = (context_id, context_token)
+            self._chunks = []
+
+        def write(self, data):
+            if self.file_id is not None:
+                raise ValueError('write to closed output')
+            use_memory(len(data))
+            self._chunks.append(data)
+
+        def close(self):
+            if self.file_id is None:
+                context_id, context_token = self._context
+                data = ''.join(self._chunks)
+                self.file_id = mesatee.mesatee_save_file_for_task_creator(
+                    context_id, context_token, data)
+
+        def __enter__(self):
+            return self
+
+        def __exit__(self, *args):
+            self.close()
+
+    class TaskLog(object):
+        def __init__(self, context_id, context_token, fd):
+            self._context = (context_id, context_token)
+            self._fd = fd
+
+        def write(self, data):
+            context_id, context_token = self._context
+            mesatee.mesatee_write_task_log(context_id, context_token, self._fd, str(data))
+
+        def flush(self):
+            pass
+
+    for module in allowed_modules:
+        try:
+            original_import(module)
+        except ImportError:
+            pass
+    if not filesystem:
+        __builtin__.open = denied_open
+        __builtin__.file = denied_open
+
+    builtins = dict(__builtin__.__dict__)
+    for name in DENIED_BUILTINS:
+        builtins.pop(name, None)
+    builtins['__import__'] = guarded_import
+    script_globals = {'__builtins__': builtins, '__name__': '__main__'}
+    code = compile(base64.b64decode(source), '<python function>', 'exec')
+    guarded(eval, code, script_globals)
+    main = script_globals.get('main')
+    if not callable(main):
+        raise TypeError('Python function must define main(inputs, output)')
+
+    def entrypoint(argv):
+        context_id, context_token = argv[0], argv[1]
+        inputs = [TaskInput(context_id, context_token, file_id) for file_id in argv[2:]]
+        output = TaskOutput(context_id, context_token)
+        # MesaPy builds without the binding of c_write_task_log keep their output
+        if hasattr(mesatee, 'mesatee_write_task_log'):
+            sys.stdout = TaskLog(context_id, context_token, 1)
+            sys.stderr = TaskLog(context_id, context_token, 2)
+        sys.settrace(trace)
+        try:
+            result = guarded(main, inputs, output)
+        finally:
+            sys.settrace(None)
+            sys.stdout, sys.stderr = sys.__stdout__, sys.__stderr__
+        output.close()
+        return result
+
+    return entrypoint
+"#;
+
+pub struct PythonFunctionWorker {
+    worker_id: u32,
+    func_name: String,
+    func_type: FunctionType,
+    input: Option<MesaPyWorkerWorkerInput>,
+}
+
+impl PythonFunctionWorker {
+    pub fn new() -> Self {
+        PythonFunctionWorker {
+            worker_id: 0,
+            func_name: tms_internal_proto::PYTHON_FUNCTION_NAME.to_string(),
+            func_type: FunctionType::Single,
+            input: None,
+        }
+    }
+}
+
+fn python_string_list(values: &[String]) -> String {
+    let quoted: Vec<String> = values.iter().map(|value| format!("{:?}", value)).collect();
+    format!("[{}]", quoted.join(", "))
+}
+
+impl Worker for PythonFunctionWorker {
+    fn function_name(&self) -> &str {
+        self.func_name.as_str()
+    }
+    fn function_type(&self) -> FunctionType {
+        self.func_type
+    }
+    fn set_id(&mut self, worker_id: u32) {
+        self.worker_id = worker_id;
+    }
+    fn id(&self) -> u32 {
+        self.worker_id
+    }
+    fn prepare_input(
+        &mut self,
+        dynamic_input: Option<String>,
+        file_ids: Vec<String>,
+    ) -> Result<()> {
+        let source = match dynamic_input {
+            Some(value) => value,
+            None => return Err(Error::from(ErrorKind::InvalidInputError)),
         };
+        if source.contains('\0') {
+            return Err(Error::from(ErrorKind::InvalidInputError));
+        }
+        self.input = Some(MesaPyWorkerWorkerInput {
+            py_script_vec: source.into_bytes(),
+            file_id_vec: file_ids,
+        });
+        Ok(())
+    }
+
+    fn execute(&mut self, context: WorkerContext) -> Result<String> {
+        let input = self
+            .input
+            .take()
+            .ok_or_else(|| Error::from(ErrorKind::InvalidInputError))?;
+        let python_config = &config::runtime_config().python_executor;
+        let limits = context.resource_limits;
+        let line_budget = limits
+            .max_cpu_seconds
+            .saturating_mul(python_config.lines_per_cpu_second);
+        let memory_limit = limits.max_memory_mb.saturating_mul(1024 * 1024);
 
-        match result {
-            MESAPY_ERROR_BUFFER_TOO_SHORT => Ok("MESAPY_ERROR_BUFFER_TOO_SHORT".to_string()),
-            MESAPY_EXEC_ERROR => Ok("MESAPY_EXEC_ERROR".to_string()),
-            len => {
-                let r: Vec<u8> = py_result.iter().take(len as usize).copied().collect();
-                let payload = format!("marshal.loads(b\"\\x{:02X}\")", r.iter().format("\\x"));
-                Ok(payload)
-            }
+        let mut script = PYTHON_FUNCTION_PRELUDE.as_bytes().to_vec();
+        let allowed_modules = python_modules(&python_config.allowed_modules, &context.capabilities);
+        let filesystem = context
+            .capabilities
+            .contains(&ExecutorCapability::Filesystem);
+        let entrypoint = format!(
+            "entrypoint = _task_entrypoint(\"{}\", frozenset({}), {}, {}, {})\n\
+             del _task_entrypoint\n",
+            base64::encode(&input.py_script_vec),
+            python_string_list(&allowed_modules),
+            if filesystem { "True" } else { "False" },
+            line_budget,
+            memory_limit,
+        );
+        script.extend_from_slice(entrypoint.as_bytes());
+        script.push(0u8);
+
+        let mut context_vec = vec![context.context_id, context.context_token];
+        context_vec.extend_from_slice(&input.file_id_vec);
+        match exec_script(&script, &context_vec) {
+            Ok(result) => Ok(marshaled_result(&result)),
+            Err(MESAPY_ERROR_BUFFER_TOO_SHORT) => Err(Error::new(
+                ErrorKind::OutputGenerationError,
+                "result of the Python function is too large",
+            )),
+            Err(_) => Err(Error::new(
+                ErrorKind::OutputGenerationError,
+                "Python function raised an exception or exceeded its limits",
+            )),
         }
     }
 }
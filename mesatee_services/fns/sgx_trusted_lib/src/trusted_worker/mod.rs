@@ -21,9 +21,9 @@ pub use demo_func::*;
 mod psi;
 pub use psi::PSIWorker;
 mod wasm;
-pub use wasm::WASMWorker;
+pub use wasm::{WASMWorker, WasmFunctionWorker};
 mod mesapy;
-pub use mesapy::MesaPyWorker;
+pub use mesapy::{MesaPyWorker, PythonFunctionWorker};
 mod gbdt_worker;
 pub use gbdt_worker::*;
 mod private_join_and_compute;
@@ -26,6 +26,8 @@ use serde_json;
 
 mod sgxwasm;
 mod sgxwasm_compute;
+mod wasm_function;
+pub use wasm_function::WasmFunctionWorker;
 
 use sgxwasm::BoundaryValue;
 
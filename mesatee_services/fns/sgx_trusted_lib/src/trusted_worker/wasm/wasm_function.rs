@@ -0,0 +1,411 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// Insert std prelude in the top for the sgx feature
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use crate::task_log;
+use crate::worker::{FunctionType, Worker, WorkerContext};
+use mesatee_core::{Error, ErrorKind, Result};
+use parity_wasm::elements::{self, External, MemoryType};
+use std::cell::RefCell;
+use std::cmp;
+use std::format;
+use wasmi::memory_units::Pages;
+use wasmi::{
+    Error as InterpreterError, Externals, FuncInstance, FuncRef, ImportsBuilder, MemoryDescriptor,
+    MemoryInstance, MemoryRef, ModuleImportResolver, ModuleInstance, RuntimeArgs, RuntimeValue,
+    Signature, Trap, TrapKind, ValueType,
+};
+
+// Modules export `entrypoint: () -> i32` and may import the host functions
+// below from `env`. Files are addressed by their index in the task's input
+// files, so a module can only reach the files declared for its task.
+//
+//   input_count() -> i32
+//   input_size(index: i32) -> i32
+//   read_input(index: i32, ptr: i32, len: i32) -> i32
+//   write_output(ptr: i32, len: i32) -> i32
+//   write_log(fd: i32, ptr: i32, len: i32) -> i32
+//
+// `write_log` writes to the task log, `fd` being 1 for stdout and 2 for stderr.
+//
+// Host functions return -1 on failure. The linear memory is either imported
+// as `env.memory` or exported as `memory`, and it may not grow beyond the
+// memory limit of the task. Memories are checked against the limit before the
+// module is instantiated, which allocates their initial size.
+const HOST_MODULE: &str = "env";
+const ENTRYPOINT: &str = "entrypoint";
+const MEMORY: &str = "memory";
+
+const INPUT_COUNT_INDEX: usize = 0;
+const INPUT_SIZE_INDEX: usize = 1;
+const READ_INPUT_INDEX: usize = 2;
+const WRITE_OUTPUT_INDEX: usize = 3;
+const WRITE_LOG_INDEX: usize = 4;
+const HOST_ERROR: i32 = -1;
+
+pub struct WasmFunctionWorker {
+    worker_id: u32,
+    func_name: String,
+    func_type: FunctionType,
+    input: Option<WasmFunctionInput>,
+}
+
+struct WasmFunctionInput {
+    module: Vec<u8>,
+    file_ids: Vec<String>,
+}
+
+impl WasmFunctionWorker {
+    pub fn new() -> Self {
+        WasmFunctionWorker {
+            worker_id: 0,
+            func_name: tms_internal_proto::WASM_FUNCTION_NAME.to_string(),
+            func_type: FunctionType::Single,
+            input: None,
+        }
+    }
+}
+
+impl Worker for WasmFunctionWorker {
+    fn function_name(&self) -> &str {
+        self.func_name.as_str()
+    }
+    fn function_type(&self) -> FunctionType {
+        self.func_type
+    }
+    fn set_id(&mut self, worker_id: u32) {
+        self.worker_id = worker_id;
+    }
+    fn id(&self) -> u32 {
+        self.worker_id
+    }
+    fn prepare_input(
+        &mut self,
+        dynamic_input: Option<String>,
+        file_ids: Vec<String>,
+    ) -> Result<()> {
+        let payload = match dynamic_input {
+            Some(value) => value,
+            None => return Err(Error::from(ErrorKind::InvalidInputError)),
+        };
+        let module =
+            base64::decode(&payload).or_else(|_| Err(Error::from(ErrorKind::InvalidInputError)))?;
+        self.input = Some(WasmFunctionInput { module, file_ids });
+        Ok(())
+    }
+
+    fn execute(&mut self, context: WorkerContext) -> Result<String> {
+        let input = self
+            .input
+            .take()
+            .ok_or_else(|| Error::from(ErrorKind::InvalidInputError))?;
+        let memory_limit = memory_limit(context.resource_limits.max_memory_mb);
+
+        let module: elements::Module =
+            parity_wasm::deserialize_buffer(&input.module).map_err(|e| {
+                Error::new(
+                    ErrorKind::InvalidInputError,
+                    format!("invalid WASM module: {:?}", e),
+                )
+            })?;
+        check_memory(&module, memory_limit)?;
+        let module = wasmi::Module::from_parity_wasm_module(module).map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidInputError,
+                format!("invalid WASM module: {:?}", e),
+            )
+        })?;
+        let resolver = HostResolver::new(memory_limit);
+        let imports = ImportsBuilder::new().with_resolver(HOST_MODULE, &resolver);
+        let instance = ModuleInstance::new(&module, &imports).map_err(instantiation_error)?;
+
+        let memory = match resolver.memory.borrow().clone() {
+            Some(memory) => memory,
+            None => instance
+                .not_started_instance()
+                .export_by_name(MEMORY)
+                .and_then(|export| export.as_memory().cloned())
+                .ok_or_else(|| {
+                    Error::new(
+                        ErrorKind::InvalidInputError,
+                        "WASM module must import or export its memory",
+                    )
+                })?,
+        };
+
+        let mut host = Host {
+            context,
+            memory,
+            file_ids: input.file_ids,
+            inputs: Vec::new(),
+        };
+        host.inputs.resize(host.file_ids.len(), None);
+        let instance = instance
+            .run_start(&mut host)
+            .map_err(|trap| execution_error(InterpreterError::from(trap)))?;
+        let result = instance
+            .invoke_export(ENTRYPOINT, &[], &mut host)
+            .map_err(execution_error)?;
+        match result {
+            Some(RuntimeValue::I32(value)) => Ok(value.to_string()),
+            _ => Err(Error::new(
+                ErrorKind::OutputGenerationError,
+                "WASM entrypoint must return an i32",
+            )),
+        }
+    }
+}
+
+fn memory_limit(max_memory_mb: u64) -> Pages {
+    let max_pages = max_memory_mb.saturating_mul(1024 * 1024) / Pages::BYTE_SIZE.0 as u64;
+    // Linear memory is limited to 4GiB anyway.
+    Pages(cmp::min(max_pages, u64::from(u16::max_value()) + 1) as usize)
+}
+
+// Rejects memories whose initial size exceeds `memory_limit`, and memories of
+// the module itself without a maximum within it. The maximum of an imported
+// memory is lowered to the limit by `HostResolver` instead.
+fn check_memory(module: &elements::Module, memory_limit: Pages) -> Result<()> {
+    let within_limit = |pages: u32| Pages(pages as usize) <= memory_limit;
+    let imported = module
+        .import_section()
+        .map_or(&[][..], |section| section.entries())
+        .iter()
+        .all(|entry| match entry.external() {
+            External::Memory(memory) => within_limit(memory.limits().initial()),
+            _ => true,
+        });
+    let own = module
+        .memory_section()
+        .map_or(&[][..], |section| section.entries())
+        .iter()
+        .all(|memory: &MemoryType| {
+            within_limit(memory.limits().initial())
+                && memory.limits().maximum().map_or(false, within_limit)
+        });
+    if imported && own {
+        Ok(())
+    } else {
+        Err(Error::new(
+            ErrorKind::InvalidInputError,
+            format!(
+                "WASM memory must have an initial size and a maximum of at most {} pages",
+                memory_limit.0
+            ),
+        ))
+    }
+}
+
+fn instantiation_error(e: InterpreterError) -> Error {
+    Error::new(
+        ErrorKind::InvalidInputError,
+        format!("cannot instantiate WASM module: {:?}", e),
+    )
+}
+
+fn execution_error(e: InterpreterError) -> Error {
+    Error::new(
+        ErrorKind::OutputGenerationError,
+        format!("WASM execution failed: {:?}", e),
+    )
+}
+
+struct HostResolver {
+    memory_limit: Pages,
+    memory: RefCell<Option<MemoryRef>>,
+}
+
+impl HostResolver {
+    fn new(memory_limit: Pages) -> Self {
+        HostResolver {
+            memory_limit,
+            memory: RefCell::new(None),
+        }
+    }
+}
+
+impl ModuleImportResolver for HostResolver {
+    fn resolve_func(
+        &self,
+        field_name: &str,
+        signature: &Signature,
+    ) -> std::result::Result<FuncRef, InterpreterError> {
+        let (index, expected) = match field_name {
+            "input_count" => (
+                INPUT_COUNT_INDEX,
+                Signature::new(&[][..], Some(ValueType::I32)),
+            ),
+            "input_size" => (
+                INPUT_SIZE_INDEX,
+                Signature::new(&[ValueType::I32][..], Some(ValueType::I32)),
+            ),
+            "read_input" => (
+                READ_INPUT_INDEX,
+                Signature::new(&[ValueType::I32; 3][..], Some(ValueType::I32)),
+            ),
+            "write_output" => (
+                WRITE_OUTPUT_INDEX,
+                Signature::new(&[ValueType::I32; 2][..], Some(ValueType::I32)),
+            ),
+            "write_log" => (
+                WRITE_LOG_INDEX,
+                Signature::new(&[ValueType::I32; 3][..], Some(ValueType::I32)),
+            ),
+            _ => {
+                return Err(InterpreterError::Instantiation(format!(
+                    "Unknown host func import {}",
+                    field_name
+                )))
+            }
+        };
+        if signature != &expected {
+            return Err(InterpreterError::Instantiation(format!(
+                "Host func import {} has signature {:?}, expected {:?}",
+                field_name, signature, expected
+            )));
+        }
+        Ok(FuncInstance::alloc_host(expected, index))
+    }
+
+    fn resolve_memory(
+        &self,
+        field_name: &str,
+        descriptor: &MemoryDescriptor,
+    ) -> std::result::Result<MemoryRef, InterpreterError> {
+        if field_name != MEMORY {
+            return Err(InterpreterError::Instantiation(format!(
+                "Unknown host memory import {}",
+                field_name
+            )));
+        }
+        let maximum = match descriptor.maximum() {
+            Some(maximum) => cmp::min(Pages(maximum as usize), self.memory_limit),
+            None => self.memory_limit,
+        };
+        let memory = MemoryInstance::alloc(Pages(descriptor.initial() as usize), Some(maximum))?;
+        *self.memory.borrow_mut() = Some(memory.clone());
+        Ok(memory)
+    }
+}
+
+struct Host {
+    context: WorkerContext,
+    memory: MemoryRef,
+    file_ids: Vec<String>,
+    // Contents of the input files, read on first use.
+    inputs: Vec<Option<Vec<u8>>>,
+}
+
+impl Host {
+    fn input(&mut self, index: i32) -> Option<&[u8]> {
+        if index < 0 || index as usize >= self.file_ids.len() {
+            return None;
+        }
+        let index = index as usize;
+        if self.inputs[index].is_none() {
+            let content = self.context.read_file(&self.file_ids[index]).ok()?;
+            self.inputs[index] = Some(content);
+        }
+        self.inputs[index]
+            .as_ref()
+            .map(|content| content.as_slice())
+    }
+
+    fn input_size(&mut self, index: i32) -> i32 {
+        match self.input(index) {
+            Some(content) => content.len() as i32,
+            None => HOST_ERROR,
+        }
+    }
+
+    fn read_input(&mut self, index: i32, ptr: i32, len: i32) -> i32 {
+        if ptr < 0 || len < 0 {
+            return HOST_ERROR;
+        }
+        let memory = self.memory.clone();
+        let content = match self.input(index) {
+            Some(content) => content,
+            None => return HOST_ERROR,
+        };
+        let len = cmp::min(len as usize, content.len());
+        match memory.set(ptr as u32, &content[..len]) {
+            Ok(()) => len as i32,
+            Err(_) => HOST_ERROR,
+        }
+    }
+
+    fn write_output(&mut self, ptr: i32, len: i32) -> i32 {
+        if ptr < 0 || len < 0 {
+            return HOST_ERROR;
+        }
+        let data = match self.memory.get(ptr as u32, len as usize) {
+            Ok(data) => data,
+            Err(_) => return HOST_ERROR,
+        };
+        match self.context.save_file_for_task_creator(&data) {
+            Ok(_) => 0,
+            Err(_) => HOST_ERROR,
+        }
+    }
+
+    fn write_log(&mut self, fd: i32, ptr: i32, len: i32) -> i32 {
+        let stream = match task_log::stream_of_fd(fd) {
+            Some(stream) => stream,
+            None => return HOST_ERROR,
+        };
+        if ptr < 0 || len < 0 {
+            return HOST_ERROR;
+        }
+        let data = match self.memory.get(ptr as u32, len as usize) {
+            Ok(data) => data,
+            Err(_) => return HOST_ERROR,
+        };
+        match self.context.write_log(stream, &data) {
+            Ok(()) => 0,
+            Err(_) => HOST_ERROR,
+        }
+    }
+}
+
+impl Externals for Host {
+    fn invoke_index(
+        &mut self,
+        index: usize,
+        args: RuntimeArgs,
+    ) -> std::result::Result<Option<RuntimeValue>, Trap> {
+        let result = match index {
+            INPUT_COUNT_INDEX => self.file_ids.len() as i32,
+            INPUT_SIZE_INDEX => self.input_size(args.nth_checked(0)?),
+            READ_INPUT_INDEX => self.read_input(
+                args.nth_checked(0)?,
+                args.nth_checked(1)?,
+                args.nth_checked(2)?,
+            ),
+            WRITE_OUTPUT_INDEX => self.write_output(args.nth_checked(0)?, args.nth_checked(1)?),
+            WRITE_LOG_INDEX => self.write_log(
+                args.nth_checked(0)?,
+                args.nth_checked(1)?,
+                args.nth_checked(2)?,
+            ),
+            _ => return Err(Trap::new(TrapKind::Unreachable)),
+        };
+        Ok(Some(RuntimeValue::I32(result)))
+    }
+}
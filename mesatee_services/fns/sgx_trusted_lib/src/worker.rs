@@ -17,32 +17,84 @@
 use crate::global;
 use lazy_static::lazy_static;
 use mesatee_core::{Error, ErrorKind, Result};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 #[cfg(feature = "mesalock_sgx")]
 use std::prelude::v1::*;
 #[cfg(feature = "mesalock_sgx")]
 use std::sync::SgxRwLock as RwLock;
 pub use tms_internal_proto::FunctionType;
+pub use tms_internal_proto::LogStream;
+use tms_internal_proto::{ExecutorCapability, FileFormat, ResourceLimits, TaskArguments};
 
 pub struct WorkerContext {
     pub context_id: String, // Context_id and context_token are used for retrieving RunningTask
     pub context_token: String,
+    // Limits of the task, workers able to bound their usage should honor them.
+    pub resource_limits: ResourceLimits,
+    // Secrets of the task by environment variable, only ever in the enclave.
+    pub env: BTreeMap<String, String>,
+    // Arguments of the task, those of its arguments file included.
+    pub arguments: TaskArguments,
+    // Capabilities the function declared and the cluster still allows.
+    pub capabilities: Vec<ExecutorCapability>,
 }
 
 impl WorkerContext {
+    pub fn env(&self, name: &str) -> Option<&str> {
+        self.env.get(name).map(|value| value.as_str())
+    }
     pub fn read_file(&self, file_id: &str) -> Result<Vec<u8>> {
         global::read_file(&self.context_id, &self.context_token, file_id)
     }
     pub fn save_file_for_file_owner(&self, data: &[u8], file_id: &str) -> Result<String> {
         global::save_file_for_file_owner(&self.context_id, &self.context_token, data, file_id)
     }
-    #[allow(dead_code)]
     pub fn save_file_for_task_creator(&self, data: &[u8]) -> Result<String> {
         global::save_file_for_task_creator(&self.context_id, &self.context_token, data)
     }
+    // Saves a file for the task creator, tagged with the content type and
+    // schema of `format`.
+    pub fn save_file_for_task_creator_with_format(
+        &self,
+        data: &[u8],
+        format: &FileFormat,
+    ) -> Result<String> {
+        global::save_file_for_task_creator_with_format(
+            &self.context_id,
+            &self.context_token,
+            data,
+            format,
+        )
+    }
+    // Functions with a dynamic output port save each file they produce on
+    // it for the task creator.
+    pub fn save_file_for_output_port(&self, port: &str, data: &[u8]) -> Result<String> {
+        global::save_file_for_output_port(&self.context_id, &self.context_token, port, data)
+    }
     pub fn save_file_for_all_participants(&self, data: &[u8]) -> Result<String> {
         global::save_file_for_all_participants(&self.context_id, &self.context_token, data)
     }
+    // Long-running workers may report their progress, with the output they
+    // have produced so far.
+    pub fn report_progress(
+        &self,
+        percent: u8,
+        message: &str,
+        partial_output: Option<&[u8]>,
+    ) -> Result<()> {
+        global::report_progress(
+            &self.context_id,
+            &self.context_token,
+            percent,
+            message,
+            partial_output,
+        )
+    }
+    // What workers write to stdout and stderr goes to the task log, which the
+    // task creator may stream while the task runs.
+    pub fn write_log(&self, stream: LogStream, data: &[u8]) -> Result<()> {
+        global::write_log(&self.context_id, &self.context_token, stream, data)
+    }
 }
 
 pub trait Worker: Send + Sync {
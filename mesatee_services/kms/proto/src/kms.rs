@@ -16,6 +16,7 @@
 // under the License.
 
 use crate::proto;
+use mesatee_core::config::SignedEnclaveInfo;
 use mesatee_core::{Error, ErrorKind, Result};
 use rand::prelude::RngCore;
 use std::convert::TryFrom;
@@ -205,3 +206,35 @@ impl proto::GetKeyResponse {
         KeyConfig::try_from(self.config.clone())
     }
 }
+
+impl proto::ReloadEnclaveInfoRequest {
+    pub fn new(fragments: &[SignedEnclaveInfo]) -> Self {
+        proto::ReloadEnclaveInfoRequest {
+            fragments: fragments
+                .iter()
+                .map(|fragment| proto::SignedEnclaveInfo {
+                    enclave_info: fragment.enclave_info.clone(),
+                    auditor_signatures: fragment.auditor_signatures.clone(),
+                })
+                .collect(),
+        }
+    }
+    pub fn get_fragments(&self) -> Vec<SignedEnclaveInfo> {
+        self.fragments
+            .iter()
+            .map(|fragment| SignedEnclaveInfo {
+                enclave_info: fragment.enclave_info.clone(),
+                auditor_signatures: fragment.auditor_signatures.clone(),
+            })
+            .collect()
+    }
+}
+
+impl proto::ReloadEnclaveInfoResponse {
+    pub fn new(services: Vec<String>) -> Self {
+        proto::ReloadEnclaveInfoResponse { services }
+    }
+    pub fn get_services(&self) -> Vec<String> {
+        self.services.clone()
+    }
+}
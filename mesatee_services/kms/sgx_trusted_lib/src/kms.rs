@@ -23,10 +23,12 @@ use uuid::Uuid;
 
 use kms_proto::proto::{
     CreateKeyRequest, CreateKeyResponse, DeleteKeyRequest, DeleteKeyResponse, GetKeyRequest,
-    GetKeyResponse, KMSRequest, KMSResponse, KMSService,
+    GetKeyResponse, KMSRequest, KMSResponse, KMSService, ReloadEnclaveInfoRequest,
+    ReloadEnclaveInfoResponse,
 };
 use kms_proto::{AEADKeyConfig, EncType, KeyConfig};
 use lazy_static::lazy_static;
+use mesatee_core::config;
 use mesatee_core::db::Memdb;
 use mesatee_core::rpc::EnclaveService;
 use mesatee_core::{Error, ErrorKind, Result};
@@ -86,6 +88,13 @@ impl KMSService for KMSEnclave<KMSRequest, KMSResponse> {
         KEY_STORE.set(&key_id, &config)?;
         Ok(CreateKeyResponse::new(&key_id, &config))
     }
+    fn reload_enclave_info(
+        req: ReloadEnclaveInfoRequest,
+    ) -> mesatee_core::Result<ReloadEnclaveInfoResponse> {
+        let services = config::reload_enclave_info(&req.get_fragments())?;
+        info!("Enclave info of {} services reloaded", services.len());
+        Ok(ReloadEnclaveInfoResponse::new(services))
+    }
 }
 
 impl EnclaveService<KMSRequest, KMSResponse> for KMSEnclave<KMSRequest, KMSResponse> {
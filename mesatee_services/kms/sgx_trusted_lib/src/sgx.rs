@@ -20,8 +20,9 @@
 use std::prelude::v1::*;
 
 use mesatee_core::config;
+use mesatee_core::enclave_size::EnclaveSize;
 use mesatee_core::prelude::*;
-use mesatee_core::rpc::server::SgxTrustedServer;
+use mesatee_core::rpc::server::{self, SgxTrustedServer};
 use mesatee_core::Result;
 
 use crate::kms::KMSEnclave;
@@ -51,21 +52,30 @@ fn handle_serve_connection(args: &ServeConnectionInput) -> Result<ServeConnectio
             return Ok(ServeConnectionOutput::default());
         }
     };
-    let _ = server.start();
+    let _ = server
+        .reject(args.reject)
+        .start_with_shutdown(server::shutdown_signal());
 
     // We discard all enclave internal errors here.
     Ok(ServeConnectionOutput::default())
 }
 
+// Sizes KMS is expected to need under load.
+const SIZE_HINTS: EnclaveSize = EnclaveSize::new(0x100_0000, 0x20_0000);
+
 #[handle_ecall]
 fn handle_init_enclave(_args: &InitEnclaveInput) -> Result<InitEnclaveOutput> {
-    mesatee_core::init_service(env!("CARGO_PKG_NAME"))?;
+    mesatee_core::init_service_with_hints(env!("CARGO_PKG_NAME"), &SIZE_HINTS)?;
 
     Ok(InitEnclaveOutput::default())
 }
 
 #[handle_ecall]
 fn handle_finalize_enclave(_args: &FinalizeEnclaveInput) -> Result<FinalizeEnclaveOutput> {
+    // Stop serving new requests. In-flight requests are completed by their
+    // own ServeConnection ecalls.
+    server::shutdown();
+
     #[cfg(feature = "cov")]
     sgx_cov::cov_writeout();
 
@@ -21,12 +21,16 @@ use std::prelude::v1::*;
 
 use crate::file_util;
 use mesatee_core::config::{OutboundDesc, TargetDesc};
+use mesatee_core::encryption_context;
 use mesatee_core::rpc::channel::SgxTrustedChannel;
 use mesatee_core::{self, Result};
+use ring::digest;
 use std::fs;
+use std::io::{Read, Write};
 use tdfs_external_proto::{
-    CreateFileResponse, DFSRequest, DFSResponse, DeleteFileResponse, GetFileResponse,
-    ListFileResponse,
+    BeginUploadResponse, CommitUploadResponse, CreateFileResponse, DFSRequest, DFSResponse,
+    DeleteFileResponse, FileInfo, GetFileResponse, GetOutputChunkResponse, HealthCheckResponse,
+    ListFileResponse, UploadChunkResponse,
 };
 
 pub struct TDFSClient {
@@ -55,13 +59,19 @@ impl TDFSClient {
     fn request_create_file(
         &mut self,
         file_name: &str,
-        sha256: &str,
+        digest_algorithm: &str,
+        digest: &str,
+        mac_key: &[u8],
         file_size: u32,
+        collaborator_list: &[&str],
     ) -> Result<CreateFileResponse> {
-        let req = DFSRequest::new_create_file(
+        let req = DFSRequest::new_create_file_with_digest(
             file_name,
-            sha256,
+            digest_algorithm,
+            digest,
+            mac_key,
             file_size,
+            collaborator_list,
             &self.user_id,
             &self.user_token,
         );
@@ -74,7 +84,7 @@ impl TDFSClient {
         }
     }
 
-    fn request_get_file(&mut self, file_id: &str) -> Result<GetFileResponse> {
+    pub fn request_get_file(&mut self, file_id: &str) -> Result<GetFileResponse> {
         let req = DFSRequest::new_get_file(file_id, &self.user_id, &self.user_token);
         let resp = self.channel.invoke(req)?;
         match resp {
@@ -86,11 +96,81 @@ impl TDFSClient {
     }
 
     pub fn save_file(&mut self, file_path: &str, file_name: &str) -> Result<String> {
+        self.save_shared_file(file_path, file_name, &[])
+    }
+
+    /// Uploads a file that the listed users may also read and use as task input.
+    pub fn save_shared_file(
+        &mut self,
+        file_path: &str,
+        file_name: &str,
+        collaborator_list: &[&str],
+    ) -> Result<String> {
+        self.save_file_with_digest_algorithm(
+            file_path,
+            file_name,
+            collaborator_list,
+            file_util::DEFAULT_DIGEST_ALGORITHM,
+        )
+    }
+
+    /// Uploads a file whose integrity is checked against a digest with
+    /// `digest_algorithm`, `sha256` or `sha384`, before it is used by a task.
+    pub fn save_file_with_digest_algorithm(
+        &mut self,
+        file_path: &str,
+        file_name: &str,
+        collaborator_list: &[&str],
+        digest_algorithm: &str,
+    ) -> Result<String> {
         let data = fs::read(&file_path)
             .map_err(|_| mesatee_core::Error::from(mesatee_core::ErrorKind::IoError))?;
-        let sha256 = file_util::cal_hash(&data)?;
+        let digest =
+            file_util::DigestAlgorithm::from_tag(digest_algorithm)?.cal_digest(&data, &[])?;
+        self.upload_file(
+            data,
+            file_name,
+            collaborator_list,
+            digest_algorithm,
+            &digest,
+            &[],
+        )
+    }
+
+    /// Uploads a file whose integrity is checked against its AES-128 CMAC
+    /// keyed with `mac_key`. The CMAC is computed by the caller, since it
+    /// cannot be computed outside enclaves by this client.
+    pub fn save_file_with_cmac(
+        &mut self,
+        file_path: &str,
+        file_name: &str,
+        cmac: &str,
+        mac_key: &[u8],
+    ) -> Result<String> {
+        let data = fs::read(&file_path)
+            .map_err(|_| mesatee_core::Error::from(mesatee_core::ErrorKind::IoError))?;
+        let digest_algorithm = file_util::DigestAlgorithm::CmacAes128.tag();
+        self.upload_file(data, file_name, &[], digest_algorithm, cmac, mac_key)
+    }
+
+    fn upload_file(
+        &mut self,
+        data: Vec<u8>,
+        file_name: &str,
+        collaborator_list: &[&str],
+        digest_algorithm: &str,
+        digest: &str,
+        mac_key: &[u8],
+    ) -> Result<String> {
         let file_size = data.len() as u32;
-        let resp = self.request_create_file(file_name, &sha256, file_size)?;
+        let resp = self.request_create_file(
+            file_name,
+            digest_algorithm,
+            digest,
+            mac_key,
+            file_size,
+            collaborator_list,
+        )?;
         let file_id = resp.file_id;
         let access_path = file_util::get_local_access_path(&resp.access_path);
         let key_config = resp.key_config;
@@ -102,23 +182,234 @@ impl TDFSClient {
         Ok(file_id)
     }
 
+    pub fn request_begin_upload(
+        &mut self,
+        file_name: &str,
+        file_size: u64,
+    ) -> Result<BeginUploadResponse> {
+        self.request_begin_upload_with_digest(
+            file_name,
+            file_size,
+            file_util::DEFAULT_DIGEST_ALGORITHM,
+        )
+    }
+
+    pub fn request_begin_upload_with_digest(
+        &mut self,
+        file_name: &str,
+        file_size: u64,
+        digest_algorithm: &str,
+    ) -> Result<BeginUploadResponse> {
+        let req = DFSRequest::new_begin_upload_with_digest(
+            file_name,
+            file_size,
+            digest_algorithm,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            DFSResponse::BeginUpload(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_upload_chunk(
+        &mut self,
+        file_id: &str,
+        offset: u64,
+        data: &[u8],
+    ) -> Result<UploadChunkResponse> {
+        let req =
+            DFSRequest::new_upload_chunk(file_id, offset, data, &self.user_id, &self.user_token);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            DFSResponse::UploadChunk(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_commit_upload(
+        &mut self,
+        file_id: &str,
+        sha256: &str,
+    ) -> Result<CommitUploadResponse> {
+        let req = DFSRequest::new_commit_upload(file_id, sha256, &self.user_id, &self.user_token);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            DFSResponse::CommitUpload(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    /// Uploads a file in chunks of `chunk_size` bytes without reading it into
+    /// memory as a whole. Use this instead of `save_file` for large files.
+    pub fn save_file_chunked(
+        &mut self,
+        file_path: &str,
+        file_name: &str,
+        chunk_size: usize,
+    ) -> Result<String> {
+        if chunk_size == 0 {
+            return Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::InvalidInputError,
+            ));
+        }
+        let mut f = fs::File::open(&file_path)?;
+        let file_size = f.metadata()?.len();
+        let file_id = self.request_begin_upload(file_name, file_size)?.file_id;
+
+        let mut ctx = digest::Context::new(&digest::SHA256);
+        let mut buffer = vec![0; chunk_size];
+        let mut offset = 0u64;
+        loop {
+            let len = f.read(&mut buffer)?;
+            if len == 0 {
+                break;
+            }
+            ctx.update(&buffer[..len]);
+            offset = self
+                .request_upload_chunk(&file_id, offset, &buffer[..len])?
+                .received;
+        }
+        let sha256 = file_util::digest_to_hex(&ctx.finish())?;
+        let resp = self.request_commit_upload(&file_id, &sha256)?;
+
+        Ok(resp.file_id)
+    }
+
     pub fn read_file(&mut self, file_id: &str) -> Result<Vec<u8>> {
         let resp = self.request_get_file(file_id)?;
         let file_info = resp.file_info;
-        let key_config = &file_info.key_config;
+        let key_config = file_info.key_config.as_ref().ok_or_else(|| {
+            mesatee_core::Error::new(
+                mesatee_core::ErrorKind::InvalidInputError,
+                "file is encrypted with a key of its owner, use read_file_with_key",
+            )
+        })?;
+        Self::read_content(
+            &file_info,
+            &key_config.key,
+            &key_config.nonce,
+            &key_config.ad,
+        )
+    }
+
+    /// Reads a file whose key is wrapped under the key-encryption key `kek`,
+    /// such as an output file of a task created with an output key.
+    pub fn read_file_with_key(&mut self, file_id: &str, kek: &[u8]) -> Result<Vec<u8>> {
+        let resp = self.request_get_file(file_id)?;
+        let file_info = resp.file_info;
+        let wrapped_key = file_info.wrapped_key.as_ref().ok_or_else(|| {
+            mesatee_core::Error::new(
+                mesatee_core::ErrorKind::InvalidInputError,
+                "file is not encrypted with a key of its owner",
+            )
+        })?;
+        let (key, nonce, ad) = file_util::unwrap_file_key(kek, &wrapped_key.wrapped_key)?;
+        Self::read_content(&file_info, &key, &nonce, &ad)
+    }
+
+    fn read_content(file_info: &FileInfo, key: &[u8], nonce: &[u8], ad: &[u8]) -> Result<Vec<u8>> {
         let access_path = file_util::get_local_access_path(&file_info.access_path);
         let ciphertxt = fs::read(&access_path)
             .map_err(|_| mesatee_core::Error::from(mesatee_core::ErrorKind::IoError))?;
 
-        let plaintxt = file_util::decrypt_data(
-            ciphertxt,
-            &key_config.key,
-            &key_config.nonce,
-            &key_config.ad,
-        )?;
+        let decrypt = if file_info.chunked {
+            file_util::decrypt_chunked_data
+        } else {
+            file_util::decrypt_data
+        };
+        let context = file_info.encryption_context.as_ref();
+        let aad = encryption_context::file_aad(ad, context);
+        let plaintxt = decrypt(ciphertxt, key, nonce, &aad)
+            .map_err(|err| encryption_context::decryption_error(err, context))?;
         Ok(plaintxt)
     }
 
+    pub fn request_get_output_chunk(
+        &mut self,
+        file_id: &str,
+        offset: u64,
+        len: u64,
+    ) -> Result<GetOutputChunkResponse> {
+        let req =
+            DFSRequest::new_get_output_chunk(file_id, offset, len, &self.user_id, &self.user_token);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            DFSResponse::GetOutputChunk(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    /// Downloads a file to `file_path` in chunks of up to `chunk_size` bytes,
+    /// decrypted by TDFS, without holding it in memory as a whole. Use this
+    /// instead of `read_file` for large files such as task outputs. Fails if
+    /// the digest of the downloaded file does not match, in which case the
+    /// partially written file is left to the caller.
+    pub fn download_file_chunked(
+        &mut self,
+        file_id: &str,
+        file_path: &str,
+        chunk_size: u64,
+    ) -> Result<()> {
+        if chunk_size == 0 {
+            return Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::InvalidInputError,
+            ));
+        }
+        let mut resp = self.request_get_output_chunk(file_id, 0, chunk_size)?;
+        let algorithm = file_util::DigestAlgorithm::from_tag(&resp.digest_algorithm)?;
+        let hash_algorithm = algorithm.hash_algorithm().ok_or_else(|| {
+            mesatee_core::Error::new(
+                mesatee_core::ErrorKind::InvalidInputError,
+                format!("{} digests cannot be checked by clients", algorithm.tag()),
+            )
+        })?;
+        let file_size = resp.file_size;
+        let expected = resp.sha256.clone();
+
+        let mut f = fs::File::create(&file_path)?;
+        let mut ctx = digest::Context::new(hash_algorithm);
+        let mut offset = 0u64;
+        loop {
+            ctx.update(&resp.data);
+            f.write_all(&resp.data)?;
+            offset += resp.data.len() as u64;
+            if offset >= file_size {
+                break;
+            }
+            if resp.data.is_empty() {
+                return Err(mesatee_core::Error::from(
+                    mesatee_core::ErrorKind::RPCResponseError,
+                ));
+            }
+            resp = self.request_get_output_chunk(file_id, offset, chunk_size)?;
+        }
+
+        let actual = file_util::digest_to_hex(&ctx.finish())?;
+        if actual != expected {
+            return Err(mesatee_core::Error::new(
+                mesatee_core::ErrorKind::CryptoError,
+                format!(
+                    "{} of the downloaded file is {}, expected {}",
+                    algorithm.tag(),
+                    actual,
+                    expected
+                ),
+            ));
+        }
+        Ok(())
+    }
+
     pub fn request_list_file(&mut self) -> Result<ListFileResponse> {
         let req = DFSRequest::new_list_file(&self.user_id, &self.user_token);
         let resp = self.channel.invoke(req)?;
@@ -140,4 +431,15 @@ impl TDFSClient {
             )),
         }
     }
+
+    pub fn request_health_check(&mut self) -> Result<HealthCheckResponse> {
+        let req = DFSRequest::new_health_check();
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            DFSResponse::HealthCheck(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
 }
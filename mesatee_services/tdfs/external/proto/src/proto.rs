@@ -24,6 +24,8 @@ use crate::key::AeadConfig;
 #[cfg(feature = "mesalock_sgx")]
 use kms_proto::proto::AeadConfig;
 
+pub use mesatee_core::encryption_context::EncryptionContext;
+use mesatee_core::health::ServiceHealth;
 use serde_derive::*;
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -33,6 +35,11 @@ pub enum DFSRequest {
     Get(GetFileRequest),
     List(ListFileRequest),
     Delete(DeleteFileRequest),
+    BeginUpload(BeginUploadRequest),
+    UploadChunk(UploadChunkRequest),
+    CommitUpload(CommitUploadRequest),
+    GetOutputChunk(GetOutputChunkRequest),
+    HealthCheck(HealthCheckRequest),
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -42,15 +49,35 @@ pub enum DFSResponse {
     Get(GetFileResponse),
     List(ListFileResponse),
     Delete(DeleteFileResponse),
+    BeginUpload(BeginUploadResponse),
+    UploadChunk(UploadChunkResponse),
+    CommitUpload(CommitUploadResponse),
+    GetOutputChunk(GetOutputChunkResponse),
+    HealthCheck(HealthCheckResponse),
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct CreateFileRequest {
     pub file_name: String,
+    // Hex digest of the plaintext with `digest_algorithm`. The field is named
+    // after the default algorithm.
     pub sha256: String,
     pub file_size: u32,
+    #[serde(default)]
+    pub collaborator_list: Vec<String>,
     pub user_id: String,
     pub user_token: String,
+    // One of `sha256`, `sha384` and `cmac-aes-128`. Files declaring another
+    // algorithm are rejected.
+    #[serde(default = "default_digest_algorithm")]
+    pub digest_algorithm: String,
+    // Key of a `cmac-aes-128` digest, 16 bytes.
+    #[serde(default)]
+    pub mac_key: Vec<u8>,
+}
+
+fn default_digest_algorithm() -> String {
+    "sha256".to_string()
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -89,11 +116,126 @@ pub struct FileInfo {
     pub user_id: String,
     pub file_name: String,
     pub sha256: String,
-    pub file_size: u32,
+    pub file_size: u64,
     pub access_path: String,
     pub task_id: Option<String>,
     pub collaborator_list: Vec<String>,
-    pub key_config: AeadConfig,
+    // Not set for a file encrypted with a key of its owner, whose key is in
+    // `wrapped_key` instead.
+    pub key_config: Option<AeadConfig>,
+    // Whether the file is stored in chunks, see `UploadChunkRequest`.
+    #[serde(default)]
+    pub chunked: bool,
+    #[serde(default)]
+    pub wrapped_key: Option<WrappedKey>,
+    // Algorithm of the `sha256` digest.
+    #[serde(default = "default_digest_algorithm")]
+    pub digest_algorithm: String,
+    // Declared format of the content, e.g. of an output file of a task, which
+    // the digest does not cover.
+    #[serde(default)]
+    pub format: Option<FileFormat>,
+    // Task the content was encrypted for, bound to the ciphertext as
+    // additional authenticated data, see `mesatee_core::encryption_context`.
+    #[serde(default)]
+    pub encryption_context: Option<EncryptionContext>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct FileFormat {
+    pub content_type: String,
+    #[serde(default)]
+    pub schema: Option<String>,
+}
+
+// File key wrapped under a key-encryption key (KEK) held by the owner of the
+// file, such as an output file of a task created with an output key. The
+// wrapped key is a random 12 byte nonce followed by the file key (32 bytes),
+// nonce (12 bytes) and additional data (5 bytes), concatenated and sealed with
+// AES-256-GCM under the KEK using the additional data `wkey1`.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct WrappedKey {
+    // Id of the KEK, chosen by its holder.
+    pub key_id: String,
+    pub wrapped_key: Vec<u8>,
+}
+
+// Streaming upload for files too large to be sent in one request. The upload
+// starts with `BeginUpload` declaring the total size, continues with
+// `UploadChunk`s in order, and ends with `CommitUpload` declaring the digest
+// of the whole file. Chunks are encrypted by TDFS as they arrive, so the file
+// is never held in memory as a whole.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct BeginUploadRequest {
+    pub file_name: String,
+    pub file_size: u64,
+    pub user_id: String,
+    pub user_token: String,
+    // `sha256` or `sha384`, CMAC digests cannot be computed incrementally.
+    #[serde(default = "default_digest_algorithm")]
+    pub digest_algorithm: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct BeginUploadResponse {
+    pub file_id: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct UploadChunkRequest {
+    pub file_id: String,
+    // Must equal the number of bytes uploaded so far.
+    pub offset: u64,
+    pub data: Vec<u8>,
+    pub user_id: String,
+    pub user_token: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct UploadChunkResponse {
+    pub received: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct CommitUploadRequest {
+    pub file_id: String,
+    // Hex digest with the algorithm declared by `BeginUpload`.
+    pub sha256: String,
+    pub user_id: String,
+    pub user_token: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct CommitUploadResponse {
+    pub file_id: String,
+}
+
+// Streaming download for files too large to be received in one response,
+// such as task outputs. The file is read with `GetOutputChunk`s at increasing
+// offsets until `file_size` bytes are received, and the reassembled file is
+// checked against the digest coming with every chunk. Chunks are decrypted
+// by TDFS as they are read, so the file is never held in memory as a whole.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct GetOutputChunkRequest {
+    pub file_id: String,
+    // Position of the first byte of the chunk in the file.
+    pub offset: u64,
+    // Upper bound of the chunk size. Chunks are shorter at the end of the file
+    // and when `len` exceeds the maximum chunk size of TDFS.
+    pub len: u64,
+    pub user_id: String,
+    pub user_token: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct GetOutputChunkResponse {
+    pub file_id: String,
+    pub offset: u64,
+    pub data: Vec<u8>,
+    pub file_size: u64,
+    // Hex digest of the whole file with `digest_algorithm`.
+    pub sha256: String,
+    pub digest_algorithm: String,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -107,6 +249,15 @@ pub struct ListFileResponse {
     pub list: Vec<String>,
 }
 
+// Health checks need no user credentials, so that probes can send them.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct HealthCheckRequest {}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct HealthCheckResponse {
+    pub health: ServiceHealth,
+}
+
 impl DFSRequest {
     pub fn new_create_file(
         file_name: &str,
@@ -119,8 +270,54 @@ impl DFSRequest {
             file_name: file_name.to_owned(),
             sha256: sha256.to_owned(),
             file_size,
+            collaborator_list: Vec::new(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            digest_algorithm: default_digest_algorithm(),
+            mac_key: Vec::new(),
+        })
+    }
+
+    pub fn new_create_shared_file(
+        file_name: &str,
+        sha256: &str,
+        file_size: u32,
+        collaborator_list: &[&str],
+        user_id: &str,
+        user_token: &str,
+    ) -> DFSRequest {
+        DFSRequest::Create(CreateFileRequest {
+            file_name: file_name.to_owned(),
+            sha256: sha256.to_owned(),
+            file_size,
+            collaborator_list: collaborator_list.iter().map(|s| (*s).to_string()).collect(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            digest_algorithm: default_digest_algorithm(),
+            mac_key: Vec::new(),
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_create_file_with_digest(
+        file_name: &str,
+        digest_algorithm: &str,
+        digest: &str,
+        mac_key: &[u8],
+        file_size: u32,
+        collaborator_list: &[&str],
+        user_id: &str,
+        user_token: &str,
+    ) -> DFSRequest {
+        DFSRequest::Create(CreateFileRequest {
+            file_name: file_name.to_owned(),
+            sha256: digest.to_owned(),
+            file_size,
+            collaborator_list: collaborator_list.iter().map(|s| (*s).to_string()).collect(),
             user_id: user_id.to_owned(),
             user_token: user_token.to_owned(),
+            digest_algorithm: digest_algorithm.to_owned(),
+            mac_key: mac_key.to_vec(),
         })
     }
 
@@ -146,6 +343,87 @@ impl DFSRequest {
             user_token: user_token.to_owned(),
         })
     }
+
+    pub fn new_begin_upload(
+        file_name: &str,
+        file_size: u64,
+        user_id: &str,
+        user_token: &str,
+    ) -> DFSRequest {
+        DFSRequest::BeginUpload(BeginUploadRequest {
+            file_name: file_name.to_owned(),
+            file_size,
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            digest_algorithm: default_digest_algorithm(),
+        })
+    }
+
+    pub fn new_begin_upload_with_digest(
+        file_name: &str,
+        file_size: u64,
+        digest_algorithm: &str,
+        user_id: &str,
+        user_token: &str,
+    ) -> DFSRequest {
+        DFSRequest::BeginUpload(BeginUploadRequest {
+            file_name: file_name.to_owned(),
+            file_size,
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            digest_algorithm: digest_algorithm.to_owned(),
+        })
+    }
+
+    pub fn new_upload_chunk(
+        file_id: &str,
+        offset: u64,
+        data: &[u8],
+        user_id: &str,
+        user_token: &str,
+    ) -> DFSRequest {
+        DFSRequest::UploadChunk(UploadChunkRequest {
+            file_id: file_id.to_owned(),
+            offset,
+            data: data.to_vec(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+        })
+    }
+
+    pub fn new_commit_upload(
+        file_id: &str,
+        sha256: &str,
+        user_id: &str,
+        user_token: &str,
+    ) -> DFSRequest {
+        DFSRequest::CommitUpload(CommitUploadRequest {
+            file_id: file_id.to_owned(),
+            sha256: sha256.to_owned(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+        })
+    }
+
+    pub fn new_get_output_chunk(
+        file_id: &str,
+        offset: u64,
+        len: u64,
+        user_id: &str,
+        user_token: &str,
+    ) -> DFSRequest {
+        DFSRequest::GetOutputChunk(GetOutputChunkRequest {
+            file_id: file_id.to_owned(),
+            offset,
+            len,
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+        })
+    }
+
+    pub fn new_health_check() -> DFSRequest {
+        DFSRequest::HealthCheck(HealthCheckRequest {})
+    }
 }
 
 impl DFSResponse {
@@ -178,4 +456,42 @@ impl DFSResponse {
             file_info: file_info.clone(),
         })
     }
+
+    pub fn new_begin_upload(file_id: &str) -> DFSResponse {
+        DFSResponse::BeginUpload(BeginUploadResponse {
+            file_id: file_id.to_owned(),
+        })
+    }
+
+    pub fn new_upload_chunk(received: u64) -> DFSResponse {
+        DFSResponse::UploadChunk(UploadChunkResponse { received })
+    }
+
+    pub fn new_commit_upload(file_id: &str) -> DFSResponse {
+        DFSResponse::CommitUpload(CommitUploadResponse {
+            file_id: file_id.to_owned(),
+        })
+    }
+
+    pub fn new_get_output_chunk(
+        file_id: &str,
+        offset: u64,
+        data: Vec<u8>,
+        file_size: u64,
+        sha256: &str,
+        digest_algorithm: &str,
+    ) -> DFSResponse {
+        DFSResponse::GetOutputChunk(GetOutputChunkResponse {
+            file_id: file_id.to_owned(),
+            offset,
+            data,
+            file_size,
+            sha256: sha256.to_owned(),
+            digest_algorithm: digest_algorithm.to_owned(),
+        })
+    }
+
+    pub fn new_health_check(health: ServiceHealth) -> DFSResponse {
+        DFSResponse::HealthCheck(HealthCheckResponse { health })
+    }
 }
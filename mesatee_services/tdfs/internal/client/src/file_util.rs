@@ -22,8 +22,10 @@ use std::prelude::v1::*;
 use mesatee_core::{Error, ErrorKind, Result};
 use ring::aead::{self, Aad, BoundKey, Nonce, UnboundKey};
 use ring::digest;
+use ring::rand::{SecureRandom, SystemRandom};
 use std::env;
 use std::fmt::Write;
+use std::io::{self, Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
 
 struct OneNonceSequence(Option<aead::Nonce>);
@@ -60,6 +62,8 @@ pub fn decrypt_data(
     Ok(decrypted_buffer.to_vec())
 }
 
+// `aes_ad` is the additional data of the key, possibly followed by an
+// encryption context, see `mesatee_core::encryption_context`.
 pub fn encrypt_data(
     mut data: Vec<u8>,
     aes_key: &[u8],
@@ -68,7 +72,7 @@ pub fn encrypt_data(
 ) -> Result<Vec<u8>> {
     let aead_alg = &aead::AES_256_GCM;
 
-    if (aes_key.len() != 32) || (aes_nonce.len() != 12) || (aes_ad.len() != 5) {
+    if (aes_key.len() != 32) || (aes_nonce.len() != 12) || (aes_ad.len() < 5) {
         return Err(Error::from(ErrorKind::CryptoError));
     }
 
@@ -86,17 +90,318 @@ pub fn encrypt_data(
     Ok(data)
 }
 
+// Files uploaded in chunks are stored as a sequence of records, each of which
+// is a little-endian u32 length followed by one chunk sealed with the file key.
+// The nonce of the n-th chunk is the file nonce with n XORed into its last
+// eight bytes, so records cannot be reordered without failing to open.
+const CHUNK_LEN_SIZE: usize = 4;
+
+fn chunk_nonce(aes_nonce: &[u8], index: u64) -> Result<Nonce> {
+    if aes_nonce.len() != 12 {
+        return Err(Error::from(ErrorKind::CryptoError));
+    }
+    let mut nonce = [0u8; 12];
+    nonce.copy_from_slice(aes_nonce);
+    for (n, i) in nonce[4..].iter_mut().zip(index.to_be_bytes().iter()) {
+        *n ^= i;
+    }
+    Ok(Nonce::assume_unique_for_key(nonce))
+}
+
+pub fn encrypt_chunk(
+    data: Vec<u8>,
+    index: u64,
+    aes_key: &[u8],
+    aes_nonce: &[u8],
+    aes_ad: &[u8],
+) -> Result<Vec<u8>> {
+    let nonce = chunk_nonce(aes_nonce, index)?;
+    let sealed = encrypt_data(data, aes_key, nonce.as_ref(), aes_ad)?;
+    if sealed.len() > u32::max_value() as usize {
+        return Err(Error::from(ErrorKind::CryptoError));
+    }
+
+    let mut record = Vec::with_capacity(CHUNK_LEN_SIZE + sealed.len());
+    record.extend_from_slice(&(sealed.len() as u32).to_le_bytes());
+    record.extend_from_slice(&sealed);
+    Ok(record)
+}
+
+pub fn decrypt_chunked_data(
+    data: Vec<u8>,
+    aes_key: &[u8],
+    aes_nonce: &[u8],
+    aes_ad: &[u8],
+) -> Result<Vec<u8>> {
+    let mut plaintxt = Vec::with_capacity(data.len());
+    let mut rest = &data[..];
+    let mut index = 0u64;
+    while !rest.is_empty() {
+        if rest.len() < CHUNK_LEN_SIZE {
+            return Err(Error::from(ErrorKind::CryptoError));
+        }
+        let mut len = [0u8; CHUNK_LEN_SIZE];
+        len.copy_from_slice(&rest[..CHUNK_LEN_SIZE]);
+        let len = u32::from_le_bytes(len) as usize;
+        rest = &rest[CHUNK_LEN_SIZE..];
+        if rest.len() < len {
+            return Err(Error::from(ErrorKind::CryptoError));
+        }
+
+        let nonce = chunk_nonce(aes_nonce, index)?;
+        let chunk = decrypt_data(rest[..len].to_vec(), aes_key, nonce.as_ref(), aes_ad)?;
+        plaintxt.extend_from_slice(&chunk);
+        rest = &rest[len..];
+        index += 1;
+    }
+    Ok(plaintxt)
+}
+
+// Length of the AES-GCM tag sealed with each chunk.
+const CHUNK_TAG_LEN: usize = 16;
+
+// Plaintext bytes `offset..offset + len` of a file stored in chunks, read from
+// `reader`. Only the records overlapping the range are read and opened, the
+// others are skipped. The range ends early at the end of the file.
+pub fn read_chunked_range<R: Read + Seek>(
+    reader: &mut R,
+    offset: u64,
+    len: usize,
+    aes_key: &[u8],
+    aes_nonce: &[u8],
+    aes_ad: &[u8],
+) -> Result<Vec<u8>> {
+    let mut plaintxt = Vec::with_capacity(len);
+    let mut chunk_start = 0u64;
+    let mut index = 0u64;
+    while plaintxt.len() < len {
+        let mut sealed_len = [0u8; CHUNK_LEN_SIZE];
+        match reader.read_exact(&mut sealed_len) {
+            Ok(()) => (),
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        }
+        let sealed_len = u32::from_le_bytes(sealed_len) as usize;
+        if sealed_len < CHUNK_TAG_LEN {
+            return Err(Error::from(ErrorKind::CryptoError));
+        }
+        let chunk_len = (sealed_len - CHUNK_TAG_LEN) as u64;
+
+        if chunk_start + chunk_len <= offset {
+            reader.seek(SeekFrom::Current(sealed_len as i64))?;
+        } else {
+            let mut sealed = vec![0u8; sealed_len];
+            reader
+                .read_exact(&mut sealed)
+                .map_err(|_| Error::from(ErrorKind::CryptoError))?;
+            let nonce = chunk_nonce(aes_nonce, index)?;
+            let chunk = decrypt_data(sealed, aes_key, nonce.as_ref(), aes_ad)?;
+            let start = offset.saturating_sub(chunk_start) as usize;
+            let end = chunk.len().min(start + len - plaintxt.len());
+            plaintxt.extend_from_slice(&chunk[start..end]);
+        }
+        chunk_start += chunk_len;
+        index += 1;
+    }
+    Ok(plaintxt)
+}
+
+// Length of the file key, nonce and additional data of a file.
+const FILE_KEY_LEN: usize = 32;
+const FILE_NONCE_LEN: usize = 12;
+const FILE_AD_LEN: usize = 5;
+
+// Files can be encrypted with a key wrapped under a key-encryption key (KEK)
+// of their owner instead of a key kept by KMS. The wrapped key is a random
+// nonce followed by the file key, nonce and additional data, concatenated and
+// sealed with AES-256-GCM under the KEK, using `WRAP_AD` as additional data.
+const WRAP_AD: &[u8] = b"wkey1";
+const WRAP_TAG_LEN: usize = 16;
+pub const WRAPPED_KEY_LEN: usize =
+    FILE_NONCE_LEN + FILE_KEY_LEN + FILE_NONCE_LEN + FILE_AD_LEN + WRAP_TAG_LEN;
+
+fn random_bytes(len: usize) -> Result<Vec<u8>> {
+    let mut bytes = vec![0u8; len];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| Error::from(ErrorKind::CryptoError))?;
+    Ok(bytes)
+}
+
+// Returns a random file key, nonce and additional data.
+pub fn generate_file_key() -> Result<(Vec<u8>, Vec<u8>, Vec<u8>)> {
+    Ok((
+        random_bytes(FILE_KEY_LEN)?,
+        random_bytes(FILE_NONCE_LEN)?,
+        random_bytes(FILE_AD_LEN)?,
+    ))
+}
+
+pub fn wrap_file_key(
+    kek: &[u8],
+    aes_key: &[u8],
+    aes_nonce: &[u8],
+    aes_ad: &[u8],
+) -> Result<Vec<u8>> {
+    if (aes_key.len() != FILE_KEY_LEN)
+        || (aes_nonce.len() != FILE_NONCE_LEN)
+        || (aes_ad.len() != FILE_AD_LEN)
+    {
+        return Err(Error::from(ErrorKind::CryptoError));
+    }
+    let mut key_material = Vec::with_capacity(FILE_KEY_LEN + FILE_NONCE_LEN + FILE_AD_LEN);
+    key_material.extend_from_slice(aes_key);
+    key_material.extend_from_slice(aes_nonce);
+    key_material.extend_from_slice(aes_ad);
+
+    let wrap_nonce = random_bytes(FILE_NONCE_LEN)?;
+    let sealed = encrypt_data(key_material, kek, &wrap_nonce, WRAP_AD)?;
+    let mut wrapped_key = wrap_nonce;
+    wrapped_key.extend_from_slice(&sealed);
+    Ok(wrapped_key)
+}
+
+// Fails with `CryptoError` if `wrapped_key` was not wrapped under `kek`.
+pub fn unwrap_file_key(kek: &[u8], wrapped_key: &[u8]) -> Result<(Vec<u8>, Vec<u8>, Vec<u8>)> {
+    if (kek.len() != FILE_KEY_LEN) || (wrapped_key.len() != WRAPPED_KEY_LEN) {
+        return Err(Error::from(ErrorKind::CryptoError));
+    }
+    let (wrap_nonce, sealed) = wrapped_key.split_at(FILE_NONCE_LEN);
+    let key_material = decrypt_data(sealed.to_vec(), kek, wrap_nonce, WRAP_AD)?;
+    let (aes_key, rest) = key_material.split_at(FILE_KEY_LEN);
+    let (aes_nonce, aes_ad) = rest.split_at(FILE_NONCE_LEN);
+    Ok((aes_key.to_vec(), aes_nonce.to_vec(), aes_ad.to_vec()))
+}
+
+pub fn digest_to_hex(digest: &digest::Digest) -> Result<String> {
+    bytes_to_hex(digest.as_ref())
+}
+
+fn bytes_to_hex(bytes: &[u8]) -> Result<String> {
+    let mut hex = String::new();
+    for &byte in bytes {
+        write!(&mut hex, "{:02x}", byte).map_err(|_| Error::from(ErrorKind::Unknown))?;
+    }
+    Ok(hex)
+}
+
 pub fn cal_hash(data: &[u8]) -> Result<String> {
     let digest_alg = &digest::SHA256;
     let mut ctx = digest::Context::new(digest_alg);
     ctx.update(data);
     let digest_result = ctx.finish();
-    let digest_bytes: &[u8] = digest_result.as_ref();
-    let mut digest_hex = String::new();
-    for &byte in digest_bytes {
-        write!(&mut digest_hex, "{:02x}", byte).map_err(|_| Error::from(ErrorKind::Unknown))?;
+    digest_to_hex(&digest_result)
+}
+
+// Algorithm of the integrity digest recorded for the plaintext of a file,
+// declared by its tag when the file is created. A CMAC is keyed with a 16 byte
+// key supplied by the uploader along with the digest.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DigestAlgorithm {
+    Sha256,
+    Sha384,
+    CmacAes128,
+}
+
+pub const DEFAULT_DIGEST_ALGORITHM: &str = "sha256";
+const CMAC_KEY_LEN: usize = 16;
+
+impl DigestAlgorithm {
+    pub fn from_tag(tag: &str) -> Result<DigestAlgorithm> {
+        match tag {
+            "sha256" => Ok(DigestAlgorithm::Sha256),
+            "sha384" => Ok(DigestAlgorithm::Sha384),
+            "cmac-aes-128" => Ok(DigestAlgorithm::CmacAes128),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInputError,
+                format!("unknown digest algorithm {}", tag),
+            )),
+        }
     }
-    Ok(digest_hex)
+
+    pub fn tag(self) -> &'static str {
+        match self {
+            DigestAlgorithm::Sha256 => "sha256",
+            DigestAlgorithm::Sha384 => "sha384",
+            DigestAlgorithm::CmacAes128 => "cmac-aes-128",
+        }
+    }
+
+    pub fn is_keyed(self) -> bool {
+        self == DigestAlgorithm::CmacAes128
+    }
+
+    // Hash to compute the digest incrementally, `None` for a CMAC.
+    pub fn hash_algorithm(self) -> Option<&'static digest::Algorithm> {
+        match self {
+            DigestAlgorithm::Sha256 => Some(&digest::SHA256),
+            DigestAlgorithm::Sha384 => Some(&digest::SHA384),
+            DigestAlgorithm::CmacAes128 => None,
+        }
+    }
+
+    fn digest_len(self) -> usize {
+        match self {
+            DigestAlgorithm::Sha256 => 32,
+            DigestAlgorithm::Sha384 => 48,
+            DigestAlgorithm::CmacAes128 => 16,
+        }
+    }
+
+    // Checks the form of a declared hex digest and of its key, and returns
+    // the digest in lower case.
+    pub fn check_digest(self, digest: &str, mac_key: &[u8]) -> Result<String> {
+        if digest.len() != 2 * self.digest_len() || !digest.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(Error::new(
+                ErrorKind::InvalidInputError,
+                format!(
+                    "{} digest must be {} hex digits",
+                    self.tag(),
+                    2 * self.digest_len()
+                ),
+            ));
+        }
+        let expected_key_len = if self.is_keyed() { CMAC_KEY_LEN } else { 0 };
+        if mac_key.len() != expected_key_len {
+            return Err(Error::new(
+                ErrorKind::InvalidInputError,
+                format!(
+                    "{} digest must have a {} byte key",
+                    self.tag(),
+                    expected_key_len
+                ),
+            ));
+        }
+        Ok(digest.to_ascii_lowercase())
+    }
+
+    pub fn cal_digest(self, data: &[u8], mac_key: &[u8]) -> Result<String> {
+        match self.hash_algorithm() {
+            Some(algorithm) => digest_to_hex(&digest::digest(algorithm, data)),
+            None => bytes_to_hex(&cal_cmac(data, mac_key)?),
+        }
+    }
+}
+
+#[cfg(feature = "mesalock_sgx")]
+fn cal_cmac(data: &[u8], mac_key: &[u8]) -> Result<Vec<u8>> {
+    if mac_key.len() != CMAC_KEY_LEN {
+        return Err(Error::from(ErrorKind::CryptoError));
+    }
+    let mut key = [0u8; CMAC_KEY_LEN];
+    key.copy_from_slice(mac_key);
+    let tag = sgx_tcrypto::rsgx_rijndael128_cmac_slice(&key, data)
+        .map_err(|_| Error::from(ErrorKind::CryptoError))?;
+    Ok(tag.to_vec())
+}
+
+// ring has no CMAC, so uploaders compute the CMAC of a file themselves.
+#[cfg(not(feature = "mesalock_sgx"))]
+fn cal_cmac(_data: &[u8], _mac_key: &[u8]) -> Result<Vec<u8>> {
+    Err(Error::new(
+        ErrorKind::CryptoError,
+        "CMAC digests are only computed in enclaves",
+    ))
 }
 
 pub fn get_local_access_path(relative_path: &str) -> PathBuf {
@@ -23,4 +23,8 @@ extern crate sgx_tstd as std;
 
 mod file_util;
 mod tdfs_client;
-pub use tdfs_client::TDFSClient;
+pub use file_util::{decrypt_data, encrypt_data, DigestAlgorithm};
+pub use tdfs_client::{TDFSChannelPool, TDFSClient};
+pub use tdfs_internal_proto::{
+    AuditEntry, EncryptionContext, Entry, FileFormat, ListAuditEntriesRequest, UserFile, WriteOp,
+};
@@ -22,14 +22,36 @@ use core::convert::TryInto;
 use kms_proto;
 use kms_proto::KMSClient;
 use mesatee_core::config::{self, OutboundDesc, TargetDesc};
-use mesatee_core::rpc::channel::SgxTrustedChannel;
+use mesatee_core::encryption_context;
+use mesatee_core::rpc::channel::{SgxTrustedChannel, SgxTrustedChannelPool};
 use mesatee_core::{self, Result};
 use std::io::{Read, Write};
+use std::sync::Arc;
 use std::untrusted::fs;
-use tdfs_internal_proto::{CreateFileResponse, DFSRequest, DFSResponse, FileInfo, GetFileResponse};
+use tdfs_internal_proto::{
+    AuditEntry, CreateFileResponse, DFSRequest, DFSResponse, EncryptionContext, Entry, FileFormat,
+    FileInfo, GetFileResponse, ListAuditEntriesRequest, SignedEnclaveInfo, UserFile, WrappedKey,
+    WriteOp, MAX_MULTI_GET_KEYS,
+};
+
+pub type TDFSChannelPool = SgxTrustedChannelPool<DFSRequest, DFSResponse>;
+
+enum TDFSChannel {
+    Dedicated(SgxTrustedChannel<DFSRequest, DFSResponse>),
+    Pooled(Arc<TDFSChannelPool>),
+}
+
+impl TDFSChannel {
+    fn invoke(&mut self, req: DFSRequest) -> Result<DFSResponse> {
+        match self {
+            TDFSChannel::Dedicated(channel) => channel.invoke(req),
+            TDFSChannel::Pooled(pool) => pool.invoke(req),
+        }
+    }
+}
 
 pub struct TDFSClient {
-    channel: SgxTrustedChannel<DFSRequest, DFSResponse>,
+    channel: TDFSChannel,
 }
 
 impl TDFSClient {
@@ -42,26 +64,20 @@ impl TDFSClient {
             }
         };
 
-        Ok(TDFSClient { channel })
+        Ok(TDFSClient {
+            channel: TDFSChannel::Dedicated(channel),
+        })
     }
 
-    fn request_create_file(
-        &mut self,
-        sha256: &str,
-        file_size: u32,
-        user_id: &str,
-        task_id: &str,
-        collaborator_list: &[&str],
-        allow_policy: u32,
-    ) -> Result<CreateFileResponse> {
-        let req = DFSRequest::new_create_file(
-            sha256,
-            file_size,
-            user_id,
-            task_id,
-            collaborator_list,
-            allow_policy,
-        );
+    /// Sends requests over connections borrowed from a shared pool instead
+    /// of a connection owned by this client.
+    pub fn with_pool(pool: Arc<TDFSChannelPool>) -> Self {
+        TDFSClient {
+            channel: TDFSChannel::Pooled(pool),
+        }
+    }
+
+    fn request_create_file(&mut self, req: DFSRequest) -> Result<CreateFileResponse> {
         let resp = self.channel.invoke(req)?;
         match resp {
             DFSResponse::Create(resp) => Ok(resp),
@@ -82,6 +98,176 @@ impl TDFSClient {
         }
     }
 
+    /// Entry `key` with the version of its latest write, if any.
+    pub fn get_entry(&mut self, key: &str) -> Result<Option<Entry>> {
+        let req = DFSRequest::new_get_entry(key);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            DFSResponse::GetEntry(resp) => Ok(resp.value.map(|value| Entry {
+                key: key.to_owned(),
+                value,
+                version: resp.version,
+            })),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    /// Stores `value` under `key`, replacing the previous value.
+    pub fn put_entry(&mut self, key: &str, value: &str) -> Result<()> {
+        self.request_put_entry(key, value, None)
+    }
+
+    /// Stores `value` under `key` for `ttl_secs` seconds, after which the
+    /// entry is absent.
+    pub fn put_entry_with_ttl(&mut self, key: &str, value: &str, ttl_secs: u64) -> Result<()> {
+        self.request_put_entry(key, value, Some(ttl_secs))
+    }
+
+    fn request_put_entry(&mut self, key: &str, value: &str, ttl_secs: Option<u64>) -> Result<()> {
+        let req = DFSRequest::new_put_entry(key, value, ttl_secs);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            DFSResponse::PutEntry(_) => Ok(()),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    /// Stores `value` under `key` if the entry still has `version`, or is
+    /// absent if `version` is 0. Fails with `StorageConflict` otherwise.
+    /// Returns the new version of the entry.
+    pub fn compare_and_put_entry(&mut self, key: &str, value: &str, version: u64) -> Result<u64> {
+        let req = DFSRequest::new_compare_and_put_entry(key, value, version);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            DFSResponse::CompareAndPutEntry(resp) => Ok(resp.version),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn delete_entry(&mut self, key: &str) -> Result<()> {
+        let req = DFSRequest::new_delete_entry(key);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            DFSResponse::DeleteEntry(_) => Ok(()),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    /// Lists the entries whose key starts with `prefix`, sorted by key.
+    pub fn scan_entries(&mut self, prefix: &str) -> Result<Vec<Entry>> {
+        let mut entries = Vec::new();
+        let mut start_key: Option<String> = None;
+        loop {
+            let (page, next_key) =
+                self.scan_entries_page(prefix, start_key.as_ref().map(|key| key.as_str()), None)?;
+            entries.extend(page);
+            match next_key {
+                Some(next_key) => start_key = Some(next_key),
+                None => return Ok(entries),
+            }
+        }
+    }
+
+    /// Lists at most `limit` entries whose key starts with `prefix`, from
+    /// `start_key` on, sorted by key. Returns the start key of the next page
+    /// if there are more entries. TDFS may return fewer entries than `limit`.
+    pub fn scan_entries_page(
+        &mut self,
+        prefix: &str,
+        start_key: Option<&str>,
+        limit: Option<usize>,
+    ) -> Result<(Vec<Entry>, Option<String>)> {
+        let req = DFSRequest::new_scan_entries(prefix, start_key, limit);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            DFSResponse::ScanEntries(resp) => Ok((resp.entries, resp.next_key)),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    /// Applies `ops` atomically: if one fails, e.g. a check with a storage
+    /// conflict, none of them is applied.
+    pub fn write_entries(&mut self, ops: &[WriteOp]) -> Result<()> {
+        let req = DFSRequest::new_write_entries(ops);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            DFSResponse::WriteEntries(_) => Ok(()),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    /// Lists the files accessible to `user_id`, in no particular order.
+    pub fn list_user_files(&mut self, user_id: &str) -> Result<Vec<UserFile>> {
+        let req = DFSRequest::new_list_user_files(user_id);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            DFSResponse::ListUserFiles(resp) => Ok(resp.files),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    /// Deletes file `file_id` with its key, whoever owns it.
+    pub fn delete_file(&mut self, file_id: &str) -> Result<()> {
+        let req = DFSRequest::new_delete_file(file_id);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            DFSResponse::DeleteFile(_) => Ok(()),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn append_audit_entry(&mut self, user_id: &str, event: &str) -> Result<AuditEntry> {
+        let req = DFSRequest::new_append_audit_entry(user_id, event);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            DFSResponse::AppendAuditEntry(resp) => Ok(resp.entry),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn list_audit_entries(
+        &mut self,
+        query: &ListAuditEntriesRequest,
+    ) -> Result<Vec<AuditEntry>> {
+        let req = DFSRequest::ListAuditEntries(query.clone());
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            DFSResponse::ListAuditEntries(resp) => Ok(resp.entries),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn reload_enclave_info(&mut self, fragments: &[SignedEnclaveInfo]) -> Result<Vec<String>> {
+        let req = DFSRequest::new_reload_enclave_info(fragments);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            DFSResponse::ReloadEnclaveInfo(resp) => Ok(resp.services),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
     pub fn save_file(
         &mut self,
         data: &[u8],
@@ -89,31 +275,108 @@ impl TDFSClient {
         task_id: &str,
         collaborator_list: &[&str],
         allow_policy: u32,
+    ) -> Result<String> {
+        self.save_file_with_format(
+            data,
+            user_id,
+            task_id,
+            collaborator_list,
+            allow_policy,
+            None,
+            None,
+        )
+    }
+
+    /// Same as `save_file`, recording the declared `format` of the content
+    /// with the file. With a `context`, which must be of task `task_id`, the
+    /// content is encrypted for the task and function of the context.
+    #[allow(clippy::too_many_arguments)]
+    pub fn save_file_with_format(
+        &mut self,
+        data: &[u8],
+        user_id: &str,
+        task_id: &str,
+        collaborator_list: &[&str],
+        allow_policy: u32,
+        format: Option<&FileFormat>,
+        context: Option<&EncryptionContext>,
     ) -> Result<String> {
         let data = data.to_vec();
         let sha256 = file_util::cal_hash(&data)?;
         let file_size = data.len() as u32;
-        let resp = self.request_create_file(
+        let req = DFSRequest::new_create_file(
             &sha256,
             file_size,
             user_id,
             task_id,
             collaborator_list,
             allow_policy,
+        );
+        let req = req.with_format(format).with_encryption_context(context);
+        let resp = self.request_create_file(req)?;
+        let key_config = resp
+            .key_config
+            .ok_or_else(|| mesatee_core::Error::from(mesatee_core::ErrorKind::MissingValue))?;
+        Self::write_content(
+            data,
+            &resp.access_path,
+            &key_config.key,
+            &key_config.nonce,
+            &encryption_context::file_aad(&key_config.ad, context),
         )?;
-        let file_id = resp.file_id;
-        let access_path = file_util::get_local_access_path(&resp.access_path);
-        let key_config = resp.key_config;
-        let encrypted_data =
-            file_util::encrypt_data(data, &key_config.key, &key_config.nonce, &key_config.ad)?;
+        Ok(resp.file_id)
+    }
+
+    /// Saves a file of `user_id` encrypted with a random key, which is only
+    /// stored wrapped under the key-encryption key `kek` of the user. Neither
+    /// KMS nor TDFS can decrypt the file. The declared `format` of the content
+    /// and the encryption `context` are recorded with the file.
+    #[allow(clippy::too_many_arguments)]
+    pub fn save_file_with_output_key(
+        &mut self,
+        data: &[u8],
+        user_id: &str,
+        task_id: &str,
+        key_id: &str,
+        kek: &[u8],
+        format: Option<&FileFormat>,
+        context: Option<&EncryptionContext>,
+    ) -> Result<String> {
+        let data = data.to_vec();
+        let sha256 = file_util::cal_hash(&data)?;
+        let file_size = data.len() as u32;
+        let (key, nonce, ad) = file_util::generate_file_key()?;
+        let wrapped_key = WrappedKey {
+            key_id: key_id.to_owned(),
+            wrapped_key: file_util::wrap_file_key(kek, &key, &nonce, &ad)?,
+        };
+        let req =
+            DFSRequest::new_create_wrapped_file(&sha256, file_size, user_id, task_id, &wrapped_key);
+        let req = req.with_format(format).with_encryption_context(context);
+        let resp = self.request_create_file(req)?;
+        let aad = encryption_context::file_aad(&ad, context);
+        Self::write_content(data, &resp.access_path, &key, &nonce, &aad)?;
+        Ok(resp.file_id)
+    }
+
+    fn write_content(
+        data: Vec<u8>,
+        access_path: &str,
+        key: &[u8],
+        nonce: &[u8],
+        aad: &[u8],
+    ) -> Result<()> {
+        let access_path = file_util::get_local_access_path(access_path);
+        let encrypted_data = file_util::encrypt_data(data, key, nonce, aad)?;
         let mut f = fs::File::create(access_path)?;
         for chunk in encrypted_data.chunks(1024 * 1024) {
             f.write_all(chunk)?;
         }
-        Ok(file_id)
+        Ok(())
     }
 
-    fn check_permission(file_info: &FileInfo, user: &str) -> bool {
+    /// Whether `user` may read the file, without asking ACS.
+    pub fn check_permission(file_info: &FileInfo, user: &str) -> bool {
         let file_owner = &file_info.user_id;
         if (file_owner == user) || (file_info.allow_policy == 2) {
             return true;
@@ -142,6 +405,39 @@ impl TDFSClient {
             ));
         }
 
+        Self::read_content(&file_info, file_info.encryption_context.as_ref())
+    }
+
+    /// Same as `read_file`, for a file expected to be encrypted in `context`.
+    /// Fails with `DataIntegrityError` if it was encrypted in another one, or
+    /// in none.
+    pub fn read_file_in_context(
+        &mut self,
+        file_id: &str,
+        user_to_check: Option<&str>,
+        context: &EncryptionContext,
+    ) -> Result<Vec<u8>> {
+        let resp = self.request_get_file(file_id)?;
+        let file_info = resp.file_info;
+        if let Some(user_id) = user_to_check {
+            if !Self::check_permission(&file_info, user_id) {
+                return Err(mesatee_core::Error::from(
+                    mesatee_core::ErrorKind::PermissionDenied,
+                ));
+            }
+        }
+        Self::read_content(&file_info, Some(context))
+    }
+
+    // Decrypts the content of a file with the AAD of `context`.
+    fn read_content(file_info: &FileInfo, context: Option<&EncryptionContext>) -> Result<Vec<u8>> {
+        // Only the holder of the key-encryption key can read the file.
+        if file_info.wrapped_key.is_some() {
+            return Err(mesatee_core::Error::new(
+                mesatee_core::ErrorKind::PermissionDenied,
+                "file is encrypted with a key of its owner",
+            ));
+        }
         let target = config::Internal::target_kms();
         let mut client = KMSClient::new(target)?;
         let key_req = kms_proto::proto::GetKeyRequest::new(&file_info.key_id);
@@ -164,19 +460,93 @@ impl TDFSClient {
             }
         }
 
-        let plaintxt = file_util::decrypt_data(
-            ciphertxt,
-            &key_config.key,
-            &key_config.nonce,
-            &key_config.ad,
-        )?;
+        let decrypt = if file_info.chunked {
+            file_util::decrypt_chunked_data
+        } else {
+            file_util::decrypt_data
+        };
+        let aad = encryption_context::file_aad(&key_config.ad, context);
+        let plaintxt = decrypt(ciphertxt, &key_config.key, &key_config.nonce, &aad)
+            .map_err(|err| encryption_context::decryption_error(err, context))?;
         Ok(plaintxt)
     }
 
+    /// Fails with `DataIntegrityError` if the content of file `file_id` does
+    /// not match the digest recorded when the file was created, computed with
+    /// the algorithm declared for the file.
+    pub fn verify_file(&mut self, file_id: &str) -> Result<()> {
+        let resp = self.request_get_file(file_id)?;
+        let file_info = resp.file_info;
+        let digest_algorithm = file_util::DigestAlgorithm::from_tag(&file_info.digest_algorithm)?;
+        let integrity_error = || {
+            mesatee_core::Error::new(
+                mesatee_core::ErrorKind::DataIntegrityError,
+                format!(
+                    "file {} does not match its recorded {} digest",
+                    file_id,
+                    digest_algorithm.tag()
+                ),
+            )
+        };
+        // Content that was modified after encryption fails to decrypt.
+        let context = file_info.encryption_context.as_ref();
+        let plaintxt = Self::read_content(&file_info, context).map_err(|err| match err.kind() {
+            mesatee_core::ErrorKind::CryptoError => integrity_error(),
+            _ => err,
+        })?;
+        if digest_algorithm.cal_digest(&plaintxt, &file_info.mac_key)? != file_info.sha256 {
+            return Err(integrity_error());
+        }
+        Ok(())
+    }
+
     pub fn check_access_permission(&mut self, file_id: &str, user_id: &str) -> Result<bool> {
         let resp = self.request_get_file(file_id)?;
         let file_info = resp.file_info;
         let accessible = Self::check_permission(&file_info, &user_id);
         Ok(accessible)
     }
+
+    /// Whether `user_id` may access all the files `file_ids`, checked with
+    /// as few requests as possible. Fails with `NotFound` if one of the files
+    /// does not exist.
+    pub fn check_access_permissions(&mut self, file_ids: &[String], user_id: &str) -> Result<bool> {
+        Ok(self.denied_files(file_ids, user_id)?.is_empty())
+    }
+
+    /// Files of `file_ids` which `user_id` neither owns nor was shared.
+    /// Fails with `NotFound` if one of the files does not exist.
+    pub fn denied_files(&mut self, file_ids: &[String], user_id: &str) -> Result<Vec<String>> {
+        let mut denied = Vec::new();
+        let file_infos = self.get_files(file_ids)?;
+        for (file_id, file_info) in file_ids.iter().zip(file_infos.into_iter()) {
+            match file_info {
+                Some(ref file_info) if Self::check_permission(file_info, user_id) => {}
+                Some(_) => denied.push(file_id.clone()),
+                None => return Err(mesatee_core::Error::from(mesatee_core::ErrorKind::NotFound)),
+            }
+        }
+        Ok(denied)
+    }
+
+    /// Infos of the files `file_ids`, in the same order, `None` for a file
+    /// which does not exist. Fetched `MAX_MULTI_GET_KEYS` files at a time.
+    pub fn get_files(&mut self, file_ids: &[String]) -> Result<Vec<Option<FileInfo>>> {
+        let mut files = Vec::with_capacity(file_ids.len());
+        for keys in file_ids.chunks(MAX_MULTI_GET_KEYS) {
+            let req = DFSRequest::new_multi_get(keys);
+            let resp = self.channel.invoke(req)?;
+            match resp {
+                DFSResponse::MultiGet(resp) if resp.files.len() == keys.len() => {
+                    files.extend(resp.files)
+                }
+                _ => {
+                    return Err(mesatee_core::Error::from(
+                        mesatee_core::ErrorKind::RPCResponseError,
+                    ))
+                }
+            }
+        }
+        Ok(files)
+    }
 }
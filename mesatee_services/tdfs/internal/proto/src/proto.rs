@@ -18,6 +18,9 @@
 use std::prelude::v1::*;
 
 use kms_proto::proto::AeadConfig;
+pub use mesatee_core::config::SignedEnclaveInfo;
+pub use mesatee_core::db::WriteOp;
+pub use mesatee_core::encryption_context::EncryptionContext;
 use serde_derive::*;
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -25,6 +28,18 @@ use serde_derive::*;
 pub enum DFSRequest {
     Create(CreateFileRequest),
     Get(GetFileRequest),
+    MultiGet(MultiGetRequest),
+    GetEntry(GetEntryRequest),
+    PutEntry(PutEntryRequest),
+    CompareAndPutEntry(CompareAndPutEntryRequest),
+    DeleteEntry(DeleteEntryRequest),
+    ScanEntries(ScanEntriesRequest),
+    WriteEntries(WriteEntriesRequest),
+    ListUserFiles(ListUserFilesRequest),
+    DeleteFile(DeleteFileRequest),
+    AppendAuditEntry(AppendAuditEntryRequest),
+    ListAuditEntries(ListAuditEntriesRequest),
+    ReloadEnclaveInfo(ReloadEnclaveInfoRequest),
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -32,6 +47,18 @@ pub enum DFSRequest {
 pub enum DFSResponse {
     Create(CreateFileResponse),
     Get(GetFileResponse),
+    MultiGet(MultiGetResponse),
+    GetEntry(GetEntryResponse),
+    PutEntry(PutEntryResponse),
+    CompareAndPutEntry(CompareAndPutEntryResponse),
+    DeleteEntry(DeleteEntryResponse),
+    ScanEntries(ScanEntriesResponse),
+    WriteEntries(WriteEntriesResponse),
+    ListUserFiles(ListUserFilesResponse),
+    DeleteFile(DeleteFileResponse),
+    AppendAuditEntry(AppendAuditEntryResponse),
+    ListAuditEntries(ListAuditEntriesResponse),
+    ReloadEnclaveInfo(ReloadEnclaveInfoResponse),
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -39,12 +66,50 @@ pub struct FileInfo {
     pub user_id: String,
     pub file_name: String,
     pub sha256: String,
-    pub file_size: u32,
+    pub file_size: u64,
     pub access_path: String,
     pub task_id: Option<String>,
     pub collaborator_list: Vec<String>,
     pub allow_policy: u32,
     pub key_id: String,
+    #[serde(default)]
+    pub chunked: bool,
+    // Set if the file key is wrapped under a key of the owner rather than
+    // kept by KMS. `key_id` is empty then.
+    #[serde(default)]
+    pub wrapped_key: Option<WrappedKey>,
+    // Algorithm of the `sha256` digest, and the key of a CMAC digest.
+    #[serde(default = "default_digest_algorithm")]
+    pub digest_algorithm: String,
+    #[serde(default)]
+    pub mac_key: Vec<u8>,
+    #[serde(default)]
+    pub format: Option<FileFormat>,
+    // Task the content was encrypted for, bound to the ciphertext as AAD.
+    #[serde(default)]
+    pub encryption_context: Option<EncryptionContext>,
+}
+
+fn default_digest_algorithm() -> String {
+    "sha256".to_string()
+}
+
+// Declared format of the content of a file, e.g. `application/parquet`, with
+// an optional reference to its schema. Not covered by the digest of the file.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct FileFormat {
+    pub content_type: String,
+    #[serde(default)]
+    pub schema: Option<String>,
+}
+
+// File key wrapped under a key-encryption key held by the owner of the file,
+// in the format of `file_util::wrap_file_key`. TDFS cannot unwrap it.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct WrappedKey {
+    // Id of the key-encryption key, chosen by its holder.
+    pub key_id: String,
+    pub wrapped_key: Vec<u8>,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -57,6 +122,21 @@ pub struct GetFileResponse {
     pub file_info: FileInfo,
 }
 
+// Files fetched by one request at most.
+pub const MAX_MULTI_GET_KEYS: usize = 1000;
+
+// Infos of the files with IDs `keys`, in the same order, `None` for a file
+// which does not exist.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct MultiGetRequest {
+    pub keys: Vec<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct MultiGetResponse {
+    pub files: Vec<Option<FileInfo>>,
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct CreateFileRequest {
     pub sha256: String,
@@ -65,13 +145,207 @@ pub struct CreateFileRequest {
     pub task_id: String,
     pub collaborator_list: Vec<String>,
     pub allow_policy: u32,
+    // If set, the file is encrypted with the wrapped key and no key is
+    // created in KMS.
+    #[serde(default)]
+    pub wrapped_key: Option<WrappedKey>,
+    // Algorithm of the `sha256` digest.
+    #[serde(default = "default_digest_algorithm")]
+    pub digest_algorithm: String,
+    #[serde(default)]
+    pub format: Option<FileFormat>,
+    // If set, the content is encrypted for the task `task_id` of the
+    // context, see `mesatee_core::encryption_context`.
+    #[serde(default)]
+    pub encryption_context: Option<EncryptionContext>,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct CreateFileResponse {
     pub file_id: String,
     pub access_path: String,
-    pub key_config: AeadConfig,
+    // Key created in KMS, not set for a file with a wrapped key.
+    pub key_config: Option<AeadConfig>,
+}
+
+// Entries are opaque to TDFS. They are kept for TMS under keys of its
+// choosing, so that its state survives TMS restarts. Every write of an entry
+// gives it a new version, greater than all the versions given before.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct Entry {
+    pub key: String,
+    pub value: String,
+    // Version of the latest write, ignored in put requests.
+    #[serde(default)]
+    pub version: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct GetEntryRequest {
+    pub key: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct GetEntryResponse {
+    pub value: Option<String>,
+    // 0 if the entry is absent.
+    #[serde(default)]
+    pub version: u64,
+}
+
+// Replaces the value of the entry, if any. An entry put with a TTL is absent
+// once `ttl_secs` have passed, removed or not yet by the periodic sweep of
+// TDFS.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PutEntryRequest {
+    pub entry: Entry,
+    #[serde(default)]
+    pub ttl_secs: Option<u64>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PutEntryResponse {}
+
+// Puts the value if the entry still has `version`, or is absent if `version`
+// is 0, and fails with a storage conflict otherwise.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct CompareAndPutEntryRequest {
+    pub key: String,
+    pub value: String,
+    pub version: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct CompareAndPutEntryResponse {
+    pub version: u64,
+}
+
+// Deleting a missing entry succeeds.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct DeleteEntryRequest {
+    pub key: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct DeleteEntryResponse {}
+
+// Entries whose key starts with `prefix`, sorted by key, from `start_key` on
+// if set. `start_key` must start with `prefix`. At most `limit` entries are
+// returned, and no more than TDFS returns at once.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ScanEntriesRequest {
+    pub prefix: String,
+    #[serde(default)]
+    pub start_key: Option<String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ScanEntriesResponse {
+    pub entries: Vec<Entry>,
+    // Start key of the next page, `None` on the last one.
+    #[serde(default)]
+    pub next_key: Option<String>,
+}
+
+// Applies the writes in order, all or none of them. A failed check fails the
+// request with a storage conflict, and no entry is changed.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct WriteEntriesRequest {
+    pub ops: Vec<WriteOp>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct WriteEntriesResponse {}
+
+// Files accessible to a user, including files shared with them.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ListUserFilesRequest {
+    pub user_id: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct UserFile {
+    pub file_id: String,
+    pub file_name: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ListUserFilesResponse {
+    pub files: Vec<UserFile>,
+}
+
+// Deletes a file and its key regardless of its owner, for files whose
+// lifetime is managed by TMS.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct DeleteFileRequest {
+    pub file_id: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct DeleteFileResponse {}
+
+// `prev_hash` of the first entry of the audit log.
+pub const AUDIT_LOG_GENESIS_HASH: &str =
+    "0000000000000000000000000000000000000000000000000000000000000000";
+
+// Entry of the append-only audit log kept for TMS. The event is opaque to
+// TDFS. Entries are numbered and timestamped by TDFS as they are appended,
+// and each entry is chained to the previous one: `hash` is the hex SHA-256 of
+// `prev_hash`, then `sequence` and `timestamp` as 8 byte big-endian integers,
+// then the length of `user_id` as an 8 byte big-endian integer, `user_id` and
+// `event`. Changing or removing an entry breaks the chain.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct AuditEntry {
+    pub sequence: u64,
+    // Seconds since the epoch.
+    pub timestamp: u64,
+    pub user_id: String,
+    pub event: String,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct AppendAuditEntryRequest {
+    pub user_id: String,
+    pub event: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct AppendAuditEntryResponse {
+    pub entry: AuditEntry,
+}
+
+// Entries with `since <= timestamp < until` by `user_id`, oldest first. At
+// most `limit` entries are returned, starting after `after_sequence`.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ListAuditEntriesRequest {
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+    pub user_id: Option<String>,
+    pub after_sequence: Option<u64>,
+    pub limit: usize,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ListAuditEntriesResponse {
+    pub entries: Vec<AuditEntry>,
+}
+
+// Replaces the enclave info TDFS verifies its peers against, as TMS does when
+// an admin reloads it. The fragments carry their auditor signatures, which
+// TDFS verifies itself.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ReloadEnclaveInfoRequest {
+    pub fragments: Vec<SignedEnclaveInfo>,
+}
+
+// Services of the reloaded enclave info, sorted by name.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ReloadEnclaveInfoResponse {
+    pub services: Vec<String>,
 }
 
 impl DFSRequest {
@@ -90,15 +364,156 @@ impl DFSRequest {
             task_id: task_id.to_owned(),
             collaborator_list: collaborator_list.iter().map(|s| (*s).to_string()).collect(),
             allow_policy,
+            wrapped_key: None,
+            digest_algorithm: default_digest_algorithm(),
+            format: None,
+            encryption_context: None,
         })
     }
 
+    pub fn new_create_wrapped_file(
+        sha256: &str,
+        file_size: u32,
+        user_id: &str,
+        task_id: &str,
+        wrapped_key: &WrappedKey,
+    ) -> DFSRequest {
+        DFSRequest::Create(CreateFileRequest {
+            sha256: sha256.to_owned(),
+            file_size,
+            user_id: user_id.to_owned(),
+            task_id: task_id.to_owned(),
+            collaborator_list: Vec::new(),
+            allow_policy: 0,
+            wrapped_key: Some(wrapped_key.clone()),
+            digest_algorithm: default_digest_algorithm(),
+            format: None,
+            encryption_context: None,
+        })
+    }
+
+    // The create request declaring the format of the file.
+    pub fn with_format(self, format: Option<&FileFormat>) -> DFSRequest {
+        match self {
+            DFSRequest::Create(req) => DFSRequest::Create(CreateFileRequest {
+                format: format.cloned(),
+                ..req
+            }),
+            req => req,
+        }
+    }
+
+    // The create request for content encrypted in `context`.
+    pub fn with_encryption_context(self, context: Option<&EncryptionContext>) -> DFSRequest {
+        match self {
+            DFSRequest::Create(req) => DFSRequest::Create(CreateFileRequest {
+                encryption_context: context.cloned(),
+                ..req
+            }),
+            req => req,
+        }
+    }
+
     pub fn new_get_file(file_id: &str) -> DFSRequest {
         let req = GetFileRequest {
             file_id: file_id.to_owned(),
         };
         DFSRequest::Get(req)
     }
+
+    pub fn new_multi_get(keys: &[String]) -> DFSRequest {
+        DFSRequest::MultiGet(MultiGetRequest {
+            keys: keys.to_vec(),
+        })
+    }
+
+    pub fn new_get_entry(key: &str) -> DFSRequest {
+        DFSRequest::GetEntry(GetEntryRequest {
+            key: key.to_owned(),
+        })
+    }
+
+    pub fn new_put_entry(key: &str, value: &str, ttl_secs: Option<u64>) -> DFSRequest {
+        DFSRequest::PutEntry(PutEntryRequest {
+            entry: Entry {
+                key: key.to_owned(),
+                value: value.to_owned(),
+                version: 0,
+            },
+            ttl_secs,
+        })
+    }
+
+    pub fn new_compare_and_put_entry(key: &str, value: &str, version: u64) -> DFSRequest {
+        DFSRequest::CompareAndPutEntry(CompareAndPutEntryRequest {
+            key: key.to_owned(),
+            value: value.to_owned(),
+            version,
+        })
+    }
+
+    pub fn new_delete_entry(key: &str) -> DFSRequest {
+        DFSRequest::DeleteEntry(DeleteEntryRequest {
+            key: key.to_owned(),
+        })
+    }
+
+    pub fn new_scan_entries(
+        prefix: &str,
+        start_key: Option<&str>,
+        limit: Option<usize>,
+    ) -> DFSRequest {
+        DFSRequest::ScanEntries(ScanEntriesRequest {
+            prefix: prefix.to_owned(),
+            start_key: start_key.map(|key| key.to_owned()),
+            limit,
+        })
+    }
+
+    pub fn new_write_entries(ops: &[WriteOp]) -> DFSRequest {
+        DFSRequest::WriteEntries(WriteEntriesRequest { ops: ops.to_vec() })
+    }
+
+    pub fn new_list_user_files(user_id: &str) -> DFSRequest {
+        DFSRequest::ListUserFiles(ListUserFilesRequest {
+            user_id: user_id.to_owned(),
+        })
+    }
+
+    pub fn new_delete_file(file_id: &str) -> DFSRequest {
+        DFSRequest::DeleteFile(DeleteFileRequest {
+            file_id: file_id.to_owned(),
+        })
+    }
+
+    pub fn new_append_audit_entry(user_id: &str, event: &str) -> DFSRequest {
+        DFSRequest::AppendAuditEntry(AppendAuditEntryRequest {
+            user_id: user_id.to_owned(),
+            event: event.to_owned(),
+        })
+    }
+
+    pub fn new_list_audit_entries(
+        since: Option<u64>,
+        until: Option<u64>,
+        user_id: Option<&str>,
+        after_sequence: Option<u64>,
+        limit: usize,
+    ) -> DFSRequest {
+        DFSRequest::ListAuditEntries(ListAuditEntriesRequest {
+            since,
+            until,
+            user_id: user_id.map(|s| s.to_owned()),
+            after_sequence,
+            limit,
+        })
+    }
+
+    pub fn new_reload_enclave_info(fragments: &[SignedEnclaveInfo]) -> DFSRequest {
+        DFSRequest::ReloadEnclaveInfo(ReloadEnclaveInfoRequest {
+            fragments: fragments.to_vec(),
+        })
+    }
 }
 
 impl DFSResponse {
@@ -106,7 +521,16 @@ impl DFSResponse {
         let resp = CreateFileResponse {
             file_id: file_id.to_owned(),
             access_path: access_path.to_owned(),
-            key_config: key.clone(),
+            key_config: Some(key.clone()),
+        };
+        DFSResponse::Create(resp)
+    }
+
+    pub fn new_create_wrapped_file(file_id: &str, access_path: &str) -> DFSResponse {
+        let resp = CreateFileResponse {
+            file_id: file_id.to_owned(),
+            access_path: access_path.to_owned(),
+            key_config: None,
         };
         DFSResponse::Create(resp)
     }
@@ -117,4 +541,54 @@ impl DFSResponse {
         };
         DFSResponse::Get(resp)
     }
+
+    pub fn new_multi_get(files: Vec<Option<FileInfo>>) -> DFSResponse {
+        DFSResponse::MultiGet(MultiGetResponse { files })
+    }
+
+    pub fn new_get_entry(value: Option<String>, version: u64) -> DFSResponse {
+        DFSResponse::GetEntry(GetEntryResponse { value, version })
+    }
+
+    pub fn new_put_entry() -> DFSResponse {
+        DFSResponse::PutEntry(PutEntryResponse {})
+    }
+
+    pub fn new_compare_and_put_entry(version: u64) -> DFSResponse {
+        DFSResponse::CompareAndPutEntry(CompareAndPutEntryResponse { version })
+    }
+
+    pub fn new_delete_entry() -> DFSResponse {
+        DFSResponse::DeleteEntry(DeleteEntryResponse {})
+    }
+
+    pub fn new_scan_entries(entries: Vec<Entry>, next_key: Option<String>) -> DFSResponse {
+        DFSResponse::ScanEntries(ScanEntriesResponse { entries, next_key })
+    }
+
+    pub fn new_write_entries() -> DFSResponse {
+        DFSResponse::WriteEntries(WriteEntriesResponse {})
+    }
+
+    pub fn new_list_user_files(files: Vec<UserFile>) -> DFSResponse {
+        DFSResponse::ListUserFiles(ListUserFilesResponse { files })
+    }
+
+    pub fn new_delete_file() -> DFSResponse {
+        DFSResponse::DeleteFile(DeleteFileResponse {})
+    }
+
+    pub fn new_append_audit_entry(entry: &AuditEntry) -> DFSResponse {
+        DFSResponse::AppendAuditEntry(AppendAuditEntryResponse {
+            entry: entry.clone(),
+        })
+    }
+
+    pub fn new_list_audit_entries(entries: Vec<AuditEntry>) -> DFSResponse {
+        DFSResponse::ListAuditEntries(ListAuditEntriesResponse { entries })
+    }
+
+    pub fn new_reload_enclave_info(services: Vec<String>) -> DFSResponse {
+        DFSResponse::ReloadEnclaveInfo(ReloadEnclaveInfoResponse { services })
+    }
 }
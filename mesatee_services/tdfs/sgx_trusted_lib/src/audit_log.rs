@@ -0,0 +1,100 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// The audit log of TMS. Entries are only ever appended, and are hash-chained
+// as described by `AuditEntry`.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use crate::file_util;
+use lazy_static::lazy_static;
+use mesatee_core::Result;
+use ring::digest;
+use std::sync::SgxMutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::untrusted::time::SystemTimeEx;
+use tdfs_internal_proto::{AuditEntry, ListAuditEntriesRequest, AUDIT_LOG_GENESIS_HASH};
+
+lazy_static! {
+    static ref AUDIT_LOG: SgxMutex<Vec<AuditEntry>> = SgxMutex::new(Vec::new());
+}
+
+fn entry_hash(
+    prev_hash: &str,
+    sequence: u64,
+    timestamp: u64,
+    user_id: &str,
+    event: &str,
+) -> Result<String> {
+    let mut ctx = digest::Context::new(&digest::SHA256);
+    ctx.update(prev_hash.as_bytes());
+    ctx.update(&sequence.to_be_bytes());
+    ctx.update(&timestamp.to_be_bytes());
+    ctx.update(&(user_id.len() as u64).to_be_bytes());
+    ctx.update(user_id.as_bytes());
+    ctx.update(event.as_bytes());
+    file_util::digest_to_hex(&ctx.finish())
+}
+
+pub fn append(user_id: &str, event: &str) -> Result<AuditEntry> {
+    let mut log = AUDIT_LOG.lock()?;
+    let prev_hash = log
+        .last()
+        .map_or(AUDIT_LOG_GENESIS_HASH, |entry| entry.hash.as_str())
+        .to_owned();
+    let sequence = log.len() as u64;
+    // Entries are in time order even if the clock goes back.
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0);
+    let timestamp = log.last().map_or(now, |entry| entry.timestamp.max(now));
+    let hash = entry_hash(&prev_hash, sequence, timestamp, user_id, event)?;
+    let entry = AuditEntry {
+        sequence,
+        timestamp,
+        user_id: user_id.to_owned(),
+        event: event.to_owned(),
+        prev_hash,
+        hash,
+    };
+    log.push(entry.clone());
+    Ok(entry)
+}
+
+pub fn list(query: &ListAuditEntriesRequest) -> Result<Vec<AuditEntry>> {
+    let log = AUDIT_LOG.lock()?;
+    let start = query
+        .after_sequence
+        .map_or(0, |sequence| sequence.saturating_add(1) as usize);
+    let entries = log
+        .iter()
+        .skip(start)
+        .filter(|entry| query.since.map_or(true, |since| entry.timestamp >= since))
+        .take_while(|entry| query.until.map_or(true, |until| entry.timestamp < until))
+        .filter(|entry| {
+            query
+                .user_id
+                .as_ref()
+                .map_or(true, |user_id| &entry.user_id == user_id)
+        })
+        .take(query.limit)
+        .cloned()
+        .collect();
+    Ok(entries)
+}
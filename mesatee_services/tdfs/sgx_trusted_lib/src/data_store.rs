@@ -19,23 +19,52 @@
 #[cfg(feature = "mesalock_sgx")]
 use std::prelude::v1::*;
 
+use crate::file_util::DigestAlgorithm;
+use kms_proto::proto::AeadConfig;
 use lazy_static::lazy_static;
-use mesatee_core::db::Memdb;
+use mesatee_core::db::{Kvdb, Memdb};
 use mesatee_core::{Error, ErrorKind, Result};
-use std::collections::HashSet;
+use ring::digest;
+use std::collections::{HashMap, HashSet};
 use std::sync::SgxMutex;
+use tdfs_internal_proto::{EncryptionContext, FileFormat, WrappedKey};
 
 #[derive(Clone)]
 pub struct FileMeta {
     pub user_id: String,
     pub file_name: String,
     pub sha256: String,
-    pub file_size: u32,
+    pub file_size: u64,
     pub key_id: String,
     pub storage_path: String,
     pub task_id: Option<String>,
     pub allow_policy: u32, //0: owner, 1: owner & collaborator, 2: everyone
     pub collaborator_list: Vec<String>,
+    pub chunked: bool,
+    // Set for files encrypted with a key of their owner, which has no key in
+    // KMS.
+    pub wrapped_key: Option<WrappedKey>,
+    // Algorithm of the `sha256` digest, and the key of a CMAC digest.
+    pub digest_algorithm: DigestAlgorithm,
+    pub mac_key: Vec<u8>,
+    // Declared format of the content, not covered by the digest.
+    pub format: Option<FileFormat>,
+    // Task the content was encrypted for, part of the AAD of the ciphertext.
+    pub encryption_context: Option<EncryptionContext>,
+}
+
+// State of a streaming upload between BeginUpload and CommitUpload.
+pub struct UploadSession {
+    pub user_id: String,
+    pub file_name: String,
+    pub file_size: u64,
+    pub received: u64,
+    pub next_chunk: u64,
+    pub key_id: String,
+    pub key_config: AeadConfig,
+    pub storage_path: String,
+    pub digest_algorithm: DigestAlgorithm,
+    pub hasher: digest::Context,
 }
 
 lazy_static! {
@@ -49,6 +78,12 @@ lazy_static! {
     pub static ref FILE_STORE: Memdb<String, FileMeta> = {
         Memdb::<String, FileMeta>::open().expect("failed to open database")
     };
+
+    pub static ref UPLOAD_SESSIONS: SgxMutex<HashMap<String, UploadSession>> =
+        SgxMutex::new(HashMap::new());
+
+    // Entries put by TMS, sorted by key for prefix scans.
+    pub static ref ENTRY_STORE: Kvdb = Kvdb::open().expect("failed to open database");
 }
 
 impl FileMeta {
@@ -123,7 +158,7 @@ fn del_file_for_user(file_id: &str, user_id: &str) -> Result<()> {
 pub fn del_file(file_id: &str) -> Result<FileMeta> {
     let file_meta = FILE_STORE
         .del(&file_id.to_owned())?
-        .ok_or_else(|| Error::from(ErrorKind::MissingValue))?;
+        .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
     let _lock = UPDATELOCK.lock()?;
     del_file_for_user(file_id, &file_meta.user_id)?;
     if file_meta.allow_policy == 1 {
@@ -146,6 +181,12 @@ pub fn add_test_infomation() {
         task_id: None,
         allow_policy: 0,
         collaborator_list: Vec::new(),
+        chunked: false,
+        wrapped_key: None,
+        digest_algorithm: DigestAlgorithm::Sha256,
+        mac_key: Vec::new(),
+        format: None,
+        encryption_context: None,
     };
     let _ = add_file(&"fake_file_record".to_string(), &fake_file_record);
     let fake_file_without_key = FileMeta {
@@ -158,6 +199,12 @@ pub fn add_test_infomation() {
         task_id: None,
         allow_policy: 0,
         collaborator_list: Vec::new(),
+        chunked: false,
+        wrapped_key: None,
+        digest_algorithm: DigestAlgorithm::Sha256,
+        mac_key: Vec::new(),
+        format: None,
+        encryption_context: None,
     };
     let _ = add_file(&"fake_file_without_key".to_string(), &fake_file_without_key);
 
@@ -171,6 +218,12 @@ pub fn add_test_infomation() {
         task_id: None,
         allow_policy: 1,
         collaborator_list: vec!["fake".to_string()],
+        chunked: false,
+        wrapped_key: None,
+        digest_algorithm: DigestAlgorithm::Sha256,
+        mac_key: Vec::new(),
+        format: None,
+        encryption_context: None,
     };
 
     let _ = add_file(
@@ -22,7 +22,11 @@ extern crate sgx_tstd as std;
 #[macro_use]
 extern crate log;
 
+mod audit_log;
 mod data_store;
+// Shared with the TDFS clients, which only some of the helpers are used by.
+#[allow(dead_code)]
+mod file_util;
 mod tdfs_external;
 mod tdfs_internal;
 
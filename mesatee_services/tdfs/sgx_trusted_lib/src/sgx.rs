@@ -18,8 +18,9 @@
 use std::prelude::v1::*;
 
 use mesatee_core::config;
+use mesatee_core::enclave_size::EnclaveSize;
 use mesatee_core::prelude::*;
-use mesatee_core::rpc::server::SgxTrustedServer;
+use mesatee_core::rpc::server::{self, SgxTrustedServer};
 use mesatee_core::Result;
 
 use crate::data_store::add_test_infomation;
@@ -31,6 +32,8 @@ register_ecall_handler!(
     (ECallCommand::ServeConnection, ServeConnectionInput, ServeConnectionOutput),
     (ECallCommand::InitEnclave, InitEnclaveInput, InitEnclaveOutput),
     (ECallCommand::FinalizeEnclave, FinalizeEnclaveInput, FinalizeEnclaveOutput),
+    (ECallCommand::GetMetrics, GetMetricsInput, GetMetricsOutput),
+    (ECallCommand::RunMaintenance, RunMaintenanceInput, RunMaintenanceOutput),
 );
 
 #[handle_ecall]
@@ -53,7 +56,9 @@ fn handle_serve_connection(args: &ServeConnectionInput) -> Result<ServeConnectio
                 return Ok(ServeConnectionOutput::default());
             }
         };
-        let _ = server.start();
+        let _ = server
+            .reject(args.reject)
+            .start_with_shutdown(server::shutdown_signal());
     } else if args.port == external.addr.port() {
         let enclave_attr = match external.inbound_desc {
             config::InboundDesc::External => None,
@@ -67,7 +72,9 @@ fn handle_serve_connection(args: &ServeConnectionInput) -> Result<ServeConnectio
                 return Ok(ServeConnectionOutput::default());
             }
         };
-        let _ = server.start();
+        let _ = server
+            .reject(args.reject)
+            .start_with_shutdown(server::shutdown_signal());
     } else {
         unreachable!()
     }
@@ -75,9 +82,12 @@ fn handle_serve_connection(args: &ServeConnectionInput) -> Result<ServeConnectio
     Ok(ServeConnectionOutput::default())
 }
 
+// Upload sessions buffer their chunks in the enclave heap.
+const SIZE_HINTS: EnclaveSize = EnclaveSize::new(0x100_0000, 0x20_0000);
+
 #[handle_ecall]
 fn handle_init_enclave(_args: &InitEnclaveInput) -> Result<InitEnclaveOutput> {
-    mesatee_core::init_service(env!("CARGO_PKG_NAME"))?;
+    mesatee_core::init_service_with_hints(env!("CARGO_PKG_NAME"), &SIZE_HINTS)?;
 
     add_test_infomation();
 
@@ -86,9 +96,30 @@ fn handle_init_enclave(_args: &InitEnclaveInput) -> Result<InitEnclaveOutput> {
 
 #[handle_ecall]
 fn handle_finalize_enclave(_args: &FinalizeEnclaveInput) -> Result<FinalizeEnclaveOutput> {
+    // Stop serving new requests. In-flight requests are completed by their
+    // own ServeConnection ecalls.
+    server::shutdown();
+
     #[cfg(feature = "cov")]
     sgx_cov::cov_writeout();
 
     debug!("Enclave [TDFS]: Finalized.");
     Ok(FinalizeEnclaveOutput::default())
 }
+
+// Expired entries are already absent, the sweep only frees their memory.
+#[handle_ecall]
+fn handle_run_maintenance(args: &RunMaintenanceInput) -> Result<RunMaintenanceOutput> {
+    if args.sweep {
+        let count = crate::data_store::ENTRY_STORE.sweep()?;
+        if count > 0 {
+            debug!("Enclave [TDFS]: Removed {} expired entries.", count);
+        }
+    }
+    Ok(RunMaintenanceOutput::default())
+}
+
+#[handle_ecall]
+fn handle_get_metrics(_args: &GetMetricsInput) -> Result<GetMetricsOutput> {
+    Ok(GetMetricsOutput::new(mesatee_core::metrics::render()))
+}
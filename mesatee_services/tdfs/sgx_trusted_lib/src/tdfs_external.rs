@@ -19,18 +19,67 @@
 #[cfg(feature = "mesalock_sgx")]
 use std::prelude::v1::*;
 
-use crate::data_store::{self, verify_user, FileMeta, FILE_STORE, USER_FILE_STORE};
+use crate::data_store::{
+    self, verify_user, FileMeta, UploadSession, FILE_STORE, UPLOAD_SESSIONS, USER_FILE_STORE,
+};
+use crate::file_util::{self, DigestAlgorithm};
 use kms_proto;
 use kms_proto::KMSClient;
 use mesatee_core::config;
+use mesatee_core::encryption_context;
+use mesatee_core::health::{DependencyHealth, ServiceHealth};
 use mesatee_core::rpc::EnclaveService;
 use mesatee_core::{Error, ErrorKind, Result};
+use ring::digest;
+use std::io::Write;
 use std::marker::PhantomData;
+use std::untrusted::fs;
 use tdfs_external_proto::{
-    CreateFileRequest, DFSRequest, DFSResponse, DeleteFileRequest, GetFileRequest, ListFileRequest,
+    BeginUploadRequest, CommitUploadRequest, CreateFileRequest, DFSRequest, DFSResponse,
+    DeleteFileRequest, GetFileRequest, GetOutputChunkRequest, HealthCheckRequest, ListFileRequest,
+    UploadChunkRequest,
 };
 use uuid::Uuid;
 
+// Upper bound of the data carried by one UploadChunk request.
+const MAX_UPLOAD_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+// Upper bound of the data carried by one GetOutputChunk response.
+const MAX_DOWNLOAD_CHUNK_SIZE: usize = MAX_UPLOAD_CHUNK_SIZE;
+
+fn file_info(
+    file_meta: FileMeta,
+    key_config: Option<kms_proto::proto::AeadConfig>,
+) -> tdfs_external_proto::FileInfo {
+    let access_path = file_meta.get_access_path();
+    let wrapped_key = file_meta
+        .wrapped_key
+        .map(|wrapped_key| tdfs_external_proto::WrappedKey {
+            key_id: wrapped_key.key_id,
+            wrapped_key: wrapped_key.wrapped_key,
+        });
+    let format = file_meta
+        .format
+        .map(|format| tdfs_external_proto::FileFormat {
+            content_type: format.content_type,
+            schema: format.schema,
+        });
+    tdfs_external_proto::FileInfo {
+        user_id: file_meta.user_id,
+        file_name: file_meta.file_name,
+        sha256: file_meta.sha256,
+        file_size: file_meta.file_size,
+        access_path,
+        task_id: file_meta.task_id,
+        collaborator_list: file_meta.collaborator_list,
+        key_config,
+        chunked: file_meta.chunked,
+        wrapped_key,
+        digest_algorithm: file_meta.digest_algorithm.tag().to_string(),
+        format,
+        encryption_context: file_meta.encryption_context,
+    }
+}
+
 pub trait HandleRequest {
     fn handle_request(&self) -> Result<DFSResponse>;
 }
@@ -39,9 +88,11 @@ impl HandleRequest for CreateFileRequest {
     fn handle_request(&self) -> Result<DFSResponse> {
         if !verify_user(&self.user_id, &self.user_token) {
             return Err(mesatee_core::Error::from(
-                mesatee_core::ErrorKind::PermissionDenied,
+                mesatee_core::ErrorKind::Unauthenticated,
             ));
         }
+        let digest_algorithm = DigestAlgorithm::from_tag(&self.digest_algorithm)?;
+        let sha256 = digest_algorithm.check_digest(&self.sha256, &self.mac_key)?;
 
         let target = config::Internal::target_kms();
         let mut client = KMSClient::new(target)?;
@@ -55,16 +106,29 @@ impl HandleRequest for CreateFileRequest {
         };
 
         let file_id = Uuid::new_v4().to_string();
+        let collaborator_list: Vec<String> = self
+            .collaborator_list
+            .iter()
+            .filter(|collaborator| **collaborator != self.user_id)
+            .cloned()
+            .collect();
+        let allow_policy = if collaborator_list.is_empty() { 0 } else { 1 };
         let file_meta = FileMeta {
             user_id: self.user_id.clone(),
             file_name: self.file_name.clone(),
-            sha256: self.sha256.clone(),
-            file_size: self.file_size,
+            sha256,
+            file_size: u64::from(self.file_size),
             key_id,
             storage_path: file_id.clone(),
             task_id: None,
-            allow_policy: 0,
-            collaborator_list: Vec::new(),
+            allow_policy,
+            collaborator_list,
+            chunked: false,
+            wrapped_key: None,
+            digest_algorithm,
+            mac_key: self.mac_key.clone(),
+            format: None,
+            encryption_context: None,
         };
 
         if FILE_STORE.get(&file_id)?.is_some() {
@@ -82,14 +146,14 @@ impl HandleRequest for GetFileRequest {
     fn handle_request(&self) -> Result<DFSResponse> {
         if !verify_user(&self.user_id, &self.user_token) {
             return Err(mesatee_core::Error::from(
-                mesatee_core::ErrorKind::PermissionDenied,
+                mesatee_core::ErrorKind::Unauthenticated,
             ));
         }
 
         let file_id = &self.file_id;
         let file_meta = FILE_STORE
             .get(file_id)?
-            .ok_or_else(|| Error::from(ErrorKind::MissingValue))?;
+            .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
 
         if !file_meta.check_permission(&self.user_id) {
             return Err(mesatee_core::Error::from(
@@ -97,26 +161,24 @@ impl HandleRequest for GetFileRequest {
             ));
         }
 
-        let target = config::Internal::target_kms();
-        let mut client = KMSClient::new(target)?;
-        let key_request = kms_proto::proto::GetKeyRequest::new(&file_meta.key_id);
-        let key_resp = client.get_key(key_request)?;
-        let key_config = key_resp.get_key_config()?;
-        let key_config = match key_config {
-            kms_proto::KeyConfig::Aead(config) => kms_proto::proto::AeadConfig::from(config),
-            kms_proto::KeyConfig::ProtectedFs(_config) => unimplemented!(), // ProtectedFS is not used by TDFS yet. Config of ProtectedFs will not be generated neither.
-        };
-        let access_path = file_meta.get_access_path();
-        let file_info = tdfs_external_proto::FileInfo {
-            user_id: file_meta.user_id,
-            file_name: file_meta.file_name,
-            sha256: file_meta.sha256,
-            file_size: file_meta.file_size,
-            access_path,
-            task_id: file_meta.task_id,
-            collaborator_list: file_meta.collaborator_list,
-            key_config,
+        let key_config = match file_meta.wrapped_key {
+            Some(_) => None,
+            None => {
+                let target = config::Internal::target_kms();
+                let mut client = KMSClient::new(target)?;
+                let key_request = kms_proto::proto::GetKeyRequest::new(&file_meta.key_id);
+                let key_resp = client.get_key(key_request)?;
+                let key_config = key_resp.get_key_config()?;
+                let key_config = match key_config {
+                    kms_proto::KeyConfig::Aead(config) => {
+                        kms_proto::proto::AeadConfig::from(config)
+                    }
+                    kms_proto::KeyConfig::ProtectedFs(_config) => unimplemented!(), // ProtectedFS is not used by TDFS yet. Config of ProtectedFs will not be generated neither.
+                };
+                Some(key_config)
+            }
         };
+        let file_info = file_info(file_meta, key_config);
 
         let resp = DFSResponse::new_get_file(&file_info);
         Ok(resp)
@@ -127,14 +189,14 @@ impl HandleRequest for DeleteFileRequest {
     fn handle_request(&self) -> Result<DFSResponse> {
         if !verify_user(&self.user_id, &self.user_token) {
             return Err(mesatee_core::Error::from(
-                mesatee_core::ErrorKind::PermissionDenied,
+                mesatee_core::ErrorKind::Unauthenticated,
             ));
         }
 
         let file_id = &self.file_id;
         let file_meta = FILE_STORE
             .get(file_id)?
-            .ok_or_else(|| Error::from(ErrorKind::MissingValue))?;
+            .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
 
         if file_meta.user_id != self.user_id {
             return Err(mesatee_core::Error::from(
@@ -143,27 +205,24 @@ impl HandleRequest for DeleteFileRequest {
         }
         let _ = data_store::del_file(file_id)?;
 
-        let target = config::Internal::target_kms();
-        let mut client = KMSClient::new(target)?;
-        let key_req = kms_proto::proto::DeleteKeyRequest::new(&file_meta.key_id);
-        let key_resp = client.del_key(key_req)?;
-        let key_config = key_resp.get_key_config()?;
-        let key_config = match key_config {
-            kms_proto::KeyConfig::Aead(config) => kms_proto::proto::AeadConfig::from(config),
-            kms_proto::KeyConfig::ProtectedFs(_config) => unimplemented!(), // ProtectedFS is not used by TDFS yet. Config of ProtectedFs will not be generated neither.
-        };
-
-        let access_path = file_meta.get_access_path();
-        let file_info = tdfs_external_proto::FileInfo {
-            user_id: file_meta.user_id,
-            file_name: file_meta.file_name,
-            sha256: file_meta.sha256,
-            file_size: file_meta.file_size,
-            access_path,
-            task_id: file_meta.task_id,
-            collaborator_list: file_meta.collaborator_list,
-            key_config,
+        let key_config = match file_meta.wrapped_key {
+            Some(_) => None,
+            None => {
+                let target = config::Internal::target_kms();
+                let mut client = KMSClient::new(target)?;
+                let key_req = kms_proto::proto::DeleteKeyRequest::new(&file_meta.key_id);
+                let key_resp = client.del_key(key_req)?;
+                let key_config = key_resp.get_key_config()?;
+                let key_config = match key_config {
+                    kms_proto::KeyConfig::Aead(config) => {
+                        kms_proto::proto::AeadConfig::from(config)
+                    }
+                    kms_proto::KeyConfig::ProtectedFs(_config) => unimplemented!(), // ProtectedFS is not used by TDFS yet. Config of ProtectedFs will not be generated neither.
+                };
+                Some(key_config)
+            }
         };
+        let file_info = file_info(file_meta, key_config);
 
         let resp = DFSResponse::new_del_file(&file_info);
         Ok(resp)
@@ -174,7 +233,7 @@ impl HandleRequest for ListFileRequest {
     fn handle_request(&self) -> Result<DFSResponse> {
         if !verify_user(&self.user_id, &self.user_token) {
             return Err(mesatee_core::Error::from(
-                mesatee_core::ErrorKind::PermissionDenied,
+                mesatee_core::ErrorKind::Unauthenticated,
             ));
         }
         // lock is not needed here
@@ -188,6 +247,314 @@ impl HandleRequest for ListFileRequest {
     }
 }
 
+impl HandleRequest for BeginUploadRequest {
+    fn handle_request(&self) -> Result<DFSResponse> {
+        if !verify_user(&self.user_id, &self.user_token) {
+            return Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::Unauthenticated,
+            ));
+        }
+
+        let digest_algorithm = DigestAlgorithm::from_tag(&self.digest_algorithm)?;
+        let hash_algorithm = digest_algorithm.hash_algorithm().ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInputError,
+                format!(
+                    "{} digests are not supported for uploads in chunks",
+                    digest_algorithm.tag()
+                ),
+            )
+        })?;
+
+        let target = config::Internal::target_kms();
+        let mut client = KMSClient::new(target)?;
+        let req = kms_proto::proto::CreateKeyRequest::new(kms_proto::EncType::Aead);
+        let resp = client.create_key(req)?;
+        let key_id = resp.get_key_id();
+        let key_config = resp.get_key_config()?;
+        let key_config = match key_config {
+            kms_proto::KeyConfig::Aead(config) => kms_proto::proto::AeadConfig::from(config),
+            kms_proto::KeyConfig::ProtectedFs(_config) => unimplemented!(), // ProtectedFS is not used by TDFS yet. Config of ProtectedFs will not be generated neither.
+        };
+
+        let file_id = Uuid::new_v4().to_string();
+        if FILE_STORE.get(&file_id)?.is_some() {
+            return Err(Error::from(ErrorKind::UUIDError));
+        }
+
+        let mut sessions = UPLOAD_SESSIONS.lock()?;
+        if sessions.contains_key(&file_id) {
+            return Err(Error::from(ErrorKind::UUIDError));
+        }
+        fs::File::create(file_util::get_local_access_path(&file_id))?;
+        let session = UploadSession {
+            user_id: self.user_id.clone(),
+            file_name: self.file_name.clone(),
+            file_size: self.file_size,
+            received: 0,
+            next_chunk: 0,
+            key_id,
+            key_config,
+            storage_path: file_id.clone(),
+            digest_algorithm,
+            hasher: digest::Context::new(hash_algorithm),
+        };
+        sessions.insert(file_id.clone(), session);
+
+        Ok(DFSResponse::new_begin_upload(&file_id))
+    }
+}
+
+impl HandleRequest for UploadChunkRequest {
+    fn handle_request(&self) -> Result<DFSResponse> {
+        if !verify_user(&self.user_id, &self.user_token) {
+            return Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::Unauthenticated,
+            ));
+        }
+        if self.data.is_empty() || self.data.len() > MAX_UPLOAD_CHUNK_SIZE {
+            return Err(Error::new(
+                ErrorKind::InvalidInputError,
+                format!(
+                    "chunk size must be between 1 and {} bytes",
+                    MAX_UPLOAD_CHUNK_SIZE
+                ),
+            ));
+        }
+
+        let mut sessions = UPLOAD_SESSIONS.lock()?;
+        let session = sessions
+            .get_mut(&self.file_id)
+            .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+        if session.user_id != self.user_id {
+            return Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::PermissionDenied,
+            ));
+        }
+        if self.offset != session.received {
+            return Err(Error::new(
+                ErrorKind::InvalidInputError,
+                format!(
+                    "chunk at offset {} is out of order, expected offset {}",
+                    self.offset, session.received
+                ),
+            ));
+        }
+        let len = self.data.len() as u64;
+        if session.file_size - session.received < len {
+            return Err(Error::new(
+                ErrorKind::InvalidInputError,
+                format!("chunk exceeds the declared file size {}", session.file_size),
+            ));
+        }
+
+        let key_config = &session.key_config;
+        let record = file_util::encrypt_chunk(
+            self.data.clone(),
+            session.next_chunk,
+            &key_config.key,
+            &key_config.nonce,
+            &key_config.ad,
+        )?;
+        let mut f = fs::OpenOptions::new()
+            .append(true)
+            .open(file_util::get_local_access_path(&session.storage_path))?;
+        f.write_all(&record)?;
+
+        session.hasher.update(&self.data);
+        session.received += len;
+        session.next_chunk += 1;
+
+        Ok(DFSResponse::new_upload_chunk(session.received))
+    }
+}
+
+impl HandleRequest for CommitUploadRequest {
+    fn handle_request(&self) -> Result<DFSResponse> {
+        if !verify_user(&self.user_id, &self.user_token) {
+            return Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::Unauthenticated,
+            ));
+        }
+
+        // The upload ends with its commit whether or not the commit succeeds.
+        let session = {
+            let mut sessions = UPLOAD_SESSIONS.lock()?;
+            match sessions.get(&self.file_id) {
+                Some(session) if session.user_id != self.user_id => {
+                    return Err(mesatee_core::Error::from(
+                        mesatee_core::ErrorKind::PermissionDenied,
+                    ));
+                }
+                Some(_) => (),
+                None => return Err(Error::from(ErrorKind::NotFound)),
+            }
+            sessions
+                .remove(&self.file_id)
+                .ok_or_else(|| Error::from(ErrorKind::NotFound))?
+        };
+
+        if session.received != session.file_size {
+            let err = Error::new(
+                ErrorKind::InvalidInputError,
+                format!(
+                    "upload is incomplete, received {} of {} bytes",
+                    session.received, session.file_size
+                ),
+            );
+            abort_upload(&session);
+            return Err(err);
+        }
+        let sha256 = file_util::digest_to_hex(&session.hasher.clone().finish())?;
+        if sha256 != self.sha256.to_ascii_lowercase() {
+            let err = Error::new(
+                ErrorKind::InvalidInputError,
+                format!(
+                    "{} of the uploaded file is {}, expected {}",
+                    session.digest_algorithm.tag(),
+                    sha256,
+                    self.sha256
+                ),
+            );
+            abort_upload(&session);
+            return Err(err);
+        }
+
+        let file_meta = FileMeta {
+            user_id: session.user_id,
+            file_name: session.file_name,
+            sha256,
+            file_size: session.file_size,
+            key_id: session.key_id,
+            storage_path: session.storage_path,
+            task_id: None,
+            allow_policy: 0,
+            collaborator_list: Vec::new(),
+            chunked: true,
+            wrapped_key: None,
+            digest_algorithm: session.digest_algorithm,
+            mac_key: Vec::new(),
+            format: None,
+            encryption_context: None,
+        };
+        data_store::add_file(&self.file_id, &file_meta)?;
+
+        Ok(DFSResponse::new_commit_upload(&self.file_id))
+    }
+}
+
+impl HandleRequest for GetOutputChunkRequest {
+    fn handle_request(&self) -> Result<DFSResponse> {
+        if !verify_user(&self.user_id, &self.user_token) {
+            return Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::Unauthenticated,
+            ));
+        }
+        if self.len == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInputError,
+                "chunk size must be positive",
+            ));
+        }
+
+        let file_meta = FILE_STORE
+            .get(&self.file_id)?
+            .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+        if !file_meta.check_permission(&self.user_id) {
+            return Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::PermissionDenied,
+            ));
+        }
+        // The key of such a file is only known to its owner, who reads the
+        // file with it.
+        if file_meta.wrapped_key.is_some() {
+            return Err(Error::new(
+                ErrorKind::InvalidInputError,
+                "file is encrypted with a key of its owner",
+            ));
+        }
+        if self.offset > file_meta.file_size {
+            return Err(Error::new(
+                ErrorKind::InvalidInputError,
+                format!(
+                    "offset {} is past the end of the file of {} bytes",
+                    self.offset, file_meta.file_size
+                ),
+            ));
+        }
+
+        let target = config::Internal::target_kms();
+        let mut client = KMSClient::new(target)?;
+        let key_request = kms_proto::proto::GetKeyRequest::new(&file_meta.key_id);
+        let key_config = match client.get_key(key_request)?.get_key_config()? {
+            kms_proto::KeyConfig::Aead(config) => kms_proto::proto::AeadConfig::from(config),
+            kms_proto::KeyConfig::ProtectedFs(_config) => unimplemented!(), // ProtectedFS is not used by TDFS yet. Config of ProtectedFs will not be generated neither.
+        };
+
+        let remaining = file_meta.file_size - self.offset;
+        let len = self.len.min(remaining).min(MAX_DOWNLOAD_CHUNK_SIZE as u64) as usize;
+        let context = file_meta.encryption_context.as_ref();
+        let aad = encryption_context::file_aad(&key_config.ad, context);
+        let access_path = file_util::get_local_access_path(&file_meta.storage_path);
+        let data = if file_meta.chunked {
+            let mut f = fs::File::open(access_path)?;
+            file_util::read_chunked_range(
+                &mut f,
+                self.offset,
+                len,
+                &key_config.key,
+                &key_config.nonce,
+                &aad,
+            )
+            .map_err(|err| encryption_context::decryption_error(err, context))?
+        } else {
+            // Files created in one request are sealed as a whole, and are at
+            // most 4 GiB.
+            let encrypted = fs::read(access_path)?;
+            let plaintxt =
+                file_util::decrypt_data(encrypted, &key_config.key, &key_config.nonce, &aad)
+                    .map_err(|err| encryption_context::decryption_error(err, context))?;
+            let start = self.offset as usize;
+            plaintxt
+                .get(start..start + len)
+                .ok_or_else(|| Error::from(ErrorKind::CryptoError))?
+                .to_vec()
+        };
+
+        Ok(DFSResponse::new_get_output_chunk(
+            &self.file_id,
+            self.offset,
+            data,
+            file_meta.file_size,
+            &file_meta.sha256,
+            file_meta.digest_algorithm.tag(),
+        ))
+    }
+}
+
+impl HandleRequest for HealthCheckRequest {
+    fn handle_request(&self) -> Result<DFSResponse> {
+        let kms_up = KMSClient::new(config::Internal::target_kms()).is_ok();
+        let dependencies = vec![DependencyHealth::new("kms", kms_up)];
+        Ok(DFSResponse::new_health_check(ServiceHealth::current(
+            dependencies,
+        )))
+    }
+}
+
+// Removes the partially uploaded file and its key, failures are only logged
+// since the upload is rejected anyway.
+fn abort_upload(session: &UploadSession) {
+    if let Err(e) = fs::remove_file(file_util::get_local_access_path(&session.storage_path)) {
+        warn!("Failed to remove aborted upload: {}", e);
+    }
+    let target = config::Internal::target_kms();
+    let key_req = kms_proto::proto::DeleteKeyRequest::new(&session.key_id);
+    if let Err(e) = KMSClient::new(target).and_then(|mut client| client.del_key(key_req)) {
+        warn!("Failed to delete key of aborted upload: {}", e);
+    }
+}
+
 pub struct DFSExternalEnclave<S, T> {
     state: i32,
     x: PhantomData<S>,
@@ -214,6 +581,11 @@ impl EnclaveService<DFSRequest, DFSResponse> for DFSExternalEnclave<DFSRequest,
             DFSRequest::Get(req) => req.handle_request()?,
             DFSRequest::List(req) => req.handle_request()?,
             DFSRequest::Delete(req) => req.handle_request()?,
+            DFSRequest::BeginUpload(req) => req.handle_request()?,
+            DFSRequest::UploadChunk(req) => req.handle_request()?,
+            DFSRequest::CommitUpload(req) => req.handle_request()?,
+            DFSRequest::GetOutputChunk(req) => req.handle_request()?,
+            DFSRequest::HealthCheck(req) => req.handle_request()?,
         };
         trace!("{}th round complete!", self.state);
         Ok(response)
@@ -18,79 +18,307 @@
 #[cfg(feature = "mesalock_sgx")]
 use std::prelude::v1::*;
 
-use crate::data_store::{self, FileMeta, FILE_STORE};
+use crate::audit_log;
+use crate::data_store::{self, FileMeta, ENTRY_STORE, FILE_STORE, USER_FILE_STORE};
+use crate::file_util::{self, DigestAlgorithm};
 use kms_proto::{self, KMSClient};
 use mesatee_core::config;
+use mesatee_core::content_type;
 use mesatee_core::rpc::EnclaveService;
 use mesatee_core::{Error, ErrorKind, Result};
 use std::marker::PhantomData;
-use tdfs_internal_proto::{CreateFileRequest, DFSRequest, DFSResponse, GetFileRequest};
+use std::time::Duration;
+use std::untrusted::fs;
+use tdfs_internal_proto::{
+    AppendAuditEntryRequest, CompareAndPutEntryRequest, CreateFileRequest, DFSRequest, DFSResponse,
+    DeleteEntryRequest, DeleteFileRequest, Entry, GetEntryRequest, GetFileRequest,
+    ListAuditEntriesRequest, ListUserFilesRequest, MultiGetRequest, PutEntryRequest,
+    ReloadEnclaveInfoRequest, ScanEntriesRequest, UserFile, WriteEntriesRequest,
+    MAX_MULTI_GET_KEYS,
+};
 use uuid::Uuid;
 
+// Entries returned by one scan at most, so that a response stays well under
+// the message size limit.
+const MAX_SCAN_LIMIT: usize = 1000;
+
 pub trait HandleRequest {
     fn handle_request(&self) -> Result<DFSResponse>;
 }
 
 impl HandleRequest for CreateFileRequest {
     fn handle_request(&self) -> Result<DFSResponse> {
-        let target = config::Internal::target_kms();
-        let mut client = KMSClient::new(target)?;
-        let req = kms_proto::proto::CreateKeyRequest::new(kms_proto::EncType::Aead);
-        let resp = client.create_key(req)?;
-        let key_id = resp.get_key_id();
-        let key_config = resp.get_key_config()?;
-        let key_config = match key_config {
-            kms_proto::KeyConfig::Aead(config) => kms_proto::proto::AeadConfig::from(config),
-            kms_proto::KeyConfig::ProtectedFs(_config) => unimplemented!(), // ProtectedFS is not used by TDFS yet. Config of ProtectedFs will not be generated neither.
+        let digest_algorithm = DigestAlgorithm::from_tag(&self.digest_algorithm)?;
+        let sha256 = digest_algorithm.check_digest(&self.sha256, &[])?;
+        if let Some(ref format) = self.format {
+            content_type::check_content_type(&format.content_type)?;
+            if let Some(ref schema) = format.schema {
+                content_type::check_schema(schema)?;
+            }
+        }
+        if let Some(ref context) = self.encryption_context {
+            if context.task_id != self.task_id || context.function_id.is_empty() {
+                return Err(Error::new(
+                    ErrorKind::InvalidInputError,
+                    "encryption context must be of the task of the file and name its function",
+                ));
+            }
+        }
+
+        // A wrapped key is kept as is, TDFS cannot check more than its form.
+        let (key_id, key_config) = match self.wrapped_key {
+            Some(ref wrapped_key) => {
+                if wrapped_key.key_id.is_empty()
+                    || wrapped_key.wrapped_key.len() != file_util::WRAPPED_KEY_LEN
+                {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInputError,
+                        "wrapped key must have a key id and be in the wrapped key format",
+                    ));
+                }
+                (String::new(), None)
+            }
+            None => {
+                let target = config::Internal::target_kms();
+                let mut client = KMSClient::new(target)?;
+                let req = kms_proto::proto::CreateKeyRequest::new(kms_proto::EncType::Aead);
+                let resp = client.create_key(req)?;
+                let key_id = resp.get_key_id();
+                let key_config = resp.get_key_config()?;
+                let key_config = match key_config {
+                    kms_proto::KeyConfig::Aead(config) => {
+                        kms_proto::proto::AeadConfig::from(config)
+                    }
+                    kms_proto::KeyConfig::ProtectedFs(_config) => unimplemented!(), // ProtectedFS is not used by TDFS yet. Config of ProtectedFs will not be generated neither.
+                };
+                (key_id, Some(key_config))
+            }
         };
 
         let file_id = Uuid::new_v4().to_string();
         let file_meta = FileMeta {
             user_id: self.user_id.clone(),
             file_name: self.task_id.clone(),
-            sha256: self.sha256.clone(),
-            file_size: self.file_size,
+            sha256,
+            file_size: u64::from(self.file_size),
             key_id,
             storage_path: file_id.clone(),
             task_id: Some(self.task_id.clone()),
             allow_policy: self.allow_policy,
             collaborator_list: self.collaborator_list.to_vec(),
+            chunked: false,
+            wrapped_key: self.wrapped_key.clone(),
+            digest_algorithm,
+            mac_key: Vec::new(),
+            format: self.format.clone(),
+            encryption_context: self.encryption_context.clone(),
         };
         if FILE_STORE.get(&file_id)?.is_some() {
             return Err(Error::from(ErrorKind::UUIDError));
         }
         data_store::add_file(&file_id, &file_meta)?;
 
-        let resp =
-            DFSResponse::new_create_file(&file_id, &file_meta.get_access_path(), &key_config);
+        let access_path = file_meta.get_access_path();
+        let resp = match key_config {
+            Some(ref key_config) => {
+                DFSResponse::new_create_file(&file_id, &access_path, key_config)
+            }
+            None => DFSResponse::new_create_wrapped_file(&file_id, &access_path),
+        };
         Ok(resp)
     }
 }
 
+fn file_info(file_meta: FileMeta) -> tdfs_internal_proto::FileInfo {
+    let access_path = file_meta.get_access_path();
+    tdfs_internal_proto::FileInfo {
+        user_id: file_meta.user_id,
+        file_name: file_meta.file_name,
+        sha256: file_meta.sha256,
+        file_size: file_meta.file_size,
+        access_path,
+        task_id: file_meta.task_id,
+        collaborator_list: file_meta.collaborator_list,
+        allow_policy: file_meta.allow_policy,
+        key_id: file_meta.key_id,
+        chunked: file_meta.chunked,
+        wrapped_key: file_meta.wrapped_key,
+        digest_algorithm: file_meta.digest_algorithm.tag().to_string(),
+        mac_key: file_meta.mac_key,
+        format: file_meta.format,
+        encryption_context: file_meta.encryption_context,
+    }
+}
+
 impl HandleRequest for GetFileRequest {
     fn handle_request(&self) -> Result<DFSResponse> {
         let file_id = &self.file_id;
         let file_meta = FILE_STORE
             .get(file_id)?
-            .ok_or_else(|| Error::from(ErrorKind::MissingValue))?;
-        let access_path = file_meta.get_access_path();
-        let file_info = tdfs_internal_proto::FileInfo {
-            user_id: file_meta.user_id,
-            file_name: file_meta.file_name,
-            sha256: file_meta.sha256,
-            file_size: file_meta.file_size,
-            access_path,
-            task_id: file_meta.task_id,
-            collaborator_list: file_meta.collaborator_list,
-            allow_policy: file_meta.allow_policy,
-            key_id: file_meta.key_id,
-        };
+            .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+
+        let resp = DFSResponse::new_get_file(&file_info(file_meta));
+        Ok(resp)
+    }
+}
+
+impl HandleRequest for MultiGetRequest {
+    fn handle_request(&self) -> Result<DFSResponse> {
+        if self.keys.len() > MAX_MULTI_GET_KEYS {
+            return Err(Error::new(
+                ErrorKind::InvalidInputError,
+                format!(
+                    "at most {} files can be fetched at once",
+                    MAX_MULTI_GET_KEYS
+                ),
+            ));
+        }
+        let files = FILE_STORE
+            .get_many(&self.keys)?
+            .into_iter()
+            .map(|file_meta| file_meta.map(file_info))
+            .collect();
+        Ok(DFSResponse::new_multi_get(files))
+    }
+}
 
-        let resp = DFSResponse::new_get_file(&file_info);
+impl HandleRequest for GetEntryRequest {
+    fn handle_request(&self) -> Result<DFSResponse> {
+        let resp = match ENTRY_STORE.get(&self.key)? {
+            Some(entry) => DFSResponse::new_get_entry(Some(entry.value), entry.version),
+            None => DFSResponse::new_get_entry(None, 0),
+        };
         Ok(resp)
     }
 }
 
+impl HandleRequest for PutEntryRequest {
+    fn handle_request(&self) -> Result<DFSResponse> {
+        match self.ttl_secs {
+            Some(ttl_secs) => ENTRY_STORE.put_with_ttl(
+                &self.entry.key,
+                &self.entry.value,
+                Duration::from_secs(ttl_secs),
+            )?,
+            None => ENTRY_STORE.put(&self.entry.key, &self.entry.value)?,
+        };
+        Ok(DFSResponse::new_put_entry())
+    }
+}
+
+impl HandleRequest for CompareAndPutEntryRequest {
+    fn handle_request(&self) -> Result<DFSResponse> {
+        let version = ENTRY_STORE.compare_and_put(&self.key, &self.value, self.version)?;
+        Ok(DFSResponse::new_compare_and_put_entry(version))
+    }
+}
+
+impl HandleRequest for DeleteEntryRequest {
+    fn handle_request(&self) -> Result<DFSResponse> {
+        ENTRY_STORE.delete(&self.key)?;
+        Ok(DFSResponse::new_delete_entry())
+    }
+}
+
+impl HandleRequest for ScanEntriesRequest {
+    fn handle_request(&self) -> Result<DFSResponse> {
+        let limit = self.limit.unwrap_or(MAX_SCAN_LIMIT).min(MAX_SCAN_LIMIT);
+        let (page, next_key) = ENTRY_STORE.scan_page(
+            &self.prefix,
+            self.start_key.as_ref().map(|key| key.as_str()),
+            limit,
+        )?;
+        let entries = page
+            .into_iter()
+            .map(|(key, entry)| Entry {
+                key,
+                value: entry.value,
+                version: entry.version,
+            })
+            .collect();
+        Ok(DFSResponse::new_scan_entries(entries, next_key))
+    }
+}
+
+impl HandleRequest for WriteEntriesRequest {
+    fn handle_request(&self) -> Result<DFSResponse> {
+        ENTRY_STORE.write(&self.ops)?;
+        Ok(DFSResponse::new_write_entries())
+    }
+}
+
+impl HandleRequest for ListUserFilesRequest {
+    fn handle_request(&self) -> Result<DFSResponse> {
+        let file_ids = USER_FILE_STORE.get(&self.user_id)?.unwrap_or_default();
+        let mut files: Vec<UserFile> = Vec::with_capacity(file_ids.len());
+        for file_id in file_ids.into_iter() {
+            // Files deleted concurrently are skipped.
+            if let Some(file_meta) = FILE_STORE.get(&file_id)? {
+                files.push(UserFile {
+                    file_id,
+                    file_name: file_meta.file_name,
+                });
+            }
+        }
+        Ok(DFSResponse::new_list_user_files(files))
+    }
+}
+
+impl HandleRequest for DeleteFileRequest {
+    fn handle_request(&self) -> Result<DFSResponse> {
+        let file_meta = data_store::del_file(&self.file_id)?;
+
+        // The file is unreadable once its key is gone, removing the content
+        // only frees the space. A wrapped key goes with the file meta. The
+        // meta is put back if KMS keeps the key, so that the deletion can be
+        // retried instead of leaving the key behind.
+        if file_meta.wrapped_key.is_none() {
+            let target = config::Internal::target_kms();
+            let deleted = KMSClient::new(target).and_then(|mut client| {
+                let key_req = kms_proto::proto::DeleteKeyRequest::new(&file_meta.key_id);
+                client.del_key(key_req)
+            });
+            if let Err(e) = deleted {
+                if let Err(restore_err) = data_store::add_file(&self.file_id, &file_meta) {
+                    warn!(
+                        "Failed to restore file {} after KMS error: {}",
+                        self.file_id, restore_err
+                    );
+                }
+                return Err(e);
+            }
+        }
+        let path = file_util::get_local_access_path(&file_meta.get_access_path());
+        if let Err(e) = fs::remove_file(path) {
+            warn!("Failed to remove content of file {}: {}", self.file_id, e);
+        }
+
+        Ok(DFSResponse::new_delete_file())
+    }
+}
+
+impl HandleRequest for AppendAuditEntryRequest {
+    fn handle_request(&self) -> Result<DFSResponse> {
+        let entry = audit_log::append(&self.user_id, &self.event)?;
+        Ok(DFSResponse::new_append_audit_entry(&entry))
+    }
+}
+
+impl HandleRequest for ListAuditEntriesRequest {
+    fn handle_request(&self) -> Result<DFSResponse> {
+        let entries = audit_log::list(self)?;
+        Ok(DFSResponse::new_list_audit_entries(entries))
+    }
+}
+
+impl HandleRequest for ReloadEnclaveInfoRequest {
+    fn handle_request(&self) -> Result<DFSResponse> {
+        let services = config::reload_enclave_info(&self.fragments)?;
+        info!("Enclave info of {} services reloaded", services.len());
+        Ok(DFSResponse::new_reload_enclave_info(services))
+    }
+}
+
 pub struct DFSInternalEnclave<S, T> {
     state: i32,
     x: PhantomData<S>,
@@ -115,6 +343,18 @@ impl EnclaveService<DFSRequest, DFSResponse> for DFSInternalEnclave<DFSRequest,
         let response = match input {
             DFSRequest::Create(req) => req.handle_request()?,
             DFSRequest::Get(req) => req.handle_request()?,
+            DFSRequest::MultiGet(req) => req.handle_request()?,
+            DFSRequest::GetEntry(req) => req.handle_request()?,
+            DFSRequest::PutEntry(req) => req.handle_request()?,
+            DFSRequest::CompareAndPutEntry(req) => req.handle_request()?,
+            DFSRequest::DeleteEntry(req) => req.handle_request()?,
+            DFSRequest::ScanEntries(req) => req.handle_request()?,
+            DFSRequest::WriteEntries(req) => req.handle_request()?,
+            DFSRequest::ListUserFiles(req) => req.handle_request()?,
+            DFSRequest::DeleteFile(req) => req.handle_request()?,
+            DFSRequest::AppendAuditEntry(req) => req.handle_request()?,
+            DFSRequest::ListAuditEntries(req) => req.handle_request()?,
+            DFSRequest::ReloadEnclaveInfo(req) => req.handle_request()?,
         };
         trace!("{}th round complete!", self.state);
         Ok(response)
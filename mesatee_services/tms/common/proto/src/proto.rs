@@ -19,6 +19,9 @@
 use std::prelude::v1::*;
 
 use serde_derive::*;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
 use std::net::IpAddr;
 
 #[derive(Copy, Clone, Serialize, Deserialize, Debug, PartialEq)]
@@ -27,19 +30,122 @@ pub enum FunctionType {
     Multiparty,
 }
 
-#[derive(Copy, Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, PartialEq, Eq, Hash)]
 pub enum TaskStatus {
     Created,
+    // Waiting for the `not_before` time of the task to become ready.
+    Scheduled,
     Ready,
     Running,
     Finished,
     Failed,
+    Cancelled,
+}
+
+impl TaskStatus {
+    // Allowed task state transitions. Finished, Failed and Cancelled tasks
+    // are final. A running task goes back to Scheduled or Ready when a
+    // failed attempt is retried.
+    pub fn can_transition_to(self, next: TaskStatus) -> bool {
+        match (self, next) {
+            (TaskStatus::Created, TaskStatus::Scheduled)
+            | (TaskStatus::Created, TaskStatus::Ready)
+            | (TaskStatus::Created, TaskStatus::Failed)
+            | (TaskStatus::Created, TaskStatus::Cancelled)
+            | (TaskStatus::Scheduled, TaskStatus::Ready)
+            | (TaskStatus::Scheduled, TaskStatus::Failed)
+            | (TaskStatus::Scheduled, TaskStatus::Cancelled)
+            | (TaskStatus::Ready, TaskStatus::Running)
+            | (TaskStatus::Ready, TaskStatus::Failed)
+            | (TaskStatus::Ready, TaskStatus::Cancelled)
+            | (TaskStatus::Running, TaskStatus::Scheduled)
+            | (TaskStatus::Running, TaskStatus::Ready)
+            | (TaskStatus::Running, TaskStatus::Finished)
+            | (TaskStatus::Running, TaskStatus::Failed)
+            | (TaskStatus::Running, TaskStatus::Cancelled) => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_final(self) -> bool {
+        match self {
+            TaskStatus::Finished | TaskStatus::Failed | TaskStatus::Cancelled => true,
+            _ => false,
+        }
+    }
+
+    // Status of a task recovered after TMS restarted. Created, Scheduled and
+    // Ready tasks resume. A running task fails, since its executor can no longer be
+    // trusted to report its results to the new TMS instance.
+    pub fn after_restart(self) -> TaskStatus {
+        match self {
+            TaskStatus::Running => TaskStatus::Failed,
+            status => status,
+        }
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
 pub struct TaskFile {
     pub user_id: String,
     pub file_id: String,
+    // Output files only: the format TDFS recorded when the file was saved.
+    #[serde(default)]
+    pub format: Option<FileFormat>,
+}
+
+// Declared format of the content of a file, e.g. `application/parquet`, with
+// an optional reference to the schema of the content, such as a URL. This is
+// metadata only, which the digest of the file does not cover.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct FileFormat {
+    pub content_type: String,
+    #[serde(default)]
+    pub schema: Option<String>,
+}
+
+impl FileFormat {
+    pub fn new(content_type: &str, schema: Option<&str>) -> FileFormat {
+        FileFormat {
+            content_type: content_type.to_owned(),
+            schema: schema.map(|schema| schema.to_owned()),
+        }
+    }
+}
+
+// How the result of a task, the output its function returns, is serialized.
+// The result is always returned to the invoker of the task as is. In the
+// other formats, it is also saved as a `TaskResultRecord` in an output file
+// of the task creator, whose digest TDFS takes over the serialized bytes.
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub enum ResultFormat {
+    Text,
+    Json,
+    Cbor,
+}
+
+impl Default for ResultFormat {
+    fn default() -> Self {
+        ResultFormat::Text
+    }
+}
+
+impl ResultFormat {
+    // Content type of the result file, none for `Text`, which has no file.
+    pub fn content_type(self) -> Option<&'static str> {
+        match self {
+            ResultFormat::Text => None,
+            ResultFormat::Json => Some("application/json"),
+            ResultFormat::Cbor => Some("application/cbor"),
+        }
+    }
+}
+
+// Result of a task as saved in its result file.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct TaskResultRecord {
+    pub task_id: String,
+    pub output: String,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
@@ -48,18 +154,663 @@ pub struct CollaboratorStatus {
     pub approved: bool,
 }
 
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct ResourceLimits {
+    pub max_memory_mb: u64,
+    pub max_cpu_seconds: u64,
+    pub max_wall_seconds: u64,
+}
+
+// Name of the execution worker running WASM functions. Tasks of a WASM
+// function are created with this function name.
+pub const WASM_FUNCTION_NAME: &str = "wasm_function";
+// Name of the execution worker running Python functions.
+pub const PYTHON_FUNCTION_NAME: &str = "python_function";
+
+// How the payload of a registered function is run.
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Executor {
+    // A worker built into the execution enclave, selected by the function name.
+    Builtin,
+    // A WebAssembly module, the payload is the base64 encoded module.
+    Wasm,
+    // A Python script defining `main(inputs, output)`, the payload is the
+    // source of the script.
+    Python,
+}
+
+impl Default for Executor {
+    fn default() -> Self {
+        Executor::Builtin
+    }
+}
+
+impl Executor {
+    // Name of the executor in requests.
+    pub fn name(self) -> &'static str {
+        match self {
+            Executor::Builtin => "builtin",
+            Executor::Wasm => "wasm",
+            Executor::Python => "python",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Executor> {
+        [Executor::Builtin, Executor::Wasm, Executor::Python]
+            .iter()
+            .cloned()
+            .find(|executor| executor.name() == name)
+    }
+}
+
+// What a function may do beyond computing over its inputs. Functions have
+// none unless they declare them, and may only declare the capabilities the
+// cluster allows in `executor_capabilities.allowed` of the runtime config.
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecutorCapability {
+    // Opening network connections.
+    Network,
+    // Reading and writing files other than the inputs and outputs of the
+    // task.
+    Filesystem,
+    // Running other programs.
+    Subprocess,
+}
+
+impl ExecutorCapability {
+    // Name of the capability in requests and in the runtime config.
+    pub fn name(self) -> &'static str {
+        match self {
+            ExecutorCapability::Network => "network",
+            ExecutorCapability::Filesystem => "filesystem",
+            ExecutorCapability::Subprocess => "subprocess",
+        }
+    }
+
+    pub fn all() -> &'static [ExecutorCapability] {
+        &[
+            ExecutorCapability::Network,
+            ExecutorCapability::Filesystem,
+            ExecutorCapability::Subprocess,
+        ]
+    }
+
+    // Top-level Python modules giving the capability. A Python function may
+    // only import such a module with all the capabilities the module gives.
+    pub fn python_modules(self) -> &'static [&'static str] {
+        match self {
+            ExecutorCapability::Network => &[
+                "ftplib",
+                "httplib",
+                "socket",
+                "ssl",
+                "telnetlib",
+                "urllib",
+                "urllib2",
+            ],
+            ExecutorCapability::Filesystem => &[
+                "fileinput",
+                "glob",
+                "io",
+                "os",
+                "posix",
+                "shutil",
+                "tempfile",
+            ],
+            ExecutorCapability::Subprocess => &["commands", "os", "popen2", "posix", "subprocess"],
+        }
+    }
+}
+
+// Python modules a function with `capabilities` may import: the
+// `allowed_modules` of the cluster and the modules of its capabilities, but
+// no module giving a capability the function lacks.
+pub fn python_modules(
+    allowed_modules: &[String],
+    capabilities: &[ExecutorCapability],
+) -> Vec<String> {
+    let denied = |module: &str| {
+        ExecutorCapability::all().iter().any(|capability| {
+            !capabilities.contains(capability) && capability.python_modules().contains(&module)
+        })
+    };
+    let granted = capabilities
+        .iter()
+        .flat_map(|capability| capability.python_modules().iter())
+        .map(|module| (*module).to_string());
+    let mut modules: Vec<String> = allowed_modules
+        .iter()
+        .cloned()
+        .chain(granted)
+        .filter(|module| !denied(module))
+        .collect();
+    modules.sort();
+    modules.dedup();
+    modules
+}
+
+// An executor the execution service runs functions with, as it advertises it
+// to TMS.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct ExecutorInfo {
+    pub executor: Executor,
+    pub version: String,
+    // How functions of the executor get their inputs, among "payload",
+    // "files", "ports" and "collections".
+    pub input_modes: Vec<String>,
+    // How functions of the executor give their outputs, among "result",
+    // "files", "ports" and "dynamic_ports".
+    pub output_modes: Vec<String>,
+    // Fields of `ResourceLimits` the executor enforces.
+    pub resource_limits: Vec<String>,
+}
+
+// Type of a function argument. The value of an `Enum` argument is a string,
+// which must be one of the allowed values of the argument.
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ArgumentType {
+    String,
+    Integer,
+    Bool,
+    Enum,
+}
+
+// Argument declared by a registered function.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct ArgumentSpec {
+    pub name: String,
+    pub arg_type: ArgumentType,
+    #[serde(default)]
+    pub required: bool,
+    // Value of an optional argument that is not supplied by the task.
+    #[serde(default)]
+    pub default: Option<Value>,
+    // If not empty, the argument must have one of these values.
+    #[serde(default)]
+    pub allowed_values: Vec<Value>,
+}
+
+impl ArgumentSpec {
+    pub fn new(name: &str, arg_type: ArgumentType, required: bool) -> ArgumentSpec {
+        ArgumentSpec {
+            name: name.to_owned(),
+            arg_type,
+            required,
+            default: None,
+            allowed_values: Vec::new(),
+        }
+    }
+}
+
+// Arguments of a task by name.
+pub type TaskArguments = BTreeMap<String, Value>;
+
+// Input file a task reads its arguments from, pinned to the digest TDFS
+// recorded for it when the task was created. The file holds a JSON object of
+// arguments by name.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct ArgumentsFile {
+    pub file_id: String,
+    pub digest_algorithm: String,
+    pub digest: String,
+}
+
+// Named input or output of a registered function. `data_type` labels the data
+// the port carries, e.g. "csv" or "model", and is empty for untyped data. An
+// output port may only be bound to an input port of the same data type, unless
+// either is untyped or `ANY_DATA_TYPE`.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct PortSpec {
+    pub name: String,
+    #[serde(default)]
+    pub data_type: String,
+    // Input ports only: whether every task of the function binds a file to it.
+    #[serde(default)]
+    pub required: bool,
+    // Output ports only: whether the function produces any number of files on
+    // it, known when the task finishes, rather than a single file.
+    #[serde(default)]
+    pub dynamic: bool,
+    // Output ports only: format of the files the function produces on it.
+    #[serde(default)]
+    pub format: Option<FileFormat>,
+}
+
+impl PortSpec {
+    pub fn new(name: &str, data_type: &str, required: bool) -> PortSpec {
+        PortSpec {
+            name: name.to_owned(),
+            data_type: data_type.to_owned(),
+            required,
+            dynamic: false,
+            format: None,
+        }
+    }
+
+    pub fn new_dynamic_output(name: &str, data_type: &str) -> PortSpec {
+        PortSpec {
+            name: name.to_owned(),
+            data_type: data_type.to_owned(),
+            required: false,
+            dynamic: true,
+            format: None,
+        }
+    }
+
+    // The output port declaring the format of its files.
+    pub fn with_format(self, format: FileFormat) -> PortSpec {
+        PortSpec {
+            format: Some(format),
+            ..self
+        }
+    }
+}
+
+pub const ANY_DATA_TYPE: &str = "any";
+
+// IDs of the files bound to the ports of a task, by port name.
+pub type PortBindings = BTreeMap<String, String>;
+
+// Output port `port` of task `task_id`, a dependency of the task whose input
+// port is bound to it.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct PortEdge {
+    pub task_id: String,
+    pub port: String,
+}
+
+impl PortEdge {
+    pub fn new(task_id: &str, port: &str) -> PortEdge {
+        PortEdge {
+            task_id: task_id.to_owned(),
+            port: port.to_owned(),
+        }
+    }
+}
+
+// Output ports of dependencies bound to the input ports of a task, by input
+// port name.
+pub type PortEdges = BTreeMap<String, PortEdge>;
+
+// IDs of the files produced on the dynamic output ports of a task, by port
+// name, in the order the function produced them.
+pub type DynamicOutputBindings = BTreeMap<String, Vec<String>>;
+
+// File produced on a dynamic output port, with the digest TMS verified when
+// the executor reported it.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct DynamicOutput {
+    pub file_id: String,
+    pub digest_algorithm: String,
+    pub digest: String,
+}
+
+// Labels users attach to their tasks and functions for their own bookkeeping,
+// e.g. `team=ml`. Labels are metadata only, TMS does not act on them.
+pub type Labels = BTreeMap<String, String>;
+
+// Output saved by a running task at a progress checkpoint, readable by the
+// task creator before the task ends. Every checkpoint saves a new file, and
+// `sha256` is the digest of that file alone, so that a partial output can be
+// checked on its own and says nothing about the final results of the task.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct PartialOutput {
+    pub file_id: String,
+    pub sha256: String,
+}
+
+// A progress checkpoint reported by the executor of a running task.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct TaskProgress {
+    // From 0 to 100.
+    pub percent: u8,
+    pub message: String,
+    pub partial_output: Option<PartialOutput>,
+    // Seconds since the epoch when the checkpoint was recorded.
+    pub reported_at: u64,
+}
+
+// Oldest checkpoints beyond this number are dropped from a task.
+pub const MAX_PROGRESS_CHECKPOINTS: usize = 64;
+
+// Output stream of the function a task log line was written to.
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+}
+
+// A line the function of a task wrote, without its line break, and with the
+// values of the task secrets redacted.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct TaskLogLine {
+    pub stream: LogStream,
+    pub line: String,
+}
+
+// How a task failing for a transient reason is run again. The n-th retry
+// waits `backoff_secs * 2^(n-1)` seconds after the failed attempt.
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct TaskRetryPolicy {
+    // Attempts in total, including the first one.
+    pub max_attempts: u32,
+    pub backoff_secs: u64,
+}
+
+// Why an execution of a task failed, as reported by its executor.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct TaskError {
+    pub message: String,
+    // Whether the failure is transient, e.g. an unavailable service, and
+    // running the task again may succeed.
+    pub retryable: bool,
+}
+
+// Outcome of one execution of a task.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct TaskAttempt {
+    pub status: TaskStatus,
+    pub error: Option<TaskError>,
+    // Seconds since the epoch when the attempt ended.
+    pub ended_at: u64,
+}
+
+// A registered function. Every update creates a new version, and a version is
+// immutable once created. `function_id` is `<id>@v<version>`.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct FunctionInfo {
+    pub function_id: String,
+    pub version: u32,
+    pub function_name: String,
+    pub description: String,
+    pub payload: Option<String>,
+    pub owner: String,
+    #[serde(default)]
+    pub executor: Executor,
+    // Names of input collections a task of the function may leave empty.
+    #[serde(default)]
+    pub optional_inputs: Vec<String>,
+    // Arguments accepted by the function. Tasks of a function without a
+    // schema may pass any arguments.
+    #[serde(default)]
+    pub argument_schema: Option<Vec<ArgumentSpec>>,
+    // Named inputs and outputs of the function. Tasks of a function without
+    // ports pass their files in order.
+    #[serde(default)]
+    pub input_ports: Vec<PortSpec>,
+    #[serde(default)]
+    pub output_ports: Vec<PortSpec>,
+    #[serde(default)]
+    pub labels: Labels,
+    #[serde(default)]
+    pub capabilities: Vec<ExecutorCapability>,
+}
+
+// Input collection of a task, resolved to the files whose names matched its
+// pattern when the task was created. The files are also part of the input
+// files of the task.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct ResolvedCollection {
+    pub name: String,
+    pub pattern: String,
+    pub file_ids: Vec<String>,
+}
+
+// Key-encryption key (KEK) of the task creator. Files saved for the creator
+// are encrypted with a random file key wrapped under the KEK, so that only the
+// holder of the KEK can read them. TMS keeps the KEK in enclave memory only,
+// never in the task records it persists.
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
+pub struct OutputKey {
+    // Chosen by the user to identify the KEK, stored with the wrapped keys.
+    pub key_id: String,
+    // 32 byte AES-256-GCM key.
+    pub kek: Vec<u8>,
+}
+
+// Length of the key-encryption key of an `OutputKey`.
+pub const OUTPUT_KEK_LEN: usize = 32;
+
+// Requests are traced with their Debug output, which must not include the KEK.
+impl fmt::Debug for OutputKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OutputKey")
+            .field("key_id", &self.key_id)
+            .field("kek", &format_args!("<{} bytes>", self.kek.len()))
+            .finish()
+    }
+}
+
+impl OutputKey {
+    pub fn new(key_id: &str, kek: &[u8]) -> OutputKey {
+        OutputKey {
+            key_id: key_id.to_owned(),
+            kek: kek.to_vec(),
+        }
+    }
+
+    // Whether the key has the material needed to wrap file keys.
+    pub fn is_complete(&self) -> bool {
+        !self.key_id.is_empty() && self.kek.len() == OUTPUT_KEK_LEN
+    }
+}
+
+// Secret of a task, e.g. an API key, which its executor gets as the
+// environment variable `env` inside the execution enclave. The secret is
+// either `name` in the secret store of `owner`, the task creator if not set,
+// or a `value` given with the task. TMS seals the values given with a task
+// when the task is created, and keeps the task without them.
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
+pub struct TaskSecret {
+    pub env: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub owner: Option<String>,
+    #[serde(default)]
+    pub value: Option<String>,
+}
+
+// Requests are traced with their Debug output, which must not include the
+// value.
+impl fmt::Debug for TaskSecret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TaskSecret")
+            .field("env", &self.env)
+            .field("name", &self.name)
+            .field("owner", &self.owner)
+            .field("value", &self.value.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+impl TaskSecret {
+    // Secret `name` of the secret store of the task creator.
+    pub fn stored(env: &str, name: &str) -> TaskSecret {
+        TaskSecret {
+            env: env.to_owned(),
+            name: Some(name.to_owned()),
+            owner: None,
+            value: None,
+        }
+    }
+
+    // Secret `name` which `owner` shared with the task creator.
+    pub fn shared(env: &str, owner: &str, name: &str) -> TaskSecret {
+        TaskSecret {
+            env: env.to_owned(),
+            name: Some(name.to_owned()),
+            owner: Some(owner.to_owned()),
+            value: None,
+        }
+    }
+
+    pub fn inline(env: &str, value: &str) -> TaskSecret {
+        TaskSecret {
+            env: env.to_owned(),
+            name: None,
+            owner: None,
+            value: Some(value.to_owned()),
+        }
+    }
+
+    // Whether the value was given with the task rather than stored.
+    pub fn is_inline(&self) -> bool {
+        self.name.is_none()
+    }
+
+    // The secret without its value.
+    pub fn redacted(&self) -> TaskSecret {
+        TaskSecret {
+            value: None,
+            ..self.clone()
+        }
+    }
+}
+
+// Digest of a file recorded by TDFS when the file was created.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct ReceiptFile {
+    pub file_id: String,
+    pub digest_algorithm: String,
+    pub digest: String,
+}
+
+// What a finished task ran on and produced, as attested by TMS.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct TaskReceipt {
+    pub task_id: String,
+    pub user_id: String,
+    pub function_name: String,
+    // Registered function version the task ran, with the SHA-256 digest of
+    // its payload. Not set for built-in functions.
+    pub function_id: Option<String>,
+    pub function_version: Option<u32>,
+    pub function_sha256: Option<String>,
+    pub input_files: Vec<ReceiptFile>,
+    pub output_files: Vec<ReceiptFile>,
+    // Seconds since the epoch.
+    pub created_at: u64,
+    pub finished_at: u64,
+    pub issued_at: u64,
+    // Hex encoded MRENCLAVE and MRSIGNER of the TMS enclave that issued the
+    // receipt.
+    pub mr_enclave: String,
+    pub mr_signer: String,
+}
+
+// Receipt signed with the key of the RA certificate of TMS. The signature is
+// of the exact bytes of `receipt`, the JSON encoded `TaskReceipt`.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct SignedTaskReceipt {
+    pub receipt: String,
+    pub signature: Vec<u8>,
+    // DER encoded RA certificate.
+    pub certificate: Vec<u8>,
+}
+
+impl SignedTaskReceipt {
+    pub fn task_receipt(&self) -> serde_json::Result<TaskReceipt> {
+        serde_json::from_str(&self.receipt)
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct TaskInfo {
     pub user_id: String,
     pub collaborator_list: Vec<CollaboratorStatus>,
     pub approved_user_number: usize,
     pub function_name: String,
+    // Registered function version the task is pinned to, if any.
+    pub function_id: Option<String>,
     pub function_type: FunctionType,
     pub status: TaskStatus,
+    // Incremented on every status change.
+    pub status_version: u64,
     pub ip: IpAddr,
     pub port: u16,
     pub task_token: String,
     pub input_files: Vec<TaskFile>,
     pub output_files: Vec<TaskFile>,
     pub task_result_file_id: Option<String>,
+    pub resource_limits: ResourceLimits,
+    // Tasks that must finish before this task becomes ready. Their results
+    // are appended to `input_files` as they finish.
+    pub dependencies: Vec<String>,
+    // Set when the task is cancelled.
+    pub cancel_reason: Option<String>,
+    #[serde(default)]
+    pub input_collections: Vec<ResolvedCollection>,
+    // Arguments of the task, including the defaults of the function schema.
+    #[serde(default)]
+    pub arguments: TaskArguments,
+    // File of the task creator read for the arguments which are not given
+    // inline. It is not one of the input files of the task.
+    #[serde(default)]
+    pub arguments_file: Option<ArgumentsFile>,
+    // Seconds since the epoch when the task reached a final status.
+    #[serde(default)]
+    pub finished_at: Option<u64>,
+    // Seconds the task is kept after it ends, the configured `result_ttl` if
+    // not set.
+    #[serde(default)]
+    pub retention_secs: Option<u64>,
+    // Output files of the task creator are encrypted under this key.
+    #[serde(default)]
+    pub output_key: Option<OutputKey>,
+    // Ready tasks with a higher priority are handed out to executors first.
+    #[serde(default)]
+    pub priority: i32,
+    // Seconds since the epoch when the task was created.
+    #[serde(default)]
+    pub created_at: u64,
+    // Chosen by the task creator, so that retrying the creation of the task
+    // does not create another one.
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+    // Input files bound to the input ports of the function when the task was
+    // created. The files are also part of the input files of the task.
+    #[serde(default)]
+    pub input_ports: PortBindings,
+    // Input ports bound to the output ports of dependencies, which are added
+    // to `input_ports` as the dependencies finish.
+    #[serde(default)]
+    pub input_edges: PortEdges,
+    // Output files reported by the executor for the output ports.
+    #[serde(default)]
+    pub output_ports: PortBindings,
+    // Output files reported by the executor for the dynamic output ports, by
+    // port name. The files are also part of the output files of the task.
+    #[serde(default)]
+    pub dynamic_outputs: BTreeMap<String, Vec<DynamicOutput>>,
+    // Progress checkpoints of the task, oldest first.
+    #[serde(default)]
+    pub progress: Vec<TaskProgress>,
+    // Seconds since the epoch before which the task does not become ready.
+    #[serde(default)]
+    pub not_before: Option<u64>,
+    // Not set for tasks which are not retried.
+    #[serde(default)]
+    pub retry_policy: Option<TaskRetryPolicy>,
+    // Outcomes of the executions of the task, oldest first.
+    #[serde(default)]
+    pub attempts: Vec<TaskAttempt>,
+    #[serde(default)]
+    pub labels: Labels,
+    // Issued when the task finishes.
+    #[serde(default)]
+    pub receipt: Option<SignedTaskReceipt>,
+    // Secrets of the task, without their values. The values given inline are
+    // sealed by TMS under the ID of the task.
+    #[serde(default)]
+    pub secrets: Vec<TaskSecret>,
+    #[serde(default)]
+    pub result_format: ResultFormat,
+    // Output file of the task creator holding the serialized result, set
+    // when a task with a result format other than `Text` finishes.
+    #[serde(default)]
+    pub result_file_id: Option<String>,
 }
@@ -0,0 +1,139 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use std::net::SocketAddr;
+
+use crate::TMSClient;
+use mesatee_core::config::EnclaveAttr;
+use mesatee_core::rpc::channel::{ChannelTimeouts, SgxTrustedChannel};
+use mesatee_core::rpc::sgx::TlsPolicy;
+use mesatee_core::{Error, ErrorKind, FieldErrors, Result};
+use teaclave_attestation::verifier::SgxQuoteVerifier;
+use tms_external_proto::{TaskRequest, TaskResponse};
+
+/// Builds a `TMSClient` connected to TMS over an attested TLS channel.
+///
+/// The address of TMS, the enclaves accepted as TMS and the credentials of
+/// the user are required. The report of TMS must be endorsed by one of the
+/// root CA certificates added with `root_ca_cert`, or by those of the build
+/// config if none is added.
+#[derive(Default)]
+pub struct TMSClientBuilder {
+    addr: Option<SocketAddr>,
+    enclave_attr: Option<EnclaveAttr>,
+    root_ca_certs: Vec<Vec<u8>>,
+    credentials: Option<(String, String)>,
+    timeouts: ChannelTimeouts,
+    tls_policy: Option<TlsPolicy>,
+}
+
+impl TMSClientBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Address TMS listens on for external clients.
+    pub fn addr(mut self, addr: SocketAddr) -> Self {
+        self.addr = Some(addr);
+        self
+    }
+
+    /// Enclaves accepted as TMS.
+    pub fn enclave_attr(mut self, enclave_attr: EnclaveAttr) -> Self {
+        self.enclave_attr = Some(enclave_attr);
+        self
+    }
+
+    /// Accepts the reports endorsed by `cert` (PEM). Can be called several
+    /// times, e.g. with both the old and new root during a rotation.
+    pub fn root_ca_cert(mut self, cert: &[u8]) -> Self {
+        self.root_ca_certs.push(cert.to_vec());
+        self
+    }
+
+    pub fn credentials(mut self, user_id: &str, user_token: &str) -> Self {
+        self.credentials = Some((user_id.to_string(), user_token.to_string()));
+        self
+    }
+
+    pub fn timeouts(mut self, timeouts: ChannelTimeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Overrides the TLS policy of the runtime config.
+    pub fn tls_policy(mut self, tls_policy: TlsPolicy) -> Self {
+        self.tls_policy = Some(tls_policy);
+        self
+    }
+
+    /// Connects to TMS, failing with the missing fields if any required one
+    /// is not set.
+    pub fn build(self) -> Result<TMSClient> {
+        let mut errors = FieldErrors::new();
+        let addr = errors.check("addr", required(self.addr));
+        let enclave_attr = errors.check("enclave_attr", required(self.enclave_attr));
+        let credentials = errors.check("credentials", required(self.credentials));
+        errors.into_result()?;
+        let (addr, enclave_attr) = (addr.unwrap(), enclave_attr.unwrap());
+        let (user_id, user_token) = credentials.unwrap();
+
+        let mut verifier = SgxQuoteVerifier::new(enclave_attr);
+        if !self.root_ca_certs.is_empty() {
+            verifier = verifier.root_ca_certs(self.root_ca_certs);
+        }
+        let tls_policy = self.tls_policy.unwrap_or_else(TlsPolicy::runtime);
+        let channel = SgxTrustedChannel::<TaskRequest, TaskResponse>::with_verifier(
+            addr,
+            verifier,
+            self.timeouts,
+            tls_policy,
+        )?;
+        Ok(TMSClient::with_channel(channel, &user_id, &user_token))
+    }
+}
+
+fn required<T>(value: Option<T>) -> Result<T> {
+    value.ok_or_else(|| Error::new(ErrorKind::MissingValue, "required to build the client"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_reports_missing_fields() {
+        let err = TMSClientBuilder::new()
+            .addr("127.0.0.1:5554".parse().unwrap())
+            .build()
+            .err()
+            .unwrap();
+        let field_paths: Vec<&str> = err
+            .field_errors()
+            .iter()
+            .map(|error| error.field_path.as_str())
+            .collect();
+        assert_eq!(field_paths, vec!["enclave_attr", "credentials"]);
+        assert!(err
+            .field_errors()
+            .iter()
+            .all(|error| error.code == ErrorKind::MissingValue));
+    }
+}
@@ -20,5 +20,7 @@
 #[cfg(feature = "mesalock_sgx")]
 extern crate sgx_tstd as std;
 
+mod builder;
 mod tms_client;
-pub use tms_client::TMSClient;
+pub use builder::TMSClientBuilder;
+pub use tms_client::{verify_task_receipt, TMSClient};
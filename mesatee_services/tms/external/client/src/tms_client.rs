@@ -18,14 +18,29 @@
 #[cfg(feature = "mesalock_sgx")]
 use std::prelude::v1::*;
 
-use mesatee_core::config::{OutboundDesc, TargetDesc};
+use crate::TMSClientBuilder;
+use mesatee_core::config::{EnclaveAttr, OutboundDesc, TargetDesc};
 use mesatee_core::rpc::channel::SgxTrustedChannel;
+use mesatee_core::rpc::sgx::verify_ra_signature;
 use mesatee_core::{self, Result};
 use tms_external_proto::{
-    CreateTaskResponse, GetTaskResponse, ListTaskResponse, TaskRequest, TaskResponse,
-    UpdateTaskResponse,
+    ArgumentSpec, AuditLogQuery, CancelTaskResponse, CreateTaskResponse, CreateTasksResponse,
+    DeleteSecretResponse, DeleteTaskResponse, Executor, ExecutorCapability,
+    GetEnclaveMeasurementResponse, GetFunctionResponse, GetFunctionVersionsResponse,
+    GetTaskReceiptResponse, GetTaskResponse, GetTaskStatusesResponse, GetVersionInfoResponse,
+    HealthCheckResponse, InputCollection, InvokeFunctionResponse, Labels, ListExecutorsResponse,
+    ListFunctionsResponse, ListSecretsResponse, ListTaskResponse, OutputKey, PortSpec,
+    PutSecretResponse, QueryAuditLogResponse, QueryTasksResponse, RegisterFunctionResponse,
+    ReloadEnclaveInfoResponse, RemotePayload, ResourceLimits, ResultFormat, RevokeUserResponse,
+    ShareFunctionResponse, SignedEnclaveInfo, SignedTaskReceipt, StreamTaskLogsResponse,
+    TaskArguments, TaskLogLine, TaskQuery, TaskReceipt, TaskRequest, TaskResponse, TaskRetryPolicy,
+    TaskSecret, TaskSpec, UnrevokeUserResponse, UnshareFunctionResponse, UpdateFunctionResponse,
+    UpdateTaskResponse, ValidateTaskResponse, WaitForTaskStatusResponse,
 };
 
+// Seconds each request of `stream_task_logs` waits for new lines.
+const LOG_POLL_SECS: u64 = 10;
+
 pub struct TMSClient {
     user_id: String,
     user_token: String,
@@ -42,11 +57,24 @@ impl TMSClient {
             }
         };
 
-        Ok(TMSClient {
+        Ok(Self::with_channel(channel, user_id, user_token))
+    }
+
+    /// Starts building a client, see `TMSClientBuilder`.
+    pub fn builder() -> TMSClientBuilder {
+        TMSClientBuilder::new()
+    }
+
+    pub(crate) fn with_channel(
+        channel: SgxTrustedChannel<TaskRequest, TaskResponse>,
+        user_id: &str,
+        user_token: &str,
+    ) -> Self {
+        TMSClient {
             channel,
             user_id: user_id.to_string(),
             user_token: user_token.to_string(),
-        })
+        }
     }
 
     pub fn request_create_task(
@@ -71,40 +99,1210 @@ impl TMSClient {
         }
     }
 
-    pub fn request_get_task(&mut self, task_id: &str) -> Result<GetTaskResponse> {
-        let req = TaskRequest::new_get_task(task_id, &self.user_id, &self.user_token);
+    // Runs function `function_id` on `inline_inputs` and returns its outputs
+    // in one request, for trying out a function while it is developed.
+    pub fn request_invoke_function(
+        &mut self,
+        function_id: &str,
+        inline_inputs: &[&[u8]],
+        arguments: &TaskArguments,
+    ) -> Result<InvokeFunctionResponse> {
+        let req = TaskRequest::new_invoke_function(
+            function_id,
+            inline_inputs,
+            arguments,
+            &self.user_id,
+            &self.user_token,
+        );
         let resp = self.channel.invoke(req)?;
         match resp {
-            TaskResponse::Get(resp) => Ok(resp),
+            TaskResponse::InvokeFunction(resp) => Ok(resp),
             _ => Err(mesatee_core::Error::from(
                 mesatee_core::ErrorKind::RPCResponseError,
             )),
         }
     }
 
-    pub fn request_update_task(
+    // Creates a new task like task `task_id`, with `arguments` instead of
+    // its arguments if set.
+    pub fn request_rerun_task(
         &mut self,
         task_id: &str,
+        arguments: Option<&TaskArguments>,
+    ) -> Result<CreateTaskResponse> {
+        let req = TaskRequest::new_rerun_task(task_id, arguments, &self.user_id, &self.user_token);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::RerunTask(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_create_tasks(&mut self, tasks: &[TaskSpec]) -> Result<CreateTasksResponse> {
+        let req = TaskRequest::new_create_tasks(tasks, &self.user_id, &self.user_token);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::CreateTasks(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_validate_task(&mut self, task: &TaskSpec) -> Result<ValidateTaskResponse> {
+        let req = TaskRequest::new_validate_task(task, &self.user_id, &self.user_token);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::ValidateTask(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_create_task_with_limits(
+        &mut self,
+        function_name: &str,
+        collaborator_list: &[&str],
         files: &[&str],
-    ) -> Result<UpdateTaskResponse> {
-        let req = TaskRequest::new_update_task(task_id, files, &self.user_id, &self.user_token);
+        resource_limits: ResourceLimits,
+    ) -> Result<CreateTaskResponse> {
+        let req = TaskRequest::new_create_task_with_limits(
+            function_name,
+            collaborator_list,
+            files,
+            resource_limits,
+            &self.user_id,
+            &self.user_token,
+        );
         let resp = self.channel.invoke(req)?;
         match resp {
-            TaskResponse::Update(resp) => Ok(resp),
+            TaskResponse::Create(resp) => Ok(resp),
             _ => Err(mesatee_core::Error::from(
                 mesatee_core::ErrorKind::RPCResponseError,
             )),
         }
     }
 
-    pub fn request_list_task(&mut self) -> Result<ListTaskResponse> {
-        let req = TaskRequest::new_list_task(&self.user_id, &self.user_token);
+    pub fn request_create_task_with_collections(
+        &mut self,
+        function_name: &str,
+        collaborator_list: &[&str],
+        files: &[&str],
+        input_collections: &[InputCollection],
+    ) -> Result<CreateTaskResponse> {
+        let req = TaskRequest::new_create_task_with_collections(
+            function_name,
+            collaborator_list,
+            files,
+            input_collections,
+            &self.user_id,
+            &self.user_token,
+        );
         let resp = self.channel.invoke(req)?;
         match resp {
-            TaskResponse::List(resp) => Ok(resp),
+            TaskResponse::Create(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_create_task_with_retention(
+        &mut self,
+        function_name: &str,
+        collaborator_list: &[&str],
+        files: &[&str],
+        retention_secs: u64,
+    ) -> Result<CreateTaskResponse> {
+        let req = TaskRequest::new_create_task_with_retention(
+            function_name,
+            collaborator_list,
+            files,
+            retention_secs,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::Create(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_create_task_with_arguments(
+        &mut self,
+        function_id: &str,
+        files: &[&str],
+        arguments: &TaskArguments,
+    ) -> Result<CreateTaskResponse> {
+        let req = TaskRequest::new_create_task_with_arguments(
+            function_id,
+            files,
+            arguments,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::Create(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_create_task_with_output_key(
+        &mut self,
+        function_id: &str,
+        files: &[&str],
+        output_key: &OutputKey,
+    ) -> Result<CreateTaskResponse> {
+        let req = TaskRequest::new_create_task_with_output_key(
+            function_id,
+            files,
+            output_key,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::Create(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_create_task_with_priority(
+        &mut self,
+        function_name: &str,
+        files: &[&str],
+        priority: i32,
+    ) -> Result<CreateTaskResponse> {
+        let req = TaskRequest::new_create_task_with_priority(
+            function_name,
+            files,
+            priority,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::Create(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_create_task_with_idempotency_key(
+        &mut self,
+        function_name: &str,
+        files: &[&str],
+        idempotency_key: &str,
+    ) -> Result<CreateTaskResponse> {
+        let req = TaskRequest::new_create_task_with_idempotency_key(
+            function_name,
+            files,
+            idempotency_key,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::Create(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    // Creates a task whose ID is derived from its content, or returns the
+    // identical task created before.
+    pub fn request_create_content_addressed_task(
+        &mut self,
+        function_name: &str,
+        files: &[&str],
+        arguments: &TaskArguments,
+    ) -> Result<CreateTaskResponse> {
+        let req = TaskRequest::new_create_content_addressed_task(
+            function_name,
+            files,
+            arguments,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::Create(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    // The task is not handed out to executors before `not_before`, in
+    // seconds since the epoch.
+    pub fn request_create_task_with_not_before(
+        &mut self,
+        function_name: &str,
+        files: &[&str],
+        not_before: u64,
+    ) -> Result<CreateTaskResponse> {
+        let req = TaskRequest::new_create_task_with_not_before(
+            function_name,
+            files,
+            not_before,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::Create(resp) => Ok(resp),
             _ => Err(mesatee_core::Error::from(
                 mesatee_core::ErrorKind::RPCResponseError,
             )),
         }
     }
+
+    // Failures of the task reported as transient are retried under
+    // `retry_policy`.
+    pub fn request_create_task_with_retry_policy(
+        &mut self,
+        function_name: &str,
+        files: &[&str],
+        retry_policy: TaskRetryPolicy,
+    ) -> Result<CreateTaskResponse> {
+        let req = TaskRequest::new_create_task_with_retry_policy(
+            function_name,
+            files,
+            retry_policy,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::Create(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_create_task_with_labels(
+        &mut self,
+        function_name: &str,
+        files: &[&str],
+        labels: &Labels,
+    ) -> Result<CreateTaskResponse> {
+        let req = TaskRequest::new_create_task_with_labels(
+            function_name,
+            files,
+            labels,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::Create(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_create_task_with_result_format(
+        &mut self,
+        function_name: &str,
+        files: &[&str],
+        result_format: ResultFormat,
+    ) -> Result<CreateTaskResponse> {
+        let req = TaskRequest::new_create_task_with_result_format(
+            function_name,
+            files,
+            result_format,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::Create(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    // Inline `arguments` replace those of the file with the same name.
+    pub fn request_create_task_with_arguments_file(
+        &mut self,
+        function_name: &str,
+        arguments: &TaskArguments,
+        arguments_file: &str,
+    ) -> Result<CreateTaskResponse> {
+        let req = TaskRequest::new_create_task_with_arguments_file(
+            function_name,
+            arguments,
+            arguments_file,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::Create(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_create_task_with_secrets(
+        &mut self,
+        function_name: &str,
+        files: &[&str],
+        secrets: &[TaskSecret],
+    ) -> Result<CreateTaskResponse> {
+        let req = TaskRequest::new_create_task_with_secrets(
+            function_name,
+            files,
+            secrets,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::Create(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    // `input_ports` binds file IDs to the input ports of the function.
+    pub fn request_create_task_with_ports(
+        &mut self,
+        function_id: &str,
+        input_ports: &[(&str, &str)],
+    ) -> Result<CreateTaskResponse> {
+        let req = TaskRequest::new_create_task_with_ports(
+            function_id,
+            input_ports,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::Create(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    // `input_edges` binds input ports of the function to output ports of
+    // other tasks, as `(input port, task ID, output port)`, which become the
+    // dependencies of the task.
+    pub fn request_create_task_with_edges(
+        &mut self,
+        function_id: &str,
+        input_ports: &[(&str, &str)],
+        input_edges: &[(&str, &str, &str)],
+    ) -> Result<CreateTaskResponse> {
+        let req = TaskRequest::new_create_task_with_edges(
+            function_id,
+            input_ports,
+            input_edges,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::Create(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_create_task_with_dependencies(
+        &mut self,
+        function_name: &str,
+        collaborator_list: &[&str],
+        files: &[&str],
+        dependencies: &[&str],
+    ) -> Result<CreateTaskResponse> {
+        let req = TaskRequest::new_create_task_with_dependencies(
+            function_name,
+            collaborator_list,
+            files,
+            dependencies,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::Create(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_create_task_for_function(
+        &mut self,
+        function_id: &str,
+        collaborator_list: &[&str],
+        files: &[&str],
+    ) -> Result<CreateTaskResponse> {
+        let req = TaskRequest::new_create_task_for_function(
+            function_id,
+            collaborator_list,
+            files,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::Create(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_register_function(
+        &mut self,
+        function_name: &str,
+        description: &str,
+        payload: Option<&str>,
+    ) -> Result<RegisterFunctionResponse> {
+        let req = TaskRequest::new_register_function(
+            function_name,
+            description,
+            payload,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::RegisterFunction(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_register_function_with_labels(
+        &mut self,
+        function_name: &str,
+        description: &str,
+        payload: Option<&str>,
+        labels: &Labels,
+    ) -> Result<RegisterFunctionResponse> {
+        let req = TaskRequest::new_register_function_with_labels(
+            function_name,
+            description,
+            payload,
+            labels,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::RegisterFunction(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_register_function_with_capabilities(
+        &mut self,
+        function_name: &str,
+        description: &str,
+        executor: Executor,
+        payload: Option<&str>,
+        capabilities: &[ExecutorCapability],
+    ) -> Result<RegisterFunctionResponse> {
+        let req = TaskRequest::new_register_function_with_capabilities(
+            function_name,
+            description,
+            executor,
+            payload,
+            capabilities,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::RegisterFunction(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_register_function_with_schema(
+        &mut self,
+        function_name: &str,
+        description: &str,
+        payload: Option<&str>,
+        argument_schema: &[ArgumentSpec],
+    ) -> Result<RegisterFunctionResponse> {
+        let req = TaskRequest::new_register_function_with_schema(
+            function_name,
+            description,
+            payload,
+            argument_schema,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::RegisterFunction(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    // `module` is the base64 encoded WebAssembly module.
+    pub fn request_register_function_with_ports(
+        &mut self,
+        function_name: &str,
+        description: &str,
+        payload: Option<&str>,
+        input_ports: &[PortSpec],
+        output_ports: &[PortSpec],
+    ) -> Result<RegisterFunctionResponse> {
+        let req = TaskRequest::new_register_function_with_ports(
+            function_name,
+            description,
+            payload,
+            input_ports,
+            output_ports,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::RegisterFunction(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_register_remote_function(
+        &mut self,
+        function_name: &str,
+        description: &str,
+        executor: Executor,
+        remote_payload: &RemotePayload,
+    ) -> Result<RegisterFunctionResponse> {
+        let req = TaskRequest::new_register_remote_function(
+            function_name,
+            description,
+            executor,
+            remote_payload,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::RegisterFunction(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    // Registers a function run by the executor named `executor`.
+    pub fn request_register_function_for_executor(
+        &mut self,
+        function_name: &str,
+        description: &str,
+        payload: Option<&str>,
+        executor: &str,
+    ) -> Result<RegisterFunctionResponse> {
+        let req = TaskRequest::new_register_function_for_executor(
+            function_name,
+            description,
+            payload,
+            executor,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::RegisterFunction(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_register_wasm_function(
+        &mut self,
+        function_name: &str,
+        description: &str,
+        module: &str,
+    ) -> Result<RegisterFunctionResponse> {
+        let req = TaskRequest::new_register_wasm_function(
+            function_name,
+            description,
+            module,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::RegisterFunction(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    // `source` is a Python script defining `main(inputs, output)`.
+    pub fn request_register_python_function(
+        &mut self,
+        function_name: &str,
+        description: &str,
+        source: &str,
+    ) -> Result<RegisterFunctionResponse> {
+        let req = TaskRequest::new_register_python_function(
+            function_name,
+            description,
+            source,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::RegisterFunction(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_register_shared_function(
+        &mut self,
+        function_name: &str,
+        description: &str,
+        payload: Option<&str>,
+        collaborators: &[&str],
+    ) -> Result<RegisterFunctionResponse> {
+        let req = TaskRequest::new_register_shared_function(
+            function_name,
+            description,
+            payload,
+            collaborators,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::RegisterFunction(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_share_function(
+        &mut self,
+        function_id: &str,
+        user_ids: &[&str],
+    ) -> Result<ShareFunctionResponse> {
+        let req =
+            TaskRequest::new_share_function(function_id, user_ids, &self.user_id, &self.user_token);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::ShareFunction(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_unshare_function(
+        &mut self,
+        function_id: &str,
+        user_ids: &[&str],
+    ) -> Result<UnshareFunctionResponse> {
+        let req = TaskRequest::new_unshare_function(
+            function_id,
+            user_ids,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::UnshareFunction(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_update_function(
+        &mut self,
+        function_id: &str,
+        function_name: &str,
+        description: &str,
+        payload: Option<&str>,
+    ) -> Result<UpdateFunctionResponse> {
+        let req = TaskRequest::new_update_function(
+            function_id,
+            function_name,
+            description,
+            payload,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::UpdateFunction(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_get_function(&mut self, function_id: &str) -> Result<GetFunctionResponse> {
+        let req = TaskRequest::new_get_function(function_id, &self.user_id, &self.user_token);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::GetFunction(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_get_function_versions(
+        &mut self,
+        function_id: &str,
+    ) -> Result<GetFunctionVersionsResponse> {
+        let req =
+            TaskRequest::new_get_function_versions(function_id, &self.user_id, &self.user_token);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::GetFunctionVersions(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_list_functions(
+        &mut self,
+        limit: Option<usize>,
+        cursor: &str,
+    ) -> Result<ListFunctionsResponse> {
+        let req = TaskRequest::new_list_functions(limit, cursor, &self.user_id, &self.user_token);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::ListFunctions(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    // Lists the executors functions may be registered for.
+    pub fn request_list_executors(&mut self) -> Result<ListExecutorsResponse> {
+        let req = TaskRequest::new_list_executors(&self.user_id, &self.user_token);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::ListExecutors(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_put_secret(
+        &mut self,
+        name: &str,
+        value: &str,
+        readers: &[&str],
+    ) -> Result<PutSecretResponse> {
+        let req =
+            TaskRequest::new_put_secret(name, value, readers, &self.user_id, &self.user_token);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::PutSecret(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_delete_secret(&mut self, name: &str) -> Result<DeleteSecretResponse> {
+        let req = TaskRequest::new_delete_secret(name, &self.user_id, &self.user_token);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::DeleteSecret(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_list_secrets(&mut self) -> Result<ListSecretsResponse> {
+        let req = TaskRequest::new_list_secrets(&self.user_id, &self.user_token);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::ListSecrets(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    // Lists the functions with all of `labels`.
+    pub fn request_list_functions_with_labels(
+        &mut self,
+        labels: &Labels,
+        limit: Option<usize>,
+        cursor: &str,
+    ) -> Result<ListFunctionsResponse> {
+        let req = TaskRequest::new_list_functions_with_labels(
+            labels,
+            limit,
+            cursor,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::ListFunctions(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_get_task(&mut self, task_id: &str) -> Result<GetTaskResponse> {
+        let req = TaskRequest::new_get_task(task_id, &self.user_id, &self.user_token);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::Get(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_update_task(
+        &mut self,
+        task_id: &str,
+        files: &[&str],
+    ) -> Result<UpdateTaskResponse> {
+        let req = TaskRequest::new_update_task(task_id, files, &self.user_id, &self.user_token);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::Update(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_list_task(&mut self) -> Result<ListTaskResponse> {
+        let req = TaskRequest::new_list_task(&self.user_id, &self.user_token);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::List(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_list_task_page(
+        &mut self,
+        limit: Option<usize>,
+        cursor: &str,
+    ) -> Result<ListTaskResponse> {
+        let req = TaskRequest::new_list_task_page(limit, cursor, &self.user_id, &self.user_token);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::List(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    // Lists the tasks with all of `labels`.
+    pub fn request_list_task_with_labels(
+        &mut self,
+        labels: &Labels,
+        limit: Option<usize>,
+        cursor: &str,
+    ) -> Result<ListTaskResponse> {
+        let req = TaskRequest::new_list_task_with_labels(
+            labels,
+            limit,
+            cursor,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::List(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_cancel_task(
+        &mut self,
+        task_id: &str,
+        reason: &str,
+    ) -> Result<CancelTaskResponse> {
+        let req = TaskRequest::new_cancel_task(task_id, reason, &self.user_id, &self.user_token);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::Cancel(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_delete_task(&mut self, task_id: &str) -> Result<DeleteTaskResponse> {
+        let req = TaskRequest::new_delete_task(task_id, &self.user_id, &self.user_token);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::Delete(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_wait_for_task_status(
+        &mut self,
+        task_id: &str,
+        current_version: u64,
+        timeout_secs: u64,
+    ) -> Result<WaitForTaskStatusResponse> {
+        let req = TaskRequest::new_wait_for_task_status(
+            task_id,
+            current_version,
+            timeout_secs,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::WaitForTaskStatus(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_stream_task_logs(
+        &mut self,
+        task_id: &str,
+        from: u64,
+        timeout_secs: u64,
+    ) -> Result<StreamTaskLogsResponse> {
+        let req = TaskRequest::new_stream_task_logs(
+            task_id,
+            from,
+            timeout_secs,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::StreamTaskLogs(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    // Tails the log of task `task_id` from its first line, and calls
+    // `on_line` with each line as it is produced, until the task has ended.
+    // Returns whether lines were dropped for the size of the log.
+    pub fn stream_task_logs<F>(&mut self, task_id: &str, mut on_line: F) -> Result<bool>
+    where
+        F: FnMut(&TaskLogLine),
+    {
+        let mut from = 0;
+        loop {
+            let resp = self.request_stream_task_logs(task_id, from, LOG_POLL_SECS)?;
+            resp.lines.iter().for_each(&mut on_line);
+            from = resp.next;
+            if resp.complete {
+                return Ok(resp.truncated);
+            }
+        }
+    }
+
+    pub fn request_health_check(&mut self) -> Result<HealthCheckResponse> {
+        let req = TaskRequest::new_health_check();
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::HealthCheck(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_get_enclave_measurement(&mut self) -> Result<GetEnclaveMeasurementResponse> {
+        let req = TaskRequest::new_get_enclave_measurement();
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::GetEnclaveMeasurement(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_get_version_info(&mut self) -> Result<GetVersionInfoResponse> {
+        let req = TaskRequest::new_get_version_info();
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::GetVersionInfo(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_query_audit_log(
+        &mut self,
+        query: &AuditLogQuery,
+    ) -> Result<QueryAuditLogResponse> {
+        let req = TaskRequest::new_query_audit_log(query, &self.user_id, &self.user_token);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::QueryAuditLog(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_query_tasks(
+        &mut self,
+        query: &TaskQuery,
+        limit: Option<usize>,
+        cursor: &str,
+    ) -> Result<QueryTasksResponse> {
+        let req =
+            TaskRequest::new_query_tasks(query, limit, cursor, &self.user_id, &self.user_token);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::QueryTasks(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    // Statuses of many tasks at once, e.g. for dashboards, rather than one
+    // `request_get_task` per task.
+    pub fn request_get_task_statuses(
+        &mut self,
+        task_ids: &[&str],
+    ) -> Result<GetTaskStatusesResponse> {
+        let req = TaskRequest::new_get_task_statuses(task_ids, &self.user_id, &self.user_token);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::GetTaskStatuses(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_get_task_receipt(&mut self, task_id: &str) -> Result<GetTaskReceiptResponse> {
+        let req = TaskRequest::new_get_task_receipt(task_id, &self.user_id, &self.user_token);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::GetTaskReceipt(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_revoke_user(
+        &mut self,
+        revoked_user_id: &str,
+        cancel_tasks: bool,
+    ) -> Result<RevokeUserResponse> {
+        let req = TaskRequest::new_revoke_user(
+            revoked_user_id,
+            cancel_tasks,
+            &self.user_id,
+            &self.user_token,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::RevokeUser(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_unrevoke_user(&mut self, revoked_user_id: &str) -> Result<UnrevokeUserResponse> {
+        let req = TaskRequest::new_unrevoke_user(revoked_user_id, &self.user_id, &self.user_token);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::UnrevokeUser(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_reload_enclave_info(
+        &mut self,
+        fragments: &[SignedEnclaveInfo],
+    ) -> Result<ReloadEnclaveInfoResponse> {
+        let req = TaskRequest::new_reload_enclave_info(fragments, &self.user_id, &self.user_token);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::ReloadEnclaveInfo(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+}
+
+/// Verifies offline that `receipt` was signed by one of the TMS enclaves of
+/// `enclave_attr`, and returns its content.
+pub fn verify_task_receipt(
+    receipt: &SignedTaskReceipt,
+    enclave_attr: EnclaveAttr,
+) -> Result<TaskReceipt> {
+    verify_ra_signature(
+        enclave_attr,
+        &receipt.certificate,
+        receipt.receipt.as_bytes(),
+        &receipt.signature,
+    )?;
+    let task_receipt = receipt.task_receipt()?;
+    Ok(task_receipt)
 }
@@ -21,4 +21,11 @@ extern crate sgx_tstd as std;
 
 mod proto;
 pub use proto::*;
-pub use tms_common_proto::{CollaboratorStatus, FunctionType, TaskStatus};
+pub use tms_common_proto::{
+    ArgumentSpec, ArgumentType, ArgumentsFile, CollaboratorStatus, DynamicOutput, Executor,
+    ExecutorCapability, ExecutorInfo, FileFormat, FunctionInfo, FunctionType, Labels, LogStream,
+    OutputKey, PartialOutput, PortBindings, PortEdge, PortEdges, PortSpec, ReceiptFile,
+    ResolvedCollection, ResourceLimits, ResultFormat, SignedTaskReceipt, TaskArguments,
+    TaskAttempt, TaskError, TaskLogLine, TaskProgress, TaskReceipt, TaskResultRecord,
+    TaskRetryPolicy, TaskSecret, TaskStatus, ANY_DATA_TYPE, OUTPUT_KEK_LEN,
+};
@@ -18,8 +18,19 @@
 #[cfg(feature = "mesalock_sgx")]
 use std::prelude::v1::*;
 
-use crate::{CollaboratorStatus, FunctionType, TaskStatus};
+use crate::{
+    ArgumentSpec, ArgumentsFile, CollaboratorStatus, DynamicOutput, Executor, ExecutorCapability,
+    ExecutorInfo, FileFormat, FunctionInfo, FunctionType, Labels, OutputKey, PortBindings,
+    PortEdge, PortEdges, PortSpec, ResolvedCollection, ResourceLimits, ResultFormat,
+    SignedTaskReceipt, TaskArguments, TaskAttempt, TaskLogLine, TaskProgress, TaskRetryPolicy,
+    TaskSecret, TaskStatus,
+};
+pub use mesatee_core::config::SignedEnclaveInfo;
+use mesatee_core::health::{EnclaveIdentity, ServiceHealth, VersionInfo};
+use mesatee_core::{Error, FieldError};
 use serde_derive::*;
+use std::collections::BTreeMap;
+use std::fmt;
 use std::net::IpAddr;
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -27,8 +38,37 @@ use std::net::IpAddr;
 pub enum TaskRequest {
     Get(GetTaskRequest),
     Create(CreateTaskRequest),
+    CreateTasks(CreateTasksRequest),
+    ValidateTask(ValidateTaskRequest),
     Update(UpdateTaskRequest),
     List(ListTaskRequest),
+    Cancel(CancelTaskRequest),
+    Delete(DeleteTaskRequest),
+    WaitForTaskStatus(WaitForTaskStatusRequest),
+    RegisterFunction(RegisterFunctionRequest),
+    UpdateFunction(UpdateFunctionRequest),
+    GetFunction(GetFunctionRequest),
+    GetFunctionVersions(GetFunctionVersionsRequest),
+    ListFunctions(ListFunctionsRequest),
+    ShareFunction(ShareFunctionRequest),
+    UnshareFunction(UnshareFunctionRequest),
+    HealthCheck(HealthCheckRequest),
+    GetEnclaveMeasurement(GetEnclaveMeasurementRequest),
+    GetVersionInfo(GetVersionInfoRequest),
+    QueryAuditLog(QueryAuditLogRequest),
+    RevokeUser(RevokeUserRequest),
+    UnrevokeUser(UnrevokeUserRequest),
+    QueryTasks(QueryTasksRequest),
+    GetTaskReceipt(GetTaskReceiptRequest),
+    ListExecutors(ListExecutorsRequest),
+    PutSecret(PutSecretRequest),
+    DeleteSecret(DeleteSecretRequest),
+    ListSecrets(ListSecretsRequest),
+    RerunTask(RerunTaskRequest),
+    InvokeFunction(InvokeFunctionRequest),
+    GetTaskStatuses(GetTaskStatusesRequest),
+    ReloadEnclaveInfo(ReloadEnclaveInfoRequest),
+    StreamTaskLogs(StreamTaskLogsRequest),
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -36,8 +76,37 @@ pub enum TaskRequest {
 pub enum TaskResponse {
     Get(GetTaskResponse),
     Create(CreateTaskResponse),
+    CreateTasks(CreateTasksResponse),
+    ValidateTask(ValidateTaskResponse),
     Update(UpdateTaskResponse),
     List(ListTaskResponse),
+    Cancel(CancelTaskResponse),
+    Delete(DeleteTaskResponse),
+    WaitForTaskStatus(WaitForTaskStatusResponse),
+    RegisterFunction(RegisterFunctionResponse),
+    UpdateFunction(UpdateFunctionResponse),
+    GetFunction(GetFunctionResponse),
+    GetFunctionVersions(GetFunctionVersionsResponse),
+    ListFunctions(ListFunctionsResponse),
+    ShareFunction(ShareFunctionResponse),
+    UnshareFunction(UnshareFunctionResponse),
+    HealthCheck(HealthCheckResponse),
+    GetEnclaveMeasurement(GetEnclaveMeasurementResponse),
+    GetVersionInfo(GetVersionInfoResponse),
+    QueryAuditLog(QueryAuditLogResponse),
+    RevokeUser(RevokeUserResponse),
+    UnrevokeUser(UnrevokeUserResponse),
+    QueryTasks(QueryTasksResponse),
+    GetTaskReceipt(GetTaskReceiptResponse),
+    ListExecutors(ListExecutorsResponse),
+    PutSecret(PutSecretResponse),
+    DeleteSecret(DeleteSecretResponse),
+    ListSecrets(ListSecretsResponse),
+    RerunTask(CreateTaskResponse),
+    InvokeFunction(InvokeFunctionResponse),
+    GetTaskStatuses(GetTaskStatusesResponse),
+    ReloadEnclaveInfo(ReloadEnclaveInfoResponse),
+    StreamTaskLogs(StreamTaskLogsResponse),
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -47,24 +116,86 @@ pub struct GetTaskRequest {
     pub user_token: String,
 }
 
+// Requests a page of at most `limit` tasks, starting after `cursor`. The first
+// page is requested with an empty cursor, and the following pages with the
+// `next_token` of the previous page. The page size is capped by the server.
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct ListTaskRequest {
     pub user_id: String,
     pub user_token: String,
+    #[serde(default)]
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub cursor: String,
+    // Only tasks with all these labels are listed.
+    #[serde(default)]
+    pub labels: Labels,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct TaskInfo {
     pub user_id: String,
     pub function_name: String,
+    pub function_id: Option<String>,
     pub function_type: FunctionType,
     pub status: TaskStatus,
+    pub status_version: u64,
     pub ip: IpAddr,
     pub port: u16,
     pub task_token: String,
     pub collaborator_list: Vec<CollaboratorStatus>,
     pub task_result_file_id: Option<String>,
     pub user_private_result_file_id: Vec<String>,
+    pub resource_limits: ResourceLimits,
+    pub dependencies: Vec<String>,
+    pub cancel_reason: Option<String>,
+    #[serde(default)]
+    pub input_collections: Vec<ResolvedCollection>,
+    #[serde(default)]
+    pub arguments: TaskArguments,
+    // File the arguments of the task are read from, pinned to its digest.
+    #[serde(default)]
+    pub arguments_file: Option<ArgumentsFile>,
+    // Seconds since the epoch after which the task and its results may be
+    // deleted. Not set until the task has ended.
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+    // Id of the key the output files of the task creator are encrypted under.
+    #[serde(default)]
+    pub output_key_id: Option<String>,
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default)]
+    pub input_ports: PortBindings,
+    #[serde(default)]
+    pub input_edges: PortEdges,
+    #[serde(default)]
+    pub output_ports: PortBindings,
+    // Files produced on the dynamic output ports, for the task creator only.
+    #[serde(default)]
+    pub dynamic_outputs: BTreeMap<String, Vec<DynamicOutput>>,
+    #[serde(default)]
+    pub progress: Vec<TaskProgress>,
+    #[serde(default)]
+    pub not_before: Option<u64>,
+    #[serde(default)]
+    pub retry_policy: Option<TaskRetryPolicy>,
+    #[serde(default)]
+    pub attempts: Vec<TaskAttempt>,
+    #[serde(default)]
+    pub labels: Labels,
+    // Secrets of the task, never with their values.
+    #[serde(default)]
+    pub secrets: Vec<TaskSecret>,
+    // Declared formats of the output files of the user, by file ID.
+    #[serde(default)]
+    pub output_formats: BTreeMap<String, FileFormat>,
+    #[serde(default)]
+    pub result_format: ResultFormat,
+    // File of the serialized result, for the task creator only. Its content
+    // type is in `output_formats`.
+    #[serde(default)]
+    pub result_file_id: Option<String>,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -72,6 +203,25 @@ pub struct GetTaskResponse {
     pub task_info: TaskInfo,
 }
 
+// A named set of input files, given by a pattern on the names of the files
+// accessible to the user. `*` matches any sequence of characters and `?` any
+// single character, so a prefix `data/` is given as `data/*`. The matching
+// files are resolved when the task is created and do not change afterwards.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct InputCollection {
+    pub name: String,
+    pub pattern: String,
+}
+
+impl InputCollection {
+    pub fn new(name: &str, pattern: &str) -> InputCollection {
+        InputCollection {
+            name: name.to_owned(),
+            pattern: pattern.to_owned(),
+        }
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct CreateTaskRequest {
     pub function_name: String,
@@ -79,79 +229,2149 @@ pub struct CreateTaskRequest {
     pub files: Vec<String>,
     pub user_id: String,
     pub user_token: String,
+    // The cluster default is used if not specified.
+    #[serde(default)]
+    pub resource_limits: Option<ResourceLimits>,
+    // Versioned ID of a registered function to run, i.e. `<id>@v<version>`.
+    // The task is pinned to that version.
+    #[serde(default)]
+    pub function_id: Option<String>,
+    // IDs of tasks whose results are inputs of this task. The task is not
+    // ready until all of them have finished.
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+    // Resolved into input files in addition to `files`. Each collection must
+    // match at least one file, unless the function marks it as optional.
+    #[serde(default)]
+    pub input_collections: Vec<InputCollection>,
+    // Checked against the argument schema of the function, if it has one.
+    #[serde(default)]
+    pub arguments: TaskArguments,
+    // Input file of the task creator holding a JSON object of arguments,
+    // which the executor reads when the task runs. Inline arguments replace
+    // those of the file with the same name. Not supported by functions with
+    // an argument schema.
+    #[serde(default)]
+    pub arguments_file: Option<String>,
+    // Seconds the task and its results are kept after the task ends. The
+    // configured retention is used if not specified.
+    #[serde(default)]
+    pub retention_secs: Option<u64>,
+    // If set, the output files of the task creator are encrypted under this
+    // key instead of a key managed by the platform. Tasks with an incomplete
+    // key are rejected.
+    #[serde(default)]
+    pub output_key: Option<OutputKey>,
+    // Ready tasks with a higher priority run first. The configured default
+    // priority is used if not specified.
+    #[serde(default)]
+    pub priority: Option<i32>,
+    // If the user created a task with the same key within the configured
+    // window, that task is returned and no task is created.
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+    // If set, the task ID is derived from the content of the task rather than
+    // random, and the task of the same user with identical content, if any, is
+    // returned instead of creating another. The content hashed is documented
+    // in `docs/api.md`.
+    #[serde(default)]
+    pub content_addressed: bool,
+    // Files bound to the named input ports of the function, by port name.
+    // Every required port must be bound, and only ports of the function.
+    #[serde(default)]
+    pub input_ports: PortBindings,
+    // Input ports bound to the output ports of dependencies, which must also
+    // be in `dependencies`. The ports must carry compatible data types, and
+    // the file a dependency produces on its port is bound once it finishes.
+    #[serde(default)]
+    pub input_edges: PortEdges,
+    // Seconds since the epoch before which the task is not handed out to
+    // executors. Until then a task otherwise ready is `Scheduled`.
+    #[serde(default)]
+    pub not_before: Option<u64>,
+    // Failures the executor reports as transient are retried under this
+    // policy. Tasks created without one run once.
+    #[serde(default)]
+    pub retry_policy: Option<TaskRetryPolicy>,
+    #[serde(default)]
+    pub labels: Labels,
+    // Given to the executor as environment variables. Secrets of a secret
+    // store may only be referenced by their owner and the users they are
+    // shared with.
+    #[serde(default)]
+    pub secrets: Vec<TaskSecret>,
+    // In `Json` and `Cbor`, the result is also saved in a file of the task
+    // creator. `Text`, which saves no file, is used if not specified.
+    #[serde(default)]
+    pub result_format: ResultFormat,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct CreateTaskResponse {
+    pub task_id: String,
+    pub task_token: String,
+    pub ip: IpAddr,
+    pub port: u16,
+}
+
+// A task of a `CreateTasksRequest`, with the fields of `CreateTaskRequest`.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct TaskSpec {
+    pub function_name: String,
+    pub collaborator_list: Vec<String>,
+    pub files: Vec<String>,
+    #[serde(default)]
+    pub resource_limits: Option<ResourceLimits>,
+    #[serde(default)]
+    pub function_id: Option<String>,
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+    #[serde(default)]
+    pub input_collections: Vec<InputCollection>,
+    #[serde(default)]
+    pub arguments: TaskArguments,
+    #[serde(default)]
+    pub arguments_file: Option<String>,
+    #[serde(default)]
+    pub retention_secs: Option<u64>,
+    #[serde(default)]
+    pub output_key: Option<OutputKey>,
+    #[serde(default)]
+    pub priority: Option<i32>,
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+    #[serde(default)]
+    pub content_addressed: bool,
+    #[serde(default)]
+    pub input_ports: PortBindings,
+    #[serde(default)]
+    pub input_edges: PortEdges,
+    #[serde(default)]
+    pub not_before: Option<u64>,
+    #[serde(default)]
+    pub retry_policy: Option<TaskRetryPolicy>,
+    #[serde(default)]
+    pub labels: Labels,
+    #[serde(default)]
+    pub secrets: Vec<TaskSecret>,
+    #[serde(default)]
+    pub result_format: ResultFormat,
+}
+
+impl TaskSpec {
+    pub fn new(function_name: &str, collaborator_list: &[&str], files: &[&str]) -> TaskSpec {
+        TaskSpec {
+            function_name: function_name.to_owned(),
+            collaborator_list: collaborator_list.iter().map(|s| (*s).to_string()).collect(),
+            files: files.iter().map(|s| (*s).to_string()).collect(),
+            resource_limits: None,
+            function_id: None,
+            dependencies: Vec::new(),
+            input_collections: Vec::new(),
+            arguments: TaskArguments::new(),
+            arguments_file: None,
+            retention_secs: None,
+            output_key: None,
+            priority: None,
+            idempotency_key: None,
+            content_addressed: false,
+            input_ports: PortBindings::new(),
+            input_edges: PortEdges::new(),
+            not_before: None,
+            retry_policy: None,
+            labels: Labels::new(),
+            secrets: Vec::new(),
+            result_format: ResultFormat::default(),
+        }
+    }
+}
+
+// Tasks are validated and created one by one. A task failing validation does
+// not affect the other tasks of the batch.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct CreateTasksRequest {
+    pub tasks: Vec<TaskSpec>,
+    pub user_id: String,
+    pub user_token: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub enum CreateTaskResult {
+    Created(CreateTaskResponse),
+    Failed(String),
+}
+
+// One result per task, in the order of the request.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct CreateTasksResponse {
+    pub results: Vec<CreateTaskResult>,
+}
+
+// Runs the validation of creating the task without creating it.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ValidateTaskRequest {
+    pub task: TaskSpec,
+    pub user_id: String,
+    pub user_token: String,
+}
+
+// `error` is the reason the task would be rejected if it is not valid, and
+// `field_errors` the problems with its fields, if the fields are the reason.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ValidateTaskResponse {
+    pub valid: bool,
+    pub error: Option<String>,
+    #[serde(default)]
+    pub field_errors: Vec<FieldError>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct UpdateTaskRequest {
+    pub task_id: String,
+    pub files: Vec<String>,
+    pub user_id: String,
+    pub user_token: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct UpdateTaskResponse {
+    pub success: bool,
+    pub status: TaskStatus,
+    pub ip: IpAddr,
+    pub port: u16,
+    pub task_token: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ListTaskResponse {
+    pub list: Vec<String>,
+    // Empty after the last page.
+    #[serde(default)]
+    pub next_token: String,
+}
+
+// Filters of `QueryTasks`, all optional. A function is either a registered
+// function ID, versioned or not, or the name of a built-in function. The
+// time range applies to the creation time of the tasks, in seconds since the
+// epoch, both bounds included.
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct TaskQuery {
+    #[serde(default)]
+    pub function_id: Option<String>,
+    #[serde(default)]
+    pub status: Option<TaskStatus>,
+    #[serde(default)]
+    pub owner: Option<String>,
+    #[serde(default)]
+    pub since: Option<u64>,
+    #[serde(default)]
+    pub until: Option<u64>,
+}
+
+// Users with the admin role may query all tasks, other users only the
+// tasks they created. Tasks are returned in ascending order of creation.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct QueryTasksRequest {
+    pub query: TaskQuery,
+    pub user_id: String,
+    pub user_token: String,
+    #[serde(default)]
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub cursor: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct TaskSummary {
+    pub task_id: String,
+    pub user_id: String,
+    pub function_name: String,
+    pub function_id: Option<String>,
+    pub status: TaskStatus,
+    pub created_at: u64,
+    pub finished_at: Option<u64>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct QueryTasksResponse {
+    pub tasks: Vec<TaskSummary>,
+    // Empty after the last page.
+    pub next_token: String,
+}
+
+// Requests the status of each of `task_ids`, at most the server page size of
+// them. Tasks which do not exist cannot be told from tasks the user may not
+// get, both are reported as not accessible.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct GetTaskStatusesRequest {
+    pub task_ids: Vec<String>,
+    pub user_id: String,
+    pub user_token: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct TaskStatusSummary {
+    pub status: TaskStatus,
+    pub status_version: u64,
+    pub created_at: u64,
+    pub finished_at: Option<u64>,
+    // Why the task was cancelled, or the error of its last failed attempt.
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub enum TaskStatusResult {
+    Status(TaskStatusSummary),
+    NotAccessible,
+}
+
+// One result per task, in the order of the request.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct GetTaskStatusesResponse {
+    pub results: Vec<TaskStatusResult>,
+}
+
+// Only the task creator may cancel a task, and only before it has finished
+// or failed.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct CancelTaskRequest {
+    pub task_id: String,
+    pub reason: String,
+    pub user_id: String,
+    pub user_token: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct CancelTaskResponse {
+    pub status: TaskStatus,
+}
+
+// Deletes a task and its results before its retention period ends. Only the
+// task creator may delete a task, and only once it has ended. Results which
+// are inputs of tasks that have not ended yet are kept.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct DeleteTaskRequest {
+    pub task_id: String,
+    pub user_id: String,
+    pub user_token: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct DeleteTaskResponse {}
+
+// Blocks until the status version of the task differs from `current_version`,
+// the task reaches a final status, or `timeout_secs` elapses. The timeout is
+// capped by the server.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct WaitForTaskStatusRequest {
+    pub task_id: String,
+    pub current_version: u64,
+    pub timeout_secs: u64,
+    pub user_id: String,
+    pub user_token: String,
+}
+
+// `changed` is false if the wait timed out.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct WaitForTaskStatusResponse {
+    pub changed: bool,
+    pub status: TaskStatus,
+    pub status_version: u64,
+}
+
+// Tails the log of a task from its line `from`, the first line being 0.
+// Blocks until the task has lines from there, the task ends, or `timeout_secs`
+// elapses. The timeout is capped by the server.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct StreamTaskLogsRequest {
+    pub task_id: String,
+    #[serde(default)]
+    pub from: u64,
+    #[serde(default)]
+    pub timeout_secs: u64,
+    pub user_id: String,
+    pub user_token: String,
+}
+
+// `next` is the `from` of the next request. `truncated` is true once lines of
+// the task were dropped for the size of its log, and `complete` once the task
+// has ended and all its lines were returned.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct StreamTaskLogsResponse {
+    pub lines: Vec<TaskLogLine>,
+    pub next: u64,
+    pub truncated: bool,
+    pub complete: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct RegisterFunctionRequest {
+    pub function_name: String,
+    pub description: String,
+    pub payload: Option<String>,
+    pub user_id: String,
+    pub user_token: String,
+    // Users besides the owner allowed to use the function, see
+    // `ShareFunctionRequest`.
+    #[serde(default)]
+    pub collaborators: Vec<String>,
+    // Name of the executor, one of those `ListExecutorsRequest` lists.
+    #[serde(default = "default_executor")]
+    pub executor: String,
+    // Names of input collections tasks of the function may leave empty. New
+    // versions of the function keep the optional inputs of the first one.
+    #[serde(default)]
+    pub optional_inputs: Vec<String>,
+    // Arguments accepted by the function, kept by new versions as well.
+    #[serde(default)]
+    pub argument_schema: Option<Vec<ArgumentSpec>>,
+    // Where the payload is downloaded from instead, `payload` is then unset.
+    #[serde(default)]
+    pub remote_payload: Option<RemotePayload>,
+    // Named inputs and outputs of the function, kept by new versions as well.
+    #[serde(default)]
+    pub input_ports: Vec<PortSpec>,
+    #[serde(default)]
+    pub output_ports: Vec<PortSpec>,
+    // Kept by new versions as well.
+    #[serde(default)]
+    pub labels: Labels,
+    // Capabilities the function runs with, kept by new versions as well.
+    #[serde(default)]
+    pub capabilities: Vec<ExecutorCapability>,
+}
+
+fn default_executor() -> String {
+    Executor::default().name().to_owned()
+}
+
+// A function payload downloaded by TMS through the untrusted host. The payload
+// is accepted only if its `digest_algorithm` digest, "sha256" or "sha384",
+// matches the hex `digest` pinned by the client. A WASM module is downloaded
+// as is, rather than base64 encoded.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct RemotePayload {
+    pub url: String,
+    pub digest_algorithm: String,
+    pub digest: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct RegisterFunctionResponse {
+    pub function_id: String,
+}
+
+// Creates a new version of a function, earlier versions are kept as they are.
+// `function_id` may name any version of the function.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct UpdateFunctionRequest {
+    pub function_id: String,
+    pub function_name: String,
+    pub description: String,
+    pub payload: Option<String>,
+    pub user_id: String,
+    pub user_token: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct UpdateFunctionResponse {
+    pub function_id: String,
+}
+
+// Without a version in `function_id` the latest version is returned.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct GetFunctionRequest {
+    pub function_id: String,
+    pub user_id: String,
+    pub user_token: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct GetFunctionResponse {
+    pub function_info: FunctionInfo,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct GetFunctionVersionsRequest {
+    pub function_id: String,
+    pub user_id: String,
+    pub user_token: String,
+}
+
+// All versions of a function, oldest first.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct GetFunctionVersionsResponse {
+    pub versions: Vec<FunctionInfo>,
+}
+
+// Lists the latest version of the functions registered by the user, paged
+// like `ListTaskRequest`.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ListFunctionsRequest {
+    pub limit: Option<usize>,
+    pub cursor: String,
+    pub user_id: String,
+    pub user_token: String,
+    // Only functions with all these labels are listed.
+    #[serde(default)]
+    pub labels: Labels,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ListFunctionsResponse {
+    pub functions: Vec<FunctionInfo>,
+    pub next_token: String,
+}
+
+// Allows `user_ids` to get the function and create tasks of it. Sharing
+// applies to all versions of the function, and only the owner may change who
+// the function is shared with.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ShareFunctionRequest {
+    pub function_id: String,
+    pub user_ids: Vec<String>,
+    pub user_id: String,
+    pub user_token: String,
+}
+
+// Users the function is shared with after the request.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ShareFunctionResponse {
+    pub collaborators: Vec<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct UnshareFunctionRequest {
+    pub function_id: String,
+    pub user_ids: Vec<String>,
+    pub user_id: String,
+    pub user_token: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct UnshareFunctionResponse {
+    pub collaborators: Vec<String>,
+}
+
+// Health checks need no user credentials, so that probes can send them.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct HealthCheckRequest {}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct HealthCheckResponse {
+    pub health: ServiceHealth,
+}
+
+// Like the health check, no credentials are needed. The measurement is also
+// attested by every client during the TLS handshake.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct GetEnclaveMeasurementRequest {}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct GetEnclaveMeasurementResponse {
+    pub identity: EnclaveIdentity,
+}
+
+// No credentials are needed either. The version is also recorded next to the
+// measurements in the signed enclave info.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct GetVersionInfoRequest {}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct GetVersionInfoResponse {
+    pub version: VersionInfo,
+}
+
+// What an entry of the audit log records: the operation, the tasks or
+// functions it applied to, the users whose access it changed, and the error
+// of a failed operation.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct AuditEvent {
+    pub operation: String,
+    pub targets: Vec<String>,
+    #[serde(default)]
+    pub users: Vec<String>,
+    pub error: Option<String>,
+}
+
+// Entry of the audit log, recorded by TDFS. `event` is an `AuditEvent` in
+// JSON. `hash` is the hex SHA-256 of `prev_hash`, then `sequence` and
+// `timestamp` as 8 byte big-endian integers, then the length of `user_id` as
+// an 8 byte big-endian integer, `user_id` and `event`. The first entry has a
+// `prev_hash` of 64 zeros.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct AuditLogEntry {
+    pub sequence: u64,
+    // Seconds since the epoch.
+    pub timestamp: u64,
+    // Who requested the operation.
+    pub user_id: String,
+    pub event: String,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl AuditLogEntry {
+    pub fn parse_event(&self) -> mesatee_core::Result<AuditEvent> {
+        Ok(serde_json::from_str(&self.event)?)
+    }
+}
+
+// Entries with `since <= timestamp < until` of operations requested by
+// `user_id`, oldest first. Entries after `after_sequence` are returned, at
+// most `limit` of them. Filters not set match all entries.
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct AuditLogQuery {
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+    pub user_id: Option<String>,
+    pub after_sequence: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+// Requires the admin role by default.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct QueryAuditLogRequest {
+    pub query: AuditLogQuery,
+    pub user_id: String,
+    pub user_token: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct QueryAuditLogResponse {
+    pub entries: Vec<AuditLogEntry>,
+}
+
+// Requires the admin role by default. Requests of a revoked user are
+// rejected until the revocation is lifted. With `cancel_tasks`, the tasks the
+// user created which have not ended are cancelled too.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct RevokeUserRequest {
+    pub revoked_user_id: String,
+    pub cancel_tasks: bool,
+    pub user_id: String,
+    pub user_token: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct RevokeUserResponse {
+    pub cancelled_tasks: usize,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct UnrevokeUserRequest {
+    pub revoked_user_id: String,
+    pub user_id: String,
+    pub user_token: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct UnrevokeUserResponse {}
+
+// Requires the admin role by default. Replaces the enclave info TMS verifies
+// its peers against with `fragments`, so that services added to the cluster
+// are accepted without restarting TMS. The enclave info is left unchanged
+// unless every fragment is signed by enough auditors and it still has every
+// service TMS connects to. TMS then sends the fragments on to TDFS and KMS,
+// and execution services pick them up when they next register their
+// executors.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ReloadEnclaveInfoRequest {
+    pub fragments: Vec<SignedEnclaveInfo>,
+    pub user_id: String,
+    pub user_token: String,
+}
+
+// Services of the reloaded enclave info, sorted by name, and whether each
+// peer TMS sent the fragments to reloaded them too.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ReloadEnclaveInfoResponse {
+    pub services: Vec<String>,
+    pub peers: Vec<PeerReload>,
 }
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
-pub struct CreateTaskResponse {
-    pub task_id: String,
-    pub task_token: String,
-    pub ip: IpAddr,
-    pub port: u16,
-}
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PeerReload {
+    pub service: String,
+    pub error: Option<String>,
+}
+
+impl PeerReload {
+    pub fn new(service: &str, error: Option<Error>) -> PeerReload {
+        PeerReload {
+            service: service.to_owned(),
+            error: error.map(|e| format!("{}", e)),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct GetTaskReceiptRequest {
+    pub task_id: String,
+    pub user_id: String,
+    pub user_token: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct GetTaskReceiptResponse {
+    pub receipt: SignedTaskReceipt,
+}
+
+// Lists the executors functions may be registered for, as the execution
+// services registered them.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ListExecutorsRequest {
+    pub user_id: String,
+    pub user_token: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ListExecutorsResponse {
+    pub executors: Vec<ExecutorInfo>,
+}
+
+// Stores secret `name` of the user, replacing the secret of that name if any.
+// Tasks of the user and of the `readers` may reference the secret. The value
+// is never returned.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PutSecretRequest {
+    pub name: String,
+    pub value: String,
+    #[serde(default)]
+    pub readers: Vec<String>,
+    pub user_id: String,
+    pub user_token: String,
+}
+
+// Requests are traced with their Debug output, which must not include the
+// value.
+impl fmt::Debug for PutSecretRequest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PutSecretRequest")
+            .field("name", &self.name)
+            .field("value", &"<redacted>")
+            .field("readers", &self.readers)
+            .field("user_id", &self.user_id)
+            .field("user_token", &self.user_token)
+            .finish()
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PutSecretResponse {}
+
+// Tasks created before the secret is deleted fail when they run.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct DeleteSecretRequest {
+    pub name: String,
+    pub user_id: String,
+    pub user_token: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct DeleteSecretResponse {}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ListSecretsRequest {
+    pub user_id: String,
+    pub user_token: String,
+}
+
+// A secret of the secret store, without its value.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct SecretInfo {
+    pub name: String,
+    pub readers: Vec<String>,
+    // Seconds since the epoch when the secret was stored.
+    pub updated_at: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ListSecretsResponse {
+    pub secrets: Vec<SecretInfo>,
+}
+
+// Creates a new task with the function, inputs, arguments and resource
+// limits of task `task_id`, which the user must be able to get. The new task
+// starts afresh, it shares no state with the task it was cloned from.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct RerunTaskRequest {
+    pub task_id: String,
+    // Replace the arguments of the task if set, checked against the argument
+    // schema of the function like the arguments of a new task.
+    #[serde(default)]
+    pub arguments: Option<TaskArguments>,
+    pub user_id: String,
+    pub user_token: String,
+}
+
+// Runs function `function_id` once on inline inputs and returns its outputs
+// inline, so that a function can be tried out while it is developed. The
+// inputs and outputs are bounded by `function_invocation` of the runtime
+// config, and so are the resource limits of the task running the function.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct InvokeFunctionRequest {
+    pub function_id: String,
+    // Contents of the input files of the function, in order.
+    #[serde(default)]
+    pub inline_inputs: Vec<Vec<u8>>,
+    #[serde(default)]
+    pub arguments: TaskArguments,
+    pub user_id: String,
+    pub user_token: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct InvokeFunctionResponse {
+    // The finished task which ran the function, kept for a short while.
+    pub task_id: String,
+    // Result returned by the function.
+    pub result: String,
+    // Contents of the files the function saved for the user, in order.
+    pub outputs: Vec<Vec<u8>>,
+}
+
+impl TaskRequest {
+    // User ID and token sent with the request, if it is sent on behalf of a
+    // user.
+    pub fn credentials(&self) -> Option<(&str, &str)> {
+        let (user_id, user_token) = match self {
+            TaskRequest::Get(req) => (&req.user_id, &req.user_token),
+            TaskRequest::Create(req) => (&req.user_id, &req.user_token),
+            TaskRequest::CreateTasks(req) => (&req.user_id, &req.user_token),
+            TaskRequest::ValidateTask(req) => (&req.user_id, &req.user_token),
+            TaskRequest::Update(req) => (&req.user_id, &req.user_token),
+            TaskRequest::List(req) => (&req.user_id, &req.user_token),
+            TaskRequest::Cancel(req) => (&req.user_id, &req.user_token),
+            TaskRequest::Delete(req) => (&req.user_id, &req.user_token),
+            TaskRequest::WaitForTaskStatus(req) => (&req.user_id, &req.user_token),
+            TaskRequest::RegisterFunction(req) => (&req.user_id, &req.user_token),
+            TaskRequest::UpdateFunction(req) => (&req.user_id, &req.user_token),
+            TaskRequest::GetFunction(req) => (&req.user_id, &req.user_token),
+            TaskRequest::GetFunctionVersions(req) => (&req.user_id, &req.user_token),
+            TaskRequest::ListFunctions(req) => (&req.user_id, &req.user_token),
+            TaskRequest::ShareFunction(req) => (&req.user_id, &req.user_token),
+            TaskRequest::UnshareFunction(req) => (&req.user_id, &req.user_token),
+            TaskRequest::QueryAuditLog(req) => (&req.user_id, &req.user_token),
+            TaskRequest::RevokeUser(req) => (&req.user_id, &req.user_token),
+            TaskRequest::UnrevokeUser(req) => (&req.user_id, &req.user_token),
+            TaskRequest::QueryTasks(req) => (&req.user_id, &req.user_token),
+            TaskRequest::GetTaskReceipt(req) => (&req.user_id, &req.user_token),
+            TaskRequest::ListExecutors(req) => (&req.user_id, &req.user_token),
+            TaskRequest::PutSecret(req) => (&req.user_id, &req.user_token),
+            TaskRequest::DeleteSecret(req) => (&req.user_id, &req.user_token),
+            TaskRequest::ListSecrets(req) => (&req.user_id, &req.user_token),
+            TaskRequest::RerunTask(req) => (&req.user_id, &req.user_token),
+            TaskRequest::InvokeFunction(req) => (&req.user_id, &req.user_token),
+            TaskRequest::GetTaskStatuses(req) => (&req.user_id, &req.user_token),
+            TaskRequest::ReloadEnclaveInfo(req) => (&req.user_id, &req.user_token),
+            TaskRequest::StreamTaskLogs(req) => (&req.user_id, &req.user_token),
+            TaskRequest::HealthCheck(_)
+            | TaskRequest::GetEnclaveMeasurement(_)
+            | TaskRequest::GetVersionInfo(_) => return None,
+        };
+        Some((user_id.as_str(), user_token.as_str()))
+    }
+
+    pub fn new_get_task(task_id: &str, user_id: &str, user_token: &str) -> TaskRequest {
+        TaskRequest::Get(GetTaskRequest {
+            task_id: task_id.to_owned(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+        })
+    }
+
+    pub fn new_create_task(
+        function_name: &str,
+        collaborator_list: &[&str],
+        files: &[&str],
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::Create(CreateTaskRequest {
+            function_name: function_name.to_owned(),
+            collaborator_list: collaborator_list.iter().map(|s| (*s).to_string()).collect(),
+            files: files.iter().map(|s| (*s).to_string()).collect(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            resource_limits: None,
+            function_id: None,
+            dependencies: Vec::new(),
+            input_collections: Vec::new(),
+            arguments: TaskArguments::new(),
+            arguments_file: None,
+            retention_secs: None,
+            output_key: None,
+            priority: None,
+            idempotency_key: None,
+            content_addressed: false,
+            input_ports: PortBindings::new(),
+            input_edges: PortEdges::new(),
+            not_before: None,
+            retry_policy: None,
+            labels: Labels::new(),
+            secrets: Vec::new(),
+            result_format: ResultFormat::default(),
+        })
+    }
+
+    pub fn new_create_task_for_function(
+        function_id: &str,
+        collaborator_list: &[&str],
+        files: &[&str],
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::Create(CreateTaskRequest {
+            function_name: String::new(),
+            collaborator_list: collaborator_list.iter().map(|s| (*s).to_string()).collect(),
+            files: files.iter().map(|s| (*s).to_string()).collect(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            resource_limits: None,
+            function_id: Some(function_id.to_owned()),
+            dependencies: Vec::new(),
+            input_collections: Vec::new(),
+            arguments: TaskArguments::new(),
+            arguments_file: None,
+            retention_secs: None,
+            output_key: None,
+            priority: None,
+            idempotency_key: None,
+            content_addressed: false,
+            input_ports: PortBindings::new(),
+            input_edges: PortEdges::new(),
+            not_before: None,
+            retry_policy: None,
+            labels: Labels::new(),
+            secrets: Vec::new(),
+            result_format: ResultFormat::default(),
+        })
+    }
+
+    pub fn new_create_task_with_limits(
+        function_name: &str,
+        collaborator_list: &[&str],
+        files: &[&str],
+        resource_limits: ResourceLimits,
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::Create(CreateTaskRequest {
+            function_name: function_name.to_owned(),
+            collaborator_list: collaborator_list.iter().map(|s| (*s).to_string()).collect(),
+            files: files.iter().map(|s| (*s).to_string()).collect(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            resource_limits: Some(resource_limits),
+            function_id: None,
+            dependencies: Vec::new(),
+            input_collections: Vec::new(),
+            arguments: TaskArguments::new(),
+            arguments_file: None,
+            retention_secs: None,
+            output_key: None,
+            priority: None,
+            idempotency_key: None,
+            content_addressed: false,
+            input_ports: PortBindings::new(),
+            input_edges: PortEdges::new(),
+            not_before: None,
+            retry_policy: None,
+            labels: Labels::new(),
+            secrets: Vec::new(),
+            result_format: ResultFormat::default(),
+        })
+    }
+
+    pub fn new_create_task_with_dependencies(
+        function_name: &str,
+        collaborator_list: &[&str],
+        files: &[&str],
+        dependencies: &[&str],
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::Create(CreateTaskRequest {
+            function_name: function_name.to_owned(),
+            collaborator_list: collaborator_list.iter().map(|s| (*s).to_string()).collect(),
+            files: files.iter().map(|s| (*s).to_string()).collect(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            resource_limits: None,
+            function_id: None,
+            dependencies: dependencies.iter().map(|s| (*s).to_string()).collect(),
+            input_collections: Vec::new(),
+            arguments: TaskArguments::new(),
+            arguments_file: None,
+            retention_secs: None,
+            output_key: None,
+            priority: None,
+            idempotency_key: None,
+            content_addressed: false,
+            input_ports: PortBindings::new(),
+            input_edges: PortEdges::new(),
+            not_before: None,
+            retry_policy: None,
+            labels: Labels::new(),
+            secrets: Vec::new(),
+            result_format: ResultFormat::default(),
+        })
+    }
+
+    pub fn new_create_task_with_collections(
+        function_name: &str,
+        collaborator_list: &[&str],
+        files: &[&str],
+        input_collections: &[InputCollection],
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::Create(CreateTaskRequest {
+            function_name: function_name.to_owned(),
+            collaborator_list: collaborator_list.iter().map(|s| (*s).to_string()).collect(),
+            files: files.iter().map(|s| (*s).to_string()).collect(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            resource_limits: None,
+            function_id: None,
+            dependencies: Vec::new(),
+            input_collections: input_collections.to_vec(),
+            arguments: TaskArguments::new(),
+            arguments_file: None,
+            retention_secs: None,
+            output_key: None,
+            priority: None,
+            idempotency_key: None,
+            content_addressed: false,
+            input_ports: PortBindings::new(),
+            input_edges: PortEdges::new(),
+            not_before: None,
+            retry_policy: None,
+            labels: Labels::new(),
+            secrets: Vec::new(),
+            result_format: ResultFormat::default(),
+        })
+    }
+
+    pub fn new_create_task_with_arguments(
+        function_id: &str,
+        files: &[&str],
+        arguments: &TaskArguments,
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::Create(CreateTaskRequest {
+            function_name: String::new(),
+            collaborator_list: Vec::new(),
+            files: files.iter().map(|s| (*s).to_string()).collect(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            resource_limits: None,
+            function_id: Some(function_id.to_owned()),
+            dependencies: Vec::new(),
+            input_collections: Vec::new(),
+            arguments: arguments.clone(),
+            arguments_file: None,
+            retention_secs: None,
+            output_key: None,
+            priority: None,
+            idempotency_key: None,
+            content_addressed: false,
+            input_ports: PortBindings::new(),
+            input_edges: PortEdges::new(),
+            not_before: None,
+            retry_policy: None,
+            labels: Labels::new(),
+            secrets: Vec::new(),
+            result_format: ResultFormat::default(),
+        })
+    }
+
+    pub fn new_create_task_with_retention(
+        function_name: &str,
+        collaborator_list: &[&str],
+        files: &[&str],
+        retention_secs: u64,
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::Create(CreateTaskRequest {
+            function_name: function_name.to_owned(),
+            collaborator_list: collaborator_list.iter().map(|s| (*s).to_string()).collect(),
+            files: files.iter().map(|s| (*s).to_string()).collect(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            resource_limits: None,
+            function_id: None,
+            dependencies: Vec::new(),
+            input_collections: Vec::new(),
+            arguments: TaskArguments::new(),
+            arguments_file: None,
+            retention_secs: Some(retention_secs),
+            output_key: None,
+            priority: None,
+            idempotency_key: None,
+            content_addressed: false,
+            input_ports: PortBindings::new(),
+            input_edges: PortEdges::new(),
+            not_before: None,
+            retry_policy: None,
+            labels: Labels::new(),
+            secrets: Vec::new(),
+            result_format: ResultFormat::default(),
+        })
+    }
+
+    pub fn new_create_task_with_output_key(
+        function_id: &str,
+        files: &[&str],
+        output_key: &OutputKey,
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::Create(CreateTaskRequest {
+            function_name: String::new(),
+            collaborator_list: Vec::new(),
+            files: files.iter().map(|s| (*s).to_string()).collect(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            resource_limits: None,
+            function_id: Some(function_id.to_owned()),
+            dependencies: Vec::new(),
+            input_collections: Vec::new(),
+            arguments: TaskArguments::new(),
+            arguments_file: None,
+            retention_secs: None,
+            output_key: Some(output_key.clone()),
+            priority: None,
+            idempotency_key: None,
+            content_addressed: false,
+            input_ports: PortBindings::new(),
+            input_edges: PortEdges::new(),
+            not_before: None,
+            retry_policy: None,
+            labels: Labels::new(),
+            secrets: Vec::new(),
+            result_format: ResultFormat::default(),
+        })
+    }
+
+    pub fn new_create_task_with_priority(
+        function_name: &str,
+        files: &[&str],
+        priority: i32,
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::Create(CreateTaskRequest {
+            function_name: function_name.to_owned(),
+            collaborator_list: Vec::new(),
+            files: files.iter().map(|s| (*s).to_string()).collect(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            resource_limits: None,
+            function_id: None,
+            dependencies: Vec::new(),
+            input_collections: Vec::new(),
+            arguments: TaskArguments::new(),
+            arguments_file: None,
+            retention_secs: None,
+            output_key: None,
+            priority: Some(priority),
+            idempotency_key: None,
+            content_addressed: false,
+            input_ports: PortBindings::new(),
+            input_edges: PortEdges::new(),
+            not_before: None,
+            retry_policy: None,
+            labels: Labels::new(),
+            secrets: Vec::new(),
+            result_format: ResultFormat::default(),
+        })
+    }
+
+    pub fn new_create_task_with_idempotency_key(
+        function_name: &str,
+        files: &[&str],
+        idempotency_key: &str,
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::Create(CreateTaskRequest {
+            function_name: function_name.to_owned(),
+            collaborator_list: Vec::new(),
+            files: files.iter().map(|s| (*s).to_string()).collect(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            resource_limits: None,
+            function_id: None,
+            dependencies: Vec::new(),
+            input_collections: Vec::new(),
+            arguments: TaskArguments::new(),
+            arguments_file: None,
+            retention_secs: None,
+            output_key: None,
+            priority: None,
+            idempotency_key: Some(idempotency_key.to_owned()),
+            content_addressed: false,
+            input_ports: PortBindings::new(),
+            input_edges: PortEdges::new(),
+            not_before: None,
+            retry_policy: None,
+            labels: Labels::new(),
+            secrets: Vec::new(),
+            result_format: ResultFormat::default(),
+        })
+    }
+
+    pub fn new_create_content_addressed_task(
+        function_name: &str,
+        files: &[&str],
+        arguments: &TaskArguments,
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::Create(CreateTaskRequest {
+            function_name: function_name.to_owned(),
+            collaborator_list: Vec::new(),
+            files: files.iter().map(|s| (*s).to_string()).collect(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            resource_limits: None,
+            function_id: None,
+            dependencies: Vec::new(),
+            input_collections: Vec::new(),
+            arguments: arguments.clone(),
+            arguments_file: None,
+            retention_secs: None,
+            output_key: None,
+            priority: None,
+            idempotency_key: None,
+            content_addressed: true,
+            input_ports: PortBindings::new(),
+            input_edges: PortEdges::new(),
+            not_before: None,
+            retry_policy: None,
+            labels: Labels::new(),
+            secrets: Vec::new(),
+            result_format: ResultFormat::default(),
+        })
+    }
+
+    pub fn new_create_task_with_ports(
+        function_id: &str,
+        input_ports: &[(&str, &str)],
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::Create(CreateTaskRequest {
+            function_name: String::new(),
+            collaborator_list: Vec::new(),
+            files: Vec::new(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            resource_limits: None,
+            function_id: Some(function_id.to_owned()),
+            dependencies: Vec::new(),
+            input_collections: Vec::new(),
+            arguments: TaskArguments::new(),
+            arguments_file: None,
+            retention_secs: None,
+            output_key: None,
+            priority: None,
+            idempotency_key: None,
+            content_addressed: false,
+            input_ports: input_ports
+                .iter()
+                .map(|(port, file_id)| ((*port).to_string(), (*file_id).to_string()))
+                .collect(),
+            input_edges: PortEdges::new(),
+            not_before: None,
+            retry_policy: None,
+            labels: Labels::new(),
+            secrets: Vec::new(),
+            result_format: ResultFormat::default(),
+        })
+    }
+
+    // Each edge binds an input port to an output port of a dependency, given
+    // as `(input port, task ID, output port)`. The tasks of the edges are the
+    // dependencies of the task.
+    pub fn new_create_task_with_edges(
+        function_id: &str,
+        input_ports: &[(&str, &str)],
+        input_edges: &[(&str, &str, &str)],
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        let mut dependencies: Vec<String> = Vec::new();
+        for (_, task_id, _) in input_edges.iter() {
+            if !dependencies.iter().any(|dependency| dependency == task_id) {
+                dependencies.push((*task_id).to_string());
+            }
+        }
+        let mut request =
+            TaskRequest::new_create_task_with_ports(function_id, input_ports, user_id, user_token);
+        if let TaskRequest::Create(ref mut req) = request {
+            req.dependencies = dependencies;
+            req.input_edges = input_edges
+                .iter()
+                .map(|(name, task_id, port)| ((*name).to_string(), PortEdge::new(task_id, port)))
+                .collect();
+        }
+        request
+    }
+
+    pub fn new_create_task_with_not_before(
+        function_name: &str,
+        files: &[&str],
+        not_before: u64,
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::Create(CreateTaskRequest {
+            function_name: function_name.to_owned(),
+            collaborator_list: Vec::new(),
+            files: files.iter().map(|s| (*s).to_string()).collect(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            resource_limits: None,
+            function_id: None,
+            dependencies: Vec::new(),
+            input_collections: Vec::new(),
+            arguments: TaskArguments::new(),
+            arguments_file: None,
+            retention_secs: None,
+            output_key: None,
+            priority: None,
+            idempotency_key: None,
+            content_addressed: false,
+            input_ports: PortBindings::new(),
+            input_edges: PortEdges::new(),
+            not_before: Some(not_before),
+            retry_policy: None,
+            labels: Labels::new(),
+            secrets: Vec::new(),
+            result_format: ResultFormat::default(),
+        })
+    }
+
+    pub fn new_create_task_with_retry_policy(
+        function_name: &str,
+        files: &[&str],
+        retry_policy: TaskRetryPolicy,
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::Create(CreateTaskRequest {
+            function_name: function_name.to_owned(),
+            collaborator_list: Vec::new(),
+            files: files.iter().map(|s| (*s).to_string()).collect(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            resource_limits: None,
+            function_id: None,
+            dependencies: Vec::new(),
+            input_collections: Vec::new(),
+            arguments: TaskArguments::new(),
+            arguments_file: None,
+            retention_secs: None,
+            output_key: None,
+            priority: None,
+            idempotency_key: None,
+            content_addressed: false,
+            input_ports: PortBindings::new(),
+            input_edges: PortEdges::new(),
+            not_before: None,
+            retry_policy: Some(retry_policy),
+            labels: Labels::new(),
+            secrets: Vec::new(),
+            result_format: ResultFormat::default(),
+        })
+    }
+
+    pub fn new_create_task_with_labels(
+        function_name: &str,
+        files: &[&str],
+        labels: &Labels,
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::Create(CreateTaskRequest {
+            function_name: function_name.to_owned(),
+            collaborator_list: Vec::new(),
+            files: files.iter().map(|s| (*s).to_string()).collect(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            resource_limits: None,
+            function_id: None,
+            dependencies: Vec::new(),
+            input_collections: Vec::new(),
+            arguments: TaskArguments::new(),
+            arguments_file: None,
+            retention_secs: None,
+            output_key: None,
+            priority: None,
+            idempotency_key: None,
+            content_addressed: false,
+            input_ports: PortBindings::new(),
+            input_edges: PortEdges::new(),
+            not_before: None,
+            retry_policy: None,
+            labels: labels.clone(),
+            secrets: Vec::new(),
+            result_format: ResultFormat::default(),
+        })
+    }
+
+    pub fn new_create_task_with_secrets(
+        function_name: &str,
+        files: &[&str],
+        secrets: &[TaskSecret],
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::Create(CreateTaskRequest {
+            function_name: function_name.to_owned(),
+            collaborator_list: Vec::new(),
+            files: files.iter().map(|s| (*s).to_string()).collect(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            resource_limits: None,
+            function_id: None,
+            dependencies: Vec::new(),
+            input_collections: Vec::new(),
+            arguments: TaskArguments::new(),
+            arguments_file: None,
+            retention_secs: None,
+            output_key: None,
+            priority: None,
+            idempotency_key: None,
+            content_addressed: false,
+            input_ports: PortBindings::new(),
+            input_edges: PortEdges::new(),
+            not_before: None,
+            retry_policy: None,
+            labels: Labels::new(),
+            secrets: secrets.to_vec(),
+            result_format: ResultFormat::default(),
+        })
+    }
+
+    pub fn new_create_task_with_result_format(
+        function_name: &str,
+        files: &[&str],
+        result_format: ResultFormat,
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::Create(CreateTaskRequest {
+            function_name: function_name.to_owned(),
+            collaborator_list: Vec::new(),
+            files: files.iter().map(|s| (*s).to_string()).collect(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            resource_limits: None,
+            function_id: None,
+            dependencies: Vec::new(),
+            input_collections: Vec::new(),
+            arguments: TaskArguments::new(),
+            arguments_file: None,
+            retention_secs: None,
+            output_key: None,
+            priority: None,
+            idempotency_key: None,
+            content_addressed: false,
+            input_ports: PortBindings::new(),
+            input_edges: PortEdges::new(),
+            not_before: None,
+            retry_policy: None,
+            labels: Labels::new(),
+            secrets: Vec::new(),
+            result_format,
+        })
+    }
+
+    pub fn new_create_task_with_arguments_file(
+        function_name: &str,
+        arguments: &TaskArguments,
+        arguments_file: &str,
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::Create(CreateTaskRequest {
+            function_name: function_name.to_owned(),
+            collaborator_list: Vec::new(),
+            files: Vec::new(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            resource_limits: None,
+            function_id: None,
+            dependencies: Vec::new(),
+            input_collections: Vec::new(),
+            arguments: arguments.clone(),
+            arguments_file: Some(arguments_file.to_owned()),
+            retention_secs: None,
+            output_key: None,
+            priority: None,
+            idempotency_key: None,
+            content_addressed: false,
+            input_ports: PortBindings::new(),
+            input_edges: PortEdges::new(),
+            not_before: None,
+            retry_policy: None,
+            labels: Labels::new(),
+            secrets: Vec::new(),
+            result_format: ResultFormat::default(),
+        })
+    }
+
+    pub fn new_create_tasks(tasks: &[TaskSpec], user_id: &str, user_token: &str) -> TaskRequest {
+        TaskRequest::CreateTasks(CreateTasksRequest {
+            tasks: tasks.to_vec(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+        })
+    }
+
+    pub fn new_validate_task(task: &TaskSpec, user_id: &str, user_token: &str) -> TaskRequest {
+        TaskRequest::ValidateTask(ValidateTaskRequest {
+            task: task.clone(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+        })
+    }
+
+    pub fn new_update_task(
+        task_id: &str,
+        files: &[&str],
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::Update(UpdateTaskRequest {
+            task_id: task_id.to_owned(),
+            files: files.iter().map(|s| (*s).to_string()).collect(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+        })
+    }
+
+    pub fn new_list_task(user_id: &str, user_token: &str) -> TaskRequest {
+        TaskRequest::List(ListTaskRequest {
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            limit: None,
+            cursor: String::new(),
+            labels: Labels::new(),
+        })
+    }
+
+    pub fn new_list_task_page(
+        limit: Option<usize>,
+        cursor: &str,
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::List(ListTaskRequest {
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            limit,
+            cursor: cursor.to_owned(),
+            labels: Labels::new(),
+        })
+    }
+
+    pub fn new_list_task_with_labels(
+        labels: &Labels,
+        limit: Option<usize>,
+        cursor: &str,
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::List(ListTaskRequest {
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            limit,
+            cursor: cursor.to_owned(),
+            labels: labels.clone(),
+        })
+    }
+
+    pub fn new_cancel_task(
+        task_id: &str,
+        reason: &str,
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::Cancel(CancelTaskRequest {
+            task_id: task_id.to_owned(),
+            reason: reason.to_owned(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+        })
+    }
+
+    pub fn new_delete_task(task_id: &str, user_id: &str, user_token: &str) -> TaskRequest {
+        TaskRequest::Delete(DeleteTaskRequest {
+            task_id: task_id.to_owned(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+        })
+    }
+
+    pub fn new_wait_for_task_status(
+        task_id: &str,
+        current_version: u64,
+        timeout_secs: u64,
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::WaitForTaskStatus(WaitForTaskStatusRequest {
+            task_id: task_id.to_owned(),
+            current_version,
+            timeout_secs,
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+        })
+    }
+
+    pub fn new_stream_task_logs(
+        task_id: &str,
+        from: u64,
+        timeout_secs: u64,
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::StreamTaskLogs(StreamTaskLogsRequest {
+            task_id: task_id.to_owned(),
+            from,
+            timeout_secs,
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+        })
+    }
+
+    pub fn new_register_function(
+        function_name: &str,
+        description: &str,
+        payload: Option<&str>,
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::RegisterFunction(RegisterFunctionRequest {
+            function_name: function_name.to_owned(),
+            description: description.to_owned(),
+            payload: payload.map(|s| s.to_owned()),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            collaborators: Vec::new(),
+            executor: Executor::Builtin.name().to_owned(),
+            optional_inputs: Vec::new(),
+            argument_schema: None,
+            remote_payload: None,
+            input_ports: Vec::new(),
+            output_ports: Vec::new(),
+            labels: Labels::new(),
+            capabilities: Vec::new(),
+        })
+    }
+
+    // `executor` is the name of an executor `ListExecutorsRequest` lists.
+    pub fn new_register_function_for_executor(
+        function_name: &str,
+        description: &str,
+        payload: Option<&str>,
+        executor: &str,
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::RegisterFunction(RegisterFunctionRequest {
+            function_name: function_name.to_owned(),
+            description: description.to_owned(),
+            payload: payload.map(|s| s.to_owned()),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            collaborators: Vec::new(),
+            executor: executor.to_owned(),
+            optional_inputs: Vec::new(),
+            argument_schema: None,
+            remote_payload: None,
+            input_ports: Vec::new(),
+            output_ports: Vec::new(),
+            labels: Labels::new(),
+            capabilities: Vec::new(),
+        })
+    }
+
+    // `module` is the base64 encoded WebAssembly module.
+    pub fn new_register_wasm_function(
+        function_name: &str,
+        description: &str,
+        module: &str,
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::RegisterFunction(RegisterFunctionRequest {
+            function_name: function_name.to_owned(),
+            description: description.to_owned(),
+            payload: Some(module.to_owned()),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            collaborators: Vec::new(),
+            executor: Executor::Wasm.name().to_owned(),
+            optional_inputs: Vec::new(),
+            argument_schema: None,
+            remote_payload: None,
+            input_ports: Vec::new(),
+            output_ports: Vec::new(),
+            labels: Labels::new(),
+            capabilities: Vec::new(),
+        })
+    }
+
+    // `source` is a Python script defining `main(inputs, output)`.
+    pub fn new_register_python_function(
+        function_name: &str,
+        description: &str,
+        source: &str,
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::RegisterFunction(RegisterFunctionRequest {
+            function_name: function_name.to_owned(),
+            description: description.to_owned(),
+            payload: Some(source.to_owned()),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            collaborators: Vec::new(),
+            executor: Executor::Python.name().to_owned(),
+            optional_inputs: Vec::new(),
+            argument_schema: None,
+            remote_payload: None,
+            input_ports: Vec::new(),
+            output_ports: Vec::new(),
+            labels: Labels::new(),
+            capabilities: Vec::new(),
+        })
+    }
+
+    pub fn new_register_shared_function(
+        function_name: &str,
+        description: &str,
+        payload: Option<&str>,
+        collaborators: &[&str],
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::RegisterFunction(RegisterFunctionRequest {
+            function_name: function_name.to_owned(),
+            description: description.to_owned(),
+            payload: payload.map(|s| s.to_owned()),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            collaborators: collaborators.iter().map(|s| (*s).to_string()).collect(),
+            executor: Executor::Builtin.name().to_owned(),
+            optional_inputs: Vec::new(),
+            argument_schema: None,
+            remote_payload: None,
+            input_ports: Vec::new(),
+            output_ports: Vec::new(),
+            labels: Labels::new(),
+            capabilities: Vec::new(),
+        })
+    }
+
+    pub fn new_register_function_with_schema(
+        function_name: &str,
+        description: &str,
+        payload: Option<&str>,
+        argument_schema: &[ArgumentSpec],
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::RegisterFunction(RegisterFunctionRequest {
+            function_name: function_name.to_owned(),
+            description: description.to_owned(),
+            payload: payload.map(|s| s.to_owned()),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            collaborators: Vec::new(),
+            executor: Executor::Builtin.name().to_owned(),
+            optional_inputs: Vec::new(),
+            argument_schema: Some(argument_schema.to_vec()),
+            remote_payload: None,
+            input_ports: Vec::new(),
+            output_ports: Vec::new(),
+            labels: Labels::new(),
+            capabilities: Vec::new(),
+        })
+    }
+
+    pub fn new_register_function_with_labels(
+        function_name: &str,
+        description: &str,
+        payload: Option<&str>,
+        labels: &Labels,
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::RegisterFunction(RegisterFunctionRequest {
+            function_name: function_name.to_owned(),
+            description: description.to_owned(),
+            payload: payload.map(|s| s.to_owned()),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            collaborators: Vec::new(),
+            executor: Executor::Builtin.name().to_owned(),
+            optional_inputs: Vec::new(),
+            argument_schema: None,
+            remote_payload: None,
+            input_ports: Vec::new(),
+            output_ports: Vec::new(),
+            labels: labels.clone(),
+            capabilities: Vec::new(),
+        })
+    }
+
+    pub fn new_register_function_with_capabilities(
+        function_name: &str,
+        description: &str,
+        executor: Executor,
+        payload: Option<&str>,
+        capabilities: &[ExecutorCapability],
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::RegisterFunction(RegisterFunctionRequest {
+            function_name: function_name.to_owned(),
+            description: description.to_owned(),
+            payload: payload.map(|s| s.to_owned()),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            collaborators: Vec::new(),
+            executor: executor.name().to_owned(),
+            optional_inputs: Vec::new(),
+            argument_schema: None,
+            remote_payload: None,
+            input_ports: Vec::new(),
+            output_ports: Vec::new(),
+            labels: Labels::new(),
+            capabilities: capabilities.to_vec(),
+        })
+    }
+
+    pub fn new_register_function_with_ports(
+        function_name: &str,
+        description: &str,
+        payload: Option<&str>,
+        input_ports: &[PortSpec],
+        output_ports: &[PortSpec],
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::RegisterFunction(RegisterFunctionRequest {
+            function_name: function_name.to_owned(),
+            description: description.to_owned(),
+            payload: payload.map(|s| s.to_owned()),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            collaborators: Vec::new(),
+            executor: Executor::Builtin.name().to_owned(),
+            optional_inputs: Vec::new(),
+            argument_schema: None,
+            remote_payload: None,
+            input_ports: input_ports.to_vec(),
+            output_ports: output_ports.to_vec(),
+            labels: Labels::new(),
+            capabilities: Vec::new(),
+        })
+    }
+
+    pub fn new_register_remote_function(
+        function_name: &str,
+        description: &str,
+        executor: Executor,
+        remote_payload: &RemotePayload,
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::RegisterFunction(RegisterFunctionRequest {
+            function_name: function_name.to_owned(),
+            description: description.to_owned(),
+            payload: None,
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            collaborators: Vec::new(),
+            executor: executor.name().to_owned(),
+            optional_inputs: Vec::new(),
+            argument_schema: None,
+            remote_payload: Some(remote_payload.clone()),
+            input_ports: Vec::new(),
+            output_ports: Vec::new(),
+            labels: Labels::new(),
+            capabilities: Vec::new(),
+        })
+    }
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
-pub struct UpdateTaskRequest {
-    pub task_id: String,
-    pub files: Vec<String>,
-    pub user_id: String,
-    pub user_token: String,
-}
+    pub fn new_update_function(
+        function_id: &str,
+        function_name: &str,
+        description: &str,
+        payload: Option<&str>,
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::UpdateFunction(UpdateFunctionRequest {
+            function_id: function_id.to_owned(),
+            function_name: function_name.to_owned(),
+            description: description.to_owned(),
+            payload: payload.map(|s| s.to_owned()),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+        })
+    }
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
-pub struct UpdateTaskResponse {
-    pub success: bool,
-    pub status: TaskStatus,
-    pub ip: IpAddr,
-    pub port: u16,
-    pub task_token: String,
-}
+    pub fn new_get_function(function_id: &str, user_id: &str, user_token: &str) -> TaskRequest {
+        TaskRequest::GetFunction(GetFunctionRequest {
+            function_id: function_id.to_owned(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+        })
+    }
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
-pub struct ListTaskResponse {
-    pub list: Vec<String>,
-}
+    pub fn new_get_function_versions(
+        function_id: &str,
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::GetFunctionVersions(GetFunctionVersionsRequest {
+            function_id: function_id.to_owned(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+        })
+    }
 
-impl TaskRequest {
-    pub fn new_get_task(task_id: &str, user_id: &str, user_token: &str) -> TaskRequest {
-        TaskRequest::Get(GetTaskRequest {
+    pub fn new_list_functions(
+        limit: Option<usize>,
+        cursor: &str,
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::ListFunctions(ListFunctionsRequest {
+            limit,
+            cursor: cursor.to_owned(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            labels: Labels::new(),
+        })
+    }
+
+    pub fn new_list_functions_with_labels(
+        labels: &Labels,
+        limit: Option<usize>,
+        cursor: &str,
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::ListFunctions(ListFunctionsRequest {
+            limit,
+            cursor: cursor.to_owned(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            labels: labels.clone(),
+        })
+    }
+
+    pub fn new_share_function(
+        function_id: &str,
+        user_ids: &[&str],
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::ShareFunction(ShareFunctionRequest {
+            function_id: function_id.to_owned(),
+            user_ids: user_ids.iter().map(|s| (*s).to_string()).collect(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+        })
+    }
+
+    pub fn new_unshare_function(
+        function_id: &str,
+        user_ids: &[&str],
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::UnshareFunction(UnshareFunctionRequest {
+            function_id: function_id.to_owned(),
+            user_ids: user_ids.iter().map(|s| (*s).to_string()).collect(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+        })
+    }
+
+    pub fn new_health_check() -> TaskRequest {
+        TaskRequest::HealthCheck(HealthCheckRequest {})
+    }
+
+    pub fn new_get_enclave_measurement() -> TaskRequest {
+        TaskRequest::GetEnclaveMeasurement(GetEnclaveMeasurementRequest {})
+    }
+
+    pub fn new_get_version_info() -> TaskRequest {
+        TaskRequest::GetVersionInfo(GetVersionInfoRequest {})
+    }
+
+    pub fn new_query_audit_log(
+        query: &AuditLogQuery,
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::QueryAuditLog(QueryAuditLogRequest {
+            query: query.clone(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+        })
+    }
+
+    pub fn new_revoke_user(
+        revoked_user_id: &str,
+        cancel_tasks: bool,
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::RevokeUser(RevokeUserRequest {
+            revoked_user_id: revoked_user_id.to_owned(),
+            cancel_tasks,
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+        })
+    }
+
+    pub fn new_query_tasks(
+        query: &TaskQuery,
+        limit: Option<usize>,
+        cursor: &str,
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::QueryTasks(QueryTasksRequest {
+            query: query.clone(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+            limit,
+            cursor: cursor.to_owned(),
+        })
+    }
+
+    pub fn new_get_task_statuses(
+        task_ids: &[&str],
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::GetTaskStatuses(GetTaskStatusesRequest {
+            task_ids: task_ids.iter().map(|s| (*s).to_string()).collect(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+        })
+    }
+
+    pub fn new_get_task_receipt(task_id: &str, user_id: &str, user_token: &str) -> TaskRequest {
+        TaskRequest::GetTaskReceipt(GetTaskReceiptRequest {
             task_id: task_id.to_owned(),
             user_id: user_id.to_owned(),
             user_token: user_token.to_owned(),
         })
     }
 
-    pub fn new_create_task(
-        function_name: &str,
-        collaborator_list: &[&str],
-        files: &[&str],
+    pub fn new_list_executors(user_id: &str, user_token: &str) -> TaskRequest {
+        TaskRequest::ListExecutors(ListExecutorsRequest {
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+        })
+    }
+
+    pub fn new_put_secret(
+        name: &str,
+        value: &str,
+        readers: &[&str],
         user_id: &str,
         user_token: &str,
     ) -> TaskRequest {
-        TaskRequest::Create(CreateTaskRequest {
-            function_name: function_name.to_owned(),
-            collaborator_list: collaborator_list.iter().map(|s| (*s).to_string()).collect(),
-            files: files.iter().map(|s| (*s).to_string()).collect(),
+        TaskRequest::PutSecret(PutSecretRequest {
+            name: name.to_owned(),
+            value: value.to_owned(),
+            readers: readers.iter().map(|s| (*s).to_string()).collect(),
             user_id: user_id.to_owned(),
             user_token: user_token.to_owned(),
         })
     }
 
-    pub fn new_update_task(
+    pub fn new_delete_secret(name: &str, user_id: &str, user_token: &str) -> TaskRequest {
+        TaskRequest::DeleteSecret(DeleteSecretRequest {
+            name: name.to_owned(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+        })
+    }
+
+    pub fn new_list_secrets(user_id: &str, user_token: &str) -> TaskRequest {
+        TaskRequest::ListSecrets(ListSecretsRequest {
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+        })
+    }
+
+    pub fn new_rerun_task(
         task_id: &str,
-        files: &[&str],
+        arguments: Option<&TaskArguments>,
         user_id: &str,
         user_token: &str,
     ) -> TaskRequest {
-        TaskRequest::Update(UpdateTaskRequest {
+        TaskRequest::RerunTask(RerunTaskRequest {
             task_id: task_id.to_owned(),
-            files: files.iter().map(|s| (*s).to_string()).collect(),
+            arguments: arguments.cloned(),
             user_id: user_id.to_owned(),
             user_token: user_token.to_owned(),
         })
     }
 
-    pub fn new_list_task(user_id: &str, user_token: &str) -> TaskRequest {
-        TaskRequest::List(ListTaskRequest {
+    pub fn new_invoke_function(
+        function_id: &str,
+        inline_inputs: &[&[u8]],
+        arguments: &TaskArguments,
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::InvokeFunction(InvokeFunctionRequest {
+            function_id: function_id.to_owned(),
+            inline_inputs: inline_inputs.iter().map(|input| input.to_vec()).collect(),
+            arguments: arguments.clone(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+        })
+    }
+
+    pub fn new_unrevoke_user(
+        revoked_user_id: &str,
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::UnrevokeUser(UnrevokeUserRequest {
+            revoked_user_id: revoked_user_id.to_owned(),
+            user_id: user_id.to_owned(),
+            user_token: user_token.to_owned(),
+        })
+    }
+
+    pub fn new_reload_enclave_info(
+        fragments: &[SignedEnclaveInfo],
+        user_id: &str,
+        user_token: &str,
+    ) -> TaskRequest {
+        TaskRequest::ReloadEnclaveInfo(ReloadEnclaveInfoRequest {
+            fragments: fragments.to_vec(),
             user_id: user_id.to_owned(),
             user_token: user_token.to_owned(),
         })
@@ -174,6 +2394,18 @@ impl TaskResponse {
         })
     }
 
+    pub fn new_create_tasks(results: Vec<CreateTaskResult>) -> TaskResponse {
+        TaskResponse::CreateTasks(CreateTasksResponse { results })
+    }
+
+    pub fn new_validate_task(error: Option<&Error>) -> TaskResponse {
+        TaskResponse::ValidateTask(ValidateTaskResponse {
+            valid: error.is_none(),
+            error: error.map(|error| error.to_string()),
+            field_errors: error.map_or_else(Vec::new, |error| error.field_errors().to_vec()),
+        })
+    }
+
     pub fn new_update_task(
         success: bool,
         status: TaskStatus,
@@ -190,9 +2422,159 @@ impl TaskResponse {
         })
     }
 
-    pub fn new_list_task(list: &[&str]) -> TaskResponse {
+    pub fn new_list_task(list: &[&str], next_token: &str) -> TaskResponse {
         TaskResponse::List(ListTaskResponse {
             list: list.iter().map(|s| (*s).to_string()).collect(),
+            next_token: next_token.to_owned(),
+        })
+    }
+
+    pub fn new_cancel_task(status: TaskStatus) -> TaskResponse {
+        TaskResponse::Cancel(CancelTaskResponse { status })
+    }
+
+    pub fn new_delete_task() -> TaskResponse {
+        TaskResponse::Delete(DeleteTaskResponse {})
+    }
+
+    pub fn new_wait_for_task_status(
+        changed: bool,
+        status: TaskStatus,
+        status_version: u64,
+    ) -> TaskResponse {
+        TaskResponse::WaitForTaskStatus(WaitForTaskStatusResponse {
+            changed,
+            status,
+            status_version,
+        })
+    }
+
+    pub fn new_stream_task_logs(
+        lines: Vec<TaskLogLine>,
+        next: u64,
+        truncated: bool,
+        complete: bool,
+    ) -> TaskResponse {
+        TaskResponse::StreamTaskLogs(StreamTaskLogsResponse {
+            lines,
+            next,
+            truncated,
+            complete,
+        })
+    }
+
+    pub fn new_register_function(function_id: &str) -> TaskResponse {
+        TaskResponse::RegisterFunction(RegisterFunctionResponse {
+            function_id: function_id.to_owned(),
+        })
+    }
+
+    pub fn new_update_function(function_id: &str) -> TaskResponse {
+        TaskResponse::UpdateFunction(UpdateFunctionResponse {
+            function_id: function_id.to_owned(),
+        })
+    }
+
+    pub fn new_get_function(function_info: &FunctionInfo) -> TaskResponse {
+        TaskResponse::GetFunction(GetFunctionResponse {
+            function_info: function_info.clone(),
+        })
+    }
+
+    pub fn new_get_function_versions(versions: Vec<FunctionInfo>) -> TaskResponse {
+        TaskResponse::GetFunctionVersions(GetFunctionVersionsResponse { versions })
+    }
+
+    pub fn new_list_functions(functions: Vec<FunctionInfo>, next_token: &str) -> TaskResponse {
+        TaskResponse::ListFunctions(ListFunctionsResponse {
+            functions,
+            next_token: next_token.to_owned(),
+        })
+    }
+
+    pub fn new_share_function(collaborators: Vec<String>) -> TaskResponse {
+        TaskResponse::ShareFunction(ShareFunctionResponse { collaborators })
+    }
+
+    pub fn new_unshare_function(collaborators: Vec<String>) -> TaskResponse {
+        TaskResponse::UnshareFunction(UnshareFunctionResponse { collaborators })
+    }
+
+    pub fn new_health_check(health: ServiceHealth) -> TaskResponse {
+        TaskResponse::HealthCheck(HealthCheckResponse { health })
+    }
+
+    pub fn new_get_enclave_measurement(identity: EnclaveIdentity) -> TaskResponse {
+        TaskResponse::GetEnclaveMeasurement(GetEnclaveMeasurementResponse { identity })
+    }
+
+    pub fn new_get_version_info(version: VersionInfo) -> TaskResponse {
+        TaskResponse::GetVersionInfo(GetVersionInfoResponse { version })
+    }
+
+    pub fn new_query_audit_log(entries: Vec<AuditLogEntry>) -> TaskResponse {
+        TaskResponse::QueryAuditLog(QueryAuditLogResponse { entries })
+    }
+
+    pub fn new_revoke_user(cancelled_tasks: usize) -> TaskResponse {
+        TaskResponse::RevokeUser(RevokeUserResponse { cancelled_tasks })
+    }
+
+    pub fn new_unrevoke_user() -> TaskResponse {
+        TaskResponse::UnrevokeUser(UnrevokeUserResponse {})
+    }
+
+    pub fn new_reload_enclave_info(services: Vec<String>, peers: Vec<PeerReload>) -> TaskResponse {
+        TaskResponse::ReloadEnclaveInfo(ReloadEnclaveInfoResponse { services, peers })
+    }
+
+    pub fn new_get_task_receipt(receipt: &SignedTaskReceipt) -> TaskResponse {
+        TaskResponse::GetTaskReceipt(GetTaskReceiptResponse {
+            receipt: receipt.clone(),
+        })
+    }
+
+    pub fn new_list_executors(executors: Vec<ExecutorInfo>) -> TaskResponse {
+        TaskResponse::ListExecutors(ListExecutorsResponse { executors })
+    }
+
+    pub fn new_put_secret() -> TaskResponse {
+        TaskResponse::PutSecret(PutSecretResponse {})
+    }
+
+    pub fn new_delete_secret() -> TaskResponse {
+        TaskResponse::DeleteSecret(DeleteSecretResponse {})
+    }
+
+    pub fn new_list_secrets(secrets: Vec<SecretInfo>) -> TaskResponse {
+        TaskResponse::ListSecrets(ListSecretsResponse { secrets })
+    }
+
+    pub fn new_rerun_task(task_id: &str, task_token: &str, ip: IpAddr, port: u16) -> TaskResponse {
+        TaskResponse::RerunTask(CreateTaskResponse {
+            task_id: task_id.to_owned(),
+            task_token: task_token.to_owned(),
+            ip,
+            port,
+        })
+    }
+
+    pub fn new_invoke_function(task_id: &str, result: &str, outputs: Vec<Vec<u8>>) -> TaskResponse {
+        TaskResponse::InvokeFunction(InvokeFunctionResponse {
+            task_id: task_id.to_owned(),
+            result: result.to_owned(),
+            outputs,
+        })
+    }
+
+    pub fn new_query_tasks(tasks: Vec<TaskSummary>, next_token: &str) -> TaskResponse {
+        TaskResponse::QueryTasks(QueryTasksResponse {
+            tasks,
+            next_token: next_token.to_owned(),
         })
     }
+
+    pub fn new_get_task_statuses(results: Vec<TaskStatusResult>) -> TaskResponse {
+        TaskResponse::GetTaskStatuses(GetTaskStatusesResponse { results })
+    }
 }
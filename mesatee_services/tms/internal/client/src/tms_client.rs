@@ -22,7 +22,11 @@ use mesatee_core::config::{OutboundDesc, TargetDesc};
 use mesatee_core::rpc::channel::SgxTrustedChannel;
 use mesatee_core::{self, Result};
 use tms_internal_proto::{
-    GetTaskResponse, TaskFile, TaskRequest, TaskResponse, TaskStatus, UpdateTaskResponse,
+    AppendTaskLogResponse, DynamicOutputBindings, ExecutorInfo, GetFunctionResponse,
+    GetTaskResponse, GetTaskSecretsResponse, GetVersionInfoResponse, PartialOutput, PortBindings,
+    PullTaskResponse, RegisterExecutorsResponse, RegisterWorkerResponse, ReportProgressResponse,
+    TaskError, TaskFile, TaskLogLine, TaskRequest, TaskResponse, TaskStatus, UpdateTaskResponse,
+    WorkerHeartbeatResponse,
 };
 
 pub struct TMSClient {
@@ -58,6 +62,74 @@ impl TMSClient {
         }
     }
 
+    // Reports output files of the task, each bound to an output port.
+    pub fn request_update_task_with_output_ports(
+        &mut self,
+        task_id: &str,
+        output_files: &[&TaskFile],
+        output_ports: &PortBindings,
+        status: Option<&TaskStatus>,
+    ) -> Result<UpdateTaskResponse> {
+        let req = TaskRequest::new_update_task_with_output_ports(
+            task_id,
+            output_files,
+            output_ports,
+            status,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::Update(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    // Reports output files of the task, some of them produced on dynamic
+    // output ports, and the one holding its serialized result, if any.
+    pub fn request_update_task_with_dynamic_outputs(
+        &mut self,
+        task_id: &str,
+        task_result_file_id: Option<&str>,
+        output_files: &[&TaskFile],
+        dynamic_outputs: &DynamicOutputBindings,
+        result_file_id: Option<&str>,
+        status: Option<&TaskStatus>,
+    ) -> Result<UpdateTaskResponse> {
+        let req = TaskRequest::new_update_task_with_dynamic_outputs(
+            task_id,
+            task_result_file_id,
+            output_files,
+            dynamic_outputs,
+            result_file_id,
+            status,
+        );
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::Update(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    // Reports that the execution of the task failed. TMS runs the task again
+    // if the error is retryable and its retry policy allows another attempt.
+    pub fn request_fail_task(
+        &mut self,
+        task_id: &str,
+        error: &TaskError,
+    ) -> Result<UpdateTaskResponse> {
+        let req = TaskRequest::new_fail_task(task_id, error);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::Update(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
     pub fn request_get_task(&mut self, task_id: &str) -> Result<GetTaskResponse> {
         let req = TaskRequest::new_get_task(task_id);
         let resp = self.channel.invoke(req)?;
@@ -68,4 +140,133 @@ impl TMSClient {
             )),
         }
     }
+
+    pub fn request_get_function(&mut self, function_id: &str) -> Result<GetFunctionResponse> {
+        let req = TaskRequest::new_get_function(function_id);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::GetFunction(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_pull_task(&mut self) -> Result<PullTaskResponse> {
+        let req = TaskRequest::new_pull_task();
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::Pull(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    // Pulls a task as the registered worker `worker_id`.
+    pub fn request_worker_pull_task(&mut self, worker_id: &str) -> Result<PullTaskResponse> {
+        let req = TaskRequest::new_worker_pull_task(worker_id);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::Pull(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_register_worker(&mut self, capacity: usize) -> Result<RegisterWorkerResponse> {
+        let req = TaskRequest::new_register_worker(capacity);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::RegisterWorker(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_register_executors(
+        &mut self,
+        executors: &[ExecutorInfo],
+        enclave_info_digest: &str,
+    ) -> Result<RegisterExecutorsResponse> {
+        let req = TaskRequest::new_register_executors(executors, enclave_info_digest);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::RegisterExecutors(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_get_task_secrets(
+        &mut self,
+        task_id: &str,
+        task_token: &str,
+    ) -> Result<GetTaskSecretsResponse> {
+        let req = TaskRequest::new_get_task_secrets(task_id, task_token);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::GetTaskSecrets(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_worker_heartbeat(&mut self, worker_id: &str) -> Result<WorkerHeartbeatResponse> {
+        let req = TaskRequest::new_worker_heartbeat(worker_id);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::WorkerHeartbeat(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_report_progress(
+        &mut self,
+        task_id: &str,
+        percent: u8,
+        message: &str,
+        partial_output: Option<&PartialOutput>,
+    ) -> Result<ReportProgressResponse> {
+        let req = TaskRequest::new_report_progress(task_id, percent, message, partial_output);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::ReportProgress(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_append_task_log(
+        &mut self,
+        task_id: &str,
+        lines: &[TaskLogLine],
+    ) -> Result<AppendTaskLogResponse> {
+        let req = TaskRequest::new_append_task_log(task_id, lines);
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::AppendTaskLog(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
+
+    pub fn request_get_version_info(&mut self) -> Result<GetVersionInfoResponse> {
+        let req = TaskRequest::new_get_version_info();
+        let resp = self.channel.invoke(req)?;
+        match resp {
+            TaskResponse::GetVersionInfo(resp) => Ok(resp),
+            _ => Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::RPCResponseError,
+            )),
+        }
+    }
 }
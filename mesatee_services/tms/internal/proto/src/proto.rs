@@ -18,14 +18,33 @@
 #[cfg(feature = "mesalock_sgx")]
 use std::prelude::v1::*;
 
+pub use mesatee_core::config::SignedEnclaveInfo;
+pub use mesatee_core::health::VersionInfo;
 use serde_derive::*;
-pub use tms_common_proto::{FunctionType, TaskFile, TaskInfo, TaskStatus};
+use std::collections::BTreeMap;
+use std::fmt;
+pub use tms_common_proto::{
+    python_modules, ArgumentsFile, DynamicOutput, DynamicOutputBindings, Executor,
+    ExecutorCapability, ExecutorInfo, FileFormat, FunctionInfo, FunctionType, LogStream,
+    PartialOutput, PortBindings, ResourceLimits, ResultFormat, TaskArguments, TaskAttempt,
+    TaskError, TaskFile, TaskInfo, TaskLogLine, TaskProgress, TaskResultRecord, TaskRetryPolicy,
+    TaskStatus, MAX_PROGRESS_CHECKPOINTS, PYTHON_FUNCTION_NAME, WASM_FUNCTION_NAME,
+};
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 #[serde(tag = "type")]
 pub enum TaskRequest {
     Get(GetTaskRequest),
     Update(UpdateTaskRequest),
+    GetFunction(GetFunctionRequest),
+    Pull(PullTaskRequest),
+    GetVersionInfo(GetVersionInfoRequest),
+    ReportProgress(ReportProgressRequest),
+    RegisterWorker(RegisterWorkerRequest),
+    WorkerHeartbeat(WorkerHeartbeatRequest),
+    RegisterExecutors(RegisterExecutorsRequest),
+    GetTaskSecrets(GetTaskSecretsRequest),
+    AppendTaskLog(AppendTaskLogRequest),
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -33,6 +52,15 @@ pub enum TaskRequest {
 pub enum TaskResponse {
     Get(GetTaskResponse),
     Update(UpdateTaskResponse),
+    GetFunction(GetFunctionResponse),
+    Pull(PullTaskResponse),
+    GetVersionInfo(GetVersionInfoResponse),
+    ReportProgress(ReportProgressResponse),
+    RegisterWorker(RegisterWorkerResponse),
+    WorkerHeartbeat(WorkerHeartbeatResponse),
+    RegisterExecutors(RegisterExecutorsResponse),
+    GetTaskSecrets(GetTaskSecretsResponse),
+    AppendTaskLog(AppendTaskLogResponse),
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -51,6 +79,19 @@ pub struct UpdateTaskRequest {
     pub task_result_file_id: Option<String>,
     pub output_files: Vec<TaskFile>,
     pub status: Option<TaskStatus>,
+    // Output files of the update bound to output ports of the function.
+    #[serde(default)]
+    pub output_ports: PortBindings,
+    // Output files of the update produced on dynamic output ports of the
+    // function, by port name.
+    #[serde(default)]
+    pub dynamic_outputs: DynamicOutputBindings,
+    // Why the execution failed, when the status is `Failed`.
+    #[serde(default)]
+    pub error: Option<TaskError>,
+    // Output file of the update holding the serialized result of the task.
+    #[serde(default)]
+    pub result_file_id: Option<String>,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -58,6 +99,140 @@ pub struct UpdateTaskResponse {
     pub success: bool,
 }
 
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct GetFunctionRequest {
+    pub function_id: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct GetFunctionResponse {
+    pub function_info: FunctionInfo,
+}
+
+// Hands out the ready task with the highest effective priority. A task is
+// handed out once, and stays ready until its executor updates its status.
+// A registered worker is only handed a task while it runs fewer tasks than its
+// capacity, and gets the task queued again if it stops sending heartbeats.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PullTaskRequest {
+    #[serde(default)]
+    pub worker_id: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PullTaskResponse {
+    // Not set if no task is ready.
+    pub task_id: Option<String>,
+    pub task_info: Option<TaskInfo>,
+}
+
+// Records a progress checkpoint of a task which has not ended.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ReportProgressRequest {
+    pub task_id: String,
+    pub percent: u8,
+    pub message: String,
+    pub partial_output: Option<PartialOutput>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ReportProgressResponse {
+    // False if the task does not exist or has ended.
+    pub success: bool,
+}
+
+// Appends the lines the function of a running task wrote to its log, in the
+// order they were written.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct AppendTaskLogRequest {
+    pub task_id: String,
+    pub lines: Vec<TaskLogLine>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct AppendTaskLogResponse {
+    // False if the task does not exist or has ended.
+    pub success: bool,
+}
+
+// Registers an execution worker, attested by the channel it connects with.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct RegisterWorkerRequest {
+    // Tasks the worker runs at once.
+    pub capacity: usize,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct RegisterWorkerResponse {
+    pub worker_id: String,
+    // Seconds the worker may go without a heartbeat, pulling included.
+    pub heartbeat_timeout: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct WorkerHeartbeatRequest {
+    pub worker_id: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct WorkerHeartbeatResponse {
+    // False if the worker is not registered, e.g. it missed its heartbeats
+    // and its tasks were queued again. It has to register again.
+    pub registered: bool,
+}
+
+// Registers the executors of an execution service, which registers them again
+// periodically, as TMS forgets them when it restarts. Functions may only be
+// registered for the executors of the execution services. The execution
+// service also sends the digest of its enclave info, and TMS returns its own
+// signed enclave info when the digests differ, so that an execution service
+// picks up a reloaded enclave info at its next registration.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct RegisterExecutorsRequest {
+    pub executors: Vec<ExecutorInfo>,
+    #[serde(default)]
+    pub enclave_info_digest: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct RegisterExecutorsResponse {
+    #[serde(default)]
+    pub enclave_info: Vec<SignedEnclaveInfo>,
+}
+
+// Opens the secrets of a task which is ready or running, for its executor.
+// References to a secret store are checked again, so that a task does not
+// get a secret deleted or unshared since it was created.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct GetTaskSecretsRequest {
+    pub task_id: String,
+    pub task_token: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GetTaskSecretsResponse {
+    // Values of the secrets, by environment variable.
+    pub env: BTreeMap<String, String>,
+}
+
+// Responses are traced with their Debug output, which must not include the
+// values.
+impl fmt::Debug for GetTaskSecretsResponse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("GetTaskSecretsResponse")
+            .field("env", &self.env.keys().collect::<Vec<&String>>())
+            .finish()
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct GetVersionInfoRequest {}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct GetVersionInfoResponse {
+    pub version: VersionInfo,
+}
+
 impl TaskRequest {
     pub fn new_get_task(task_id: &str) -> TaskRequest {
         let req = GetTaskRequest {
@@ -80,9 +255,143 @@ impl TaskRequest {
                 .map(|&task_file| task_file.clone())
                 .collect(),
             status: status.copied(),
+            output_ports: PortBindings::new(),
+            dynamic_outputs: DynamicOutputBindings::new(),
+            error: None,
+            result_file_id: None,
+        };
+        TaskRequest::Update(req)
+    }
+
+    pub fn new_update_task_with_output_ports(
+        task_id: &str,
+        output_files: &[&TaskFile],
+        output_ports: &PortBindings,
+        status: Option<&TaskStatus>,
+    ) -> TaskRequest {
+        let req = UpdateTaskRequest {
+            task_id: task_id.to_owned(),
+            task_result_file_id: None,
+            output_files: output_files
+                .iter()
+                .map(|&task_file| task_file.clone())
+                .collect(),
+            status: status.copied(),
+            output_ports: output_ports.clone(),
+            dynamic_outputs: DynamicOutputBindings::new(),
+            error: None,
+            result_file_id: None,
+        };
+        TaskRequest::Update(req)
+    }
+
+    pub fn new_update_task_with_dynamic_outputs(
+        task_id: &str,
+        task_result_file_id: Option<&str>,
+        output_files: &[&TaskFile],
+        dynamic_outputs: &DynamicOutputBindings,
+        result_file_id: Option<&str>,
+        status: Option<&TaskStatus>,
+    ) -> TaskRequest {
+        let req = UpdateTaskRequest {
+            task_id: task_id.to_owned(),
+            task_result_file_id: task_result_file_id.map(|s| (*s).to_string()),
+            output_files: output_files
+                .iter()
+                .map(|&task_file| task_file.clone())
+                .collect(),
+            status: status.copied(),
+            output_ports: PortBindings::new(),
+            dynamic_outputs: dynamic_outputs.clone(),
+            error: None,
+            result_file_id: result_file_id.map(|s| s.to_owned()),
         };
         TaskRequest::Update(req)
     }
+
+    pub fn new_fail_task(task_id: &str, error: &TaskError) -> TaskRequest {
+        let req = UpdateTaskRequest {
+            task_id: task_id.to_owned(),
+            task_result_file_id: None,
+            output_files: Vec::new(),
+            status: Some(TaskStatus::Failed),
+            output_ports: PortBindings::new(),
+            dynamic_outputs: DynamicOutputBindings::new(),
+            error: Some(error.clone()),
+            result_file_id: None,
+        };
+        TaskRequest::Update(req)
+    }
+
+    pub fn new_get_function(function_id: &str) -> TaskRequest {
+        let req = GetFunctionRequest {
+            function_id: function_id.to_owned(),
+        };
+        TaskRequest::GetFunction(req)
+    }
+
+    pub fn new_pull_task() -> TaskRequest {
+        TaskRequest::Pull(PullTaskRequest { worker_id: None })
+    }
+
+    pub fn new_worker_pull_task(worker_id: &str) -> TaskRequest {
+        TaskRequest::Pull(PullTaskRequest {
+            worker_id: Some(worker_id.to_owned()),
+        })
+    }
+
+    pub fn new_register_worker(capacity: usize) -> TaskRequest {
+        TaskRequest::RegisterWorker(RegisterWorkerRequest { capacity })
+    }
+
+    pub fn new_worker_heartbeat(worker_id: &str) -> TaskRequest {
+        TaskRequest::WorkerHeartbeat(WorkerHeartbeatRequest {
+            worker_id: worker_id.to_owned(),
+        })
+    }
+
+    pub fn new_register_executors(
+        executors: &[ExecutorInfo],
+        enclave_info_digest: &str,
+    ) -> TaskRequest {
+        TaskRequest::RegisterExecutors(RegisterExecutorsRequest {
+            executors: executors.to_vec(),
+            enclave_info_digest: enclave_info_digest.to_owned(),
+        })
+    }
+
+    pub fn new_get_task_secrets(task_id: &str, task_token: &str) -> TaskRequest {
+        TaskRequest::GetTaskSecrets(GetTaskSecretsRequest {
+            task_id: task_id.to_owned(),
+            task_token: task_token.to_owned(),
+        })
+    }
+
+    pub fn new_get_version_info() -> TaskRequest {
+        TaskRequest::GetVersionInfo(GetVersionInfoRequest {})
+    }
+
+    pub fn new_report_progress(
+        task_id: &str,
+        percent: u8,
+        message: &str,
+        partial_output: Option<&PartialOutput>,
+    ) -> TaskRequest {
+        let req = ReportProgressRequest {
+            task_id: task_id.to_owned(),
+            percent,
+            message: message.to_owned(),
+            partial_output: partial_output.cloned(),
+        };
+        TaskRequest::ReportProgress(req)
+    }
+
+    pub fn new_append_task_log(task_id: &str, lines: &[TaskLogLine]) -> TaskRequest {
+        TaskRequest::AppendTaskLog(AppendTaskLogRequest {
+            task_id: task_id.to_owned(),
+            lines: lines.to_vec(),
+        })
+    }
 }
 
 impl TaskResponse {
@@ -97,4 +406,50 @@ impl TaskResponse {
         };
         TaskResponse::Get(resp)
     }
+
+    pub fn new_get_function(function_info: &FunctionInfo) -> TaskResponse {
+        let resp = GetFunctionResponse {
+            function_info: function_info.clone(),
+        };
+        TaskResponse::GetFunction(resp)
+    }
+
+    pub fn new_pull_task(task: Option<(&str, &TaskInfo)>) -> TaskResponse {
+        let resp = PullTaskResponse {
+            task_id: task.map(|(task_id, _)| task_id.to_owned()),
+            task_info: task.map(|(_, task_info)| task_info.clone()),
+        };
+        TaskResponse::Pull(resp)
+    }
+
+    pub fn new_get_version_info(version: VersionInfo) -> TaskResponse {
+        TaskResponse::GetVersionInfo(GetVersionInfoResponse { version })
+    }
+
+    pub fn new_report_progress(success: bool) -> TaskResponse {
+        TaskResponse::ReportProgress(ReportProgressResponse { success })
+    }
+
+    pub fn new_append_task_log(success: bool) -> TaskResponse {
+        TaskResponse::AppendTaskLog(AppendTaskLogResponse { success })
+    }
+
+    pub fn new_register_worker(worker_id: &str, heartbeat_timeout: u64) -> TaskResponse {
+        TaskResponse::RegisterWorker(RegisterWorkerResponse {
+            worker_id: worker_id.to_owned(),
+            heartbeat_timeout,
+        })
+    }
+
+    pub fn new_worker_heartbeat(registered: bool) -> TaskResponse {
+        TaskResponse::WorkerHeartbeat(WorkerHeartbeatResponse { registered })
+    }
+
+    pub fn new_register_executors(enclave_info: Vec<SignedEnclaveInfo>) -> TaskResponse {
+        TaskResponse::RegisterExecutors(RegisterExecutorsResponse { enclave_info })
+    }
+
+    pub fn new_get_task_secrets(env: BTreeMap<String, String>) -> TaskResponse {
+        TaskResponse::GetTaskSecrets(GetTaskSecretsResponse { env })
+    }
 }
@@ -18,16 +18,23 @@
 #[macro_use]
 extern crate log;
 
+use mesatee_core::rpc::listener::{wait_reachable, ConnectionPool, ListenerBuilder};
 use mesatee_core::{config, Result};
 
-use std::net::TcpListener;
+use std::fs;
+use std::io;
+use std::net::TcpStream;
 use std::os::unix::io::IntoRawFd;
-use threadpool::ThreadPool;
 
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use teaclave_binder::TeeBinder;
 
 use std::thread;
+use std::time::{Duration, Instant};
+
+// Interval to check the shutdown signal when there is no incoming connection
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
 use mesatee_core::prelude::*;
 
@@ -47,75 +54,171 @@ fn main() -> Result<()> {
 
     let tee = Arc::new(tee);
 
+    let shutdown = Arc::new(AtomicBool::new(false));
     {
-        let ref_tee = tee.clone();
+        let shutdown = shutdown.clone();
         ctrlc::set_handler(move || {
-            info!("\nCTRL+C pressed. Destroying server enclave");
-            ref_tee.finalize();
-            std::process::exit(0);
+            info!("\nCTRL+C pressed. Shutting down server enclave");
+            shutdown.store(true, Ordering::SeqCst);
         })
         .expect("Error setting Ctrl-C handler");
     }
 
-    run_tms_service(tee)?;
+    run_tms_service(tee, shutdown)?;
 
     Ok(())
 }
 
-fn launch_external_server(tee: Arc<TeeBinder>) -> Result<()> {
+fn launch_external_server(
+    tee: Arc<TeeBinder>,
+    shutdown: Arc<AtomicBool>,
+) -> Result<thread::JoinHandle<()>> {
     let config = config::External::tms();
-    let listener = TcpListener::bind(config.addr)?;
+    let listener = ListenerBuilder::new(config.addr)
+        .name("TMS API listen_address")
+        .bind()?;
+    listener.set_nonblocking(true)?;
     let port = config.addr.port();
-    thread::spawn(move || {
-        let n_workers = 10;
-        let pool = ThreadPool::new(n_workers);
-        for stream in listener.incoming() {
-            match stream {
-                Ok(stream) => {
-                    let tee = tee.clone();
-                    pool.execute(move || {
-                        debug!("new client from {:?}", stream.peer_addr());
-                        let fd = stream.into_raw_fd();
-                        let input = ServeConnectionInput::new(fd, port);
-                        let cmd = ECallCommand::ServeConnection;
-                        let _ = tee.invoke::<ServeConnectionInput, ServeConnectionOutput>(
-                            cmd.into(),
-                            input,
-                        );
-                    });
+    let handle = thread::spawn(move || {
+        let pool = connection_pool(tee.clone(), port);
+        while !shutdown.load(Ordering::SeqCst) {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    if !pool.dispatch(stream) {
+                        warn!("Rejecting connection: no worker is free");
+                    }
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    thread::sleep(ACCEPT_POLL_INTERVAL)
                 }
                 Err(e) => warn!("couldn't get client: {:?}", e),
             }
         }
+        pool.join();
     });
-    Ok(())
+    Ok(handle)
 }
 
-fn run_tms_service(tee: Arc<TeeBinder>) -> Result<()> {
+// Periodically writes the enclave metrics to `<export_dir>/tms.prom`, if an
+// export directory is configured.
+fn launch_metrics_exporter(
+    tee: Arc<TeeBinder>,
+    shutdown: Arc<AtomicBool>,
+) -> Option<thread::JoinHandle<()>> {
+    let metrics_config = &config::runtime_config().metrics;
+    let export_dir = metrics_config.export_dir.as_ref()?;
+    let interval = Duration::from_secs(metrics_config.export_interval);
+    let path = export_dir.join("tms.prom");
+    // Written first and renamed so scrapers never read a partial file
+    let tmp_path = export_dir.join("tms.prom.tmp");
+    let handle = thread::spawn(move || {
+        let mut last_export: Option<Instant> = None;
+        while !shutdown.load(Ordering::SeqCst) {
+            if last_export.map_or(true, |time| time.elapsed() >= interval) {
+                last_export = Some(Instant::now());
+                let result = tee.metrics().and_then(|metrics| {
+                    fs::write(&tmp_path, metrics)?;
+                    fs::rename(&tmp_path, &path)?;
+                    Ok(())
+                });
+                if let Err(e) = result {
+                    warn!("couldn't export metrics: {:?}", e);
+                }
+            }
+            thread::sleep(ACCEPT_POLL_INTERVAL);
+        }
+    });
+    Some(handle)
+}
+
+// Periodically promotes the scheduled tasks whose time has come, and deletes
+// the tasks whose retention period has ended.
+fn launch_maintenance(tee: Arc<TeeBinder>, shutdown: Arc<AtomicBool>) -> thread::JoinHandle<()> {
+    let task_config = &config::runtime_config().task_management;
+    let schedule_interval = Duration::from_secs(task_config.schedule_interval);
+    let sweep_interval = Duration::from_secs(task_config.sweep_interval);
+    thread::spawn(move || {
+        let mut last_run = Instant::now();
+        let mut last_sweep = Instant::now();
+        while !shutdown.load(Ordering::SeqCst) {
+            if last_run.elapsed() >= schedule_interval {
+                last_run = Instant::now();
+                let sweep = last_sweep.elapsed() >= sweep_interval;
+                if sweep {
+                    last_sweep = Instant::now();
+                }
+                if let Err(e) = tee.run_maintenance(sweep) {
+                    warn!("couldn't run task maintenance: {:?}", e);
+                }
+            }
+            thread::sleep(ACCEPT_POLL_INTERVAL);
+        }
+    })
+}
+
+fn run_tms_service(tee: Arc<TeeBinder>, shutdown: Arc<AtomicBool>) -> Result<()> {
     trace!("Running as TMS Server ...");
 
-    launch_external_server(tee.clone())?;
+    let tdfs_address = config::runtime_config()
+        .internal_endpoints
+        .tdfs
+        .advertised_address;
+    wait_reachable("TDFS internal advertised_address", tdfs_address)?;
+
+    let external_server = launch_external_server(tee.clone(), shutdown.clone())?;
+    let metrics_exporter = launch_metrics_exporter(tee.clone(), shutdown.clone());
+    let maintenance = launch_maintenance(tee.clone(), shutdown.clone());
     let config = config::Internal::tms();
-    let listener = TcpListener::bind(config.addr)?;
+    let listener = ListenerBuilder::new(config.addr)
+        .name("TMS internal listen_address")
+        .bind()?;
+    listener.set_nonblocking(true)?;
     let port = config.addr.port();
-    let n_workers = 10;
-    let pool = ThreadPool::new(n_workers);
-    for stream in listener.incoming() {
-        match stream {
-            Ok(stream) => {
-                let tee = tee.clone();
-                pool.execute(move || {
-                    debug!("new worker from {:?}", stream.peer_addr());
-                    let fd = stream.into_raw_fd();
-                    let input = ServeConnectionInput::new(fd, port);
-                    let cmd = ECallCommand::ServeConnection;
-                    let _ = tee
-                        .invoke::<ServeConnectionInput, ServeConnectionOutput>(cmd.into(), input);
-                });
+    let pool = connection_pool(tee.clone(), port);
+    while !shutdown.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                if !pool.dispatch(stream) {
+                    warn!("Rejecting connection: no worker is free");
+                }
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(ACCEPT_POLL_INTERVAL)
             }
             Err(e) => warn!("couldn't get client: {:?}", e),
         }
     }
 
+    // Stop the trusted servers and wait for in-flight connections
+    tee.finalize();
+    pool.join();
+    let _ = external_server.join();
+    if let Some(metrics_exporter) = metrics_exporter {
+        let _ = metrics_exporter.join();
+    }
+    let _ = maintenance.join();
+
     Ok(())
 }
+
+// Hands the connections accepted by the listener on `port` to the enclave.
+fn connection_pool(tee: Arc<TeeBinder>, port: u16) -> ConnectionPool<TcpStream> {
+    let rejecting_tee = tee.clone();
+    ConnectionPool::from_runtime_config(
+        move |stream| serve_connection(&tee, stream, port, false),
+        move |stream| serve_connection(&rejecting_tee, stream, port, true),
+    )
+}
+
+fn serve_connection(tee: &TeeBinder, stream: TcpStream, port: u16, reject: bool) {
+    debug!("new client from {:?}", stream.peer_addr());
+    let _ = stream.set_nonblocking(false);
+    let fd = stream.into_raw_fd();
+    let input = if reject {
+        ServeConnectionInput::new_rejected(fd, port)
+    } else {
+        ServeConnectionInput::new(fd, port)
+    };
+    let cmd = ECallCommand::ServeConnection;
+    let _ = tee.invoke::<ServeConnectionInput, ServeConnectionOutput>(cmd.into(), input);
+}
@@ -0,0 +1,138 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// Files and functions are accessible to their owners and the users they are
+// shared with. When `access_control.enabled` is set in the runtime config, the
+// access these checks deny is asked to ACS, which grants it through the
+// relationships it keeps, e.g. the groups a user belongs to. ACS is reached
+// over attested connections like TDFS, and the access it grants is
+// remembered for `access_control.decision_cache_ttl` seconds. Access is denied
+// when ACS cannot be asked.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use acs_client::{ACSChannelPool, ACSClient};
+use lazy_static::lazy_static;
+use mesatee_core::config;
+use mesatee_core::rpc::authorization::{
+    Access, AccessEvaluator, AccessQuery, CachedAccessEvaluator,
+};
+use mesatee_core::rpc::channel::RetryPolicy;
+use mesatee_core::{Error, ErrorKind, Result};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::tms_external::tdfs_client;
+use tdfs_internal_client::TDFSClient;
+
+const ACS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+const ACS_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+// Number of granted accesses remembered at once.
+const DECISION_CACHE_CAPACITY: usize = 4096;
+
+// Asks ACS over the shared connections.
+struct AcsEvaluator {
+    pool: Arc<ACSChannelPool>,
+}
+
+impl AccessEvaluator for AcsEvaluator {
+    fn evaluate(&self, query: &AccessQuery) -> Result<bool> {
+        let mut client = ACSClient::with_pool(self.pool.clone());
+        let user_id = query.user_id.clone();
+        let object_id = query.object_id.clone();
+        match query.access {
+            Access::ReadData => client.enforce_data_read(user_id, object_id),
+            Access::UseFunction => client.enforce_script_use(user_id, object_id),
+        }
+    }
+}
+
+lazy_static! {
+    static ref ACS_EVALUATOR: CachedAccessEvaluator<AcsEvaluator> = {
+        let runtime_config = config::runtime_config();
+        let endpoint = &runtime_config.internal_endpoints.acs;
+        let pool = ACSChannelPool::new(config::Internal::target_acs(), endpoint.pool_size)
+            .resolve_target(config::Internal::target_acs)
+            .max_age(Duration::from_secs(
+                runtime_config.attestation.report_cache_ttl,
+            ))
+            .retry_policy(RetryPolicy::new(3, Duration::from_millis(200)))
+            .handshake_timeout(ACS_HANDSHAKE_TIMEOUT)
+            .timeout(ACS_REQUEST_TIMEOUT);
+        CachedAccessEvaluator::new(
+            AcsEvaluator {
+                pool: Arc::new(pool),
+            },
+            Duration::from_secs(runtime_config.access_control.decision_cache_ttl),
+            DECISION_CACHE_CAPACITY,
+        )
+    };
+}
+
+fn enabled() -> bool {
+    config::runtime_config().access_control.enabled
+}
+
+// Fails with `PermissionDenied` unless ACS grants `access` of `object_id` to
+// `user_id`. Without ACS, nothing beyond the own checks of TMS is granted.
+pub fn check(user_id: &str, access: Access, object_id: &str) -> Result<()> {
+    if !enabled() {
+        return Err(Error::from(ErrorKind::PermissionDenied));
+    }
+    let query = AccessQuery::new(user_id, access, object_id);
+    ACS_EVALUATOR.check(&query).map_err(|e| {
+        if e.kind() == ErrorKind::PermissionDenied {
+            e
+        } else {
+            Error::new(
+                ErrorKind::PermissionDenied,
+                format!("access control is unavailable: {}", e),
+            )
+        }
+    })
+}
+
+// Fails with `PermissionDenied` unless `user_id` may read all the files
+// `file_ids`, or `NotFound` if one of them does not exist.
+pub fn check_file_access(file_ids: &[String], user_id: &str) -> Result<()> {
+    for file_id in tdfs_client().denied_files(file_ids, user_id)?.iter() {
+        check(user_id, Access::ReadData, file_id)?;
+    }
+    Ok(())
+}
+
+// Why each of the files `file_ids` cannot be read by `user_id`, by index of
+// the file, so that the files are reported together.
+pub fn file_access_errors(file_ids: &[String], user_id: &str) -> Result<Vec<(usize, Error)>> {
+    let file_infos = tdfs_client().get_files(file_ids)?;
+    let mut errors = Vec::new();
+    for (index, (file_id, file_info)) in file_ids.iter().zip(file_infos.iter()).enumerate() {
+        let result = match file_info {
+            Some(file_info) if TDFSClient::check_permission(file_info, user_id) => Ok(()),
+            Some(_) => check(user_id, Access::ReadData, file_id),
+            None => Err(Error::new(
+                ErrorKind::NotFound,
+                format!("file {} does not exist", file_id),
+            )),
+        };
+        if let Err(e) = result {
+            errors.push((index, e));
+        }
+    }
+    Ok(errors)
+}
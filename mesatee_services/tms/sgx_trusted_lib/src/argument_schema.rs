@@ -0,0 +1,137 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use mesatee_core::{Error, ErrorKind, FieldErrors, Result};
+use serde_json::Value;
+
+use crate::data_store::{ArgumentSpec, ArgumentType, TaskArguments};
+
+fn has_type(arg_type: ArgumentType, value: &Value) -> bool {
+    match arg_type {
+        ArgumentType::String | ArgumentType::Enum => value.is_string(),
+        ArgumentType::Integer => value.is_i64() || value.is_u64(),
+        ArgumentType::Bool => value.is_boolean(),
+    }
+}
+
+// Returns why `value` is not valid for `spec`, if it is not.
+fn check_value(spec: &ArgumentSpec, value: &Value) -> Option<String> {
+    if !has_type(spec.arg_type, value) {
+        return Some(format!(
+            "argument {} must be of type {:?}, got {}",
+            spec.name, spec.arg_type, value
+        ));
+    }
+    if !spec.allowed_values.is_empty() && !spec.allowed_values.contains(value) {
+        let allowed: Vec<String> = spec.allowed_values.iter().map(|v| v.to_string()).collect();
+        return Some(format!(
+            "argument {} must be one of [{}], got {}",
+            spec.name,
+            allowed.join(", "),
+            value
+        ));
+    }
+    None
+}
+
+fn invalid(message: String) -> Error {
+    Error::new(ErrorKind::InvalidInputError, message)
+}
+
+// Checks the argument schema of a function being registered.
+pub(crate) fn check_schema(schema: &[ArgumentSpec]) -> Result<()> {
+    let mut errors = FieldErrors::new();
+    for (index, spec) in schema.iter().enumerate() {
+        let field_path = format!("[{}]", index);
+        let mut push = |message| errors.push(&field_path, invalid(message));
+        if spec.name.is_empty() {
+            push(format!("argument {} has no name", index));
+            continue;
+        }
+        if schema[..index].iter().any(|s| s.name == spec.name) {
+            push(format!("duplicate argument {}", spec.name));
+        }
+        if spec.arg_type == ArgumentType::Enum && spec.allowed_values.is_empty() {
+            push(format!(
+                "enumerated argument {} has no allowed values",
+                spec.name
+            ));
+        }
+        for value in spec.allowed_values.iter() {
+            if !has_type(spec.arg_type, value) {
+                push(format!(
+                    "allowed value {} of argument {} is not of type {:?}",
+                    value, spec.name, spec.arg_type
+                ));
+            }
+        }
+        if let Some(ref default) = spec.default {
+            if spec.required {
+                push(format!("required argument {} has a default", spec.name));
+            } else if let Some(error) = check_value(spec, default) {
+                push(format!("invalid default: {}", error));
+            }
+        }
+    }
+    errors.into_result()
+}
+
+// Checks the arguments of a task against the schema of its function, and
+// returns them with the defaults of arguments that are not supplied. All the
+// offending arguments are listed in the error.
+pub(crate) fn apply_schema(
+    schema: &Option<Vec<ArgumentSpec>>,
+    arguments: &TaskArguments,
+) -> Result<TaskArguments> {
+    let schema = match schema {
+        Some(schema) => schema,
+        None => return Ok(arguments.clone()),
+    };
+
+    let mut errors = FieldErrors::new();
+    for name in arguments.keys() {
+        if !schema.iter().any(|spec| &spec.name == name) {
+            errors.push(name, invalid(format!("unknown argument {}", name)));
+        }
+    }
+
+    let mut applied = TaskArguments::new();
+    for spec in schema.iter() {
+        match (arguments.get(&spec.name), &spec.default) {
+            (Some(value), _) => match check_value(spec, value) {
+                Some(error) => errors.push(&spec.name, invalid(error)),
+                None => {
+                    applied.insert(spec.name.to_owned(), value.clone());
+                }
+            },
+            (None, Some(default)) => {
+                applied.insert(spec.name.to_owned(), default.clone());
+            }
+            (None, None) if spec.required => {
+                errors.push(
+                    &spec.name,
+                    invalid(format!("missing required argument {}", spec.name)),
+                );
+            }
+            (None, None) => {}
+        }
+    }
+    errors.into_result()?;
+    Ok(applied)
+}
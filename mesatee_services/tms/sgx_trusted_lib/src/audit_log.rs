@@ -0,0 +1,183 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// Requests changing functions or tasks are recorded in the audit log kept by
+// TDFS, with the requesting user and the outcome of the operation. Unlike
+// the debug log, entries are hash-chained by TDFS so that changing or removing
+// an entry can be detected.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use crate::tms_external::tdfs_client;
+use mesatee_core::Result;
+use tms_external_proto::{AuditEvent, CreateTaskResult, TaskRequest, TaskResponse};
+
+// Identity of operations TMS runs on its own, e.g. deleting expired tasks.
+pub const SYSTEM_USER_ID: &str = "tms";
+
+// Recording is best effort like recording tasks, the operation has already
+// been applied when it is recorded.
+pub fn record(user_id: &str, event: &AuditEvent) {
+    let event_json = match serde_json::to_string(event) {
+        Ok(event_json) => event_json,
+        Err(e) => {
+            warn!("Cannot serialize audit event {:?}: {}", event, e);
+            return;
+        }
+    };
+    if let Err(e) = tdfs_client().append_audit_entry(user_id, &event_json) {
+        warn!("Cannot record audit event {:?}: {}", event, e);
+    }
+}
+
+// An audited request, recorded once it has been handled.
+pub struct AuditedRequest {
+    user_id: String,
+    event: AuditEvent,
+}
+
+impl AuditedRequest {
+    // `None` for requests which change nothing.
+    pub fn of(request: &TaskRequest) -> Option<AuditedRequest> {
+        let (user_id, operation, targets, users) = match request {
+            TaskRequest::Create(req) => (&req.user_id, "create_task", vec![], vec![]),
+            TaskRequest::CreateTasks(req) => (&req.user_id, "create_tasks", vec![], vec![]),
+            TaskRequest::RerunTask(req) => (
+                &req.user_id,
+                "rerun_task",
+                vec![req.task_id.to_owned()],
+                vec![],
+            ),
+            TaskRequest::InvokeFunction(req) => (
+                &req.user_id,
+                "invoke_function",
+                vec![req.function_id.to_owned()],
+                vec![],
+            ),
+            TaskRequest::Update(req) => (
+                &req.user_id,
+                "update_task",
+                vec![req.task_id.to_owned()],
+                vec![],
+            ),
+            TaskRequest::Cancel(req) => (
+                &req.user_id,
+                "cancel_task",
+                vec![req.task_id.to_owned()],
+                vec![],
+            ),
+            TaskRequest::Delete(req) => (
+                &req.user_id,
+                "delete_task",
+                vec![req.task_id.to_owned()],
+                vec![],
+            ),
+            TaskRequest::RegisterFunction(req) => (
+                &req.user_id,
+                "register_function",
+                vec![],
+                req.collaborators.clone(),
+            ),
+            TaskRequest::UpdateFunction(req) => (
+                &req.user_id,
+                "update_function",
+                vec![req.function_id.to_owned()],
+                vec![],
+            ),
+            TaskRequest::ShareFunction(req) => (
+                &req.user_id,
+                "share_function",
+                vec![req.function_id.to_owned()],
+                req.user_ids.clone(),
+            ),
+            TaskRequest::UnshareFunction(req) => (
+                &req.user_id,
+                "unshare_function",
+                vec![req.function_id.to_owned()],
+                req.user_ids.clone(),
+            ),
+            TaskRequest::QueryAuditLog(req) => (&req.user_id, "query_audit_log", vec![], vec![]),
+            TaskRequest::ReloadEnclaveInfo(req) => {
+                (&req.user_id, "reload_enclave_info", vec![], vec![])
+            }
+            TaskRequest::RevokeUser(req) => (
+                &req.user_id,
+                "revoke_user",
+                vec![],
+                vec![req.revoked_user_id.to_owned()],
+            ),
+            TaskRequest::UnrevokeUser(req) => (
+                &req.user_id,
+                "unrevoke_user",
+                vec![],
+                vec![req.revoked_user_id.to_owned()],
+            ),
+            TaskRequest::PutSecret(req) => (
+                &req.user_id,
+                "put_secret",
+                vec![req.name.to_owned()],
+                req.readers.clone(),
+            ),
+            TaskRequest::DeleteSecret(req) => (
+                &req.user_id,
+                "delete_secret",
+                vec![req.name.to_owned()],
+                vec![],
+            ),
+            _ => return None,
+        };
+        Some(AuditedRequest {
+            user_id: user_id.to_owned(),
+            event: AuditEvent {
+                operation: operation.to_owned(),
+                targets,
+                users,
+                error: None,
+            },
+        })
+    }
+
+    // Tasks and functions created by the request are only known from its
+    // response.
+    pub fn record(mut self, result: &Result<TaskResponse>) {
+        match result {
+            Ok(TaskResponse::Create(resp)) | Ok(TaskResponse::RerunTask(resp)) => {
+                self.event.targets.push(resp.task_id.to_owned())
+            }
+            Ok(TaskResponse::CreateTasks(resp)) => {
+                for result in resp.results.iter() {
+                    if let CreateTaskResult::Created(created) = result {
+                        self.event.targets.push(created.task_id.to_owned());
+                    }
+                }
+            }
+            Ok(TaskResponse::InvokeFunction(resp)) => {
+                self.event.targets.push(resp.task_id.to_owned())
+            }
+            Ok(TaskResponse::RegisterFunction(resp)) => {
+                self.event.targets.push(resp.function_id.to_owned())
+            }
+            Ok(TaskResponse::UpdateFunction(resp)) => {
+                self.event.targets.push(resp.function_id.to_owned())
+            }
+            Ok(_) => {}
+            Err(e) => self.event.error = Some(format!("{}", e)),
+        }
+        record(&self.user_id, &self.event);
+    }
+}
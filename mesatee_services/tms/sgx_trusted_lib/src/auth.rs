@@ -0,0 +1,150 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// Requests sent on behalf of a user are rejected with `Unauthenticated`
+// before dispatch unless the token of the user is valid and the user is not
+// revoked, so that handlers may trust the user ID of a request.
+//
+// Authenticated requests are then rejected with `PermissionDenied` unless the
+// user has the role their method requires by the `[authorization]` section of
+// the runtime config.
+//
+// Handlers check that the user of their request was authenticated, so that a
+// server set up without `AuthInterceptor` fails closed.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use crate::audit_log::AuditedRequest;
+use crate::data_store::verify_user;
+use crate::user_revocation;
+use lazy_static::lazy_static;
+use mesatee_core::config;
+use mesatee_core::rpc::authorization::RolePolicy;
+use mesatee_core::rpc::interceptor::{Interceptor, RequestContext};
+use mesatee_core::{Error, ErrorKind, Result};
+use std::cell::RefCell;
+use tms_external_proto::{TaskRequest, TaskResponse};
+
+pub const ADMIN_ROLE: &str = "admin";
+
+// Methods requiring the admin role unless configured otherwise.
+const ADMIN_METHODS: &[&str] = &[
+    "QueryAuditLog",
+    "RevokeUser",
+    "UnrevokeUser",
+    "ReloadEnclaveInfo",
+];
+
+thread_local! {
+    // User authenticated by `AuthInterceptor` for the request being handled
+    // on this thread.
+    static AUTHENTICATED_USER: RefCell<Option<String>> = RefCell::new(None);
+}
+
+lazy_static! {
+    static ref ROLE_POLICY: RolePolicy = {
+        let runtime_config = config::runtime_config();
+        let authorization = &runtime_config.authorization;
+        let mut policy = RolePolicy::new().grant(ADMIN_ROLE, &runtime_config.audit_log.admins);
+        for (role, users) in authorization.roles.iter() {
+            policy = policy.grant(role, users);
+        }
+        for method in ADMIN_METHODS {
+            policy = policy.require(method, ADMIN_ROLE);
+        }
+        for (method, role) in authorization.method_roles.iter() {
+            policy = policy.require(method, role);
+        }
+        policy
+    };
+}
+
+pub fn has_role(user_id: &str, role: &str) -> bool {
+    ROLE_POLICY.has_role(user_id, role)
+}
+
+// Rejected requests are audited like failing requests
+fn audit_rejection(request: &TaskRequest, error: &Error) {
+    if let Some(audited) = AuditedRequest::of(request) {
+        audited.record(&Err(Error::from(error.kind())));
+    }
+}
+
+// Fails with `Unauthenticated` unless `AuthInterceptor` authenticated the
+// user of `request`, for requests sent on behalf of a user.
+pub fn check_authenticated(request: &TaskRequest) -> Result<()> {
+    let user_id = match request.credentials() {
+        Some((user_id, _)) => user_id,
+        None => return Ok(()),
+    };
+    let authenticated =
+        AUTHENTICATED_USER.with(|user| user.borrow().as_ref().map(String::as_str) == Some(user_id));
+    if !authenticated {
+        error!(
+            "Request of user {} reached its handler unauthenticated",
+            user_id
+        );
+        return Err(Error::from(ErrorKind::Unauthenticated));
+    }
+    Ok(())
+}
+
+pub struct AuthInterceptor;
+
+impl Interceptor<TaskRequest, TaskResponse> for AuthInterceptor {
+    fn before(&self, context: &RequestContext, request: &TaskRequest) -> Result<()> {
+        AUTHENTICATED_USER.with(|user| *user.borrow_mut() = None);
+        let (user_id, user_token) = match request.credentials() {
+            Some(credentials) => credentials,
+            None => return Ok(()),
+        };
+        let error = if !verify_user(user_id, user_token) {
+            Error::from(ErrorKind::Unauthenticated)
+        } else if user_revocation::is_revoked(user_id) {
+            Error::new(ErrorKind::Unauthenticated, "user is revoked")
+        } else {
+            AUTHENTICATED_USER.with(|user| *user.borrow_mut() = Some(user_id.to_owned()));
+            return Ok(());
+        };
+        debug!("Rejecting {} request of user {}", context.method, user_id);
+        audit_rejection(request, &error);
+        Err(error)
+    }
+
+    fn after(&self, _context: &RequestContext, _result: &mut Result<TaskResponse>) {
+        AUTHENTICATED_USER.with(|user| *user.borrow_mut() = None);
+    }
+}
+
+// Runs after `AuthInterceptor`, which verified the user of the request.
+pub struct RoleInterceptor;
+
+impl Interceptor<TaskRequest, TaskResponse> for RoleInterceptor {
+    fn before(&self, context: &RequestContext, request: &TaskRequest) -> Result<()> {
+        let user_id = match request.credentials() {
+            Some((user_id, _)) => user_id,
+            None => return Ok(()),
+        };
+        if let Err(error) = ROLE_POLICY.check(context.method, user_id) {
+            debug!("Denying {} request of user {}", context.method, user_id);
+            audit_rejection(request, &error);
+            return Err(error);
+        }
+        Ok(())
+    }
+}
@@ -18,21 +18,70 @@
 #[cfg(feature = "mesalock_sgx")]
 use std::prelude::v1::*;
 
-use mesatee_core::db::Memdb;
-use mesatee_core::Result;
-use std::collections::HashSet;
+use mesatee_core::config;
+use mesatee_core::db::{Kvdb, Memdb};
+use mesatee_core::rpc::authorization::Access;
+use mesatee_core::worker_pool::WorkerPool;
+use mesatee_core::{Error, ErrorKind, Result};
+use std::collections::{BTreeMap, HashSet};
 use std::fmt::Write;
 use std::net::{IpAddr, Ipv4Addr};
 use std::sync::SgxMutex;
+use std::time::Duration;
+use teaclave_config::runtime_config::ResourceLimitsConfig;
 
+pub use tms_common_proto::python_modules;
+pub use tms_common_proto::ArgumentSpec;
+pub use tms_common_proto::ArgumentType;
+pub use tms_common_proto::ArgumentsFile;
 pub use tms_common_proto::CollaboratorStatus;
+pub use tms_common_proto::DynamicOutput;
+pub use tms_common_proto::DynamicOutputBindings;
+pub use tms_common_proto::Executor;
+pub use tms_common_proto::ExecutorCapability;
+pub use tms_common_proto::FileFormat;
+pub use tms_common_proto::FunctionInfo;
 pub use tms_common_proto::FunctionType;
+pub use tms_common_proto::Labels;
+pub use tms_common_proto::OutputKey;
+pub use tms_common_proto::PortBindings;
+pub use tms_common_proto::PortEdges;
+pub use tms_common_proto::PortSpec;
+pub use tms_common_proto::ReceiptFile;
+pub use tms_common_proto::ResolvedCollection;
+pub use tms_common_proto::ResourceLimits;
+pub use tms_common_proto::ResultFormat;
+pub use tms_common_proto::SignedTaskReceipt;
+pub use tms_common_proto::TaskArguments;
+pub use tms_common_proto::TaskAttempt;
+pub use tms_common_proto::TaskError;
 pub use tms_common_proto::TaskFile;
 pub use tms_common_proto::TaskInfo;
+pub use tms_common_proto::TaskLogLine;
+pub use tms_common_proto::TaskReceipt;
+pub use tms_common_proto::TaskRetryPolicy;
+pub use tms_common_proto::TaskSecret;
 pub use tms_common_proto::TaskStatus;
+pub use tms_common_proto::ANY_DATA_TYPE;
+pub use tms_common_proto::OUTPUT_KEK_LEN;
+pub use tms_common_proto::PYTHON_FUNCTION_NAME;
+pub use tms_common_proto::WASM_FUNCTION_NAME;
 
+use crate::access_control;
+use crate::scheduler::Scheduler;
+use crate::status_notifier::StatusNotifier;
+use crate::task_index;
+use crate::task_logs::TaskLogs;
+use crate::task_recovery;
+use crate::task_retention;
+use crate::task_schedule;
 use lazy_static::lazy_static;
 
+// Bounded below the number of TMS server threads so that waiting clients
+// cannot block other requests.
+const MAX_STATUS_WAITERS: usize = 5;
+const MAX_LOG_TAILERS: usize = 3;
+
 lazy_static! {
     // At this moment, this is just a workaround;
     // Later the data structure will be redesigned according to the persistent db, lock mechanism  and architecture.
@@ -44,6 +93,215 @@ lazy_static! {
     };
 
     pub static ref UPDATELOCK: SgxMutex<u32> = SgxMutex::new(0);
+
+    // Task created with an idempotency key, keyed by `idempotency_index`.
+    // Keys expire at the end of the idempotency window of their task.
+    pub static ref IDEMPOTENCY_KEY_STORE: Kvdb = Kvdb::open().expect("cannot open db");
+
+    // Tasks waiting for a task to finish, keyed by the ID of that task.
+    pub static ref TASK_DEPENDENTS_STORE: Memdb<String, HashSet<String>> = {
+        Memdb::<String, HashSet<String>>::open().expect("cannot open db")
+    };
+    // Serializes task status transitions. Taken before UPDATELOCK when both
+    // are needed.
+    pub static ref TASK_STATUS_LOCK: SgxMutex<u32> = SgxMutex::new(0);
+    pub static ref TASK_STATUS_NOTIFIER: StatusNotifier = StatusNotifier::new(MAX_STATUS_WAITERS);
+    pub static ref TASK_LOGS: TaskLogs =
+        TaskLogs::new(MAX_LOG_TAILERS, config::runtime_config().task_logs.max_bytes);
+    pub static ref TASK_SCHEDULER: Scheduler =
+        Scheduler::new(config::runtime_config().task_management.priority_aging_interval);
+    // Registered execution workers, and the tasks they were handed.
+    pub static ref WORKER_POOL: WorkerPool =
+        WorkerPool::new(config::runtime_config().task_management.worker_heartbeat_timeout);
+
+    // Keyed by versioned function ID, i.e. `<id>@v<version>`.
+    pub static ref FUNCTION_STORE: Memdb<String, FunctionInfo> = {
+        Memdb::<String, FunctionInfo>::open().expect("cannot open db")
+    };
+    // Latest version of each function, keyed by unversioned function ID.
+    pub static ref FUNCTION_VERSION_STORE: Memdb<String, u32> = {
+        Memdb::<String, u32>::open().expect("cannot open db")
+    };
+    // Unversioned IDs of the functions registered by each user.
+    pub static ref USER_FUNCTION_STORE: Memdb<String, HashSet<String>> = {
+        Memdb::<String, HashSet<String>>::open().expect("cannot open db")
+    };
+    // Users besides the owner allowed to use a function, keyed by unversioned
+    // function ID. Applies to all versions of the function.
+    pub static ref FUNCTION_ACL_STORE: Memdb<String, HashSet<String>> = {
+        Memdb::<String, HashSet<String>>::open().expect("cannot open db")
+    };
+    pub static ref FUNCTION_LOCK: SgxMutex<u32> = SgxMutex::new(0);
+}
+
+pub fn versioned_function_id(function_id: &str, version: u32) -> String {
+    format!("{}@v{}", function_id, version)
+}
+
+// Splits `<id>@v<version>` into its parts. The version is `None` if
+// `function_id` is not versioned.
+pub fn parse_function_id(function_id: &str) -> Result<(&str, Option<u32>)> {
+    let mut parts = function_id.splitn(2, '@');
+    let id = parts.next().unwrap_or_default();
+    let version = match parts.next() {
+        Some(version) => {
+            let version = if version.starts_with('v') {
+                version[1..].parse::<u32>().ok()
+            } else {
+                None
+            };
+            match version {
+                Some(version) if version > 0 => Some(version),
+                _ => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInputError,
+                        format!("invalid function version in {}", function_id),
+                    ))
+                }
+            }
+        }
+        None => None,
+    };
+    if id.is_empty() {
+        return Err(Error::new(
+            ErrorKind::InvalidInputError,
+            "function id is empty",
+        ));
+    }
+    Ok((id, version))
+}
+
+// Returns the requested version of a function, or the latest version if
+// `function_id` is not versioned.
+pub fn get_function(function_id: &str) -> Result<FunctionInfo> {
+    let (id, version) = parse_function_id(function_id)?;
+    let version = match version {
+        Some(version) => version,
+        None => FUNCTION_VERSION_STORE
+            .get(&id.to_owned())?
+            .ok_or_else(|| Error::from(ErrorKind::NotFound))?,
+    };
+    FUNCTION_STORE
+        .get(&versioned_function_id(id, version))?
+        .ok_or_else(|| Error::from(ErrorKind::NotFound))
+}
+
+// Stores `function_info` as the next version of function `function_id`, or
+// as its first version if the function does not exist yet.
+pub fn add_function_version(function_id: &str, mut function_info: FunctionInfo) -> Result<String> {
+    let _lock = FUNCTION_LOCK.lock()?;
+    let version = match FUNCTION_VERSION_STORE.get(&function_id.to_owned())? {
+        Some(latest) => latest + 1,
+        None => 1,
+    };
+    let versioned_id = versioned_function_id(function_id, version);
+    function_info.function_id = versioned_id.clone();
+    function_info.version = version;
+    FUNCTION_STORE.set(&versioned_id, &function_info)?;
+    FUNCTION_VERSION_STORE.set(&function_id.to_owned(), &version)?;
+    if version == 1 {
+        let owner = function_info.owner.to_owned();
+        let mut function_ids = USER_FUNCTION_STORE.get(&owner)?.unwrap_or_default();
+        function_ids.insert(function_id.to_owned());
+        USER_FUNCTION_STORE.set(&owner, &function_ids)?;
+    }
+    Ok(versioned_id)
+}
+
+// Whether `user_id` owns function `function_info` or it is shared with them.
+pub fn check_function_permission(function_info: &FunctionInfo, user_id: &str) -> Result<bool> {
+    if function_info.owner == user_id {
+        return Ok(true);
+    }
+    let (id, _) = parse_function_id(&function_info.function_id)?;
+    let collaborators = FUNCTION_ACL_STORE.get(&id.to_owned())?;
+    Ok(collaborators.map_or(false, |users| users.contains(user_id)))
+}
+
+// Like `get_function`, but fails with `PermissionDenied` unless `user_id` may
+// use the function, as its owner, a collaborator, or through the
+// relationships ACS keeps.
+pub fn get_function_for_user(function_id: &str, user_id: &str) -> Result<FunctionInfo> {
+    let function_info = get_function(function_id)?;
+    if !check_function_permission(&function_info, user_id)? {
+        let (id, _) = parse_function_id(&function_info.function_id)?;
+        access_control::check(user_id, Access::UseFunction, id)?;
+    }
+    Ok(function_info)
+}
+
+// Adds `add` to and removes `remove` from the collaborators of function
+// `function_id`, and returns the resulting collaborators in ascending order.
+pub fn update_function_acl(
+    function_id: &str,
+    add: &[String],
+    remove: &[String],
+) -> Result<Vec<String>> {
+    let _lock = FUNCTION_LOCK.lock()?;
+    let id = function_id.to_owned();
+    let mut collaborators = FUNCTION_ACL_STORE.get(&id)?.unwrap_or_default();
+    for user_id in add.iter() {
+        collaborators.insert(user_id.to_string());
+    }
+    for user_id in remove.iter() {
+        collaborators.remove(user_id);
+    }
+    if collaborators.is_empty() {
+        FUNCTION_ACL_STORE.del(&id)?;
+    } else {
+        FUNCTION_ACL_STORE.set(&id, &collaborators)?;
+    }
+    let mut collaborators: Vec<String> = collaborators.into_iter().collect();
+    collaborators.sort();
+    Ok(collaborators)
+}
+
+// All versions of a function, oldest first.
+pub fn get_function_versions(function_id: &str) -> Result<Vec<FunctionInfo>> {
+    let (id, _) = parse_function_id(function_id)?;
+    let latest = FUNCTION_VERSION_STORE
+        .get(&id.to_owned())?
+        .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+    let mut versions = Vec::new();
+    for version in 1..=latest {
+        if let Some(function_info) = FUNCTION_STORE.get(&versioned_function_id(id, version))? {
+            versions.push(function_info);
+        }
+    }
+    Ok(versions)
+}
+
+// Number of entries of a list request, capped by the configured maximum.
+pub fn page_size(limit: Option<usize>) -> Result<usize> {
+    let max_page_size = config::runtime_config().task_management.max_page_size;
+    match limit {
+        Some(0) => Err(Error::new(
+            ErrorKind::InvalidInputError,
+            "page size must be positive",
+        )),
+        Some(limit) => Ok(std::cmp::min(limit, max_page_size)),
+        None => Ok(max_page_size),
+    }
+}
+
+// Returns at most `limit` IDs following `cursor` in ascending order, and the
+// cursor of the next page, which is empty after the last page. The cursor is
+// the last ID of the page, so IDs added concurrently neither shift nor repeat
+// entries of later pages.
+pub fn paginate(ids: &HashSet<String>, cursor: &str, limit: usize) -> (Vec<String>, String) {
+    let mut page: Vec<String> = ids
+        .iter()
+        .filter(|id| cursor.is_empty() || id.as_str() > cursor)
+        .cloned()
+        .collect();
+    page.sort();
+    let next_token = if page.len() > limit {
+        page.truncate(limit);
+        page[limit - 1].to_string()
+    } else {
+        String::new()
+    };
+    (page, next_token)
 }
 
 pub fn verify_user(_user_id: &str, user_token: &str) -> bool {
@@ -65,6 +323,234 @@ pub fn check_get_permission(task_info: &TaskInfo, user_id: &str) -> bool {
     false
 }
 
+// Fails if a dependency does not exist, or if following the dependencies
+// leads back to `task_id`.
+pub fn check_dependency_cycle(task_id: &str, dependencies: &[String]) -> Result<()> {
+    let mut visited: HashSet<String> = HashSet::new();
+    let mut stack: Vec<String> = dependencies.to_vec();
+    while let Some(current) = stack.pop() {
+        if current == task_id {
+            return Err(Error::new(
+                ErrorKind::InvalidInputError,
+                format!("dependency cycle through task {}", task_id),
+            ));
+        }
+        if !visited.insert(current.clone()) {
+            continue;
+        }
+        let task_info = TASK_STORE.get(&current)?.ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInputError,
+                format!("unknown dependency {}", current),
+            )
+        })?;
+        stack.extend(task_info.dependencies.iter().cloned());
+    }
+    Ok(())
+}
+
+// Results of a finished dependency that become inputs of a task created by
+// `user_id`. Files are passed by ID and never leave TDFS.
+pub fn dependency_outputs(dependency: &TaskInfo, user_id: &str) -> Vec<TaskFile> {
+    let mut files = Vec::new();
+    if let Some(ref file_id) = dependency.task_result_file_id {
+        files.push(TaskFile {
+            user_id: dependency.user_id.to_string(),
+            file_id: file_id.to_string(),
+            format: None,
+        });
+    }
+    for task_file in dependency.output_files.iter() {
+        if task_file.user_id == user_id {
+            files.push(task_file.clone());
+        }
+    }
+    files
+}
+
+// Binds the input ports of `dependent` bound to output ports of its finished
+// dependency `dependency_id` to the files the dependency saved on them for
+// the creator of `dependent`. Fails with the first output port without such a
+// file.
+pub fn bind_input_edges(
+    dependency_id: &str,
+    dependency: &TaskInfo,
+    dependent: &mut TaskInfo,
+) -> std::result::Result<(), String> {
+    for (name, edge) in dependent.input_edges.iter() {
+        if edge.task_id != dependency_id {
+            continue;
+        }
+        let file_id = dependency
+            .output_ports
+            .get(&edge.port)
+            .filter(|file_id| {
+                dependency
+                    .output_files
+                    .iter()
+                    .any(|file| &file.file_id == *file_id && file.user_id == dependent.user_id)
+            })
+            .ok_or_else(|| {
+                format!(
+                    "dependency {} produced no file on output port {}",
+                    dependency_id, edge.port
+                )
+            })?;
+        dependent
+            .input_ports
+            .insert(name.to_string(), file_id.to_string());
+    }
+    Ok(())
+}
+
+// A task is ready once all collaborators of a multiparty task approved it and
+// all its dependencies have finished.
+pub fn is_task_ready(task_info: &TaskInfo) -> Result<bool> {
+    if task_info.function_type == FunctionType::Multiparty
+        && task_info.approved_user_number != task_info.collaborator_list.len()
+    {
+        return Ok(false);
+    }
+    for dependency_id in task_info.dependencies.iter() {
+        match TASK_STORE.get(dependency_id)? {
+            Some(ref dependency) if dependency.status == TaskStatus::Finished => {}
+            _ => return Ok(false),
+        }
+    }
+    Ok(true)
+}
+
+// Stores `task_info` with its status set to `status`, and wakes up clients
+// waiting for the status change.
+// Before calling this function, use TASK_STATUS_LOCK to avoid data race;
+pub fn update_task_status(
+    task_id: &str,
+    task_info: &mut TaskInfo,
+    status: TaskStatus,
+) -> Result<()> {
+    let previous = task_info.status;
+    task_info.status = status;
+    task_info.status_version += 1;
+    // The time may already be set, e.g. to be signed in the receipt of the task.
+    if status.is_final() && task_info.finished_at.is_none() {
+        task_info.finished_at = Some(task_retention::now());
+    }
+    save_task(task_id, task_info)?;
+    task_index::update_status(task_id, task_info, previous)?;
+    if status == TaskStatus::Ready {
+        TASK_SCHEDULER.enqueue(task_id, task_info.priority)?;
+    } else {
+        TASK_SCHEDULER.remove(task_id)?;
+    }
+    // The task leaves its worker unless it starts running there
+    if status != TaskStatus::Running {
+        WORKER_POOL.complete(task_id)?;
+    }
+    TASK_STATUS_NOTIFIER.notify(task_id)?;
+    TASK_LOGS.notify()
+}
+
+// Idempotency keys are scoped by user.
+fn idempotency_index(user_id: &str, idempotency_key: &str) -> String {
+    format!("{}:{}{}", user_id.len(), user_id, idempotency_key)
+}
+
+// Task `user_id` created with `idempotency_key` less than
+// `idempotency_window` seconds ago, if any.
+// Before calling this function, use TASK_STATUS_LOCK to avoid data race;
+pub fn find_idempotent_task(
+    user_id: &str,
+    idempotency_key: &str,
+) -> Result<Option<(String, TaskInfo)>> {
+    let index = idempotency_index(user_id, idempotency_key);
+    let task_id = match IDEMPOTENCY_KEY_STORE.get(&index)? {
+        Some(entry) => entry.value,
+        None => return Ok(None),
+    };
+    let task_info = match TASK_STORE.get(&task_id)? {
+        Some(task_info) => task_info,
+        None => return Ok(None),
+    };
+    Ok(Some((task_id, task_info)))
+}
+
+// Before calling this function, use TASK_STATUS_LOCK to avoid data race;
+pub fn add_dependent(dependency_id: &str, task_id: &str) -> Result<()> {
+    let id = dependency_id.to_owned();
+    let mut dependents = TASK_DEPENDENTS_STORE.get(&id)?.unwrap_or_default();
+    dependents.insert(task_id.to_owned());
+    TASK_DEPENDENTS_STORE.set(&id, &dependents)?;
+    Ok(())
+}
+
+// Called once task `task_id` has finished, failed or been cancelled. Results
+// of a finished task are appended to the inputs of its dependents and bound
+// to their input ports connected to it, and the dependents become ready once all their dependencies have finished. Dependents of a
+// failed or cancelled task fail or are cancelled too.
+// Before calling this function, use TASK_STATUS_LOCK to avoid data race;
+pub fn resolve_dependents(task_id: &str, task_info: &TaskInfo) -> Result<()> {
+    let dependents = match TASK_DEPENDENTS_STORE.del(&task_id.to_owned())? {
+        Some(dependents) => dependents,
+        None => return Ok(()),
+    };
+    for dependent_id in dependents.iter() {
+        let mut dependent = match TASK_STORE.get(dependent_id)? {
+            Some(value) => value,
+            None => continue,
+        };
+        if dependent.status != TaskStatus::Created {
+            continue;
+        }
+        match task_info.status {
+            TaskStatus::Finished => {
+                let files = dependency_outputs(task_info, &dependent.user_id);
+                dependent.input_files.extend(files);
+                if let Err(reason) = bind_input_edges(task_id, task_info, &mut dependent) {
+                    dependent.cancel_reason = Some(reason);
+                    update_task_status(dependent_id, &mut dependent, TaskStatus::Cancelled)?;
+                    resolve_dependents(dependent_id, &dependent)?;
+                    continue;
+                }
+                if is_task_ready(&dependent)? {
+                    let status = task_schedule::ready_status(&dependent);
+                    update_task_status(dependent_id, &mut dependent, status)?;
+                } else {
+                    save_task(dependent_id, &dependent)?;
+                }
+            }
+            TaskStatus::Failed => {
+                update_task_status(dependent_id, &mut dependent, TaskStatus::Failed)?;
+                resolve_dependents(dependent_id, &dependent)?;
+            }
+            TaskStatus::Cancelled => {
+                dependent.cancel_reason = Some(format!("dependency {} was cancelled", task_id));
+                update_task_status(dependent_id, &mut dependent, TaskStatus::Cancelled)?;
+                resolve_dependents(dependent_id, &dependent)?;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn resource_limits_from_config(config: &ResourceLimitsConfig) -> ResourceLimits {
+    ResourceLimits {
+        max_memory_mb: config.max_memory_mb,
+        max_cpu_seconds: config.max_cpu_seconds,
+        max_wall_seconds: config.max_wall_seconds,
+    }
+}
+
+// Limits of tasks created without resource limits.
+pub fn default_resource_limits() -> ResourceLimits {
+    resource_limits_from_config(&config::runtime_config().task_resource_limits.default)
+}
+
+// Upper bound of the limits users can request.
+pub fn maximum_resource_limits() -> ResourceLimits {
+    resource_limits_from_config(&config::runtime_config().task_resource_limits.maximum)
+}
+
 pub fn gen_token() -> Result<String> {
     use rand::prelude::RngCore;
     let mut token: [u8; 16] = [0; 16];
@@ -96,7 +582,30 @@ fn add_task_to_user(task_id: &str, user_id: &str) -> Result<()> {
     Ok(())
 }
 pub fn add_task(task_id: &str, task_info: &TaskInfo) -> Result<()> {
+    index_task(task_id, task_info)?;
+    task_recovery::persist_task(task_id, task_info);
+    Ok(())
+}
+
+// Adds the task to the store and to the tasks of its users, without
+// persisting it. A ready task is queued for the executors.
+pub fn index_task(task_id: &str, task_info: &TaskInfo) -> Result<()> {
     let _ = TASK_STORE.set(&task_id.to_owned(), &task_info)?;
+    task_index::add(task_id, task_info)?;
+    if task_info.status == TaskStatus::Ready {
+        TASK_SCHEDULER.enqueue(task_id, task_info.priority)?;
+    }
+    if let Some(ref idempotency_key) = task_info.idempotency_key {
+        let window = config::runtime_config().task_management.idempotency_window;
+        let ends_at = task_info.created_at.saturating_add(window);
+        let now = task_retention::now();
+        // A recovered task may be past its window already.
+        if ends_at > now {
+            let index = idempotency_index(&task_info.user_id, idempotency_key);
+            let ttl = Duration::from_secs(ends_at - now);
+            IDEMPOTENCY_KEY_STORE.put_with_ttl(&index, task_id, ttl)?;
+        }
+    }
     let _lock = UPDATELOCK.lock()?;
     add_task_to_user(task_id, &task_info.user_id)?;
     for collaborator in task_info.collaborator_list.iter() {
@@ -105,6 +614,51 @@ pub fn add_task(task_id: &str, task_info: &TaskInfo) -> Result<()> {
     Ok(())
 }
 
+// Stores the updated `task_info` of an existing task.
+pub fn save_task(task_id: &str, task_info: &TaskInfo) -> Result<()> {
+    let _ = TASK_STORE.set(&task_id.to_owned(), task_info)?;
+    task_recovery::persist_task(task_id, task_info);
+    Ok(())
+}
+
+// Removes the task from the store, from the tasks of its users and from the
+// dependents of other tasks, without deleting its record.
+// Before calling this function, use TASK_STATUS_LOCK to avoid data race;
+pub fn remove_task(task_id: &str, task_info: &TaskInfo) -> Result<()> {
+    let id = task_id.to_owned();
+    TASK_STORE.del(&id)?;
+    task_index::remove_task(task_id, task_info)?;
+    TASK_DEPENDENTS_STORE.del(&id)?;
+    TASK_SCHEDULER.remove(task_id)?;
+    WORKER_POOL.complete(task_id)?;
+    TASK_LOGS.remove(task_id)?;
+    if let Some(ref idempotency_key) = task_info.idempotency_key {
+        let index = idempotency_index(&task_info.user_id, idempotency_key);
+        if IDEMPOTENCY_KEY_STORE
+            .get(&index)?
+            .map_or(false, |entry| entry.value == task_id)
+        {
+            IDEMPOTENCY_KEY_STORE.delete(&index)?;
+        }
+    }
+    let _lock = UPDATELOCK.lock()?;
+    let mut user_ids = vec![task_info.user_id.as_str()];
+    user_ids.extend(
+        task_info
+            .collaborator_list
+            .iter()
+            .map(|collaborator| collaborator.user_id.as_str()),
+    );
+    for user_id in user_ids {
+        let uid = user_id.to_owned();
+        if let Some(mut task_ids) = USER_TASK_STORE.get(&uid)? {
+            task_ids.remove(task_id);
+            USER_TASK_STORE.set(&uid, &task_ids)?;
+        }
+    }
+    Ok(())
+}
+
 // For API Test, called by enclave_init
 pub fn add_test_information() {
     let fake_task = TaskInfo {
@@ -112,16 +666,67 @@ pub fn add_test_information() {
         collaborator_list: Vec::new(),
         approved_user_number: 0,
         function_name: "echo".to_string(),
+        function_id: None,
         function_type: FunctionType::Single,
         status: TaskStatus::Ready,
+        status_version: 0,
         ip: IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)),
         port: 0,
         task_token: "fake".to_string(),
         input_files: Vec::new(),
         output_files: Vec::new(),
         task_result_file_id: None,
+        resource_limits: default_resource_limits(),
+        dependencies: Vec::new(),
+        cancel_reason: None,
+        input_collections: Vec::new(),
+        arguments: TaskArguments::new(),
+        arguments_file: None,
+        finished_at: None,
+        retention_secs: None,
+        output_key: None,
+        priority: 0,
+        created_at: 0,
+        idempotency_key: None,
+        input_ports: PortBindings::new(),
+        input_edges: PortEdges::new(),
+        output_ports: PortBindings::new(),
+        dynamic_outputs: BTreeMap::new(),
+        progress: Vec::new(),
+        not_before: None,
+        retry_policy: None,
+        attempts: Vec::new(),
+        labels: Labels::new(),
+        receipt: None,
+        secrets: Vec::new(),
+        result_format: ResultFormat::Text,
+        result_file_id: None,
     };
-    let _ = add_task(&"fake".to_owned(), &fake_task);
+    let _ = index_task(&"fake".to_owned(), &fake_task);
+
+    // Staged in this order, the high-priority task must be handed out first
+    let mut fake_low_priority_task = fake_task.clone();
+    fake_low_priority_task.user_id = "fake_scheduler".to_string();
+    fake_low_priority_task.priority = -10;
+    let _ = index_task(
+        &"fake_low_priority_task".to_owned(),
+        &fake_low_priority_task,
+    );
+    let mut fake_high_priority_task = fake_low_priority_task.clone();
+    fake_high_priority_task.priority = 10;
+    let _ = index_task(
+        &"fake_high_priority_task".to_owned(),
+        &fake_high_priority_task,
+    );
+
+    // Retried once after a transient failure
+    let mut fake_retried_task = fake_task.clone();
+    fake_retried_task.status = TaskStatus::Running;
+    fake_retried_task.retry_policy = Some(TaskRetryPolicy {
+        max_attempts: 2,
+        backoff_secs: 0,
+    });
+    let _ = index_task(&"fake_retried_task".to_owned(), &fake_retried_task);
 
     let collaborator_for_fake_task = CollaboratorStatus {
         user_id: "fake_file_owner".to_string(),
@@ -133,14 +738,41 @@ pub fn add_test_information() {
         collaborator_list: vec![collaborator_for_fake_task],
         approved_user_number: 0,
         function_name: "fake".to_string(),
+        function_id: None,
         function_type: FunctionType::Multiparty,
         status: TaskStatus::Created,
+        status_version: 0,
         ip: IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)),
         port: 0,
         task_token: "fake".to_string(),
         input_files: Vec::new(),
         output_files: Vec::new(),
         task_result_file_id: None,
+        resource_limits: default_resource_limits(),
+        dependencies: Vec::new(),
+        cancel_reason: None,
+        input_collections: Vec::new(),
+        arguments: TaskArguments::new(),
+        arguments_file: None,
+        finished_at: None,
+        retention_secs: None,
+        output_key: None,
+        priority: 0,
+        created_at: 0,
+        idempotency_key: None,
+        input_ports: PortBindings::new(),
+        input_edges: PortEdges::new(),
+        output_ports: PortBindings::new(),
+        dynamic_outputs: BTreeMap::new(),
+        progress: Vec::new(),
+        not_before: None,
+        retry_policy: None,
+        attempts: Vec::new(),
+        labels: Labels::new(),
+        receipt: None,
+        secrets: Vec::new(),
+        result_format: ResultFormat::Text,
+        result_file_id: None,
     };
-    let _ = add_task(&"fake_multi_task".to_owned(), &fake_multi_task);
+    let _ = index_task(&"fake_multi_task".to_owned(), &fake_multi_task);
 }
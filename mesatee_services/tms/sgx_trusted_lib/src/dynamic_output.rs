@@ -0,0 +1,92 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// A function may produce any number of files on its dynamic output ports,
+// which its executor reports with the other outputs of the task. Each file
+// must have been saved by the task for its creator. TMS checks the content of
+// the file against the digest TDFS recorded, unless the file is encrypted
+// under the output key of the task, and records the file with its digest.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use crate::data_store::{DynamicOutput, DynamicOutputBindings, PortSpec, TaskInfo};
+use crate::output_format;
+use crate::port_binding;
+use crate::tms_external::tdfs_client;
+use mesatee_core::config;
+use mesatee_core::{Error, ErrorKind, Result};
+
+// Records the files reported for the dynamic output ports `ports` of the
+// function of task `task_id`. The files must already be output files of the
+// task.
+pub fn record(
+    task_id: &str,
+    task_info: &mut TaskInfo,
+    ports: &[PortSpec],
+    bindings: &DynamicOutputBindings,
+) -> Result<()> {
+    let output_file_ids: Vec<&str> = task_info
+        .output_files
+        .iter()
+        .map(|file| file.file_id.as_str())
+        .collect();
+    let recorded: usize = task_info
+        .dynamic_outputs
+        .values()
+        .map(|outputs| outputs.len())
+        .sum();
+    let max_outputs = config::runtime_config().task_management.max_dynamic_outputs;
+    port_binding::check_dynamic_outputs(ports, bindings, &output_file_ids, recorded, max_outputs)?;
+
+    let mut client = tdfs_client();
+    for (port, file_ids) in bindings.iter() {
+        let file_infos = client.get_files(file_ids)?;
+        let mut outputs = Vec::with_capacity(file_ids.len());
+        for (file_id, file_info) in file_ids.iter().zip(file_infos.into_iter()) {
+            let file_info = file_info.ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+            if file_info.task_id.as_ref().map(|id| id.as_str()) != Some(task_id)
+                || file_info.user_id != task_info.user_id
+            {
+                return Err(Error::new(
+                    ErrorKind::InvalidInputError,
+                    format!(
+                        "output {} was not saved by the task for its creator",
+                        file_id
+                    ),
+                ));
+            }
+            if file_info.wrapped_key.is_none() {
+                client.verify_file(file_id)?;
+            }
+            if let Some(port) = ports.iter().find(|spec| &spec.name == port) {
+                output_format::check_dynamic(port, file_info.format)?;
+            }
+            outputs.push(DynamicOutput {
+                file_id: file_id.to_string(),
+                digest_algorithm: file_info.digest_algorithm,
+                digest: file_info.sha256,
+            });
+        }
+        task_info
+            .dynamic_outputs
+            .entry(port.to_string())
+            .or_insert_with(Vec::new)
+            .extend(outputs);
+    }
+    Ok(())
+}
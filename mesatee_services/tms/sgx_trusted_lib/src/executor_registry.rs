@@ -0,0 +1,73 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// Executors the execution services registered, which clients list to find
+// out how to register their functions. They are kept in the TMS enclave only,
+// the execution services registering them again periodically. When several
+// execution services register the same executor the last registration is
+// kept.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use lazy_static::lazy_static;
+use mesatee_core::{Error, ErrorKind, Result};
+use std::collections::BTreeMap;
+use std::sync::SgxMutex;
+use tms_internal_proto::{Executor, ExecutorInfo};
+
+lazy_static! {
+    static ref EXECUTORS: SgxMutex<BTreeMap<Executor, ExecutorInfo>> =
+        SgxMutex::new(BTreeMap::new());
+}
+
+pub fn register(executors: &[ExecutorInfo]) -> Result<()> {
+    let mut registered = EXECUTORS.lock()?;
+    for executor in executors.iter() {
+        registered.insert(executor.executor, executor.clone());
+    }
+    Ok(())
+}
+
+// Registered executors, ordered by name.
+pub fn list() -> Result<Vec<ExecutorInfo>> {
+    let registered = EXECUTORS.lock()?;
+    let mut executors: Vec<ExecutorInfo> = registered.values().cloned().collect();
+    executors.sort_by_key(|info| info.executor.name());
+    Ok(executors)
+}
+
+// Executor named `name`, which an execution service must have registered.
+pub fn resolve(name: &str) -> Result<Executor> {
+    let registered = EXECUTORS.lock()?;
+    if let Some(executor) = Executor::from_name(name) {
+        if registered.contains_key(&executor) {
+            return Ok(executor);
+        }
+    }
+    let mut names: Vec<&str> = registered.keys().map(|executor| executor.name()).collect();
+    names.sort();
+    let valid = if names.is_empty() {
+        "no executor is registered yet".to_string()
+    } else {
+        format!("valid executors are {}", names.join(", "))
+    };
+    Err(Error::new(
+        ErrorKind::InvalidInputError,
+        format!("executor {} is not available, {}", name, valid),
+    ))
+}
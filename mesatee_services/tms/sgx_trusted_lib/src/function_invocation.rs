@@ -0,0 +1,147 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// Quick invocations of functions for development. TMS creates the task of an
+// invocation like any other task, runs it on FNS itself and returns its
+// outputs inline, which are therefore bounded much tighter than the files of
+// a task.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use std::cmp;
+use std::time::Duration;
+
+use uuid::Uuid;
+
+use crate::data_store::{default_resource_limits, maximum_resource_limits, ResourceLimits};
+use crate::data_store::{TaskInfo, TaskStatus, TASK_STORE};
+use crate::tms_external::tdfs_client;
+use fns_client::FNSClient;
+use mesatee_core::config;
+use mesatee_core::rpc::channel::ChannelTimeouts;
+use mesatee_core::{Error, ErrorKind, Result};
+use tdfs_internal_client::EncryptionContext;
+
+const FNS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+// Slack over the wall-clock limit of the task for FNS to report the result.
+const FNS_RESULT_SLACK: Duration = Duration::from_secs(10);
+
+fn check_inline_bytes(what: &str, bytes: u64) -> Result<()> {
+    let max_inline_bytes = config::runtime_config()
+        .function_invocation
+        .max_inline_bytes;
+    if bytes > max_inline_bytes {
+        return Err(Error::new(
+            ErrorKind::MsgSizeLimitExceedError,
+            format!(
+                "{} have {} bytes, exceeding max_inline_bytes of {}",
+                what, bytes, max_inline_bytes
+            ),
+        ));
+    }
+    Ok(())
+}
+
+// Resource limits of the task of an invocation, which are never above the
+// cluster maximum.
+pub fn invocation_limits() -> ResourceLimits {
+    let invocation = &config::runtime_config().function_invocation;
+    let maximum = maximum_resource_limits();
+    ResourceLimits {
+        max_memory_mb: default_resource_limits().max_memory_mb,
+        max_cpu_seconds: cmp::min(invocation.max_cpu_seconds, maximum.max_cpu_seconds),
+        max_wall_seconds: cmp::min(invocation.max_wall_seconds, maximum.max_wall_seconds),
+    }
+}
+
+// Saves the inline inputs as files of the user. The task is only created once
+// its inputs exist, so the files are saved under a fresh invocation ID.
+pub fn save_inline_inputs(inline_inputs: &[Vec<u8>], user_id: &str) -> Result<Vec<String>> {
+    let input_bytes: u64 = inline_inputs.iter().map(|input| input.len() as u64).sum();
+    check_inline_bytes("inline inputs", input_bytes)?;
+    let invocation_id = Uuid::new_v4().to_string();
+    let mut client = tdfs_client();
+    inline_inputs
+        .iter()
+        .map(|input| client.save_file(input, user_id, &invocation_id, &[], 0))
+        .collect()
+}
+
+// Runs the ready task `task_id` on FNS and waits for its result.
+pub fn run_task(task_id: &str, task_info: &TaskInfo) -> Result<String> {
+    if task_info.status != TaskStatus::Ready {
+        return Err(Error::new(
+            ErrorKind::InvalidInputError,
+            format!(
+                "task of the invocation is {:?} rather than ready",
+                task_info.status
+            ),
+        ));
+    }
+    let max_wall_time = Duration::from_secs(task_info.resource_limits.max_wall_seconds);
+    let timeouts = ChannelTimeouts {
+        handshake: Some(FNS_HANDSHAKE_TIMEOUT),
+        request: Some(max_wall_time + FNS_RESULT_SLACK),
+    };
+    let mut client = FNSClient::with_timeouts(&config::External::target_fns(), timeouts)?;
+    let response = client.invoke_task(
+        task_id,
+        &task_info.function_name,
+        &task_info.task_token,
+        None,
+    )?;
+    Ok(response.result)
+}
+
+// Reads the files the finished task `task_id` saved for `user_id`, which must
+// have been encrypted for the task. Functions whose result and outputs
+// together exceed the inline limit are rejected, the outputs are then only
+// kept with the task.
+pub fn read_outputs(task_id: &str, user_id: &str, result: &str) -> Result<Vec<Vec<u8>>> {
+    let task_info = TASK_STORE
+        .get(task_id)?
+        .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+    if task_info.status != TaskStatus::Finished {
+        return Err(Error::new(
+            ErrorKind::OutputGenerationError,
+            format!("task of the invocation is {:?}", task_info.status),
+        ));
+    }
+    let file_ids: Vec<String> = task_info
+        .output_files
+        .iter()
+        .filter(|file| file.user_id == user_id)
+        .map(|file| file.file_id.to_string())
+        .collect();
+    let mut client = tdfs_client();
+    let mut output_bytes = result.len() as u64;
+    for file_info in client.get_files(&file_ids)?.into_iter() {
+        let file_info = file_info.ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+        output_bytes = output_bytes.saturating_add(file_info.file_size);
+    }
+    check_inline_bytes("outputs of the function", output_bytes)?;
+    let function_id = task_info
+        .function_id
+        .as_ref()
+        .unwrap_or(&task_info.function_name);
+    let context = EncryptionContext::new(task_id, function_id);
+    file_ids
+        .iter()
+        .map(|file_id| client.read_file_in_context(file_id, Some(user_id), &context))
+        .collect()
+}
@@ -0,0 +1,104 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use mesatee_core::{Error, ErrorKind, Result};
+use tms_external_proto::InputCollection;
+
+use crate::data_store::ResolvedCollection;
+use crate::tms_external::tdfs_client;
+
+// Matches `name` against `pattern`, where `*` matches any sequence of
+// characters and `?` any single character.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` in the pattern and of the name it matched up to
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            // Let the last `*` match one more character
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, n));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+// Resolves the input collections of a task against the files accessible to
+// `user_id`. The files of each collection are ordered by name and ID, so that
+// the function sees them in the same order every time.
+pub(crate) fn resolve(
+    collections: &[InputCollection],
+    optional_inputs: &[String],
+    user_id: &str,
+) -> Result<Vec<ResolvedCollection>> {
+    if collections.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut files = tdfs_client().list_user_files(user_id)?;
+    files.sort_by(|a, b| (&a.file_name, &a.file_id).cmp(&(&b.file_name, &b.file_id)));
+
+    let mut resolved: Vec<ResolvedCollection> = Vec::with_capacity(collections.len());
+    for collection in collections.iter() {
+        if collection.name.is_empty() || collection.pattern.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidInputError,
+                "input collection without a name or pattern",
+            ));
+        }
+        if resolved.iter().any(|r| r.name == collection.name) {
+            return Err(Error::new(
+                ErrorKind::InvalidInputError,
+                format!("duplicate input collection {}", collection.name),
+            ));
+        }
+
+        let file_ids: Vec<String> = files
+            .iter()
+            .filter(|file| glob_match(&collection.pattern, &file.file_name))
+            .map(|file| file.file_id.to_owned())
+            .collect();
+        if file_ids.is_empty() && !optional_inputs.contains(&collection.name) {
+            return Err(Error::new(
+                ErrorKind::InvalidInputError,
+                format!(
+                    "input collection {} matches no files: {}",
+                    collection.name, collection.pattern
+                ),
+            ));
+        }
+        resolved.push(ResolvedCollection {
+            name: collection.name.to_owned(),
+            pattern: collection.pattern.to_owned(),
+            file_ids,
+        });
+    }
+    Ok(resolved)
+}
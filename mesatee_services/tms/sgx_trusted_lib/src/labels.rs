@@ -0,0 +1,67 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// Labels users attach to their tasks and functions are only stored and
+// returned. They are bounded so that they cannot bloat the records they are
+// attached to.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use crate::data_store::Labels;
+use mesatee_core::{Error, ErrorKind, Result};
+
+const MAX_LABEL_KEY_LEN: usize = 64;
+const MAX_LABEL_VALUE_LEN: usize = 256;
+// Bytes of all the keys and values of a task or function.
+const MAX_LABELS_SIZE: usize = 4096;
+
+pub fn check_labels(labels: &Labels) -> Result<()> {
+    let mut size = 0;
+    for (key, value) in labels.iter() {
+        if key.is_empty() || key.len() > MAX_LABEL_KEY_LEN {
+            return Err(Error::new(
+                ErrorKind::InvalidInputError,
+                format!("label keys must be 1 to {} bytes long", MAX_LABEL_KEY_LEN),
+            ));
+        }
+        if value.len() > MAX_LABEL_VALUE_LEN {
+            return Err(Error::new(
+                ErrorKind::InvalidInputError,
+                format!(
+                    "value of label {} is longer than {} bytes",
+                    key, MAX_LABEL_VALUE_LEN
+                ),
+            ));
+        }
+        size += key.len() + value.len();
+    }
+    if size > MAX_LABELS_SIZE {
+        return Err(Error::new(
+            ErrorKind::InvalidInputError,
+            format!("labels are larger than {} bytes", MAX_LABELS_SIZE),
+        ));
+    }
+    Ok(())
+}
+
+// Whether `labels` has all the labels of `filter`.
+pub fn matches(labels: &Labels, filter: &Labels) -> bool {
+    filter
+        .iter()
+        .all(|(key, value)| labels.get(key) == Some(value))
+}
@@ -22,9 +22,40 @@ extern crate sgx_tstd as std;
 #[macro_use]
 extern crate log;
 
+mod access_control;
+mod argument_schema;
+mod audit_log;
+mod auth;
 mod data_store;
+mod dynamic_output;
+mod executor_registry;
+mod function_invocation;
+mod input_collection;
+mod labels;
+mod output_format;
+mod port_binding;
+mod python_check;
+mod rate_limit;
+mod remote_payload;
+mod scheduler;
+mod status_notifier;
+mod storage;
+mod task_arguments;
+mod task_id;
+mod task_index;
+mod task_limits;
+mod task_logs;
+mod task_receipt;
+mod task_recovery;
+mod task_retention;
+mod task_retry;
+mod task_schedule;
+mod task_secret;
+mod task_staging;
 mod tms_external;
+mod tms_function;
 mod tms_internal;
+mod user_revocation;
 
 use cfg_if::cfg_if;
 cfg_if! {
@@ -0,0 +1,83 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+// A function declares the format of the files of its output ports, which a
+// task records with each file it saves on TDFS. TMS takes the format of the
+// output files of a task from TDFS rather than from its executor, and checks
+// the files bound to a port that declares a format against it.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use crate::data_store::{FileFormat, PortBindings, PortSpec, TaskFile};
+use crate::port_binding;
+use crate::tms_external::tdfs_client;
+use mesatee_core::{Error, ErrorKind, Result};
+
+fn from_tdfs(format: tdfs_internal_client::FileFormat) -> FileFormat {
+    FileFormat {
+        content_type: format.content_type,
+        schema: format.schema,
+    }
+}
+
+// Sets the format of the output files reported by an executor to the format
+// TDFS recorded for them.
+pub fn fill(output_files: &mut [TaskFile]) -> Result<()> {
+    if output_files.is_empty() {
+        return Ok(());
+    }
+    let file_ids: Vec<String> = output_files
+        .iter()
+        .map(|file| file.file_id.to_string())
+        .collect();
+    let file_infos = tdfs_client().get_files(&file_ids)?;
+    for (file, file_info) in output_files.iter_mut().zip(file_infos.into_iter()) {
+        let file_info = file_info.ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+        file.format = file_info.format.map(from_tdfs);
+    }
+    Ok(())
+}
+
+// Checks the output files bound to the static output ports `ports` against
+// the formats the ports declare.
+pub fn check_bindings(
+    ports: &[PortSpec],
+    bindings: &PortBindings,
+    output_files: &[TaskFile],
+) -> Result<()> {
+    for (name, file_id) in bindings.iter() {
+        let port = match ports.iter().find(|port| &port.name == name) {
+            Some(port) => port,
+            None => continue,
+        };
+        let format = output_files
+            .iter()
+            .find(|file| &file.file_id == file_id)
+            .and_then(|file| file.format.as_ref());
+        port_binding::check_output_format(port, format)?;
+    }
+    Ok(())
+}
+
+// Checks an output file TDFS recorded with `format` that is reported for the
+// dynamic output port `port`.
+pub fn check_dynamic(
+    port: &PortSpec,
+    format: Option<tdfs_internal_client::FileFormat>,
+) -> Result<()> {
+    port_binding::check_output_format(port, format.map(from_tdfs).as_ref())
+}
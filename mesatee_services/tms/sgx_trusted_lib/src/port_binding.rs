@@ -0,0 +1,245 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use mesatee_core::content_type;
+use mesatee_core::{Error, ErrorKind, Result};
+
+use std::collections::HashMap;
+
+use crate::data_store::{
+    DynamicOutputBindings, FileFormat, PortBindings, PortEdges, PortSpec, ANY_DATA_TYPE,
+};
+
+fn into_result(errors: Vec<String>) -> Result<()> {
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::new(ErrorKind::InvalidInputError, errors.join("; ")))
+    }
+}
+
+fn check_format(format: &FileFormat) -> Result<()> {
+    content_type::check_content_type(&format.content_type)?;
+    if let Some(schema) = &format.schema {
+        content_type::check_schema(schema)?;
+    }
+    Ok(())
+}
+
+// Checks the input and output ports of a function being registered. Port
+// names are unique among the inputs and among the outputs, and only output
+// ports declare the format of their files.
+pub(crate) fn check_ports(input_ports: &[PortSpec], output_ports: &[PortSpec]) -> Result<()> {
+    let mut errors: Vec<String> = Vec::new();
+    for (direction, ports) in [("input", input_ports), ("output", output_ports)].iter() {
+        for (index, port) in ports.iter().enumerate() {
+            if port.name.is_empty() {
+                errors.push(format!("{} port {} has no name", direction, index));
+                continue;
+            }
+            if ports[..index].iter().any(|p| p.name == port.name) {
+                errors.push(format!("duplicate {} port {}", direction, port.name));
+            }
+        }
+    }
+    if let Some(port) = output_ports.iter().find(|port| port.required) {
+        errors.push(format!("output port {} cannot be required", port.name));
+    }
+    if let Some(port) = input_ports.iter().find(|port| port.dynamic) {
+        errors.push(format!("input port {} cannot be dynamic", port.name));
+    }
+    if let Some(port) = input_ports.iter().find(|port| port.format.is_some()) {
+        errors.push(format!("input port {} cannot declare a format", port.name));
+    }
+    for port in output_ports.iter() {
+        if let Some(Err(err)) = port.format.as_ref().map(check_format) {
+            errors.push(format!("output port {}: {}", port.name, err));
+        }
+    }
+    into_result(errors)
+}
+
+// Checks the format TDFS recorded for a file bound to an output port against
+// the format the port declares. Ports declaring no format take any file.
+pub(crate) fn check_output_format(port: &PortSpec, format: Option<&FileFormat>) -> Result<()> {
+    match &port.format {
+        Some(declared) if format != Some(declared) => Err(Error::new(
+            ErrorKind::InvalidInputError,
+            format!(
+                "output port {} is declared as {}, but its file is not",
+                port.name, declared.content_type
+            ),
+        )),
+        _ => Ok(()),
+    }
+}
+
+// Checks the files a task binds to the input ports of its function: every
+// required port is bound, to a file or by one of `edges`, and no port unknown
+// to the function.
+pub(crate) fn check_input_bindings(
+    ports: &[PortSpec],
+    bindings: &PortBindings,
+    edges: &PortEdges,
+) -> Result<()> {
+    let mut errors: Vec<String> = Vec::new();
+    for (name, file_id) in bindings.iter() {
+        if !ports.iter().any(|port| &port.name == name) {
+            errors.push(format!("unknown input port {}", name));
+        } else if file_id.is_empty() {
+            errors.push(format!("input port {} is bound to no file", name));
+        }
+    }
+    for port in ports.iter() {
+        if port.required && !bindings.contains_key(&port.name) && !edges.contains_key(&port.name) {
+            errors.push(format!("required input port {} is not bound", port.name));
+        }
+    }
+    into_result(errors)
+}
+
+// Whether an output port carrying `output_type` may be bound to an input port
+// of `input_type`.
+pub(crate) fn is_compatible(output_type: &str, input_type: &str) -> bool {
+    let is_wildcard = |data_type: &str| data_type.is_empty() || data_type == ANY_DATA_TYPE;
+    output_type == input_type || is_wildcard(output_type) || is_wildcard(input_type)
+}
+
+// Checks the input ports of a task bound to output ports of its dependencies,
+// given the output ports of the function of each dependency. An input port is
+// bound either to a file or to a static output port of a compatible type.
+pub(crate) fn check_input_edges(
+    ports: &[PortSpec],
+    bindings: &PortBindings,
+    edges: &PortEdges,
+    dependency_ports: &HashMap<&str, Vec<PortSpec>>,
+) -> Result<()> {
+    let mut errors: Vec<String> = Vec::new();
+    for (name, edge) in edges.iter() {
+        let input = match ports.iter().find(|port| &port.name == name) {
+            Some(input) => input,
+            None => {
+                errors.push(format!("unknown input port {}", name));
+                continue;
+            }
+        };
+        if bindings.contains_key(name) {
+            errors.push(format!("input port {} is also bound to a file", name));
+            continue;
+        }
+        let output_ports = match dependency_ports.get(edge.task_id.as_str()) {
+            Some(output_ports) => output_ports,
+            None => {
+                errors.push(format!(
+                    "input port {} is bound to task {}, which is not a dependency",
+                    name, edge.task_id
+                ));
+                continue;
+            }
+        };
+        match output_ports.iter().find(|port| port.name == edge.port) {
+            None => errors.push(format!(
+                "input port {} is bound to unknown output port {} of task {}",
+                name, edge.port, edge.task_id
+            )),
+            Some(output) if output.dynamic => errors.push(format!(
+                "input port {} is bound to dynamic output port {} of task {}",
+                name, edge.port, edge.task_id
+            )),
+            Some(output) if !is_compatible(&output.data_type, &input.data_type) => {
+                errors.push(format!(
+                    "input port {} expects {}, but output port {} of task {} carries {}",
+                    name, input.data_type, edge.port, edge.task_id, output.data_type
+                ))
+            }
+            Some(_) => {}
+        }
+    }
+    into_result(errors)
+}
+
+// Checks the files an executor binds to the output ports of the function of
+// a task. Only output files of the task may be bound.
+pub(crate) fn check_output_bindings(
+    ports: &[PortSpec],
+    bindings: &PortBindings,
+    output_file_ids: &[&str],
+) -> Result<()> {
+    let mut errors: Vec<String> = Vec::new();
+    for (name, file_id) in bindings.iter() {
+        match ports.iter().find(|port| &port.name == name) {
+            None => errors.push(format!("unknown output port {}", name)),
+            Some(port) if port.dynamic => errors.push(format!("output port {} is dynamic", name)),
+            Some(_) if !output_file_ids.contains(&file_id.as_str()) => errors.push(format!(
+                "output port {} is bound to {}, which is not an output of the task",
+                name, file_id
+            )),
+            Some(_) => {}
+        }
+    }
+    into_result(errors)
+}
+
+// Checks the files an executor reports for the dynamic output ports of the
+// function of a task. Only output files of the task may be reported, each on
+// one port, and at most `max_outputs` files in total, counting the
+// `recorded` files reported before.
+pub(crate) fn check_dynamic_outputs(
+    ports: &[PortSpec],
+    bindings: &DynamicOutputBindings,
+    output_file_ids: &[&str],
+    recorded: usize,
+    max_outputs: usize,
+) -> Result<()> {
+    let mut errors: Vec<String> = Vec::new();
+    let mut reported: Vec<&str> = Vec::new();
+    for (name, file_ids) in bindings.iter() {
+        match ports.iter().find(|port| &port.name == name) {
+            None => {
+                errors.push(format!("unknown output port {}", name));
+                continue;
+            }
+            Some(port) if !port.dynamic => {
+                errors.push(format!("output port {} is not dynamic", name));
+                continue;
+            }
+            Some(_) => {}
+        }
+        for file_id in file_ids.iter() {
+            if !output_file_ids.contains(&file_id.as_str()) {
+                errors.push(format!(
+                    "output port {} is given {}, which is not an output of the task",
+                    name, file_id
+                ));
+            } else if reported.contains(&file_id.as_str()) {
+                errors.push(format!("output {} is given more than once", file_id));
+            }
+            reported.push(file_id);
+        }
+    }
+    if recorded + reported.len() > max_outputs {
+        errors.push(format!(
+            "task produced {} dynamic outputs, more than the maximum of {}",
+            recorded + reported.len(),
+            max_outputs
+        ));
+    }
+    into_result(errors)
+}
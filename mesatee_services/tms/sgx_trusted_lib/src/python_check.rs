@@ -0,0 +1,320 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// Static checks of Python function scripts at registration. The execution
+// service guards imports again at run time, these checks reject scripts that
+// cannot work early.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use mesatee_core::{Error, ErrorKind, Result};
+
+// Entry function every Python function script defines.
+const MAIN_FUNCTION: &str = "main";
+
+// Names giving access to the interpreter internals the import guard relies on,
+// or looking attributes up by computed names such as `attrgetter('func_glo' +
+// 'bals')`, denied wherever they appear.
+const DENIED_NAMES: &[&str] = &[
+    "Formatter",
+    "__base__",
+    "__bases__",
+    "__builtin__",
+    "__builtins__",
+    "__class__",
+    "__closure__",
+    "__code__",
+    "__dict__",
+    "__func__",
+    "__getattribute__",
+    "__globals__",
+    "__import__",
+    "__mro__",
+    "__self__",
+    "__subclasses__",
+    "attrgetter",
+    "co_consts",
+    "f_back",
+    "f_builtins",
+    "f_globals",
+    "f_locals",
+    "func_closure",
+    "func_code",
+    "func_globals",
+    "gi_code",
+    "gi_frame",
+    "im_func",
+    "im_self",
+    "methodcaller",
+    "tb_frame",
+];
+
+// Builtins and statements evaluating code or reaching other namespaces, e.g.
+// `eval('__imp' + 'ort__("os")')`, denied unless they are attributes such as
+// `re.compile`. The execution service runs scripts without these builtins.
+const DENIED_BUILTINS: &[&str] = &[
+    "compile", "delattr", "eval", "exec", "execfile", "getattr", "globals", "input", "locals",
+    "reload", "setattr", "vars",
+];
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Name(String),
+    Op(char),
+    // End of a logical line, or a `;` or `:` starting a new statement.
+    StatementEnd,
+    Literal,
+}
+
+struct Tokenizer<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+    brackets: Vec<(char, usize)>,
+}
+
+fn syntax_error<T>(line: usize, msg: &str) -> Result<T> {
+    Err(Error::new(
+        ErrorKind::InvalidInputError,
+        format!("line {}: {}", line, msg),
+    ))
+}
+
+impl<'a> Tokenizer<'a> {
+    fn new(source: &'a str) -> Self {
+        Tokenizer {
+            chars: source.chars().peekable(),
+            line: 1,
+            brackets: Vec::new(),
+        }
+    }
+
+    fn tokenize(mut self) -> Result<Vec<(Token, usize)>> {
+        let mut tokens = Vec::new();
+        while let Some(c) = self.chars.next() {
+            let line = self.line;
+            match c {
+                '\n' => {
+                    self.line += 1;
+                    if self.brackets.is_empty() {
+                        tokens.push((Token::StatementEnd, line));
+                    }
+                }
+                '#' => {
+                    while self.chars.peek().map_or(false, |c| *c != '\n') {
+                        self.chars.next();
+                    }
+                }
+                '\\' => match self.chars.next() {
+                    Some('\n') => self.line += 1,
+                    Some('\r') if self.chars.peek() == Some(&'\n') => {}
+                    _ => return syntax_error(line, "unexpected character after line continuation"),
+                },
+                '\'' | '"' => {
+                    self.skip_string(c)?;
+                    tokens.push((Token::Literal, line));
+                }
+                c if c.is_ascii_alphabetic() || c == '_' => {
+                    let mut name = c.to_string();
+                    while let Some(&c) = self.chars.peek() {
+                        if !(c.is_ascii_alphanumeric() || c == '_') {
+                            break;
+                        }
+                        name.push(c);
+                        self.chars.next();
+                    }
+                    // String prefixes such as r"" or b''
+                    let is_prefix = name.len() <= 2
+                        && name.chars().all(|c| "bBrRuU".contains(c))
+                        && self.chars.peek().map_or(false, |c| *c == '\'' || *c == '"');
+                    if is_prefix {
+                        let quote = self.chars.next().unwrap_or('"');
+                        self.skip_string(quote)?;
+                        tokens.push((Token::Literal, line));
+                    } else {
+                        tokens.push((Token::Name(name), line));
+                    }
+                }
+                c if c.is_ascii_digit() => {
+                    while self
+                        .chars
+                        .peek()
+                        .map_or(false, |c| c.is_ascii_alphanumeric() || *c == '.')
+                    {
+                        self.chars.next();
+                    }
+                    tokens.push((Token::Literal, line));
+                }
+                '(' | '[' | '{' => {
+                    self.brackets.push((c, line));
+                    tokens.push((Token::Op(c), line));
+                }
+                ')' | ']' | '}' => {
+                    let open = match c {
+                        ')' => '(',
+                        ']' => '[',
+                        _ => '{',
+                    };
+                    match self.brackets.pop() {
+                        Some((bracket, _)) if bracket == open => {}
+                        _ => return syntax_error(line, &format!("unmatched '{}'", c)),
+                    }
+                    tokens.push((Token::Op(c), line));
+                }
+                ';' => tokens.push((Token::StatementEnd, line)),
+                ':' if self.brackets.is_empty() => tokens.push((Token::StatementEnd, line)),
+                c if c.is_whitespace() => {}
+                c if c.is_ascii() => tokens.push((Token::Op(c), line)),
+                _ => return syntax_error(line, &format!("invalid character {:?}", c)),
+            }
+        }
+        if let Some((bracket, line)) = self.brackets.pop() {
+            return syntax_error(line, &format!("'{}' was never closed", bracket));
+        }
+        tokens.push((Token::StatementEnd, self.line));
+        Ok(tokens)
+    }
+
+    fn skip_string(&mut self, quote: char) -> Result<()> {
+        let start = self.line;
+        let triple = if self.chars.peek() == Some(&quote) {
+            self.chars.next();
+            if self.chars.peek() != Some(&quote) {
+                // An empty string
+                return Ok(());
+            }
+            self.chars.next();
+            true
+        } else {
+            false
+        };
+        let mut closing = 0;
+        while let Some(c) = self.chars.next() {
+            match c {
+                '\\' => {
+                    if self.chars.next() == Some('\n') {
+                        self.line += 1;
+                    }
+                    closing = 0;
+                }
+                '\n' if !triple => return syntax_error(start, "unterminated string"),
+                '\n' => {
+                    self.line += 1;
+                    closing = 0;
+                }
+                c if c == quote => {
+                    closing += 1;
+                    if !triple || closing == 3 {
+                        return Ok(());
+                    }
+                }
+                _ => closing = 0,
+            }
+        }
+        syntax_error(start, "unterminated string")
+    }
+}
+
+// Reads a dotted module name such as `os.path` starting at `tokens[index]`.
+fn module_name(tokens: &[(Token, usize)], mut index: usize) -> Option<String> {
+    let mut name = String::new();
+    loop {
+        match tokens.get(index) {
+            Some((Token::Name(part), _)) => name.push_str(part),
+            _ => return None,
+        }
+        match tokens.get(index + 1) {
+            Some((Token::Op('.'), _)) => {
+                name.push('.');
+                index += 2;
+            }
+            _ => return Some(name),
+        }
+    }
+}
+
+fn check_import(module: &str, line: usize, allowed_modules: &[String]) -> Result<()> {
+    let top_level = module.split('.').next().unwrap_or_default();
+    if top_level == "__future__" || allowed_modules.iter().any(|allowed| allowed == top_level) {
+        Ok(())
+    } else {
+        syntax_error(line, &format!("import of module {} is not allowed", module))
+    }
+}
+
+// Checks that `source` tokenizes, only imports `allowed_modules`, does not
+// evaluate code or reach for interpreter internals, and defines `main`.
+pub fn check_script(source: &str, allowed_modules: &[String]) -> Result<()> {
+    let tokens = Tokenizer::new(source).tokenize()?;
+    let mut defines_main = false;
+    let mut statement_start = true;
+    for (index, (token, line)) in tokens.iter().enumerate() {
+        let line = *line;
+        if let Token::Name(name) = token {
+            let is_attribute = index > 0 && tokens[index - 1].0 == Token::Op('.');
+            if DENIED_NAMES.contains(&name.as_str())
+                || (!is_attribute && DENIED_BUILTINS.contains(&name.as_str()))
+            {
+                return syntax_error(line, &format!("use of {} is not allowed", name));
+            }
+            match name.as_str() {
+                "def" if statement_start => {
+                    if let Some((Token::Name(function), _)) = tokens.get(index + 1) {
+                        defines_main |= function == MAIN_FUNCTION;
+                    }
+                }
+                "from" if statement_start => match module_name(&tokens, index + 1) {
+                    Some(module) => check_import(&module, line, allowed_modules)?,
+                    None => return syntax_error(line, "relative imports are not allowed"),
+                },
+                "import" if statement_start => {
+                    let mut next = index + 1;
+                    loop {
+                        let module = match module_name(&tokens, next) {
+                            Some(module) => module,
+                            None => return syntax_error(line, "invalid import statement"),
+                        };
+                        check_import(&module, line, allowed_modules)?;
+                        // Skip to the next module of `import a as b, c`
+                        next += module.split('.').count() * 2 - 1;
+                        if let Some((Token::Name(alias), _)) = tokens.get(next) {
+                            if alias == "as" {
+                                next += 2;
+                            }
+                        }
+                        match tokens.get(next) {
+                            Some((Token::Op(','), _)) => next += 1,
+                            _ => break,
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        statement_start = *token == Token::StatementEnd;
+    }
+    if !defines_main {
+        return Err(Error::new(
+            ErrorKind::InvalidInputError,
+            format!(
+                "Python function must define {}(inputs, output)",
+                MAIN_FUNCTION
+            ),
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,135 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// Requests of each authenticated user are limited by a token bucket: a
+// request takes a token, and tokens are added at `requests_per_second` up to
+// `burst`. Requests over the limit fail with `RateLimited` and the time until
+// the next token is added. Requests are only limited after `AuthInterceptor`
+// let them through, so that a client cannot use up the requests of another
+// user.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use lazy_static::lazy_static;
+use mesatee_core::config;
+use mesatee_core::rpc::interceptor::{Interceptor, RequestContext};
+use mesatee_core::{Error, ErrorKind, Result};
+use std::collections::HashMap;
+use std::sync::SgxMutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::untrusted::time::SystemTimeEx;
+use teaclave_config::runtime_config::RateLimitConfig;
+use tms_external_proto::{TaskRequest, TaskResponse};
+
+lazy_static! {
+    pub static ref RATE_LIMITER: RateLimiter =
+        RateLimiter::new(&config::runtime_config().rate_limit);
+}
+
+// Milliseconds since the epoch.
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: u64,
+}
+
+pub struct RateLimiter {
+    buckets: SgxMutex<HashMap<String, Bucket>>,
+    // Tokens added per millisecond.
+    rate: f64,
+    burst: f64,
+    max_tracked_users: usize,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        RateLimiter {
+            buckets: SgxMutex::new(HashMap::new()),
+            rate: f64::from(config.requests_per_second) / 1000.0,
+            burst: f64::from(config.burst.max(1)),
+            max_tracked_users: config.max_tracked_users.max(1),
+        }
+    }
+
+    fn tokens_at(&self, bucket: &Bucket, now: u64) -> f64 {
+        let elapsed = now.saturating_sub(bucket.updated_at) as f64;
+        (bucket.tokens + elapsed * self.rate).min(self.burst)
+    }
+
+    // Takes a token from the bucket of `user_id`.
+    pub fn check(&self, user_id: &str) -> Result<()> {
+        if self.rate == 0.0 {
+            return Ok(());
+        }
+        let now = now_millis();
+        let mut buckets = self.buckets.lock()?;
+        if !buckets.contains_key(user_id) && buckets.len() >= self.max_tracked_users {
+            self.evict(&mut buckets, now);
+        }
+        let burst = self.burst;
+        let bucket = buckets.entry(user_id.to_owned()).or_insert(Bucket {
+            tokens: burst,
+            updated_at: now,
+        });
+        bucket.tokens = self.tokens_at(bucket, now);
+        bucket.updated_at = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        let wait = ((1.0 - bucket.tokens) / self.rate).ceil() as u64;
+        debug!("Rate limiting user {}, retry after {} ms", user_id, wait);
+        Err(Error::with_retry_after(
+            ErrorKind::RateLimited,
+            Duration::from_millis(wait),
+        ))
+    }
+
+    // Full buckets are dropped first, since a new bucket is full too. If no
+    // bucket is full, the bucket used least recently is dropped.
+    fn evict(&self, buckets: &mut HashMap<String, Bucket>, now: u64) {
+        buckets.retain(|_, bucket| self.tokens_at(bucket, now) < self.burst);
+        if buckets.len() < self.max_tracked_users {
+            return;
+        }
+        let idlest = buckets
+            .iter()
+            .min_by_key(|(_, bucket)| bucket.updated_at)
+            .map(|(user_id, _)| user_id.to_owned());
+        if let Some(user_id) = idlest {
+            buckets.remove(&user_id);
+        }
+    }
+}
+
+pub struct RateLimitInterceptor;
+
+impl Interceptor<TaskRequest, TaskResponse> for RateLimitInterceptor {
+    fn before(&self, _context: &RequestContext, request: &TaskRequest) -> Result<()> {
+        match request.credentials() {
+            Some((user_id, _)) => RATE_LIMITER.check(user_id),
+            None => Ok(()),
+        }
+    }
+}
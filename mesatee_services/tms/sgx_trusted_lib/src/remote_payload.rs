@@ -0,0 +1,126 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Downloads of function payloads registered by URL. The payload goes through
+//! the untrusted host over plain HTTP, so it is only trusted once it matches
+//! the digest pinned by the client.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use mesatee_core::{config, Error, ErrorKind, Result};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+use tdfs_internal_client::DigestAlgorithm;
+use tms_external_proto::RemotePayload;
+
+const HTTP_SCHEME: &str = "http://";
+// Responses with longer headers are rejected.
+const MAX_HEADER_SIZE: u64 = 64 * 1024;
+
+/// Downloads the payload and checks it against its pinned digest.
+pub fn fetch(remote: &RemotePayload) -> Result<Vec<u8>> {
+    let algorithm = DigestAlgorithm::from_tag(&remote.digest_algorithm)?;
+    // A keyed digest cannot be pinned, since no key is given.
+    let expected = algorithm.check_digest(&remote.digest, &[])?;
+    let payload = download(&remote.url)?;
+    if algorithm.cal_digest(&payload, &[])? != expected {
+        return Err(Error::new(
+            ErrorKind::DataIntegrityError,
+            format!("payload of {} does not match its digest", remote.url),
+        ));
+    }
+    Ok(payload)
+}
+
+// Splits an `http://host[:port][/path]` URL into its authority and path.
+fn parse_url(url: &str) -> Result<(&str, &str)> {
+    if !url.starts_with(HTTP_SCHEME) {
+        return Err(Error::new(
+            ErrorKind::InvalidInputError,
+            format!("unsupported payload URL {}", url),
+        ));
+    }
+    let rest = &url[HTTP_SCHEME.len()..];
+    let (authority, path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, "/"),
+    };
+    if authority.is_empty() {
+        return Err(Error::new(
+            ErrorKind::InvalidInputError,
+            format!("payload URL {} without a host", url),
+        ));
+    }
+    Ok((authority, path))
+}
+
+fn download(url: &str) -> Result<Vec<u8>> {
+    let (authority, path) = parse_url(url)?;
+    let addr = if authority.contains(':') {
+        authority.to_string()
+    } else {
+        format!("{}:80", authority)
+    };
+    let remote_config = &config::runtime_config().remote_function;
+    let timeout = Some(Duration::from_secs(remote_config.timeout_secs));
+
+    let mut stream = TcpStream::connect(&addr)?;
+    stream.set_read_timeout(timeout)?;
+    stream.set_write_timeout(timeout)?;
+    write!(
+        stream,
+        "GET {} HTTP/1.0\r\nHost: {}\r\nConnection: close\r\n\r\n",
+        path, authority
+    )?;
+    stream.flush()?;
+
+    // One byte beyond the limits tells an oversize payload apart
+    let mut response = Vec::new();
+    stream
+        .take(MAX_HEADER_SIZE + remote_config.max_payload_size + 1)
+        .read_to_end(&mut response)?;
+    parse_response(&response, remote_config.max_payload_size)
+}
+
+// Returns the body of a successful response.
+fn parse_response(response: &[u8], max_payload_size: u64) -> Result<Vec<u8>> {
+    let header_len = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .filter(|len| *len as u64 <= MAX_HEADER_SIZE)
+        .ok_or_else(|| Error::new(ErrorKind::InvalidHTTPRequest, "malformed response headers"))?;
+    let headers = String::from_utf8_lossy(&response[..header_len]);
+    let status = headers
+        .lines()
+        .next()
+        .and_then(|status_line| status_line.split_whitespace().nth(1))
+        .unwrap_or("");
+    if status != "200" {
+        return Err(Error::new(
+            ErrorKind::InvalidHTTPRequest,
+            format!("payload download failed with status {}", status),
+        ));
+    }
+
+    let body = &response[header_len + 4..];
+    if body.len() as u64 > max_payload_size {
+        return Err(Error::from(ErrorKind::MsgSizeLimitExceedError));
+    }
+    Ok(body.to_vec())
+}
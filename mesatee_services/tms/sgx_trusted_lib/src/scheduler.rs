@@ -0,0 +1,120 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// Ready tasks waiting to be handed out to an executor. The task with the
+// highest effective priority is handed out first, that is its priority raised
+// by one for every `priority_aging_interval` seconds it has been waiting, so
+// that a stream of high-priority tasks cannot starve low-priority ones. Tasks
+// with the same effective priority are handed out in the order they became
+// ready.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use mesatee_core::Result;
+use std::sync::SgxMutex;
+
+use crate::task_retention;
+
+struct QueuedTask {
+    task_id: String,
+    priority: i32,
+    // Seconds since the epoch when the task became ready.
+    ready_at: u64,
+    // Order in which the tasks became ready, to break ties.
+    sequence: u64,
+}
+
+impl QueuedTask {
+    fn effective_priority(&self, now: u64, aging_interval: u64) -> i64 {
+        let waited = now.saturating_sub(self.ready_at);
+        let boost = if aging_interval == 0 {
+            0
+        } else {
+            waited / aging_interval
+        };
+        i64::from(self.priority).saturating_add(boost as i64)
+    }
+}
+
+struct Queue {
+    tasks: Vec<QueuedTask>,
+    next_sequence: u64,
+}
+
+pub struct Scheduler {
+    queue: SgxMutex<Queue>,
+    aging_interval: u64,
+}
+
+impl Scheduler {
+    // The priority of a waiting task rises by one every `aging_interval`
+    // seconds, a zero interval disables aging.
+    pub fn new(aging_interval: u64) -> Self {
+        Scheduler {
+            queue: SgxMutex::new(Queue {
+                tasks: Vec::new(),
+                next_sequence: 0,
+            }),
+            aging_interval,
+        }
+    }
+
+    // Called when task `task_id` becomes ready. A task already queued keeps
+    // its place.
+    pub fn enqueue(&self, task_id: &str, priority: i32) -> Result<()> {
+        let mut queue = self.queue.lock()?;
+        if queue.tasks.iter().any(|task| task.task_id == task_id) {
+            return Ok(());
+        }
+        let sequence = queue.next_sequence;
+        queue.next_sequence += 1;
+        queue.tasks.push(QueuedTask {
+            task_id: task_id.to_owned(),
+            priority,
+            ready_at: task_retention::now(),
+            sequence,
+        });
+        Ok(())
+    }
+
+    // Called when task `task_id` is no longer ready, e.g. it started running
+    // or was cancelled.
+    pub fn remove(&self, task_id: &str) -> Result<()> {
+        let mut queue = self.queue.lock()?;
+        queue.tasks.retain(|task| task.task_id != task_id);
+        Ok(())
+    }
+
+    // Removes and returns the next task to run, if any task is ready.
+    pub fn dequeue(&self) -> Result<Option<String>> {
+        let mut queue = self.queue.lock()?;
+        let now = task_retention::now();
+        let aging_interval = self.aging_interval;
+        let next = queue
+            .tasks
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| {
+                a.effective_priority(now, aging_interval)
+                    .cmp(&b.effective_priority(now, aging_interval))
+                    .then_with(|| b.sequence.cmp(&a.sequence))
+            })
+            .map(|(index, _)| index);
+        Ok(next.map(|index| queue.tasks.remove(index).task_id))
+    }
+}
@@ -19,10 +19,14 @@
 use std::prelude::v1::*;
 
 use mesatee_core::config;
+use mesatee_core::enclave_size::EnclaveSize;
 use mesatee_core::prelude::*;
-use mesatee_core::rpc::server::SgxTrustedServer;
+use mesatee_core::rpc::interceptor::InboundServices;
+use mesatee_core::rpc::server::{self, SgxTrustedServer};
 use mesatee_core::Result;
 
+use crate::auth::{AuthInterceptor, RoleInterceptor};
+use crate::rate_limit::RateLimitInterceptor;
 use crate::tms_external::TMSExternalEnclave;
 use crate::tms_internal::TMSInternalEnclave;
 
@@ -31,11 +35,28 @@ register_ecall_handler!(
     (ECallCommand::ServeConnection, ServeConnectionInput, ServeConnectionOutput),
     (ECallCommand::InitEnclave, InitEnclaveInput, InitEnclaveOutput),
     (ECallCommand::FinalizeEnclave, FinalizeEnclaveInput, FinalizeEnclaveOutput),
+    (ECallCommand::GetMetrics, GetMetricsInput, GetMetricsOutput),
+    (ECallCommand::RunMaintenance, RunMaintenanceInput, RunMaintenanceOutput),
 );
 
+// Tasks and functions are kept in the enclave heap.
+const SIZE_HINTS: EnclaveSize = EnclaveSize::new(0x100_0000, 0x20_0000);
+
 #[handle_ecall]
 fn handle_init_enclave(_args: &InitEnclaveInput) -> Result<InitEnclaveOutput> {
-    mesatee_core::init_service(env!("CARGO_PKG_NAME"))?;
+    mesatee_core::init_service_with_hints(env!("CARGO_PKG_NAME"), &SIZE_HINTS)?;
+    crate::storage::init(crate::storage::configured())?;
+
+    // TMS still starts if its storage is unavailable, without the previous
+    // tasks.
+    match crate::task_recovery::recover_tasks() {
+        Ok(count) => info!("Enclave [TMS]: Recovered {} tasks.", count),
+        Err(e) => warn!("Enclave [TMS]: Cannot recover tasks: {}", e),
+    }
+    match crate::user_revocation::recover_revoked_users() {
+        Ok(count) => info!("Enclave [TMS]: Recovered {} revoked users.", count),
+        Err(e) => warn!("Enclave [TMS]: Cannot recover revoked users: {}", e),
+    }
 
     if cfg!(test_mode) {
         crate::data_store::add_test_information();
@@ -46,6 +67,10 @@ fn handle_init_enclave(_args: &InitEnclaveInput) -> Result<InitEnclaveOutput> {
 
 #[handle_ecall]
 fn handle_finalize_enclave(_args: &FinalizeEnclaveInput) -> Result<FinalizeEnclaveOutput> {
+    // Stop serving new requests. In-flight requests are completed by their
+    // own ServeConnection ecalls.
+    server::shutdown();
+
     #[cfg(feature = "cov")]
     sgx_cov::cov_writeout();
 
@@ -62,22 +87,27 @@ fn handle_serve_connection(args: &ServeConnectionInput) -> Result<ServeConnectio
 
     if args.port == internal.addr.port() {
         let enclave_attr = match internal.inbound_desc {
-            config::InboundDesc::Sgx(enclave_attr) => Some(enclave_attr),
+            config::InboundDesc::Sgx(enclave_attr) => enclave_attr,
             _ => unreachable!(),
         };
 
+        // Peers are also checked for every request, against the inbound
+        // services as of the connection.
+        let inbound = InboundServices::new(enclave_attr.clone());
         let server = match SgxTrustedServer::new(
             TMSInternalEnclave::default(),
             args.socket_fd,
-            enclave_attr,
+            Some(enclave_attr),
         ) {
-            Ok(s) => s,
+            Ok(s) => s.interceptor(inbound),
             Err(e) => {
                 error!("New server failed: {:?}.", e);
                 return Ok(ServeConnectionOutput::default());
             }
         };
-        let _ = server.start();
+        let _ = server
+            .reject(args.reject)
+            .start_with_shutdown(server::shutdown_signal());
     } else if args.port == external.addr.port() {
         let enclave_attr = match external.inbound_desc {
             config::InboundDesc::External => None,
@@ -89,16 +119,49 @@ fn handle_serve_connection(args: &ServeConnectionInput) -> Result<ServeConnectio
             args.socket_fd,
             enclave_attr,
         ) {
-            Ok(s) => s,
+            Ok(s) => s
+                .interceptor(AuthInterceptor)
+                .interceptor(RoleInterceptor)
+                .interceptor(RateLimitInterceptor),
             Err(e) => {
                 error!("New server failed: {:?}.", e);
                 return Ok(ServeConnectionOutput::default());
             }
         };
-        let _ = server.start();
+        let _ = server
+            .reject(args.reject)
+            .start_with_shutdown(server::shutdown_signal());
     } else {
         unreachable!()
     }
 
     Ok(ServeConnectionOutput::default())
 }
+
+#[handle_ecall]
+fn handle_run_maintenance(args: &RunMaintenanceInput) -> Result<RunMaintenanceOutput> {
+    let count = crate::task_schedule::promote_due_tasks()?;
+    if count > 0 {
+        info!("Enclave [TMS]: Promoted {} scheduled tasks.", count);
+    }
+    let count = crate::task_retry::requeue_lost_tasks()?;
+    if count > 0 {
+        warn!("Enclave [TMS]: Requeued {} tasks of gone workers.", count);
+    }
+    if args.sweep {
+        let count = crate::task_retention::sweep()?;
+        if count > 0 {
+            info!("Enclave [TMS]: Deleted {} expired tasks.", count);
+        }
+        crate::data_store::IDEMPOTENCY_KEY_STORE.sweep()?;
+    }
+    if let Err(e) = crate::tms_external::TDFS_CHANNEL_POOL.keep_alive() {
+        warn!("Enclave [TMS]: Cannot reconnect to TDFS: {}", e);
+    }
+    Ok(RunMaintenanceOutput::default())
+}
+
+#[handle_ecall]
+fn handle_get_metrics(_args: &GetMetricsInput) -> Result<GetMetricsOutput> {
+    Ok(GetMetricsOutput::new(mesatee_core::metrics::render()))
+}
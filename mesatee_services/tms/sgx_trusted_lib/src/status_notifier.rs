@@ -0,0 +1,117 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use mesatee_core::{Error, ErrorKind, Result};
+use std::collections::HashMap;
+use std::sync::{Arc, SgxCondvar, SgxMutex};
+use std::time::{Duration, SystemTime};
+use std::untrusted::time::SystemTimeEx;
+
+use crate::data_store::{TaskInfo, TASK_STORE};
+
+struct Waiters {
+    count: usize,
+    condvar: Arc<SgxCondvar>,
+}
+
+// Clients waiting for a task status change, keyed by task ID. An entry only
+// exists while a client is waiting on that task.
+pub struct StatusNotifier {
+    registry: SgxMutex<HashMap<String, Waiters>>,
+    max_waiters: usize,
+}
+
+impl StatusNotifier {
+    // Waiting clients occupy a server thread each, so at most `max_waiters`
+    // may wait at the same time.
+    pub fn new(max_waiters: usize) -> Self {
+        StatusNotifier {
+            registry: SgxMutex::new(HashMap::new()),
+            max_waiters,
+        }
+    }
+
+    // Called after the status of task `task_id` has been stored.
+    pub fn notify(&self, task_id: &str) -> Result<()> {
+        let registry = self.registry.lock()?;
+        if let Some(waiters) = registry.get(task_id) {
+            waiters.condvar.notify_all();
+        }
+        Ok(())
+    }
+
+    // Blocks until the status version of task `task_id` differs from
+    // `current_version`, the task reaches a final status, or `timeout` elapses,
+    // and returns the task as stored at that point. The wait is bounded by
+    // `timeout` even if the client has disconnected, and the registry entry is
+    // removed on every return path.
+    pub fn wait(&self, task_id: &str, current_version: u64, timeout: Duration) -> Result<TaskInfo> {
+        let deadline = SystemTime::now() + timeout;
+        let mut registry = self.registry.lock()?;
+        let waiting: usize = registry.values().map(|waiters| waiters.count).sum();
+        if waiting >= self.max_waiters {
+            return Err(Error::new(
+                ErrorKind::RPCRequestError,
+                "too many clients waiting for task status",
+            ));
+        }
+        let condvar = {
+            let waiters = registry
+                .entry(task_id.to_owned())
+                .or_insert_with(|| Waiters {
+                    count: 0,
+                    condvar: Arc::new(SgxCondvar::new()),
+                });
+            waiters.count += 1;
+            waiters.condvar.clone()
+        };
+
+        let result = loop {
+            let task_info = match TASK_STORE.get(&task_id.to_owned()) {
+                Ok(Some(task_info)) => task_info,
+                Ok(None) => break Err(Error::from(ErrorKind::NotFound)),
+                Err(err) => break Err(err),
+            };
+            if task_info.status_version != current_version || task_info.status.is_final() {
+                break Ok(task_info);
+            }
+            let remaining = match deadline.duration_since(SystemTime::now()) {
+                Ok(remaining) if remaining > Duration::from_secs(0) => remaining,
+                _ => break Ok(task_info),
+            };
+            registry = match condvar.wait_timeout(registry, remaining) {
+                Ok((guard, _)) => guard,
+                Err(poisoned) => poisoned.into_inner().0,
+            };
+        };
+
+        let remove = match registry.get_mut(task_id) {
+            Some(waiters) => {
+                waiters.count -= 1;
+                waiters.count == 0
+            }
+            None => false,
+        };
+        if remove {
+            registry.remove(task_id);
+        }
+        result
+    }
+}
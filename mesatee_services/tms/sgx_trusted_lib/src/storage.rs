@@ -0,0 +1,171 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// The state TMS keeps across restarts, e.g. task records and revoked users,
+// is stored as string entries in a `StorageBackend`. The backend is chosen by
+// `task_management.storage_backend` of the runtime config when the enclave
+// starts: TDFS in production, or enclave memory, which does not outlive TMS,
+// for tests and development.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use crate::tms_external::tdfs_client;
+use lazy_static::lazy_static;
+use mesatee_core::config;
+use mesatee_core::db::{Kvdb, Versioned, WriteOp};
+use mesatee_core::{Error, ErrorKind, Result};
+use std::sync::{Arc, SgxRwLock};
+use teaclave_config::runtime_config::StorageBackendKind;
+
+pub trait StorageBackend: Send + Sync {
+    // Value of `key` with the version of its latest write. Every write gives
+    // the entry a new version, greater than all the versions given before.
+    fn get_versioned(&self, key: &str) -> Result<Option<Versioned>>;
+
+    fn get(&self, key: &str) -> Result<Option<String>> {
+        Ok(self.get_versioned(key)?.map(|entry| entry.value))
+    }
+
+    // Replaces the value of `key`, if any.
+    fn put(&self, key: &str, value: &str) -> Result<()>;
+
+    // Replaces the value of `key` if it still has `version`, or sets it if
+    // `version` is 0 and there is none. Fails with `StorageConflict`
+    // otherwise. Returns the new version.
+    fn compare_and_put(&self, key: &str, value: &str, version: u64) -> Result<u64>;
+
+    // Deleting a missing key succeeds.
+    fn delete(&self, key: &str) -> Result<()>;
+
+    // Entries whose key starts with `prefix`, sorted by key.
+    fn scan(&self, prefix: &str) -> Result<Vec<(String, String)>>;
+
+    // Applies `ops` in order, all or none of them. A failed check fails with
+    // `StorageConflict`.
+    fn write(&self, ops: &[WriteOp]) -> Result<()>;
+}
+
+// Entries kept by TDFS.
+pub struct TdfsStorage;
+
+impl StorageBackend for TdfsStorage {
+    fn get_versioned(&self, key: &str) -> Result<Option<Versioned>> {
+        let entry = tdfs_client().get_entry(key)?;
+        Ok(entry.map(|entry| Versioned {
+            value: entry.value,
+            version: entry.version,
+        }))
+    }
+
+    fn put(&self, key: &str, value: &str) -> Result<()> {
+        tdfs_client().put_entry(key, value)
+    }
+
+    fn compare_and_put(&self, key: &str, value: &str, version: u64) -> Result<u64> {
+        tdfs_client().compare_and_put_entry(key, value, version)
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        tdfs_client().delete_entry(key)
+    }
+
+    fn scan(&self, prefix: &str) -> Result<Vec<(String, String)>> {
+        let entries = tdfs_client().scan_entries(prefix)?;
+        Ok(entries
+            .into_iter()
+            .map(|entry| (entry.key, entry.value))
+            .collect())
+    }
+
+    fn write(&self, ops: &[WriteOp]) -> Result<()> {
+        tdfs_client().write_entries(ops)
+    }
+}
+
+// Entries kept in enclave memory, lost when TMS stops.
+pub struct MemoryStorage {
+    entries: Kvdb,
+}
+
+impl Default for MemoryStorage {
+    fn default() -> Self {
+        MemoryStorage {
+            entries: Kvdb::open().expect("failed to open database"),
+        }
+    }
+}
+
+impl StorageBackend for MemoryStorage {
+    fn get_versioned(&self, key: &str) -> Result<Option<Versioned>> {
+        self.entries.get(key)
+    }
+
+    fn put(&self, key: &str, value: &str) -> Result<()> {
+        self.entries.put(key, value)?;
+        Ok(())
+    }
+
+    fn compare_and_put(&self, key: &str, value: &str, version: u64) -> Result<u64> {
+        self.entries.compare_and_put(key, value, version)
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        self.entries.delete(key)
+    }
+
+    fn scan(&self, prefix: &str) -> Result<Vec<(String, String)>> {
+        let entries = self.entries.scan(prefix)?;
+        Ok(entries
+            .into_iter()
+            .map(|(key, entry)| (key, entry.value))
+            .collect())
+    }
+
+    fn write(&self, ops: &[WriteOp]) -> Result<()> {
+        self.entries.write(ops)?;
+        Ok(())
+    }
+}
+
+lazy_static! {
+    static ref BACKEND: SgxRwLock<Option<Arc<dyn StorageBackend>>> = SgxRwLock::new(None);
+}
+
+// Backend selected by the runtime config.
+pub fn configured() -> Arc<dyn StorageBackend> {
+    match config::runtime_config().task_management.storage_backend {
+        StorageBackendKind::Tdfs => Arc::new(TdfsStorage),
+        StorageBackendKind::Memory => Arc::new(MemoryStorage::default()),
+    }
+}
+
+// Sets the backend all the state of TMS is stored in. Called at enclave init,
+// before any request is served.
+pub fn init(backend: Arc<dyn StorageBackend>) -> Result<()> {
+    *BACKEND.write()? = Some(backend);
+    Ok(())
+}
+
+pub fn backend() -> Result<Arc<dyn StorageBackend>> {
+    BACKEND.read()?.clone().ok_or_else(|| {
+        Error::new(
+            ErrorKind::MissingValue,
+            "storage backend is not initialized",
+        )
+    })
+}
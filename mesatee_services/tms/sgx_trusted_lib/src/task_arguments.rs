@@ -0,0 +1,90 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// Tasks may take their arguments from a file of the task creator, e.g. a
+// large configuration reused by several tasks. The file is pinned to the
+// digest TDFS recorded for it when the task is created, and checked again
+// when the task is staged: a file which is gone, or whose digest changed,
+// fails the task. The executor reads and parses the file when the task runs,
+// and the inline arguments of the task replace those of the file with the
+// same name.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use crate::access_control;
+use crate::data_store::{ArgumentsFile, TaskInfo};
+use crate::tms_external::tdfs_client;
+use mesatee_core::{Error, ErrorKind, Result};
+
+// Digest algorithm and digest TDFS recorded for the file `file_id`, which
+// `user_id` must be able to read.
+fn readable_file_digest(file_id: &str, user_id: &str) -> Result<(String, String)> {
+    let file_ids = [file_id.to_owned()];
+    if let Some((_, err)) = access_control::file_access_errors(&file_ids, user_id)?
+        .into_iter()
+        .next()
+    {
+        return Err(err);
+    }
+    let file_info = tdfs_client()
+        .get_files(&file_ids)?
+        .pop()
+        .and_then(|file_info| file_info)
+        .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+    Ok((file_info.digest_algorithm, file_info.sha256))
+}
+
+// Pins the arguments file `file_id` of a task of `user_id`.
+pub fn pin_arguments_file(file_id: &str, user_id: &str) -> Result<ArgumentsFile> {
+    let (digest_algorithm, digest) = readable_file_digest(file_id, user_id)?;
+    Ok(ArgumentsFile {
+        file_id: file_id.to_owned(),
+        digest_algorithm,
+        digest,
+    })
+}
+
+// Why the task creator cannot read the arguments file of the task as it was
+// pinned, if they cannot.
+pub fn arguments_file_error(task_info: &TaskInfo) -> Result<Option<Error>> {
+    let arguments_file = match task_info.arguments_file {
+        Some(ref arguments_file) => arguments_file,
+        None => return Ok(None),
+    };
+    let file_id = &arguments_file.file_id;
+    let (digest_algorithm, digest) = match readable_file_digest(file_id, &task_info.user_id) {
+        Ok(pinned) => pinned,
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            let message = format!("arguments file {} is missing", file_id);
+            return Ok(Some(Error::new(ErrorKind::NotFound, message)));
+        }
+        Err(err) if err.kind() == ErrorKind::PermissionDenied => {
+            let message = format!("arguments file {} is no longer accessible", file_id);
+            return Ok(Some(Error::new(ErrorKind::PermissionDenied, message)));
+        }
+        Err(err) => return Err(err),
+    };
+    if digest_algorithm != arguments_file.digest_algorithm || digest != arguments_file.digest {
+        let message = format!(
+            "arguments file {} does not match the digest it was pinned to",
+            file_id
+        );
+        return Ok(Some(Error::new(ErrorKind::DataIntegrityError, message)));
+    }
+    Ok(None)
+}
@@ -0,0 +1,155 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// Tasks created with `content_addressed` get an ID derived from what they
+// compute, so that resubmitting an identical task finds the task created
+// first. The content is hashed with `mesatee_core::content_id` in the form
+// documented in `docs/api.md`. Input files are given by their TDFS digests
+// rather than their IDs, secrets by their references rather than their values,
+// and the settings which do not change the results of a task, e.g. its
+// priority or retention, are left out.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use crate::data_store::TaskInfo;
+use crate::tms_external::tdfs_client;
+use mesatee_core::content_id::ContentHasher;
+use mesatee_core::{Error, ErrorKind, Result};
+use std::collections::HashMap;
+
+const TASK_ID_DOMAIN: &str = "teaclave-task-v1";
+
+// ID of the task `task_info` describes, before the results of its
+// dependencies are added to its input files.
+pub fn content_task_id(task_info: &TaskInfo) -> Result<String> {
+    let file_ids: Vec<String> = task_info
+        .input_files
+        .iter()
+        .map(|file| file.file_id.clone())
+        .collect();
+    let file_infos = tdfs_client().get_files(&file_ids)?;
+    let mut digests: HashMap<&str, (String, String)> = HashMap::new();
+    for (file_id, file_info) in file_ids.iter().zip(file_infos.into_iter()) {
+        let file_info = file_info.ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+        digests.insert(
+            file_id.as_str(),
+            (file_info.digest_algorithm, file_info.sha256),
+        );
+    }
+    let digest_of = |file_id: &str| {
+        digests
+            .get(file_id)
+            .ok_or_else(|| Error::from(ErrorKind::NotFound))
+    };
+
+    let mut hasher = ContentHasher::new(TASK_ID_DOMAIN);
+    hasher.field(task_info.user_id.as_bytes());
+    hasher.field(
+        task_info
+            .function_id
+            .as_ref()
+            .map_or(&[][..], |function_id| function_id.as_bytes()),
+    );
+    hasher.field(task_info.function_name.as_bytes());
+    hasher.field(&serde_json::to_vec(&task_info.arguments)?);
+
+    let mut collaborators: Vec<&str> = task_info
+        .collaborator_list
+        .iter()
+        .map(|collaborator| collaborator.user_id.as_str())
+        .collect();
+    collaborators.sort();
+    hasher.fields(&collaborators);
+
+    hasher.count(task_info.input_files.len());
+    for file in task_info.input_files.iter() {
+        let (algorithm, digest) = digest_of(&file.file_id)?;
+        hasher.field(algorithm.as_bytes()).field(digest.as_bytes());
+    }
+    hasher.count(task_info.input_ports.len());
+    for (port, file_id) in task_info.input_ports.iter() {
+        let (algorithm, digest) = digest_of(file_id)?;
+        hasher
+            .field(port.as_bytes())
+            .field(algorithm.as_bytes())
+            .field(digest.as_bytes());
+    }
+    hasher.count(task_info.input_collections.len());
+    for collection in task_info.input_collections.iter() {
+        hasher
+            .field(collection.name.as_bytes())
+            .count(collection.file_ids.len());
+        for file_id in collection.file_ids.iter() {
+            let (algorithm, digest) = digest_of(file_id)?;
+            hasher.field(algorithm.as_bytes()).field(digest.as_bytes());
+        }
+    }
+
+    hasher.fields(&task_info.dependencies);
+    hasher.field(
+        task_info
+            .output_key
+            .as_ref()
+            .map_or(&[][..], |output_key| output_key.key_id.as_bytes()),
+    );
+    // Only stored secrets are hashed, by reference. Tasks without secrets
+    // hash nothing more, so that their IDs stay the same.
+    if !task_info.secrets.is_empty() {
+        let mut secrets: Vec<(&str, &str, &str)> = Vec::with_capacity(task_info.secrets.len());
+        for secret in task_info.secrets.iter() {
+            let name = secret.name.as_ref().ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidInputError,
+                    "tasks with inline secrets cannot be content addressed",
+                )
+            })?;
+            let owner = secret.owner.as_ref().unwrap_or(&task_info.user_id);
+            secrets.push((secret.env.as_str(), owner.as_str(), name.as_str()));
+        }
+        secrets.sort();
+        hasher.count(secrets.len());
+        for (env, owner, name) in secrets.iter() {
+            hasher
+                .field(env.as_bytes())
+                .field(owner.as_bytes())
+                .field(name.as_bytes());
+        }
+    }
+    // Input ports bound to output ports of dependencies, likewise only when
+    // there are any. The map is ordered by input port.
+    if !task_info.input_edges.is_empty() {
+        hasher.count(task_info.input_edges.len());
+        for (port, edge) in task_info.input_edges.iter() {
+            hasher
+                .field(port.as_bytes())
+                .field(edge.task_id.as_bytes())
+                .field(edge.port.as_bytes());
+        }
+    }
+    // The content type of the result file, likewise only with one.
+    if let Some(content_type) = task_info.result_format.content_type() {
+        hasher.field(content_type.as_bytes());
+    }
+    // The pinned digest of the arguments file, likewise only with one.
+    if let Some(ref arguments_file) = task_info.arguments_file {
+        hasher
+            .field(arguments_file.digest_algorithm.as_bytes())
+            .field(arguments_file.digest.as_bytes());
+    }
+    Ok(hasher.finish())
+}
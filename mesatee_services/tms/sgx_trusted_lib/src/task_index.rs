@@ -0,0 +1,181 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+// Secondary indexes of the tasks for `QueryTasks`, keyed by function and
+// status, and by owner. Each index entry is the creation time and the ID of
+// a task, so that a time range of the tasks of a key is read without
+// scanning other tasks. The indexes are updated wherever a task is added,
+// changes status or is removed, see `data_store`.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use crate::data_store::{parse_function_id, TaskInfo, TaskStatus};
+use lazy_static::lazy_static;
+use mesatee_core::{Error, ErrorKind, Result};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::SgxMutex;
+
+type Entries = BTreeSet<(u64, String)>;
+
+lazy_static! {
+    static ref FUNCTION_STATUS_INDEX: SgxMutex<HashMap<(String, TaskStatus), Entries>> =
+        SgxMutex::new(HashMap::new());
+    static ref OWNER_INDEX: SgxMutex<HashMap<String, Entries>> = SgxMutex::new(HashMap::new());
+}
+
+// Tasks of a registered function are indexed by its unversioned ID, those of
+// a built-in function by its name.
+pub fn function_key(function_id: &str) -> &str {
+    match parse_function_id(function_id) {
+        Ok((id, _)) => id,
+        Err(_) => function_id,
+    }
+}
+
+fn task_function_key(task_info: &TaskInfo) -> String {
+    match task_info.function_id {
+        Some(ref function_id) => function_key(function_id).to_owned(),
+        None => task_info.function_name.to_owned(),
+    }
+}
+
+fn insert<K: std::hash::Hash + Eq>(index: &mut HashMap<K, Entries>, key: K, entry: (u64, String)) {
+    index.entry(key).or_insert_with(BTreeSet::new).insert(entry);
+}
+
+fn remove<K: std::hash::Hash + Eq>(
+    index: &mut HashMap<K, Entries>,
+    key: &K,
+    entry: &(u64, String),
+) {
+    let now_empty = match index.get_mut(key) {
+        Some(entries) => {
+            entries.remove(entry);
+            entries.is_empty()
+        }
+        None => false,
+    };
+    if now_empty {
+        index.remove(key);
+    }
+}
+
+pub fn add(task_id: &str, task_info: &TaskInfo) -> Result<()> {
+    let entry = (task_info.created_at, task_id.to_owned());
+    let key = (task_function_key(task_info), task_info.status);
+    insert(&mut *FUNCTION_STATUS_INDEX.lock()?, key, entry.clone());
+    insert(
+        &mut *OWNER_INDEX.lock()?,
+        task_info.user_id.to_owned(),
+        entry,
+    );
+    Ok(())
+}
+
+// Moves the task from the entries of `previous` to those of its status.
+pub fn update_status(task_id: &str, task_info: &TaskInfo, previous: TaskStatus) -> Result<()> {
+    if previous == task_info.status {
+        return Ok(());
+    }
+    let entry = (task_info.created_at, task_id.to_owned());
+    let function_key = task_function_key(task_info);
+    let mut index = FUNCTION_STATUS_INDEX.lock()?;
+    remove(&mut *index, &(function_key.clone(), previous), &entry);
+    insert(&mut *index, (function_key, task_info.status), entry);
+    Ok(())
+}
+
+pub fn remove_task(task_id: &str, task_info: &TaskInfo) -> Result<()> {
+    let entry = (task_info.created_at, task_id.to_owned());
+    let key = (task_function_key(task_info), task_info.status);
+    remove(&mut *FUNCTION_STATUS_INDEX.lock()?, &key, &entry);
+    remove(&mut *OWNER_INDEX.lock()?, &task_info.user_id, &entry);
+    Ok(())
+}
+
+// Filters of a task query. All bounds are inclusive.
+pub struct Query<'a> {
+    pub function_id: Option<&'a str>,
+    pub status: Option<TaskStatus>,
+    pub owner: Option<&'a str>,
+    pub since: u64,
+    pub until: u64,
+}
+
+fn select(entries: &Entries, query: &Query, after: &(u64, String), selected: &mut Entries) {
+    let from = if after.0 >= query.since {
+        after.clone()
+    } else {
+        (query.since, String::new())
+    };
+    for entry in entries.range(from..) {
+        if entry.0 > query.until {
+            break;
+        }
+        if entry > after {
+            selected.insert(entry.clone());
+        }
+    }
+}
+
+// Candidate tasks of `query` created after `after`, in ascending order of
+// creation time and ID. Only the filters covered by the index read are
+// applied, the caller checks the others on the tasks.
+pub fn candidates(query: &Query, after: &(u64, String)) -> Result<Entries> {
+    let mut selected = BTreeSet::new();
+    if let Some(function_id) = query.function_id {
+        let function_key = function_key(function_id);
+        let index = FUNCTION_STATUS_INDEX.lock()?;
+        for ((key, status), entries) in index.iter() {
+            if key == function_key && query.status.map_or(true, |s| s == *status) {
+                select(entries, query, after, &mut selected);
+            }
+        }
+    } else if let Some(owner) = query.owner {
+        if let Some(entries) = OWNER_INDEX.lock()?.get(owner) {
+            select(entries, query, after, &mut selected);
+        }
+    } else {
+        let index = FUNCTION_STATUS_INDEX.lock()?;
+        for ((_, status), entries) in index.iter() {
+            if query.status.map_or(true, |s| s == *status) {
+                select(entries, query, after, &mut selected);
+            }
+        }
+    }
+    Ok(selected)
+}
+
+pub fn encode_cursor(entry: &(u64, String)) -> String {
+    format!("{}/{}", entry.0, entry.1)
+}
+
+// The empty cursor starts before the first task.
+pub fn decode_cursor(cursor: &str) -> Result<(u64, String)> {
+    if cursor.is_empty() {
+        return Ok((0, String::new()));
+    }
+    let mut parts = cursor.splitn(2, '/');
+    let created_at = parts.next().and_then(|s| s.parse::<u64>().ok());
+    match (created_at, parts.next()) {
+        (Some(created_at), Some(task_id)) => Ok((created_at, task_id.to_owned())),
+        _ => Err(Error::new(
+            ErrorKind::InvalidInputError,
+            format!("invalid cursor {}", cursor),
+        )),
+    }
+}
@@ -0,0 +1,84 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// The files of a task are bounded by the runtime config, so that a task
+// referencing huge numbers of files cannot exhaust the memory of TMS and of
+// the execution enclave, which prepares all the inputs of a task before it
+// runs.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use crate::data_store::TaskFile;
+use crate::tms_external::tdfs_client;
+use mesatee_core::config;
+use mesatee_core::{Error, ErrorKind, Result};
+
+// Rejects `count` input files, before they are looked up.
+pub fn check_input_count(count: usize) -> Result<()> {
+    let max_input_files = config::runtime_config().task_inputs.max_input_files;
+    if count > max_input_files {
+        return Err(Error::new(
+            ErrorKind::InvalidInputError,
+            format!(
+                "task has {} input files, exceeding max_input_files of {}",
+                count, max_input_files
+            ),
+        ));
+    }
+    Ok(())
+}
+
+// Checks the number and the total size of the input files of a task.
+pub fn check_input_files(input_files: &[TaskFile]) -> Result<()> {
+    check_input_count(input_files.len())?;
+    let file_ids: Vec<String> = input_files
+        .iter()
+        .map(|file| file.file_id.to_string())
+        .collect();
+    let mut input_bytes: u64 = 0;
+    for file_info in tdfs_client().get_files(&file_ids)?.into_iter() {
+        let file_info = file_info.ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+        input_bytes = input_bytes.saturating_add(file_info.file_size);
+    }
+    let max_input_bytes = config::runtime_config().task_inputs.max_input_bytes;
+    if input_bytes > max_input_bytes {
+        return Err(Error::new(
+            ErrorKind::InvalidInputError,
+            format!(
+                "input files of task have {} bytes, exceeding max_input_bytes of {}",
+                input_bytes, max_input_bytes
+            ),
+        ));
+    }
+    Ok(())
+}
+
+// Checks the number of output files of a task.
+pub fn check_output_count(count: usize) -> Result<()> {
+    let max_output_files = config::runtime_config().task_management.max_output_files;
+    if count > max_output_files {
+        return Err(Error::new(
+            ErrorKind::InvalidInputError,
+            format!(
+                "task has {} output files, exceeding max_output_files of {}",
+                count, max_output_files
+            ),
+        ));
+    }
+    Ok(())
+}
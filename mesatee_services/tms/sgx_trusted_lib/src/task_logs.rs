@@ -0,0 +1,167 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// Logs of tasks: the lines their functions wrote to stdout and stderr, which
+// the execution service captures and redacts, and appends while the task
+// runs. Logs are kept in enclave memory until their task is removed, and do
+// not outlive TMS. A log holds at most `task_logs.max_bytes` bytes of lines;
+// the first line beyond is replaced by a marker and later lines are dropped.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use mesatee_core::{Error, ErrorKind, Result};
+use std::collections::HashMap;
+use std::sync::{SgxCondvar, SgxMutex};
+use std::time::{Duration, SystemTime};
+use std::untrusted::time::SystemTimeEx;
+
+use crate::data_store::{TaskLogLine, TASK_STORE};
+
+#[derive(Default)]
+struct TaskLog {
+    lines: Vec<TaskLogLine>,
+    bytes: usize,
+    truncated: bool,
+}
+
+struct State {
+    logs: HashMap<String, TaskLog>,
+    waiting: usize,
+}
+
+// Lines of a log from a given line on, as returned to a tailing client.
+pub struct LogSlice {
+    pub lines: Vec<TaskLogLine>,
+    pub next: u64,
+    pub truncated: bool,
+    // The task has ended, so no lines follow.
+    pub complete: bool,
+}
+
+pub struct TaskLogs {
+    state: SgxMutex<State>,
+    // Signalled when lines are appended and when a task status changes.
+    changed: SgxCondvar,
+    max_waiters: usize,
+    max_bytes: usize,
+}
+
+impl TaskLogs {
+    // Tailing clients occupy a server thread each, so at most `max_waiters`
+    // may wait at the same time.
+    pub fn new(max_waiters: usize, max_bytes: usize) -> Self {
+        TaskLogs {
+            state: SgxMutex::new(State {
+                logs: HashMap::new(),
+                waiting: 0,
+            }),
+            changed: SgxCondvar::new(),
+            max_waiters,
+            max_bytes,
+        }
+    }
+
+    pub fn append(&self, task_id: &str, lines: &[TaskLogLine]) -> Result<()> {
+        let mut state = self.state.lock()?;
+        let log = state
+            .logs
+            .entry(task_id.to_owned())
+            .or_insert_with(TaskLog::default);
+        for line in lines.iter() {
+            if log.truncated {
+                break;
+            }
+            if log.bytes + line.line.len() > self.max_bytes {
+                log.truncated = true;
+                log.lines.push(TaskLogLine {
+                    stream: line.stream,
+                    line: format!("[log truncated at {} bytes]", self.max_bytes),
+                });
+                break;
+            }
+            log.bytes += line.line.len();
+            log.lines.push(line.clone());
+        }
+        self.changed.notify_all();
+        Ok(())
+    }
+
+    // Called after the status of a task has been stored, so that clients
+    // tailing its log see it end.
+    pub fn notify(&self) -> Result<()> {
+        let _state = self.state.lock()?;
+        self.changed.notify_all();
+        Ok(())
+    }
+
+    pub fn remove(&self, task_id: &str) -> Result<()> {
+        let mut state = self.state.lock()?;
+        state.logs.remove(task_id);
+        Ok(())
+    }
+
+    // Blocks until the log of task `task_id` has lines from line `from` on,
+    // the task reaches a final status, or `timeout` elapses, and returns the
+    // lines from there.
+    pub fn wait(&self, task_id: &str, from: u64, timeout: Duration) -> Result<LogSlice> {
+        let deadline = SystemTime::now() + timeout;
+        let mut state = self.state.lock()?;
+        if state.waiting >= self.max_waiters {
+            return Err(Error::new(
+                ErrorKind::RPCRequestError,
+                "too many clients streaming task logs",
+            ));
+        }
+        state.waiting += 1;
+
+        let result = loop {
+            let ended = match TASK_STORE.get(&task_id.to_owned()) {
+                Ok(Some(task_info)) => task_info.status.is_final(),
+                Ok(None) => break Err(Error::from(ErrorKind::NotFound)),
+                Err(err) => break Err(err),
+            };
+            let (lines, truncated) = match state.logs.get(task_id) {
+                Some(log) => {
+                    let lines: Vec<TaskLogLine> =
+                        log.lines.iter().skip(from as usize).cloned().collect();
+                    (lines, log.truncated)
+                }
+                None => (Vec::new(), false),
+            };
+            let remaining = match deadline.duration_since(SystemTime::now()) {
+                Ok(remaining) => remaining,
+                Err(_) => Duration::from_secs(0),
+            };
+            if !lines.is_empty() || ended || remaining == Duration::from_secs(0) {
+                break Ok(LogSlice {
+                    next: from + lines.len() as u64,
+                    lines,
+                    truncated,
+                    complete: ended,
+                });
+            }
+            state = match self.changed.wait_timeout(state, remaining) {
+                Ok((guard, _)) => guard,
+                Err(poisoned) => poisoned.into_inner().0,
+            };
+        };
+
+        state.waiting -= 1;
+        result
+    }
+}
@@ -0,0 +1,114 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// A finished task gets a receipt of the function it ran and the digests of
+// its input and output files, signed with the key of the RA certificate of
+// TMS. The certificate comes with the receipt, so that the task creator can
+// show anyone who trusts the TMS measurement what the task did, without
+// talking to TMS.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use crate::data_store::{self, ReceiptFile, SignedTaskReceipt, TaskFile, TaskInfo, TaskReceipt};
+use crate::task_retention;
+use crate::tms_external::tdfs_client;
+use mesatee_core::health::EnclaveIdentity;
+use mesatee_core::rpc::sgx::sign_with_ra_key;
+use mesatee_core::{Error, ErrorKind, Result};
+use std::time::Duration;
+use tdfs_internal_client::DigestAlgorithm;
+use teaclave_utils::clock;
+
+fn receipt_files(files: &[TaskFile]) -> Result<Vec<ReceiptFile>> {
+    let file_ids: Vec<String> = files.iter().map(|file| file.file_id.clone()).collect();
+    let file_infos = tdfs_client().get_files(&file_ids)?;
+    file_ids
+        .into_iter()
+        .zip(file_infos.into_iter())
+        .map(|(file_id, file_info)| {
+            let file_info = file_info.ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+            Ok(ReceiptFile {
+                file_id,
+                digest_algorithm: file_info.digest_algorithm,
+                digest: file_info.sha256,
+            })
+        })
+        .collect()
+}
+
+// Finish and issue times of the receipt of `task_info`, no earlier than the
+// creation time. The clock of the host may have moved back since the task was
+// created, by no more than the clock skew tolerance.
+fn receipt_times(task_info: &TaskInfo) -> Result<(u64, u64)> {
+    let now = task_retention::now();
+    let skew = task_retention::clock_skew();
+    let created_at = task_info.created_at;
+    if !clock::has_come(
+        Duration::from_secs(now),
+        Duration::from_secs(created_at),
+        Duration::from_secs(skew),
+    ) {
+        return Err(Error::new(
+            ErrorKind::SystemTimeError,
+            format!(
+                "clock moved back by {} seconds since the task was created",
+                created_at - now
+            ),
+        ));
+    }
+    let finished_at = task_info.finished_at.unwrap_or_default().max(created_at);
+    Ok((finished_at, now.max(finished_at)))
+}
+
+// Issues the receipt of task `task_id`, which has just finished.
+pub fn issue(task_id: &str, task_info: &TaskInfo) -> Result<SignedTaskReceipt> {
+    let (function_version, function_sha256) = match task_info.function_id {
+        Some(ref function_id) => {
+            let function_info = data_store::get_function(function_id)?;
+            let payload = function_info.payload.unwrap_or_default();
+            let digest = DigestAlgorithm::Sha256.cal_digest(payload.as_bytes(), &[])?;
+            (Some(function_info.version), Some(digest))
+        }
+        None => (None, None),
+    };
+    let identity = EnclaveIdentity::current()?;
+    let (finished_at, issued_at) = receipt_times(task_info)?;
+
+    let receipt = TaskReceipt {
+        task_id: task_id.to_owned(),
+        user_id: task_info.user_id.clone(),
+        function_name: task_info.function_name.clone(),
+        function_id: task_info.function_id.clone(),
+        function_version,
+        function_sha256,
+        input_files: receipt_files(&task_info.input_files)?,
+        output_files: receipt_files(&task_info.output_files)?,
+        created_at: task_info.created_at,
+        finished_at,
+        issued_at,
+        mr_enclave: identity.mr_enclave,
+        mr_signer: identity.mr_signer,
+    };
+    let receipt = serde_json::to_string(&receipt)?;
+    let (signature, certificate) = sign_with_ra_key(receipt.as_bytes())?;
+    Ok(SignedTaskReceipt {
+        receipt,
+        signature,
+        certificate,
+    })
+}
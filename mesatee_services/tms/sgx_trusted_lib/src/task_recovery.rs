@@ -0,0 +1,209 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// Tasks are kept in memory by TMS. Every change of a task is also recorded
+// in the storage backend, which outlives TMS restarts, and the tasks are
+// restored from their records when the TMS enclave starts. Records are
+// written with compare-and-swap, so that a change is not lost to a
+// concurrent write of the same record from a stale read. The records of
+// the tasks changed by one transition, e.g. a task and the dependents it
+// fails, are written in one atomic batch, so that a TMS stopping midway does
+// not restore some of the changes only.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use crate::data_store::{self, TaskInfo, TaskStatus, TASK_STATUS_LOCK, TASK_STORE};
+use crate::storage;
+use lazy_static::lazy_static;
+use mesatee_core::db::{self, WriteOp};
+use mesatee_core::Result;
+use std::collections::BTreeMap;
+use std::sync::SgxMutex;
+
+// Records are keyed by task ID under this prefix.
+const RECORD_PREFIX: &str = "task/";
+
+pub fn record_key(task_id: &str) -> String {
+    format!("{}{}", RECORD_PREFIX, task_id)
+}
+
+// Attempts to write records which keep conflicting with concurrent writes.
+const MAX_WRITE_ATTEMPTS: usize = 3;
+
+lazy_static! {
+    // Tasks changed in the open batch by ID, with their latest change.
+    // Transitions are made under `TASK_STATUS_LOCK`, so one batch is open at
+    // a time.
+    static ref BATCH: SgxMutex<Option<BTreeMap<String, TaskInfo>>> = SgxMutex::new(None);
+}
+
+// Runs `transition`, recording the tasks it changes in one batch. A nested
+// call joins the open batch. The batch is written even if `transition` fails,
+// since the changes made until then are kept in memory.
+pub fn in_batch<T, F>(transition: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T>,
+{
+    {
+        let mut batch = BATCH.lock()?;
+        if batch.is_some() {
+            drop(batch);
+            return transition();
+        }
+        *batch = Some(BTreeMap::new());
+    }
+    let result = transition();
+    let tasks = BATCH.lock()?.take().unwrap_or_default();
+    if !tasks.is_empty() {
+        if let Err(e) = write_records(&tasks) {
+            warn!("Cannot record {} changed tasks: {}", tasks.len(), e);
+        }
+    }
+    result
+}
+
+// A record is not replaced by the change of a task which a concurrent write
+// has meanwhile ended with another status, e.g. cancelled.
+fn is_stale(task_info: &TaskInfo, recorded: &TaskInfo) -> bool {
+    recorded.status.is_final() && recorded.status != task_info.status
+}
+
+// Writes the records of `tasks` atomically, each one only if it has not been
+// written since it was read. On a conflict the records are read again and
+// the write is retried.
+fn write_records(tasks: &BTreeMap<String, TaskInfo>) -> Result<()> {
+    let backend = storage::backend()?;
+    db::retry_on_conflict(MAX_WRITE_ATTEMPTS, || {
+        let mut ops: Vec<WriteOp> = Vec::with_capacity(tasks.len());
+        for (task_id, task_info) in tasks.iter() {
+            let key = record_key(task_id);
+            let version = match backend.get_versioned(&key)? {
+                Some(recorded) => {
+                    if let Ok(ref recorded) = serde_json::from_str::<TaskInfo>(&recorded.value) {
+                        if is_stale(task_info, recorded) {
+                            warn!("Not recording stale change of task {}", task_id);
+                            continue;
+                        }
+                    }
+                    recorded.version
+                }
+                None => 0,
+            };
+            ops.push(WriteOp::PutIfVersion {
+                key,
+                value: serde_json::to_string(task_info)?,
+                version,
+            });
+        }
+        if ops.is_empty() {
+            return Ok(());
+        }
+        backend.write(&ops)
+    })
+}
+
+// Recording is best effort, so that the storage being unavailable does not
+// fail the request changing the task. A task whose latest change is not
+// recorded is restored with its previous status. The key material of the
+// output key is not recorded, only its id.
+pub fn persist_task(task_id: &str, task_info: &TaskInfo) {
+    let mut task_info = task_info.clone();
+    if let Some(ref mut output_key) = task_info.output_key {
+        output_key.kek.clear();
+    }
+    let mut tasks = match BATCH.lock() {
+        Ok(mut batch) => match *batch {
+            Some(ref mut tasks) => {
+                tasks.insert(task_id.to_owned(), task_info);
+                return;
+            }
+            None => BTreeMap::new(),
+        },
+        Err(e) => {
+            warn!("Cannot record task {}: {}", task_id, e);
+            return;
+        }
+    };
+    tasks.insert(task_id.to_owned(), task_info);
+    if let Err(e) = write_records(&tasks) {
+        warn!("Cannot record task {}: {}", task_id, e);
+    }
+}
+
+// Restores the tasks recorded by the previous TMS instance, and returns the
+// number of restored tasks. Tasks resume with the status given by
+// `TaskStatus::after_restart`, and dependents of tasks which failed that way
+// fail too. Tasks which have not ended fail if they have an output key, since
+// its key material is not recorded. Called at enclave init, before any request is served.
+pub fn recover_tasks() -> Result<usize> {
+    let records = storage::backend()?.scan(RECORD_PREFIX)?;
+
+    let _lock = TASK_STATUS_LOCK.lock()?;
+    let mut recovered: Vec<(String, TaskInfo)> = Vec::with_capacity(records.len());
+    for (key, record) in records.iter() {
+        let task_id = &key[RECORD_PREFIX.len()..];
+        let task_info: TaskInfo = match serde_json::from_str(record) {
+            Ok(task_info) => task_info,
+            Err(e) => {
+                warn!("Skipping invalid record of task {}: {}", task_id, e);
+                continue;
+            }
+        };
+        data_store::index_task(task_id, &task_info)?;
+        recovered.push((task_id.to_owned(), task_info));
+    }
+
+    for (task_id, task_info) in recovered.iter_mut() {
+        let mut status = task_info.status.after_restart();
+        let key_lost = match task_info.output_key {
+            Some(ref output_key) => !output_key.is_complete(),
+            None => false,
+        };
+        if key_lost && !status.is_final() {
+            status = TaskStatus::Failed;
+        }
+        if status != task_info.status {
+            warn!(
+                "Task {} was {:?} when TMS stopped, marking it {:?}",
+                task_id, task_info.status, status
+            );
+            data_store::update_task_status(task_id, task_info, status)?;
+        }
+    }
+
+    // Outputs of finished dependencies have already been added to the inputs
+    // of their dependents.
+    for (task_id, task_info) in recovered.iter() {
+        if task_info.status != TaskStatus::Created {
+            continue;
+        }
+        for dependency_id in task_info.dependencies.iter() {
+            match TASK_STORE.get(dependency_id)? {
+                Some(ref dependency) if dependency.status == TaskStatus::Finished => {}
+                _ => data_store::add_dependent(dependency_id, task_id)?,
+            }
+        }
+    }
+    for (task_id, task_info) in recovered.iter() {
+        if task_info.status.is_final() && task_info.status != TaskStatus::Finished {
+            data_store::resolve_dependents(task_id, task_info)?;
+        }
+    }
+
+    Ok(recovered.len())
+}
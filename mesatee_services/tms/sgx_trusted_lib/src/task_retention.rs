@@ -0,0 +1,160 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// Tasks which have ended are kept with their results for a retention period,
+// the `result_ttl` of the runtime config unless the task creator chose
+// another one. Expired tasks are deleted by a sweep the TMS host runs
+// periodically, or earlier by their creator.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use crate::audit_log;
+use crate::data_store::{self, TaskInfo, TASK_STATUS_LOCK, TASK_STORE};
+use crate::storage;
+use crate::task_recovery;
+use crate::task_secret;
+use crate::tms_external::tdfs_client;
+use mesatee_core::config;
+use mesatee_core::{Error, ErrorKind, Result};
+use std::collections::HashSet;
+use teaclave_utils::clock::{Clock, SystemClock};
+use tms_external_proto::AuditEvent;
+
+// Seconds since the epoch, by the clock of the host.
+pub fn now() -> u64 {
+    SystemClock.now().as_secs()
+}
+
+// Seconds by which the clock of the host may be off, see
+// `teaclave_utils::clock`.
+pub fn clock_skew() -> u64 {
+    config::runtime_config().clock.skew_tolerance
+}
+
+// When the task and its results may be deleted, `None` until the task ends.
+pub fn expires_at(task_info: &TaskInfo) -> Option<u64> {
+    let retention = task_info
+        .retention_secs
+        .unwrap_or_else(|| config::runtime_config().task_management.result_ttl);
+    task_info
+        .finished_at
+        .map(|finished_at| finished_at.saturating_add(retention))
+}
+
+// What tasks which have not ended yet still need: their input files, and
+// their dependencies, which they check before becoming ready.
+struct LiveReferences {
+    files: HashSet<String>,
+    tasks: HashSet<String>,
+}
+
+impl LiveReferences {
+    fn collect() -> Result<Self> {
+        let mut references = LiveReferences {
+            files: HashSet::new(),
+            tasks: HashSet::new(),
+        };
+        for (_, task_info) in TASK_STORE.entries()? {
+            if task_info.status.is_final() {
+                continue;
+            }
+            for task_file in task_info.input_files.iter() {
+                references.files.insert(task_file.file_id.to_owned());
+            }
+            references.tasks.extend(task_info.dependencies.into_iter());
+        }
+        Ok(references)
+    }
+}
+
+// Files the task produced.
+fn result_files(task_info: &TaskInfo) -> Vec<&str> {
+    let mut files: Vec<&str> = task_info
+        .output_files
+        .iter()
+        .map(|task_file| task_file.file_id.as_str())
+        .collect();
+    if let Some(ref file_id) = task_info.task_result_file_id {
+        files.push(file_id);
+    }
+    files
+}
+
+// Deleting from TDFS is best effort. A file or record left behind is only
+// wasted space, since the task is gone from TMS.
+fn delete(task_id: &str, task_info: &TaskInfo, references: &LiveReferences) -> Result<()> {
+    data_store::remove_task(task_id, task_info)?;
+    let record_key = task_recovery::record_key(task_id);
+    if let Err(e) = storage::backend().and_then(|backend| backend.delete(&record_key)) {
+        warn!("Cannot delete record of task {}: {}", task_id, e);
+    }
+    task_secret::delete_task_secrets(task_id, task_info);
+    let mut client = tdfs_client();
+    for file_id in result_files(task_info) {
+        if references.files.contains(file_id) {
+            continue;
+        }
+        if let Err(e) = client.delete_file(file_id) {
+            warn!("Cannot delete file {} of task {}: {}", file_id, task_id, e);
+        }
+    }
+    Ok(())
+}
+
+// Deletes task `task_id`, which has ended, and its results.
+// Before calling this function, use TASK_STATUS_LOCK to avoid data race;
+pub fn delete_task(task_id: &str, task_info: &TaskInfo) -> Result<()> {
+    let references = LiveReferences::collect()?;
+    if references.tasks.contains(task_id) {
+        return Err(Error::new(
+            ErrorKind::InvalidInputError,
+            format!(
+                "task {} is a dependency of a task which has not ended",
+                task_id
+            ),
+        ));
+    }
+    delete(task_id, task_info, &references)
+}
+
+// Deletes the expired tasks and returns how many were deleted. Expired
+// dependencies of tasks which have not ended are kept until those end.
+pub fn sweep() -> Result<usize> {
+    let _lock = TASK_STATUS_LOCK.lock()?;
+    let now = now();
+    let references = LiveReferences::collect()?;
+    let mut count = 0;
+    for (task_id, task_info) in TASK_STORE.entries()? {
+        let expired = expires_at(&task_info).map_or(false, |expires_at| expires_at <= now);
+        if !expired || references.tasks.contains(&task_id) {
+            continue;
+        }
+        delete(&task_id, &task_info, &references)?;
+        audit_log::record(
+            audit_log::SYSTEM_USER_ID,
+            &AuditEvent {
+                operation: "expire_task".to_owned(),
+                targets: vec![task_id.to_owned()],
+                users: Vec::new(),
+                error: None,
+            },
+        );
+        count += 1;
+    }
+    Ok(count)
+}
@@ -0,0 +1,114 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// Executors report why an execution of a task failed. A task created with a
+// retry policy, whose execution failed for a transient reason, is scheduled
+// again after a backoff until it runs out of attempts. Other failures, e.g.
+// invalid inputs or denied permissions, would fail again and are final.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use crate::data_store::{
+    self, TaskAttempt, TaskError, TaskInfo, TaskRetryPolicy, TaskStatus, TASK_SCHEDULER,
+    TASK_STATUS_LOCK, TASK_STORE, WORKER_POOL,
+};
+use crate::task_recovery;
+use crate::task_retention::now;
+use crate::task_schedule;
+use mesatee_core::config;
+use mesatee_core::{Error, ErrorKind, Result};
+
+// The backoff stops doubling after this many retries.
+const MAX_BACKOFF_DOUBLINGS: u32 = 16;
+
+pub fn check_retry_policy(policy: &TaskRetryPolicy) -> Result<()> {
+    let max_attempts = config::runtime_config().task_management.max_task_attempts;
+    if policy.max_attempts == 0 || policy.max_attempts > max_attempts {
+        return Err(Error::new(
+            ErrorKind::InvalidInputError,
+            format!("max_attempts must be from 1 to {}", max_attempts),
+        ));
+    }
+    Ok(())
+}
+
+// Records the outcome of an execution of a running task, and returns the
+// status the task goes to: `status`, unless the failure is retried.
+pub fn end_attempt(
+    task_info: &mut TaskInfo,
+    status: TaskStatus,
+    error: Option<&TaskError>,
+) -> TaskStatus {
+    task_info.attempts.push(TaskAttempt {
+        status,
+        error: error.cloned(),
+        ended_at: now(),
+    });
+    let retryable = error.map_or(false, |error| error.retryable);
+    if status != TaskStatus::Failed || !retryable {
+        return status;
+    }
+    let policy = match task_info.retry_policy {
+        Some(policy) => policy,
+        None => return status,
+    };
+    let attempts = task_info.attempts.len() as u32;
+    if attempts >= policy.max_attempts {
+        return status;
+    }
+    let doublings = (attempts - 1).min(MAX_BACKOFF_DOUBLINGS);
+    let backoff = policy.backoff_secs.saturating_mul(1 << doublings);
+    task_info.not_before = Some(now().saturating_add(backoff));
+    task_schedule::ready_status(task_info)
+}
+
+// Queues again the tasks handed to the workers which stopped sending
+// heartbeats, and returns how many. A task which was running there has its
+// attempt recorded as failed for a transient reason, whatever its retry policy.
+pub fn requeue_lost_tasks() -> Result<usize> {
+    let _lock = TASK_STATUS_LOCK.lock()?;
+    let lost = WORKER_POOL.expire(now())?;
+    let mut count = 0;
+    for task_id in lost.iter() {
+        let mut task_info = match TASK_STORE.get(task_id)? {
+            Some(task_info) => task_info,
+            None => continue,
+        };
+        match task_info.status {
+            // Handed out but not started
+            TaskStatus::Ready => TASK_SCHEDULER.enqueue(task_id, task_info.priority)?,
+            TaskStatus::Running => {
+                task_info.attempts.push(TaskAttempt {
+                    status: TaskStatus::Failed,
+                    error: Some(TaskError {
+                        message: "the worker running the task is gone".to_owned(),
+                        retryable: true,
+                    }),
+                    ended_at: now(),
+                });
+                let status = task_schedule::ready_status(&task_info);
+                task_recovery::in_batch(|| {
+                    data_store::update_task_status(task_id, &mut task_info, status)
+                })?;
+            }
+            _ => continue,
+        }
+        count += 1;
+    }
+    Ok(count)
+}
@@ -0,0 +1,78 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// A task created with a `not_before` time is scheduled, instead of ready,
+// until that time. The TMS host periodically asks for due tasks to be
+// promoted, after which they are handed out like any other ready task. A task
+// is due up to the clock skew tolerance before its `not_before`, which the
+// clock of its creator may be ahead of.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use crate::data_store::{self, TaskInfo, TaskStatus, TASK_STATUS_LOCK, TASK_STORE};
+use crate::task_retention::{clock_skew, now};
+use mesatee_core::config;
+use mesatee_core::{Error, ErrorKind, Result};
+use std::time::Duration;
+use teaclave_utils::clock;
+
+// Status of a task which has everything it needs to run.
+pub fn ready_status(task_info: &TaskInfo) -> TaskStatus {
+    match task_info.not_before {
+        Some(not_before) if !is_due(not_before) => TaskStatus::Scheduled,
+        _ => TaskStatus::Ready,
+    }
+}
+
+fn is_due(not_before: u64) -> bool {
+    clock::has_come(
+        Duration::from_secs(now()),
+        Duration::from_secs(not_before),
+        Duration::from_secs(clock_skew()),
+    )
+}
+
+// Rejects start times further away than the configured `max_schedule_delay`,
+// plus the clock skew tolerance.
+pub fn check_not_before(not_before: u64) -> Result<()> {
+    let max_delay = config::runtime_config().task_management.max_schedule_delay;
+    if not_before > now().saturating_add(max_delay).saturating_add(clock_skew()) {
+        return Err(Error::new(
+            ErrorKind::InvalidInputError,
+            format!("not_before must be at most {} seconds from now", max_delay),
+        ));
+    }
+    Ok(())
+}
+
+// Makes the scheduled tasks whose time has come ready, and returns how many
+// were promoted.
+pub fn promote_due_tasks() -> Result<usize> {
+    let _lock = TASK_STATUS_LOCK.lock()?;
+    let mut count = 0;
+    for (task_id, mut task_info) in TASK_STORE.entries()? {
+        if task_info.status != TaskStatus::Scheduled {
+            continue;
+        }
+        if ready_status(&task_info) == TaskStatus::Ready {
+            data_store::update_task_status(&task_id, &mut task_info, TaskStatus::Ready)?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
@@ -0,0 +1,344 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// Users keep secrets, e.g. API keys, in the secret store of TMS and reference
+// them from their tasks, whose executors get them as environment variables.
+// Each value is encrypted under its own AEAD key created by KMS, like the
+// files of TDFS, and stored with the key id in the storage backend. A secret
+// may be referenced by its owner and by the readers the owner shared it with.
+// Values given inline with a task are sealed the same way under the ID of
+// the task when the task is created, and deleted with the task.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use crate::data_store::{TaskInfo, TaskSecret};
+use crate::storage;
+use crate::task_retention;
+use kms_proto::{self, KMSClient};
+use mesatee_core::config;
+use mesatee_core::{Error, ErrorKind, Result};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use tdfs_internal_client::{decrypt_data, encrypt_data};
+use tms_external_proto::SecretInfo;
+
+// Secrets of a user are stored under `secret/<owner>/<name>`, the inline
+// secrets of a task under `task_secret/<task_id>/<env>`.
+const SECRET_PREFIX: &str = "secret/";
+const TASK_SECRET_PREFIX: &str = "task_secret/";
+
+// Secrets are small, e.g. keys and credentials.
+const MAX_SECRET_LEN: usize = 64 * 1024;
+const MAX_NAME_LEN: usize = 128;
+const MAX_TASK_SECRETS: usize = 32;
+
+#[derive(Serialize, Deserialize)]
+struct SealedSecret {
+    // KMS key the value is encrypted under.
+    key_id: String,
+    // Base64 encoded, with the AEAD tag.
+    ciphertext: String,
+    #[serde(default)]
+    readers: Vec<String>,
+    updated_at: u64,
+}
+
+fn secret_key(owner: &str, name: &str) -> String {
+    format!("{}{}/{}", SECRET_PREFIX, owner, name)
+}
+
+fn task_secret_key(task_id: &str, env: &str) -> String {
+    format!("{}{}/{}", TASK_SECRET_PREFIX, task_id, env)
+}
+
+fn aead_config(key_config: kms_proto::KeyConfig) -> Result<kms_proto::proto::AeadConfig> {
+    match key_config {
+        kms_proto::KeyConfig::Aead(config) => Ok(kms_proto::proto::AeadConfig::from(config)),
+        kms_proto::KeyConfig::ProtectedFs(_) => Err(Error::from(ErrorKind::CryptoError)),
+    }
+}
+
+fn kms_client() -> Result<KMSClient> {
+    KMSClient::new(config::Internal::target_kms())
+}
+
+fn seal(value: &str, readers: &[String]) -> Result<SealedSecret> {
+    let req = kms_proto::proto::CreateKeyRequest::new(kms_proto::EncType::Aead);
+    let resp = kms_client()?.create_key(req)?;
+    let key = aead_config(resp.get_key_config()?)?;
+    let ciphertext = encrypt_data(value.as_bytes().to_vec(), &key.key, &key.nonce, &key.ad)?;
+    Ok(SealedSecret {
+        key_id: resp.get_key_id(),
+        ciphertext: base64::encode(&ciphertext),
+        readers: readers.to_vec(),
+        updated_at: task_retention::now(),
+    })
+}
+
+fn open(sealed: &SealedSecret) -> Result<String> {
+    let req = kms_proto::proto::GetKeyRequest::new(&sealed.key_id);
+    let key = aead_config(kms_client()?.get_key(req)?.get_key_config()?)?;
+    let ciphertext =
+        base64::decode(&sealed.ciphertext).map_err(|_| Error::from(ErrorKind::CryptoError))?;
+    let value = decrypt_data(ciphertext, &key.key, &key.nonce, &key.ad)?;
+    String::from_utf8(value).map_err(|_| Error::from(ErrorKind::CryptoError))
+}
+
+// Deleting the key is best effort, a key left behind opens nothing stored.
+fn delete_key(key_id: &str) {
+    let req = kms_proto::proto::DeleteKeyRequest::new(key_id);
+    if let Err(e) = kms_client().and_then(|mut client| client.del_key(req)) {
+        warn!("Cannot delete secret key {}: {}", key_id, e);
+    }
+}
+
+fn read(key: &str) -> Result<Option<SealedSecret>> {
+    match storage::backend()?.get(key)? {
+        Some(record) => Ok(Some(serde_json::from_str(&record)?)),
+        None => Ok(None),
+    }
+}
+
+fn check_name(kind: &str, name: &str, valid_char: fn(char) -> bool, expected: &str) -> Result<()> {
+    if name.is_empty() || name.len() > MAX_NAME_LEN || !name.chars().all(valid_char) {
+        return Err(Error::new(
+            ErrorKind::InvalidInputError,
+            format!(
+                "invalid {} {:?}, expected 1 to {} {}",
+                kind, name, MAX_NAME_LEN, expected
+            ),
+        ));
+    }
+    Ok(())
+}
+
+fn check_secret_name(name: &str) -> Result<()> {
+    check_name(
+        "secret name",
+        name,
+        |c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.',
+        "letters, digits, `_`, `-` or `.`",
+    )
+}
+
+fn check_env_name(env: &str) -> Result<()> {
+    let valid_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let expected = "letters, digits or `_`, not starting with a digit";
+    check_name("environment variable", env, valid_char, expected)?;
+    if env.starts_with(|c: char| c.is_ascii_digit()) {
+        return Err(Error::new(
+            ErrorKind::InvalidInputError,
+            format!(
+                "invalid environment variable {:?}, expected {}",
+                env, expected
+            ),
+        ));
+    }
+    Ok(())
+}
+
+fn check_value(value: &str) -> Result<()> {
+    if value.len() > MAX_SECRET_LEN {
+        return Err(Error::new(
+            ErrorKind::InvalidInputError,
+            format!(
+                "secret of {} bytes exceeds the maximum of {}",
+                value.len(),
+                MAX_SECRET_LEN
+            ),
+        ));
+    }
+    Ok(())
+}
+
+// Stores secret `name` of `owner`, replacing the previous value and readers.
+pub fn put(owner: &str, name: &str, value: &str, readers: &[String]) -> Result<()> {
+    check_secret_name(name)?;
+    check_value(value)?;
+    let key = secret_key(owner, name);
+    let previous = read(&key)?;
+    let sealed = seal(value, readers)?;
+    storage::backend()?.put(&key, &serde_json::to_string(&sealed)?)?;
+    if let Some(previous) = previous {
+        delete_key(&previous.key_id);
+    }
+    Ok(())
+}
+
+pub fn delete(owner: &str, name: &str) -> Result<()> {
+    let key = secret_key(owner, name);
+    let sealed = read(&key)?
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("no secret named {}", name)))?;
+    storage::backend()?.delete(&key)?;
+    delete_key(&sealed.key_id);
+    Ok(())
+}
+
+// Secrets of `owner`, ordered by name.
+pub fn list(owner: &str) -> Result<Vec<SecretInfo>> {
+    let prefix = secret_key(owner, "");
+    let mut secrets = Vec::new();
+    for (key, record) in storage::backend()?.scan(&prefix)?.iter() {
+        let sealed: SealedSecret = serde_json::from_str(record)?;
+        secrets.push(SecretInfo {
+            name: key[prefix.len()..].to_owned(),
+            readers: sealed.readers,
+            updated_at: sealed.updated_at,
+        });
+    }
+    Ok(secrets)
+}
+
+// Stored secret `secret` of a task of `user_id`, which must be shared with
+// them.
+fn read_shared(secret: &TaskSecret, name: &str, user_id: &str) -> Result<SealedSecret> {
+    let owner = secret
+        .owner
+        .as_ref()
+        .map_or(user_id, |owner| owner.as_str());
+    let sealed = read(&secret_key(owner, name))?.ok_or_else(|| {
+        Error::new(
+            ErrorKind::NotFound,
+            format!(
+                "secret {} of {} for {} does not exist",
+                name, owner, secret.env
+            ),
+        )
+    })?;
+    if owner != user_id && !sealed.readers.iter().any(|reader| reader == user_id) {
+        return Err(Error::new(
+            ErrorKind::PermissionDenied,
+            format!(
+                "secret {} of {} is not shared with {}",
+                name, owner, user_id
+            ),
+        ));
+    }
+    Ok(sealed)
+}
+
+// Checks the secrets of a task `user_id` creates. Each secret has a distinct
+// environment variable, and either a value or a stored secret the user may
+// reference.
+pub fn check_secrets(secrets: &[TaskSecret], user_id: &str) -> Result<()> {
+    if secrets.len() > MAX_TASK_SECRETS {
+        return Err(Error::new(
+            ErrorKind::InvalidInputError,
+            format!(
+                "{} secrets exceed the maximum of {} per task",
+                secrets.len(),
+                MAX_TASK_SECRETS
+            ),
+        ));
+    }
+    let mut envs = HashSet::new();
+    for secret in secrets.iter() {
+        check_env_name(&secret.env)?;
+        if !envs.insert(secret.env.as_str()) {
+            return Err(Error::new(
+                ErrorKind::InvalidInputError,
+                format!("duplicate secret for {}", secret.env),
+            ));
+        }
+        match (&secret.name, &secret.value) {
+            (Some(name), None) => {
+                read_shared(secret, name, user_id)?;
+            }
+            (None, Some(value)) if secret.owner.is_none() => check_value(value)?,
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInputError,
+                    format!(
+                        "secret for {} must have either a value or the name of a stored secret",
+                        secret.env
+                    ),
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+// Seals the inline values of the secrets of task `task_id`, which keeps the
+// secrets without their values.
+pub fn seal_task_secrets(task_id: &str, secrets: &[TaskSecret]) -> Result<()> {
+    let backend = storage::backend()?;
+    for secret in secrets.iter() {
+        if let Some(ref value) = secret.value {
+            let sealed = seal(value, &[])?;
+            let key = task_secret_key(task_id, &secret.env);
+            backend.put(&key, &serde_json::to_string(&sealed)?)?;
+        }
+    }
+    Ok(())
+}
+
+// Checks the secrets of task `source_id` for its rerun `task_id`, sealing the
+// inline values again under their own keys, so that deleting either task
+// leaves the secrets of the other.
+pub fn copy_task_secrets(source_id: &str, task_id: &str, task_info: &TaskInfo) -> Result<()> {
+    let env = open_task_secrets(source_id, task_info)?;
+    let secrets: Vec<TaskSecret> = task_info
+        .secrets
+        .iter()
+        .filter(|secret| secret.is_inline())
+        .map(|secret| TaskSecret {
+            value: env.get(&secret.env).cloned(),
+            ..secret.clone()
+        })
+        .collect();
+    seal_task_secrets(task_id, &secrets)
+}
+
+// Values of the secrets of task `task_id`, by environment variable.
+pub fn open_task_secrets(task_id: &str, task_info: &TaskInfo) -> Result<BTreeMap<String, String>> {
+    let mut env = BTreeMap::new();
+    for secret in task_info.secrets.iter() {
+        let sealed = match secret.name {
+            Some(ref name) => read_shared(secret, name, &task_info.user_id)?,
+            None => read(&task_secret_key(task_id, &secret.env))?.ok_or_else(|| {
+                Error::new(
+                    ErrorKind::NotFound,
+                    format!("secret for {} of task {} is lost", secret.env, task_id),
+                )
+            })?,
+        };
+        env.insert(secret.env.to_owned(), open(&sealed)?);
+    }
+    Ok(env)
+}
+
+// Deletes the inline secrets of task `task_id`. Best effort like deleting
+// the other records of the task.
+pub fn delete_task_secrets(task_id: &str, task_info: &TaskInfo) {
+    for secret in task_info.secrets.iter().filter(|secret| secret.is_inline()) {
+        let key = task_secret_key(task_id, &secret.env);
+        let deleted = read(&key).and_then(|sealed| {
+            storage::backend()?.delete(&key)?;
+            Ok(sealed)
+        });
+        match deleted {
+            Ok(Some(sealed)) => delete_key(&sealed.key_id),
+            Ok(None) => {}
+            Err(e) => warn!(
+                "Cannot delete secret {} of task {}: {}",
+                secret.env, task_id, e
+            ),
+        }
+    }
+}
@@ -0,0 +1,157 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// A ready task is staged when it is handed to an executor: its input files,
+// which may have been deleted since the task was created, are looked up in
+// TDFS again. A missing file fails the task at once, unless it is a file of an
+// optional input and `task_inputs.missing_inputs` skips those. The inputs a
+// function marks optional are its `optional_inputs` collections and its input
+// ports which are not required; their missing files are then dropped from the
+// task, which runs without them. The arguments file of a task, if any, is
+// never optional.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use std::collections::HashSet;
+
+use crate::data_store::{self, PortBindings, TaskAttempt, TaskError, TaskInfo, TaskStatus};
+use crate::task_arguments;
+use crate::task_recovery;
+use crate::task_retention::now;
+use crate::tms_external::tdfs_client;
+use mesatee_core::config;
+use mesatee_core::{Error, ErrorKind, Result};
+use teaclave_config::runtime_config::MissingInputPolicy;
+
+// Input files of the task which the function marks optional, less those which
+// are also mandatory inputs.
+fn optional_files(task_info: &TaskInfo) -> Result<HashSet<String>> {
+    let function_info = match task_info.function_id {
+        Some(ref function_id) => data_store::get_function(function_id)?,
+        None => return Ok(HashSet::new()),
+    };
+    let mut optional = HashSet::new();
+    let mut mandatory = HashSet::new();
+    for collection in task_info.input_collections.iter() {
+        let files = if function_info.optional_inputs.contains(&collection.name) {
+            &mut optional
+        } else {
+            &mut mandatory
+        };
+        files.extend(collection.file_ids.iter().cloned());
+    }
+    for port in function_info.input_ports.iter() {
+        if let Some(file_id) = task_info.input_ports.get(&port.name) {
+            let files = if port.required {
+                &mut mandatory
+            } else {
+                &mut optional
+            };
+            files.insert(file_id.to_string());
+        }
+    }
+    Ok(optional.difference(&mandatory).cloned().collect())
+}
+
+// Removes the files `missing` from the inputs of the task.
+fn drop_inputs(task_info: &mut TaskInfo, missing: &[String]) {
+    task_info
+        .input_files
+        .retain(|file| !missing.contains(&file.file_id));
+    for collection in task_info.input_collections.iter_mut() {
+        collection
+            .file_ids
+            .retain(|file_id| !missing.contains(file_id));
+    }
+    let input_ports = std::mem::replace(&mut task_info.input_ports, PortBindings::new());
+    task_info.input_ports = input_ports
+        .into_iter()
+        .filter(|(_, file_id)| !missing.contains(file_id))
+        .collect();
+}
+
+// Fails the task for the problem `message` with one of its files. The failure
+// is recorded as an attempt which never started, and is not retried.
+fn fail_task(
+    task_id: &str,
+    task_info: &mut TaskInfo,
+    kind: ErrorKind,
+    message: String,
+) -> Result<Error> {
+    task_info.attempts.push(TaskAttempt {
+        status: TaskStatus::Failed,
+        error: Some(TaskError {
+            message: message.clone(),
+            retryable: false,
+        }),
+        ended_at: now(),
+    });
+    task_recovery::in_batch(|| {
+        data_store::update_task_status(task_id, task_info, TaskStatus::Failed)?;
+        data_store::resolve_dependents(task_id, task_info)
+    })?;
+    Ok(Error::new(kind, message))
+}
+
+// Checks the input files and the arguments file of the ready task `task_id`
+// before it is handed to an executor. Fails with `NotFound`, naming the file,
+// if the task failed for a missing file, and with `PermissionDenied` or
+// `DataIntegrityError` if its arguments file is no longer accessible or
+// changed. Otherwise `task_info` is the task as it runs, without the missing
+// files it skips.
+// Before calling this function, use TASK_STATUS_LOCK to avoid data race;
+pub fn handle_staged_task(task_id: &str, task_info: &mut TaskInfo) -> Result<()> {
+    if let Some(err) = task_arguments::arguments_file_error(task_info)? {
+        warn!("Task {}: {}", task_id, err);
+        return Err(fail_task(task_id, task_info, err.kind(), err.to_string())?);
+    }
+    let file_ids: Vec<String> = task_info
+        .input_files
+        .iter()
+        .map(|file| file.file_id.to_string())
+        .collect();
+    if file_ids.is_empty() {
+        return Ok(());
+    }
+    let file_infos = tdfs_client().get_files(&file_ids)?;
+    let missing: Vec<String> = file_ids
+        .into_iter()
+        .zip(file_infos.into_iter())
+        .filter(|(_, file_info)| file_info.is_none())
+        .map(|(file_id, _)| file_id)
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    let skippable = match config::runtime_config().task_inputs.missing_inputs {
+        MissingInputPolicy::Fail => HashSet::new(),
+        MissingInputPolicy::SkipOptional => optional_files(task_info)?,
+    };
+    if let Some(file_id) = missing.iter().find(|file_id| !skippable.contains(*file_id)) {
+        warn!("Task {}: input file {} is missing", task_id, file_id);
+        let message = format!("input file {} of task {} is missing", file_id, task_id);
+        return Err(fail_task(task_id, task_info, ErrorKind::NotFound, message)?);
+    }
+    info!(
+        "Task {}: runs without its missing optional inputs {:?}",
+        task_id, missing
+    );
+    drop_inputs(task_info, &missing);
+    data_store::save_task(task_id, task_info)
+}
@@ -20,36 +20,144 @@ use std::prelude::v1::*;
 
 use uuid::Uuid;
 
+use kms_proto::{self, KMSClient};
 use mesatee_core::config;
+use mesatee_core::health::{DependencyHealth, EnclaveIdentity, ServiceHealth, VersionInfo};
+use mesatee_core::rpc::channel::RetryPolicy;
+use mesatee_core::rpc::deadline;
 use mesatee_core::rpc::EnclaveService;
-use mesatee_core::{Error, ErrorKind, Result};
+use mesatee_core::{Error, ErrorKind, FieldErrors, Result};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::marker::PhantomData;
-use tdfs_internal_client::TDFSClient;
+use std::sync::Arc;
+use std::time::Duration;
+use tdfs_internal_client::{ListAuditEntriesRequest, TDFSChannelPool, TDFSClient};
 
+use lazy_static::lazy_static;
+
+use crate::access_control;
+use crate::argument_schema;
+use crate::audit_log::AuditedRequest;
+use crate::auth;
 use crate::data_store::{
-    self, check_get_permission, gen_token, verify_user, CollaboratorStatus, FunctionType, TaskFile,
-    TaskInfo, TaskStatus, TASK_STORE, UPDATELOCK, USER_TASK_STORE,
+    self, check_get_permission, default_resource_limits, gen_token, maximum_resource_limits,
+    CollaboratorStatus, Executor, FunctionInfo, FunctionType, PortBindings, PortEdges, PortSpec,
+    ResourceLimits, TaskFile, TaskInfo, TaskStatus, OUTPUT_KEK_LEN, PYTHON_FUNCTION_NAME,
+    TASK_LOGS, TASK_STATUS_LOCK, TASK_STATUS_NOTIFIER, TASK_STORE, UPDATELOCK, USER_TASK_STORE,
+    WASM_FUNCTION_NAME,
 };
+use crate::function_invocation;
+use crate::input_collection;
+use crate::labels;
+use crate::port_binding;
+use crate::task_arguments;
+use crate::task_id;
+use crate::task_index;
+use crate::task_limits;
+use crate::task_recovery;
+use crate::task_retention;
+use crate::task_retry;
+use crate::task_schedule;
+use crate::task_secret;
+use crate::user_revocation;
 use tms_external_proto::{
-    CreateTaskRequest, GetTaskRequest, ListTaskRequest, TaskRequest, TaskResponse,
-    UpdateTaskRequest,
+    AuditLogEntry, CancelTaskRequest, CreateTaskRequest, CreateTaskResult, CreateTasksRequest,
+    DeleteSecretRequest, DeleteTaskRequest, GetEnclaveMeasurementRequest, GetTaskReceiptRequest,
+    GetTaskRequest, GetTaskStatusesRequest, GetVersionInfoRequest, HealthCheckRequest,
+    InvokeFunctionRequest, ListSecretsRequest, ListTaskRequest, PeerReload, PutSecretRequest,
+    QueryAuditLogRequest, QueryTasksRequest, ReloadEnclaveInfoRequest, RerunTaskRequest,
+    RevokeUserRequest, StreamTaskLogsRequest, TaskRequest, TaskResponse, TaskSpec,
+    TaskStatusResult, TaskStatusSummary, TaskSummary, UnrevokeUserRequest, UpdateTaskRequest,
+    ValidateTaskRequest, WaitForTaskStatusRequest,
 };
 
+// A wedged TDFS would otherwise block the TMS worker serving the request.
+const TDFS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+const TDFS_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+// Upper bound of how long a client may wait for a task status change, or for
+// the next lines of a task log.
+const MAX_STATUS_WAIT: Duration = Duration::from_secs(60);
+
+lazy_static! {
+    // Attested connections to TDFS shared by all requests. Connections are
+    // re-established before the TDFS attestation report is refreshed, and
+    // after TDFS restarts. Idle connections are probed by the maintenance
+    // ECALL. New connections verify TDFS against the current enclave info.
+    pub(crate) static ref TDFS_CHANNEL_POOL: Arc<TDFSChannelPool> = {
+        let runtime_config = config::runtime_config();
+        let endpoint = &runtime_config.internal_endpoints.tdfs;
+        let pool = TDFSChannelPool::new(config::Internal::target_tdfs(), endpoint.pool_size)
+            .resolve_target(config::Internal::target_tdfs)
+            .max_age(Duration::from_secs(
+                runtime_config.attestation.report_cache_ttl,
+            ))
+            .retry_policy(RetryPolicy::new(5, Duration::from_millis(200)))
+            .handshake_timeout(TDFS_HANDSHAKE_TIMEOUT)
+            .timeout(TDFS_REQUEST_TIMEOUT);
+        // Requests fail at once while TDFS is down, rather than holding TMS
+        // workers until they time out.
+        let storage = &runtime_config.storage;
+        let pool = if storage.breaker_failure_threshold > 0 {
+            pool.circuit_breaker(
+                storage.breaker_failure_threshold,
+                Duration::from_secs(storage.breaker_cool_down),
+            )
+        } else {
+            pool
+        };
+        if endpoint.keepalive_interval > 0 {
+            Arc::new(pool.keepalive(Duration::from_secs(endpoint.keepalive_interval)))
+        } else {
+            Arc::new(pool)
+        }
+    };
+}
+
+pub(crate) fn tdfs_client() -> TDFSClient {
+    TDFSClient::with_pool(TDFS_CHANNEL_POOL.clone())
+}
+
+// Rejects limits that are zero or exceed the cluster maximum.
+fn check_resource_limits(limits: &ResourceLimits) -> Result<()> {
+    let maximum = maximum_resource_limits();
+    let checks = [
+        ("max_memory_mb", limits.max_memory_mb, maximum.max_memory_mb),
+        (
+            "max_cpu_seconds",
+            limits.max_cpu_seconds,
+            maximum.max_cpu_seconds,
+        ),
+        (
+            "max_wall_seconds",
+            limits.max_wall_seconds,
+            maximum.max_wall_seconds,
+        ),
+    ];
+    let mut errors = FieldErrors::new();
+    for (name, value, max) in checks.iter() {
+        if *value == 0 || value > max {
+            errors.push(
+                name,
+                Error::new(
+                    ErrorKind::InvalidInputError,
+                    format!("{} must be between 1 and {}, got {}", name, max, value),
+                ),
+            );
+        }
+    }
+    errors.into_result()
+}
+
+// Users are authenticated by `AuthInterceptor` before requests are handled.
 pub trait HandleRequest {
     fn handle_request(&self) -> Result<TaskResponse>;
 }
 
 impl HandleRequest for GetTaskRequest {
     fn handle_request(&self) -> Result<TaskResponse> {
-        if !verify_user(&self.user_id, &self.user_token) {
-            return Err(mesatee_core::Error::from(
-                mesatee_core::ErrorKind::PermissionDenied,
-            ));
-        }
-
         let saved_info = TASK_STORE
             .get(&self.task_id)?
-            .ok_or_else(|| Error::from(ErrorKind::MissingValue))?;
+            .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
 
         // Check user permission
         if !check_get_permission(&saved_info, &self.user_id) {
@@ -60,23 +168,71 @@ impl HandleRequest for GetTaskRequest {
 
         // retrive current user's output files
         let mut output_files: Vec<String> = Vec::new();
+        let mut output_formats = BTreeMap::new();
         for task_file in saved_info.output_files.iter() {
             if task_file.user_id == self.user_id {
                 output_files.push(task_file.file_id.to_owned());
+                if let Some(format) = &task_file.format {
+                    output_formats.insert(task_file.file_id.to_owned(), format.clone());
+                }
+            }
+        }
+
+        // Partial and dynamic outputs are saved for the task creator alone
+        let mut progress = saved_info.progress.clone();
+        let mut dynamic_outputs = saved_info.dynamic_outputs.clone();
+        if saved_info.user_id != self.user_id {
+            for checkpoint in progress.iter_mut() {
+                checkpoint.partial_output = None;
             }
+            dynamic_outputs.clear();
         }
 
+        let result_file_id = if saved_info.user_id == self.user_id {
+            saved_info.result_file_id.clone()
+        } else {
+            None
+        };
+        let expires_at = task_retention::expires_at(&saved_info);
         let return_info = tms_external_proto::TaskInfo {
             user_id: saved_info.user_id,
             function_name: saved_info.function_name,
+            function_id: saved_info.function_id,
             function_type: saved_info.function_type,
             status: saved_info.status,
+            status_version: saved_info.status_version,
             ip: saved_info.ip,
             port: saved_info.port,
             task_token: saved_info.task_token,
             collaborator_list: saved_info.collaborator_list,
             task_result_file_id: saved_info.task_result_file_id,
             user_private_result_file_id: output_files,
+            resource_limits: saved_info.resource_limits,
+            dependencies: saved_info.dependencies,
+            cancel_reason: saved_info.cancel_reason,
+            input_collections: saved_info.input_collections,
+            arguments: saved_info.arguments,
+            arguments_file: saved_info.arguments_file,
+            expires_at,
+            output_key_id: saved_info.output_key.map(|key| key.key_id),
+            priority: saved_info.priority,
+            input_ports: saved_info.input_ports,
+            input_edges: saved_info.input_edges,
+            output_ports: saved_info.output_ports,
+            dynamic_outputs,
+            progress,
+            not_before: saved_info.not_before,
+            retry_policy: saved_info.retry_policy,
+            attempts: saved_info.attempts,
+            labels: saved_info.labels,
+            secrets: saved_info
+                .secrets
+                .iter()
+                .map(|secret| secret.redacted())
+                .collect(),
+            output_formats,
+            result_format: saved_info.result_format,
+            result_file_id,
         };
 
         let resp = TaskResponse::new_get_task(&return_info);
@@ -84,94 +240,464 @@ impl HandleRequest for GetTaskRequest {
     }
 }
 
-impl HandleRequest for CreateTaskRequest {
-    fn handle_request(&self) -> Result<TaskResponse> {
-        if !verify_user(&self.user_id, &self.user_token) {
-            return Err(mesatee_core::Error::from(
-                mesatee_core::ErrorKind::PermissionDenied,
-            ));
-        }
+fn spec_request(spec: &TaskSpec, user_id: &str, user_token: &str) -> CreateTaskRequest {
+    CreateTaskRequest {
+        function_name: spec.function_name.to_string(),
+        collaborator_list: spec.collaborator_list.clone(),
+        files: spec.files.clone(),
+        user_id: user_id.to_string(),
+        user_token: user_token.to_string(),
+        resource_limits: spec.resource_limits,
+        function_id: spec.function_id.clone(),
+        dependencies: spec.dependencies.clone(),
+        input_collections: spec.input_collections.clone(),
+        arguments: spec.arguments.clone(),
+        arguments_file: spec.arguments_file.clone(),
+        retention_secs: spec.retention_secs,
+        output_key: spec.output_key.clone(),
+        priority: spec.priority,
+        idempotency_key: spec.idempotency_key.clone(),
+        content_addressed: spec.content_addressed,
+        input_ports: spec.input_ports.clone(),
+        input_edges: spec.input_edges.clone(),
+        not_before: spec.not_before,
+        retry_policy: spec.retry_policy,
+        labels: spec.labels.clone(),
+        secrets: spec.secrets.clone(),
+        result_format: spec.result_format,
+    }
+}
 
-        let func_type = match self.function_name.as_str() {
-            "psi" | "concat" | "swap_file" | "private_join_and_compute" => FunctionType::Multiparty,
-            _ => FunctionType::Single,
-        };
+// The registered function of a task, which is pinned to an exact version.
+fn task_function(function_id: &str, user_id: &str) -> Result<FunctionInfo> {
+    let (_, version) = data_store::parse_function_id(function_id)?;
+    if version.is_none() {
+        return Err(Error::new(
+            ErrorKind::InvalidInputError,
+            "function id of a task must include a version",
+        ));
+    }
+    data_store::get_function_for_user(function_id, user_id)
+}
 
-        // check collaborator_list and files if func_type is Multiparty
-        if let FunctionType::Multiparty = func_type {
-            if self.collaborator_list.is_empty() {
-                return Err(mesatee_core::Error::from(
-                    mesatee_core::ErrorKind::MissingValue,
-                ));
+// Output ports of the function of a task, none for built-in functions.
+fn task_output_ports(task_info: &TaskInfo) -> Result<Vec<PortSpec>> {
+    match task_info.function_id {
+        Some(ref function_id) => Ok(data_store::get_function(function_id)?.output_ports),
+        None => Ok(Vec::new()),
+    }
+}
+
+// Validates and creates a task. With `validate_only`, all the checks of
+// creating the task are run, but the task is neither stored nor registered as
+// a dependent of its dependencies.
+fn create_task(req: &CreateTaskRequest, validate_only: bool) -> Result<(String, TaskInfo)> {
+    // All the fields are checked before failing, so that the user sees every
+    // problem with the request at once.
+    let mut errors = FieldErrors::new();
+
+    let function_info = match req.function_id {
+        Some(ref function_id) => {
+            errors.check("function_id", task_function(function_id, &req.user_id))
+        }
+        None => None,
+    };
+    let (function_name, function_id, optional_inputs, arguments, ports) = match function_info {
+        Some(function_info) => {
+            if !req.function_name.is_empty() && req.function_name != function_info.function_name {
+                errors.push(
+                    "function_name",
+                    Error::new(
+                        ErrorKind::InvalidInputError,
+                        format!(
+                            "function name {} does not match function {}",
+                            req.function_name, function_info.function_id
+                        ),
+                    ),
+                );
             }
-            if self.files.is_empty() {
-                return Err(mesatee_core::Error::from(
-                    mesatee_core::ErrorKind::MissingValue,
-                ));
+            let function_name = match function_info.executor {
+                Executor::Builtin => function_info.function_name,
+                Executor::Wasm => WASM_FUNCTION_NAME.to_string(),
+                Executor::Python => PYTHON_FUNCTION_NAME.to_string(),
+            };
+            let arguments = errors
+                .check(
+                    "arguments",
+                    argument_schema::apply_schema(&function_info.argument_schema, &req.arguments),
+                )
+                .unwrap_or_default();
+            // The schema is applied when the task is created, before the
+            // executor reads the file.
+            if req.arguments_file.is_some() && function_info.argument_schema.is_some() {
+                errors.push(
+                    "arguments_file",
+                    Error::new(
+                        ErrorKind::InvalidInputError,
+                        format!(
+                            "function {} has an argument schema, its arguments are given inline",
+                            function_info.function_id
+                        ),
+                    ),
+                );
             }
+            errors.check(
+                "input_ports",
+                port_binding::check_input_bindings(
+                    &function_info.input_ports,
+                    &req.input_ports,
+                    &req.input_edges,
+                ),
+            );
+            (
+                function_name,
+                Some(function_info.function_id),
+                function_info.optional_inputs,
+                arguments,
+                function_info.input_ports,
+            )
         }
-
-        // check file permission
-        for file_id in self.files.iter() {
-            let target = config::Internal::target_tdfs();
-            let mut client = TDFSClient::new(target)?;
-            let accessible = client.check_access_permission(file_id, &self.user_id)?;
-            if !accessible {
-                return Err(mesatee_core::Error::from(
-                    mesatee_core::ErrorKind::PermissionDenied,
-                ));
+        None => {
+            if req.function_id.is_none() && !req.input_ports.is_empty() {
+                errors.push(
+                    "input_ports",
+                    Error::new(
+                        ErrorKind::InvalidInputError,
+                        "input ports are only bound for tasks of a registered function",
+                    ),
+                );
             }
+            if req.function_id.is_none() && !req.input_edges.is_empty() {
+                errors.push(
+                    "input_edges",
+                    Error::new(
+                        ErrorKind::InvalidInputError,
+                        "input ports are only bound for tasks of a registered function",
+                    ),
+                );
+            }
+            (
+                req.function_name.to_string(),
+                None,
+                Vec::new(),
+                req.arguments.clone(),
+                Vec::new(),
+            )
         }
+    };
 
-        let collaborator_list: Vec<CollaboratorStatus> = self
-            .collaborator_list
-            .iter()
-            .map(|user_id| CollaboratorStatus {
-                user_id: user_id.to_string(),
-                approved: false,
-            })
-            .collect();
+    let func_type = match function_name.as_str() {
+        "psi" | "concat" | "swap_file" | "private_join_and_compute" => FunctionType::Multiparty,
+        _ => FunctionType::Single,
+    };
 
-        let token = gen_token()?;
-        let input_files: Vec<TaskFile> = self
-            .files
-            .iter()
-            .map(|file_id| TaskFile {
-                user_id: self.user_id.to_string(),
-                file_id: file_id.to_string(),
-            })
-            .collect();
-        let fns_config = config::External::target_fns();
-        let mut task_info = TaskInfo {
-            user_id: self.user_id.to_string(),
-            collaborator_list,
-            approved_user_number: 0,
-            function_name: self.function_name.to_string(),
-            function_type: func_type,
-            status: TaskStatus::Created,
-            ip: fns_config.addr.ip(),
-            port: fns_config.addr.port(),
-            task_token: token,
-            input_files,
-            output_files: Vec::new(),
-            task_result_file_id: None,
-        };
+    // check collaborator_list and files if func_type is Multiparty
+    if let FunctionType::Multiparty = func_type {
+        if req.collaborator_list.is_empty() {
+            errors.push(
+                "collaborator_list",
+                Error::new(
+                    ErrorKind::InvalidInputError,
+                    "multiparty task without collaborators",
+                ),
+            );
+        }
+        if req.files.is_empty() {
+            errors.push(
+                "files",
+                Error::new(
+                    ErrorKind::InvalidInputError,
+                    "multiparty task without input files",
+                ),
+            );
+        }
+    }
 
-        match func_type {
-            FunctionType::Single => {
-                task_info.status = TaskStatus::Ready;
-            }
-            _ => {
-                task_info.status = TaskStatus::Created;
+    if let Some(ref output_key) = req.output_key {
+        if !output_key.is_complete() {
+            errors.push(
+                "output_key",
+                Error::new(
+                    ErrorKind::InvalidInputError,
+                    format!(
+                        "output key must have an id and a {} byte key",
+                        OUTPUT_KEK_LEN
+                    ),
+                ),
+            );
+        }
+    }
+
+    let resource_limits = match req.resource_limits {
+        Some(limits) => {
+            errors.check("resource_limits", check_resource_limits(&limits));
+            limits
+        }
+        None => default_resource_limits(),
+    };
+    let priority = req
+        .priority
+        .unwrap_or_else(|| config::runtime_config().task_management.default_priority);
+    if let Some(not_before) = req.not_before {
+        errors.check("not_before", task_schedule::check_not_before(not_before));
+    }
+    if let Some(ref retry_policy) = req.retry_policy {
+        errors.check("retry_policy", task_retry::check_retry_policy(retry_policy));
+    }
+    errors.check("labels", labels::check_labels(&req.labels));
+    errors.check(
+        "secrets",
+        task_secret::check_secrets(&req.secrets, &req.user_id),
+    );
+    let arguments_file = match req.arguments_file {
+        Some(ref file_id) => errors.check(
+            "arguments_file",
+            task_arguments::pin_arguments_file(file_id, &req.user_id),
+        ),
+        None => None,
+    };
+    if req.content_addressed && req.secrets.iter().any(|secret| secret.is_inline()) {
+        errors.push(
+            "content_addressed",
+            Error::new(
+                ErrorKind::InvalidInputError,
+                "tasks with inline secrets cannot be content addressed",
+            ),
+        );
+    }
+
+    // check file permission, of all the files at once. Too many files are
+    // rejected before they are looked up.
+    let file_ids: Vec<String> = req
+        .files
+        .iter()
+        .chain(req.input_ports.values())
+        .cloned()
+        .collect();
+    let file_paths: Vec<String> = (0..req.files.len())
+        .map(|index| format!("files[{}]", index))
+        .chain(
+            req.input_ports
+                .keys()
+                .map(|port| format!("input_ports.{}", port)),
+        )
+        .collect();
+    let input_count = task_limits::check_input_count(file_ids.len());
+    if errors.check("files", input_count).is_some() {
+        for (index, err) in access_control::file_access_errors(&file_ids, &req.user_id)? {
+            errors.push(&file_paths[index], err);
+        }
+    }
+
+    // Collections are resolved once, the task keeps the files they matched
+    let input_collections = errors
+        .check(
+            "input_collections",
+            input_collection::resolve(&req.input_collections, &optional_inputs, &req.user_id),
+        )
+        .unwrap_or_default();
+
+    // check dependencies, and collect the output ports of those the input
+    // ports of the task are bound to
+    let mut dependencies: Vec<String> = Vec::new();
+    let mut dependency_ports: HashMap<&str, Vec<PortSpec>> = HashMap::new();
+    for (index, dependency_id) in req.dependencies.iter().enumerate() {
+        let field_path = format!("dependencies[{}]", index);
+        if dependencies.contains(dependency_id) {
+            errors.push(
+                &field_path,
+                Error::new(
+                    ErrorKind::InvalidInputError,
+                    format!("duplicate dependency {}", dependency_id),
+                ),
+            );
+            continue;
+        }
+        dependencies.push(dependency_id.to_string());
+        match TASK_STORE.get(dependency_id)? {
+            Some(ref dependency) if check_get_permission(dependency, &req.user_id) => {
+                let is_bound = req
+                    .input_edges
+                    .values()
+                    .any(|edge| &edge.task_id == dependency_id);
+                if is_bound {
+                    if let Some(output_ports) =
+                        errors.check(&field_path, task_output_ports(dependency))
+                    {
+                        dependency_ports.insert(dependency_id.as_str(), output_ports);
+                    }
+                }
             }
+            Some(_) => errors.push(&field_path, Error::from(ErrorKind::PermissionDenied)),
+            None => errors.push(
+                &field_path,
+                Error::new(
+                    ErrorKind::InvalidInputError,
+                    format!("unknown dependency {}", dependency_id),
+                ),
+            ),
         }
+    }
+    if function_id.is_some() && !req.input_edges.is_empty() {
+        errors.check(
+            "input_edges",
+            port_binding::check_input_edges(
+                &ports,
+                &req.input_ports,
+                &req.input_edges,
+                &dependency_ports,
+            ),
+        );
+    }
+    errors.into_result()?;
 
+    let collaborator_list: Vec<CollaboratorStatus> = req
+        .collaborator_list
+        .iter()
+        .map(|user_id| CollaboratorStatus {
+            user_id: user_id.to_string(),
+            approved: false,
+        })
+        .collect();
+
+    let token = gen_token()?;
+    let mut input_files: Vec<TaskFile> = req
+        .files
+        .iter()
+        .map(|file_id| TaskFile {
+            user_id: req.user_id.to_string(),
+            file_id: file_id.to_string(),
+            format: None,
+        })
+        .collect();
+    // Files of collections and ports are inputs of the task as well
+    let collection_file_ids = input_collections
+        .iter()
+        .flat_map(|collection| collection.file_ids.iter());
+    for file_id in collection_file_ids.chain(req.input_ports.values()) {
+        if !input_files.iter().any(|file| &file.file_id == file_id) {
+            input_files.push(TaskFile {
+                user_id: req.user_id.to_string(),
+                file_id: file_id.to_string(),
+                format: None,
+            });
+        }
+    }
+    let fns_config = config::External::target_fns();
+    let mut task_info = TaskInfo {
+        user_id: req.user_id.to_string(),
+        collaborator_list,
+        approved_user_number: 0,
+        function_name,
+        function_id,
+        function_type: func_type,
+        status: TaskStatus::Created,
+        status_version: 0,
+        ip: fns_config.addr.ip(),
+        port: fns_config.addr.port(),
+        task_token: token,
+        input_files,
+        output_files: Vec::new(),
+        task_result_file_id: None,
+        resource_limits,
+        dependencies,
+        cancel_reason: None,
+        input_collections,
+        arguments,
+        arguments_file,
+        finished_at: None,
+        retention_secs: req.retention_secs,
+        output_key: req.output_key.clone(),
+        priority,
+        created_at: task_retention::now(),
+        idempotency_key: req.idempotency_key.clone(),
+        input_ports: req.input_ports.clone(),
+        input_edges: req.input_edges.clone(),
+        output_ports: PortBindings::new(),
+        dynamic_outputs: BTreeMap::new(),
+        progress: Vec::new(),
+        not_before: req.not_before,
+        retry_policy: req.retry_policy,
+        attempts: Vec::new(),
+        labels: req.labels.clone(),
+        receipt: None,
+        secrets: req.secrets.iter().map(|secret| secret.redacted()).collect(),
+        result_format: req.result_format,
+        result_file_id: None,
+    };
+
+    let task_id = if req.content_addressed {
+        task_id::content_task_id(&task_info)?
+    } else {
         let task_id = Uuid::new_v4().to_string();
         if TASK_STORE.get(&task_id)?.is_some() {
             return Err(Error::from(ErrorKind::UUIDError));
         }
-        data_store::add_task(&task_id, &task_info)?;
+        task_id
+    };
+    data_store::check_dependency_cycle(&task_id, &task_info.dependencies)?;
+
+    // Dependencies must not finish between collecting their results and
+    // registering this task as their dependent.
+    let _lock = TASK_STATUS_LOCK.lock()?;
+    for dependency_id in task_info.dependencies.clone().iter() {
+        let dependency = TASK_STORE
+            .get(dependency_id)?
+            .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+        match dependency.status {
+            TaskStatus::Finished => {
+                let files = data_store::dependency_outputs(&dependency, &req.user_id);
+                task_info.input_files.extend(files);
+                data_store::bind_input_edges(dependency_id, &dependency, &mut task_info)
+                    .map_err(|reason| Error::new(ErrorKind::InvalidInputError, reason))?;
+            }
+            TaskStatus::Failed | TaskStatus::Cancelled => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInputError,
+                    format!("dependency {} is {:?}", dependency_id, dependency.status),
+                ))
+            }
+            _ => {}
+        }
+    }
+    task_limits::check_input_files(&task_info.input_files)?;
+    if data_store::is_task_ready(&task_info)? {
+        task_info.status = task_schedule::ready_status(&task_info);
+    }
+
+    if validate_only {
+        return Ok((task_id, task_info));
+    }
+
+    // A retried creation returns the task created the first time.
+    if let Some(ref idempotency_key) = req.idempotency_key {
+        if let Some(created) = data_store::find_idempotent_task(&req.user_id, idempotency_key)? {
+            return Ok(created);
+        }
+    }
+    // The user created an identical task before. IDs include the creator, so
+    // a task of another user means the content hash collided.
+    if req.content_addressed {
+        if let Some(existing) = TASK_STORE.get(&task_id)? {
+            if existing.user_id != req.user_id {
+                return Err(Error::from(ErrorKind::UUIDError));
+            }
+            return Ok((task_id, existing));
+        }
+    }
+
+    task_secret::seal_task_secrets(&task_id, &req.secrets)?;
+    data_store::add_task(&task_id, &task_info)?;
+    for dependency_id in task_info.dependencies.iter() {
+        data_store::add_dependent(dependency_id, &task_id)?;
+    }
+
+    Ok((task_id, task_info))
+}
 
+impl HandleRequest for CreateTaskRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        let (task_id, task_info) = create_task(self, false)?;
         let resp = TaskResponse::new_create_task(
             &task_id,
             &task_info.task_token,
@@ -182,35 +708,396 @@ impl HandleRequest for CreateTaskRequest {
     }
 }
 
-impl HandleRequest for ListTaskRequest {
+impl HandleRequest for CreateTasksRequest {
     fn handle_request(&self) -> Result<TaskResponse> {
-        if !verify_user(&self.user_id, &self.user_token) {
+        if self.tasks.is_empty() {
             return Err(mesatee_core::Error::from(
-                mesatee_core::ErrorKind::PermissionDenied,
+                mesatee_core::ErrorKind::InvalidInputError,
             ));
         }
-        // lock is not needed here
-        let task_ids = USER_TASK_STORE.get(&self.user_id)?;
-        let list: Vec<&str> = match task_ids {
-            Some(ref ids) => ids.iter().map(|s| s.as_str()).collect(),
-            None => Vec::new(),
-        };
-        let resp = TaskResponse::new_list_task(&list);
+        let max_batch_size = config::runtime_config().task_management.max_batch_size;
+        if self.tasks.len() > max_batch_size {
+            return Err(Error::new(
+                ErrorKind::InvalidInputError,
+                format!(
+                    "batch of {} tasks exceeds the maximum of {}",
+                    self.tasks.len(),
+                    max_batch_size
+                ),
+            ));
+        }
+
+        // Each task is committed on its own, so a failing task does not roll
+        // back the tasks created before it.
+        let mut results: Vec<CreateTaskResult> = Vec::with_capacity(self.tasks.len());
+        for spec in self.tasks.iter() {
+            let req = spec_request(spec, &self.user_id, &self.user_token);
+            let result = match req.handle_request() {
+                Ok(TaskResponse::Create(resp)) => CreateTaskResult::Created(resp),
+                Ok(_) => {
+                    CreateTaskResult::Failed(Error::from(ErrorKind::BadImplementation).to_string())
+                }
+                Err(err) => CreateTaskResult::Failed(err.to_string()),
+            };
+            results.push(result);
+        }
+
+        let resp = TaskResponse::new_create_tasks(results);
         Ok(resp)
     }
 }
 
-impl HandleRequest for UpdateTaskRequest {
+impl HandleRequest for ValidateTaskRequest {
     fn handle_request(&self) -> Result<TaskResponse> {
-        if !verify_user(&self.user_id, &self.user_token) {
+        let req = spec_request(&self.task, &self.user_id, &self.user_token);
+        let error = create_task(&req, true).err();
+        let resp = TaskResponse::new_validate_task(error.as_ref());
+        Ok(resp)
+    }
+}
+
+// Creates a new task of `req.user_id` from the ended task `source_id`, with
+// the function, inputs, arguments and limits of the source and the state of
+// a task just created. Nothing is shared with the source but the input files.
+fn rerun_task(
+    req: &RerunTaskRequest,
+    source_id: &str,
+    source: &TaskInfo,
+) -> Result<(String, TaskInfo)> {
+    if !source.status.is_final() {
+        return Err(Error::new(
+            ErrorKind::InvalidInputError,
+            format!(
+                "task {} cannot be rerun in status {:?}",
+                source_id, source.status
+            ),
+        ));
+    }
+    // Collaborators and secrets are chosen by the creator of the source
+    if source.user_id != req.user_id
+        && (!source.collaborator_list.is_empty() || !source.secrets.is_empty())
+    {
+        return Err(Error::new(
+            ErrorKind::PermissionDenied,
+            format!(
+                "task {} has collaborators or secrets, only its creator can rerun it",
+                source_id
+            ),
+        ));
+    }
+    // The outputs of the dependencies are collected as the task becomes ready
+    if !source.dependencies.is_empty() && source.attempts.is_empty() {
+        return Err(Error::new(
+            ErrorKind::InvalidInputError,
+            format!(
+                "task {} never ran, the outputs of its dependencies are missing",
+                source_id
+            ),
+        ));
+    }
+
+    let arguments = match (&source.function_id, &req.arguments) {
+        (Some(function_id), arguments) => {
+            let function_info = data_store::get_function_for_user(function_id, &req.user_id)?;
+            match arguments {
+                Some(arguments) => {
+                    argument_schema::apply_schema(&function_info.argument_schema, arguments)?
+                }
+                None => source.arguments.clone(),
+            }
+        }
+        (None, Some(arguments)) => arguments.clone(),
+        (None, None) => source.arguments.clone(),
+    };
+    check_resource_limits(&source.resource_limits)?;
+
+    // The files of collaborators are given again as they approve the task
+    let input_files: Vec<TaskFile> = source
+        .input_files
+        .iter()
+        .filter(|file| file.user_id == source.user_id)
+        .map(|file| TaskFile {
+            user_id: req.user_id.to_string(),
+            file_id: file.file_id.to_string(),
+            format: None,
+        })
+        .collect();
+    let file_ids: Vec<String> = input_files
+        .iter()
+        .map(|file| file.file_id.to_string())
+        .collect();
+    access_control::check_file_access(&file_ids, &req.user_id)?;
+    task_limits::check_input_files(&input_files)?;
+
+    let collaborator_list: Vec<CollaboratorStatus> = source
+        .collaborator_list
+        .iter()
+        .map(|collaborator| CollaboratorStatus {
+            user_id: collaborator.user_id.to_string(),
+            approved: false,
+        })
+        .collect();
+    let fns_config = config::External::target_fns();
+    let mut task_info = TaskInfo {
+        user_id: req.user_id.to_string(),
+        collaborator_list,
+        approved_user_number: 0,
+        function_name: source.function_name.to_string(),
+        function_id: source.function_id.clone(),
+        function_type: source.function_type,
+        status: TaskStatus::Created,
+        status_version: 0,
+        ip: fns_config.addr.ip(),
+        port: fns_config.addr.port(),
+        task_token: gen_token()?,
+        input_files,
+        output_files: Vec::new(),
+        task_result_file_id: None,
+        resource_limits: source.resource_limits,
+        dependencies: Vec::new(),
+        cancel_reason: None,
+        input_collections: source.input_collections.clone(),
+        arguments,
+        arguments_file: source.arguments_file.clone(),
+        finished_at: None,
+        retention_secs: source.retention_secs,
+        output_key: source.output_key.clone(),
+        priority: source.priority,
+        created_at: task_retention::now(),
+        idempotency_key: None,
+        input_ports: source.input_ports.clone(),
+        input_edges: PortEdges::new(),
+        output_ports: PortBindings::new(),
+        dynamic_outputs: BTreeMap::new(),
+        progress: Vec::new(),
+        not_before: None,
+        retry_policy: source.retry_policy,
+        attempts: Vec::new(),
+        labels: source.labels.clone(),
+        receipt: None,
+        secrets: source.secrets.clone(),
+        result_format: source.result_format,
+        result_file_id: None,
+    };
+
+    let task_id = Uuid::new_v4().to_string();
+    if TASK_STORE.get(&task_id)?.is_some() {
+        return Err(Error::from(ErrorKind::UUIDError));
+    }
+    if data_store::is_task_ready(&task_info)? {
+        task_info.status = task_schedule::ready_status(&task_info);
+    }
+
+    task_secret::copy_task_secrets(source_id, &task_id, source)?;
+    data_store::add_task(&task_id, &task_info)?;
+
+    Ok((task_id, task_info))
+}
+
+impl HandleRequest for RerunTaskRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        let source = TASK_STORE
+            .get(&self.task_id)?
+            .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+        if !check_get_permission(&source, &self.user_id) {
             return Err(mesatee_core::Error::from(
                 mesatee_core::ErrorKind::PermissionDenied,
             ));
         }
 
+        let (task_id, task_info) = rerun_task(self, &self.task_id, &source)?;
+        let resp = TaskResponse::new_rerun_task(
+            &task_id,
+            &task_info.task_token,
+            task_info.ip,
+            task_info.port,
+        );
+        Ok(resp)
+    }
+}
+
+impl HandleRequest for InvokeFunctionRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        // Inputs are only saved for functions the user may run
+        task_function(&self.function_id, &self.user_id)?;
+        task_limits::check_input_count(self.inline_inputs.len())?;
+        let files = function_invocation::save_inline_inputs(&self.inline_inputs, &self.user_id)?;
+
+        let mut spec = TaskSpec::new("", &[], &[]);
+        spec.function_id = Some(self.function_id.to_string());
+        spec.files = files;
+        spec.arguments = self.arguments.clone();
+        spec.resource_limits = Some(function_invocation::invocation_limits());
+        spec.retention_secs = Some(config::runtime_config().function_invocation.retention_secs);
+        let req = spec_request(&spec, &self.user_id, &self.user_token);
+        let (task_id, task_info) = create_task(&req, false)?;
+
+        let result = function_invocation::run_task(&task_id, &task_info)?;
+        let outputs = function_invocation::read_outputs(&task_id, &self.user_id, &result)?;
+        let resp = TaskResponse::new_invoke_function(&task_id, &result, outputs);
+        Ok(resp)
+    }
+}
+
+impl HandleRequest for ListTaskRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        let limit = data_store::page_size(self.limit)?;
+        // lock is not needed here
+        let mut task_ids = USER_TASK_STORE.get(&self.user_id)?.unwrap_or_default();
+        if !self.labels.is_empty() {
+            let mut labeled = HashSet::new();
+            for task_id in task_ids.into_iter() {
+                let matched = match TASK_STORE.get(&task_id)? {
+                    Some(task_info) => labels::matches(&task_info.labels, &self.labels),
+                    None => false,
+                };
+                if matched {
+                    labeled.insert(task_id);
+                }
+            }
+            task_ids = labeled;
+        }
+        let (page, next_token) = data_store::paginate(&task_ids, &self.cursor, limit);
+        let list: Vec<&str> = page.iter().map(|s| s.as_str()).collect();
+        let resp = TaskResponse::new_list_task(&list, &next_token);
+        Ok(resp)
+    }
+}
+
+impl HandleRequest for QueryTasksRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        let limit = data_store::page_size(self.limit)?;
+        let query = &self.query;
+        // Users other than admins only query their own tasks
+        let is_admin = auth::has_role(&self.user_id, auth::ADMIN_ROLE);
+        let owner = match query.owner {
+            Some(ref owner) => Some(owner.as_str()),
+            None if is_admin => None,
+            None => Some(self.user_id.as_str()),
+        };
+        if !is_admin && owner != Some(self.user_id.as_str()) {
+            return Err(Error::from(ErrorKind::PermissionDenied));
+        }
+        let index_query = task_index::Query {
+            function_id: query.function_id.as_ref().map(|s| s.as_str()),
+            status: query.status,
+            owner,
+            since: query.since.unwrap_or(0),
+            until: query.until.unwrap_or(u64::max_value()),
+        };
+        let after = task_index::decode_cursor(&self.cursor)?;
+
+        let mut tasks = Vec::new();
+        let mut next_token = String::new();
+        for entry in task_index::candidates(&index_query, &after)?.iter() {
+            let task_info = match TASK_STORE.get(&entry.1)? {
+                Some(task_info) => task_info,
+                None => continue,
+            };
+            if !query_matches(&index_query, &task_info) {
+                continue;
+            }
+            // The cursor is the last task of the page, if another follows
+            if tasks.len() == limit {
+                let last: &TaskSummary = &tasks[limit - 1];
+                next_token = task_index::encode_cursor(&(last.created_at, last.task_id.to_owned()));
+                break;
+            }
+            tasks.push(TaskSummary {
+                task_id: entry.1.to_owned(),
+                user_id: task_info.user_id,
+                function_name: task_info.function_name,
+                function_id: task_info.function_id,
+                status: task_info.status,
+                created_at: task_info.created_at,
+                finished_at: task_info.finished_at,
+            });
+        }
+        Ok(TaskResponse::new_query_tasks(tasks, &next_token))
+    }
+}
+
+impl HandleRequest for GetTaskStatusesRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        let max_page_size = config::runtime_config().task_management.max_page_size;
+        if self.task_ids.len() > max_page_size {
+            return Err(Error::new(
+                ErrorKind::InvalidInputError,
+                format!(
+                    "{} task IDs exceed the maximum of {}",
+                    self.task_ids.len(),
+                    max_page_size
+                ),
+            ));
+        }
+        let results = TASK_STORE
+            .get_many(&self.task_ids)?
+            .into_iter()
+            .map(|task_info| match task_info {
+                Some(ref task_info) if check_get_permission(task_info, &self.user_id) => {
+                    TaskStatusResult::Status(status_summary(task_info))
+                }
+                _ => TaskStatusResult::NotAccessible,
+            })
+            .collect();
+        Ok(TaskResponse::new_get_task_statuses(results))
+    }
+}
+
+fn status_summary(task_info: &TaskInfo) -> TaskStatusSummary {
+    let error = match task_info.cancel_reason {
+        Some(ref reason) => Some(reason.to_owned()),
+        None => task_info
+            .attempts
+            .iter()
+            .rev()
+            .filter_map(|attempt| attempt.error.as_ref())
+            .next()
+            .map(|error| error.message.to_owned()),
+    };
+    TaskStatusSummary {
+        status: task_info.status,
+        status_version: task_info.status_version,
+        created_at: task_info.created_at,
+        finished_at: task_info.finished_at,
+        error,
+    }
+}
+
+// Whether `task_info` matches all the filters of `query`. A versioned
+// function ID only matches tasks of that version.
+fn query_matches(query: &task_index::Query, task_info: &TaskInfo) -> bool {
+    if let Some(function_id) = query.function_id {
+        let versioned = task_index::function_key(function_id) != function_id;
+        let matched = match task_info.function_id {
+            Some(ref task_function_id) if versioned => task_function_id == function_id,
+            Some(ref task_function_id) => task_index::function_key(task_function_id) == function_id,
+            None => task_info.function_name == function_id,
+        };
+        if !matched {
+            return false;
+        }
+    }
+    if query
+        .status
+        .map_or(false, |status| status != task_info.status)
+    {
+        return false;
+    }
+    if query
+        .owner
+        .map_or(false, |owner| owner != task_info.user_id)
+    {
+        return false;
+    }
+    task_info.created_at >= query.since && task_info.created_at <= query.until
+}
+
+impl HandleRequest for UpdateTaskRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
         if self.files.is_empty() {
             return Err(mesatee_core::Error::from(
-                mesatee_core::ErrorKind::MissingValue,
+                mesatee_core::ErrorKind::InvalidInputError,
             ));
         }
 
@@ -242,6 +1129,7 @@ impl HandleRequest for UpdateTaskRequest {
             }
         }
 
+        let _status_lock = TASK_STATUS_LOCK.lock()?;
         let _lock = UPDATELOCK.lock()?;
         let task_info = TASK_STORE.get(&self.task_id)?;
         let mut task_info = task_info.ok_or_else(|| mesatee_core::ErrorKind::PermissionDenied)?;
@@ -261,28 +1149,27 @@ impl HandleRequest for UpdateTaskRequest {
             ));
         }
 
-        // update task status
-        if task_info.approved_user_number == task_info.collaborator_list.len() {
-            task_info.status = TaskStatus::Ready;
-        }
+        let ready = data_store::is_task_ready(&task_info)?;
 
         // Verify file permissions and update input files
-        let target = config::Internal::target_tdfs();
-        let mut client = TDFSClient::new(target)?;
+        task_limits::check_input_count(task_info.input_files.len() + self.files.len())?;
+        access_control::check_file_access(&self.files, &self.user_id)?;
         for file_id in self.files.iter() {
-            let accessible = client.check_access_permission(&file_id, &self.user_id)?;
-            if !accessible {
-                return Err(mesatee_core::Error::from(
-                    mesatee_core::ErrorKind::PermissionDenied,
-                ));
-            }
             task_info.input_files.push(TaskFile {
                 user_id: self.user_id.to_string(),
                 file_id: file_id.to_string(),
+                format: None,
             });
         }
+        task_limits::check_input_files(&task_info.input_files)?;
 
-        TASK_STORE.set(&self.task_id, &task_info)?;
+        // update task status
+        if ready {
+            let status = task_schedule::ready_status(&task_info);
+            data_store::update_task_status(&self.task_id, &mut task_info, status)?;
+        } else {
+            data_store::save_task(&self.task_id, &task_info)?;
+        }
 
         let resp = TaskResponse::new_update_task(
             true,
@@ -296,6 +1183,285 @@ impl HandleRequest for UpdateTaskRequest {
     }
 }
 
+impl HandleRequest for CancelTaskRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        let _lock = TASK_STATUS_LOCK.lock()?;
+        let mut task_info = TASK_STORE
+            .get(&self.task_id)?
+            .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+
+        if task_info.user_id != self.user_id {
+            return Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::PermissionDenied,
+            ));
+        }
+
+        if !task_info.status.can_transition_to(TaskStatus::Cancelled) {
+            return Err(Error::new(
+                ErrorKind::InvalidInputError,
+                format!(
+                    "task {} cannot be cancelled in status {:?}",
+                    self.task_id, task_info.status
+                ),
+            ));
+        }
+
+        // Ready tasks are no longer accepted by FNS. Running tasks are
+        // stopped by FNS and their results are discarded.
+        task_info.cancel_reason = Some(self.reason.to_string());
+        task_recovery::in_batch(|| {
+            data_store::update_task_status(&self.task_id, &mut task_info, TaskStatus::Cancelled)?;
+            data_store::resolve_dependents(&self.task_id, &task_info)
+        })?;
+
+        let resp = TaskResponse::new_cancel_task(task_info.status);
+        Ok(resp)
+    }
+}
+
+impl HandleRequest for DeleteTaskRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        let _lock = TASK_STATUS_LOCK.lock()?;
+        let task_info = TASK_STORE
+            .get(&self.task_id)?
+            .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+
+        if task_info.user_id != self.user_id {
+            return Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::PermissionDenied,
+            ));
+        }
+
+        if !task_info.status.is_final() {
+            return Err(Error::new(
+                ErrorKind::InvalidInputError,
+                format!(
+                    "task {} cannot be deleted in status {:?}",
+                    self.task_id, task_info.status
+                ),
+            ));
+        }
+
+        task_retention::delete_task(&self.task_id, &task_info)?;
+
+        let resp = TaskResponse::new_delete_task();
+        Ok(resp)
+    }
+}
+
+impl HandleRequest for WaitForTaskStatusRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        let task_info = TASK_STORE
+            .get(&self.task_id)?
+            .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+        if !check_get_permission(&task_info, &self.user_id) {
+            return Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::PermissionDenied,
+            ));
+        }
+
+        let mut timeout = std::cmp::min(Duration::from_secs(self.timeout_secs), MAX_STATUS_WAIT);
+        // The client stops waiting at its deadline, and so does the server
+        if let Some(deadline) = deadline::current() {
+            timeout = std::cmp::min(timeout, deadline.remaining());
+        }
+        let task_info = TASK_STATUS_NOTIFIER.wait(&self.task_id, self.current_version, timeout)?;
+        if task_info.status_version == self.current_version {
+            deadline::check_current()?;
+        }
+
+        let resp = TaskResponse::new_wait_for_task_status(
+            task_info.status_version != self.current_version,
+            task_info.status,
+            task_info.status_version,
+        );
+        Ok(resp)
+    }
+}
+
+impl HandleRequest for StreamTaskLogsRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        let task_info = TASK_STORE
+            .get(&self.task_id)?
+            .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+        if !check_get_permission(&task_info, &self.user_id) {
+            return Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::PermissionDenied,
+            ));
+        }
+        // Like the partial outputs of the task, its log is for the task
+        // creator alone
+        if task_info.user_id != self.user_id {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                "only the task creator may read the task log",
+            ));
+        }
+
+        let mut timeout = std::cmp::min(Duration::from_secs(self.timeout_secs), MAX_STATUS_WAIT);
+        if let Some(deadline) = deadline::current() {
+            timeout = std::cmp::min(timeout, deadline.remaining());
+        }
+        let slice = TASK_LOGS.wait(&self.task_id, self.from, timeout)?;
+        if slice.lines.is_empty() && !slice.complete {
+            deadline::check_current()?;
+        }
+
+        Ok(TaskResponse::new_stream_task_logs(
+            slice.lines,
+            slice.next,
+            slice.truncated,
+            slice.complete,
+        ))
+    }
+}
+
+impl HandleRequest for HealthCheckRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        let tdfs_up = TDFS_CHANNEL_POOL.check_connection().is_ok();
+        let dependencies = vec![DependencyHealth::new("tdfs", tdfs_up)];
+        Ok(TaskResponse::new_health_check(ServiceHealth::current(
+            dependencies,
+        )))
+    }
+}
+
+impl HandleRequest for GetTaskReceiptRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        let task_info = TASK_STORE
+            .get(&self.task_id)?
+            .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+        if !check_get_permission(&task_info, &self.user_id) {
+            return Err(Error::from(ErrorKind::PermissionDenied));
+        }
+        // Only finished tasks have a receipt.
+        let receipt = task_info.receipt.ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("task {} has no receipt", self.task_id),
+            )
+        })?;
+        Ok(TaskResponse::new_get_task_receipt(&receipt))
+    }
+}
+
+impl HandleRequest for GetEnclaveMeasurementRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        let identity = EnclaveIdentity::current()?;
+        Ok(TaskResponse::new_get_enclave_measurement(identity))
+    }
+}
+
+impl HandleRequest for GetVersionInfoRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        Ok(TaskResponse::new_get_version_info(VersionInfo::current()))
+    }
+}
+
+// The roles required to query the audit log and to revoke users are checked
+// by `RoleInterceptor`.
+impl HandleRequest for QueryAuditLogRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        let query = ListAuditEntriesRequest {
+            since: self.query.since,
+            until: self.query.until,
+            user_id: self.query.user_id.clone(),
+            after_sequence: self.query.after_sequence,
+            limit: data_store::page_size(self.query.limit)?,
+        };
+        let entries = tdfs_client()
+            .list_audit_entries(&query)?
+            .into_iter()
+            .map(|entry| AuditLogEntry {
+                sequence: entry.sequence,
+                timestamp: entry.timestamp,
+                user_id: entry.user_id,
+                event: entry.event,
+                prev_hash: entry.prev_hash,
+                hash: entry.hash,
+            })
+            .collect();
+        Ok(TaskResponse::new_query_audit_log(entries))
+    }
+}
+
+impl HandleRequest for RevokeUserRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        if self.revoked_user_id == self.user_id {
+            return Err(Error::new(
+                ErrorKind::InvalidInputError,
+                "admins cannot revoke themselves",
+            ));
+        }
+        let cancelled_tasks = user_revocation::revoke(&self.revoked_user_id, self.cancel_tasks)?;
+        info!(
+            "User {} revoked by {}, {} tasks cancelled",
+            self.revoked_user_id, self.user_id, cancelled_tasks
+        );
+        Ok(TaskResponse::new_revoke_user(cancelled_tasks))
+    }
+}
+
+impl HandleRequest for UnrevokeUserRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        user_revocation::unrevoke(&self.revoked_user_id)?;
+        info!(
+            "Revocation of user {} lifted by {}",
+            self.revoked_user_id, self.user_id
+        );
+        Ok(TaskResponse::new_unrevoke_user())
+    }
+}
+
+impl HandleRequest for ReloadEnclaveInfoRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        let services = config::reload_enclave_info(&self.fragments)?;
+        info!(
+            "Enclave info of {} services reloaded by {}",
+            services.len(),
+            self.user_id
+        );
+        // The peers verify the fragments themselves. A peer that fails to
+        // reload keeps its enclave info, which is reported rather than
+        // undoing the reload of TMS.
+        let tdfs = tdfs_client().reload_enclave_info(&self.fragments);
+        let kms = KMSClient::new(config::Internal::target_kms()).and_then(|mut client| {
+            client.reload_enclave_info(kms_proto::proto::ReloadEnclaveInfoRequest::new(
+                &self.fragments,
+            ))
+        });
+        let peers = vec![
+            PeerReload::new("tdfs", tdfs.err()),
+            PeerReload::new("kms", kms.err()),
+        ];
+        for peer in peers.iter().filter(|peer| peer.error.is_some()) {
+            warn!("Failed to reload enclave info of {}", peer.service);
+        }
+        Ok(TaskResponse::new_reload_enclave_info(services, peers))
+    }
+}
+
+impl HandleRequest for PutSecretRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        task_secret::put(&self.user_id, &self.name, &self.value, &self.readers)?;
+        Ok(TaskResponse::new_put_secret())
+    }
+}
+
+impl HandleRequest for DeleteSecretRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        task_secret::delete(&self.user_id, &self.name)?;
+        Ok(TaskResponse::new_delete_secret())
+    }
+}
+
+impl HandleRequest for ListSecretsRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        let secrets = task_secret::list(&self.user_id)?;
+        Ok(TaskResponse::new_list_secrets(secrets))
+    }
+}
+
 pub struct TMSExternalEnclave<S, T> {
     state: i32,
     x: PhantomData<S>,
@@ -317,12 +1483,47 @@ impl EnclaveService<TaskRequest, TaskResponse> for TMSExternalEnclave<TaskReques
         trace!("handle_invoke invoked!");
         trace!("incoming payload = {:?}", input);
         self.state += 1;
-        let response = match input {
-            TaskRequest::Create(req) => req.handle_request()?,
-            TaskRequest::Get(req) => req.handle_request()?,
-            TaskRequest::Update(req) => req.handle_request()?,
-            TaskRequest::List(req) => req.handle_request()?,
+        auth::check_authenticated(&input)?;
+        let audited = AuditedRequest::of(&input);
+        let result = match input {
+            TaskRequest::Create(req) => req.handle_request(),
+            TaskRequest::CreateTasks(req) => req.handle_request(),
+            TaskRequest::ValidateTask(req) => req.handle_request(),
+            TaskRequest::Get(req) => req.handle_request(),
+            TaskRequest::Update(req) => req.handle_request(),
+            TaskRequest::List(req) => req.handle_request(),
+            TaskRequest::Cancel(req) => req.handle_request(),
+            TaskRequest::Delete(req) => req.handle_request(),
+            TaskRequest::WaitForTaskStatus(req) => req.handle_request(),
+            TaskRequest::RegisterFunction(req) => req.handle_request(),
+            TaskRequest::UpdateFunction(req) => req.handle_request(),
+            TaskRequest::GetFunction(req) => req.handle_request(),
+            TaskRequest::GetFunctionVersions(req) => req.handle_request(),
+            TaskRequest::ListFunctions(req) => req.handle_request(),
+            TaskRequest::ShareFunction(req) => req.handle_request(),
+            TaskRequest::UnshareFunction(req) => req.handle_request(),
+            TaskRequest::HealthCheck(req) => req.handle_request(),
+            TaskRequest::GetEnclaveMeasurement(req) => req.handle_request(),
+            TaskRequest::GetVersionInfo(req) => req.handle_request(),
+            TaskRequest::QueryAuditLog(req) => req.handle_request(),
+            TaskRequest::RevokeUser(req) => req.handle_request(),
+            TaskRequest::UnrevokeUser(req) => req.handle_request(),
+            TaskRequest::QueryTasks(req) => req.handle_request(),
+            TaskRequest::GetTaskReceipt(req) => req.handle_request(),
+            TaskRequest::ListExecutors(req) => req.handle_request(),
+            TaskRequest::PutSecret(req) => req.handle_request(),
+            TaskRequest::DeleteSecret(req) => req.handle_request(),
+            TaskRequest::ListSecrets(req) => req.handle_request(),
+            TaskRequest::RerunTask(req) => req.handle_request(),
+            TaskRequest::InvokeFunction(req) => req.handle_request(),
+            TaskRequest::GetTaskStatuses(req) => req.handle_request(),
+            TaskRequest::ReloadEnclaveInfo(req) => req.handle_request(),
+            TaskRequest::StreamTaskLogs(req) => req.handle_request(),
         };
+        if let Some(audited) = audited {
+            audited.record(&result);
+        }
+        let response = result?;
         trace!("{}th round complete!", self.state);
         Ok(response)
     }
@@ -0,0 +1,404 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use uuid::Uuid;
+
+use mesatee_core::{config, Error, ErrorKind, FieldErrors, Result};
+use parity_wasm::elements::{self, External, MemoryType};
+use std::collections::HashSet;
+use wasmi::memory_units::Pages;
+
+use crate::argument_schema;
+use crate::data_store::{self, python_modules, Executor, ExecutorCapability, FunctionInfo};
+use crate::executor_registry;
+use crate::labels;
+use crate::port_binding;
+use crate::python_check;
+use crate::remote_payload;
+use crate::tms_external::HandleRequest;
+use tms_external_proto::{
+    GetFunctionRequest, GetFunctionVersionsRequest, ListExecutorsRequest, ListFunctionsRequest,
+    RegisterFunctionRequest, RemotePayload, ShareFunctionRequest, TaskResponse,
+    UnshareFunctionRequest, UpdateFunctionRequest,
+};
+
+// Only the owner may publish new versions of a function or change who it is
+// shared with.
+fn check_function_owner(function_id: &str, user_id: &str) -> Result<(String, FunctionInfo)> {
+    let (function_id, _) = data_store::parse_function_id(function_id)?;
+    let latest = data_store::get_function(function_id)?;
+    if latest.owner != user_id {
+        return Err(mesatee_core::Error::from(
+            mesatee_core::ErrorKind::PermissionDenied,
+        ));
+    }
+    Ok((function_id.to_owned(), latest))
+}
+
+fn check_function_name(function_name: &str) -> Result<()> {
+    if function_name.is_empty() {
+        return Err(Error::new(
+            ErrorKind::InvalidInputError,
+            "function name is empty",
+        ));
+    }
+    Ok(())
+}
+
+// A function may only declare the capabilities allowed by the cluster, and
+// only the Python executor honors them.
+fn check_capabilities(
+    errors: &mut FieldErrors,
+    executor: Option<Executor>,
+    capabilities: &[ExecutorCapability],
+) {
+    let allowed = &config::runtime_config().executor_capabilities.allowed;
+    for (index, capability) in capabilities.iter().enumerate() {
+        let path = format!("capabilities[{}]", index);
+        if capabilities[..index].contains(capability) {
+            errors.push(
+                &path,
+                Error::new(
+                    ErrorKind::InvalidInputError,
+                    format!("duplicated capability {}", capability.name()),
+                ),
+            );
+        } else if !allowed.iter().any(|name| name == capability.name()) {
+            errors.push(
+                &path,
+                Error::new(
+                    ErrorKind::PermissionDenied,
+                    format!(
+                        "capability {} is not allowed by the cluster",
+                        capability.name()
+                    ),
+                ),
+            );
+        }
+    }
+    match executor {
+        Some(Executor::Python) | None => {}
+        Some(_) if capabilities.is_empty() => {}
+        Some(_) => errors.push(
+            "capabilities",
+            Error::new(
+                ErrorKind::InvalidInputError,
+                "only Python functions may declare capabilities",
+            ),
+        ),
+    }
+}
+
+// The payload of a WASM or Python function is checked before the function is
+// accepted, the payload of a builtin function is passed to its worker as is.
+// A Python function may import the modules of the capabilities it declares.
+fn validate_payload(
+    executor: Executor,
+    payload: &Option<String>,
+    capabilities: &[ExecutorCapability],
+) -> Result<()> {
+    let payload = match (executor, payload) {
+        (Executor::Builtin, _) => return Ok(()),
+        (_, Some(payload)) => payload,
+        (_, None) => {
+            return Err(Error::new(
+                ErrorKind::InvalidInputError,
+                "function without a payload",
+            ))
+        }
+    };
+    match executor {
+        Executor::Wasm => {
+            let module = base64::decode(payload).map_err(|_| {
+                Error::new(
+                    ErrorKind::InvalidInputError,
+                    "WASM module is not base64 encoded",
+                )
+            })?;
+            let invalid = |e: &dyn std::fmt::Debug| {
+                Error::new(
+                    ErrorKind::InvalidInputError,
+                    format!("invalid WASM module: {:?}", e),
+                )
+            };
+            let module: elements::Module =
+                parity_wasm::deserialize_buffer(&module).map_err(|e| invalid(&e))?;
+            check_wasm_memory(&module)?;
+            wasmi::Module::from_parity_wasm_module(module).map_err(|e| invalid(&e))?;
+        }
+        Executor::Python => {
+            let allowed_modules = &config::runtime_config().python_executor.allowed_modules;
+            python_check::check_script(payload, &python_modules(allowed_modules, capabilities))?;
+        }
+        Executor::Builtin => {}
+    }
+    Ok(())
+}
+
+// Rejects modules whose memory cannot fit in the memory limit of any task. FNS
+// checks the memory against the limit of each task before instantiating the
+// module: initial sizes must be within it, and so must the maximum of the
+// memory of the module itself.
+fn check_wasm_memory(module: &elements::Module) -> Result<()> {
+    let max_memory_mb = data_store::maximum_resource_limits().max_memory_mb;
+    let max_pages = max_memory_mb.saturating_mul(1024 * 1024) / Pages::BYTE_SIZE.0 as u64;
+    let within_limit = |pages: u32| u64::from(pages) <= max_pages;
+    let imported = module
+        .import_section()
+        .map_or(&[][..], |section| section.entries())
+        .iter()
+        .all(|entry| match entry.external() {
+            External::Memory(memory) => within_limit(memory.limits().initial()),
+            _ => true,
+        });
+    let own = module
+        .memory_section()
+        .map_or(&[][..], |section| section.entries())
+        .iter()
+        .all(|memory: &MemoryType| {
+            within_limit(memory.limits().initial())
+                && memory.limits().maximum().map_or(false, within_limit)
+        });
+    if imported && own {
+        Ok(())
+    } else {
+        Err(Error::new(
+            ErrorKind::InvalidInputError,
+            format!(
+                "WASM memory must have an initial size and a maximum of at most {} pages",
+                max_pages
+            ),
+        ))
+    }
+}
+
+// Downloads a payload registered by URL, which is then validated like a
+// payload sent inline. A WASM module is base64 encoded as if sent inline.
+fn resolve_payload(
+    executor: Executor,
+    payload: &Option<String>,
+    remote_payload: &Option<RemotePayload>,
+) -> Result<Option<String>> {
+    let remote_payload = match remote_payload {
+        Some(remote_payload) => remote_payload,
+        None => return Ok(payload.clone()),
+    };
+    if payload.is_some() {
+        return Err(Error::new(
+            ErrorKind::InvalidInputError,
+            "function with both a payload and a payload URL",
+        ));
+    }
+    let downloaded = remote_payload::fetch(remote_payload)?;
+    let payload = match executor {
+        Executor::Wasm => base64::encode(&downloaded),
+        _ => String::from_utf8(downloaded).map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidInputError,
+                "downloaded payload is not UTF-8",
+            )
+        })?,
+    };
+    Ok(Some(payload))
+}
+
+impl HandleRequest for RegisterFunctionRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        // All the fields are checked before failing
+        let mut errors = FieldErrors::new();
+        errors.check("function_name", check_function_name(&self.function_name));
+        let executor = errors.check("executor", executor_registry::resolve(&self.executor));
+        let mut payload = None;
+        if let Some(executor) = executor {
+            let payload_field = if self.remote_payload.is_some() {
+                "remote_payload"
+            } else {
+                "payload"
+            };
+            payload = errors.check(
+                payload_field,
+                resolve_payload(executor, &self.payload, &self.remote_payload).and_then(
+                    |payload| {
+                        validate_payload(executor, &payload, &self.capabilities).map(|_| payload)
+                    },
+                ),
+            );
+        }
+        check_capabilities(&mut errors, executor, &self.capabilities);
+        if let Some(ref schema) = self.argument_schema {
+            errors.check("argument_schema", argument_schema::check_schema(schema));
+        }
+        errors.check(
+            "ports",
+            port_binding::check_ports(&self.input_ports, &self.output_ports),
+        );
+        errors.check("labels", labels::check_labels(&self.labels));
+        errors.into_result()?;
+        let (executor, payload) = match (executor, payload) {
+            (Some(executor), Some(payload)) => (executor, payload),
+            _ => return Err(Error::from(ErrorKind::BadImplementation)),
+        };
+
+        let function_id = Uuid::new_v4().to_string();
+        if data_store::FUNCTION_VERSION_STORE
+            .get(&function_id)?
+            .is_some()
+        {
+            return Err(Error::from(ErrorKind::UUIDError));
+        }
+        if !self.collaborators.is_empty() {
+            data_store::update_function_acl(&function_id, &self.collaborators, &[])?;
+        }
+        let function_info = FunctionInfo {
+            function_id: String::new(),
+            version: 0,
+            function_name: self.function_name.to_string(),
+            description: self.description.to_string(),
+            payload,
+            owner: self.user_id.to_string(),
+            executor,
+            optional_inputs: self.optional_inputs.clone(),
+            argument_schema: self.argument_schema.clone(),
+            input_ports: self.input_ports.clone(),
+            output_ports: self.output_ports.clone(),
+            labels: self.labels.clone(),
+            capabilities: self.capabilities.clone(),
+        };
+        let versioned_id = data_store::add_function_version(&function_id, function_info)?;
+
+        Ok(TaskResponse::new_register_function(&versioned_id))
+    }
+}
+
+impl HandleRequest for UpdateFunctionRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        // The executor, optional inputs, argument schema, ports, labels and
+        // capabilities of a function are fixed when it is registered.
+        let (function_id, latest) = check_function_owner(&self.function_id, &self.user_id)?;
+        let mut errors = FieldErrors::new();
+        errors.check("function_name", check_function_name(&self.function_name));
+        errors.check(
+            "payload",
+            validate_payload(latest.executor, &self.payload, &latest.capabilities),
+        );
+        errors.into_result()?;
+        let function_info = FunctionInfo {
+            function_id: String::new(),
+            version: 0,
+            function_name: self.function_name.to_string(),
+            description: self.description.to_string(),
+            payload: self.payload.clone(),
+            owner: self.user_id.to_string(),
+            executor: latest.executor,
+            optional_inputs: latest.optional_inputs,
+            argument_schema: latest.argument_schema,
+            input_ports: latest.input_ports,
+            output_ports: latest.output_ports,
+            labels: latest.labels,
+            capabilities: latest.capabilities,
+        };
+        let versioned_id = data_store::add_function_version(&function_id, function_info)?;
+
+        Ok(TaskResponse::new_update_function(&versioned_id))
+    }
+}
+
+impl HandleRequest for GetFunctionRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        let function_info = data_store::get_function_for_user(&self.function_id, &self.user_id)?;
+        Ok(TaskResponse::new_get_function(&function_info))
+    }
+}
+
+impl HandleRequest for GetFunctionVersionsRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        let (function_id, _) = data_store::parse_function_id(&self.function_id)?;
+        let _ = data_store::get_function_for_user(function_id, &self.user_id)?;
+        let versions = data_store::get_function_versions(function_id)?;
+        Ok(TaskResponse::new_get_function_versions(versions))
+    }
+}
+
+impl HandleRequest for ListFunctionsRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        let limit = data_store::page_size(self.limit)?;
+        let mut function_ids = data_store::USER_FUNCTION_STORE
+            .get(&self.user_id)?
+            .unwrap_or_default();
+        if !self.labels.is_empty() {
+            let mut labeled = HashSet::new();
+            for function_id in function_ids.into_iter() {
+                let function_info = data_store::get_function(&function_id)?;
+                if labels::matches(&function_info.labels, &self.labels) {
+                    labeled.insert(function_id);
+                }
+            }
+            function_ids = labeled;
+        }
+        let (page, next_token) = data_store::paginate(&function_ids, &self.cursor, limit);
+        let mut functions: Vec<FunctionInfo> = Vec::with_capacity(page.len());
+        for function_id in page.iter() {
+            functions.push(data_store::get_function(function_id)?);
+        }
+        Ok(TaskResponse::new_list_functions(functions, &next_token))
+    }
+}
+
+impl HandleRequest for ShareFunctionRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        if self.user_ids.is_empty() {
+            return Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::InvalidInputError,
+            ));
+        }
+
+        let (function_id, _) = check_function_owner(&self.function_id, &self.user_id)?;
+        // The owner always has access and is never listed as a collaborator.
+        let user_ids: Vec<String> = self
+            .user_ids
+            .iter()
+            .filter(|user_id| **user_id != self.user_id)
+            .cloned()
+            .collect();
+        let collaborators = data_store::update_function_acl(&function_id, &user_ids, &[])?;
+        Ok(TaskResponse::new_share_function(collaborators))
+    }
+}
+
+impl HandleRequest for UnshareFunctionRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        if self.user_ids.is_empty() {
+            return Err(mesatee_core::Error::from(
+                mesatee_core::ErrorKind::InvalidInputError,
+            ));
+        }
+
+        let (function_id, _) = check_function_owner(&self.function_id, &self.user_id)?;
+        let collaborators = data_store::update_function_acl(&function_id, &[], &self.user_ids)?;
+        Ok(TaskResponse::new_unshare_function(collaborators))
+    }
+}
+
+impl HandleRequest for ListExecutorsRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        let executors = executor_registry::list()?;
+        Ok(TaskResponse::new_list_executors(executors))
+    }
+}
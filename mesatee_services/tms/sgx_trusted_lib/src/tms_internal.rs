@@ -18,11 +18,50 @@
 #[cfg(feature = "mesalock_sgx")]
 use std::prelude::v1::*;
 
-use crate::data_store::TASK_STORE;
+use crate::data_store::{
+    self, TaskInfo, TaskStatus, TASK_LOGS, TASK_SCHEDULER, TASK_STATUS_LOCK, TASK_STORE,
+    WORKER_POOL,
+};
+use crate::dynamic_output;
+use crate::executor_registry;
+use crate::output_format;
+use crate::port_binding;
+use crate::task_limits;
+use crate::task_receipt;
+use crate::task_recovery;
+use crate::task_retention;
+use crate::task_retry;
+use crate::task_secret;
+use crate::task_staging;
+use mesatee_core::config;
+use mesatee_core::health::VersionInfo;
 use mesatee_core::rpc::EnclaveService;
 use mesatee_core::{Error, ErrorKind, Result};
 use std::marker::PhantomData;
-use tms_internal_proto::{GetTaskRequest, TaskRequest, TaskResponse, UpdateTaskRequest};
+use tms_internal_proto::{
+    AppendTaskLogRequest, GetFunctionRequest, GetTaskRequest, GetTaskSecretsRequest,
+    GetVersionInfoRequest, PullTaskRequest, RegisterExecutorsRequest, RegisterWorkerRequest,
+    ReportProgressRequest, TaskProgress, TaskRequest, TaskResponse, UpdateTaskRequest,
+    WorkerHeartbeatRequest, MAX_PROGRESS_CHECKPOINTS,
+};
+
+const MAX_PROGRESS_MESSAGE_LEN: usize = 1024;
+
+// The receipt is issued before the task is seen as finished, so that it can be
+// fetched as soon as the task has finished. The task finishes without a receipt
+// if it cannot be issued.
+fn issue_receipt(task_id: &str, task_info: &mut TaskInfo) {
+    let mut finished_info = task_info.clone();
+    // As on the receipt, despite a clock moved back since the task was created
+    finished_info.finished_at = Some(task_retention::now().max(task_info.created_at));
+    match task_receipt::issue(task_id, &finished_info) {
+        Ok(receipt) => {
+            task_info.finished_at = finished_info.finished_at;
+            task_info.receipt = Some(receipt);
+        }
+        Err(e) => warn!("Cannot issue the receipt of task {}: {}", task_id, e),
+    }
+}
 
 pub trait HandleRequest {
     fn handle_request(&self) -> Result<TaskResponse>;
@@ -30,24 +69,105 @@ pub trait HandleRequest {
 
 impl HandleRequest for UpdateTaskRequest {
     fn handle_request(&self) -> Result<TaskResponse> {
+        let _lock = TASK_STATUS_LOCK.lock()?;
         let old_info = TASK_STORE.get(&self.task_id)?;
-        // ToDo: Race Condition can be solved in FNS
         let mut old_info = match old_info {
             Some(value) => value,
             None => return Ok(TaskResponse::new_update_task(false)),
         };
 
+        // A task cancelled while running is not updated with its results.
+        if let Some(status) = self.status {
+            if !old_info.status.can_transition_to(status) {
+                return Ok(TaskResponse::new_update_task(false));
+            }
+        } else if old_info.status.is_final() {
+            return Ok(TaskResponse::new_update_task(false));
+        }
+
         if self.task_result_file_id.is_some() {
             old_info.task_result_file_id = self.task_result_file_id.clone();
         }
 
-        old_info.output_files.extend_from_slice(&self.output_files);
+        let mut output_files = self.output_files.clone();
+        output_format::fill(&mut output_files)?;
+        old_info.output_files.extend(output_files);
+        task_limits::check_output_count(old_info.output_files.len())?;
 
-        if let Some(ref status) = self.status {
-            old_info.status = *status;
+        // The result is saved for the task creator
+        if let Some(ref result_file_id) = self.result_file_id {
+            let is_creator_output = old_info
+                .output_files
+                .iter()
+                .any(|file| &file.file_id == result_file_id && file.user_id == old_info.user_id);
+            if !is_creator_output {
+                return Err(Error::new(
+                    ErrorKind::InvalidInputError,
+                    format!(
+                        "result file {} is not an output of the task",
+                        result_file_id
+                    ),
+                ));
+            }
+            old_info.result_file_id = Some(result_file_id.to_owned());
         }
 
-        let _ = TASK_STORE.set(&self.task_id, &old_info)?;
+        if !self.output_ports.is_empty() || !self.dynamic_outputs.is_empty() {
+            let output_ports = match old_info.function_id {
+                Some(ref function_id) => data_store::get_function(function_id)?.output_ports,
+                None => Vec::new(),
+            };
+            if !self.output_ports.is_empty() {
+                let output_file_ids: Vec<&str> = old_info
+                    .output_files
+                    .iter()
+                    .map(|file| file.file_id.as_str())
+                    .collect();
+                port_binding::check_output_bindings(
+                    &output_ports,
+                    &self.output_ports,
+                    &output_file_ids,
+                )?;
+                output_format::check_bindings(
+                    &output_ports,
+                    &self.output_ports,
+                    &old_info.output_files,
+                )?;
+                old_info.output_ports.extend(self.output_ports.clone());
+            }
+            if !self.dynamic_outputs.is_empty() {
+                dynamic_output::record(
+                    &self.task_id,
+                    &mut old_info,
+                    &output_ports,
+                    &self.dynamic_outputs,
+                )?;
+            }
+        }
+
+        match self.status {
+            Some(status) => {
+                // The executor ends an attempt, which may be retried
+                let status = if old_info.status == TaskStatus::Running && status.is_final() {
+                    task_retry::end_attempt(&mut old_info, status, self.error.as_ref())
+                } else {
+                    status
+                };
+                if status == TaskStatus::Finished {
+                    issue_receipt(&self.task_id, &mut old_info);
+                }
+                task_recovery::in_batch(|| {
+                    data_store::update_task_status(&self.task_id, &mut old_info, status)?;
+                    if status.is_final() {
+                        data_store::resolve_dependents(&self.task_id, &old_info)?;
+                    }
+                    Ok(())
+                })?;
+            }
+            None => {
+                data_store::save_task(&self.task_id, &old_info)?;
+            }
+        }
 
         let resp = TaskResponse::new_update_task(true);
         Ok(resp)
@@ -56,14 +176,177 @@ impl HandleRequest for UpdateTaskRequest {
 
 impl HandleRequest for GetTaskRequest {
     fn handle_request(&self) -> Result<TaskResponse> {
-        let task_info = TASK_STORE
+        let _lock = TASK_STATUS_LOCK.lock()?;
+        let mut task_info = TASK_STORE
             .get(&self.task_id)?
-            .ok_or_else(|| Error::from(ErrorKind::MissingValue))?;
+            .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+        // Executors get a ready task before they start it
+        if task_info.status == TaskStatus::Ready {
+            task_staging::handle_staged_task(&self.task_id, &mut task_info)?;
+        }
         let resp = TaskResponse::new_get_task(&task_info);
         Ok(resp)
     }
 }
 
+impl HandleRequest for GetTaskSecretsRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        let task_info = TASK_STORE
+            .get(&self.task_id)?
+            .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+        if task_info.task_token != self.task_token {
+            return Err(Error::from(ErrorKind::PermissionDenied));
+        }
+        match task_info.status {
+            TaskStatus::Ready | TaskStatus::Running => {}
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::PermissionDenied,
+                    format!("task {} is {:?}", self.task_id, task_info.status),
+                ))
+            }
+        }
+        let env = task_secret::open_task_secrets(&self.task_id, &task_info)?;
+        Ok(TaskResponse::new_get_task_secrets(env))
+    }
+}
+
+impl HandleRequest for GetFunctionRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        let function_info = data_store::get_function(&self.function_id)?;
+        let resp = TaskResponse::new_get_function(&function_info);
+        Ok(resp)
+    }
+}
+
+impl HandleRequest for PullTaskRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        let _lock = TASK_STATUS_LOCK.lock()?;
+        if let Some(ref worker_id) = self.worker_id {
+            if !WORKER_POOL.has_room(worker_id, task_retention::now())? {
+                return Ok(TaskResponse::new_pull_task(None));
+            }
+        }
+        while let Some(task_id) = TASK_SCHEDULER.dequeue()? {
+            // Skip tasks deleted since they were queued, and those failing
+            // for a missing input. Tasks which cannot be staged yet stay queued.
+            if let Some(mut task_info) = TASK_STORE.get(&task_id)? {
+                if task_info.status == TaskStatus::Ready {
+                    match task_staging::handle_staged_task(&task_id, &mut task_info) {
+                        Ok(()) => {}
+                        Err(_) if task_info.status == TaskStatus::Failed => continue,
+                        Err(err) => {
+                            TASK_SCHEDULER.enqueue(&task_id, task_info.priority)?;
+                            return Err(err);
+                        }
+                    }
+                    if let Some(ref worker_id) = self.worker_id {
+                        WORKER_POOL.assign(worker_id, &task_id)?;
+                    }
+                    return Ok(TaskResponse::new_pull_task(Some((&task_id, &task_info))));
+                }
+            }
+        }
+        Ok(TaskResponse::new_pull_task(None))
+    }
+}
+
+impl HandleRequest for RegisterWorkerRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        let worker_id = WORKER_POOL.register(self.capacity, task_retention::now())?;
+        info!(
+            "Registered worker {} running {} tasks at once",
+            worker_id, self.capacity
+        );
+        Ok(TaskResponse::new_register_worker(
+            &worker_id,
+            WORKER_POOL.heartbeat_timeout(),
+        ))
+    }
+}
+
+impl HandleRequest for RegisterExecutorsRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        executor_registry::register(&self.executors)?;
+        let enclave_info = if self.enclave_info_digest == config::enclave_info_digest() {
+            Vec::new()
+        } else {
+            config::signed_enclave_info()
+        };
+        Ok(TaskResponse::new_register_executors(enclave_info))
+    }
+}
+
+impl HandleRequest for WorkerHeartbeatRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        let registered = WORKER_POOL.heartbeat(&self.worker_id, task_retention::now())?;
+        Ok(TaskResponse::new_worker_heartbeat(registered))
+    }
+}
+
+impl HandleRequest for ReportProgressRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        if self.percent > 100 {
+            return Err(Error::new(
+                ErrorKind::InvalidInputError,
+                format!("progress of {}% is over 100%", self.percent),
+            ));
+        }
+        if self.message.len() > MAX_PROGRESS_MESSAGE_LEN {
+            return Err(Error::new(
+                ErrorKind::InvalidInputError,
+                format!(
+                    "progress message is longer than {} bytes",
+                    MAX_PROGRESS_MESSAGE_LEN
+                ),
+            ));
+        }
+
+        let _lock = TASK_STATUS_LOCK.lock()?;
+        let mut task_info = match TASK_STORE.get(&self.task_id)? {
+            Some(task_info) => task_info,
+            None => return Ok(TaskResponse::new_report_progress(false)),
+        };
+        if task_info.status.is_final() {
+            return Ok(TaskResponse::new_report_progress(false));
+        }
+
+        task_info.progress.push(TaskProgress {
+            percent: self.percent,
+            message: self.message.clone(),
+            partial_output: self.partial_output.clone(),
+            reported_at: task_retention::now(),
+        });
+        if task_info.progress.len() > MAX_PROGRESS_CHECKPOINTS {
+            let excess = task_info.progress.len() - MAX_PROGRESS_CHECKPOINTS;
+            task_info.progress.drain(..excess);
+        }
+        data_store::save_task(&self.task_id, &task_info)?;
+
+        Ok(TaskResponse::new_report_progress(true))
+    }
+}
+
+impl HandleRequest for AppendTaskLogRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        // Lines are appended before the task ends, and tailing clients see
+        // them before they see the task end
+        let _lock = TASK_STATUS_LOCK.lock()?;
+        match TASK_STORE.get(&self.task_id)? {
+            Some(ref task_info) if !task_info.status.is_final() => {}
+            _ => return Ok(TaskResponse::new_append_task_log(false)),
+        }
+        TASK_LOGS.append(&self.task_id, &self.lines)?;
+        Ok(TaskResponse::new_append_task_log(true))
+    }
+}
+
+impl HandleRequest for GetVersionInfoRequest {
+    fn handle_request(&self) -> Result<TaskResponse> {
+        Ok(TaskResponse::new_get_version_info(VersionInfo::current()))
+    }
+}
+
 pub struct TMSInternalEnclave<S, T> {
     state: i32,
     x: PhantomData<S>,
@@ -88,6 +371,15 @@ impl EnclaveService<TaskRequest, TaskResponse> for TMSInternalEnclave<TaskReques
         let response = match input {
             TaskRequest::Update(req) => req.handle_request()?,
             TaskRequest::Get(req) => req.handle_request()?,
+            TaskRequest::GetFunction(req) => req.handle_request()?,
+            TaskRequest::Pull(req) => req.handle_request()?,
+            TaskRequest::GetVersionInfo(req) => req.handle_request()?,
+            TaskRequest::ReportProgress(req) => req.handle_request()?,
+            TaskRequest::RegisterWorker(req) => req.handle_request()?,
+            TaskRequest::WorkerHeartbeat(req) => req.handle_request()?,
+            TaskRequest::RegisterExecutors(req) => req.handle_request()?,
+            TaskRequest::GetTaskSecrets(req) => req.handle_request()?,
+            TaskRequest::AppendTaskLog(req) => req.handle_request()?,
         };
         trace!("{}th round complete!", self.state);
         Ok(response)
@@ -0,0 +1,115 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+// Admins may revoke a user, whose requests are then rejected by the
+// `AuthInterceptor` whatever their token, until the revocation is lifted.
+// Revoked users are recorded in the storage backend, which outlives TMS
+// restarts, and are restored when the TMS enclave starts.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use crate::data_store::{self, TaskStatus, TASK_STATUS_LOCK, TASK_STORE, USER_TASK_STORE};
+use crate::storage;
+use crate::task_recovery;
+use lazy_static::lazy_static;
+use mesatee_core::{Error, ErrorKind, Result};
+use std::collections::HashSet;
+use std::sync::SgxRwLock;
+
+// Reason recorded on the tasks cancelled by a revocation.
+const CANCEL_REASON: &str = "user revoked";
+
+// Revoked users are recorded under this prefix, with an empty value.
+const RECORD_PREFIX: &str = "revoked_user/";
+
+fn record_key(user_id: &str) -> String {
+    format!("{}{}", RECORD_PREFIX, user_id)
+}
+
+lazy_static! {
+    static ref REVOKED_USERS: SgxRwLock<HashSet<String>> = SgxRwLock::new(HashSet::new());
+}
+
+pub fn is_revoked(user_id: &str) -> bool {
+    match REVOKED_USERS.read() {
+        Ok(revoked_users) => revoked_users.contains(user_id),
+        // Fail closed, nobody is let through with a poisoned blocklist.
+        Err(_) => true,
+    }
+}
+
+// Revokes `user_id` and, if `cancel_tasks`, cancels the tasks they created
+// which have not ended. Returns the number of cancelled tasks. The revocation
+// is recorded before it takes effect, so that an error leaves it unapplied.
+pub fn revoke(user_id: &str, cancel_tasks: bool) -> Result<usize> {
+    storage::backend()?.put(&record_key(user_id), "")?;
+    REVOKED_USERS.write()?.insert(user_id.to_owned());
+    if !cancel_tasks {
+        return Ok(0);
+    }
+
+    let _lock = TASK_STATUS_LOCK.lock()?;
+    let task_ids = USER_TASK_STORE
+        .get(&user_id.to_owned())?
+        .unwrap_or_default();
+    let mut count = 0;
+    for task_id in task_ids.iter() {
+        let mut task_info = match TASK_STORE.get(task_id)? {
+            Some(task_info) => task_info,
+            None => continue,
+        };
+        // Tasks the user only collaborates on are left to their creator.
+        if task_info.user_id != user_id
+            || !task_info.status.can_transition_to(TaskStatus::Cancelled)
+        {
+            continue;
+        }
+        task_info.cancel_reason = Some(CANCEL_REASON.to_owned());
+        task_recovery::in_batch(|| {
+            data_store::update_task_status(task_id, &mut task_info, TaskStatus::Cancelled)?;
+            data_store::resolve_dependents(task_id, &task_info)
+        })?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+// Lifts the revocation of `user_id`. The user is let through again even if
+// the record cannot be deleted, in which case the revocation comes back when
+// TMS restarts.
+pub fn unrevoke(user_id: &str) -> Result<()> {
+    if !REVOKED_USERS.write()?.remove(user_id) {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!("user {} is not revoked", user_id),
+        ));
+    }
+    storage::backend()?.delete(&record_key(user_id))
+}
+
+// Restores the users revoked before TMS restarted, and returns their number.
+// Called at enclave init, before any request is served.
+pub fn recover_revoked_users() -> Result<usize> {
+    let records = storage::backend()?.scan(RECORD_PREFIX)?;
+    let mut revoked_users = REVOKED_USERS.write()?;
+    revoked_users.extend(
+        records
+            .into_iter()
+            .map(|(key, _)| key[RECORD_PREFIX.len()..].to_owned()),
+    );
+    Ok(revoked_users.len())
+}
@@ -0,0 +1,53 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// Insert std prelude in the top for the sgx feature
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+/// Attestation scheme and service used to endorse the RA certificate of an
+/// enclave.
+#[derive(Clone, Debug)]
+pub enum AttestationConfig {
+    /// EPID based attestation verified by Intel Attestation Service.
+    Epid {
+        ias_key: String,
+        ias_spid: String,
+        production: bool,
+    },
+    /// ECDSA based attestation (DCAP). The quote is generated by the
+    /// platform's quoting enclave and verified by the attestation service at
+    /// `pccs_url` against the collateral (PCK certificates, TCB info and QE
+    /// identity) cached in the PCCS.
+    Dcap { pccs_url: String },
+}
+
+impl AttestationConfig {
+    pub fn new(ias_key: &str, ias_spid: &str, production: bool) -> Self {
+        AttestationConfig::Epid {
+            ias_key: ias_key.to_owned(),
+            ias_spid: ias_spid.to_owned(),
+            production,
+        }
+    }
+
+    pub fn dcap(pccs_url: &str) -> Self {
+        AttestationConfig::Dcap {
+            pccs_url: pccs_url.to_owned(),
+        }
+    }
+}
@@ -0,0 +1,124 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use crate::ias::IasClient;
+use crate::report::IasReport;
+use crate::AttestationError;
+use anyhow::Error;
+use anyhow::Result;
+use log::debug;
+use sgx_types::*;
+use std::ffi::CString;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::prelude::v1::*;
+use std::sync::Arc;
+
+extern "C" {
+    fn ocall_sgx_get_dcap_socket(p_retval: *mut i32, p_addr: *const c_char) -> sgx_status_t;
+}
+
+/// Client of a DCAP attestation service. The service verifies ECDSA quotes
+/// against the collateral cached in the PCCS and replies with an
+/// IAS-compatible attestation report, so that EPID and DCAP reports can be
+/// embedded in the RA certificate and verified in the same way.
+pub struct DcapClient {
+    hostname: String,
+    port: u16,
+    base_path: String,
+}
+
+impl DcapClient {
+    pub fn new(pccs_url: &str) -> Result<Self> {
+        let scheme = "https://";
+        if !pccs_url.starts_with(scheme) {
+            return Err(Error::new(AttestationError::DcapError));
+        }
+        let url = &pccs_url[scheme.len()..];
+        let (authority, base_path) = match url.find('/') {
+            Some(i) => (&url[..i], url[i..].trim_end_matches('/')),
+            None => (url, ""),
+        };
+        let (hostname, port) = match authority.rfind(':') {
+            Some(i) => (&authority[..i], authority[i + 1..].parse::<u16>()?),
+            None => (authority, 443),
+        };
+
+        Ok(Self {
+            hostname: hostname.to_owned(),
+            port,
+            base_path: base_path.to_owned(),
+        })
+    }
+
+    fn get_dcap_socket(&self) -> Result<c_int> {
+        debug!("get_dcap_socket");
+        let addr = CString::new(format!("{}:{}", self.hostname, self.port))?;
+        let mut fd: i32 = -1i32;
+        let res = unsafe { ocall_sgx_get_dcap_socket(&mut fd as _, addr.as_ptr()) };
+
+        if res != sgx_status_t::SGX_SUCCESS || fd < 0 {
+            Err(Error::new(AttestationError::OCallError))
+        } else {
+            Ok(fd)
+        }
+    }
+
+    fn new_tls_stream(&self) -> Result<rustls::StreamOwned<rustls::ClientSession, TcpStream>> {
+        let fd = self.get_dcap_socket()?;
+        let dns_name = webpki::DNSNameRef::try_from_ascii_str(&self.hostname)?;
+        let mut config = rustls::ClientConfig::new();
+        config
+            .root_store
+            .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
+        let client = rustls::ClientSession::new(&Arc::new(config), dns_name);
+        let socket = TcpStream::new(fd)?;
+        let stream = rustls::StreamOwned::new(client, socket);
+
+        Ok(stream)
+    }
+
+    pub fn get_report(&mut self, quote: &[u8]) -> Result<IasReport> {
+        debug!("get_report");
+        let report_uri = format!("{}/sgx/attestation/v3/report", self.base_path);
+        let encoded_quote = base64::encode(quote);
+        let encoded_json = format!("{{\"isvEnclaveQuote\":\"{}\"}}\r\n", encoded_quote);
+
+        let request = format!(
+            "POST {} HTTP/1.1\r\n\
+             HOST: {}\r\n\
+             Connection: Close\r\n\
+             Content-Length: {}\r\n\
+             Content-Type: application/json\r\n\r\n\
+             {}",
+            report_uri,
+            self.hostname,
+            encoded_json.len(),
+            encoded_json
+        );
+        debug!("{}", request);
+
+        let mut stream = self.new_tls_stream()?;
+        stream.write_all(request.as_bytes())?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response)?;
+
+        debug!("{}", String::from_utf8_lossy(&response));
+
+        IasClient::parse_report(&response)
+    }
+}
@@ -0,0 +1,320 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// Insert std prelude in the top for the sgx feature
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use crate::quote::{self, CertVerificationError};
+use anyhow::{Error, Result};
+use serde_cbor::Value as CborValue;
+use serde_json::{json, Value as JsonValue};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// Version of the JSON and CBOR schema of `AttestationReport`. Fields may
+/// only be added within a version, readers ignore the ones they do not know.
+pub const ATTESTATION_REPORT_VERSION: u64 = 1;
+
+// Separates the fields of the evidence embedded in the RA certificate.
+const PAYLOAD_SEPARATOR: u8 = 0x7C;
+
+#[derive(Error, Debug)]
+pub enum ReportFormatError {
+    #[error("Unsupported attestation report version {0}")]
+    UnsupportedVersion(u64),
+    #[error("Missing or invalid field {0} of attestation report")]
+    InvalidField(&'static str),
+}
+
+/// Attestation evidence of an enclave: the report of the attestation service
+/// and the signature over it, with the certificate it was signed with. It is
+/// embedded in the RA certificate of the enclave, and can be exported to
+/// JSON or CBOR for external tools to verify it independently.
+///
+/// Both formats have the same fields:
+///
+/// * `version`: `ATTESTATION_REPORT_VERSION`;
+/// * `report`: the report as a string, verbatim as it was signed;
+/// * `signature`: the RSA-SHA256 signature over the report;
+/// * `signing_cert`: the DER certificate of the signing key.
+///
+/// The signature and the certificate are base64 strings in JSON and byte
+/// strings in CBOR.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AttestationReport {
+    pub report: String,
+    pub signature: Vec<u8>,
+    pub signing_cert: Vec<u8>,
+}
+
+impl AttestationReport {
+    /// Extracts the evidence embedded in an RA certificate.
+    pub fn from_cert(cert_der: &[u8]) -> Result<Self> {
+        let (_, payload) = quote::parse_ra_cert(cert_der)?;
+        Self::from_payload(&payload)
+    }
+
+    /// Parses the evidence as embedded in an RA certificate, the report,
+    /// the base64 signature and the base64 signing certificate separated by
+    /// `|`.
+    pub fn from_payload(payload: &[u8]) -> Result<Self> {
+        let mut iter = payload.split(|x| *x == PAYLOAD_SEPARATOR);
+        let mut next = || {
+            iter.next()
+                .ok_or_else(|| Error::new(CertVerificationError::InvalidCertFormat))
+        };
+        let report = String::from_utf8(next()?.to_vec())
+            .map_err(|_| CertVerificationError::InvalidCertFormat)?;
+        let signature = base64::decode(next()?)?;
+        let signing_cert = base64::decode_config(next()?, base64::STANDARD)?;
+        Ok(AttestationReport {
+            report,
+            signature,
+            signing_cert,
+        })
+    }
+
+    /// Parses the evidence in any of its forms: exported to JSON or CBOR, or
+    /// as embedded in an RA certificate.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if let Some(report) = std::str::from_utf8(bytes)
+            .ok()
+            .and_then(|json| Self::from_json(json).ok())
+        {
+            return Ok(report);
+        }
+        Self::from_cbor(bytes).or_else(|_| Self::from_payload(bytes))
+    }
+
+    /// Verifies that the report is signed by a signing certificate issued
+    /// by one of the accepted root CA certificates, returning the index of
+    /// that root. The content of the report is left to `SgxQuote`.
+    pub fn verify_signature(&self, ias_report_ca_certs: &[&[u8]]) -> Result<usize> {
+        let sig_cert = webpki::EndEntityCert::from(&self.signing_cert)
+            .map_err(|_| CertVerificationError::InvalidCertFormat)?;
+
+        // Verify if the signing cert is issued by one of the accepted root CAs
+        let root_ca_index = ias_report_ca_certs
+            .iter()
+            .position(|ca_cert| quote::verify_signing_cert(&sig_cert, ca_cert).is_ok())
+            .ok_or_else(|| Error::new(CertVerificationError::WebpkiFailure))?;
+
+        // Verify the signature against the signing cert
+        sig_cert
+            .verify_signature(
+                &webpki::RSA_PKCS1_2048_8192_SHA256,
+                self.report.as_bytes(),
+                &self.signature,
+            )
+            .map_err(|_| CertVerificationError::WebpkiFailure)?;
+
+        Ok(root_ca_index)
+    }
+
+    pub fn to_json(&self) -> String {
+        json!({
+            "version": ATTESTATION_REPORT_VERSION,
+            "report": self.report,
+            "signature": base64::encode(&self.signature),
+            "signing_cert": base64::encode(&self.signing_cert),
+        })
+        .to_string()
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        let value: JsonValue = serde_json::from_str(json)?;
+        let version = value["version"]
+            .as_u64()
+            .ok_or_else(|| ReportFormatError::InvalidField("version"))?;
+        check_version(version)?;
+        let field = |name: &'static str| {
+            value[name]
+                .as_str()
+                .ok_or_else(|| Error::new(ReportFormatError::InvalidField(name)))
+        };
+        let bytes = |name: &'static str| {
+            base64::decode(field(name)?)
+                .map_err(|_| Error::new(ReportFormatError::InvalidField(name)))
+        };
+        Ok(AttestationReport {
+            report: field("report")?.to_owned(),
+            signature: bytes("signature")?,
+            signing_cert: bytes("signing_cert")?,
+        })
+    }
+
+    pub fn to_cbor(&self) -> Result<Vec<u8>> {
+        let mut map = BTreeMap::new();
+        let mut insert = |name: &str, value| map.insert(CborValue::Text(name.to_owned()), value);
+        insert(
+            "version",
+            CborValue::Integer(i128::from(ATTESTATION_REPORT_VERSION)),
+        );
+        insert("report", CborValue::Text(self.report.to_owned()));
+        insert("signature", CborValue::Bytes(self.signature.to_owned()));
+        insert(
+            "signing_cert",
+            CborValue::Bytes(self.signing_cert.to_owned()),
+        );
+        Ok(serde_cbor::to_vec(&CborValue::Map(map))?)
+    }
+
+    pub fn from_cbor(cbor: &[u8]) -> Result<Self> {
+        let mut map = match serde_cbor::from_slice(cbor)? {
+            CborValue::Map(map) => map,
+            _ => return Err(Error::new(ReportFormatError::InvalidField("version"))),
+        };
+        let mut field = |name: &'static str| {
+            map.remove(&CborValue::Text(name.to_owned()))
+                .ok_or_else(|| Error::new(ReportFormatError::InvalidField(name)))
+        };
+        let version = match field("version")? {
+            CborValue::Integer(version) if version >= 0 => version as u64,
+            _ => return Err(Error::new(ReportFormatError::InvalidField("version"))),
+        };
+        check_version(version)?;
+        let report = match field("report")? {
+            CborValue::Text(report) => report,
+            _ => return Err(Error::new(ReportFormatError::InvalidField("report"))),
+        };
+        let signature = match field("signature")? {
+            CborValue::Bytes(signature) => signature,
+            _ => return Err(Error::new(ReportFormatError::InvalidField("signature"))),
+        };
+        let signing_cert = match field("signing_cert")? {
+            CborValue::Bytes(signing_cert) => signing_cert,
+            _ => return Err(Error::new(ReportFormatError::InvalidField("signing_cert"))),
+        };
+        Ok(AttestationReport {
+            report,
+            signature,
+            signing_cert,
+        })
+    }
+}
+
+fn check_version(version: u64) -> Result<()> {
+    if version != ATTESTATION_REPORT_VERSION {
+        return Err(Error::new(ReportFormatError::UnsupportedVersion(version)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Escapes, non-ASCII characters and the payload separator in the report
+    // must survive both formats, or the signature no longer verifies.
+    const REPORT: &str = "{\"id\":\"1\",\"timestamp\":\"2020-01-01T00:00:00.000000\",\
+                          \"isvEnclaveQuoteStatus\":\"OK\",\"advisoryURL\":\"a|b\\/c\",\
+                          \"note\":\"\u{e9}\\u00e9\\n\"}";
+
+    fn report() -> AttestationReport {
+        AttestationReport {
+            report: REPORT.to_owned(),
+            signature: (0..=255).collect(),
+            signing_cert: vec![0x30, 0x82, 0x00, 0x7C, 0xFF],
+        }
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let report = report();
+        let json = report.to_json();
+        let parsed = AttestationReport::from_json(&json).unwrap();
+        assert_eq!(parsed, report);
+        assert_eq!(parsed.report.as_bytes(), REPORT.as_bytes());
+        assert_eq!(parsed.to_json(), json);
+
+        let value: JsonValue = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["version"], ATTESTATION_REPORT_VERSION);
+        assert_eq!(value["signature"], base64::encode(&report.signature));
+    }
+
+    #[test]
+    fn test_cbor_round_trip() {
+        let report = report();
+        let cbor = report.to_cbor().unwrap();
+        let parsed = AttestationReport::from_cbor(&cbor).unwrap();
+        assert_eq!(parsed, report);
+        assert_eq!(parsed.report.as_bytes(), REPORT.as_bytes());
+        assert_eq!(parsed.to_cbor().unwrap(), cbor);
+    }
+
+    #[test]
+    fn test_cross_format_round_trip() {
+        let report = report();
+        let from_json = AttestationReport::from_json(&report.to_json()).unwrap();
+        let from_cbor = AttestationReport::from_cbor(&from_json.to_cbor().unwrap()).unwrap();
+        assert_eq!(from_cbor, report);
+    }
+
+    #[test]
+    fn test_payload() {
+        let signature = base64::encode(&[1u8, 2, 3]);
+        let signing_cert = base64::encode(&[4u8, 5, 6]);
+        let payload = format!("{{\"id\":\"1\"}}|{}|{}", signature, signing_cert);
+        let report = AttestationReport::from_payload(payload.as_bytes()).unwrap();
+        assert_eq!(report.report, "{\"id\":\"1\"}");
+        assert_eq!(report.signature, vec![1, 2, 3]);
+        assert_eq!(report.signing_cert, vec![4, 5, 6]);
+        assert!(AttestationReport::from_payload(b"{}|AQID").is_err());
+    }
+
+    #[test]
+    fn test_from_bytes_detects_format() {
+        let report = report();
+        let json = report.to_json();
+        let cbor = report.to_cbor().unwrap();
+        assert_eq!(
+            AttestationReport::from_bytes(json.as_bytes()).unwrap(),
+            report
+        );
+        assert_eq!(AttestationReport::from_bytes(&cbor).unwrap(), report);
+
+        let signature = base64::encode(&[1u8, 2, 3]);
+        let signing_cert = base64::encode(&[4u8, 5, 6]);
+        let payload = format!("{{\"id\":\"1\"}}|{}|{}", signature, signing_cert);
+        let report = AttestationReport::from_bytes(payload.as_bytes()).unwrap();
+        assert_eq!(report.report, "{\"id\":\"1\"}");
+        assert!(AttestationReport::from_bytes(b"").is_err());
+    }
+
+    #[test]
+    fn test_rejects_bad_schema() {
+        let mut value: JsonValue = serde_json::from_str(&report().to_json()).unwrap();
+        value["version"] = json!(ATTESTATION_REPORT_VERSION + 1);
+        assert!(AttestationReport::from_json(&value.to_string()).is_err());
+        value["version"] = json!(ATTESTATION_REPORT_VERSION);
+        value["signature"] = json!("not base64!");
+        assert!(AttestationReport::from_json(&value.to_string()).is_err());
+        value.as_object_mut().unwrap().remove("signature");
+        assert!(AttestationReport::from_json(&value.to_string()).is_err());
+
+        let mut map = BTreeMap::new();
+        map.insert(CborValue::Text("version".to_owned()), CborValue::Integer(1));
+        map.insert(
+            CborValue::Text("report".to_owned()),
+            CborValue::Bytes(REPORT.as_bytes().to_vec()),
+        );
+        let cbor = serde_cbor::to_vec(&CborValue::Map(map)).unwrap();
+        assert!(AttestationReport::from_cbor(&cbor).is_err());
+        assert!(AttestationReport::from_cbor(b"\x01").is_err());
+    }
+}
@@ -150,11 +150,17 @@ impl IasClient {
 
         debug!("{}", String::from_utf8_lossy(&response));
 
+        Self::parse_report(&response)
+    }
+
+    /// Parses an IAS-compatible attestation report response. The DCAP
+    /// attestation service replies in the same format.
+    pub(crate) fn parse_report(response: &[u8]) -> Result<IasReport> {
         let mut headers = [httparse::EMPTY_HEADER; 16];
         let mut http_response = httparse::Response::new(&mut headers);
         debug!("http_response.parse");
         let header_len = match http_response
-            .parse(&response)
+            .parse(response)
             .map_err(|_| Error::new(AttestationError::IasError))?
         {
             httparse::Status::Complete(s) => s,
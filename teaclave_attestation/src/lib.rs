@@ -30,19 +30,28 @@ pub enum AttestationError {
     IasError,
     #[error("Get quote error")]
     QuoteError,
+    #[error("Dcap attestation service error")]
+    DcapError,
 }
 
 #[macro_use]
 mod cert;
+mod config;
+mod evidence;
 pub mod quote;
 pub mod verifier;
 
+pub use config::AttestationConfig;
+pub use evidence::{AttestationReport, ReportFormatError, ATTESTATION_REPORT_VERSION};
+pub use verifier::{verify_report, VerifiedReport};
+
 use cfg_if::cfg_if;
 cfg_if! {
     if #[cfg(feature = "mesalock_sgx")]  {
         pub mod key;
         mod report;
         mod ias;
+        mod dcap;
         pub use report::IasReport;
     } else {
     }
@@ -21,6 +21,7 @@
 #[cfg(feature = "mesalock_sgx")]
 use std::prelude::v1::*;
 
+use crate::evidence::AttestationReport;
 use anyhow::{Error, Result};
 use chrono::DateTime;
 use rustls;
@@ -33,6 +34,7 @@ use std::time::*;
 #[cfg(feature = "mesalock_sgx")]
 use std::untrusted::time::SystemTimeEx;
 
+use teaclave_utils::clock::{Clock, SystemClock};
 use uuid::Uuid;
 
 type SignatureAlgorithms = &'static [&'static webpki::SignatureAlgorithm];
@@ -60,6 +62,8 @@ pub enum CertVerificationError {
     BadAttnReport,
     #[error("Webpki failure")]
     WebpkiFailure,
+    #[error("Quote with status {0:?} rejected by the quote verifier")]
+    RejectedQuote(SgxQuoteStatus),
 }
 
 pub struct SgxReport {
@@ -73,21 +77,33 @@ pub struct SgxReport {
     pub report_data: [u8; 64],
 }
 
+#[derive(PartialEq, Debug)]
 pub enum SgxQuoteVersion {
+    /// EPID quote
     V1,
+    /// EPID quote
     V2,
+    /// ECDSA quote generated by the DCAP quoting enclave
+    V3,
 }
 
+#[derive(PartialEq, Debug)]
 pub enum SgxQuoteSigType {
     Unlinkable,
     Linkable,
+    /// Attestation key type of V3 quotes
+    EcdsaP256,
+    /// Attestation key type of V3 quotes
+    EcdsaP384,
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub enum SgxQuoteStatus {
     OK,
     GroupOutOfDate,
     ConfigurationNeeded,
+    SwHardeningNeeded,
+    ConfigurationAndSwHardeningNeeded,
     UnknownBadStatus,
 }
 
@@ -97,6 +113,10 @@ impl From<&str> for SgxQuoteStatus {
             "OK" => SgxQuoteStatus::OK,
             "GROUP_OUT_OF_DATE" => SgxQuoteStatus::GroupOutOfDate,
             "CONFIGURATION_NEEDED" => SgxQuoteStatus::ConfigurationNeeded,
+            "SW_HARDENING_NEEDED" => SgxQuoteStatus::SwHardeningNeeded,
+            "CONFIGURATION_AND_SW_HARDENING_NEEDED" => {
+                SgxQuoteStatus::ConfigurationAndSwHardeningNeeded
+            }
             _ => SgxQuoteStatus::UnknownBadStatus,
         }
     }
@@ -133,17 +153,27 @@ impl SgxQuoteBody {
         let version = match u16::from_le_bytes(<[u8; 2]>::try_from(take(2)?).ok()?) {
             1 => SgxQuoteVersion::V1,
             2 => SgxQuoteVersion::V2,
+            3 => SgxQuoteVersion::V3,
             _ => return None,
         };
 
         // off 2, size 2
-        let signature_type = match u16::from_le_bytes(<[u8; 2]>::try_from(take(2)?).ok()?) {
-            0 => SgxQuoteSigType::Unlinkable,
-            1 => SgxQuoteSigType::Linkable,
+        // The signature type of EPID quotes, or the attestation key type of
+        // ECDSA quotes.
+        let signature_type = match (
+            &version,
+            u16::from_le_bytes(<[u8; 2]>::try_from(take(2)?).ok()?),
+        ) {
+            (SgxQuoteVersion::V3, 2) => SgxQuoteSigType::EcdsaP256,
+            (SgxQuoteVersion::V3, 3) => SgxQuoteSigType::EcdsaP384,
+            (SgxQuoteVersion::V3, _) => return None,
+            (_, 0) => SgxQuoteSigType::Unlinkable,
+            (_, 1) => SgxQuoteSigType::Linkable,
             _ => return None,
         };
 
         // off 4, size 4
+        // Reserved in ECDSA quotes
         let gid = u32::from_le_bytes(<[u8; 4]>::try_from(take(4)?).ok()?);
 
         // off 8, size 2
@@ -226,104 +256,150 @@ impl SgxQuoteBody {
     }
 }
 
+pub(crate) fn verify_signing_cert(
+    sig_cert: &webpki::EndEntityCert,
+    ias_report_ca_cert: &[u8],
+) -> Result<()> {
+    let mut ias_ca_stripped = ias_report_ca_cert.to_vec();
+    ias_ca_stripped.retain(|&x| x != 0x0d && x != 0x0a);
+    let head_len = "-----BEGIN CERTIFICATE-----".len();
+    let tail_len = "-----END CERTIFICATE-----".len();
+    let full_len = ias_ca_stripped.len();
+    if full_len < head_len + tail_len {
+        return Err(Error::new(CertVerificationError::InvalidCertFormat));
+    }
+    let ias_ca_core: &[u8] = &ias_ca_stripped[head_len..full_len - tail_len];
+    let ias_cert_dec = base64::decode_config(ias_ca_core, base64::STANDARD)
+        .map_err(|_| CertVerificationError::InvalidCertFormat)?;
+
+    let mut ca_reader = BufReader::new(&ias_report_ca_cert[..]);
+
+    let mut root_store = rustls::RootCertStore::empty();
+    root_store
+        .add_pem_file(&mut ca_reader)
+        .map_err(|_| CertVerificationError::InvalidCertFormat)?;
+
+    let trust_anchors: Vec<webpki::TrustAnchor> = root_store
+        .roots
+        .iter()
+        .map(|cert| cert.to_trust_anchor())
+        .collect();
+
+    let chain: Vec<&[u8]> = vec![&ias_cert_dec];
+
+    let now_func = webpki::Time::try_from(SystemTime::now())
+        .map_err(|_| CertVerificationError::WebpkiFailure)?;
+
+    sig_cert
+        .verify_is_valid_tls_server_cert(
+            SUPPORTED_SIG_ALGS,
+            &webpki::TLSServerTrustAnchors(&trust_anchors),
+            &chain,
+            now_func,
+        )
+        .map_err(|_| CertVerificationError::WebpkiFailure)?;
+
+    Ok(())
+}
+
+/// Extracts the public key of an RA certificate and the attestation evidence
+/// embedded in it.
+pub(crate) fn parse_ra_cert(cert_der: &[u8]) -> Result<(Vec<u8>, Vec<u8>)> {
+    // Before we reach here, Webpki already verifed the cert is properly signed
+    use super::cert::*;
+
+    let x509 = yasna::parse_der(cert_der, |reader| X509::load(reader))
+        .map_err(|_| CertVerificationError::InvalidCertFormat)?;
+
+    let tbs_cert: <TbsCert as Asn1Ty>::ValueTy = x509.0;
+
+    let pub_key: <PubKey as Asn1Ty>::ValueTy = ((((((tbs_cert.1).1).1).1).1).1).0;
+    let pub_k = (pub_key.1).0;
+
+    let sgx_ra_cert_ext: <SgxRaCertExt as Asn1Ty>::ValueTy = (((((((tbs_cert.1).1).1).1).1).1).1).0;
+
+    let payload: Vec<u8> = ((sgx_ra_cert_ext.0).1).0;
+
+    Ok((pub_k.to_bytes(), payload))
+}
+
 pub struct SgxQuote {
+    /// Time since the report was issued, zero if it was issued in the future
+    /// of the local clock.
     pub freshness: Duration,
+    /// Time of the report since the epoch, as given by the attestation
+    /// service.
+    pub issued_at: Duration,
     pub status: SgxQuoteStatus,
     pub body: SgxQuoteBody,
+    /// Index of the root CA certificate which endorsed the report
+    pub root_ca_index: usize,
 }
 
 impl SgxQuote {
     pub fn extract_verified_quote(cert_der: &[u8], ias_report_ca_cert: &[u8]) -> Result<SgxQuote> {
-        // Before we reach here, Webpki already verifed the cert is properly signed
-        use super::cert::*;
-
-        let x509 = yasna::parse_der(cert_der, |reader| X509::load(reader))
-            .map_err(|_| CertVerificationError::InvalidCertFormat)?;
-
-        let tbs_cert: <TbsCert as Asn1Ty>::ValueTy = x509.0;
-
-        let pub_key: <PubKey as Asn1Ty>::ValueTy = ((((((tbs_cert.1).1).1).1).1).1).0;
-        let pub_k = (pub_key.1).0;
-
-        let sgx_ra_cert_ext: <SgxRaCertExt as Asn1Ty>::ValueTy =
-            (((((((tbs_cert.1).1).1).1).1).1).1).0;
-
-        let payload: Vec<u8> = ((sgx_ra_cert_ext.0).1).0;
-
-        // Extract each field
-        let mut iter = payload.split(|x| *x == 0x7C);
-        let attn_report_raw = iter
-            .next()
-            .ok_or_else(|| Error::new(CertVerificationError::InvalidCertFormat))?;
-        let sig_raw = iter
-            .next()
-            .ok_or_else(|| Error::new(CertVerificationError::InvalidCertFormat))?;
-        let sig = base64::decode(&sig_raw)?;
-        let sig_cert_raw = iter
-            .next()
-            .ok_or_else(|| Error::new(CertVerificationError::InvalidCertFormat))?;
-        let sig_cert_dec = base64::decode_config(&sig_cert_raw, base64::STANDARD)?;
-
-        let sig_cert = webpki::EndEntityCert::from(&sig_cert_dec)
-            .map_err(|_| CertVerificationError::InvalidCertFormat)?;
-
-        // Verify if the signing cert is issued by Intel CA
-        let mut ias_ca_stripped = ias_report_ca_cert.to_vec();
-        ias_ca_stripped.retain(|&x| x != 0x0d && x != 0x0a);
-        let head_len = "-----BEGIN CERTIFICATE-----".len();
-        let tail_len = "-----END CERTIFICATE-----".len();
-        let full_len = ias_ca_stripped.len();
-        let ias_ca_core: &[u8] = &ias_ca_stripped[head_len..full_len - tail_len];
-        let ias_cert_dec = base64::decode_config(ias_ca_core, base64::STANDARD)
-            .map_err(|_| CertVerificationError::InvalidCertFormat)?;
-
-        let mut ca_reader = BufReader::new(&ias_report_ca_cert[..]);
-
-        let mut root_store = rustls::RootCertStore::empty();
-        root_store
-            .add_pem_file(&mut ca_reader)
-            .expect("Failed to add CA");
-
-        let trust_anchors: Vec<webpki::TrustAnchor> = root_store
-            .roots
-            .iter()
-            .map(|cert| cert.to_trust_anchor())
-            .collect();
-
-        let chain: Vec<&[u8]> = vec![&ias_cert_dec];
-
-        let now_func = webpki::Time::try_from(SystemTime::now())
-            .map_err(|_| CertVerificationError::WebpkiFailure)?;
-
-        sig_cert
-            .verify_is_valid_tls_server_cert(
-                SUPPORTED_SIG_ALGS,
-                &webpki::TLSServerTrustAnchors(&trust_anchors),
-                &chain,
-                now_func,
-            )
-            .map_err(|_| CertVerificationError::WebpkiFailure)?;
-
-        // Verify the signature against the signing cert
-        sig_cert
-            .verify_signature(&webpki::RSA_PKCS1_2048_8192_SHA256, &attn_report_raw, &sig)
-            .map_err(|_| CertVerificationError::WebpkiFailure)?;
+        Self::extract_verified_quote_with_roots(cert_der, &[ias_report_ca_cert])
+    }
+
+    /// Same as `extract_verified_quote`, accepting reports endorsed by any of
+    /// the root CA certificates, e.g. both the old and new root during a
+    /// rotation.
+    pub fn extract_verified_quote_with_roots(
+        cert_der: &[u8],
+        ias_report_ca_certs: &[&[u8]],
+    ) -> Result<SgxQuote> {
+        let (pub_k, payload) = parse_ra_cert(cert_der)?;
+        let report = AttestationReport::from_payload(&payload)?;
+        let quote = Self::from_report(&report, ias_report_ca_certs)?;
+        quote.verify_key_binding(&pub_k)?;
+        Ok(quote)
+    }
+
+    /// Verifies that the report data of the quote is the public key `pub_k`
+    /// of the RA certificate it was embedded in.
+    pub(crate) fn verify_key_binding(&self, pub_k: &[u8]) -> Result<()> {
+        // According to RFC 5480 `Elliptic Curve Cryptography Subject Public Key Information',
+        // SEC 2.2:
+        // ``The first octet of the OCTET STRING indicates whether the key is
+        // compressed or uncompressed.  The uncompressed form is indicated
+        // by 0x04 and the compressed form is indicated by either 0x02 or
+        // 0x03 (see 2.3.3 in [SEC1]).  The public key MUST be rejected if
+        // any other value is included in the first octet.''
+        //
+        // We only accept the uncompressed form here.
+        match pub_k.split_first() {
+            Some((4, pub_k)) if pub_k == &self.body.report_body.report_data[..] => Ok(()),
+            _ => Err(Error::new(CertVerificationError::BadAttnReport)),
+        }
+    }
+
+    /// Verifies the signature of attestation evidence, e.g. exported from an
+    /// RA certificate, and extracts the quote from the report. Unlike
+    /// `extract_verified_quote`, the report data of the quote is not checked
+    /// against the key of a certificate.
+    pub fn from_report(
+        report: &AttestationReport,
+        ias_report_ca_certs: &[&[u8]],
+    ) -> Result<SgxQuote> {
+        let root_ca_index = report.verify_signature(ias_report_ca_certs)?;
 
         // Verify attestation report
-        let attn_report: Value = serde_json::from_slice(attn_report_raw)
+        let attn_report: Value = serde_json::from_str(&report.report)
             .map_err(|_| CertVerificationError::BadAttnReport)?;
 
-        // 1. Check timestamp is within 24H (90day is recommended by Intel)
-        let quote_freshness = {
+        // 1. Get the timestamp, whose freshness is left to the verifier
+        let quote_issued_at = {
             let time = attn_report["timestamp"]
                 .as_str()
                 .ok_or_else(|| Error::new(CertVerificationError::BadAttnReport))?;
             let time_fixed = String::from(time) + "+0000";
             let date_time = DateTime::parse_from_str(&time_fixed, "%Y-%m-%dT%H:%M:%S%.f%z")?;
-            let ts = date_time.naive_utc();
-            let now = DateTime::<chrono::offset::Utc>::from(SystemTime::now()).naive_utc();
-            u64::try_from((now - ts).num_seconds())?
+            Duration::from_secs(u64::try_from(date_time.timestamp())?)
         };
+        let quote_freshness = SystemClock
+            .now()
+            .checked_sub(quote_issued_at)
+            .unwrap_or_default();
 
         // 2. Get quote status
         let quote_status = {
@@ -344,27 +420,12 @@ impl SgxQuote {
                 .ok_or_else(|| Error::new(CertVerificationError::BadAttnReport))?
         };
 
-        let raw_pub_k = pub_k.to_bytes();
-
-        // According to RFC 5480 `Elliptic Curve Cryptography Subject Public Key Information',
-        // SEC 2.2:
-        // ``The first octet of the OCTET STRING indicates whether the key is
-        // compressed or uncompressed.  The uncompressed form is indicated
-        // by 0x04 and the compressed form is indicated by either 0x02 or
-        // 0x03 (see 2.3.3 in [SEC1]).  The public key MUST be rejected if
-        // any other value is included in the first octet.''
-        //
-        // We only accept the uncompressed form here.
-        let is_uncompressed = raw_pub_k[0] == 4;
-        let pub_k = &raw_pub_k.as_slice()[1..];
-        if !is_uncompressed || pub_k != &quote_body.report_body.report_data[..] {
-            return Err(Error::new(CertVerificationError::BadAttnReport));
-        }
-
         Ok(SgxQuote {
-            freshness: std::time::Duration::from_secs(quote_freshness),
+            freshness: quote_freshness,
+            issued_at: quote_issued_at,
             status: quote_status,
             body: quote_body,
+            root_ca_index,
         })
     }
 }
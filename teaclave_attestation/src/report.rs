@@ -15,7 +15,9 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use crate::dcap::DcapClient;
 use crate::ias::IasClient;
+use crate::AttestationConfig;
 use crate::AttestationError;
 use anyhow::Error;
 use anyhow::Result;
@@ -55,6 +57,23 @@ extern "C" {
         p_quote: *mut u8,
         quote_size: u32,
     ) -> sgx_status_t;
+
+    fn ocall_sgx_qe_get_target_info(
+        p_retval: *mut sgx_status_t,
+        p_target_info: *mut sgx_target_info_t,
+    ) -> sgx_status_t;
+
+    fn ocall_sgx_qe_get_quote_size(
+        p_retval: *mut sgx_status_t,
+        p_quote_size: *mut u32,
+    ) -> sgx_status_t;
+
+    fn ocall_sgx_qe_get_quote(
+        p_retval: *mut sgx_status_t,
+        p_report: *const sgx_report_t,
+        p_quote: *mut u8,
+        quote_size: u32,
+    ) -> sgx_status_t;
 }
 
 #[derive(Default)]
@@ -65,6 +84,28 @@ pub struct IasReport {
 }
 
 impl IasReport {
+    /// Generates a quote with the scheme selected in `config` and gets it
+    /// endorsed by the corresponding attestation service.
+    pub fn from_config(pub_k: sgx_ec256_public_t, config: &AttestationConfig) -> Result<Self> {
+        match config {
+            AttestationConfig::Epid {
+                ias_key,
+                ias_spid,
+                production,
+            } => Self::new(pub_k, ias_key, ias_spid, *production),
+            AttestationConfig::Dcap { pccs_url } => Self::new_with_dcap(pub_k, pccs_url),
+        }
+    }
+
+    pub fn new_with_dcap(pub_k: sgx_ec256_public_t, pccs_url: &str) -> Result<Self> {
+        let target_info = Self::init_dcap_quote()?;
+        let report = Self::create_report(pub_k, target_info)?;
+        let quote = Self::get_dcap_quote(report)?;
+        let mut dcap_client = DcapClient::new(pccs_url)?;
+        let report = dcap_client.get_report(&quote)?;
+        Ok(report)
+    }
+
     pub fn new(
         pub_k: sgx_ec256_public_t,
         ias_key: &str,
@@ -95,6 +136,47 @@ impl IasReport {
         }
     }
 
+    fn init_dcap_quote() -> Result<sgx_target_info_t> {
+        debug!("init_dcap_quote");
+        let mut ti: sgx_target_info_t = sgx_target_info_t::default();
+        let mut rt: sgx_status_t = sgx_status_t::SGX_ERROR_UNEXPECTED;
+
+        let res = unsafe { ocall_sgx_qe_get_target_info(&mut rt as _, &mut ti as _) };
+
+        if res != sgx_status_t::SGX_SUCCESS || rt != sgx_status_t::SGX_SUCCESS {
+            Err(Error::new(AttestationError::OCallError))
+        } else {
+            Ok(ti)
+        }
+    }
+
+    fn get_dcap_quote(report: sgx_report_t) -> Result<Vec<u8>> {
+        let mut rt: sgx_status_t = sgx_status_t::SGX_ERROR_UNEXPECTED;
+        let mut quote_len: u32 = 0;
+
+        let res = unsafe { ocall_sgx_qe_get_quote_size(&mut rt as _, &mut quote_len as _) };
+
+        if res != sgx_status_t::SGX_SUCCESS || rt != sgx_status_t::SGX_SUCCESS {
+            return Err(Error::new(AttestationError::OCallError));
+        }
+
+        let mut quote = vec![0; quote_len as usize];
+
+        debug!("ocall_sgx_qe_get_quote");
+        let res = unsafe {
+            ocall_sgx_qe_get_quote(&mut rt as _, &report as _, quote.as_mut_ptr(), quote_len)
+        };
+
+        if res != sgx_status_t::SGX_SUCCESS || rt != sgx_status_t::SGX_SUCCESS {
+            return Err(Error::new(AttestationError::OCallError));
+        }
+
+        // Unlike EPID, there is no QE report to check here. The quote is
+        // signed by the attestation key of the quoting enclave and its
+        // certification chain is verified by the attestation service.
+        Ok(quote)
+    }
+
     fn create_report(
         pub_k: sgx_ec256_public_t,
         target_info: sgx_target_info_t,
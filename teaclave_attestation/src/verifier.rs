@@ -15,10 +15,17 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use crate::quote::SgxQuote;
+use crate::evidence::AttestationReport;
+use crate::quote::{
+    self, CertVerificationError, SgxQuote, SgxQuoteSigType, SgxQuoteStatus, SgxQuoteVersion,
+};
+use anyhow::{Error, Result};
+use log::debug;
 use std::hash::{Hash, Hasher};
+use std::time::Duration;
 use std::vec::Vec;
 use teaclave_config::build_config::BUILD_CONFIG;
+use teaclave_utils::clock::{self, Clock, SystemClock};
 use teaclave_utils::EnclaveMeasurement;
 
 #[derive(Clone)]
@@ -26,6 +33,18 @@ pub struct EnclaveAttr {
     pub measures: Vec<EnclaveMeasurement>,
 }
 
+impl EnclaveAttr {
+    /// Whether an enclave measured as `mr_enclave` and `mr_signer` is one of
+    /// the accepted enclaves. The MRENCLAVE is not tested in test mode since
+    /// we have a dedicated test enclave not known to production enclaves. It
+    /// is signed by the same key though, so the MRSIGNER is still checked.
+    pub fn accepts(&self, mr_enclave: &[u8], mr_signer: &[u8]) -> bool {
+        self.measures.iter().any(|m| {
+            m.mr_signer[..] == *mr_signer && (cfg!(test_mode) || m.mr_enclave[..] == *mr_enclave)
+        })
+    }
+}
+
 impl PartialEq for EnclaveAttr {
     fn eq(&self, other: &EnclaveAttr) -> bool {
         self.measures == other.measures
@@ -41,15 +60,33 @@ impl Hash for EnclaveAttr {
     }
 }
 
+/// Decides whether the quote of an attested peer is acceptable, after its
+/// signature and measurements have been verified. A plain function is used so
+/// that verifiers can be compared and hashed as part of the TLS config caches.
+pub type QuoteVerifier = fn(&SgxQuote) -> bool;
+
 #[derive(Clone)]
 pub struct SgxQuoteVerifier {
     pub enclave_attr: EnclaveAttr,
-    pub verifier: fn(&SgxQuote) -> bool,
+    pub verifier: QuoteVerifier,
+    /// Reports issued longer ago are rejected. Not checked if unset.
+    pub max_report_age: Option<Duration>,
+    /// How far the local clock and the clock of the attestation service may
+    /// disagree. Reports issued up to that far in the future are accepted, and
+    /// up to that much older than `max_report_age`.
+    pub clock_skew: Duration,
+    /// Root CA certificates of the attestation service whose reports are
+    /// accepted. Those of the build config if unset.
+    pub root_ca_certs: Option<Vec<Vec<u8>>>,
 }
 
 impl PartialEq for SgxQuoteVerifier {
     fn eq(&self, other: &SgxQuoteVerifier) -> bool {
-        self.verifier as usize == other.verifier as usize && self.enclave_attr == other.enclave_attr
+        self.verifier as usize == other.verifier as usize
+            && self.enclave_attr == other.enclave_attr
+            && self.max_report_age == other.max_report_age
+            && self.clock_skew == other.clock_skew
+            && self.root_ca_certs == other.root_ca_certs
     }
 }
 
@@ -59,51 +96,213 @@ impl Hash for SgxQuoteVerifier {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.enclave_attr.hash(state);
         (self.verifier as usize).hash(state);
+        self.max_report_age.hash(state);
+        self.clock_skew.hash(state);
+        self.root_ca_certs.hash(state);
+    }
+}
+
+/// Accepts quotes of platforms which may need a TCB recovery or
+/// configuration.
+pub fn universal_quote_verifier(quote: &SgxQuote) -> bool {
+    match quote.body.version {
+        // EPID quotes verified by IAS
+        SgxQuoteVersion::V1 | SgxQuoteVersion::V2 => match quote.status {
+            SgxQuoteStatus::OK
+            | SgxQuoteStatus::GroupOutOfDate
+            | SgxQuoteStatus::ConfigurationNeeded => true,
+            _ => false,
+        },
+        // ECDSA quotes verified by the DCAP attestation service
+        SgxQuoteVersion::V3 => match quote.body.signature_type {
+            SgxQuoteSigType::EcdsaP256 | SgxQuoteSigType::EcdsaP384 => {
+                quote.status != SgxQuoteStatus::UnknownBadStatus
+            }
+            _ => false,
+        },
+    }
+}
+
+/// Only accepts quotes with an OK status and an ISV SVN no lower than the
+/// `quote_policy.min_isv_svn` of the build config.
+pub fn strict_quote_verifier(quote: &SgxQuote) -> bool {
+    verify_strict_policy(quote, BUILD_CONFIG.quote_policy.min_isv_svn)
+}
+
+fn verify_strict_policy(quote: &SgxQuote, min_isv_svn: u16) -> bool {
+    if quote.status != SgxQuoteStatus::OK {
+        debug!(
+            "Quote rejected by the strict verifier, status {:?}",
+            quote.status
+        );
+        return false;
+    }
+    if quote.body.report_body.isv_svn < min_isv_svn {
+        debug!(
+            "Quote rejected by the strict verifier, ISV SVN {} < {}",
+            quote.body.report_body.isv_svn, min_isv_svn
+        );
+        return false;
+    }
+    universal_quote_verifier(quote)
+}
+
+/// The quote verifier selected by `quote_policy.verifier` of the build config.
+pub fn default_quote_verifier() -> QuoteVerifier {
+    if BUILD_CONFIG.quote_policy.strict {
+        strict_quote_verifier
+    } else {
+        universal_quote_verifier
     }
 }
 
-fn universal_quote_verifier(quote: &SgxQuote) -> bool {
-    quote.status != crate::quote::SgxQuoteStatus::UnknownBadStatus
+/// An attestation report accepted by `verify_report`.
+pub struct VerifiedReport {
+    /// Measurement of the attested enclave
+    pub measurement: EnclaveMeasurement,
+    /// Status of the TCB of its platform, as given by the attestation service
+    pub tcb_status: SgxQuoteStatus,
+    pub quote: SgxQuote,
+}
+
+/// Verifies an attestation report without a handshake, e.g. one captured
+/// from an enclave, to check the root CA and quote policy of a deployment
+/// before it is rolled out. The report is the attestation evidence exported
+/// to JSON or CBOR, or as embedded in an RA certificate. It must be endorsed
+/// by one of `root_ca_certs` (PEM), and its quote accepted by `verifier`.
+///
+/// Which enclave was attested and how long ago is left to the caller, who
+/// has the measurement and the time of the report.
+pub fn verify_report(
+    report_bytes: &[u8],
+    root_ca_certs: &[&[u8]],
+    verifier: QuoteVerifier,
+) -> Result<VerifiedReport> {
+    let report = AttestationReport::from_bytes(report_bytes)?;
+    let quote = SgxQuote::from_report(&report, root_ca_certs)?;
+    debug!("Report endorsed by root CA #{}", quote.root_ca_index);
+    if !verifier(&quote) {
+        return Err(Error::new(CertVerificationError::RejectedQuote(
+            quote.status,
+        )));
+    }
+    let report_body = &quote.body.report_body;
+    Ok(VerifiedReport {
+        measurement: EnclaveMeasurement::new(report_body.mr_enclave, report_body.mr_signer),
+        tcb_status: quote.status.clone(),
+        quote,
+    })
 }
 
 impl SgxQuoteVerifier {
     pub fn new(enclave_attr: EnclaveAttr) -> Self {
+        Self::with_verifier(enclave_attr, default_quote_verifier())
+    }
+
+    pub fn with_verifier(enclave_attr: EnclaveAttr, verifier: QuoteVerifier) -> Self {
         Self {
             enclave_attr,
-            verifier: universal_quote_verifier,
+            verifier,
+            max_report_age: None,
+            clock_skew: Duration::from_secs(0),
+            root_ca_certs: None,
         }
     }
 
-    fn verify_measures(&self, quote: &SgxQuote) -> bool {
-        let this_mr_signer = quote.body.report_body.mr_signer;
-        let this_mr_enclave = quote.body.report_body.mr_enclave;
+    /// Only accepts the reports endorsed by one of `certs` (PEM), instead of
+    /// the root CA certificates of the build config.
+    pub fn root_ca_certs(mut self, certs: Vec<Vec<u8>>) -> Self {
+        self.root_ca_certs = Some(certs);
+        self
+    }
+
+    /// Rejects the reports issued more than `age` ago.
+    pub fn max_report_age(mut self, age: Duration) -> Self {
+        self.max_report_age = Some(age);
+        self
+    }
+
+    /// Tolerates a disagreement of up to `skew` between the local clock and
+    /// the clock of the attestation service.
+    pub fn clock_skew(mut self, skew: Duration) -> Self {
+        self.clock_skew = skew;
+        self
+    }
+
+    /// Age of the report of `quote` at `now`, `None` if the report is not
+    /// fresh.
+    fn verify_freshness(&self, quote: &SgxQuote, now: Duration) -> Option<Duration> {
+        let age = match clock::elapsed_within_skew(now, quote.issued_at, self.clock_skew) {
+            Some(age) => age,
+            None => {
+                debug!(
+                    "Peer report rejected, issued {}s in the future",
+                    (quote.issued_at - now).as_secs()
+                );
+                return None;
+            }
+        };
+        match self.max_report_age {
+            Some(max_age) if age > max_age + self.clock_skew => {
+                debug!(
+                    "Peer report rejected, issued {}s ago, over the maximum of {}s",
+                    age.as_secs(),
+                    max_age.as_secs()
+                );
+                None
+            }
+            _ => Some(age),
+        }
+    }
 
+    fn verify_measures(&self, quote: &SgxQuote) -> bool {
+        let report_body = &quote.body.report_body;
         self.enclave_attr
-            .measures
-            .iter()
-            .any(|m| m.mr_signer == this_mr_signer && m.mr_enclave == this_mr_enclave)
+            .accepts(&report_body.mr_enclave, &report_body.mr_signer)
+    }
+
+    /// Verifies the RA certificate presented by a peer during the handshake,
+    /// on both the client and the server side. The quote embedded in the
+    /// certificate must be endorsed by the attestation service, bound to the
+    /// key of the certificate, and taken from one of the accepted enclaves.
+    pub fn verify_cert(&self, cert_der: &[u8]) -> bool {
+        self.verify_cert_age(cert_der).is_some()
     }
 
-    fn verify_cert(&self, cert_der: &[u8]) -> bool {
+    /// Same as `verify_cert`, returning how long ago the report of an
+    /// accepted certificate was issued.
+    pub fn verify_cert_age(&self, cert_der: &[u8]) -> Option<Duration> {
         if cfg!(sgx_sim) {
-            return true;
+            return Some(Duration::from_secs(0));
         }
 
-        let quote = match SgxQuote::extract_verified_quote(&cert_der, BUILD_CONFIG.ias_root_ca_cert)
-        {
-            Ok(quote) => quote,
-            Err(_) => {
-                return false;
+        let roots: Vec<&[u8]> = match self.root_ca_certs {
+            Some(ref certs) => certs.iter().map(|cert| cert.as_slice()).collect(),
+            None => BUILD_CONFIG.ias_root_ca_certs.to_vec(),
+        };
+        let verified = quote::parse_ra_cert(cert_der).and_then(|(pub_k, payload)| {
+            let verified = verify_report(&payload, &roots, self.verifier)?;
+            verified.quote.verify_key_binding(&pub_k)?;
+            Ok(verified)
+        });
+        let quote = match verified {
+            Ok(verified) => verified.quote,
+            Err(e) => {
+                debug!("Peer presented no acceptable attestation report: {}", e);
+                return None;
             }
         };
 
-        // Enclave measures are not tested in test mode since we have
-        // a dedicated test enclave not known to production enclaves
-        if cfg!(test_mode) {
-            return (self.verifier)(&quote);
+        if !self.verify_measures(&quote) {
+            debug!(
+                "Peer enclave is not accepted, MRENCLAVE {:02x?}, MRSIGNER {:02x?}",
+                quote.body.report_body.mr_enclave, quote.body.report_body.mr_signer
+            );
+            return None;
         }
-
-        self.verify_measures(&quote) && (self.verifier)(&quote)
+        let age = self.verify_freshness(&quote, SystemClock.now())?;
+        debug!("Peer attested with a report issued {}s ago", age.as_secs());
+        Some(age)
     }
 }
 
@@ -151,3 +350,140 @@ impl rustls::ClientCertVerifier for SgxQuoteVerifier {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quote::{SgxQuoteBody, SgxReport};
+    use std::time::Duration;
+    use teaclave_utils::clock::ManualClock;
+    use uuid::Uuid;
+
+    const NOW: Duration = Duration::from_secs(1_600_000_000);
+
+    fn epid_quote(status: SgxQuoteStatus, isv_svn: u16) -> SgxQuote {
+        SgxQuote {
+            freshness: Duration::from_secs(0),
+            issued_at: NOW,
+            status,
+            body: SgxQuoteBody {
+                version: SgxQuoteVersion::V2,
+                signature_type: SgxQuoteSigType::Linkable,
+                gid: 0,
+                isv_svn_qe: 0,
+                isv_svn_pce: 0,
+                qe_vendor_id: Uuid::nil(),
+                user_data: [0; 20],
+                report_body: SgxReport {
+                    cpu_svn: [0; 16],
+                    misc_select: 0,
+                    attributes: [0; 16],
+                    mr_enclave: [0; 32],
+                    mr_signer: [0; 32],
+                    isv_prod_id: 0,
+                    isv_svn,
+                    report_data: [0; 64],
+                },
+            },
+            root_ca_index: 0,
+        }
+    }
+
+    #[test]
+    fn test_universal_quote_verifier() {
+        assert!(universal_quote_verifier(&epid_quote(SgxQuoteStatus::OK, 0)));
+        assert!(universal_quote_verifier(&epid_quote(
+            SgxQuoteStatus::GroupOutOfDate,
+            0
+        )));
+        assert!(!universal_quote_verifier(&epid_quote(
+            SgxQuoteStatus::UnknownBadStatus,
+            0
+        )));
+    }
+
+    #[test]
+    fn test_strict_quote_verifier() {
+        assert!(verify_strict_policy(&epid_quote(SgxQuoteStatus::OK, 2), 2));
+        assert!(!verify_strict_policy(&epid_quote(SgxQuoteStatus::OK, 1), 2));
+        assert!(!verify_strict_policy(
+            &epid_quote(SgxQuoteStatus::GroupOutOfDate, 2),
+            2
+        ));
+        assert!(!verify_strict_policy(
+            &epid_quote(SgxQuoteStatus::ConfigurationNeeded, 2),
+            2
+        ));
+
+        let min_isv_svn = BUILD_CONFIG.quote_policy.min_isv_svn;
+        assert!(strict_quote_verifier(&epid_quote(
+            SgxQuoteStatus::OK,
+            min_isv_svn
+        )));
+        assert!(!strict_quote_verifier(&epid_quote(
+            SgxQuoteStatus::GroupOutOfDate,
+            min_isv_svn
+        )));
+    }
+
+    #[test]
+    fn test_old_report_is_rejected() {
+        let enclave_attr = EnclaveAttr { measures: vec![] };
+        let verifier = SgxQuoteVerifier::new(enclave_attr.clone());
+        let clock = ManualClock::new(NOW + Duration::from_secs(30 * 86400));
+        let quote = epid_quote(SgxQuoteStatus::OK, 0);
+        assert!(verifier.verify_freshness(&quote, clock.now()).is_some());
+
+        let verifier = verifier.max_report_age(Duration::from_secs(86400));
+        assert!(verifier.verify_freshness(&quote, clock.now()).is_none());
+        assert!(verifier.verify_freshness(&quote, NOW).is_some());
+        assert!(verifier != SgxQuoteVerifier::new(enclave_attr));
+    }
+
+    #[test]
+    fn test_freshness_tolerates_clock_skew() {
+        let enclave_attr = EnclaveAttr { measures: vec![] };
+        let max_age = Duration::from_secs(86400);
+        let skew = Duration::from_secs(60);
+        let strict = SgxQuoteVerifier::new(enclave_attr.clone()).max_report_age(max_age);
+        let tolerant = strict.clone().clock_skew(skew);
+        assert!(strict != tolerant);
+        let quote = epid_quote(SgxQuoteStatus::OK, 0);
+
+        // A report from the future of the local clock is as fresh as one
+        // issued now, within the skew
+        let clock = ManualClock::new(NOW - skew);
+        assert_eq!(
+            tolerant.verify_freshness(&quote, clock.now()),
+            Some(Duration::from_secs(0))
+        );
+        assert!(strict.verify_freshness(&quote, clock.now()).is_none());
+        clock.set(NOW - skew - Duration::from_secs(1));
+        assert!(tolerant.verify_freshness(&quote, clock.now()).is_none());
+
+        // An old report may exceed the maximum age by the skew
+        clock.set(NOW + max_age);
+        assert_eq!(strict.verify_freshness(&quote, clock.now()), Some(max_age));
+        clock.advance(skew);
+        assert!(strict.verify_freshness(&quote, clock.now()).is_none());
+        assert_eq!(
+            tolerant.verify_freshness(&quote, clock.now()),
+            Some(max_age + skew)
+        );
+        clock.advance(Duration::from_secs(1));
+        assert!(tolerant.verify_freshness(&quote, clock.now()).is_none());
+    }
+
+    #[test]
+    fn test_unendorsed_report_is_rejected() {
+        let report = AttestationReport {
+            report: "{\"isvEnclaveQuoteStatus\":\"OK\"}".to_owned(),
+            signature: vec![0; 256],
+            signing_cert: vec![0x30, 0x82, 0x00, 0x00],
+        };
+        let roots = BUILD_CONFIG.ias_root_ca_certs;
+        let json = report.to_json();
+        assert!(verify_report(json.as_bytes(), roots, universal_quote_verifier).is_err());
+        assert!(verify_report(b"not a report", roots, universal_quote_verifier).is_err());
+    }
+}
@@ -23,7 +23,9 @@ use serde::Serialize;
 
 use mesatee_core::ipc::channel::ECallChannel;
 use mesatee_core::ipc::protos::ecall::{FinalizeEnclaveInput, FinalizeEnclaveOutput};
+use mesatee_core::ipc::protos::ecall::{GetMetricsInput, GetMetricsOutput};
 use mesatee_core::ipc::protos::ecall::{InitEnclaveInput, InitEnclaveOutput};
+use mesatee_core::ipc::protos::ecall::{RunMaintenanceInput, RunMaintenanceOutput};
 use mesatee_core::ipc::protos::ECallCommand;
 use mesatee_core::ipc::IpcSender;
 use mesatee_core::Result;
@@ -72,6 +74,25 @@ impl TeeBinder {
         channel.invoke::<U, V>(cmd, args_info)
     }
 
+    /// Returns the metrics of the enclave in the Prometheus text format.
+    pub fn metrics(&self) -> Result<String> {
+        let output = self.invoke::<GetMetricsInput, GetMetricsOutput>(
+            ECallCommand::GetMetrics.into(),
+            GetMetricsInput::default(),
+        )?;
+        Ok(output.metrics)
+    }
+
+    /// Runs the periodic housekeeping of the enclave, if it has any, with
+    /// the less frequent part of it when `sweep` is set.
+    pub fn run_maintenance(&self, sweep: bool) -> Result<()> {
+        let _ = self.invoke::<RunMaintenanceInput, RunMaintenanceOutput>(
+            ECallCommand::RunMaintenance.into(),
+            RunMaintenanceInput { sweep },
+        )?;
+        Ok(())
+    }
+
     pub fn finalize(&self) {
         let args_info = FinalizeEnclaveInput::default();
         match self.invoke::<FinalizeEnclaveInput, FinalizeEnclaveOutput>(
@@ -16,7 +16,9 @@
 // under the License.
 
 use sgx_types::*;
+use std::ffi::CStr;
 use std::net::TcpStream;
+use std::os::raw::c_char;
 use std::os::unix::io::IntoRawFd;
 
 #[link(name = "sgx_uae_service")]
@@ -43,6 +45,19 @@ extern "C" {
     ) -> sgx_status_t;
 }
 
+// The DCAP quote library is only linked in hardware mode.
+#[cfg(not(sgx_sim))]
+#[link(name = "sgx_dcap_ql")]
+extern "C" {
+    fn sgx_qe_get_target_info(p_qe_target_info: *mut sgx_target_info_t) -> sgx_quote3_error_t;
+    fn sgx_qe_get_quote_size(p_quote_size: *mut u32) -> sgx_quote3_error_t;
+    fn sgx_qe_get_quote(
+        p_app_report: *const sgx_report_t,
+        quote_size: u32,
+        p_quote: *mut u8,
+    ) -> sgx_quote3_error_t;
+}
+
 #[no_mangle]
 pub extern "C" fn ocall_sgx_get_ias_socket() -> i32 {
     let ias_addr = "api.trustedservices.intel.com:443";
@@ -95,3 +110,74 @@ pub extern "C" fn ocall_sgx_get_quote(
         )
     }
 }
+
+#[no_mangle]
+pub extern "C" fn ocall_sgx_get_dcap_socket(p_addr: *const c_char) -> i32 {
+    let dcap_addr = match unsafe { CStr::from_ptr(p_addr) }.to_str() {
+        Ok(addr) => addr,
+        Err(_) => return -1,
+    };
+    match TcpStream::connect(dcap_addr) {
+        Ok(socket) => socket.into_raw_fd(),
+        Err(_) => -1,
+    }
+}
+
+#[cfg(not(sgx_sim))]
+fn quote3_error_to_status(ret: sgx_quote3_error_t) -> sgx_status_t {
+    if ret == sgx_quote3_error_t::SGX_QL_SUCCESS {
+        sgx_status_t::SGX_SUCCESS
+    } else {
+        error!("DCAP quote library error: {:?}", ret);
+        sgx_status_t::SGX_ERROR_UNEXPECTED
+    }
+}
+
+#[cfg(not(sgx_sim))]
+#[no_mangle]
+pub extern "C" fn ocall_sgx_qe_get_target_info(
+    p_target_info: *mut sgx_target_info_t,
+) -> sgx_status_t {
+    quote3_error_to_status(unsafe { sgx_qe_get_target_info(p_target_info) })
+}
+
+#[cfg(not(sgx_sim))]
+#[no_mangle]
+pub extern "C" fn ocall_sgx_qe_get_quote_size(p_quote_size: *mut u32) -> sgx_status_t {
+    quote3_error_to_status(unsafe { sgx_qe_get_quote_size(p_quote_size) })
+}
+
+#[cfg(not(sgx_sim))]
+#[no_mangle]
+pub extern "C" fn ocall_sgx_qe_get_quote(
+    p_report: *const sgx_report_t,
+    p_quote: *mut u8,
+    quote_size: u32,
+) -> sgx_status_t {
+    quote3_error_to_status(unsafe { sgx_qe_get_quote(p_report, quote_size, p_quote) })
+}
+
+// There is no quoting enclave in simulation mode.
+#[cfg(sgx_sim)]
+#[no_mangle]
+pub extern "C" fn ocall_sgx_qe_get_target_info(
+    _p_target_info: *mut sgx_target_info_t,
+) -> sgx_status_t {
+    sgx_status_t::SGX_ERROR_UNEXPECTED
+}
+
+#[cfg(sgx_sim)]
+#[no_mangle]
+pub extern "C" fn ocall_sgx_qe_get_quote_size(_p_quote_size: *mut u32) -> sgx_status_t {
+    sgx_status_t::SGX_ERROR_UNEXPECTED
+}
+
+#[cfg(sgx_sim)]
+#[no_mangle]
+pub extern "C" fn ocall_sgx_qe_get_quote(
+    _p_report: *const sgx_report_t,
+    _p_quote: *mut u8,
+    _quote_size: u32,
+) -> sgx_status_t {
+    sgx_status_t::SGX_ERROR_UNEXPECTED
+}
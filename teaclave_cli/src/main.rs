@@ -66,6 +66,10 @@ struct AuditOpt {
     #[structopt(short = "c", long, required = true, name = "ENCLAVE_INFO_FILE")]
     /// Path to Enclave info file.
     enclave_info: path::PathBuf,
+
+    #[structopt(short = "t", long)]
+    /// Minimum number of valid auditor signatures. All auditors by default.
+    threshold: Option<usize>,
 }
 
 #[derive(Debug, StructOpt)]
@@ -89,6 +93,10 @@ struct ConnectOpt {
     #[structopt(short = "c", long, required = true, name = "ENCLAVE_INFO_FILE")]
     /// Path to Enclave info file.
     enclave_info: path::PathBuf,
+
+    #[structopt(short = "t", long)]
+    /// Minimum number of valid auditor signatures. All auditors by default.
+    threshold: Option<usize>,
 }
 
 #[derive(Debug, StructOpt)]
@@ -140,7 +148,7 @@ generate_runner_for!(fns, InvokeTaskRequest, InvokeTaskResponse);
 
 fn connect(args: ConnectOpt) -> Result<(), failure::Error> {
     let enclave_info_content = fs::read_to_string(&args.enclave_info)?;
-    let enclave_info = teaclave_utils::load_enclave_info(&enclave_info_content);
+    let enclave_info = teaclave_utils::load_enclave_info(&enclave_info_content)?;
 
     let reader: Box<dyn Read> = match args.input {
         Some(i) => Box::new(io::BufReader::new(fs::File::open(i)?)),
@@ -171,7 +179,21 @@ fn audit(args: AuditOpt) -> Result<(), failure::Error> {
         signatures.push(signature);
     }
 
-    if teaclave_utils::verify_enclave_info(enclave_info_content.as_bytes(), &keys, &signatures) {
+    let results = teaclave_utils::verify_enclave_info_verbose(
+        enclave_info_content.as_bytes(),
+        &keys,
+        &signatures,
+    );
+    for (index, result) in &results {
+        match result {
+            Ok(_) => println!("Auditor #{}: verified", index),
+            Err(e) => println!("Auditor #{}: {}", index, e),
+        }
+    }
+
+    let threshold = args.threshold.unwrap_or_else(|| keys.len());
+    let verified = results.iter().filter(|(_, result)| result.is_ok()).count();
+    if verified >= threshold {
         println!("Enclave info is successfully verified.");
         Ok(())
     } else {
@@ -14,6 +14,11 @@ fn main() {
     let dest_file = Path::new(&out_dir).join("build_config.rs");
     println!("cargo:rerun-if-changed=build.config.toml");
     println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=MESATEE_BUILD_ID");
+    println!("cargo:rerun-if-env-changed=SGX_SDK_VERSION");
+    let crate_version = env::var("CARGO_PKG_VERSION").expect("$CARGO_PKG_VERSION not set");
+    let build_id = env::var("MESATEE_BUILD_ID").unwrap_or_else(|_| "unknown".to_string());
+    let sgx_sdk_version = env::var("SGX_SDK_VERSION").unwrap_or_else(|_| "unknown".to_string());
     let c = Command::new("cargo")
         .args(&[
             "run",
@@ -22,6 +27,9 @@ fn main() {
             "--",
             "build.config.toml",
             &dest_file.to_string_lossy(),
+            &crate_version,
+            &build_id,
+            &sgx_sdk_version,
         ])
         .output()
         .expect("Cannot generate build_config.rs");
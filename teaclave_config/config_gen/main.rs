@@ -10,8 +10,17 @@ use std::path::PathBuf;
 #[derive(Serialize, Deserialize)]
 struct BuildConfigToml {
     ias_root_ca_cert: ConfigSource,
+    extra_ias_root_ca_certs: Option<Vec<ConfigSource>>,
     auditor_public_keys: Vec<ConfigSource>,
+    auditor_signature_threshold: Option<usize>,
     rpc_max_message_size: u32,
+    quote_policy: Option<QuotePolicyToml>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct QuotePolicyToml {
+    verifier: Option<String>,
+    min_isv_svn: Option<u16>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -41,11 +50,25 @@ fn main() {
     if args.len() < 3 {
         panic!("Please specify the path of build config toml and output path.");
     }
+    // Version info is optional, for builds outside of the CMake build
+    let version_arg = |index: usize| args.get(index).map(|s| s.as_str()).unwrap_or("unknown");
+    let crate_version = version_arg(3);
+    let build_id = version_arg(4);
+    let sgx_sdk_version = version_arg(5);
     let contents = fs::read_to_string(&args[1]).expect("Something went wrong reading the file");
     let config: BuildConfigToml = toml::from_str(&contents).expect("Failed to parse the config.");
 
     let ias_root_ca_cert = display_config_source(&config.ias_root_ca_cert);
 
+    // The primary root CA is always the first accepted one
+    let extra_ias_root_ca_certs = config.extra_ias_root_ca_certs.unwrap_or_default();
+    let mut ias_root_ca_certs = String::new();
+    ias_root_ca_certs.push_str(&format!("&[{}, ", ias_root_ca_cert));
+    for cert in &extra_ias_root_ca_certs {
+        ias_root_ca_certs.push_str(&format!("{}, ", display_config_source(cert)));
+    }
+    ias_root_ca_certs.push_str("]");
+
     let mut auditor_public_keys = String::new();
     auditor_public_keys.push_str("&[");
     for key in &config.auditor_public_keys {
@@ -54,25 +77,76 @@ fn main() {
     }
     auditor_public_keys.push_str("]");
 
+    // All auditors are required to sign the enclave info by default
+    let auditor_signature_threshold = config
+        .auditor_signature_threshold
+        .unwrap_or_else(|| config.auditor_public_keys.len());
+    if auditor_signature_threshold == 0
+        || auditor_signature_threshold > config.auditor_public_keys.len()
+    {
+        panic!(
+            "Invalid auditor signature threshold: {} of {} auditors.",
+            auditor_signature_threshold,
+            config.auditor_public_keys.len()
+        );
+    }
+
+    let quote_policy = config.quote_policy.unwrap_or_default();
+    let strict_quote_verifier = match quote_policy.verifier.as_ref().map(|v| v.as_str()) {
+        None | Some("universal") => false,
+        Some("strict") => true,
+        Some(v) => panic!("Invalid quote verifier: {}.", v),
+    };
+    let min_isv_svn = quote_policy.min_isv_svn.unwrap_or(0);
+
     let mut build_config_generated = String::new();
     build_config_generated.push_str(&format!(
         r#"
     #[derive(Debug)]
     pub struct BuildConfig<'a> {{
         pub ias_root_ca_cert: &'a [u8],
+        pub ias_root_ca_certs: &'a [&'a [u8];{}],
         pub auditor_public_keys: &'a [&'a [u8];{}],
+        pub auditor_signature_threshold: usize,
         pub rpc_max_message_size: u64,
+        pub quote_policy: QuotePolicy,
+        pub crate_version: &'a str,
+        pub build_id: &'a str,
+        pub sgx_sdk_version: &'a str,
+    }}
+
+    #[derive(Debug)]
+    pub struct QuotePolicy {{
+        pub strict: bool,
+        pub min_isv_svn: u16,
     }}
 
     pub static BUILD_CONFIG: BuildConfig<'static> = BuildConfig {{
         ias_root_ca_cert: {},
+        ias_root_ca_certs: {},
         auditor_public_keys: {},
+        auditor_signature_threshold: {},
         rpc_max_message_size: {},
+        quote_policy: QuotePolicy {{
+            strict: {},
+            min_isv_svn: {},
+        }},
+        crate_version: {:?},
+        build_id: {:?},
+        sgx_sdk_version: {:?},
     }};"#,
+        extra_ias_root_ca_certs.len() + 1,
         config.auditor_public_keys.len(),
         ias_root_ca_cert,
+        ias_root_ca_certs,
         auditor_public_keys,
-        config.rpc_max_message_size
+        auditor_signature_threshold,
+        config.rpc_max_message_size,
+        strict_quote_verifier,
+        min_isv_svn,
+        crate_version,
+        build_id,
+        sgx_sdk_version
     ));
 
     let dest_path = Path::new(&args[2]);
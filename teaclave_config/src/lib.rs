@@ -21,9 +21,11 @@ pub mod runtime_config {
     #[cfg(feature = "mesalock_sgx")]
     use std::untrusted::fs;
 
+    use serde::de::{self, Deserialize, Deserializer};
     use serde_derive::Deserialize;
+    use std::collections::BTreeMap;
     use std::env;
-    use std::net::SocketAddr;
+    use std::net::{SocketAddr, ToSocketAddrs};
     use std::path::Path;
     use std::path::PathBuf;
     use std::string::String;
@@ -35,6 +37,42 @@ pub mod runtime_config {
         pub api_endpoints: ApiEndpointsConfig,
         pub internal_endpoints: InternalEndpointsConfig,
         pub audit: AuditConfig,
+        #[serde(default)]
+        pub attestation: AttestationServiceConfig,
+        #[serde(default)]
+        pub task_resource_limits: TaskResourceLimitsConfig,
+        #[serde(default)]
+        pub task_inputs: TaskInputsConfig,
+        #[serde(default)]
+        pub task_management: TaskManagementConfig,
+        #[serde(default)]
+        pub python_executor: PythonExecutorConfig,
+        #[serde(default)]
+        pub executor_capabilities: ExecutorCapabilitiesConfig,
+        #[serde(default)]
+        pub remote_function: RemoteFunctionConfig,
+        #[serde(default)]
+        pub function_invocation: FunctionInvocationConfig,
+        #[serde(default)]
+        pub task_logs: TaskLogsConfig,
+        #[serde(default)]
+        pub metrics: MetricsConfig,
+        #[serde(default)]
+        pub tls: TlsConfig,
+        #[serde(default)]
+        pub server: ServerConfig,
+        #[serde(default)]
+        pub rate_limit: RateLimitConfig,
+        #[serde(default)]
+        pub audit_log: AuditLogConfig,
+        #[serde(default)]
+        pub authorization: AuthorizationConfig,
+        #[serde(default)]
+        pub access_control: AccessControlConfig,
+        #[serde(default)]
+        pub storage: StorageConfig,
+        #[serde(default)]
+        pub clock: ClockConfig,
         #[serde(skip_deserializing)]
         pub env: EnvConfig,
     }
@@ -56,33 +94,904 @@ pub mod runtime_config {
 
     #[derive(Debug, Deserialize)]
     pub struct EndpointListenConfig {
+        #[serde(deserialize_with = "deserialize_address")]
         pub listen_address: SocketAddr,
     }
 
     #[derive(Debug, Deserialize)]
     pub struct EndpointListenAdvertisedConfig {
+        #[serde(deserialize_with = "deserialize_address")]
         pub listen_address: SocketAddr,
+        #[serde(deserialize_with = "deserialize_address")]
         pub advertised_address: SocketAddr,
+        #[serde(default = "default_pool_size")]
+        pub pool_size: usize,
+        // Seconds a pooled connection may be idle before it is probed with
+        // a heartbeat, 0 to never probe.
+        #[serde(default = "default_keepalive_interval")]
+        pub keepalive_interval: u64,
+    }
+
+    fn default_pool_size() -> usize {
+        1
+    }
+
+    fn default_keepalive_interval() -> u64 {
+        30
+    }
+
+    /// Parses an endpoint address, either a socket address or a `host:port`
+    /// pair whose host is resolved. The first address the host resolves to
+    /// is used.
+    pub fn parse_address(address: &str) -> Result<SocketAddr, String> {
+        if let Ok(addr) = address.parse::<SocketAddr>() {
+            return Ok(addr);
+        }
+        if !address.contains(':') {
+            return Err(format!("'{}' failed to parse: missing port", address));
+        }
+        let mut addrs = address
+            .to_socket_addrs()
+            .map_err(|e| format!("'{}' failed to resolve: {}", address, e))?;
+        addrs
+            .next()
+            .ok_or_else(|| format!("'{}' resolves to no address", address))
+    }
+
+    fn deserialize_address<'de, D>(deserializer: D) -> Result<SocketAddr, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let address = String::deserialize(deserializer)?;
+        parse_address(&address).map_err(de::Error::custom)
     }
 
     #[derive(Debug, Deserialize)]
     pub struct AuditConfig {
-        #[serde(rename(deserialize = "enclave_info"))]
-        enclave_info_source: ConfigSource,
-        #[serde(rename(deserialize = "auditor_signatures"))]
+        #[serde(default, rename(deserialize = "enclave_info"))]
+        enclave_info_source: Option<ConfigSource>,
+        #[serde(default, rename(deserialize = "auditor_signatures"))]
         auditor_signatures_source: Vec<ConfigSource>,
+        #[serde(default, rename(deserialize = "enclave_info_fragments"))]
+        fragment_sources: Vec<EnclaveInfoFragmentSource>,
+        /// The enclave info, followed by its fragments signed separately.
         #[serde(skip_deserializing)]
+        pub enclave_info_fragments: Vec<EnclaveInfoFragment>,
+    }
+
+    /// Part of the enclave info, e.g. the measurements of the enclaves of
+    /// one service, with the auditor signatures over it.
+    #[derive(Debug)]
+    pub struct EnclaveInfoFragment {
         pub enclave_info: String,
-        #[serde(skip_deserializing)]
         pub auditor_signatures: Vec<Vec<u8>>,
     }
 
+    #[derive(Debug, Deserialize)]
+    struct EnclaveInfoFragmentSource {
+        enclave_info: ConfigSource,
+        auditor_signatures: Vec<ConfigSource>,
+    }
+
+    fn read_enclave_info_fragment(
+        enclave_info_source: &ConfigSource,
+        auditor_signatures_source: &[ConfigSource],
+    ) -> Option<EnclaveInfoFragment> {
+        let enclave_info = match enclave_info_source {
+            ConfigSource::Path(ref enclave_info_path) => {
+                match fs::read_to_string(enclave_info_path) {
+                    Ok(c) => c,
+                    Err(_) => {
+                        error!("Cannot find enclave info at {:?}.", enclave_info_path);
+                        return None;
+                    }
+                }
+            }
+        };
+
+        let mut auditor_signatures: Vec<Vec<u8>> = vec![];
+        for source in auditor_signatures_source {
+            let signature = match source {
+                ConfigSource::Path(ref path) => match fs::read(path) {
+                    Ok(s) => s,
+                    Err(_) => {
+                        error!("Cannot find signature file {:?}.", path);
+                        return None;
+                    }
+                },
+            };
+            auditor_signatures.push(signature);
+        }
+        Some(EnclaveInfoFragment {
+            enclave_info,
+            auditor_signatures,
+        })
+    }
+
     #[derive(Debug, Deserialize)]
     #[serde(rename_all(deserialize = "snake_case"))]
     pub enum ConfigSource {
         Path(PathBuf),
     }
 
+    #[derive(Debug, Deserialize)]
+    pub struct AttestationServiceConfig {
+        #[serde(flatten)]
+        pub scheme: AttestationScheme,
+        /// Seconds an endorsed attestation report is reused before a new
+        /// one is requested from the attestation service.
+        #[serde(default = "default_report_cache_ttl")]
+        pub report_cache_ttl: u64,
+        /// Seconds since it was issued after which the attestation report of
+        /// a peer is rejected. Must exceed `report_cache_ttl`, so that peers
+        /// accept the cached reports.
+        #[serde(default = "default_max_report_age")]
+        pub max_report_age: u64,
+        /// Attempts at endorsing a report when the enclave starts.
+        #[serde(default = "default_startup_attempts")]
+        pub startup_attempts: u32,
+        /// Milliseconds before the second attempt, doubled for each further
+        /// attempt.
+        #[serde(default = "default_startup_backoff_ms")]
+        pub startup_backoff_ms: u64,
+        /// Directory where each enclave keeps its last endorsed credential,
+        /// sealed to its measurement, the working directory of the service
+        /// by default.
+        #[serde(default = "default_credential_cache_dir")]
+        pub credential_cache_dir: PathBuf,
+        /// Whether an enclave which cannot reach the attestation service when
+        /// it starts uses its kept credential, as long as peers still accept
+        /// the report.
+        #[serde(default = "default_allow_cached_on_outage")]
+        pub allow_cached_on_outage: bool,
+    }
+
+    impl Default for AttestationServiceConfig {
+        fn default() -> Self {
+            AttestationServiceConfig {
+                scheme: AttestationScheme::Epid,
+                report_cache_ttl: default_report_cache_ttl(),
+                max_report_age: default_max_report_age(),
+                startup_attempts: default_startup_attempts(),
+                startup_backoff_ms: default_startup_backoff_ms(),
+                credential_cache_dir: default_credential_cache_dir(),
+                allow_cached_on_outage: default_allow_cached_on_outage(),
+            }
+        }
+    }
+
+    fn default_startup_attempts() -> u32 {
+        3
+    }
+
+    fn default_startup_backoff_ms() -> u64 {
+        1000
+    }
+
+    fn default_credential_cache_dir() -> PathBuf {
+        PathBuf::from(".")
+    }
+
+    fn default_allow_cached_on_outage() -> bool {
+        true
+    }
+
+    fn default_report_cache_ttl() -> u64 {
+        86400
+    }
+
+    fn default_max_report_age() -> u64 {
+        2 * 86400
+    }
+
+    /// Resource limits applied to tasks. `default` is used for tasks created
+    /// without limits, and limits requested at task creation must not exceed
+    /// `maximum`.
+    #[derive(Debug, Deserialize)]
+    pub struct TaskResourceLimitsConfig {
+        #[serde(default = "default_task_resource_limits")]
+        pub default: ResourceLimitsConfig,
+        #[serde(default = "default_task_resource_limits")]
+        pub maximum: ResourceLimitsConfig,
+    }
+
+    impl Default for TaskResourceLimitsConfig {
+        fn default() -> Self {
+            TaskResourceLimitsConfig {
+                default: default_task_resource_limits(),
+                maximum: default_task_resource_limits(),
+            }
+        }
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct ResourceLimitsConfig {
+        pub max_memory_mb: u64,
+        pub max_cpu_seconds: u64,
+        pub max_wall_seconds: u64,
+    }
+
+    fn default_task_resource_limits() -> ResourceLimitsConfig {
+        ResourceLimitsConfig {
+            max_memory_mb: 1024,
+            max_cpu_seconds: 3600,
+            max_wall_seconds: 3600,
+        }
+    }
+
+    /// Checks of task input files when a task is created and before it
+    /// starts running.
+    #[derive(Debug, Deserialize)]
+    pub struct TaskInputsConfig {
+        #[serde(default)]
+        pub integrity_check: IntegrityCheck,
+        /// Input files a task may have, including the files of its
+        /// collections, ports and dependencies.
+        #[serde(default = "default_max_input_files")]
+        pub max_input_files: usize,
+        /// Total size in bytes of the input files of a task.
+        #[serde(default = "default_max_input_bytes")]
+        pub max_input_bytes: u64,
+        /// What becomes of a task whose input file is gone by the time the
+        /// task is handed to an executor.
+        #[serde(default)]
+        pub missing_inputs: MissingInputPolicy,
+    }
+
+    impl Default for TaskInputsConfig {
+        fn default() -> Self {
+            TaskInputsConfig {
+                integrity_check: IntegrityCheck::default(),
+                max_input_files: default_max_input_files(),
+                max_input_bytes: default_max_input_bytes(),
+                missing_inputs: MissingInputPolicy::default(),
+            }
+        }
+    }
+
+    fn default_max_input_files() -> usize {
+        1024
+    }
+
+    fn default_max_input_bytes() -> u64 {
+        16 * 1024 * 1024 * 1024
+    }
+
+    /// `Mandatory` fails a task if an input file does not match the SHA-256
+    /// recorded when it was uploaded. `BestEffort` logs the mismatch and lets
+    /// the task run.
+    #[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+    #[serde(rename_all(deserialize = "snake_case"))]
+    pub enum IntegrityCheck {
+        Mandatory,
+        BestEffort,
+    }
+
+    impl Default for IntegrityCheck {
+        fn default() -> Self {
+            IntegrityCheck::Mandatory
+        }
+    }
+
+    /// `Fail` fails a task if any of its input files is missing. `SkipOptional`
+    /// runs the task without its missing files of the inputs its function
+    /// marks optional, and fails it only for a missing mandatory input.
+    #[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+    #[serde(rename_all(deserialize = "snake_case"))]
+    pub enum MissingInputPolicy {
+        Fail,
+        SkipOptional,
+    }
+
+    impl Default for MissingInputPolicy {
+        fn default() -> Self {
+            MissingInputPolicy::Fail
+        }
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct TaskManagementConfig {
+        /// Maximum number of tasks created by one batch request.
+        #[serde(default = "default_max_batch_size")]
+        pub max_batch_size: usize,
+        /// Maximum number of entries returned by one list request.
+        #[serde(default = "default_max_page_size")]
+        pub max_page_size: usize,
+        /// Seconds a task in a final status is kept with its results, if its
+        /// creator did not specify the retention.
+        #[serde(default = "default_result_ttl")]
+        pub result_ttl: u64,
+        /// Seconds between two sweeps deleting expired tasks.
+        #[serde(default = "default_sweep_interval")]
+        pub sweep_interval: u64,
+        /// Priority of tasks created without one. Ready tasks with a higher
+        /// priority are handed out to executors first.
+        #[serde(default)]
+        pub default_priority: i32,
+        /// Seconds a ready task waits for its priority to be raised by one,
+        /// so that low-priority tasks are not starved.
+        #[serde(default = "default_priority_aging_interval")]
+        pub priority_aging_interval: u64,
+        /// Seconds during which a task creation with the idempotency key of
+        /// an earlier one returns the earlier task.
+        #[serde(default = "default_idempotency_window")]
+        pub idempotency_window: u64,
+        /// Seconds between two checks for scheduled tasks whose `not_before`
+        /// time has come.
+        #[serde(default = "default_schedule_interval")]
+        pub schedule_interval: u64,
+        /// Seconds from now a task may be scheduled to start at the latest.
+        #[serde(default = "default_max_schedule_delay")]
+        pub max_schedule_delay: u64,
+        /// Attempts the retry policy of a task may allow at most.
+        #[serde(default = "default_max_task_attempts")]
+        pub max_task_attempts: u32,
+        /// Where TMS keeps the state it recovers after a restart.
+        #[serde(default)]
+        pub storage_backend: StorageBackendKind,
+        /// Seconds a registered worker may go without a heartbeat before
+        /// the tasks it was handed are queued again.
+        #[serde(default = "default_worker_heartbeat_timeout")]
+        pub worker_heartbeat_timeout: u64,
+        /// Files a task may produce on the dynamic output ports of its
+        /// function, all ports together.
+        #[serde(default = "default_max_dynamic_outputs")]
+        pub max_dynamic_outputs: usize,
+        /// Files a task may produce for its participants, including the
+        /// files on dynamic output ports.
+        #[serde(default = "default_max_output_files")]
+        pub max_output_files: usize,
+        /// Seconds between two registrations of the executors of the
+        /// execution service, which TMS forgets when it restarts.
+        #[serde(default = "default_executor_registration_interval")]
+        pub executor_registration_interval: u64,
+    }
+
+    /// `Tdfs` stores the state of TMS in TDFS. `Memory` keeps it in the TMS
+    /// enclave, so that it is lost when TMS stops, e.g. for tests.
+    #[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+    #[serde(rename_all(deserialize = "snake_case"))]
+    pub enum StorageBackendKind {
+        Tdfs,
+        Memory,
+    }
+
+    impl Default for StorageBackendKind {
+        fn default() -> Self {
+            StorageBackendKind::Tdfs
+        }
+    }
+
+    impl Default for TaskManagementConfig {
+        fn default() -> Self {
+            TaskManagementConfig {
+                max_batch_size: default_max_batch_size(),
+                max_page_size: default_max_page_size(),
+                result_ttl: default_result_ttl(),
+                sweep_interval: default_sweep_interval(),
+                default_priority: 0,
+                priority_aging_interval: default_priority_aging_interval(),
+                idempotency_window: default_idempotency_window(),
+                schedule_interval: default_schedule_interval(),
+                max_schedule_delay: default_max_schedule_delay(),
+                max_task_attempts: default_max_task_attempts(),
+                storage_backend: StorageBackendKind::default(),
+                worker_heartbeat_timeout: default_worker_heartbeat_timeout(),
+                max_dynamic_outputs: default_max_dynamic_outputs(),
+                max_output_files: default_max_output_files(),
+                executor_registration_interval: default_executor_registration_interval(),
+            }
+        }
+    }
+
+    fn default_max_batch_size() -> usize {
+        100
+    }
+
+    fn default_max_page_size() -> usize {
+        1000
+    }
+
+    fn default_result_ttl() -> u64 {
+        7 * 24 * 3600
+    }
+
+    fn default_sweep_interval() -> u64 {
+        600
+    }
+
+    fn default_priority_aging_interval() -> u64 {
+        60
+    }
+
+    fn default_idempotency_window() -> u64 {
+        24 * 3600
+    }
+
+    fn default_schedule_interval() -> u64 {
+        1
+    }
+
+    fn default_max_schedule_delay() -> u64 {
+        30 * 24 * 3600
+    }
+
+    fn default_max_task_attempts() -> u32 {
+        10
+    }
+
+    fn default_worker_heartbeat_timeout() -> u64 {
+        30
+    }
+
+    fn default_max_dynamic_outputs() -> usize {
+        256
+    }
+
+    fn default_max_output_files() -> usize {
+        1024
+    }
+
+    fn default_executor_registration_interval() -> u64 {
+        60
+    }
+
+    /// Restrictions of Python functions.
+    #[derive(Debug, Deserialize)]
+    pub struct PythonExecutorConfig {
+        /// Top-level modules a Python function may import.
+        #[serde(default = "default_allowed_modules")]
+        pub allowed_modules: Vec<String>,
+        /// Lines a Python function may execute per second of its CPU limit.
+        #[serde(default = "default_lines_per_cpu_second")]
+        pub lines_per_cpu_second: u64,
+    }
+
+    impl Default for PythonExecutorConfig {
+        fn default() -> Self {
+            PythonExecutorConfig {
+                allowed_modules: default_allowed_modules(),
+                lines_per_cpu_second: default_lines_per_cpu_second(),
+            }
+        }
+    }
+
+    fn default_allowed_modules() -> Vec<String> {
+        [
+            "collections",
+            "functools",
+            "itertools",
+            "json",
+            "math",
+            "numpy",
+            "random",
+            "re",
+            "string",
+        ]
+        .iter()
+        .map(|module| (*module).to_string())
+        .collect()
+    }
+
+    fn default_lines_per_cpu_second() -> u64 {
+        1_000_000
+    }
+
+    /// Most capabilities a function may be registered with.
+    #[derive(Debug, Default, Deserialize)]
+    pub struct ExecutorCapabilitiesConfig {
+        /// Names of the capabilities functions may declare, among "network",
+        /// "filesystem" and "subprocess". The others are denied.
+        #[serde(default)]
+        pub allowed: Vec<String>,
+    }
+
+    /// Downloads of function payloads registered by URL.
+    #[derive(Debug, Deserialize)]
+    pub struct RemoteFunctionConfig {
+        /// Size in bytes of the largest payload downloaded.
+        #[serde(default = "default_max_payload_size")]
+        pub max_payload_size: u64,
+        /// Deadline in seconds for each socket read and write of a download.
+        #[serde(default = "default_fetch_timeout_secs")]
+        pub timeout_secs: u64,
+    }
+
+    impl Default for RemoteFunctionConfig {
+        fn default() -> Self {
+            RemoteFunctionConfig {
+                max_payload_size: default_max_payload_size(),
+                timeout_secs: default_fetch_timeout_secs(),
+            }
+        }
+    }
+
+    fn default_max_payload_size() -> u64 {
+        16 * 1024 * 1024
+    }
+
+    fn default_fetch_timeout_secs() -> u64 {
+        30
+    }
+
+    /// Quick invocations of functions with inline inputs, for development.
+    #[derive(Debug, Deserialize)]
+    pub struct FunctionInvocationConfig {
+        /// Size in bytes of the inline inputs, and of the outputs, of one
+        /// invocation.
+        #[serde(default = "default_max_inline_bytes")]
+        pub max_inline_bytes: u64,
+        /// CPU limit in seconds of the task of an invocation.
+        #[serde(default = "default_invocation_cpu_seconds")]
+        pub max_cpu_seconds: u64,
+        /// Wall-clock limit in seconds of the task of an invocation.
+        #[serde(default = "default_invocation_wall_seconds")]
+        pub max_wall_seconds: u64,
+        /// Seconds the task of an invocation is kept after it ends.
+        #[serde(default = "default_invocation_retention_secs")]
+        pub retention_secs: u64,
+    }
+
+    impl Default for FunctionInvocationConfig {
+        fn default() -> Self {
+            FunctionInvocationConfig {
+                max_inline_bytes: default_max_inline_bytes(),
+                max_cpu_seconds: default_invocation_cpu_seconds(),
+                max_wall_seconds: default_invocation_wall_seconds(),
+                retention_secs: default_invocation_retention_secs(),
+            }
+        }
+    }
+
+    fn default_max_inline_bytes() -> u64 {
+        64 * 1024
+    }
+
+    fn default_invocation_cpu_seconds() -> u64 {
+        5
+    }
+
+    fn default_invocation_wall_seconds() -> u64 {
+        10
+    }
+
+    fn default_invocation_retention_secs() -> u64 {
+        600
+    }
+
+    /// Logs the functions of tasks write to their stdout and stderr, which
+    /// the execution service captures and TMS keeps with the task.
+    #[derive(Debug, Deserialize)]
+    pub struct TaskLogsConfig {
+        /// Bytes of log lines kept for one task. Later lines are dropped.
+        #[serde(default = "default_max_log_bytes")]
+        pub max_bytes: usize,
+        /// Bytes of one log line. Longer lines are cut.
+        #[serde(default = "default_max_log_line_bytes")]
+        pub max_line_bytes: usize,
+    }
+
+    impl Default for TaskLogsConfig {
+        fn default() -> Self {
+            TaskLogsConfig {
+                max_bytes: default_max_log_bytes(),
+                max_line_bytes: default_max_log_line_bytes(),
+            }
+        }
+    }
+
+    fn default_max_log_bytes() -> usize {
+        64 * 1024
+    }
+
+    fn default_max_log_line_bytes() -> usize {
+        1024
+    }
+
+    /// Export of enclave metrics by the host of TMS and TDFS.
+    #[derive(Debug, Deserialize)]
+    pub struct MetricsConfig {
+        /// Directory the metrics are written to as `<service>.prom`. Metrics
+        /// are not exported if not specified.
+        #[serde(default)]
+        pub export_dir: Option<PathBuf>,
+        /// Seconds between two exports.
+        #[serde(default = "default_export_interval")]
+        pub export_interval: u64,
+        /// Upper bounds in bytes of the buckets of the request and response
+        /// size histograms, ascending. Sizes are not recorded if empty.
+        #[serde(default = "default_size_buckets")]
+        pub size_buckets: Vec<u64>,
+    }
+
+    impl Default for MetricsConfig {
+        fn default() -> Self {
+            MetricsConfig {
+                export_dir: None,
+                export_interval: default_export_interval(),
+                size_buckets: default_size_buckets(),
+            }
+        }
+    }
+
+    fn default_export_interval() -> u64 {
+        15
+    }
+
+    // 256 bytes to 16 MiB, by powers of 4
+    fn default_size_buckets() -> Vec<u64> {
+        (0..9).map(|i| 256 << (2 * i)).collect()
+    }
+
+    /// TLS restrictions of trusted channels, the defaults of rustls are kept
+    /// if not specified.
+    #[derive(Debug, Deserialize)]
+    pub struct TlsConfig {
+        /// Oldest protocol version accepted, "1.2" or "1.3".
+        #[serde(default)]
+        pub min_protocol_version: Option<String>,
+        /// Names of the enabled cipher suites, in order of preference.
+        #[serde(default)]
+        pub cipher_suites: Vec<String>,
+        /// Seconds a session may be resumed after the full handshake which
+        /// attested the peer, 0 to always run a full handshake. At most a
+        /// tenth of `max_report_age`.
+        #[serde(default = "default_session_resumption_window")]
+        pub session_resumption_window: u64,
+        /// Sessions kept for resumption by each TLS config.
+        #[serde(default = "default_session_cache_size")]
+        pub session_cache_size: usize,
+    }
+
+    impl Default for TlsConfig {
+        fn default() -> Self {
+            TlsConfig {
+                min_protocol_version: None,
+                cipher_suites: Vec::new(),
+                session_resumption_window: default_session_resumption_window(),
+                session_cache_size: default_session_cache_size(),
+            }
+        }
+    }
+
+    fn default_session_resumption_window() -> u64 {
+        60
+    }
+
+    fn default_session_cache_size() -> usize {
+        256
+    }
+
+    /// Connection handling of the listeners of all services.
+    #[derive(Debug, Deserialize)]
+    pub struct ServerConfig {
+        /// Pending connections the OS queues for a listener before refusing
+        /// new ones.
+        #[serde(default = "default_backlog")]
+        pub backlog: i32,
+        /// Connections a listener serves at the same time. Further
+        /// connections are rejected until a connection finishes.
+        #[serde(default = "default_max_connections")]
+        pub max_connections: usize,
+        /// Size in bytes of the largest request a listener reads. Larger
+        /// requests are rejected before they are buffered. Capped by the
+        /// `rpc_max_message_size` of the build config.
+        #[serde(default = "default_max_request_size")]
+        pub max_request_size: u64,
+        /// Threads of a listener handing connections to the enclave.
+        #[serde(default = "default_worker_threads")]
+        pub worker_threads: usize,
+        /// Connections waiting for a free worker thread of a listener.
+        /// Further connections are rejected.
+        #[serde(default = "default_max_queued_connections")]
+        pub max_queued_connections: usize,
+        /// Seconds a service waits at startup for the advertised addresses
+        /// of the services it depends on to accept connections.
+        #[serde(default = "default_dependency_wait")]
+        pub dependency_wait: u64,
+        /// Whether a panic of a request handler is answered with an
+        /// internal error, instead of unwinding through the server or
+        /// aborting the enclave with `panic = "abort"`.
+        #[serde(default = "default_catch_panics")]
+        pub catch_panics: bool,
+    }
+
+    impl Default for ServerConfig {
+        fn default() -> Self {
+            ServerConfig {
+                backlog: default_backlog(),
+                max_connections: default_max_connections(),
+                max_request_size: default_max_request_size(),
+                worker_threads: default_worker_threads(),
+                max_queued_connections: default_max_queued_connections(),
+                dependency_wait: default_dependency_wait(),
+                catch_panics: default_catch_panics(),
+            }
+        }
+    }
+
+    fn default_backlog() -> i32 {
+        128
+    }
+
+    fn default_max_connections() -> usize {
+        10
+    }
+
+    fn default_max_request_size() -> u64 {
+        crate::build_config::BUILD_CONFIG.rpc_max_message_size
+    }
+
+    fn default_worker_threads() -> usize {
+        default_max_connections()
+    }
+
+    fn default_max_queued_connections() -> usize {
+        64
+    }
+
+    fn default_dependency_wait() -> u64 {
+        60
+    }
+
+    fn default_catch_panics() -> bool {
+        true
+    }
+
+    /// Requests a user may send to TMS, as a token bucket per user.
+    #[derive(Debug, Deserialize)]
+    pub struct RateLimitConfig {
+        /// Rate at which the bucket of a user refills. Zero disables rate
+        /// limiting.
+        #[serde(default = "default_requests_per_second")]
+        pub requests_per_second: u32,
+        /// Requests a user may send at once after being idle.
+        #[serde(default = "default_burst")]
+        pub burst: u32,
+        /// Users whose buckets are kept at the same time. Buckets of idle
+        /// users are dropped first.
+        #[serde(default = "default_max_tracked_users")]
+        pub max_tracked_users: usize,
+    }
+
+    impl Default for RateLimitConfig {
+        fn default() -> Self {
+            RateLimitConfig {
+                requests_per_second: default_requests_per_second(),
+                burst: default_burst(),
+                max_tracked_users: default_max_tracked_users(),
+            }
+        }
+    }
+
+    fn default_requests_per_second() -> u32 {
+        100
+    }
+
+    fn default_burst() -> u32 {
+        200
+    }
+
+    fn default_max_tracked_users() -> usize {
+        10000
+    }
+
+    /// Access to the audit log of TMS.
+    #[derive(Debug, Default, Deserialize)]
+    pub struct AuditLogConfig {
+        /// Users who may query the audit log. They have the `admin` role.
+        #[serde(default)]
+        pub admins: Vec<String>,
+    }
+
+    /// Roles of the users of TMS, and the roles its methods require.
+    #[derive(Debug, Default, Deserialize)]
+    pub struct AuthorizationConfig {
+        /// Users granted each role.
+        #[serde(default)]
+        pub roles: BTreeMap<String, Vec<String>>,
+        /// Role required by each method, keyed by request type, e.g.
+        /// `Delete`. Overrides the `admin` role required by default to query
+        /// the audit log and revoke users.
+        #[serde(default)]
+        pub method_roles: BTreeMap<String, String>,
+    }
+
+    /// Access to files and functions granted by the relationships ACS keeps,
+    /// e.g. group membership, beyond what their owners shared.
+    #[derive(Debug, Deserialize)]
+    pub struct AccessControlConfig {
+        /// Whether TMS asks ACS about the access its own checks deny.
+        #[serde(default)]
+        pub enabled: bool,
+        /// Seconds a granted access is remembered, 0 to ask ACS every time.
+        #[serde(default = "default_decision_cache_ttl")]
+        pub decision_cache_ttl: u64,
+    }
+
+    impl Default for AccessControlConfig {
+        fn default() -> Self {
+            AccessControlConfig {
+                enabled: false,
+                decision_cache_ttl: default_decision_cache_ttl(),
+            }
+        }
+    }
+
+    fn default_decision_cache_ttl() -> u64 {
+        30
+    }
+
+    /// Entries TDFS keeps for TMS, and the requests of TMS to TDFS.
+    #[derive(Debug, Deserialize)]
+    pub struct StorageConfig {
+        /// Seconds between two sweeps removing the expired entries.
+        #[serde(default = "default_storage_sweep_interval")]
+        pub sweep_interval: u64,
+        /// Requests to TDFS failing in a row before TMS stops making them,
+        /// 0 to always make them.
+        #[serde(default = "default_breaker_failure_threshold")]
+        pub breaker_failure_threshold: u32,
+        /// Seconds TMS stops making requests to TDFS for, before probing it
+        /// with one request.
+        #[serde(default = "default_breaker_cool_down")]
+        pub breaker_cool_down: u64,
+    }
+
+    impl Default for StorageConfig {
+        fn default() -> Self {
+            StorageConfig {
+                sweep_interval: default_storage_sweep_interval(),
+                breaker_failure_threshold: default_breaker_failure_threshold(),
+                breaker_cool_down: default_breaker_cool_down(),
+            }
+        }
+    }
+
+    fn default_storage_sweep_interval() -> u64 {
+        60
+    }
+
+    fn default_breaker_failure_threshold() -> u32 {
+        5
+    }
+
+    fn default_breaker_cool_down() -> u64 {
+        30
+    }
+
+    /// Time of the host, which the enclaves take as the current time.
+    #[derive(Debug, Deserialize)]
+    pub struct ClockConfig {
+        /// Seconds by which the clocks of the hosts and of the attestation
+        /// service may disagree. Timestamps this close to the current time
+        /// are taken as the current time when checking the freshness of
+        /// reports, timestamping receipts and scheduling tasks.
+        #[serde(default = "default_clock_skew_tolerance")]
+        pub skew_tolerance: u64,
+    }
+
+    impl Default for ClockConfig {
+        fn default() -> Self {
+            ClockConfig {
+                skew_tolerance: default_clock_skew_tolerance(),
+            }
+        }
+    }
+
+    fn default_clock_skew_tolerance() -> u64 {
+        60
+    }
+
+    /// Attestation scheme used to endorse RA certificates. EPID (with IAS
+    /// credentials from the environment) is used if not specified.
+    #[derive(Debug, Deserialize)]
+    #[serde(tag = "algorithm", rename_all(deserialize = "snake_case"))]
+    pub enum AttestationScheme {
+        Epid,
+        Dcap { pccs_url: String },
+    }
+
     #[derive(Debug, Default)]
     pub struct EnvConfig {
         pub ias_spid: String,
@@ -101,30 +1010,38 @@ pub mod runtime_config {
             };
             let mut config: RuntimeConfig = match toml::from_str(&contents) {
                 Ok(c) => c,
-                Err(_) => {
-                    error!("Something went wrong reading the runtime config file.");
+                Err(e) => {
+                    error!("Cannot parse the runtime config file: {}", e);
                     return None;
                 }
             };
 
-            config.audit.enclave_info = match &config.audit.enclave_info_source {
-                ConfigSource::Path(ref enclave_info_path) => fs::read_to_string(enclave_info_path)
-                    .unwrap_or_else(|_| {
-                        panic!("Cannot find enclave info at {:?}.", enclave_info_path)
-                    }),
-            };
-
-            let mut signatures: Vec<Vec<u8>> = vec![];
-            for source in &config.audit.auditor_signatures_source {
-                let signature = match source {
-                    ConfigSource::Path(ref path) => fs::read(path)
-                        .unwrap_or_else(|_| panic!("Cannot find signature file {:?}.", path)),
-                };
-                signatures.push(signature);
+            let audit = &config.audit;
+            let mut fragments = Vec::new();
+            if let Some(ref enclave_info_source) = audit.enclave_info_source {
+                fragments.push(read_enclave_info_fragment(
+                    enclave_info_source,
+                    &audit.auditor_signatures_source,
+                )?);
+            }
+            for source in audit.fragment_sources.iter() {
+                fragments.push(read_enclave_info_fragment(
+                    &source.enclave_info,
+                    &source.auditor_signatures,
+                )?);
             }
-            config.audit.auditor_signatures = signatures;
+            if fragments.is_empty() {
+                error!("Cannot find enclave info in the runtime config.");
+                return None;
+            }
+            config.audit.enclave_info_fragments = fragments;
 
-            if !cfg!(sgx_sim) {
+            // IAS credentials are not needed for DCAP attestation
+            let uses_epid = match config.attestation.scheme {
+                AttestationScheme::Epid => true,
+                AttestationScheme::Dcap { .. } => false,
+            };
+            if !cfg!(sgx_sim) && uses_epid {
                 let ias_spid = match env::var("IAS_SPID") {
                     Ok(e) => e.trim().to_string(),
                     Err(_) => {
@@ -161,9 +1078,29 @@ mod tests {
         println!("{:?}", runtime_config::RUNTIME_CONFIG.api_endpoints);
         println!("{:?}", runtime_config::RUNTIME_CONFIG.internal_endpoints);
         println!("{:?}", runtime_config::RUNTIME_CONFIG.audit);
+        println!("{:?}", runtime_config::RUNTIME_CONFIG.attestation);
         println!("{:?}", runtime_config::RUNTIME_CONFIG.env);
     }
 
+    #[test]
+    fn test_parse_address() {
+        use runtime_config::parse_address;
+        let addr = parse_address("127.0.0.1:5066").unwrap();
+        assert_eq!(addr.to_string(), "127.0.0.1:5066");
+        let addr = parse_address("[::1]:5066").unwrap();
+        assert_eq!(addr.port(), 5066);
+        let addr = parse_address("localhost:5066").unwrap();
+        assert_eq!(addr.port(), 5066);
+        assert!(addr.ip().is_loopback());
+
+        let err = parse_address("127.0.0.1").unwrap_err();
+        assert_eq!(err, "'127.0.0.1' failed to parse: missing port");
+        let err = parse_address("127.0.0.1:port").unwrap_err();
+        assert!(err.starts_with("'127.0.0.1:port' failed to resolve"));
+        let err = parse_address("no-such-host.invalid:5066").unwrap_err();
+        assert!(err.starts_with("'no-such-host.invalid:5066' failed to resolve"));
+    }
+
     #[test]
     fn test_build_config() {
         println!("{:?}", build_config::BUILD_CONFIG);
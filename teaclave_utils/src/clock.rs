@@ -0,0 +1,93 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! The current time, as the services see it.
+//!
+//! SGX offers no trusted time source to the enclaves. `SystemTime::now()` of
+//! sgx_tstd is an OCALL returning the time of the host, which the host may
+//! set at will. A host can therefore make a stale attestation report look
+//! fresh, or start its scheduled tasks early or late, but nothing else
+//! depends on time: the freshness of reports, the timestamps of receipts and
+//! the start of scheduled tasks. The clocks of the hosts and of the
+//! attestation service, which timestamps the reports, are expected to agree
+//! within the `clock.skew_tolerance` of the runtime config, and timestamps
+//! within that tolerance from the current time are taken as the current time.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+#[cfg(feature = "mesalock_sgx")]
+use std::untrusted::time::SystemTimeEx;
+
+pub trait Clock: Send + Sync {
+    /// Time since the epoch.
+    fn now(&self) -> Duration;
+}
+
+/// The clock of the host.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_else(|_| Duration::from_secs(0))
+    }
+}
+
+/// A clock which only moves when told to, for tests.
+pub struct ManualClock {
+    millis: AtomicU64,
+}
+
+impl ManualClock {
+    pub fn new(now: Duration) -> Self {
+        ManualClock {
+            millis: AtomicU64::new(now.as_millis() as u64),
+        }
+    }
+
+    pub fn set(&self, now: Duration) {
+        self.millis.store(now.as_millis() as u64, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.millis
+            .fetch_add(by.as_millis() as u64, Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        Duration::from_millis(self.millis.load(Ordering::SeqCst))
+    }
+}
+
+/// Time elapsed from `then` to `now`, zero if `then` is ahead of `now` by no
+/// more than `skew`. `None` if `then` is further in the future.
+pub fn elapsed_within_skew(now: Duration, then: Duration, skew: Duration) -> Option<Duration> {
+    if then > now + skew {
+        None
+    } else {
+        Some(now.checked_sub(then).unwrap_or_default())
+    }
+}
+
+/// Whether `time` has come at `now`, counting times up to `skew` ahead of
+/// `now` as come.
+pub fn has_come(now: Duration, time: Duration, skew: Duration) -> bool {
+    time <= now + skew
+}
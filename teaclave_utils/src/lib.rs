@@ -11,6 +11,8 @@ use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
 
+pub mod clock;
+
 type Result<T> = std::result::Result<T, UtilsError>;
 use sgx_types::SGX_HASH_SIZE;
 
@@ -42,7 +44,8 @@ fn decode_hex_digit(digit: char) -> Result<u8> {
     }
 }
 
-fn decode_hex(hex: &str) -> Result<Vec<u8>> {
+/// Decodes hex digits, ignoring spaces between bytes.
+pub fn decode_hex(hex: &str) -> Result<Vec<u8>> {
     let mut r: Vec<u8> = Vec::new();
     let mut chars = hex.chars().enumerate();
     loop {
@@ -99,7 +102,10 @@ where
     String::deserialize(deserializer).and_then(|string| {
         let v = decode_hex(&string).map_err(|_| Error::custom("ParseError"))?;
         let mut array = [0; SGX_HASH_SIZE];
-        let bytes = &v[..array.len()]; // panics if not enough data
+        if v.len() < array.len() {
+            return Err(Error::custom("ParseError"));
+        }
+        let bytes = &v[..array.len()];
         array.copy_from_slice(bytes);
         Ok(array)
     })
@@ -107,7 +113,111 @@ where
 
 #[derive(Debug, Deserialize)]
 #[serde(transparent)]
-struct EnclaveInfoToml(HashMap<String, EnclaveMeasurement>);
+struct EnclaveInfoToml(HashMap<String, toml::Value>);
+
+/// Reason why the signature of an auditor over enclave info is not verified.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SignatureError {
+    /// No signature is provided at all.
+    NoSignatures,
+    /// None of the provided signatures is valid under the auditor's public key.
+    NoValidSignature,
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureError::NoSignatures => write!(f, "no signatures"),
+            SignatureError::NoValidSignature => write!(f, "no valid signature"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum EnclaveInfoError {
+    /// Fewer valid auditor signatures than required. Auditors whose
+    /// signatures are not verified are listed as (auditor_index, reason).
+    Signature {
+        required: usize,
+        verified: usize,
+        failures: Vec<(usize, SignatureError)>,
+    },
+    /// Enclave entries that cannot be loaded, as (enclave_name, reason).
+    Measurement(Vec<(String, String)>),
+    /// Enclave info is not a valid TOML table.
+    Format,
+    /// Fragment `index` of the enclave info is not verified or loaded.
+    Fragment {
+        index: usize,
+        error: Box<EnclaveInfoError>,
+    },
+    /// Enclaves listed by more than one fragment of the enclave info.
+    Duplicate(Vec<DuplicateEnclave>),
+}
+
+/// An enclave listed by two fragments of the enclave info, which may or may
+/// not agree on its measurement.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicateEnclave {
+    pub name: String,
+    pub fragments: (usize, usize),
+    pub conflicting: bool,
+}
+
+impl fmt::Display for EnclaveInfoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnclaveInfoError::Signature {
+                required,
+                verified,
+                failures,
+            } => {
+                write!(
+                    f,
+                    "{} valid auditor signatures found, {} required; unverified auditors:",
+                    verified, required
+                )?;
+                for (index, reason) in failures {
+                    write!(f, " #{} ({})", index, reason)?;
+                }
+                Ok(())
+            }
+            EnclaveInfoError::Measurement(failures) => {
+                write!(f, "failed to load measurements of enclaves:")?;
+                for (name, reason) in failures {
+                    write!(f, " {} ({})", name, reason)?;
+                }
+                Ok(())
+            }
+            EnclaveInfoError::Format => write!(f, "invalid enclave info format"),
+            EnclaveInfoError::Fragment { index, error } => {
+                write!(f, "fragment #{} of enclave info: {}", index, error)
+            }
+            EnclaveInfoError::Duplicate(duplicates) => {
+                write!(f, "enclaves listed by more than one fragment:")?;
+                for duplicate in duplicates {
+                    let (first, second) = duplicate.fragments;
+                    if duplicate.conflicting {
+                        write!(
+                            f,
+                            " {} (conflicting measurements in #{} and #{})",
+                            duplicate.name, first, second
+                        )?;
+                    } else {
+                        write!(f, " {} (#{} and #{})", duplicate.name, first, second)?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for EnclaveInfoError {
+    fn description(&self) -> &str {
+        "Enclave info error"
+    }
+}
 
 #[derive(Debug, Deserialize, Copy, Clone, Eq, PartialEq)]
 pub struct EnclaveMeasurement {
@@ -126,38 +236,209 @@ impl EnclaveMeasurement {
     }
 }
 
-pub fn verify_enclave_info<T, U>(enclave_info: &[u8], public_keys: &[T], signatures: &[U]) -> bool
+/// Verifies the signature of each auditor over enclave info. The result of
+/// every auditor is returned, as (auditor_index, result), even if all of them
+/// are verified.
+pub fn verify_enclave_info_verbose<T, U>(
+    enclave_info: &[u8],
+    public_keys: &[T],
+    signatures: &[U],
+) -> Vec<(usize, std::result::Result<(), SignatureError>)>
 where
     T: AsRef<[u8]>,
     U: AsRef<[u8]>,
 {
     use ring::signature;
 
-    for k in public_keys {
-        let mut verified = false;
-        for s in signatures {
-            if signature::UnparsedPublicKey::new(&signature::RSA_PKCS1_2048_8192_SHA256, k)
-                .verify(enclave_info, s.as_ref())
-                .is_ok()
-            {
-                verified = true;
+    public_keys
+        .iter()
+        .enumerate()
+        .map(|(index, k)| {
+            if signatures.is_empty() {
+                return (index, Err(SignatureError::NoSignatures));
             }
+            let verified = signatures.iter().any(|s| {
+                signature::UnparsedPublicKey::new(&signature::RSA_PKCS1_2048_8192_SHA256, k)
+                    .verify(enclave_info, s.as_ref())
+                    .is_ok()
+            });
+            if verified {
+                (index, Ok(()))
+            } else {
+                (index, Err(SignatureError::NoValidSignature))
+            }
+        })
+        .collect()
+}
+
+/// Verifies that at least `threshold` of the auditors have valid signatures
+/// over enclave info.
+pub fn verify_enclave_info<T, U>(
+    enclave_info: &[u8],
+    public_keys: &[T],
+    signatures: &[U],
+    threshold: usize,
+) -> std::result::Result<(), EnclaveInfoError>
+where
+    T: AsRef<[u8]>,
+    U: AsRef<[u8]>,
+{
+    let results = verify_enclave_info_verbose(enclave_info, public_keys, signatures);
+    let verified = results.iter().filter(|(_, result)| result.is_ok()).count();
+    let failures: Vec<(usize, SignatureError)> = results
+        .into_iter()
+        .filter_map(|(index, result)| result.err().map(|e| (index, e)))
+        .collect();
+
+    if verified >= threshold {
+        Ok(())
+    } else {
+        Err(EnclaveInfoError::Signature {
+            required: threshold,
+            verified,
+            failures,
+        })
+    }
+}
+
+pub fn load_enclave_info(
+    content: &str,
+) -> std::result::Result<HashMap<String, EnclaveMeasurement>, EnclaveInfoError> {
+    let config: EnclaveInfoToml = toml::from_str(&content).map_err(|_| EnclaveInfoError::Format)?;
+    let mut info_map = HashMap::new();
+    let mut failures = Vec::new();
+    for (k, v) in config.0 {
+        match v.try_into::<EnclaveMeasurement>() {
+            Ok(m) => {
+                info_map.insert(k, EnclaveMeasurement::new(m.mr_enclave, m.mr_signer));
+            }
+            Err(e) => failures.push((k, e.to_string())),
         }
-        if !verified {
-            return false;
+    }
+
+    if failures.is_empty() {
+        Ok(info_map)
+    } else {
+        Err(EnclaveInfoError::Measurement(failures))
+    }
+}
+
+/// Merges the measurements of the fragments of enclave info. An enclave may
+/// only be listed by one fragment, so that fragments never override each
+/// other; duplicates are rejected, and reported as conflicting if the
+/// fragments disagree on the measurement.
+pub fn merge_enclave_info(
+    fragments: Vec<HashMap<String, EnclaveMeasurement>>,
+) -> std::result::Result<HashMap<String, EnclaveMeasurement>, EnclaveInfoError> {
+    let mut merged: HashMap<String, (usize, EnclaveMeasurement)> = HashMap::new();
+    let mut duplicates = Vec::new();
+    for (index, fragment) in fragments.into_iter().enumerate() {
+        for (name, measurement) in fragment {
+            match merged.get(&name) {
+                Some((first, existing)) => duplicates.push(DuplicateEnclave {
+                    conflicting: *existing != measurement,
+                    fragments: (*first, index),
+                    name,
+                }),
+                None => {
+                    merged.insert(name, (index, measurement));
+                }
+            }
         }
     }
 
-    true
+    if duplicates.is_empty() {
+        Ok(merged
+            .into_iter()
+            .map(|(name, (_, measurement))| (name, measurement))
+            .collect())
+    } else {
+        duplicates.sort_by(|a, b| (&a.name, a.fragments).cmp(&(&b.name, b.fragments)));
+        Err(EnclaveInfoError::Duplicate(duplicates))
+    }
 }
 
-pub fn load_enclave_info(content: &str) -> std::collections::HashMap<String, EnclaveMeasurement> {
-    let config: EnclaveInfoToml =
-        toml::from_str(&content).expect("Content not correct, unable to load enclave info.");
-    let mut info_map = std::collections::HashMap::new();
-    for (k, v) in config.0 {
-        info_map.insert(k, EnclaveMeasurement::new(v.mr_enclave, v.mr_signer));
+/// Verifies and loads each fragment of enclave info, given with the auditor
+/// signatures over it, and merges them. Each fragment must be signed by at
+/// least `threshold` of the auditors on its own.
+pub fn load_enclave_info_fragments<T, U>(
+    fragments: &[(&str, &[U])],
+    public_keys: &[T],
+    threshold: usize,
+) -> std::result::Result<HashMap<String, EnclaveMeasurement>, EnclaveInfoError>
+where
+    T: AsRef<[u8]>,
+    U: AsRef<[u8]>,
+{
+    let mut loaded = Vec::new();
+    for (index, (content, signatures)) in fragments.iter().enumerate() {
+        let fragment = verify_enclave_info(content.as_bytes(), public_keys, signatures, threshold)
+            .and_then(|_| load_enclave_info(content))
+            .map_err(|error| EnclaveInfoError::Fragment {
+                index,
+                error: Box::new(error),
+            })?;
+        loaded.push(fragment);
     }
+    merge_enclave_info(loaded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TMS_INFO: &str = r#"
+[tms]
+mr_signer = "83d719e77deaca1470f6baf62a4d774303c899db69020f9c70ee1dfc08c7ce9e"
+mr_enclave = "0000000000000000000000000000000000000000000000000000000000000001"
+"#;
+    const FNS_INFO: &str = r#"
+[fns]
+mr_signer = "83d719e77deaca1470f6baf62a4d774303c899db69020f9c70ee1dfc08c7ce9e"
+mr_enclave = "0000000000000000000000000000000000000000000000000000000000000002"
+"#;
+    const CONFLICTING_TMS_INFO: &str = r#"
+[tms]
+mr_signer = "83d719e77deaca1470f6baf62a4d774303c899db69020f9c70ee1dfc08c7ce9e"
+mr_enclave = "0000000000000000000000000000000000000000000000000000000000000003"
+"#;
+
+    #[test]
+    fn test_merge_enclave_info() {
+        let fragments = vec![
+            load_enclave_info(TMS_INFO).unwrap(),
+            load_enclave_info(FNS_INFO).unwrap(),
+        ];
+        let merged = merge_enclave_info(fragments).unwrap();
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged["tms"].mr_enclave[SGX_HASH_SIZE - 1], 1);
+        assert_eq!(merged["fns"].mr_enclave[SGX_HASH_SIZE - 1], 2);
 
-    info_map
+        let fragments = vec![
+            load_enclave_info(TMS_INFO).unwrap(),
+            load_enclave_info(FNS_INFO).unwrap(),
+            load_enclave_info(CONFLICTING_TMS_INFO).unwrap(),
+        ];
+        match merge_enclave_info(fragments).unwrap_err() {
+            EnclaveInfoError::Duplicate(duplicates) => assert_eq!(
+                duplicates,
+                vec![DuplicateEnclave {
+                    name: "tms".to_string(),
+                    fragments: (0, 2),
+                    conflicting: true,
+                }]
+            ),
+            e => panic!("unexpected error: {}", e),
+        }
+
+        let fragments = vec![
+            load_enclave_info(TMS_INFO).unwrap(),
+            load_enclave_info(TMS_INFO).unwrap(),
+        ];
+        let err = merge_enclave_info(fragments).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "enclaves listed by more than one fragment: tms (#0 and #1)"
+        );
+    }
 }
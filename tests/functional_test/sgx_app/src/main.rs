@@ -46,14 +46,69 @@ fn test_from_unstrusted() {
     unit_tests!(
         tests::tdfs_test::read_not_exist_file,
         tests::tdfs_test::save_and_read,
+        tests::tdfs_test::save_and_read_chunked,
+        tests::tdfs_test::download_chunked,
+        tests::tdfs_test::save_and_read_with_digest_algorithm,
+        tests::tdfs_test::save_and_read_shared,
         tests::tdfs_test::list_file_api,
         tests::tdfs_test::delete_file_api,
+        tests::tdfs_test::api_health_check,
         tests::tms_test::api_get_task,
+        tests::tms_test::api_get_task_statuses,
         tests::tms_test::api_create_task,
+        tests::tms_test::api_create_task_with_limits,
+        tests::tms_test::api_create_task_with_idempotency_key,
+        tests::tms_test::api_create_content_addressed_task,
+        tests::tms_test::api_create_tasks,
+        tests::tms_test::api_validate_task,
+        tests::tms_test::api_validation_field_errors,
+        tests::tms_test::api_register_function_with_capabilities,
+        tests::tms_test::api_create_task_with_collections,
+        tests::tms_test::api_function_versions,
+        tests::tms_test::api_function_argument_schema,
+        tests::tms_test::api_function_sharing,
+        tests::tms_test::api_labels,
+        tests::tms_test::api_register_remote_function,
+        tests::tms_test::api_function_ports,
+        tests::tms_test::api_port_edges,
+        tests::tms_test::api_cancel_task,
+        tests::tms_test::api_delete_task,
+        tests::tms_test::api_wait_for_task_status,
+        tests::tms_test::api_create_scheduled_task,
         tests::tms_test::api_update_task,
         tests::tms_test::api_list_task,
+        tests::tms_test::api_list_task_pages,
+        tests::tms_test::api_query_tasks,
+        tests::tms_test::api_health_check,
+        tests::tms_test::api_max_connections,
+        tests::tms_test::api_rate_limit,
+        tests::tms_test::api_query_audit_log,
+        tests::tms_test::api_revoke_user,
+        tests::tms_test::api_reload_enclave_info,
+        tests::tms_test::api_get_enclave_measurement,
+        tests::tms_test::api_get_version_info,
+        tests::tms_test::api_list_executors,
+        tests::tms_test::api_create_task_with_max_input_files,
+        tests::tms_test::api_tls_session_resumption,
+        tests::tms_test::api_client_builder,
         tests::fns_test::api_invoke_task,
+        tests::fns_test::api_get_task_receipt,
         tests::fns_test::api_invoke_multiparty_task,
+        tests::fns_test::api_invoke_task_with_dependencies,
+        tests::fns_test::api_invoke_task_with_tampered_input,
+        tests::fns_test::api_invoke_task_with_sha384_input,
+        tests::fns_test::api_invoke_wasm_function,
+        tests::fns_test::api_invoke_python_function,
+        tests::fns_test::api_invoke_function_inline,
+        tests::fns_test::api_invoke_task_with_output_key,
+        tests::fns_test::api_invoke_task_with_dynamic_outputs,
+        tests::fns_test::api_invoke_task_with_output_format,
+        tests::fns_test::api_invoke_task_with_result_format,
+        tests::fns_test::api_invoke_task_with_missing_inputs,
+        tests::fns_test::api_invoke_task_with_arguments_file,
+        tests::fns_test::api_invoke_task_with_secrets,
+        tests::fns_test::api_stream_task_logs,
+        tests::fns_test::api_rerun_task,
     );
 }
 
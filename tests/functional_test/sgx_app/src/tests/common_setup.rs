@@ -57,8 +57,30 @@ pub(crate) const USER_FOUR: User = User {
     user_token: "token4",
 };
 
+pub(crate) const USER_FIVE: User = User {
+    user_id: "user_five",
+    user_token: "token5",
+};
+
+pub(crate) const USER_SIX: User = User {
+    user_id: "user_six",
+    user_token: "token6",
+};
+
+// Revoked and restored by `api_revoke_user`.
+pub(crate) const USER_REVOKED: User = User {
+    user_id: "user_revoked",
+    user_token: "token7",
+};
+
+// One of the `audit_log.admins` of the runtime config.
+pub(crate) const USER_ADMIN: User = User {
+    user_id: "admin",
+    user_token: "admin_token",
+};
+
 #[inline]
-fn target_tms() -> TargetDesc {
+pub(crate) fn target_tms() -> TargetDesc {
     TargetDesc::new(
         SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 5554),
         OutboundDesc::Sgx(get_trusted_enclave_attr(vec!["tms"])),
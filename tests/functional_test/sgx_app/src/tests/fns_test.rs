@@ -16,8 +16,19 @@
 // under the License.
 
 use super::common_setup::{
-    read_file_for_user, save_file_for_user, setup_fns_client, setup_tms_external_client, USER_ONE,
-    USER_TWO,
+    read_file_for_user, save_file_for_user, setup_fns_client, setup_tdfs_external_client,
+    setup_tms_external_client, USER_ONE, USER_THREE, USER_TWO,
+};
+use mesatee_core::config::{self, get_trusted_enclave_attr};
+use mesatee_core::ErrorKind;
+use std::env;
+use std::fs;
+use std::path::Path;
+use teaclave_config::runtime_config::MissingInputPolicy;
+use tms_external_client::{verify_task_receipt, TMSClient};
+use tms_external_proto::{
+    CreateTaskResponse, Executor, FileFormat, LogStream, OutputKey, PortSpec, ResultFormat,
+    TaskArguments, TaskResultRecord, TaskSecret, TaskStatus,
 };
 
 pub fn api_invoke_task() {
@@ -94,6 +105,57 @@ pub fn api_invoke_task() {
     assert!(response.is_err());
 }
 
+pub fn api_get_task_receipt() {
+    trace!("Test FNS: get the signed receipt of a finished task.");
+    let file_id = save_file_for_user(&USER_ONE, b"abc", "./receipt_input");
+
+    let function_name = "echo_file";
+    let mut tms_client = setup_tms_external_client(&USER_ONE);
+    let launch_info = tms_client
+        .request_create_task(function_name, &[], &[&file_id])
+        .unwrap();
+
+    // Not finished yet
+    let response = tms_client.request_get_task_receipt(&launch_info.task_id);
+    assert!(response.is_err());
+
+    let mut fns_client = setup_fns_client(launch_info.ip, launch_info.port);
+    fns_client
+        .invoke_task(
+            &launch_info.task_id,
+            function_name,
+            &launch_info.task_token,
+            Some(file_id.as_str()),
+        )
+        .unwrap();
+
+    let receipt = tms_client
+        .request_get_task_receipt(&launch_info.task_id)
+        .unwrap()
+        .receipt;
+    let tms_attr = get_trusted_enclave_attr(vec!["tms"]);
+    let task_receipt = verify_task_receipt(&receipt, tms_attr.clone()).unwrap();
+    assert_eq!(task_receipt.task_id, launch_info.task_id);
+    assert_eq!(task_receipt.user_id, USER_ONE.user_id);
+    assert_eq!(task_receipt.function_name, function_name);
+    assert_eq!(task_receipt.input_files.len(), 1);
+    assert_eq!(task_receipt.input_files[0].file_id, file_id);
+    assert_eq!(task_receipt.input_files[0].digest_algorithm, "sha256");
+    assert!(task_receipt.finished_at >= task_receipt.created_at);
+
+    // A receipt which was changed after it was signed is rejected
+    let mut tampered = receipt.clone();
+    tampered.receipt = tampered
+        .receipt
+        .replace(&launch_info.task_id, "another_task");
+    assert!(verify_task_receipt(&tampered, tms_attr).is_err());
+
+    // Other users cannot get the receipt
+    let mut other_client = setup_tms_external_client(&USER_TWO);
+    let response = other_client.request_get_task_receipt(&launch_info.task_id);
+    assert!(response.is_err());
+}
+
 pub fn api_invoke_multiparty_task() {
     trace!("Test FNS: invoke multiparty task.");
     let user_file_id = save_file_for_user(&USER_ONE, b"abc", "concat_file1");
@@ -129,3 +191,989 @@ pub fn api_invoke_multiparty_task() {
 
     assert_eq!(plaintxt, b"abcdef");
 }
+
+pub fn api_invoke_task_with_dependencies() {
+    trace!("Test FNS: invoke task with dependencies.");
+
+    let mut tms_client = setup_tms_external_client(&USER_ONE);
+    let function_name = "echo";
+    let payload = Some("abc");
+
+    // Unknown dependency
+    let response =
+        tms_client.request_create_task_with_dependencies(function_name, &[], &[], &["bad_task_id"]);
+    assert!(response.is_err());
+
+    let first = tms_client
+        .request_create_task(function_name, &[], &[])
+        .unwrap();
+    let second = tms_client
+        .request_create_task_with_dependencies(function_name, &[], &[], &[&first.task_id])
+        .unwrap();
+
+    let task_info = tms_client
+        .request_get_task(&second.task_id)
+        .unwrap()
+        .task_info;
+    assert_eq!(task_info.status, TaskStatus::Created);
+    assert_eq!(task_info.dependencies, vec![first.task_id.to_string()]);
+
+    // Not ready before its dependency finished
+    let mut fns_client = setup_fns_client(second.ip, second.port);
+    let response =
+        fns_client.invoke_task(&second.task_id, function_name, &second.task_token, payload);
+    assert!(response.is_err());
+
+    let mut fns_client = setup_fns_client(first.ip, first.port);
+    let _ = fns_client
+        .invoke_task(&first.task_id, function_name, &first.task_token, payload)
+        .unwrap();
+
+    let task_info = tms_client
+        .request_get_task(&second.task_id)
+        .unwrap()
+        .task_info;
+    assert_eq!(task_info.status, TaskStatus::Ready);
+
+    let mut fns_client = setup_fns_client(second.ip, second.port);
+    let response = fns_client
+        .invoke_task(&second.task_id, function_name, &second.task_token, payload)
+        .unwrap();
+    assert_eq!("abc", response.result.as_str());
+}
+
+pub fn api_invoke_task_with_tampered_input() {
+    trace!("Test FNS: invoke task with tampered input file.");
+    let file_id = save_file_for_user(&USER_ONE, b"abc", "tampered_file");
+
+    // Flip a bit of the stored ciphertext
+    let mut tdfs_client = setup_tdfs_external_client(&USER_ONE);
+    let file_info = tdfs_client.request_get_file(&file_id).unwrap().file_info;
+    let storage_dir = env::var("MESATEE_STORAGE_DIR").unwrap_or_else(|_| "/tmp".into());
+    let access_path = Path::new(&storage_dir).join(&file_info.access_path);
+    let mut ciphertxt = fs::read(&access_path).unwrap();
+    ciphertxt[0] ^= 1;
+    fs::write(&access_path, &ciphertxt).unwrap();
+
+    let function_name = "echo_file";
+    let mut tms_client = setup_tms_external_client(&USER_ONE);
+    let launch_info = tms_client
+        .request_create_task(function_name, &[], &[&file_id])
+        .unwrap();
+
+    let mut fns_client = setup_fns_client(launch_info.ip, launch_info.port);
+    let response = fns_client.invoke_task(
+        &launch_info.task_id,
+        function_name,
+        &launch_info.task_token,
+        Some(file_id.as_str()),
+    );
+    assert!(response.is_err());
+
+    let task_info = tms_client
+        .request_get_task(&launch_info.task_id)
+        .unwrap()
+        .task_info;
+    assert_eq!(task_info.status, TaskStatus::Failed);
+}
+
+pub fn api_invoke_task_with_sha384_input() {
+    trace!("Test FNS: invoke task with an input file digested with SHA-384.");
+    let file_path = "./sha384_input";
+    fs::write(file_path, b"abc").unwrap();
+    let mut tdfs_client = setup_tdfs_external_client(&USER_ONE);
+    let file_id = tdfs_client
+        .save_file_with_digest_algorithm(file_path, "sha384_input", &[], "sha384")
+        .unwrap();
+
+    let function_name = "echo_file";
+    let mut tms_client = setup_tms_external_client(&USER_ONE);
+    let launch_info = tms_client
+        .request_create_task(function_name, &[], &[&file_id])
+        .unwrap();
+
+    let mut fns_client = setup_fns_client(launch_info.ip, launch_info.port);
+    let response = fns_client
+        .invoke_task(
+            &launch_info.task_id,
+            function_name,
+            &launch_info.task_token,
+            Some(file_id.as_str()),
+        )
+        .unwrap();
+    assert_eq!("abc", response.result.as_str());
+}
+
+pub fn api_invoke_wasm_function() {
+    trace!("Test FNS: invoke WASM function.");
+
+    let mut tms_client = setup_tms_external_client(&USER_ONE);
+
+    // Not a WASM module
+    let response = tms_client.request_register_wasm_function("answer", "", "YWJj");
+    assert!(response.is_err());
+
+    // A memory of 4GiB, over the memory limit of tasks
+    // (module
+    //   (memory (export "memory") 65536 65536)
+    //   (func (export "entrypoint") (result i32) i32.const 42))
+    let module =
+        "AGFzbQEAAAABBQFgAAF/AwIBAAUIAQGAgASAgAQHFwIGbWVtb3J5AgAKZW50cnlwb2ludAAACgYBBABBKgs=";
+    let err = tms_client
+        .request_register_wasm_function("answer", "", module)
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInputError);
+
+    // (module
+    //   (memory (export "memory") 1 1)
+    //   (func (export "entrypoint") (result i32) i32.const 42))
+    let module = "AGFzbQEAAAABBQFgAAF/AwIBAAUEAQEBAQcXAgZtZW1vcnkCAAplbnRyeXBvaW50AAAKBgEEAEEqCw==";
+    let function_id = tms_client
+        .request_register_wasm_function("answer", "returns 42", module)
+        .unwrap()
+        .function_id;
+    let function_info = tms_client
+        .request_get_function(&function_id)
+        .unwrap()
+        .function_info;
+    assert_eq!(function_info.executor, Executor::Wasm);
+
+    let launch_info = tms_client
+        .request_create_task_for_function(&function_id, &[], &[])
+        .unwrap();
+    let task_info = tms_client
+        .request_get_task(&launch_info.task_id)
+        .unwrap()
+        .task_info;
+
+    let mut fns_client = setup_fns_client(launch_info.ip, launch_info.port);
+    let response = fns_client
+        .invoke_task(
+            &launch_info.task_id,
+            &task_info.function_name,
+            &launch_info.task_token,
+            None,
+        )
+        .unwrap();
+    assert_eq!("42", response.result.as_str());
+}
+
+pub fn api_invoke_python_function() {
+    trace!("Test FNS: invoke Python function.");
+
+    let mut tms_client = setup_tms_external_client(&USER_ONE);
+
+    // Imports outside the allowlist
+    let source = "import os\n\ndef main(inputs, output):\n    return 1\n";
+    let response = tms_client.request_register_python_function("upper", "", source);
+    assert!(response.is_err());
+    // Unbalanced brackets
+    let source = "def main(inputs, output:\n    return 1\n";
+    let response = tms_client.request_register_python_function("upper", "", source);
+    assert!(response.is_err());
+    // Imports and evaluation through builtins or interpreter internals
+    let bypasses = [
+        "__import__('os')",
+        "eval('__imp' + 'ort__(\"os\")')",
+        "exec 'import os'",
+        "compile('import os', '', 'exec')",
+        "getattr(inputs, 'read')",
+        "globals()['main']",
+        "vars()",
+        "execfile('script.py')",
+        "reload(string)",
+        "input()",
+        "string.__dict__['__buil' + 'tins__']",
+        "().__class__.__base__",
+        "string.Formatter().get_field('0.func_glo' + 'bals', [main], {})",
+        "main.__call__.__self__",
+    ];
+    for bypass in bypasses.iter() {
+        let source = format!(
+            "import string\n\ndef main(inputs, output):\n    {}\n    return 1\n",
+            bypass
+        );
+        let err = tms_client
+            .request_register_python_function("upper", "", &source)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInputError, "{}", bypass);
+    }
+    // Attributes looked up by computed names, up to the closure of the import
+    // guard
+    let source = "import operator\n\ndef main(inputs, output):\n    builtins = operator.attrgetter('func_glo' + 'bals')(main)['__builtins__']\n    cell = operator.attrgetter('func_clo' + 'sure')(builtins['__imp' + 'ort__'])[0]\n    return cell.cell_contents('os').getcwd()\n";
+    let err = tms_client
+        .request_register_python_function("escape", "", source)
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInputError);
+
+    // Allowed modules may be imported by the functions of the script, and
+    // builtins such as compile stay usable as attributes
+    let source = "def main(inputs, output):\n    import re\n    output.write(re.compile('b+').sub('-', inputs[0].read()))\n    output.close()\n    return 'done'\n";
+    let function_id = tms_client
+        .request_register_python_function("dash", "replaces runs of b", source)
+        .unwrap()
+        .function_id;
+    let response = tms_client
+        .request_invoke_function(&function_id, &[b"abbc"], &TaskArguments::new())
+        .unwrap();
+    assert_eq!(response.outputs, vec![b"a-c".to_vec()]);
+
+    let source = "import string\n\ndef main(inputs, output):\n    output.write(string.upper(inputs[0].read()))\n    output.close()\n    return output.file_id\n";
+    let function_id = tms_client
+        .request_register_python_function("upper", "upper cases a file", source)
+        .unwrap()
+        .function_id;
+
+    let file_id = save_file_for_user(&USER_ONE, b"abc", "./python_function_input");
+    let launch_info = tms_client
+        .request_create_task_for_function(&function_id, &[], &[&file_id])
+        .unwrap();
+    let task_info = tms_client
+        .request_get_task(&launch_info.task_id)
+        .unwrap()
+        .task_info;
+
+    let mut fns_client = setup_fns_client(launch_info.ip, launch_info.port);
+    let _ = fns_client
+        .invoke_task(
+            &launch_info.task_id,
+            &task_info.function_name,
+            &launch_info.task_token,
+            None,
+        )
+        .unwrap();
+    let task_info = tms_client
+        .request_get_task(&launch_info.task_id)
+        .unwrap()
+        .task_info;
+    assert_eq!(task_info.status, TaskStatus::Finished);
+    let output_file_id = &task_info.user_private_result_file_id[0];
+    let plaintxt = read_file_for_user(&USER_ONE, output_file_id);
+    assert_eq!(plaintxt, b"ABC");
+}
+
+pub fn api_invoke_function_inline() {
+    trace!("Test FNS: invoke function with inline inputs.");
+
+    let mut tms_client = setup_tms_external_client(&USER_ONE);
+    let source = "import string\n\ndef main(inputs, output):\n    output.write(string.upper(inputs[0].read()))\n    output.close()\n    return 'done'\n";
+    let function_id = tms_client
+        .request_register_python_function("upper", "upper cases a file", source)
+        .unwrap()
+        .function_id;
+
+    let response = tms_client
+        .request_invoke_function(&function_id, &[b"abc"], &TaskArguments::new())
+        .unwrap();
+    assert_eq!(response.outputs, vec![b"ABC".to_vec()]);
+    assert!(!response.result.is_empty());
+    let task_info = tms_client
+        .request_get_task(&response.task_id)
+        .unwrap()
+        .task_info;
+    assert_eq!(task_info.status, TaskStatus::Finished);
+
+    // Inline inputs are capped
+    let large_input = vec![b'a'; 64 * 1024 + 1];
+    let err = tms_client
+        .request_invoke_function(&function_id, &[&large_input], &TaskArguments::new())
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::MsgSizeLimitExceedError);
+}
+
+pub fn api_invoke_task_with_output_key() {
+    trace!("Test FNS: invoke task with an output key.");
+
+    let mut tms_client = setup_tms_external_client(&USER_ONE);
+    let source = "import string\n\ndef main(inputs, output):\n    output.write(string.upper(inputs[0].read()))\n    output.close()\n    return output.file_id\n";
+    let function_id = tms_client
+        .request_register_python_function("upper", "upper cases a file", source)
+        .unwrap()
+        .function_id;
+    let file_id = save_file_for_user(&USER_ONE, b"abc", "./output_key_input");
+
+    // Key material missing
+    let short_key = OutputKey::new("user_one_key", &[7u8; 16]);
+    let response =
+        tms_client.request_create_task_with_output_key(&function_id, &[&file_id], &short_key);
+    assert!(response.is_err());
+    let unnamed_key = OutputKey::new("", &[7u8; 32]);
+    let response =
+        tms_client.request_create_task_with_output_key(&function_id, &[&file_id], &unnamed_key);
+    assert!(response.is_err());
+
+    let kek = [7u8; 32];
+    let output_key = OutputKey::new("user_one_key", &kek);
+    let launch_info = tms_client
+        .request_create_task_with_output_key(&function_id, &[&file_id], &output_key)
+        .unwrap();
+    let task_info = tms_client
+        .request_get_task(&launch_info.task_id)
+        .unwrap()
+        .task_info;
+    assert_eq!(task_info.output_key_id, Some("user_one_key".to_string()));
+
+    let mut fns_client = setup_fns_client(launch_info.ip, launch_info.port);
+    let _ = fns_client
+        .invoke_task(
+            &launch_info.task_id,
+            &task_info.function_name,
+            &launch_info.task_token,
+            None,
+        )
+        .unwrap();
+    let task_info = tms_client
+        .request_get_task(&launch_info.task_id)
+        .unwrap()
+        .task_info;
+    assert_eq!(task_info.status, TaskStatus::Finished);
+    let output_file_id = &task_info.user_private_result_file_id[0];
+
+    // Only the wrapped key is stored with the output
+    let mut tdfs_client = setup_tdfs_external_client(&USER_ONE);
+    let file_info = tdfs_client
+        .request_get_file(output_file_id)
+        .unwrap()
+        .file_info;
+    assert!(file_info.key_config.is_none());
+    let wrapped_key = file_info.wrapped_key.unwrap();
+    assert_eq!(wrapped_key.key_id, "user_one_key");
+    assert!(tdfs_client.read_file(output_file_id).is_err());
+    assert!(tdfs_client
+        .read_file_with_key(output_file_id, &[8u8; 32])
+        .is_err());
+    let plaintxt = tdfs_client
+        .read_file_with_key(output_file_id, &kek)
+        .unwrap();
+    assert_eq!(plaintxt, b"ABC");
+}
+
+pub fn api_invoke_task_with_dynamic_outputs() {
+    trace!("Test FNS: invoke task producing files on a dynamic output port.");
+    let mut tms_client = setup_tms_external_client(&USER_ONE);
+    let output_ports = [PortSpec::new_dynamic_output("parts", "text")];
+    let function_id = tms_client
+        .request_register_function_with_ports("partition", "splits lines", None, &[], &output_ports)
+        .unwrap()
+        .function_id;
+    let file_id = save_file_for_user(&USER_ONE, b"a\nb\nc\nd\ne\nf", "./partition_input");
+    let launch_info = tms_client
+        .request_create_task_for_function(&function_id, &[], &[&file_id])
+        .unwrap();
+
+    let mut fns_client = setup_fns_client(launch_info.ip, launch_info.port);
+    let _ = fns_client
+        .invoke_task(
+            &launch_info.task_id,
+            "partition",
+            &launch_info.task_token,
+            Some("3"),
+        )
+        .unwrap();
+    let task_info = tms_client
+        .request_get_task(&launch_info.task_id)
+        .unwrap()
+        .task_info;
+    assert_eq!(task_info.status, TaskStatus::Finished);
+
+    // All three partitions are recorded, with the digests of their content
+    let outputs = &task_info.dynamic_outputs["parts"];
+    assert_eq!(outputs.len(), 3);
+    let mut tdfs_client = setup_tdfs_external_client(&USER_ONE);
+    let mut partitions = Vec::new();
+    for output in outputs.iter() {
+        assert!(task_info
+            .user_private_result_file_id
+            .contains(&output.file_id));
+        let file_info = tdfs_client
+            .request_get_file(&output.file_id)
+            .unwrap()
+            .file_info;
+        assert_eq!(output.digest_algorithm, file_info.digest_algorithm);
+        assert_eq!(output.digest, file_info.sha256);
+        partitions.push(read_file_for_user(&USER_ONE, &output.file_id));
+    }
+    assert_eq!(
+        partitions,
+        vec![b"a\nb".to_vec(), b"c\nd".to_vec(), b"e\nf".to_vec()]
+    );
+}
+
+pub fn api_invoke_task_with_output_format() {
+    trace!("Test FNS: invoke task producing files of a declared format.");
+    let mut tms_client = setup_tms_external_client(&USER_ONE);
+
+    // Formats are checked when the function is registered
+    let output_ports = [PortSpec::new_dynamic_output("parts", "text")
+        .with_format(FileFormat::new("text plain", None))];
+    let err = tms_client
+        .request_register_function_with_ports("partition", "splits lines", None, &[], &output_ports)
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInputError);
+
+    let format = FileFormat::new("text/plain", Some("https://example.com/lines.schema"));
+    let output_ports = [PortSpec::new_dynamic_output("parts", "text").with_format(format.clone())];
+    let function_id = tms_client
+        .request_register_function_with_ports("partition", "splits lines", None, &[], &output_ports)
+        .unwrap()
+        .function_id;
+    let file_id = save_file_for_user(&USER_ONE, b"a\nb\nc\nd", "./partition_format_input");
+    let launch_info = tms_client
+        .request_create_task_for_function(&function_id, &[], &[&file_id])
+        .unwrap();
+
+    let mut fns_client = setup_fns_client(launch_info.ip, launch_info.port);
+    let _ = fns_client
+        .invoke_task(
+            &launch_info.task_id,
+            "partition",
+            &launch_info.task_token,
+            Some("2"),
+        )
+        .unwrap();
+    let task_info = tms_client
+        .request_get_task(&launch_info.task_id)
+        .unwrap()
+        .task_info;
+    assert_eq!(task_info.status, TaskStatus::Finished);
+
+    // Every partition carries the declared format, in TMS and in TDFS
+    let outputs = &task_info.dynamic_outputs["parts"];
+    assert_eq!(outputs.len(), 2);
+    let mut tdfs_client = setup_tdfs_external_client(&USER_ONE);
+    for output in outputs.iter() {
+        assert_eq!(task_info.output_formats[&output.file_id], format);
+        let file_info = tdfs_client
+            .request_get_file(&output.file_id)
+            .unwrap()
+            .file_info;
+        let file_format = file_info.format.unwrap();
+        assert_eq!(file_format.content_type, format.content_type);
+        assert_eq!(file_format.schema, format.schema);
+    }
+}
+
+pub fn api_invoke_task_with_result_format() {
+    trace!("Test FNS: invoke task with a serialized result.");
+    let mut tms_client = setup_tms_external_client(&USER_ONE);
+    let function_name = "echo";
+    let payload = "abc";
+    let formats = [
+        (ResultFormat::Text, None),
+        (ResultFormat::Json, Some("application/json")),
+        (ResultFormat::Cbor, Some("application/cbor")),
+    ];
+    for (result_format, content_type) in formats.iter() {
+        let launch_info = tms_client
+            .request_create_task_with_result_format(function_name, &[], *result_format)
+            .unwrap();
+        let mut fns_client = setup_fns_client(launch_info.ip, launch_info.port);
+        let response = fns_client
+            .invoke_task(
+                &launch_info.task_id,
+                function_name,
+                &launch_info.task_token,
+                Some(payload),
+            )
+            .unwrap();
+        assert_eq!(response.result, payload);
+        let task_info = tms_client
+            .request_get_task(&launch_info.task_id)
+            .unwrap()
+            .task_info;
+        assert_eq!(task_info.status, TaskStatus::Finished);
+        assert_eq!(task_info.result_format, *result_format);
+
+        let content_type = match content_type {
+            Some(content_type) => content_type,
+            None => {
+                // Text results have no file
+                assert_eq!(task_info.result_file_id, None);
+                continue;
+            }
+        };
+        let file_id = task_info.result_file_id.unwrap();
+        assert_eq!(
+            task_info.output_formats[&file_id].content_type,
+            *content_type
+        );
+        let data = read_file_for_user(&USER_ONE, &file_id);
+        let record: TaskResultRecord = match result_format {
+            ResultFormat::Cbor => serde_cbor::from_slice(&data).unwrap(),
+            _ => serde_json::from_slice(&data).unwrap(),
+        };
+        assert_eq!(record.task_id, launch_info.task_id);
+        assert_eq!(record.output, payload);
+    }
+}
+
+// Creates a task of `function_id` with new files on its ports "data" and
+// "extra".
+fn create_task_with_inputs(
+    tms_client: &mut TMSClient,
+    function_id: &str,
+    name: &str,
+) -> (CreateTaskResponse, String, String) {
+    let data = save_file_for_user(&USER_ONE, b"data", &format!("./{}_data", name));
+    let extra = save_file_for_user(&USER_ONE, b"extra", &format!("./{}_extra", name));
+    let launch_info = tms_client
+        .request_create_task_with_ports(function_id, &[("data", &data), ("extra", &extra)])
+        .unwrap();
+    (launch_info, data, extra)
+}
+
+pub fn api_invoke_task_with_missing_inputs() {
+    trace!("Test FNS: invoke task whose input files were deleted.");
+    let mut tms_client = setup_tms_external_client(&USER_ONE);
+    let mut tdfs_client = setup_tdfs_external_client(&USER_ONE);
+    let input_ports = [
+        PortSpec::new("data", "", true),
+        PortSpec::new("extra", "", false),
+    ];
+    let function_id = tms_client
+        .request_register_function_with_ports("echo", "optional extra", None, &input_ports, &[])
+        .unwrap()
+        .function_id;
+
+    // A missing mandatory input fails the task before it runs, whatever the
+    // policy
+    let (launch_info, data, _) =
+        create_task_with_inputs(&mut tms_client, &function_id, "missing_mandatory");
+    tdfs_client.request_del_file(&data).unwrap();
+    let mut fns_client = setup_fns_client(launch_info.ip, launch_info.port);
+    let err = fns_client
+        .invoke_task(&launch_info.task_id, "echo", &launch_info.task_token, None)
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    let task_info = tms_client
+        .request_get_task(&launch_info.task_id)
+        .unwrap()
+        .task_info;
+    assert_eq!(task_info.status, TaskStatus::Failed);
+    let error = task_info.attempts.last().unwrap().error.clone().unwrap();
+    assert!(error.message.contains(&data));
+    assert!(!error.retryable);
+
+    // A missing optional input is skipped if the policy allows it
+    let (launch_info, data, extra) =
+        create_task_with_inputs(&mut tms_client, &function_id, "missing_optional");
+    tdfs_client.request_del_file(&extra).unwrap();
+    let mut fns_client = setup_fns_client(launch_info.ip, launch_info.port);
+    let response = fns_client.invoke_task(
+        &launch_info.task_id,
+        "echo",
+        &launch_info.task_token,
+        Some("skipped"),
+    );
+    let task_info = tms_client
+        .request_get_task(&launch_info.task_id)
+        .unwrap()
+        .task_info;
+    match config::runtime_config().task_inputs.missing_inputs {
+        MissingInputPolicy::Fail => {
+            assert_eq!(response.unwrap_err().kind(), ErrorKind::NotFound);
+            assert_eq!(task_info.status, TaskStatus::Failed);
+        }
+        MissingInputPolicy::SkipOptional => {
+            assert_eq!(response.unwrap().result, "skipped");
+            assert_eq!(task_info.status, TaskStatus::Finished);
+            assert_eq!(task_info.input_ports.len(), 1);
+            assert_eq!(task_info.input_ports["data"], data);
+        }
+    }
+}
+
+pub fn api_invoke_task_with_arguments_file() {
+    trace!("Test FNS: invoke task whose arguments come from a file.");
+    let mut tms_client = setup_tms_external_client(&USER_ONE);
+    let mut tdfs_client = setup_tdfs_external_client(&USER_ONE);
+    let function_name = "echo_arg";
+    let file_id = save_file_for_user(
+        &USER_ONE,
+        br#"{"threshold": 3, "mode": "fast"}"#,
+        "./arguments_file_input",
+    );
+    let mut arguments = TaskArguments::new();
+    arguments.insert("mode".to_string(), "exact".into());
+
+    // The file is pinned to its digest when the task is created
+    let staged_info = tms_client
+        .request_create_task_with_arguments_file(function_name, &arguments, &file_id)
+        .unwrap();
+    let task_info = tms_client
+        .request_get_task(&staged_info.task_id)
+        .unwrap()
+        .task_info;
+    assert_eq!(task_info.status, TaskStatus::Ready);
+    assert_eq!(task_info.arguments, arguments);
+    let arguments_file = task_info.arguments_file.unwrap();
+    assert_eq!(arguments_file.file_id, file_id);
+    let file_info = tdfs_client.request_get_file(&file_id).unwrap().file_info;
+    assert_eq!(arguments_file.digest, file_info.sha256);
+
+    // The file is read when the task runs, and inline arguments take
+    // precedence
+    for (name, value) in [("threshold", "3"), ("mode", "\"exact\"")].iter() {
+        let launch_info = tms_client
+            .request_create_task_with_arguments_file(function_name, &arguments, &file_id)
+            .unwrap();
+        let mut fns_client = setup_fns_client(launch_info.ip, launch_info.port);
+        let response = fns_client
+            .invoke_task(
+                &launch_info.task_id,
+                function_name,
+                &launch_info.task_token,
+                Some(name),
+            )
+            .unwrap();
+        assert_eq!(response.result, *value);
+    }
+
+    // Files the creator cannot read are rejected
+    let other_file_id = save_file_for_user(&USER_TWO, b"{}", "./arguments_file_other");
+    let err = tms_client
+        .request_create_task_with_arguments_file(function_name, &arguments, &other_file_id)
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+
+    // A file which is not an object of arguments fails the task
+    let bad_file_id = save_file_for_user(&USER_ONE, b"[1, 2]", "./arguments_file_bad");
+    let launch_info = tms_client
+        .request_create_task_with_arguments_file(function_name, &arguments, &bad_file_id)
+        .unwrap();
+    let mut fns_client = setup_fns_client(launch_info.ip, launch_info.port);
+    let err = fns_client
+        .invoke_task(
+            &launch_info.task_id,
+            function_name,
+            &launch_info.task_token,
+            Some("mode"),
+        )
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInputError);
+    let task_info = tms_client
+        .request_get_task(&launch_info.task_id)
+        .unwrap()
+        .task_info;
+    assert_eq!(task_info.status, TaskStatus::Failed);
+
+    // A file deleted since the task was created fails it at staging
+    tdfs_client.request_del_file(&file_id).unwrap();
+    let mut fns_client = setup_fns_client(staged_info.ip, staged_info.port);
+    let err = fns_client
+        .invoke_task(
+            &staged_info.task_id,
+            function_name,
+            &staged_info.task_token,
+            Some("mode"),
+        )
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    let task_info = tms_client
+        .request_get_task(&staged_info.task_id)
+        .unwrap()
+        .task_info;
+    assert_eq!(task_info.status, TaskStatus::Failed);
+    let error = task_info.attempts.last().unwrap().error.clone().unwrap();
+    assert!(error.message.contains(&file_id));
+    assert!(!error.retryable);
+}
+
+pub fn api_invoke_task_with_secrets() {
+    trace!("Test FNS: invoke task with secrets.");
+    let mut tms_client = setup_tms_external_client(&USER_ONE);
+    let mut other_client = setup_tms_external_client(&USER_TWO);
+    let function_name = "echo_env";
+    tms_client
+        .request_put_secret("fns_api_key", "s3cr3t", &[USER_TWO.user_id])
+        .unwrap();
+    other_client
+        .request_put_secret("fns_partner_key", "p4rtn3r", &[])
+        .unwrap();
+
+    // Secrets which do not exist or are not shared cannot be referenced
+    let secrets = [TaskSecret::stored("API_KEY", "no_such_secret")];
+    let err = tms_client
+        .request_create_task_with_secrets(function_name, &[], &secrets)
+        .unwrap_err();
+    assert_eq!(err.kind(), mesatee_core::ErrorKind::NotFound);
+    let secrets = [TaskSecret::shared(
+        "API_KEY",
+        USER_TWO.user_id,
+        "fns_partner_key",
+    )];
+    let err = tms_client
+        .request_create_task_with_secrets(function_name, &[], &secrets)
+        .unwrap_err();
+    assert_eq!(err.kind(), mesatee_core::ErrorKind::PermissionDenied);
+    let mut third_client = setup_tms_external_client(&USER_THREE);
+    let secrets = [TaskSecret::shared(
+        "API_KEY",
+        USER_ONE.user_id,
+        "fns_api_key",
+    )];
+    let err = third_client
+        .request_create_task_with_secrets(function_name, &[], &secrets)
+        .unwrap_err();
+    assert_eq!(err.kind(), mesatee_core::ErrorKind::PermissionDenied);
+    let secrets = [
+        TaskSecret::inline("API_KEY", "a"),
+        TaskSecret::inline("API_KEY", "b"),
+    ];
+    assert!(tms_client
+        .request_create_task_with_secrets(function_name, &[], &secrets)
+        .is_err());
+
+    // The executor gets both stored and inline secrets
+    let secrets = [
+        TaskSecret::stored("API_KEY", "fns_api_key"),
+        TaskSecret::inline("SALT", "s4lt"),
+    ];
+    for (env, value) in [("API_KEY", "s3cr3t"), ("SALT", "s4lt")].iter() {
+        let launch_info = tms_client
+            .request_create_task_with_secrets(function_name, &[], &secrets)
+            .unwrap();
+
+        // Task reads never return the values
+        let task_info = tms_client
+            .request_get_task(&launch_info.task_id)
+            .unwrap()
+            .task_info;
+        assert_eq!(task_info.secrets.len(), 2);
+        assert!(task_info
+            .secrets
+            .iter()
+            .all(|secret| secret.value.is_none()));
+        assert_eq!(task_info.secrets[0].name, Some("fns_api_key".to_string()));
+
+        let mut fns_client = setup_fns_client(launch_info.ip, launch_info.port);
+        let response = fns_client
+            .invoke_task(
+                &launch_info.task_id,
+                function_name,
+                &launch_info.task_token,
+                Some(*env),
+            )
+            .unwrap();
+        assert_eq!(response.result, *value);
+    }
+
+    // A secret shared with another user can be referenced by them
+    let secrets = [TaskSecret::shared(
+        "API_KEY",
+        USER_ONE.user_id,
+        "fns_api_key",
+    )];
+    let launch_info = other_client
+        .request_create_task_with_secrets(function_name, &[], &secrets)
+        .unwrap();
+    let mut fns_client = setup_fns_client(launch_info.ip, launch_info.port);
+    let response = fns_client
+        .invoke_task(
+            &launch_info.task_id,
+            function_name,
+            &launch_info.task_token,
+            Some("API_KEY"),
+        )
+        .unwrap();
+    assert_eq!(response.result, "s3cr3t");
+
+    // No longer shared, the secret fails the task when it is invoked
+    let launch_info = other_client
+        .request_create_task_with_secrets(function_name, &[], &secrets)
+        .unwrap();
+    tms_client
+        .request_put_secret("fns_api_key", "s3cr3t", &[])
+        .unwrap();
+    let mut fns_client = setup_fns_client(launch_info.ip, launch_info.port);
+    assert!(fns_client
+        .invoke_task(
+            &launch_info.task_id,
+            function_name,
+            &launch_info.task_token,
+            Some("API_KEY"),
+        )
+        .is_err());
+    let task_info = other_client
+        .request_get_task(&launch_info.task_id)
+        .unwrap()
+        .task_info;
+    assert_eq!(task_info.status, TaskStatus::Failed);
+
+    // Listed without values, and gone once deleted
+    let secrets = tms_client.request_list_secrets().unwrap().secrets;
+    let secret = secrets
+        .iter()
+        .find(|secret| secret.name == "fns_api_key")
+        .unwrap();
+    assert!(secret.readers.is_empty());
+    tms_client.request_delete_secret("fns_api_key").unwrap();
+    other_client
+        .request_delete_secret("fns_partner_key")
+        .unwrap();
+    assert!(tms_client.request_delete_secret("fns_api_key").is_err());
+    let secrets = tms_client.request_list_secrets().unwrap().secrets;
+    assert!(secrets.iter().all(|secret| secret.name != "fns_api_key"));
+}
+
+pub fn api_stream_task_logs() {
+    trace!("Test FNS: stream task logs.");
+    let mut tms_client = setup_tms_external_client(&USER_ONE);
+    let function_name = "echo_log";
+    let secrets = [TaskSecret::inline("TOKEN", "s3cr3t")];
+    let launch_info = tms_client
+        .request_create_task_with_secrets(function_name, &[], &secrets)
+        .unwrap();
+
+    // Nothing is written before the task runs
+    let response = tms_client
+        .request_stream_task_logs(&launch_info.task_id, 0, 0)
+        .unwrap();
+    assert!(response.lines.is_empty());
+    assert_eq!(response.next, 0);
+    assert!(!response.complete);
+
+    let mut fns_client = setup_fns_client(launch_info.ip, launch_info.port);
+    let payload = "first\nsecond with s3cr3t\nthird";
+    fns_client
+        .invoke_task(
+            &launch_info.task_id,
+            function_name,
+            &launch_info.task_token,
+            Some(payload),
+        )
+        .unwrap();
+
+    // Lines come in the order they were completed, the last line without a
+    // line break once the task ends, and secrets are redacted
+    let mut lines = Vec::new();
+    let truncated = tms_client
+        .stream_task_logs(&launch_info.task_id, |line| {
+            lines.push((line.stream, line.line.clone()))
+        })
+        .unwrap();
+    assert!(!truncated);
+    let expected = vec![
+        (LogStream::Stdout, "first".to_string()),
+        (LogStream::Stdout, "second with [REDACTED]".to_string()),
+        (LogStream::Stderr, "wrote 30 bytes".to_string()),
+        (LogStream::Stdout, "third".to_string()),
+    ];
+    assert_eq!(lines, expected);
+
+    // Tailing resumes from a line
+    let response = tms_client
+        .request_stream_task_logs(&launch_info.task_id, 2, 0)
+        .unwrap();
+    assert_eq!(response.lines.len(), 2);
+    assert_eq!(response.lines[1].line, "third");
+    assert_eq!(response.next, 4);
+    assert!(response.complete);
+
+    // The log is for the task creator alone
+    let mut other_client = setup_tms_external_client(&USER_TWO);
+    let err = other_client
+        .request_stream_task_logs(&launch_info.task_id, 0, 0)
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+    let err = tms_client
+        .request_stream_task_logs("no_such_task", 0, 0)
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+}
+
+pub fn api_rerun_task() {
+    trace!("Test FNS: rerun task.");
+    let mut tms_client = setup_tms_external_client(&USER_ONE);
+    let function_name = "echo_env";
+    let secrets = [TaskSecret::inline("SALT", "s4lt")];
+    let launch_info = tms_client
+        .request_create_task_with_secrets(function_name, &[], &secrets)
+        .unwrap();
+
+    // Only ended tasks are rerun
+    let err = tms_client
+        .request_rerun_task(&launch_info.task_id, None)
+        .unwrap_err();
+    assert_eq!(err.kind(), mesatee_core::ErrorKind::InvalidInputError);
+
+    let mut fns_client = setup_fns_client(launch_info.ip, launch_info.port);
+    let response = fns_client
+        .invoke_task(
+            &launch_info.task_id,
+            function_name,
+            &launch_info.task_token,
+            Some("SALT"),
+        )
+        .unwrap();
+    assert_eq!(response.result, "s4lt");
+    let source_info = tms_client
+        .request_get_task(&launch_info.task_id)
+        .unwrap()
+        .task_info;
+    assert_eq!(source_info.status, TaskStatus::Finished);
+
+    let mut other_client = setup_tms_external_client(&USER_TWO);
+    let err = other_client
+        .request_rerun_task(&launch_info.task_id, None)
+        .unwrap_err();
+    assert_eq!(err.kind(), mesatee_core::ErrorKind::PermissionDenied);
+    let err = tms_client
+        .request_rerun_task("no_such_task", None)
+        .unwrap_err();
+    assert_eq!(err.kind(), mesatee_core::ErrorKind::NotFound);
+
+    // The rerun is a new task with the function and secrets of the source
+    let mut arguments = TaskArguments::new();
+    arguments.insert("round".to_string(), 2.into());
+    let rerun_info = tms_client
+        .request_rerun_task(&launch_info.task_id, Some(&arguments))
+        .unwrap();
+    assert_ne!(rerun_info.task_id, launch_info.task_id);
+    assert_ne!(rerun_info.task_token, launch_info.task_token);
+    let task_info = tms_client
+        .request_get_task(&rerun_info.task_id)
+        .unwrap()
+        .task_info;
+    assert_eq!(task_info.status, TaskStatus::Ready);
+    assert_eq!(task_info.status_version, 0);
+    assert_eq!(task_info.function_name, source_info.function_name);
+    assert_eq!(task_info.resource_limits, source_info.resource_limits);
+    assert_eq!(task_info.secrets, source_info.secrets);
+    assert_eq!(task_info.arguments, arguments);
+    assert_eq!(task_info.task_result_file_id, None);
+    assert!(task_info.attempts.is_empty());
+
+    // The inline secret of the rerun outlives the source
+    tms_client
+        .request_delete_task(&launch_info.task_id)
+        .unwrap();
+    let mut fns_client = setup_fns_client(rerun_info.ip, rerun_info.port);
+    let response = fns_client
+        .invoke_task(
+            &rerun_info.task_id,
+            function_name,
+            &rerun_info.task_token,
+            Some("SALT"),
+        )
+        .unwrap();
+    assert_eq!(response.result, "s4lt");
+    let task_info = tms_client
+        .request_get_task(&rerun_info.task_id)
+        .unwrap()
+        .task_info;
+    assert_eq!(task_info.status, TaskStatus::Finished);
+
+    // A rerun without arguments keeps the ones of its source
+    let rerun_info = tms_client
+        .request_rerun_task(&rerun_info.task_id, None)
+        .unwrap();
+    let task_info = tms_client
+        .request_get_task(&rerun_info.task_id)
+        .unwrap()
+        .task_info;
+    assert_eq!(task_info.arguments, arguments);
+    tms_client
+        .request_cancel_task(&rerun_info.task_id, "not needed")
+        .unwrap();
+}
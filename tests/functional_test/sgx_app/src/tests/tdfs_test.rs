@@ -19,6 +19,7 @@ use super::common_setup::{
     setup_tdfs_external_client, USER_ERR, USER_FAKE, USER_ONE, USER_THREE, USER_TWO,
 };
 use super::fns_test;
+use mesatee_core::health::ServiceStatus;
 use std::fs;
 
 pub fn read_not_exist_file() {
@@ -63,6 +64,123 @@ pub fn save_and_read() {
     assert!(read_err.is_err());
 }
 
+pub fn save_and_read_chunked() {
+    trace!("Test tdfs: save file in chunks and read it.");
+    let mut client = setup_tdfs_external_client(&USER_ONE);
+
+    let file_path = "./tdfs_functional_test_chunked";
+    let data: Vec<u8> = (0..10000u32).map(|i| i as u8).collect();
+    fs::write(file_path, &data).unwrap();
+    let file_name = "functional_test_chunked";
+    let file_id = client
+        .save_file_chunked(file_path, file_name, 3000)
+        .unwrap();
+
+    let plaintxt = client.read_file(&file_id).unwrap();
+    assert_eq!(plaintxt, data);
+
+    // out of order and oversized chunks
+    let file_id = client.request_begin_upload(file_name, 6).unwrap().file_id;
+    assert!(client.request_upload_chunk(&file_id, 3, b"def").is_err());
+    let received = client.request_upload_chunk(&file_id, 0, b"abc").unwrap();
+    assert_eq!(received.received, 3);
+    assert!(client.request_upload_chunk(&file_id, 3, b"defg").is_err());
+
+    // commit with a mismatched hash
+    assert!(client.request_upload_chunk(&file_id, 3, b"def").is_ok());
+    assert!(client.request_commit_upload(&file_id, "aaa").is_err());
+    assert!(client.read_file(&file_id).is_err());
+
+    // commit an incomplete upload
+    let file_id = client.request_begin_upload(file_name, 6).unwrap().file_id;
+    assert!(client.request_upload_chunk(&file_id, 0, b"abc").is_ok());
+    let sha256 = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+    assert!(client.request_commit_upload(&file_id, sha256).is_err());
+}
+
+pub fn download_chunked() {
+    trace!("Test tdfs: download a file in chunks.");
+    let mut client = setup_tdfs_external_client(&USER_ONE);
+
+    let file_path = "./tdfs_functional_test_download";
+    let data: Vec<u8> = (0..10000u32).map(|i| (i % 251) as u8).collect();
+    fs::write(file_path, &data).unwrap();
+    let file_id = client
+        .save_file_chunked(file_path, "functional_test_download", 3000)
+        .unwrap();
+
+    // the downloaded file is checked against the digest of the upload
+    let download_path = "./tdfs_functional_test_downloaded";
+    client
+        .download_file_chunked(&file_id, download_path, 4096)
+        .unwrap();
+    assert_eq!(fs::read(download_path).unwrap(), data);
+
+    // a chunk spanning two uploaded chunks, and the end of the file
+    let chunk = client
+        .request_get_output_chunk(&file_id, 2500, 1000)
+        .unwrap();
+    assert_eq!(chunk.file_size, 10000);
+    assert_eq!(chunk.data, &data[2500..3500]);
+    let chunk = client
+        .request_get_output_chunk(&file_id, 9000, 4096)
+        .unwrap();
+    assert_eq!(chunk.data, &data[9000..]);
+    assert!(client.request_get_output_chunk(&file_id, 10001, 1).is_err());
+    assert!(client.request_get_output_chunk(&file_id, 0, 0).is_err());
+
+    // files uploaded in one request are downloaded in chunks too
+    let file_id = client
+        .save_file(file_path, "functional_test_download")
+        .unwrap();
+    client
+        .download_file_chunked(&file_id, download_path, 3333)
+        .unwrap();
+    assert_eq!(fs::read(download_path).unwrap(), data);
+
+    // only users the file is shared with may download it
+    let mut client = setup_tdfs_external_client(&USER_THREE);
+    assert!(client.request_get_output_chunk(&file_id, 0, 4096).is_err());
+    assert!(client
+        .download_file_chunked(&file_id, download_path, 4096)
+        .is_err());
+}
+
+pub fn save_and_read_with_digest_algorithm() {
+    trace!("Test tdfs: save files with declared digest algorithms.");
+    let mut client = setup_tdfs_external_client(&USER_ONE);
+
+    let file_path = "./tdfs_functional_test_digest";
+    fs::write(file_path, b"abc").unwrap();
+    let file_name = "functional_test_digest";
+    for algorithm in ["sha256", "sha384"].iter() {
+        let file_id = client
+            .save_file_with_digest_algorithm(file_path, file_name, &[], algorithm)
+            .unwrap();
+        let file_info = client.request_get_file(&file_id).unwrap().file_info;
+        assert_eq!(file_info.digest_algorithm, *algorithm);
+        let plaintxt = client.read_file(&file_id).unwrap();
+        assert_eq!(plaintxt, b"abc");
+    }
+
+    // unknown algorithms are rejected by TDFS
+    assert!(client
+        .request_begin_upload_with_digest(file_name, 3, "md5")
+        .is_err());
+    // a CMAC cannot be computed in chunks
+    assert!(client
+        .request_begin_upload_with_digest(file_name, 3, "cmac-aes-128")
+        .is_err());
+    // a CMAC needs a 16 byte key
+    let cmac = "7649abac8119b246cee98e9b12e9197d";
+    assert!(client
+        .save_file_with_cmac(file_path, file_name, cmac, &[1u8; 8])
+        .is_err());
+    assert!(client
+        .save_file_with_cmac(file_path, file_name, "xyz", &[1u8; 16])
+        .is_err());
+}
+
 pub fn delete_file_api() {
     trace!("Test tdfs: delete a file");
     let mut client = setup_tdfs_external_client(&USER_THREE);
@@ -87,6 +205,26 @@ pub fn delete_file_api() {
     assert_eq!(list.len(), 1);
 }
 
+pub fn save_and_read_shared() {
+    trace!("Test tdfs: save and read shared file.");
+    let mut client = setup_tdfs_external_client(&USER_ONE);
+
+    let file_path = "./tdfs_functional_test_shared";
+    fs::write(file_path, b"abc").unwrap();
+    let file_name = "functional_test";
+    let file_id = client
+        .save_shared_file(file_path, file_name, &[USER_TWO.user_id])
+        .unwrap();
+
+    let mut client = setup_tdfs_external_client(&USER_TWO);
+    let plaintxt = client.read_file(&file_id).unwrap();
+    assert_eq!(plaintxt, b"abc");
+
+    let mut client = setup_tdfs_external_client(&USER_THREE);
+    let resp = client.read_file(&file_id);
+    assert!(resp.is_err());
+}
+
 pub fn list_file_api() {
     trace!("Test tdfs: list files");
     let mut client = setup_tdfs_external_client(&USER_THREE);
@@ -121,3 +259,14 @@ pub fn list_file_api() {
     let list = client.request_list_file().unwrap().list;
     assert_eq!(user_two_file_count + 3, list.len());
 }
+
+pub fn api_health_check() {
+    trace!("Test tdfs: health check.");
+    // no valid credentials are needed
+    let mut client = setup_tdfs_external_client(&USER_ERR);
+    let health = client.request_health_check().unwrap().health;
+    assert_eq!(health.status, ServiceStatus::Ready);
+    assert_eq!(health.dependencies.len(), 1);
+    assert_eq!(health.dependencies[0].name, "kms");
+    assert!(health.dependencies[0].up);
+}
@@ -16,10 +16,964 @@
 // under the License.
 
 use super::common_setup::{
-    save_file_for_user, setup_tms_external_client, USER_ERR, USER_FAKE, USER_FOUR, USER_ONE,
+    save_file_for_user, setup_tdfs_external_client, setup_tms_external_client, target_tms,
+    USER_ADMIN, USER_ERR, USER_FAKE, USER_FIVE, USER_FOUR, USER_ONE, USER_REVOKED, USER_SIX,
     USER_THREE, USER_TWO,
 };
-use tms_external_proto::{FunctionType, TaskStatus};
+use mesatee_core::config::{self, get_trusted_enclave_attr, OutboundDesc};
+use mesatee_core::health::{ServiceStatus, VersionInfo};
+use mesatee_core::rpc::channel::{ChannelTimeouts, SgxTrustedChannel};
+use mesatee_core::rpc::sgx::TlsPolicy;
+use mesatee_core::{ErrorKind, Status};
+use serde_json::json;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use teaclave_attestation::quote::SgxQuote;
+use teaclave_attestation::verifier::{default_quote_verifier, SgxQuoteVerifier};
+use tms_external_client::TMSClient;
+use tms_external_proto::{
+    ArgumentSpec, ArgumentType, AuditLogQuery, CreateTaskResult, Executor, ExecutorCapability,
+    FunctionType, InputCollection, Labels, PortSpec, RemotePayload, ResourceLimits,
+    SignedEnclaveInfo, TaskArguments, TaskQuery, TaskRequest, TaskResponse, TaskSpec, TaskStatus,
+    TaskStatusResult,
+};
+
+pub fn api_create_task_with_limits() {
+    trace!("Test tms: create task with resource limits.");
+    let mut client = setup_tms_external_client(&USER_ONE);
+
+    let limits = ResourceLimits {
+        max_memory_mb: 256,
+        max_cpu_seconds: 60,
+        max_wall_seconds: 120,
+    };
+    let response = client
+        .request_create_task_with_limits("echo", &[], &[], limits)
+        .unwrap();
+    let task_info = client
+        .request_get_task(&response.task_id)
+        .unwrap()
+        .task_info;
+    assert_eq!(task_info.resource_limits, limits);
+
+    // default limits
+    let response = client.request_create_task("echo", &[], &[]).unwrap();
+    let task_info = client
+        .request_get_task(&response.task_id)
+        .unwrap()
+        .task_info;
+    assert!(task_info.resource_limits.max_wall_seconds > 0);
+
+    // exceeding the cluster maximum
+    let limits = ResourceLimits {
+        max_memory_mb: u64::max_value(),
+        max_cpu_seconds: 60,
+        max_wall_seconds: 120,
+    };
+    let response = client.request_create_task_with_limits("echo", &[], &[], limits);
+    assert!(response.is_err());
+
+    // zero limit
+    let limits = ResourceLimits {
+        max_memory_mb: 256,
+        max_cpu_seconds: 60,
+        max_wall_seconds: 0,
+    };
+    let response = client.request_create_task_with_limits("echo", &[], &[], limits);
+    assert!(response.is_err());
+}
+
+pub fn api_function_argument_schema() {
+    trace!("Test tms: validate task arguments against the function schema.");
+    let mut client = setup_tms_external_client(&USER_ONE);
+
+    let mut mode = ArgumentSpec::new("mode", ArgumentType::Enum, false);
+    mode.allowed_values = vec![json!("fast"), json!("exact")];
+    mode.default = Some(json!("fast"));
+    let schema = vec![
+        ArgumentSpec::new("path", ArgumentType::String, true),
+        ArgumentSpec::new("rounds", ArgumentType::Integer, true),
+        ArgumentSpec::new("verbose", ArgumentType::Bool, false),
+        mode,
+    ];
+    let function_id = client
+        .request_register_function_with_schema("echo", "with schema", None, &schema)
+        .unwrap()
+        .function_id;
+    let info = client
+        .request_get_function(&function_id)
+        .unwrap()
+        .function_info;
+    assert_eq!(info.argument_schema, Some(schema));
+
+    // An invalid schema is rejected
+    let invalid_schema = vec![ArgumentSpec::new("mode", ArgumentType::Enum, false)];
+    let response =
+        client.request_register_function_with_schema("echo", "invalid", None, &invalid_schema);
+    assert!(response.is_err());
+
+    // Defaults are filled in
+    let mut arguments = TaskArguments::new();
+    arguments.insert("path".to_string(), json!("/data"));
+    arguments.insert("rounds".to_string(), json!(3));
+    let response = client
+        .request_create_task_with_arguments(&function_id, &[], &arguments)
+        .unwrap();
+    let task_info = client
+        .request_get_task(&response.task_id)
+        .unwrap()
+        .task_info;
+    assert_eq!(task_info.arguments.len(), 3);
+    assert_eq!(task_info.arguments["mode"], json!("fast"));
+    assert_eq!(task_info.arguments["rounds"], json!(3));
+
+    // Every offending argument is reported
+    let mut arguments = TaskArguments::new();
+    arguments.insert("rounds".to_string(), json!("three"));
+    arguments.insert("mode".to_string(), json!("slow"));
+    arguments.insert("rouns".to_string(), json!(3));
+    let mut task = TaskSpec::new("", &[], &[]);
+    task.function_id = Some(function_id.to_string());
+    task.arguments = arguments;
+    let response = client.request_validate_task(&task).unwrap();
+    assert!(!response.valid);
+    let error = response.error.unwrap();
+    assert!(error.contains("unknown argument rouns"));
+    assert!(error.contains("missing required argument path"));
+    assert!(error.contains("argument rounds must be of type Integer"));
+    assert!(error.contains("argument mode must be one of"));
+}
+
+pub fn api_function_sharing() {
+    trace!("Test tms: share and unshare function.");
+    let mut client = setup_tms_external_client(&USER_ONE);
+    let mut other_client = setup_tms_external_client(&USER_TWO);
+
+    let function_id = client
+        .request_register_function("mesapy", "shared", Some("def entrypoint(argv): return 1"))
+        .unwrap()
+        .function_id;
+    assert!(other_client.request_get_function(&function_id).is_err());
+    assert!(other_client
+        .request_create_task_for_function(&function_id, &[], &[])
+        .is_err());
+
+    // only the owner may share
+    let resp = other_client.request_share_function(&function_id, &[USER_TWO.user_id]);
+    assert!(resp.is_err());
+
+    let resp = client
+        .request_share_function(&function_id, &[USER_ONE.user_id, USER_TWO.user_id])
+        .unwrap();
+    assert_eq!(resp.collaborators, vec![USER_TWO.user_id.to_string()]);
+    let info = other_client
+        .request_get_function(&function_id)
+        .unwrap()
+        .function_info;
+    assert_eq!(info.owner, USER_ONE.user_id);
+    assert!(other_client
+        .request_create_task_for_function(&function_id, &[], &[])
+        .is_ok());
+
+    let resp = client
+        .request_unshare_function(&function_id, &[USER_TWO.user_id])
+        .unwrap();
+    assert!(resp.collaborators.is_empty());
+    assert!(other_client.request_get_function(&function_id).is_err());
+
+    // shared at registration
+    let function_id = client
+        .request_register_shared_function("mesapy", "shared", None, &[USER_TWO.user_id])
+        .unwrap()
+        .function_id;
+    assert!(other_client.request_get_function(&function_id).is_ok());
+    let mut third_client = setup_tms_external_client(&USER_THREE);
+    assert!(third_client.request_get_function(&function_id).is_err());
+}
+
+pub fn api_labels() {
+    trace!("Test tms: labels of tasks and functions.");
+    let mut client = setup_tms_external_client(&USER_FOUR);
+    let mut ml_labels = Labels::new();
+    ml_labels.insert("team".to_string(), "ml".to_string());
+    ml_labels.insert("experiment-id".to_string(), "42".to_string());
+    let mut web_labels = Labels::new();
+    web_labels.insert("team".to_string(), "web".to_string());
+
+    let ml_task = client
+        .request_create_task_with_labels("echo", &[], &ml_labels)
+        .unwrap();
+    let web_task = client
+        .request_create_task_with_labels("echo", &[], &web_labels)
+        .unwrap();
+    let task_info = client.request_get_task(&ml_task.task_id).unwrap().task_info;
+    assert_eq!(task_info.labels, ml_labels);
+
+    let mut filter = Labels::new();
+    filter.insert("team".to_string(), "ml".to_string());
+    let list = client
+        .request_list_task_with_labels(&filter, None, "")
+        .unwrap()
+        .list;
+    assert!(list.contains(&ml_task.task_id));
+    assert!(!list.contains(&web_task.task_id));
+
+    let ml_function = client
+        .request_register_function_with_labels("echo", "labeled", None, &ml_labels)
+        .unwrap()
+        .function_id;
+    let web_function = client
+        .request_register_function_with_labels("echo", "labeled", None, &web_labels)
+        .unwrap()
+        .function_id;
+    let functions = client
+        .request_list_functions_with_labels(&filter, None, "")
+        .unwrap()
+        .functions;
+    assert!(functions.iter().any(|info| info.function_id == ml_function));
+    assert!(!functions
+        .iter()
+        .any(|info| info.function_id == web_function));
+    assert!(functions.iter().all(|info| info.labels["team"] == "ml"));
+
+    // too long
+    let mut long_labels = Labels::new();
+    long_labels.insert("team".to_string(), "x".repeat(4096));
+    let resp = client.request_create_task_with_labels("echo", &[], &long_labels);
+    assert_eq!(resp.unwrap_err().kind(), ErrorKind::InvalidInputError);
+    let resp = client.request_register_function_with_labels("echo", "long", None, &long_labels);
+    assert_eq!(resp.unwrap_err().kind(), ErrorKind::InvalidInputError);
+}
+
+// Serves `payload` over HTTP to `connections` clients, returning its URL.
+fn serve_payload(payload: &'static [u8], connections: usize) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/function.py", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        for stream in listener.incoming().take(connections) {
+            let mut stream = stream.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                let len = stream.read(&mut buf).unwrap();
+                if len == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..len]);
+            }
+            let headers = format!(
+                "HTTP/1.0 200 OK\r\nContent-Length: {}\r\n\r\n",
+                payload.len()
+            );
+            stream.write_all(headers.as_bytes()).unwrap();
+            stream.write_all(payload).unwrap();
+        }
+    });
+    url
+}
+
+pub fn api_register_remote_function() {
+    trace!("Test tms: register function from a remote URL.");
+    let mut client = setup_tms_external_client(&USER_ONE);
+    let source: &[u8] = b"def main(inputs, output):\n    return \"42\"\n";
+    let url = serve_payload(source, 2);
+
+    let mut remote_payload = RemotePayload {
+        url,
+        digest_algorithm: "sha256".to_string(),
+        digest: "96bc582471eb0003f0535304990e44784f7303615c112f7602bee49ebe512c21".to_string(),
+    };
+    let function_id = client
+        .request_register_remote_function("remote", "downloaded", Executor::Python, &remote_payload)
+        .unwrap()
+        .function_id;
+    let function_info = client
+        .request_get_function(&function_id)
+        .unwrap()
+        .function_info;
+    assert_eq!(function_info.payload.unwrap().as_bytes(), source);
+
+    // The downloaded bytes do not match the pinned digest
+    remote_payload.digest =
+        "0000000000000000000000000000000000000000000000000000000000000000".to_string();
+    let err = client
+        .request_register_remote_function("remote", "tampered", Executor::Python, &remote_payload)
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::DataIntegrityError);
+}
+
+pub fn api_function_ports() {
+    trace!("Test tms: bind files to named function ports.");
+    let mut client = setup_tms_external_client(&USER_ONE);
+
+    // Port names must be unique
+    let duplicate = [
+        PortSpec::new("features", "csv", true),
+        PortSpec::new("features", "csv", false),
+    ];
+    assert!(client
+        .request_register_function_with_ports("train", "", None, &duplicate, &[])
+        .is_err());
+
+    let input_ports = [
+        PortSpec::new("features", "csv", true),
+        PortSpec::new("labels", "csv", true),
+    ];
+    let output_ports = [PortSpec::new("model", "model", false)];
+    let function_id = client
+        .request_register_function_with_ports(
+            "train",
+            "trains a model",
+            None,
+            &input_ports,
+            &output_ports,
+        )
+        .unwrap()
+        .function_id;
+    let function_info = client
+        .request_get_function(&function_id)
+        .unwrap()
+        .function_info;
+    assert_eq!(function_info.input_ports, input_ports.to_vec());
+    assert_eq!(function_info.output_ports, output_ports.to_vec());
+
+    let features = save_file_for_user(&USER_ONE, b"1,2,3", "./port_features_input");
+    let labels = save_file_for_user(&USER_ONE, b"0,1,0", "./port_labels_input");
+
+    // A required port is not bound
+    let response = client.request_create_task_with_ports(&function_id, &[("features", &features)]);
+    assert!(response.is_err());
+    // An unknown port is bound
+    let response = client.request_create_task_with_ports(
+        &function_id,
+        &[
+            ("features", &features),
+            ("labels", &labels),
+            ("weights", &labels),
+        ],
+    );
+    assert!(response.is_err());
+
+    let task_id = client
+        .request_create_task_with_ports(
+            &function_id,
+            &[("features", &features), ("labels", &labels)],
+        )
+        .unwrap()
+        .task_id;
+    let task_info = client.request_get_task(&task_id).unwrap().task_info;
+    assert_eq!(task_info.input_ports.len(), 2);
+    assert_eq!(task_info.input_ports["features"], features);
+    assert_eq!(task_info.input_ports["labels"], labels);
+    assert!(task_info.output_ports.is_empty());
+}
+
+pub fn api_port_edges() {
+    trace!("Test tms: bind input ports to output ports of dependencies.");
+    let mut client = setup_tms_external_client(&USER_ONE);
+    let train_id = client
+        .request_register_function_with_ports(
+            "train",
+            "trains a model",
+            None,
+            &[PortSpec::new("features", "csv", true)],
+            &[
+                PortSpec::new("model", "model", false),
+                PortSpec::new("metrics", "", false),
+            ],
+        )
+        .unwrap()
+        .function_id;
+    let predict_id = client
+        .request_register_function_with_ports(
+            "predict",
+            "predicts with a model",
+            None,
+            &[
+                PortSpec::new("model", "model", true),
+                PortSpec::new("features", "features", false),
+                PortSpec::new("extra", "any", false),
+            ],
+            &[],
+        )
+        .unwrap()
+        .function_id;
+
+    let features = save_file_for_user(&USER_ONE, b"1,2,3", "./edge_features_input");
+    let train_task_id = client
+        .request_create_task_with_ports(&train_id, &[("features", &features)])
+        .unwrap()
+        .task_id;
+
+    // The edge satisfies the required port, and "any" accepts every type
+    let task_id = client
+        .request_create_task_with_edges(
+            &predict_id,
+            &[],
+            &[
+                ("model", &train_task_id, "model"),
+                ("extra", &train_task_id, "model"),
+            ],
+        )
+        .unwrap()
+        .task_id;
+    let task_info = client.request_get_task(&task_id).unwrap().task_info;
+    assert_eq!(task_info.dependencies, vec![train_task_id.to_string()]);
+    assert_eq!(task_info.input_edges["model"].task_id, train_task_id);
+    assert_eq!(task_info.input_edges["model"].port, "model");
+    // Bound once the dependency finishes
+    assert!(task_info.input_ports.is_empty());
+
+    // "model" cannot be bound to an input port expecting "features"
+    let err = client
+        .request_create_task_with_edges(
+            &predict_id,
+            &[],
+            &[
+                ("model", &train_task_id, "model"),
+                ("features", &train_task_id, "model"),
+            ],
+        )
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInputError);
+    assert_eq!(err.field_errors().len(), 1);
+    assert_eq!(err.field_errors()[0].field_path, "input_edges");
+    assert!(err
+        .to_string()
+        .contains("input port features expects features, but output port model"));
+
+    // Unknown output ports and ports bound twice are rejected
+    assert!(client
+        .request_create_task_with_edges(&predict_id, &[], &[("model", &train_task_id, "weights")])
+        .is_err());
+    assert!(client
+        .request_create_task_with_edges(
+            &predict_id,
+            &[("model", &features)],
+            &[("model", &train_task_id, "model")],
+        )
+        .is_err());
+}
+
+pub fn api_create_task_with_idempotency_key() {
+    trace!("Test tms: create task with idempotency key.");
+    let mut client = setup_tms_external_client(&USER_TWO);
+    let first = client
+        .request_create_task_with_idempotency_key("echo", &[], "idempotent-echo")
+        .unwrap();
+
+    // a retry returns the task created first
+    let retried = client
+        .request_create_task_with_idempotency_key("echo", &[], "idempotent-echo")
+        .unwrap();
+    assert_eq!(retried.task_id, first.task_id);
+    assert_eq!(retried.task_token, first.task_token);
+
+    // keys are scoped by user
+    let mut other_client = setup_tms_external_client(&USER_ONE);
+    let other = other_client
+        .request_create_task_with_idempotency_key("echo", &[], "idempotent-echo")
+        .unwrap();
+    assert_ne!(other.task_id, first.task_id);
+
+    // a distinct key creates a new task
+    let distinct = client
+        .request_create_task_with_idempotency_key("echo", &[], "another-echo")
+        .unwrap();
+    assert_ne!(distinct.task_id, first.task_id);
+}
+
+pub fn api_create_content_addressed_task() {
+    trace!("Test tms: create content addressed task.");
+    let mut client = setup_tms_external_client(&USER_TWO);
+    let file_id = save_file_for_user(&USER_TWO, b"content", "./content_addressed_input");
+    let other_file_id = save_file_for_user(&USER_TWO, b"other", "./content_addressed_other");
+    let mut arguments = TaskArguments::new();
+    arguments.insert("greeting".to_string(), json!({"to": "world", "times": 2}));
+
+    // identical specs get the same ID, and the task created first
+    let first = client
+        .request_create_content_addressed_task("echo", &[file_id.as_str()], &arguments)
+        .unwrap();
+    let second = client
+        .request_create_content_addressed_task("echo", &[file_id.as_str()], &arguments)
+        .unwrap();
+    assert_eq!(second.task_id, first.task_id);
+    assert_eq!(second.task_token, first.task_token);
+    assert_eq!(first.task_id.len(), 64);
+
+    // the inputs and the arguments are part of the content
+    let other_input = client
+        .request_create_content_addressed_task("echo", &[other_file_id.as_str()], &arguments)
+        .unwrap();
+    assert_ne!(other_input.task_id, first.task_id);
+    let mut other_arguments = arguments.clone();
+    other_arguments.insert("greeting".to_string(), json!({"to": "world", "times": 3}));
+    let other_argument = client
+        .request_create_content_addressed_task("echo", &[file_id.as_str()], &other_arguments)
+        .unwrap();
+    assert_ne!(other_argument.task_id, first.task_id);
+
+    // tasks created without the option keep a random ID
+    let random = client
+        .request_create_task("echo", &[], &[file_id.as_str()])
+        .unwrap();
+    assert_ne!(random.task_id, first.task_id);
+}
+
+pub fn api_cancel_task() {
+    trace!("Test tms: cancel task.");
+    let mut client = setup_tms_external_client(&USER_ONE);
+    let response = client.request_create_task("echo", &[], &[]).unwrap();
+
+    // not the task creator
+    let mut other_client = setup_tms_external_client(&USER_TWO);
+    let resp = other_client.request_cancel_task(&response.task_id, "not mine");
+    assert!(resp.is_err());
+
+    let resp = client
+        .request_cancel_task(&response.task_id, "no longer needed")
+        .unwrap();
+    assert_eq!(resp.status, TaskStatus::Cancelled);
+
+    let task_info = client
+        .request_get_task(&response.task_id)
+        .unwrap()
+        .task_info;
+    assert_eq!(task_info.status, TaskStatus::Cancelled);
+    assert_eq!(
+        task_info.cancel_reason,
+        Some("no longer needed".to_string())
+    );
+
+    // already cancelled
+    let resp = client.request_cancel_task(&response.task_id, "again");
+    assert!(resp.is_err());
+
+    // dependents are cancelled too
+    let first = client.request_create_task("echo", &[], &[]).unwrap();
+    let second = client
+        .request_create_task_with_dependencies("echo", &[], &[], &[&first.task_id])
+        .unwrap();
+    let _ = client
+        .request_cancel_task(&first.task_id, "no longer needed")
+        .unwrap();
+    let task_info = client.request_get_task(&second.task_id).unwrap().task_info;
+    assert_eq!(task_info.status, TaskStatus::Cancelled);
+}
+
+pub fn api_delete_task() {
+    trace!("Test tms: delete task.");
+    let mut client = setup_tms_external_client(&USER_ONE);
+    let response = client
+        .request_create_task_with_retention("echo", &[], &[], 3600)
+        .unwrap();
+    let task_info = client
+        .request_get_task(&response.task_id)
+        .unwrap()
+        .task_info;
+    assert_eq!(task_info.expires_at, None);
+
+    // not ended yet
+    let resp = client.request_delete_task(&response.task_id);
+    assert!(resp.is_err());
+
+    let _ = client
+        .request_cancel_task(&response.task_id, "no longer needed")
+        .unwrap();
+    let task_info = client
+        .request_get_task(&response.task_id)
+        .unwrap()
+        .task_info;
+    assert!(task_info.expires_at.is_some());
+
+    // not the task creator
+    let mut other_client = setup_tms_external_client(&USER_TWO);
+    let resp = other_client.request_delete_task(&response.task_id);
+    assert!(resp.is_err());
+
+    let _ = client.request_delete_task(&response.task_id).unwrap();
+    let resp = client.request_get_task(&response.task_id);
+    assert!(resp.is_err());
+    let list = client.request_list_task().unwrap().list;
+    assert!(!list.contains(&response.task_id));
+}
+
+pub fn api_wait_for_task_status() {
+    trace!("Test tms: wait for task status.");
+    let mut client = setup_tms_external_client(&USER_ONE);
+    let response = client.request_create_task("echo", &[], &[]).unwrap();
+    let task_info = client
+        .request_get_task(&response.task_id)
+        .unwrap()
+        .task_info;
+
+    // times out without a status change
+    let resp = client
+        .request_wait_for_task_status(&response.task_id, task_info.status_version, 1)
+        .unwrap();
+    assert!(!resp.changed);
+    assert_eq!(resp.status, TaskStatus::Ready);
+
+    let _ = client
+        .request_cancel_task(&response.task_id, "no longer needed")
+        .unwrap();
+    let resp = client
+        .request_wait_for_task_status(&response.task_id, task_info.status_version, 1)
+        .unwrap();
+    assert!(resp.changed);
+    assert_eq!(resp.status, TaskStatus::Cancelled);
+    assert!(resp.status_version > task_info.status_version);
+
+    // not a participant
+    let mut other_client = setup_tms_external_client(&USER_TWO);
+    let resp = other_client.request_wait_for_task_status(&response.task_id, 0, 1);
+    assert!(resp.is_err());
+}
+
+pub fn api_create_scheduled_task() {
+    trace!("Test tms: create scheduled task.");
+    let mut client = setup_tms_external_client(&USER_ONE);
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let response = client
+        .request_create_task_with_not_before("echo", &[], now + 2)
+        .unwrap();
+    let task_info = client
+        .request_get_task(&response.task_id)
+        .unwrap()
+        .task_info;
+    assert_eq!(task_info.status, TaskStatus::Scheduled);
+    assert_eq!(task_info.not_before, Some(now + 2));
+
+    // withheld until its time, then released to executors
+    let resp = client
+        .request_wait_for_task_status(&response.task_id, task_info.status_version, 10)
+        .unwrap();
+    assert!(resp.changed);
+    assert_eq!(resp.status, TaskStatus::Ready);
+    let elapsed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    assert!(elapsed >= now + 2);
+
+    // too far in the future
+    let max_delay = config::runtime_config().task_management.max_schedule_delay;
+    let resp = client.request_create_task_with_not_before("echo", &[], now + max_delay + 3600);
+    assert_eq!(resp.unwrap_err().kind(), ErrorKind::InvalidInputError);
+}
+
+pub fn api_create_tasks() {
+    trace!("Test tms: create tasks in a batch.");
+    let mut client = setup_tms_external_client(&USER_ONE);
+
+    // The multiparty task without collaborators is invalid
+    let tasks = vec![
+        TaskSpec::new("echo", &[], &[]),
+        TaskSpec::new("psi", &[], &[]),
+        TaskSpec::new("echo", &[], &[]),
+    ];
+    let response = client.request_create_tasks(&tasks).unwrap();
+    assert_eq!(response.results.len(), 3);
+    for (index, result) in response.results.iter().enumerate() {
+        match (index, result) {
+            (1, CreateTaskResult::Failed(_)) => {}
+            (1, _) => panic!("invalid task created"),
+            (_, CreateTaskResult::Created(resp)) => {
+                let task_info = client.request_get_task(&resp.task_id).unwrap().task_info;
+                assert_eq!(task_info.status, TaskStatus::Ready);
+            }
+            (_, CreateTaskResult::Failed(err)) => panic!("task not created: {}", err),
+        }
+    }
+
+    let response = client.request_create_tasks(&[]);
+    assert!(response.is_err());
+
+    // exceeding the default maximum batch size
+    let tasks = vec![TaskSpec::new("echo", &[], &[]); 101];
+    let response = client.request_create_tasks(&tasks);
+    assert!(response.is_err());
+}
+
+pub fn api_validate_task() {
+    trace!("Test tms: validate a task before creating it.");
+    let mut client = setup_tms_external_client(&USER_ONE);
+    let task_count = client.request_list_task().unwrap().list.len();
+
+    let task = TaskSpec::new("echo", &[], &[]);
+    let response = client.request_validate_task(&task).unwrap();
+    assert!(response.valid);
+    assert!(response.error.is_none());
+
+    // The multiparty task without collaborators is invalid
+    let invalid_task = TaskSpec::new("psi", &[], &[]);
+    let response = client.request_validate_task(&invalid_task).unwrap();
+    assert!(!response.valid);
+    assert!(response.error.is_some());
+
+    let mut invalid_task = TaskSpec::new("echo", &[], &[]);
+    invalid_task.dependencies = vec!["unknown_task".to_string()];
+    let response = client.request_validate_task(&invalid_task).unwrap();
+    assert!(!response.valid);
+
+    // Validation does not create tasks
+    assert_eq!(client.request_list_task().unwrap().list.len(), task_count);
+
+    let response = client
+        .request_create_task(&task.function_name, &[], &[])
+        .unwrap();
+    let task_info = client
+        .request_get_task(&response.task_id)
+        .unwrap()
+        .task_info;
+    assert_eq!(task_info.status, TaskStatus::Ready);
+    assert_eq!(
+        client.request_list_task().unwrap().list.len(),
+        task_count + 1
+    );
+
+    let mut client = setup_tms_external_client(&USER_ERR);
+    let response = client.request_validate_task(&task);
+    assert!(response.is_err());
+}
+
+pub fn api_validation_field_errors() {
+    trace!("Test tms: report all the invalid fields of a request.");
+    let mut client = setup_tms_external_client(&USER_ONE);
+    let own_file_id = save_file_for_user(&USER_ONE, b"own", "./field_errors_own");
+    let private_file_id = save_file_for_user(&USER_TWO, b"private", "./field_errors_private");
+
+    let limits = ResourceLimits {
+        max_memory_mb: 0,
+        max_cpu_seconds: 60,
+        max_wall_seconds: 0,
+    };
+    let files = [
+        own_file_id.as_str(),
+        "no_such_file",
+        private_file_id.as_str(),
+    ];
+    let err = client
+        .request_create_task_with_limits("echo", &[], &files, limits)
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInputError);
+    let field_errors: Vec<(&str, ErrorKind)> = err
+        .field_errors()
+        .iter()
+        .map(|error| (error.field_path.as_str(), error.code))
+        .collect();
+    assert_eq!(
+        field_errors,
+        vec![
+            (
+                "resource_limits.max_memory_mb",
+                ErrorKind::InvalidInputError
+            ),
+            (
+                "resource_limits.max_wall_seconds",
+                ErrorKind::InvalidInputError
+            ),
+            ("files[1]", ErrorKind::NotFound),
+            ("files[2]", ErrorKind::PermissionDenied),
+        ]
+    );
+    let message = err.to_string();
+    assert!(message.contains("files[1]: NotFound"));
+    assert!(message.contains("max_wall_seconds must be between 1 and"));
+
+    // A single problem keeps its own kind
+    let err = client
+        .request_create_task("echo", &[], &[private_file_id.as_str()])
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+    assert_eq!(err.field_errors().len(), 1);
+
+    let mut task = TaskSpec::new("psi", &[], &[]);
+    task.labels.insert(String::new(), "empty".to_string());
+    task.dependencies = vec!["unknown_task".to_string(), "unknown_task".to_string()];
+    let response = client.request_validate_task(&task).unwrap();
+    assert!(!response.valid);
+    let field_paths: Vec<&str> = response
+        .field_errors
+        .iter()
+        .map(|error| error.field_path.as_str())
+        .collect();
+    assert_eq!(
+        field_paths,
+        vec![
+            "collaborator_list",
+            "files",
+            "labels",
+            "dependencies[0]",
+            "dependencies[1]"
+        ]
+    );
+    assert!(response.error.unwrap().contains("dependencies[1]"));
+
+    let mut labels = Labels::new();
+    labels.insert(String::new(), "empty".to_string());
+    let err = client
+        .request_register_function_with_labels("", "invalid", None, &labels)
+        .unwrap_err();
+    let field_paths: Vec<&str> = err
+        .field_errors()
+        .iter()
+        .map(|error| error.field_path.as_str())
+        .collect();
+    assert_eq!(field_paths, vec!["function_name", "labels"]);
+}
+
+pub fn api_register_function_with_capabilities() {
+    trace!("Test tms: register function with capabilities the cluster forbids.");
+    let mut client = setup_tms_external_client(&USER_ONE);
+    let source = "import socket\n\ndef main(inputs, output):\n    return 1\n";
+
+    // The default runtime config allows no capability
+    let err = client
+        .request_register_function_with_capabilities(
+            "fetch",
+            "",
+            Executor::Python,
+            Some(source),
+            &[ExecutorCapability::Network],
+        )
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+    let field_paths: Vec<&str> = err
+        .field_errors()
+        .iter()
+        .map(|error| error.field_path.as_str())
+        .collect();
+    assert_eq!(field_paths, vec!["capabilities[0]"]);
+
+    // Without the network capability the script may not import socket
+    let err = client
+        .request_register_function_with_capabilities(
+            "fetch",
+            "",
+            Executor::Python,
+            Some(source),
+            &[],
+        )
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInputError);
+}
+
+pub fn api_create_task_with_collections() {
+    trace!("Test tms: create task with an input collection.");
+    let mut tdfs_client = setup_tdfs_external_client(&USER_FIVE);
+    fs::write("./tdfs_collection", b"abc").unwrap();
+    let mut file_ids: Vec<String> = Vec::new();
+    for file_name in ["dataset/part-1.csv", "dataset/part-2.csv", "notes.txt"].iter() {
+        let file_id = tdfs_client
+            .save_file("./tdfs_collection", file_name)
+            .unwrap();
+        file_ids.push(file_id);
+    }
+
+    let mut client = setup_tms_external_client(&USER_FIVE);
+    let collections = vec![InputCollection::new("dataset", "dataset/*.csv")];
+    let response = client
+        .request_create_task_with_collections("echo", &[], &[], &collections)
+        .unwrap();
+    let task_info = client
+        .request_get_task(&response.task_id)
+        .unwrap()
+        .task_info;
+    assert_eq!(task_info.input_collections.len(), 1);
+    let collection = &task_info.input_collections[0];
+    assert_eq!(collection.name, "dataset");
+    assert_eq!(collection.pattern, "dataset/*.csv");
+    // Ordered by file name
+    assert_eq!(collection.file_ids, file_ids[..2].to_vec());
+
+    // The resolved files do not change when more files match later
+    let _ = tdfs_client
+        .save_file("./tdfs_collection", "dataset/part-3.csv")
+        .unwrap();
+    let task_info = client
+        .request_get_task(&response.task_id)
+        .unwrap()
+        .task_info;
+    assert_eq!(task_info.input_collections[0].file_ids.len(), 2);
+
+    // A collection matching no files is rejected
+    let collections = vec![InputCollection::new("images", "images/*")];
+    let response = client.request_create_task_with_collections("echo", &[], &[], &collections);
+    assert!(response.is_err());
+}
+
+pub fn api_function_versions() {
+    trace!("Test tms: register and update function.");
+    let mut client = setup_tms_external_client(&USER_ONE);
+
+    let function_id = client
+        .request_register_function("mesapy", "v1", Some("def entrypoint(argv): return 1"))
+        .unwrap()
+        .function_id;
+    assert!(function_id.ends_with("@v1"));
+    let v1 = client
+        .request_get_function(&function_id)
+        .unwrap()
+        .function_info;
+
+    let updated_id = client
+        .request_update_function(
+            &function_id,
+            "mesapy",
+            "v2",
+            Some("def entrypoint(argv): return 2"),
+        )
+        .unwrap()
+        .function_id;
+    assert!(updated_id.ends_with("@v2"));
+    assert_ne!(updated_id, function_id);
+
+    // v1 is unchanged
+    let info = client
+        .request_get_function(&function_id)
+        .unwrap()
+        .function_info;
+    assert_eq!(info, v1);
+    let info = client
+        .request_get_function(&updated_id)
+        .unwrap()
+        .function_info;
+    assert_eq!(info.version, 2);
+    assert_eq!(info.description, "v2");
+
+    let versions = client
+        .request_get_function_versions(&updated_id)
+        .unwrap()
+        .versions;
+    assert_eq!(versions.len(), 2);
+    assert_eq!(versions[0], v1);
+
+    // tasks are pinned to a version
+    let task_id = client
+        .request_create_task_for_function(&function_id, &[], &[])
+        .unwrap()
+        .task_id;
+    let task_info = client.request_get_task(&task_id).unwrap().task_info;
+    assert_eq!(task_info.function_id, Some(function_id.clone()));
+    assert_eq!(task_info.function_name, "mesapy");
+    let unversioned_id = function_id.trim_end_matches("@v1");
+    let response = client.request_create_task_for_function(unversioned_id, &[], &[]);
+    assert!(response.is_err());
+
+    // only the owner can update
+    let mut client = setup_tms_external_client(&USER_TWO);
+    let response = client.request_update_function(&function_id, "mesapy", "v3", None);
+    assert!(response.is_err());
+}
 
 pub fn api_create_task() {
     trace!("Test tms: create task.");
@@ -59,6 +1013,54 @@ pub fn api_create_task() {
     assert!(response.is_err());
 }
 
+pub fn api_get_task_statuses() {
+    trace!("Test tms: get the statuses of many tasks.");
+    let mut client = setup_tms_external_client(&USER_ONE);
+    let owned = client
+        .request_create_task("echo", &[], &[])
+        .unwrap()
+        .task_id;
+    client.request_cancel_task(&owned, "not needed").unwrap();
+    let shared = setup_tms_external_client(&USER_TWO)
+        .request_create_task("abc", &[USER_ONE.user_id], &[])
+        .unwrap()
+        .task_id;
+    let inaccessible = setup_tms_external_client(&USER_THREE)
+        .request_create_task("echo", &[], &[])
+        .unwrap()
+        .task_id;
+
+    let results = client
+        .request_get_task_statuses(&[&owned, &shared, &inaccessible, "no_such_task"])
+        .unwrap()
+        .results;
+    assert_eq!(results.len(), 4);
+    match &results[0] {
+        TaskStatusResult::Status(summary) => {
+            assert_eq!(summary.status, TaskStatus::Cancelled);
+            assert!(summary.finished_at.is_some());
+            assert_eq!(summary.error, Some("not needed".to_string()));
+        }
+        result => panic!("unexpected result {:?}", result),
+    }
+    match &results[1] {
+        TaskStatusResult::Status(summary) => assert_eq!(summary.error, None),
+        result => panic!("unexpected result {:?}", result),
+    }
+    // Inaccessible and missing tasks are reported alike
+    for result in results[2..].iter() {
+        match result {
+            TaskStatusResult::NotAccessible => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    // At most `max_page_size` IDs, 1000 by default
+    let task_ids = vec!["no_such_task"; 1001];
+    let err = client.request_get_task_statuses(&task_ids).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInputError);
+}
+
 pub fn api_get_task() {
     trace!("Test tms: get task.");
     let mut client = setup_tms_external_client(&USER_ONE);
@@ -84,17 +1086,17 @@ pub fn api_get_task() {
     assert!(&task_info.user_private_result_file_id.is_empty());
 
     let resp = client.request_get_task("null");
-    assert!(resp.is_err());
+    assert_eq!(resp.unwrap_err().status(), Status::NotFound);
 
     // invalid user
     let mut client = setup_tms_external_client(&USER_ERR);
     let response = client.request_get_task(&launch_info.task_id);
-    assert!(response.is_err());
+    assert_eq!(response.unwrap_err().status(), Status::Unauthenticated);
 
     // no permission
     let mut client = setup_tms_external_client(&USER_TWO);
     let response = client.request_get_task(&launch_info.task_id);
-    assert!(response.is_err());
+    assert_eq!(response.unwrap_err().status(), Status::PermissionDenied);
 }
 
 pub fn api_update_task() {
@@ -210,3 +1212,605 @@ pub fn api_list_task() {
     let list = user_tms_client.request_list_task().unwrap().list;
     assert_eq!(list.len(), 0);
 }
+
+pub fn api_list_task_pages() {
+    trace!("Test tms: list task by pages.");
+    let mut client = setup_tms_external_client(&USER_ONE);
+    for _ in 0..3 {
+        let _ = client.request_create_task("echo", &[], &[]).unwrap();
+    }
+    let mut expected = client.request_list_task().unwrap().list;
+    expected.sort();
+    assert!(expected.len() >= 3);
+
+    let mut listed: Vec<String> = Vec::new();
+    let mut cursor = String::new();
+    loop {
+        let response = client.request_list_task_page(Some(2), &cursor).unwrap();
+        assert!(response.list.len() <= 2);
+        listed.extend(response.list);
+        if response.next_token.is_empty() {
+            break;
+        }
+        cursor = response.next_token;
+    }
+    assert_eq!(listed, expected);
+
+    let response = client.request_list_task_page(Some(0), "");
+    assert!(response.is_err());
+
+    // functions
+    let mut registered: Vec<String> = Vec::new();
+    for name in ["echo", "bytes_plus_one"].iter() {
+        let response = client
+            .request_register_function(name, "paging test", None)
+            .unwrap();
+        let function_id = response.function_id.splitn(2, '@').next().unwrap();
+        registered.push(function_id.to_string());
+    }
+
+    let mut listed: Vec<String> = Vec::new();
+    let mut cursor = String::new();
+    loop {
+        let response = client.request_list_functions(Some(1), &cursor).unwrap();
+        assert!(response.functions.len() <= 1);
+        for function_info in response.functions.iter() {
+            assert_eq!(function_info.owner, USER_ONE.user_id);
+            let function_id = function_info.function_id.splitn(2, '@').next().unwrap();
+            listed.push(function_id.to_string());
+        }
+        if response.next_token.is_empty() {
+            break;
+        }
+        cursor = response.next_token;
+    }
+    for function_id in registered.iter() {
+        assert!(listed.contains(function_id));
+    }
+}
+
+pub fn api_query_tasks() {
+    trace!("Test tms: query tasks by function and status.");
+    let mut client = setup_tms_external_client(&USER_FIVE);
+    let function_id = client
+        .request_register_function("mesapy", "queried", None)
+        .unwrap()
+        .function_id;
+    let first = client
+        .request_create_task_for_function(&function_id, &[], &[])
+        .unwrap()
+        .task_id;
+    let second = client
+        .request_create_task_for_function(&function_id, &[], &[])
+        .unwrap()
+        .task_id;
+    client.request_cancel_task(&second, "queried").unwrap();
+
+    let by_function = TaskQuery {
+        function_id: Some(function_id.to_owned()),
+        ..Default::default()
+    };
+    let tasks = client
+        .request_query_tasks(&by_function, None, "")
+        .unwrap()
+        .tasks;
+    let mut task_ids: Vec<_> = tasks.iter().map(|task| task.task_id.as_str()).collect();
+    task_ids.sort();
+    let mut expected = vec![first.as_str(), second.as_str()];
+    expected.sort();
+    assert_eq!(task_ids, expected);
+    assert!(tasks.iter().all(|task| task.user_id == USER_FIVE.user_id));
+
+    let cancelled = TaskQuery {
+        status: Some(TaskStatus::Cancelled),
+        ..by_function.clone()
+    };
+    let tasks = client
+        .request_query_tasks(&cancelled, None, "")
+        .unwrap()
+        .tasks;
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(tasks[0].task_id, second);
+    assert_eq!(tasks[0].status, TaskStatus::Cancelled);
+    assert!(tasks[0].finished_at.is_some());
+
+    // a page at a time
+    let page = client
+        .request_query_tasks(&by_function, Some(1), "")
+        .unwrap();
+    assert_eq!(page.tasks.len(), 1);
+    assert!(!page.next_token.is_empty());
+    let next = client
+        .request_query_tasks(&by_function, Some(1), &page.next_token)
+        .unwrap();
+    assert_eq!(next.tasks.len(), 1);
+    assert_ne!(next.tasks[0].task_id, page.tasks[0].task_id);
+    assert!(next.next_token.is_empty());
+
+    // created later
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let later = TaskQuery {
+        since: Some(now + 3600),
+        ..by_function.clone()
+    };
+    let resp = client.request_query_tasks(&later, None, "").unwrap();
+    assert!(resp.tasks.is_empty());
+
+    // only admins query the tasks of other users
+    let of_owner = TaskQuery {
+        owner: Some(USER_FIVE.user_id.to_owned()),
+        ..by_function.clone()
+    };
+    let mut other_client = setup_tms_external_client(&USER_ONE);
+    let err = other_client
+        .request_query_tasks(&of_owner, None, "")
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+    let resp = other_client
+        .request_query_tasks(&by_function, None, "")
+        .unwrap();
+    assert!(resp.tasks.is_empty());
+    let mut admin_client = setup_tms_external_client(&USER_ADMIN);
+    let resp = admin_client
+        .request_query_tasks(&of_owner, None, "")
+        .unwrap();
+    assert_eq!(resp.tasks.len(), 2);
+}
+
+pub fn api_health_check() {
+    trace!("Test tms: health check.");
+    // no valid credentials are needed
+    let mut client = setup_tms_external_client(&USER_ERR);
+    let health = client.request_health_check().unwrap().health;
+    assert_eq!(health.status, ServiceStatus::Ready);
+    assert_eq!(health.dependencies.len(), 1);
+    assert_eq!(health.dependencies[0].name, "tdfs");
+    assert!(health.dependencies[0].up);
+}
+
+pub fn api_max_connections() {
+    trace!("Test tms: reject connections beyond max_connections.");
+    let max_connections = config::runtime_config().server.max_connections;
+
+    // each served client holds a connection slot
+    let mut clients: Vec<_> = (0..max_connections)
+        .map(|_| setup_tms_external_client(&USER_ONE))
+        .collect();
+    for client in clients.iter_mut() {
+        assert!(client.request_health_check().is_ok());
+    }
+
+    let mut overflow = setup_tms_external_client(&USER_ONE);
+    let err = overflow.request_health_check().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TooManyConnections);
+
+    // the slot is freed when a connection closes
+    clients.pop();
+    let mut served = false;
+    for _ in 0..10 {
+        let mut client = setup_tms_external_client(&USER_ONE);
+        if client.request_health_check().is_ok() {
+            served = true;
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    assert!(served);
+}
+
+pub fn api_rate_limit() {
+    trace!("Test tms: rate limit requests of a user.");
+    let rate_limit = &config::runtime_config().rate_limit;
+    if rate_limit.requests_per_second == 0 {
+        return;
+    }
+
+    // the bucket refills while the requests are sent, so more than `burst`
+    // requests may be served
+    let mut client = setup_tms_external_client(&USER_SIX);
+    let max_requests = (rate_limit.burst + rate_limit.requests_per_second) * 10;
+    let mut limited = None;
+    for _ in 0..max_requests {
+        if let Err(e) = client.request_list_task() {
+            limited = Some(e);
+            break;
+        }
+    }
+    let err = limited.unwrap();
+    assert_eq!(err.kind(), ErrorKind::RateLimited);
+    let retry_after = err.retry_after().unwrap();
+
+    // other users are not limited
+    let mut other_client = setup_tms_external_client(&USER_FIVE);
+    assert!(other_client.request_list_task().is_ok());
+
+    // the user is served again once a token is added
+    thread::sleep(retry_after);
+    assert!(client.request_list_task().is_ok());
+}
+
+pub fn api_query_audit_log() {
+    trace!("Test tms: query the audit log.");
+    let since = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        - 1;
+
+    let mut client = setup_tms_external_client(&USER_FOUR);
+    let function_id = client
+        .request_register_function("mesapy", "audited", None)
+        .unwrap()
+        .function_id;
+    // a failed operation is recorded with its error
+    assert!(client
+        .request_cancel_task("audit_no_such_task", "audit")
+        .is_err());
+
+    // only admins may query the audit log
+    let query = AuditLogQuery {
+        since: Some(since),
+        user_id: Some(USER_FOUR.user_id.to_owned()),
+        ..Default::default()
+    };
+    let err = client.request_query_audit_log(&query).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+
+    let mut admin_client = setup_tms_external_client(&USER_ADMIN);
+    let entries = admin_client
+        .request_query_audit_log(&query)
+        .unwrap()
+        .entries;
+    let events: Vec<_> = entries
+        .iter()
+        .map(|entry| {
+            assert_eq!(entry.user_id, USER_FOUR.user_id);
+            assert!(entry.timestamp >= since);
+            entry.parse_event().unwrap()
+        })
+        .collect();
+    let registered = events
+        .iter()
+        .find(|event| event.operation == "register_function")
+        .unwrap();
+    assert_eq!(registered.targets, vec![function_id]);
+    assert!(registered.error.is_none());
+    let cancelled = events
+        .iter()
+        .find(|event| event.operation == "cancel_task")
+        .unwrap();
+    assert_eq!(cancelled.targets, vec!["audit_no_such_task".to_owned()]);
+    assert!(cancelled.error.is_some());
+
+    // consecutive entries are chained
+    let query = AuditLogQuery {
+        since: Some(since),
+        ..Default::default()
+    };
+    let entries = admin_client
+        .request_query_audit_log(&query)
+        .unwrap()
+        .entries;
+    assert!(entries.len() >= 2);
+    for pair in entries.windows(2) {
+        assert_eq!(pair[0].sequence + 1, pair[1].sequence);
+        assert_eq!(pair[0].hash, pair[1].prev_hash);
+    }
+
+    // paged by sequence
+    let first = &entries[0];
+    let query = AuditLogQuery {
+        after_sequence: Some(first.sequence),
+        limit: Some(1),
+        ..Default::default()
+    };
+    let page = admin_client
+        .request_query_audit_log(&query)
+        .unwrap()
+        .entries;
+    assert_eq!(page.len(), 1);
+    assert_eq!(page[0].prev_hash, first.hash);
+}
+
+pub fn api_reload_enclave_info() {
+    trace!("Test tms: reload the enclave info.");
+    let fragments: Vec<SignedEnclaveInfo> = config::runtime_config()
+        .audit
+        .enclave_info_fragments
+        .iter()
+        .map(|fragment| SignedEnclaveInfo {
+            enclave_info: fragment.enclave_info.to_owned(),
+            auditor_signatures: fragment.auditor_signatures.clone(),
+        })
+        .collect();
+    let unknown_info = r#"
+[unknown]
+mr_signer = "83d719e77deaca1470f6baf62a4d774303c899db69020f9c70ee1dfc08c7ce9e"
+mr_enclave = "0000000000000000000000000000000000000000000000000000000000000001"
+"#;
+
+    // only admins may reload the enclave info
+    let err = setup_tms_external_client(&USER_ONE)
+        .request_reload_enclave_info(&fragments)
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+
+    // enclave info the auditors did not sign is rejected
+    let mut admin_client = setup_tms_external_client(&USER_ADMIN);
+    let mut tampered = fragments.clone();
+    tampered[0].enclave_info.push_str(unknown_info);
+    let err = admin_client
+        .request_reload_enclave_info(&tampered)
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::CryptoError);
+    let mut unsigned = fragments.clone();
+    unsigned.push(SignedEnclaveInfo {
+        enclave_info: unknown_info.to_string(),
+        auditor_signatures: Vec::new(),
+    });
+    let err = admin_client
+        .request_reload_enclave_info(&unsigned)
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInputError);
+
+    // signed enclave info lacking the services TMS connects to is rejected
+    let test_service = SignedEnclaveInfo {
+        enclave_info: fs::read_to_string("test_service_enclave_info.toml").unwrap(),
+        auditor_signatures: ["godzilla", "optimus_prime", "albus_dumbledore"]
+            .iter()
+            .map(|auditor| fs::read(format!("auditors/{}/test_service.sign.sha256", auditor)))
+            .collect::<std::io::Result<_>>()
+            .unwrap(),
+    };
+    let err = admin_client
+        .request_reload_enclave_info(&[test_service.clone()])
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInputError);
+
+    // a newly added service is accepted by TMS and the peers it reloads
+    let mut expanded = fragments.clone();
+    expanded.push(test_service);
+    let resp = admin_client.request_reload_enclave_info(&expanded).unwrap();
+    assert!(resp
+        .services
+        .iter()
+        .any(|service| service == "test_service"));
+    assert!(resp.services.iter().any(|service| service == "tdfs"));
+    assert_eq!(resp.peers.len(), 2);
+    for peer in resp.peers.iter() {
+        assert_eq!(peer.error, None, "{} failed to reload", peer.service);
+    }
+
+    // the original enclave info is restored, and TMS still reaches TDFS
+    let resp = admin_client
+        .request_reload_enclave_info(&fragments)
+        .unwrap();
+    assert!(!resp
+        .services
+        .iter()
+        .any(|service| service == "test_service"));
+    assert!(resp.peers.iter().all(|peer| peer.error.is_none()));
+    let file_id = save_file_for_user(&USER_ONE, b"reloaded", "./reload_enclave_info");
+    setup_tms_external_client(&USER_ONE)
+        .request_create_task("echo", &[], &[&file_id])
+        .unwrap();
+}
+
+pub fn api_revoke_user() {
+    trace!("Test tms: revoke a user and lift the revocation.");
+    let mut client = setup_tms_external_client(&USER_REVOKED);
+    let task_id = client
+        .request_create_task("echo", &[], &[])
+        .unwrap()
+        .task_id;
+
+    // only admins may revoke users
+    let mut other_client = setup_tms_external_client(&USER_ONE);
+    let err = other_client
+        .request_revoke_user(USER_REVOKED.user_id, true)
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+
+    let mut admin_client = setup_tms_external_client(&USER_ADMIN);
+    let resp = admin_client
+        .request_revoke_user(USER_REVOKED.user_id, true)
+        .unwrap();
+    assert_eq!(resp.cancelled_tasks, 1);
+
+    // every request of a revoked user is rejected
+    let err = client.request_get_task(&task_id).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Unauthenticated);
+    let err = client.request_create_task("echo", &[], &[]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Unauthenticated);
+    let mut new_client = setup_tms_external_client(&USER_REVOKED);
+    assert!(new_client.request_list_task().is_err());
+
+    admin_client
+        .request_unrevoke_user(USER_REVOKED.user_id)
+        .unwrap();
+    let err = admin_client
+        .request_unrevoke_user(USER_REVOKED.user_id)
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+
+    let task_info = client.request_get_task(&task_id).unwrap().task_info;
+    assert_eq!(task_info.status, TaskStatus::Cancelled);
+    assert_eq!(task_info.cancel_reason, Some("user revoked".to_string()));
+}
+
+pub fn api_get_enclave_measurement() {
+    trace!("Test tms: get enclave measurement.");
+    let mut client = setup_tms_external_client(&USER_ERR);
+    let identity = client.request_get_enclave_measurement().unwrap().identity;
+
+    // Matches the signed enclave info
+    let measurement = get_trusted_enclave_attr(vec!["tms"]).measures[0];
+    let to_hex = |bytes: &[u8]| -> String { bytes.iter().map(|b| format!("{:02x}", b)).collect() };
+    assert_eq!(identity.mr_enclave, to_hex(&measurement.mr_enclave));
+    assert_eq!(identity.mr_signer, to_hex(&measurement.mr_signer));
+}
+
+pub fn api_get_version_info() {
+    trace!("Test tms: get version info.");
+    let mut client = setup_tms_external_client(&USER_ERR);
+    let version = client.request_get_version_info().unwrap().version;
+
+    // Built from the same tree as this test
+    assert_eq!(version, VersionInfo::current());
+    assert!(!version.crate_version.is_empty());
+}
+
+pub fn api_list_executors() {
+    trace!("Test tms: list executors.");
+    let mut client = setup_tms_external_client(&USER_ONE);
+    let executors = client.request_list_executors().unwrap().executors;
+
+    // Registered by FNS, ordered by name
+    let names: Vec<&str> = executors.iter().map(|info| info.executor.name()).collect();
+    assert_eq!(names, vec!["builtin", "python", "wasm"]);
+    for info in executors.iter() {
+        assert!(!info.version.is_empty());
+        assert!(!info.input_modes.is_empty());
+        assert!(!info.output_modes.is_empty());
+        let wall_limit = "max_wall_seconds".to_string();
+        assert!(info.resource_limits.contains(&wall_limit));
+    }
+
+    // Functions for unknown executors are rejected with the valid ones
+    let err = client
+        .request_register_function_for_executor("native_fn", "native function", None, "native")
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInputError);
+    let message = err.to_string();
+    assert!(message.contains("executor native is not available"));
+    assert!(message.contains("valid executors are builtin, python, wasm"));
+}
+
+pub fn api_create_task_with_max_input_files() {
+    trace!("Test tms: create tasks with the maximum number of input files.");
+    let mut client = setup_tms_external_client(&USER_ONE);
+    let file_id = save_file_for_user(&USER_ONE, b"abc", "./max_input_files");
+
+    // At the default limit
+    let files = vec![file_id.as_str(); 1024];
+    let resp = client.request_create_task("echo", &[], &files).unwrap();
+    let task_info = client.request_get_task(&resp.task_id).unwrap().task_info;
+    assert_eq!(task_info.input_files.len(), 1024);
+
+    // Over the limit, rejected before the files are looked up
+    let mut files = files;
+    files.push("no_such_file");
+    let err = client.request_create_task("echo", &[], &files).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInputError);
+    assert!(err
+        .to_string()
+        .contains("task has 1025 input files, exceeding max_input_files of 1024"));
+}
+
+// Average time of opening a channel to TMS with `tls_policy` and sending a
+// request on it.
+static VERIFIED_QUOTES: AtomicUsize = AtomicUsize::new(0);
+
+// Quotes are only verified by full handshakes, resumed sessions skip them.
+fn counting_quote_verifier(quote: &SgxQuote) -> bool {
+    VERIFIED_QUOTES.fetch_add(1, Ordering::SeqCst);
+    default_quote_verifier()(quote)
+}
+
+// Average time of a connection to TMS, and the number of full handshakes.
+fn time_connections(tls_policy: &TlsPolicy, connections: u32) -> (Duration, usize) {
+    let target = target_tms();
+    let enclave_attr = match target.desc {
+        OutboundDesc::Sgx(ref enclave_attr) => enclave_attr.clone(),
+    };
+    let verifier = SgxQuoteVerifier::with_verifier(enclave_attr, counting_quote_verifier);
+    let verified_before = VERIFIED_QUOTES.load(Ordering::SeqCst);
+    let start = Instant::now();
+    for _ in 0..connections {
+        let mut channel = SgxTrustedChannel::<TaskRequest, TaskResponse>::with_verifier(
+            target.addr,
+            verifier.clone(),
+            ChannelTimeouts::default(),
+            tls_policy.clone(),
+        )
+        .unwrap();
+        channel.invoke(TaskRequest::new_get_version_info()).unwrap();
+    }
+    let elapsed = start.elapsed() / connections;
+    (
+        elapsed,
+        VERIFIED_QUOTES.load(Ordering::SeqCst) - verified_before,
+    )
+}
+
+pub fn api_tls_session_resumption() {
+    trace!("Test tms: resume TLS sessions of reconnecting clients.");
+    let connections = 10;
+
+    // Each policy has its own TLS config, whose session cache starts empty
+    let full = TlsPolicy::default().session_resumption(Duration::from_secs(0), 0);
+    let (full_time, full_handshakes) = time_connections(&full, connections);
+    let resumed = TlsPolicy::default().session_resumption(Duration::from_secs(60), 16);
+    let (resumed_time, resumed_handshakes) = time_connections(&resumed, connections);
+    info!(
+        "Connection to TMS: {:?} with full handshakes, {:?} with resumed sessions",
+        full_time, resumed_time
+    );
+
+    // Only the first connection with `resumed` runs a full handshake. Quotes
+    // are not verified in simulation mode.
+    if !cfg!(sgx_sim) {
+        assert_eq!(full_handshakes, connections as usize);
+        assert_eq!(resumed_handshakes, 1);
+    }
+}
+
+static IAS_ROOT_CA_CERT: &[u8] = include_bytes!("../../../../../keys/ias_root_ca_cert.pem");
+// A self-signed root CA which endorses no attestation reports
+static UNRELATED_ROOT_CA_CERT: &[u8] =
+    include_bytes!("../../../sgx_trusted_lib/test_data/unrelated_root_ca_cert.pem");
+
+pub fn api_client_builder() {
+    trace!("Test tms: build a client from its address, enclaves and root CA.");
+    let target = target_tms();
+    let enclave_attr = match target.desc {
+        OutboundDesc::Sgx(ref enclave_attr) => enclave_attr.clone(),
+    };
+
+    let mut client = TMSClient::builder()
+        .addr(target.addr)
+        .enclave_attr(enclave_attr.clone())
+        .root_ca_cert(IAS_ROOT_CA_CERT)
+        .credentials(USER_ONE.user_id, USER_ONE.user_token)
+        .build()
+        .unwrap();
+    let response = client.request_create_task("echo", &[], &[]).unwrap();
+    let task_info = client
+        .request_get_task(&response.task_id)
+        .unwrap()
+        .task_info;
+    assert_eq!(task_info.user_id, USER_ONE.user_id);
+
+    // required fields
+    let err = TMSClient::builder()
+        .addr(target.addr)
+        .build()
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), ErrorKind::MissingValue);
+    assert_eq!(err.field_errors().len(), 2);
+
+    // Reports are not verified in simulation mode
+    if !cfg!(sgx_sim) {
+        let client = TMSClient::builder()
+            .addr(target.addr)
+            .enclave_attr(enclave_attr)
+            .root_ca_cert(UNRELATED_ROOT_CA_CERT)
+            .credentials(USER_ONE.user_id, USER_ONE.user_token)
+            .build();
+        assert!(client.is_err());
+    }
+}
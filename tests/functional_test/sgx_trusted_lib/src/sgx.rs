@@ -47,8 +47,15 @@ fn handle_run_functional_test(_args: &RunFunctionalTestInput) -> Result<RunFunct
         tests::tms_test::get_task,
         tests::tms_test::update_task_result,
         tests::tms_test::update_private_result,
+        tests::tms_test::report_progress,
+        tests::tms_test::retry_failed_task,
         tests::tms_test::update_status,
+        tests::tms_test::recover_tasks_after_restart,
+        tests::tms_test::pull_task_by_priority,
         tests::acs_test::access_control_model,
+        tests::attestation_test::verify_client_measurement,
+        tests::attestation_test::verify_report_with_multiple_roots,
+        tests::attestation_test::verify_captured_report,
     );
 
     Ok(RunFunctionalTestOutput::new(nfailed))
@@ -0,0 +1,128 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+#[cfg(feature = "mesalock_sgx")]
+use std::prelude::v1::*;
+
+use mesatee_core::rpc::sgx::ra_certificate;
+use rustls::ClientCertVerifier;
+use teaclave_attestation::quote::SgxQuote;
+use teaclave_attestation::verifier::{self, EnclaveAttr, SgxQuoteVerifier};
+use teaclave_attestation::AttestationReport;
+use teaclave_config::build_config::BUILD_CONFIG;
+use teaclave_utils::EnclaveMeasurement;
+
+// A self-signed root CA which endorses no attestation reports
+static UNRELATED_ROOT_CA_CERT: &[u8] = include_bytes!("../../test_data/unrelated_root_ca_cert.pem");
+
+fn client_verifier(mr_enclave: [u8; 32], mr_signer: [u8; 32]) -> SgxQuoteVerifier {
+    SgxQuoteVerifier::new(EnclaveAttr {
+        measures: vec![EnclaveMeasurement::new(mr_enclave, mr_signer)],
+    })
+}
+
+pub fn verify_client_measurement() {
+    trace!("Test attestation: verify the measurement of a client.");
+    // Reports are not verified in simulation mode
+    if cfg!(sgx_sim) {
+        return;
+    }
+
+    // The certificate this enclave presents as a client of TMS, TDFS and KMS
+    let cert = rustls::Certificate(ra_certificate());
+    let quote = SgxQuote::extract_verified_quote(&cert.0, BUILD_CONFIG.ias_root_ca_cert).unwrap();
+    let report_body = &quote.body.report_body;
+
+    let verifier = client_verifier(report_body.mr_enclave, report_body.mr_signer);
+    assert!(verifier.verify_client_cert(&[cert.clone()]).is_ok());
+
+    let mut other_signer = report_body.mr_signer;
+    other_signer[0] ^= 0xff;
+    let verifier = client_verifier(report_body.mr_enclave, other_signer);
+    assert!(verifier.verify_client_cert(&[cert.clone()]).is_err());
+
+    // The MRENCLAVE of the test enclave is only checked outside test mode
+    if !cfg!(test_mode) {
+        let mut other_enclave = report_body.mr_enclave;
+        other_enclave[0] ^= 0xff;
+        let verifier = client_verifier(other_enclave, report_body.mr_signer);
+        assert!(verifier.verify_client_cert(&[cert.clone()]).is_err());
+    }
+
+    // A client must present exactly one certificate
+    let verifier = client_verifier(report_body.mr_enclave, report_body.mr_signer);
+    assert!(verifier.verify_client_cert(&[]).is_err());
+}
+
+pub fn verify_report_with_multiple_roots() {
+    trace!("Test attestation: verify a report against multiple root CAs.");
+    if cfg!(sgx_sim) {
+        return;
+    }
+
+    let cert = ra_certificate();
+    let ias_root_ca_cert = BUILD_CONFIG.ias_root_ca_cert;
+
+    let roots: &[&[u8]] = &[UNRELATED_ROOT_CA_CERT, ias_root_ca_cert];
+    let quote = SgxQuote::extract_verified_quote_with_roots(&cert, roots).unwrap();
+    assert_eq!(quote.root_ca_index, 1);
+
+    let roots: &[&[u8]] = &[ias_root_ca_cert, UNRELATED_ROOT_CA_CERT];
+    let quote = SgxQuote::extract_verified_quote_with_roots(&cert, roots).unwrap();
+    assert_eq!(quote.root_ca_index, 0);
+
+    assert!(SgxQuote::extract_verified_quote(&cert, UNRELATED_ROOT_CA_CERT).is_err());
+    assert!(SgxQuote::extract_verified_quote_with_roots(&cert, &[]).is_err());
+}
+
+fn reject_all(_quote: &SgxQuote) -> bool {
+    false
+}
+
+pub fn verify_captured_report() {
+    trace!("Test attestation: verify a captured report without a handshake.");
+    if cfg!(sgx_sim) {
+        return;
+    }
+
+    let cert = ra_certificate();
+    let quote = SgxQuote::extract_verified_quote(&cert, BUILD_CONFIG.ias_root_ca_cert).unwrap();
+    let report = AttestationReport::from_cert(&cert).unwrap();
+    let roots = BUILD_CONFIG.ias_root_ca_certs;
+    let universal = verifier::universal_quote_verifier;
+
+    let json = report.to_json();
+    let verified = verifier::verify_report(json.as_bytes(), roots, universal).unwrap();
+    let report_body = &quote.body.report_body;
+    assert_eq!(
+        verified.measurement,
+        EnclaveMeasurement::new(report_body.mr_enclave, report_body.mr_signer)
+    );
+    assert_eq!(verified.tcb_status, quote.status);
+    let cbor = report.to_cbor().unwrap();
+    assert!(verifier::verify_report(&cbor, roots, universal).is_ok());
+
+    // An untrusted root, a rejected quote and a tampered report all fail
+    let unrelated: &[&[u8]] = &[UNRELATED_ROOT_CA_CERT];
+    assert!(verifier::verify_report(json.as_bytes(), unrelated, universal).is_err());
+    assert!(verifier::verify_report(json.as_bytes(), roots, reject_all).is_err());
+    let mut tampered = report.clone();
+    tampered.report = tampered.report.replacen("\"id\"", "\"Id\"", 1);
+    assert_ne!(tampered, report);
+    let json = tampered.to_json();
+    assert!(verifier::verify_report(json.as_bytes(), roots, universal).is_err());
+}
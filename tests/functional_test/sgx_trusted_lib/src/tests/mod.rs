@@ -16,6 +16,7 @@
 // under the License.
 
 pub mod acs_test;
+pub mod attestation_test;
 pub mod common_setup;
 pub mod kms_test;
 pub mod leveldb_test;
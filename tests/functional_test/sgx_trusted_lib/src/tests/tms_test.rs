@@ -15,10 +15,10 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use super::common_setup::setup_tms_internal_client;
+use super::common_setup::{setup_tdfs_internal_client, setup_tms_internal_client};
 use std::net::{IpAddr, Ipv4Addr};
 use std::prelude::v1::*;
-use tms_internal_proto::{FunctionType, TaskFile, TaskStatus};
+use tms_internal_proto::{FunctionType, PartialOutput, TaskError, TaskFile, TaskInfo, TaskStatus};
 
 pub fn get_task() {
     trace!("Test TMS: get_task.");
@@ -68,6 +68,7 @@ pub fn update_private_result() {
     let task_file = TaskFile {
         user_id: "fake".to_owned(),
         file_id: "client_private_result".to_owned(),
+        format: None,
     };
     let update_input = [&task_file];
     let resp = client
@@ -83,6 +84,86 @@ pub fn update_private_result() {
     );
 }
 
+pub fn report_progress() {
+    trace!("Test TMS: report_progress.");
+    let mut client = setup_tms_internal_client();
+
+    let resp = client
+        .request_report_progress("fake", 30, "loading", None)
+        .unwrap();
+    assert!(resp.success);
+    let partial_output = PartialOutput {
+        file_id: "partial_result".to_owned(),
+        sha256: "aaa".to_owned(),
+    };
+    let resp = client
+        .request_report_progress("fake", 60, "training", Some(&partial_output))
+        .unwrap();
+    assert!(resp.success);
+
+    let progress = client.request_get_task("fake").unwrap().task_info.progress;
+    assert_eq!(progress.len(), 2);
+    assert_eq!(progress[0].percent, 30);
+    assert_eq!(progress[0].message, "loading");
+    assert!(progress[0].partial_output.is_none());
+    assert_eq!(progress[1].percent, 60);
+    assert_eq!(progress[1].message, "training");
+    assert_eq!(progress[1].partial_output, Some(partial_output));
+    assert!(progress[0].reported_at <= progress[1].reported_at);
+
+    // over 100%
+    assert!(client
+        .request_report_progress("fake", 101, "done", None)
+        .is_err());
+    // task not exists
+    let resp = client
+        .request_report_progress("NULL", 10, "loading", None)
+        .unwrap();
+    assert!(!resp.success);
+}
+
+pub fn retry_failed_task() {
+    trace!("Test TMS: retry_failed_task.");
+    let mut client = setup_tms_internal_client();
+    let unavailable = TaskError {
+        message: "storage is unavailable".to_owned(),
+        retryable: true,
+    };
+    let resp = client
+        .request_fail_task("fake_retried_task", &unavailable)
+        .unwrap();
+    assert!(resp.success);
+
+    // staged again for a second attempt
+    let task_info = client
+        .request_get_task("fake_retried_task")
+        .unwrap()
+        .task_info;
+    assert_eq!(TaskStatus::Ready, task_info.status);
+    assert_eq!(task_info.attempts.len(), 1);
+    assert_eq!(task_info.attempts[0].status, TaskStatus::Failed);
+    assert_eq!(task_info.attempts[0].error, Some(unavailable));
+
+    let running = Some(&TaskStatus::Running);
+    let resp = client
+        .request_update_task("fake_retried_task", None, &[], running)
+        .unwrap();
+    assert!(resp.success);
+    let finished = Some(&TaskStatus::Finished);
+    let resp = client
+        .request_update_task("fake_retried_task", None, &[], finished)
+        .unwrap();
+    assert!(resp.success);
+    let task_info = client
+        .request_get_task("fake_retried_task")
+        .unwrap()
+        .task_info;
+    assert_eq!(TaskStatus::Finished, task_info.status);
+    assert_eq!(task_info.attempts.len(), 2);
+    assert_eq!(task_info.attempts[1].status, TaskStatus::Finished);
+    assert!(task_info.attempts[1].error.is_none());
+}
+
 pub fn update_status() {
     trace!("Test TMS: update_status.");
     let mut client = setup_tms_internal_client();
@@ -96,3 +177,61 @@ pub fn update_status() {
     let task_info = resp.task_info;
     assert_eq!(TaskStatus::Finished, task_info.status);
 }
+
+pub fn recover_tasks_after_restart() {
+    trace!("Test TMS: recover tasks after a restart.");
+    let mut client = setup_tms_internal_client();
+    // A multiparty task waiting for the approval of its collaborator
+    let staged_task = client
+        .request_get_task("fake_multi_task")
+        .unwrap()
+        .task_info;
+    assert_eq!(TaskStatus::Created, staged_task.status);
+    let mut running_task = staged_task.clone();
+    running_task.status = TaskStatus::Running;
+
+    // Records left by the previous TMS instance
+    let mut tdfs_client = setup_tdfs_internal_client();
+    for (task_id, task_info) in [
+        ("recovery_staged_task", &staged_task),
+        ("recovery_running_task", &running_task),
+    ]
+    .iter()
+    {
+        let record = serde_json::to_string(task_info).unwrap();
+        tdfs_client.put_task_record(task_id, &record).unwrap();
+    }
+
+    // What the restarted instance restores
+    let records = tdfs_client.list_task_records().unwrap();
+    let restored_status = |task_id: &str| -> TaskStatus {
+        let record = records
+            .iter()
+            .find(|record| record.task_id == task_id)
+            .unwrap();
+        let task_info: TaskInfo = serde_json::from_str(&record.record).unwrap();
+        task_info.status.after_restart()
+    };
+    // The staged task is enqueued again, the running task fails
+    assert_eq!(TaskStatus::Created, restored_status("recovery_staged_task"));
+    assert_eq!(TaskStatus::Failed, restored_status("recovery_running_task"));
+}
+
+pub fn pull_task_by_priority() {
+    trace!("Test TMS: pull ready tasks by priority.");
+    let mut client = setup_tms_internal_client();
+    // The low-priority task was staged before the high-priority one
+    let mut pulled: Vec<String> = Vec::new();
+    while let Some(task_id) = client.request_pull_task().unwrap().task_id {
+        let task_info = client.request_get_task(&task_id).unwrap().task_info;
+        assert_eq!(TaskStatus::Ready, task_info.status);
+        pulled.push(task_id);
+    }
+    let position = |task_id: &str| pulled.iter().position(|id| id == task_id).unwrap();
+    assert!(position("fake_high_priority_task") < position("fake_low_priority_task"));
+
+    // Tasks are handed out once
+    let resp = client.request_pull_task().unwrap();
+    assert!(resp.task_id.is_none());
+    assert!(resp.task_info.is_none());
+}